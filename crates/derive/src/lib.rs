@@ -0,0 +1,78 @@
+//! `#[derive(Absorb)]` for the `safe_api::absorb::Absorb` trait.
+//!
+//! The derived implementation absorbs the fields of a struct in declaration order, each with
+//! its own encoding. It is implemented over every prime field in which the types of all the
+//! fields of the struct are absorbable. See the documentation of `safe_api::absorb` for the
+//! encodings.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Error, Fields, Index};
+
+/// Derives `safe_api::absorb::Absorb` for a struct, see the [crate documentation](crate).
+#[proc_macro_derive(Absorb)]
+pub fn derive_absorb(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream2, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        Data::Enum(_) | Data::Union(_) => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "`Absorb` can only be derived for structs",
+            ))
+        }
+    };
+    let accessors: Vec<TokenStream2> = match fields {
+        Fields::Named(fields) => fields
+            .named
+            .iter()
+            .map(|field| {
+                let name = &field.ident;
+                quote!(self.#name)
+            })
+            .collect(),
+        Fields::Unnamed(fields) => (0..fields.unnamed.len())
+            .map(|index| {
+                let index = Index::from(index);
+                quote!(self.#index)
+            })
+            .collect(),
+        Fields::Unit => Vec::new(),
+    };
+
+    let name = &input.ident;
+    let (_, type_generics, _) = input.generics.split_for_impl();
+    let mut generics = input.generics.clone();
+    generics
+        .params
+        .push(parse_quote!(__F: ::safe_api::absorb::__private::PrimeField));
+    let where_clause = generics.make_where_clause();
+    for field in fields.iter() {
+        let ty = &field.ty;
+        where_clause
+            .predicates
+            .push(parse_quote!(#ty: ::safe_api::absorb::Absorb<__F>));
+    }
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::safe_api::absorb::Absorb<__F> for #name #type_generics
+        #where_clause
+        {
+            fn absorb_into(&self, elements: &mut ::safe_api::absorb::__private::Vec<__F>) {
+                #(::safe_api::absorb::Absorb::<__F>::absorb_into(&#accessors, elements);)*
+            }
+
+            fn absorb_len(&self) -> usize {
+                0 #(+ ::safe_api::absorb::Absorb::<__F>::absorb_len(&#accessors))*
+            }
+        }
+    })
+}
@@ -0,0 +1,118 @@
+#ifndef SAFE_API_H
+#define SAFE_API_H
+
+/* Generated by cbindgen from crates/ffi. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Size of a serialized field element.
+ */
+#define SAFE_ELEMENT_SIZE 32
+
+/**
+ * Result of the functions of this library.
+ */
+typedef enum SafeStatus {
+  SAFE_STATUS_OK = 0,
+  /**
+   * A required pointer is null.
+   */
+  SAFE_STATUS_NULL_POINTER = 1,
+  /**
+   * The next operation of the IO pattern is not the one called.
+   */
+  SAFE_STATUS_PATTERN_MISMATCH = 2,
+  /**
+   * The number of elements does not match the next operation of the IO pattern.
+   */
+  SAFE_STATUS_LENGTH_MISMATCH = 3,
+  /**
+   * A field element is not below the modulus.
+   */
+  SAFE_STATUS_NON_CANONICAL = 4,
+  /**
+   * `safe_sponge_finish` was called before the whole IO pattern ran.
+   */
+  SAFE_STATUS_NOT_COMPLETED = 5,
+} SafeStatus;
+
+/**
+ * A SAFE sponge, created with `safe_sponge_new` and released with `safe_sponge_free`.
+ */
+typedef struct SafeSponge SafeSponge;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Computes the tag of the `io_pattern_len` words at `io_pattern` under the 64-byte
+ * `domain_separator`, and writes it to the 16 bytes at `tag`.
+ *
+ * # Safety
+ * `io_pattern` must point to `io_pattern_len` words (or may be null when it is 0),
+ * `domain_separator` to 64 readable bytes and `tag` to 16 writable bytes.
+ */
+enum SafeStatus safe_compute_tag(const uint32_t *io_pattern,
+                                 size_t io_pattern_len,
+                                 const uint8_t *domain_separator,
+                                 uint8_t *tag);
+
+/**
+ * Starts a sponge for the `io_pattern_len` words at `io_pattern` under the 64-byte
+ * `domain_separator`. Returns null if a pointer is null.
+ *
+ * # Safety
+ * `io_pattern` must point to `io_pattern_len` words (or may be null when it is 0) and
+ * `domain_separator` to 64 readable bytes.
+ */
+struct SafeSponge *safe_sponge_new(const uint32_t *io_pattern,
+                                   size_t io_pattern_len,
+                                   const uint8_t *domain_separator);
+
+/**
+ * Absorbs the `len` field elements at `input` (`32 * len` bytes).
+ *
+ * # Safety
+ * `sponge` must come from `safe_sponge_new` and not be freed, and `input` must point to
+ * `32 * len` readable bytes (or may be null when `len` is 0).
+ */
+enum SafeStatus safe_sponge_absorb(struct SafeSponge *sponge, const uint8_t *input, size_t len);
+
+/**
+ * Squeezes the next operation, which must have `len` elements, into `output`
+ * (`32 * len` bytes).
+ *
+ * # Safety
+ * `sponge` must come from `safe_sponge_new` and not be freed, and `output` must point to
+ * `32 * len` writable bytes (or may be null when `len` is 0).
+ */
+enum SafeStatus safe_sponge_squeeze(struct SafeSponge *sponge, uint8_t *output, size_t len);
+
+/**
+ * Checks that the whole IO pattern has run and erases the state. The sponge must still be
+ * released with `safe_sponge_free`.
+ *
+ * # Safety
+ * `sponge` must come from `safe_sponge_new` and not be freed.
+ */
+enum SafeStatus safe_sponge_finish(struct SafeSponge *sponge);
+
+/**
+ * Releases a sponge. Does nothing if `sponge` is null.
+ *
+ * # Safety
+ * `sponge` must come from `safe_sponge_new` and not be freed already.
+ */
+void safe_sponge_free(struct SafeSponge *sponge);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SAFE_API_H */
@@ -0,0 +1,319 @@
+//! C ABI of `safe-api` over BN254 with Poseidon2, for languages that cannot link Rust crates
+//! (the Go relayer through cgo, the iOS wallet through Swift).
+//!
+//! The header `include/safe_api.h` is generated with cbindgen. Conventions:
+//!
+//! - domain separators are 64 bytes, already zero padded,
+//! - tags are written as 16 big-endian bytes,
+//! - field elements are 32 big-endian bytes each and must be canonical (below the modulus),
+//! - every function returns a [`SafeStatus`]; on an error nothing is written and the sponge is
+//!   left unchanged, so a misused sponge never aborts the host process.
+//!
+//! ```c
+//! SafeSponge *sponge = safe_sponge_new(io_pattern, 2, domain_separator);
+//! safe_sponge_absorb(sponge, inputs, 3);
+//! safe_sponge_squeeze(sponge, digest, 1);
+//! safe_sponge_finish(sponge);
+//! safe_sponge_free(sponge);
+//! ```
+
+use core::slice;
+
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField};
+use safe_api::domain::is_empty_domain;
+use safe_api::poseidon2::Poseidon2;
+use safe_api::{IoPattern, Sponge};
+
+/// Size of a serialized field element.
+pub const SAFE_ELEMENT_SIZE: usize = 32;
+
+/// Result of the functions of this library.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SafeStatus {
+    Ok = 0,
+    /// A required pointer is null.
+    NullPointer = 1,
+    /// The next operation of the IO pattern is not the one called.
+    PatternMismatch = 2,
+    /// The number of elements does not match the next operation of the IO pattern.
+    LengthMismatch = 3,
+    /// A field element is not below the modulus.
+    NonCanonical = 4,
+    /// `safe_sponge_finish` was called before the whole IO pattern ran.
+    NotCompleted = 5,
+}
+
+/// A SAFE sponge, created with `safe_sponge_new` and released with `safe_sponge_free`.
+pub struct SafeSponge {
+    sponge: Sponge<Poseidon2>,
+    position: usize,
+}
+
+/// Computes the tag of the `io_pattern_len` words at `io_pattern` under the 64-byte
+/// `domain_separator`, and writes it to the 16 bytes at `tag`.
+///
+/// # Safety
+/// `io_pattern` must point to `io_pattern_len` words (or may be null when it is 0),
+/// `domain_separator` to 64 readable bytes and `tag` to 16 writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn safe_compute_tag(
+    io_pattern: *const u32,
+    io_pattern_len: usize,
+    domain_separator: *const u8,
+    tag: *mut u8,
+) -> SafeStatus {
+    let (Some(words), Some(domain_separator)) = (
+        words(io_pattern, io_pattern_len),
+        domain_separator.cast::<[u8; 64]>().as_ref(),
+    ) else {
+        return SafeStatus::NullPointer;
+    };
+    if tag.is_null() {
+        return SafeStatus::NullPointer;
+    }
+    let value = safe_api::compute_tag(words, domain_separator);
+    slice::from_raw_parts_mut(tag, 16).copy_from_slice(value.as_bytes());
+    SafeStatus::Ok
+}
+
+/// Starts a sponge for the `io_pattern_len` words at `io_pattern` under the 64-byte
+/// `domain_separator`. Returns null if a pointer is null or if the domain separator is all
+/// zeros.
+///
+/// # Safety
+/// `io_pattern` must point to `io_pattern_len` words (or may be null when it is 0) and
+/// `domain_separator` to 64 readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn safe_sponge_new(
+    io_pattern: *const u32,
+    io_pattern_len: usize,
+    domain_separator: *const u8,
+) -> *mut SafeSponge {
+    let (Some(words), Some(domain_separator)) = (
+        words(io_pattern, io_pattern_len),
+        domain_separator.cast::<[u8; 64]>().as_ref(),
+    ) else {
+        return core::ptr::null_mut();
+    };
+    if is_empty_domain(domain_separator) {
+        return core::ptr::null_mut();
+    }
+    Box::into_raw(Box::new(SafeSponge {
+        sponge: Sponge::start(IoPattern::from_words(words), domain_separator),
+        position: 0,
+    }))
+}
+
+/// Absorbs the `len` field elements at `input` (`32 * len` bytes).
+///
+/// # Safety
+/// `sponge` must come from `safe_sponge_new` and not be freed, and `input` must point to
+/// `32 * len` readable bytes (or may be null when `len` is 0).
+#[no_mangle]
+pub unsafe extern "C" fn safe_sponge_absorb(
+    sponge: *mut SafeSponge,
+    input: *const u8,
+    len: usize,
+) -> SafeStatus {
+    let Some(sponge) = sponge.as_mut() else {
+        return SafeStatus::NullPointer;
+    };
+    if let Err(status) = sponge.check_next(true, len) {
+        return status;
+    }
+    let Some(input) = bytes(input, len * SAFE_ELEMENT_SIZE) else {
+        return SafeStatus::NullPointer;
+    };
+
+    let mut elements = Vec::with_capacity(len);
+    for bytes in input.chunks(SAFE_ELEMENT_SIZE) {
+        let element = Fr::from_be_bytes_mod_order(bytes);
+        if element.into_bigint().to_bytes_be() != bytes {
+            return SafeStatus::NonCanonical;
+        }
+        elements.push(element);
+    }
+    sponge.sponge.absorb(&elements);
+    sponge.position += 1;
+    SafeStatus::Ok
+}
+
+/// Squeezes the next operation, which must have `len` elements, into `output`
+/// (`32 * len` bytes).
+///
+/// # Safety
+/// `sponge` must come from `safe_sponge_new` and not be freed, and `output` must point to
+/// `32 * len` writable bytes (or may be null when `len` is 0).
+#[no_mangle]
+pub unsafe extern "C" fn safe_sponge_squeeze(
+    sponge: *mut SafeSponge,
+    output: *mut u8,
+    len: usize,
+) -> SafeStatus {
+    let Some(sponge) = sponge.as_mut() else {
+        return SafeStatus::NullPointer;
+    };
+    if output.is_null() && len != 0 {
+        return SafeStatus::NullPointer;
+    }
+    if let Err(status) = sponge.check_next(false, len) {
+        return status;
+    }
+
+    sponge.position += 1;
+    for (index, element) in sponge.sponge.squeeze().iter().enumerate() {
+        let bytes = element.into_bigint().to_bytes_be();
+        slice::from_raw_parts_mut(output.add(index * SAFE_ELEMENT_SIZE), SAFE_ELEMENT_SIZE)
+            .copy_from_slice(&bytes);
+    }
+    SafeStatus::Ok
+}
+
+/// Checks that the whole IO pattern has run and erases the state. The sponge must still be
+/// released with `safe_sponge_free`.
+///
+/// # Safety
+/// `sponge` must come from `safe_sponge_new` and not be freed.
+#[no_mangle]
+pub unsafe extern "C" fn safe_sponge_finish(sponge: *mut SafeSponge) -> SafeStatus {
+    let Some(sponge) = sponge.as_mut() else {
+        return SafeStatus::NullPointer;
+    };
+    if sponge.position != sponge.sponge.io_pattern().len() {
+        return SafeStatus::NotCompleted;
+    }
+    sponge.sponge.finish();
+    SafeStatus::Ok
+}
+
+/// Releases a sponge. Does nothing if `sponge` is null.
+///
+/// # Safety
+/// `sponge` must come from `safe_sponge_new` and not be freed already.
+#[no_mangle]
+pub unsafe extern "C" fn safe_sponge_free(sponge: *mut SafeSponge) {
+    if !sponge.is_null() {
+        drop(Box::from_raw(sponge));
+    }
+}
+
+impl SafeSponge {
+    /// Checks that the next operation is an ABSORB (or a SQUEEZE) of `len` elements.
+    fn check_next(&self, absorb: bool, len: usize) -> Result<(), SafeStatus> {
+        match self.sponge.io_pattern().operations().get(self.position) {
+            Some(operation) if operation.is_absorb() == absorb => {
+                if operation.len() as usize == len {
+                    Ok(())
+                } else {
+                    Err(SafeStatus::LengthMismatch)
+                }
+            }
+            _ => Err(SafeStatus::PatternMismatch),
+        }
+    }
+}
+
+unsafe fn words<'a>(pointer: *const u32, len: usize) -> Option<&'a [u32]> {
+    match len {
+        0 => Some(&[]),
+        _ if pointer.is_null() => None,
+        _ => Some(slice::from_raw_parts(pointer, len)),
+    }
+}
+
+unsafe fn bytes<'a>(pointer: *const u8, len: usize) -> Option<&'a [u8]> {
+    match len {
+        0 => Some(&[]),
+        _ if pointer.is_null() => None,
+        _ => Some(slice::from_raw_parts(pointer, len)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr;
+    use safe_api::domain::padded_domain_separator;
+
+    fn abcd_domain() -> [u8; 64] {
+        padded_domain_separator(b"ABCD")
+    }
+
+    fn element(value: u64) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        bytes[24..].copy_from_slice(&value.to_be_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_compute_tag() {
+        let words = [0x80000003u32, 0x00000001];
+        let mut tag = [0u8; 16];
+        let status = unsafe {
+            safe_compute_tag(words.as_ptr(), 2, abcd_domain().as_ptr(), tag.as_mut_ptr())
+        };
+        assert_eq!(status, SafeStatus::Ok);
+        assert_eq!(u128::from_be_bytes(tag), 0x0ea2aa7e178caa74de1f91e83ad43a81);
+
+        let status = unsafe { safe_compute_tag(words.as_ptr(), 2, ptr::null(), tag.as_mut_ptr()) };
+        assert_eq!(status, SafeStatus::NullPointer);
+    }
+
+    #[test]
+    fn test_sponge_session() {
+        let words = [0x80000002u32, 0x00000001];
+        let domain_separator = abcd_domain();
+        let input: Vec<u8> = [element(1), element(2)].concat();
+        let mut output = [0u8; 32];
+
+        unsafe {
+            let sponge = safe_sponge_new(words.as_ptr(), 2, domain_separator.as_ptr());
+            assert_eq!(
+                safe_sponge_squeeze(sponge, output.as_mut_ptr(), 1),
+                SafeStatus::PatternMismatch
+            );
+            assert_eq!(
+                safe_sponge_absorb(sponge, input.as_ptr(), 1),
+                SafeStatus::LengthMismatch
+            );
+            assert_eq!(safe_sponge_finish(sponge), SafeStatus::NotCompleted);
+            assert_eq!(
+                safe_sponge_absorb(sponge, input.as_ptr(), 2),
+                SafeStatus::Ok
+            );
+            assert_eq!(
+                safe_sponge_squeeze(sponge, output.as_mut_ptr(), 1),
+                SafeStatus::Ok
+            );
+            assert_eq!(safe_sponge_finish(sponge), SafeStatus::Ok);
+            safe_sponge_free(sponge);
+        }
+
+        let mut expected =
+            Sponge::<Poseidon2>::start(IoPattern::from_words(&words), domain_separator);
+        expected.absorb(&[Fr::from(1u64), Fr::from(2u64)]);
+        assert_eq!(
+            output.to_vec(),
+            expected.squeeze()[0].into_bigint().to_bytes_be()
+        );
+    }
+
+    #[test]
+    fn test_rejects_non_canonical_elements() {
+        let words = [0x80000001u32];
+        unsafe {
+            let sponge = safe_sponge_new(words.as_ptr(), 1, abcd_domain().as_ptr());
+            assert_eq!(
+                safe_sponge_absorb(sponge, [0xffu8; 32].as_ptr(), 1),
+                SafeStatus::NonCanonical
+            );
+            assert_eq!(
+                safe_sponge_absorb(sponge, element(5).as_ptr(), 1),
+                SafeStatus::Ok
+            );
+            safe_sponge_free(sponge);
+        }
+    }
+}
@@ -0,0 +1,12 @@
+//! The tag computation never panics, and agrees with the aggregated pattern and the heapless
+//! and 256-bit computations (see `safe_api::fuzzing::check_tag`).
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use safe_api::domain::DomainSeparator;
+use safe_api::IoPattern;
+
+fuzz_target!(|input: (IoPattern, DomainSeparator)| {
+    safe_api::fuzzing::check_tag(&input.0, &input.1);
+});
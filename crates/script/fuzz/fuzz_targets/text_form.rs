@@ -0,0 +1,10 @@
+//! Patterns, with their labels, parse back from their text form.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use safe_api::IoPattern;
+
+fuzz_target!(|pattern: IoPattern| {
+    safe_api::fuzzing::check_text_form(&pattern);
+});
@@ -0,0 +1,318 @@
+//! Canonical absorption of structured values.
+//!
+//! Flattening a struct into field elements by hand is where encodings drift: a field
+//! forgotten on one side, two fields swapped, a vector absorbed without its length. A type
+//! implementing [`Absorb`] knows its encoding, and [`Sponge::absorb_value`] absorbs it as one
+//! ABSORB of [`Absorb::absorb_len`] elements, as declared by [`IoPattern::absorb_value`].
+//! With the `derive` feature, `#[derive(Absorb)]` implements it for structs, absorbing
+//! their fields in declaration order:
+//!
+//! ```
+//! # #[cfg(feature = "derive")] {
+//! use ark_bn254::Fr;
+//! use safe_api::absorb::Absorb;
+//! use safe_api::domain::padded_domain_separator;
+//! use safe_api::poseidon2::Poseidon2;
+//! use safe_api::{IoPattern, Sponge};
+//!
+//! #[derive(Absorb)]
+//! struct Note {
+//!     owner: Fr,
+//!     amount: u64,
+//!     memo: String,
+//! }
+//!
+//! let note = Note { owner: Fr::from(7u64), amount: 100, memo: "rent".into() };
+//! let pattern = IoPattern::new().absorb_value::<Fr, _>(&note).squeeze(1);
+//! let mut sponge = Sponge::<Poseidon2>::start(pattern, &padded_domain_separator(b"notes"));
+//! sponge.absorb_value(&note);
+//! let commitment = sponge.squeeze();
+//! sponge.finish();
+//! # let _ = commitment;
+//! # }
+//! ```
+//!
+//! The encodings, each injective for a given type:
+//!
+//! | Type                                          | Elements                                      |
+//! |-----------------------------------------------|-----------------------------------------------|
+//! | a field element (any arkworks `Fp`)           | the element itself                            |
+//! | `bool`, `u8`, `u16`, `u32`, `u64`, `u128`     | the integer, one element                      |
+//! | `usize`                                       | as a `u64`                                    |
+//! | `str`, `String`                               | the [byte string encoding](crate::encoding)   |
+//! | `[T; N]`, tuples, structs                     | the encodings of the parts, in order          |
+//! | `[T]`, `Vec<T>`                               | the number of items, then the items           |
+//!
+//! The length of fixed-size values is part of their type and is not absorbed; the length of
+//! variable-size values always is, so `(vec![a], vec![b, c])` and `(vec![a, b], vec![c])`
+//! absorb different elements. An integer type must be narrower than the modulus, so that
+//! every value is its own element: absorbing a `u64` into a field of 64 bits or less, such as
+//! Goldilocks, panics.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use ark_ff::{Fp, FpConfig, PrimeField};
+
+use crate::encoding::{encode_bytes, encoded_len};
+
+/// Re-exports for the code generated by `#[derive(Absorb)]`.
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::vec::Vec;
+    pub use ark_ff::PrimeField;
+}
+
+#[cfg(feature = "derive")]
+pub use safe_api_derive::Absorb;
+
+/// A value with a canonical encoding into elements of `F`, see the
+/// [module documentation](self).
+pub trait Absorb<F: PrimeField> {
+    /// Appends the encoding of the value to `elements`.
+    fn absorb_into(&self, elements: &mut Vec<F>);
+
+    /// Returns the number of elements of the encoding, i.e. the length of its ABSORB.
+    fn absorb_len(&self) -> usize;
+
+    /// Returns the encoding of the value.
+    fn to_elements(&self) -> Vec<F> {
+        let mut elements = Vec::with_capacity(self.absorb_len());
+        self.absorb_into(&mut elements);
+        elements
+    }
+}
+
+impl<C: FpConfig<N>, const N: usize> Absorb<Fp<C, N>> for Fp<C, N> {
+    fn absorb_into(&self, elements: &mut Vec<Fp<C, N>>) {
+        elements.push(*self);
+    }
+
+    fn absorb_len(&self) -> usize {
+        1
+    }
+}
+
+macro_rules! impl_absorb_integer {
+    ($($ty:ty),*) => {$(
+        impl<F: PrimeField> Absorb<F> for $ty {
+            fn absorb_into(&self, elements: &mut Vec<F>) {
+                assert!(
+                    <$ty>::BITS < F::MODULUS_BIT_SIZE,
+                    concat!("A ", stringify!($ty), " does not fit in one element of the field")
+                );
+                elements.push(F::from(*self));
+            }
+
+            fn absorb_len(&self) -> usize {
+                1
+            }
+        }
+    )*};
+}
+
+impl_absorb_integer!(u8, u16, u32, u64, u128);
+
+impl<F: PrimeField> Absorb<F> for bool {
+    fn absorb_into(&self, elements: &mut Vec<F>) {
+        elements.push(F::from(*self));
+    }
+
+    fn absorb_len(&self) -> usize {
+        1
+    }
+}
+
+impl<F: PrimeField> Absorb<F> for usize {
+    fn absorb_into(&self, elements: &mut Vec<F>) {
+        Absorb::<F>::absorb_into(&(*self as u64), elements);
+    }
+
+    fn absorb_len(&self) -> usize {
+        1
+    }
+}
+
+impl<F: PrimeField> Absorb<F> for str {
+    fn absorb_into(&self, elements: &mut Vec<F>) {
+        elements.extend(encode_bytes::<F>(self.as_bytes()));
+    }
+
+    fn absorb_len(&self) -> usize {
+        encoded_len::<F>(self.len())
+    }
+}
+
+impl<F: PrimeField> Absorb<F> for String {
+    fn absorb_into(&self, elements: &mut Vec<F>) {
+        self.as_str().absorb_into(elements);
+    }
+
+    fn absorb_len(&self) -> usize {
+        Absorb::<F>::absorb_len(self.as_str())
+    }
+}
+
+impl<F: PrimeField, T: Absorb<F>, const N: usize> Absorb<F> for [T; N] {
+    fn absorb_into(&self, elements: &mut Vec<F>) {
+        for item in self {
+            item.absorb_into(elements);
+        }
+    }
+
+    fn absorb_len(&self) -> usize {
+        self.iter().map(|item| Absorb::<F>::absorb_len(item)).sum()
+    }
+}
+
+impl<F: PrimeField, T: Absorb<F>> Absorb<F> for [T] {
+    fn absorb_into(&self, elements: &mut Vec<F>) {
+        Absorb::<F>::absorb_into(&self.len(), elements);
+        for item in self {
+            item.absorb_into(elements);
+        }
+    }
+
+    fn absorb_len(&self) -> usize {
+        1 + self
+            .iter()
+            .map(|item| Absorb::<F>::absorb_len(item))
+            .sum::<usize>()
+    }
+}
+
+impl<F: PrimeField, T: Absorb<F>> Absorb<F> for Vec<T> {
+    fn absorb_into(&self, elements: &mut Vec<F>) {
+        self.as_slice().absorb_into(elements);
+    }
+
+    fn absorb_len(&self) -> usize {
+        self.as_slice().absorb_len()
+    }
+}
+
+impl<F: PrimeField, T: Absorb<F> + ?Sized> Absorb<F> for &T {
+    fn absorb_into(&self, elements: &mut Vec<F>) {
+        (**self).absorb_into(elements);
+    }
+
+    fn absorb_len(&self) -> usize {
+        (**self).absorb_len()
+    }
+}
+
+macro_rules! impl_absorb_tuple {
+    ($($name:ident $index:tt),+) => {
+        impl<F: PrimeField, $($name: Absorb<F>),+> Absorb<F> for ($($name,)+) {
+            fn absorb_into(&self, elements: &mut Vec<F>) {
+                $(self.$index.absorb_into(elements);)+
+            }
+
+            fn absorb_len(&self) -> usize {
+                0 $(+ self.$index.absorb_len())+
+            }
+        }
+    };
+}
+
+impl_absorb_tuple!(A 0);
+impl_absorb_tuple!(A 0, B 1);
+impl_absorb_tuple!(A 0, B 1, C 2);
+impl_absorb_tuple!(A 0, B 1, C 2, D 3);
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::domain::padded_domain_separator;
+    use crate::poseidon2::Poseidon2;
+    use crate::{IoPattern, Sponge};
+    use alloc::vec;
+    use ark_bn254::Fr;
+
+    #[test]
+    fn test_encodings() {
+        let element = Fr::from(9u64);
+        assert_eq!(element.to_elements(), [element]);
+        assert_eq!(
+            Absorb::<Fr>::to_elements(&(true, 7u8, u128::MAX)),
+            [Fr::from(1u64), Fr::from(7u64), Fr::from(u128::MAX)]
+        );
+        assert_eq!(Absorb::<Fr>::to_elements("abc"), encode_bytes::<Fr>(b"abc"));
+        assert_eq!(
+            Absorb::<Fr>::to_elements(&[1u32, 2]),
+            [Fr::from(1u64), Fr::from(2u64)]
+        );
+        assert_eq!(
+            Absorb::<Fr>::to_elements(&vec![1u32, 2]),
+            [Fr::from(2u64), Fr::from(1u64), Fr::from(2u64)]
+        );
+
+        // Variable-size values carry their lengths.
+        let split = |left: Vec<u32>, right: Vec<u32>| Absorb::<Fr>::to_elements(&(left, right));
+        assert_ne!(split(vec![1], vec![2, 3]), split(vec![1, 2], vec![3]));
+        for value in [(vec![1u32], vec![2u32, 3]), (vec![], vec![])] {
+            assert_eq!(
+                Absorb::<Fr>::absorb_len(&value),
+                Absorb::<Fr>::to_elements(&value).len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_absorb_value() {
+        let value = (Fr::from(5u64), vec![String::from("a"), String::from("bc")]);
+        let domain_separator = padded_domain_separator(b"absorb");
+        let pattern = IoPattern::new().absorb_value::<Fr, _>(&value).squeeze(1);
+        assert_eq!(pattern, IoPattern::new().absorb(6).squeeze(1));
+
+        let mut sponge = Sponge::<Poseidon2>::start(pattern.clone(), domain_separator);
+        sponge.absorb_value(&value);
+        let mut expected = Sponge::<Poseidon2>::start(pattern, domain_separator);
+        let mut elements = vec![Fr::from(5u64), Fr::from(2u64)];
+        elements.extend(encode_bytes::<Fr>(b"a"));
+        elements.extend(encode_bytes::<Fr>(b"bc"));
+        expected.absorb(&elements);
+        assert_eq!(sponge.squeeze(), expected.squeeze());
+    }
+
+    #[cfg(feature = "small-fields")]
+    #[test]
+    #[should_panic(expected = "A u64 does not fit in one element of the field")]
+    fn test_integer_wider_than_field() {
+        Absorb::<crate::fields::Goldilocks>::to_elements(&1u64);
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_derive() {
+        #[derive(Absorb)]
+        struct Note {
+            owner: Fr,
+            amount: u64,
+            memo: String,
+        }
+
+        #[derive(Absorb)]
+        struct Pair<T>(T, [T; 2]);
+
+        #[derive(Absorb)]
+        struct Unit;
+
+        let note = Note {
+            owner: Fr::from(7u64),
+            amount: 100,
+            memo: "rent".into(),
+        };
+        let elements = Absorb::<Fr>::to_elements(&note);
+        assert_eq!(
+            elements,
+            Absorb::<Fr>::to_elements(&(note.owner, note.amount, note.memo.as_str()))
+        );
+        assert_eq!(Absorb::<Fr>::absorb_len(&note), elements.len());
+        assert_eq!(
+            Absorb::<Fr>::to_elements(&Pair(1u8, [2u8, 3])),
+            [Fr::from(1u64), Fr::from(2u64), Fr::from(3u64)]
+        );
+        assert!(Absorb::<Fr>::to_elements(&Unit).is_empty());
+    }
+}
@@ -0,0 +1,384 @@
+//! Authenticated encryption of field-element payloads in duplex mode.
+//!
+//! A SAFE IO pattern may interleave squeezes and absorbs, which is all a duplex construction
+//! needs. Encrypting `n` elements runs the pattern of [`aead_pattern`]:
+//!
+//! 1. ABSORB the lengths of the key, the nonce and the associated data, then these,
+//! 2. SQUEEZE(`n`) the keystream, adding it to the plaintext,
+//! 3. ABSORB(`n`) the ciphertext, so the tag authenticates it,
+//! 4. SQUEEZE(1) the authentication tag.
+//!
+//! The tag of the sponge aggregates the four absorbs of the first step into one of their total
+//! length, so it alone would not tell the key, the nonce and the associated data apart: the
+//! lengths absorbed first do, and the domain separator binds the application. A
+//! nonce must never be reused with the same key: two payloads encrypted under the same key and
+//! nonce share their keystream. The Noir `aead_decrypt` decrypts and authenticates in a
+//! circuit, e.g. to prove statements about an encrypted payload.
+//!
+//! The authentication tag is compared in constant time, and the keystream is erased after use
+//! (see [`secret`](crate::secret)).
+//!
+//! # Prover hints
+//! [`encrypt_hints`] encrypts auxiliary prover hints under a key squeezed from the transcript
+//! of a proof, so that hint blobs stored alongside proofs can only be decrypted, with
+//! [`decrypt_hints`], by parties able to replay the transcript. The protocol declares the key
+//! as a SQUEEZE, of one element over BN254, at the point its value should depend on:
+//!
+//! ```
+//! use ark_bn254::Fr;
+//! use safe_api::aead::{decrypt_hints, encrypt_hints};
+//! use safe_api::domain::padded_domain_separator;
+//! use safe_api::poseidon2::Poseidon2;
+//! use safe_api::{IoPattern, Sponge};
+//!
+//! let protocol = IoPattern::new().absorb(2).squeeze(1).squeeze_labeled("hint key", 1);
+//! let domain_separator = padded_domain_separator(b"my-app/proof");
+//! let transcript = |statement: &[Fr]| {
+//!     let mut sponge = Sponge::<Poseidon2>::start(protocol.clone(), &domain_separator);
+//!     sponge.absorb(statement);
+//!     let _challenge = sponge.squeeze();
+//!     sponge
+//! };
+//! let statement = [Fr::from(3u64), Fr::from(5u64)];
+//! let nonce = [Fr::from(1u64)];
+//!
+//! let mut prover = transcript(&statement);
+//! let blob = encrypt_hints(&mut prover, &nonce, &[Fr::from(15u64)]);
+//! prover.finish();
+//!
+//! let mut verifier = transcript(&statement);
+//! assert_eq!(decrypt_hints(&mut verifier, &nonce, &blob), Ok(vec![Fr::from(15u64)]));
+//! verifier.finish();
+//! ```
+//!
+//! The hints are encrypted as above, under the squeezed key, the nonce and no associated data,
+//! with the domain separator `SAFE-HINTS-V1`. The key only repeats with the transcript, so the
+//! nonce only has to differ between blobs encrypted for the same transcript.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::domain::padded_domain_separator;
+use crate::io_pattern::IoPattern;
+use crate::permutation::Permutation;
+use crate::secret::{ct_eq, SecretAbsorb, Zeroizing};
+use crate::sponge::Sponge;
+
+/// Domain separator of the encryption of prover hints.
+const HINTS_DOMAIN: &[u8] = b"SAFE-HINTS-V1";
+
+/// Encrypted payload with its authentication tag.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ciphertext<F> {
+    pub payload: Vec<F>,
+    pub tag: F,
+}
+
+/// Error of [`decrypt`]: the ciphertext, the associated data, the nonce or the key differ from
+/// the ones of the encryption.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AuthenticationError;
+
+impl fmt::Display for AuthenticationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ciphertext authentication failed")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AuthenticationError {}
+
+/// IO pattern of the encryption of `payload_len` elements, starting with the absorb of the
+/// three lengths.
+pub fn aead_pattern(
+    key_len: usize,
+    nonce_len: usize,
+    associated_data_len: usize,
+    payload_len: usize,
+) -> IoPattern {
+    IoPattern::new()
+        .absorb(3)
+        .absorb(key_len as u32)
+        .absorb(nonce_len as u32)
+        .absorb(associated_data_len as u32)
+        .squeeze(payload_len as u32)
+        .absorb(payload_len as u32)
+        .squeeze(1)
+}
+
+/// Starts the duplex and returns it with the keystream of `payload_len` elements.
+fn keystream<P: Permutation + Default>(
+    key: &[P::Field],
+    nonce: &[P::Field],
+    associated_data: &[P::Field],
+    payload_len: usize,
+    domain_separator: &[u8; 64],
+) -> (Sponge<P>, Zeroizing<Vec<P::Field>>) {
+    let pattern = aead_pattern(key.len(), nonce.len(), associated_data.len(), payload_len);
+    let mut sponge = Sponge::<P>::start(pattern, domain_separator);
+    // The tag aggregates the absorbs below into one: the lengths tell them apart.
+    sponge.absorb(
+        &[key.len(), nonce.len(), associated_data.len()].map(|len| P::Field::from(len as u64)),
+    );
+    sponge.absorb(key);
+    sponge.absorb(nonce);
+    sponge.absorb(associated_data);
+    let keystream = sponge.squeeze_secret();
+    (sponge, keystream)
+}
+
+/// Absorbs the ciphertext and squeezes the authentication tag.
+fn authenticate<P: Permutation>(mut sponge: Sponge<P>, payload: &[P::Field]) -> P::Field {
+    sponge.absorb(payload);
+    let tag = sponge.squeeze()[0];
+    sponge.finish();
+    tag
+}
+
+/// Encrypts `plaintext` under `key` and `nonce`, authenticating `associated_data` as well.
+pub fn encrypt<P: Permutation + Default>(
+    key: &[P::Field],
+    nonce: &[P::Field],
+    associated_data: &[P::Field],
+    plaintext: &[P::Field],
+    domain_separator: &[u8; 64],
+) -> Ciphertext<P::Field> {
+    let (sponge, keystream) = keystream::<P>(
+        key,
+        nonce,
+        associated_data,
+        plaintext.len(),
+        domain_separator,
+    );
+    let payload: Vec<P::Field> = plaintext
+        .iter()
+        .zip(keystream.iter())
+        .map(|(&message, &mask)| message + mask)
+        .collect();
+    let tag = authenticate(sponge, &payload);
+    Ciphertext { payload, tag }
+}
+
+/// Authenticates and decrypts `ciphertext`. Nothing of the plaintext is returned if the
+/// authentication fails.
+pub fn decrypt<P: Permutation + Default>(
+    key: &[P::Field],
+    nonce: &[P::Field],
+    associated_data: &[P::Field],
+    ciphertext: &Ciphertext<P::Field>,
+    domain_separator: &[u8; 64],
+) -> Result<Vec<P::Field>, AuthenticationError> {
+    let (sponge, keystream) = keystream::<P>(
+        key,
+        nonce,
+        associated_data,
+        ciphertext.payload.len(),
+        domain_separator,
+    );
+    if !ct_eq(&authenticate(sponge, &ciphertext.payload), &ciphertext.tag) {
+        return Err(AuthenticationError);
+    }
+    Ok(ciphertext
+        .payload
+        .iter()
+        .zip(keystream.iter())
+        .map(|(&payload, &mask)| payload - mask)
+        .collect())
+}
+
+/// Encrypts the prover `hints` under a key squeezed from `transcript`, see
+/// [prover hints](self#prover-hints).
+///
+/// # Panics
+/// If the next operation of the IO pattern of `transcript` is not a SQUEEZE of at least one
+/// element.
+pub fn encrypt_hints<P: Permutation + Default>(
+    transcript: &mut Sponge<P>,
+    nonce: &[P::Field],
+    hints: &[P::Field],
+) -> Ciphertext<P::Field> {
+    let key = hint_key(transcript);
+    encrypt::<P>(
+        &key,
+        nonce,
+        &[],
+        hints,
+        &padded_domain_separator(HINTS_DOMAIN),
+    )
+}
+
+/// Authenticates and decrypts hints encrypted by [`encrypt_hints`], squeezing the key from
+/// `transcript`, which must be at the same point of the same transcript.
+///
+/// # Panics
+/// If the next operation of the IO pattern of `transcript` is not a SQUEEZE of at least one
+/// element.
+pub fn decrypt_hints<P: Permutation + Default>(
+    transcript: &mut Sponge<P>,
+    nonce: &[P::Field],
+    ciphertext: &Ciphertext<P::Field>,
+) -> Result<Vec<P::Field>, AuthenticationError> {
+    let key = hint_key(transcript);
+    decrypt::<P>(
+        &key,
+        nonce,
+        &[],
+        ciphertext,
+        &padded_domain_separator(HINTS_DOMAIN),
+    )
+}
+
+fn hint_key<P: Permutation>(transcript: &mut Sponge<P>) -> Zeroizing<Vec<P::Field>> {
+    let key = transcript.squeeze_secret();
+    assert!(!key.is_empty(), "Empty hint key");
+    key
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::poseidon2::Poseidon2;
+    use ark_bn254::Fr;
+    use core::str::FromStr;
+
+    fn abcd_domain() -> [u8; 64] {
+        crate::domain::padded_domain_separator(b"ABCD")
+    }
+
+    fn elements(values: &[u64]) -> Vec<Fr> {
+        values.iter().map(|&value| Fr::from(value)).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (key, nonce, data) = (elements(&[1, 2]), elements(&[3]), elements(&[4]));
+        let plaintext = elements(&[10, 20, 30, 40]);
+        let ciphertext = encrypt::<Poseidon2>(&key, &nonce, &data, &plaintext, &abcd_domain());
+        assert_ne!(ciphertext.payload, plaintext);
+        assert_eq!(
+            decrypt::<Poseidon2>(&key, &nonce, &data, &ciphertext, &abcd_domain()),
+            Ok(plaintext.clone())
+        );
+
+        let empty = encrypt::<Poseidon2>(&key, &nonce, &[], &[], &abcd_domain());
+        assert_eq!(
+            decrypt::<Poseidon2>(&key, &nonce, &[], &empty, &abcd_domain()),
+            Ok(Vec::new())
+        );
+    }
+
+    #[test]
+    fn test_authentication() {
+        let (key, nonce, data) = (elements(&[1, 2]), elements(&[3]), elements(&[4]));
+        let ciphertext =
+            encrypt::<Poseidon2>(&key, &nonce, &data, &elements(&[10]), &abcd_domain());
+
+        let decrypt = |key: &[Fr], nonce: &[Fr], data: &[Fr], ciphertext: &Ciphertext<Fr>| {
+            decrypt::<Poseidon2>(key, nonce, data, ciphertext, &abcd_domain())
+        };
+        let mut forged = ciphertext.clone();
+        forged.payload[0] += Fr::from(1u64);
+        assert_eq!(
+            decrypt(&key, &nonce, &data, &forged),
+            Err(AuthenticationError)
+        );
+        assert_eq!(
+            decrypt(&key, &nonce, &elements(&[5]), &ciphertext),
+            Err(AuthenticationError)
+        );
+        assert_eq!(
+            decrypt(&key, &elements(&[4]), &data, &ciphertext),
+            Err(AuthenticationError)
+        );
+        assert_eq!(
+            decrypt(&elements(&[1, 3]), &nonce, &data, &ciphertext),
+            Err(AuthenticationError)
+        );
+    }
+
+    #[test]
+    fn test_boundaries() {
+        // The same elements absorbed, split differently between the nonce and the data.
+        let key = elements(&[1, 2]);
+        let ciphertext = encrypt::<Poseidon2>(
+            &key,
+            &elements(&[3, 4]),
+            &elements(&[5]),
+            &elements(&[10]),
+            &abcd_domain(),
+        );
+        for (nonce, data) in [
+            (elements(&[3]), elements(&[4, 5])),
+            (elements(&[3, 4, 5]), vec![]),
+        ] {
+            assert_eq!(
+                decrypt::<Poseidon2>(&key, &nonce, &data, &ciphertext, &abcd_domain()),
+                Err(AuthenticationError)
+            );
+        }
+        assert_eq!(
+            decrypt::<Poseidon2>(
+                &elements(&[1]),
+                &elements(&[2, 3, 4]),
+                &elements(&[5]),
+                &ciphertext,
+                &abcd_domain()
+            ),
+            Err(AuthenticationError)
+        );
+    }
+
+    #[test]
+    fn test_matches_noir() {
+        // Same values as `test_aead_decrypt` of the Noir implementation.
+        let ciphertext = encrypt::<Poseidon2>(
+            &elements(&[1, 2]),
+            &elements(&[3]),
+            &elements(&[4]),
+            &elements(&[10, 20]),
+            &abcd_domain(),
+        );
+        let expected = Ciphertext {
+            payload: vec![
+                Fr::from_str(
+                    "10596597518435394130199946608649717020177141747356051826984872356509989533397",
+                )
+                .unwrap(),
+                Fr::from_str(
+                    "18295629054372947087599750028624489665797532148280630354361418876749282710344",
+                )
+                .unwrap(),
+            ],
+            tag: Fr::from_str(
+                "7397297365647666740737524674533809293975926609654814951860107773041807034643",
+            )
+            .unwrap(),
+        };
+        assert_eq!(ciphertext, expected);
+    }
+
+    #[test]
+    fn test_hints_bound_to_transcript() {
+        let protocol = IoPattern::new().absorb(1).squeeze(1);
+        let transcript = |statement: u64| {
+            let mut sponge = Sponge::<Poseidon2>::start(protocol.clone(), abcd_domain());
+            sponge.absorb(&elements(&[statement]));
+            sponge
+        };
+        let hints = elements(&[7, 8, 9]);
+        let blob = encrypt_hints(&mut transcript(1), &elements(&[0]), &hints);
+        assert_ne!(blob.payload, hints);
+        assert_eq!(
+            decrypt_hints(&mut transcript(1), &elements(&[0]), &blob),
+            Ok(hints)
+        );
+        assert_eq!(
+            decrypt_hints(&mut transcript(2), &elements(&[0]), &blob),
+            Err(AuthenticationError)
+        );
+        assert_eq!(
+            decrypt_hints(&mut transcript(1), &elements(&[1]), &blob),
+            Err(AuthenticationError)
+        );
+    }
+}
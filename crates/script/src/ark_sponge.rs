@@ -0,0 +1,233 @@
+//! The SAFE sponge behind the arkworks [`CryptographicSponge`] trait.
+//!
+//! [`ArkSponge`] lets arkworks code (Fiat-Shamir transforms, polynomial commitments, SNARK
+//! tooling) run on the same transcripts as the Noir circuits. The trait is free-form while the
+//! SAFE sponge is not: every call must still match the next operation of the IO pattern given
+//! in the [`ArkSpongeConfig`], with these lengths, in elements of the native field:
+//!
+//! - `absorb(input)` is one ABSORB of [`absorb_len`]`(input)` elements, i.e. the elements of
+//!   [`Absorb::to_sponge_field_elements`]. Native field elements map to themselves.
+//! - `squeeze_field_elements(n)` is one SQUEEZE of `n` elements, returned as they are for the
+//!   native field and otherwise reduced from their little-endian bytes.
+//! - `squeeze_bytes(n)` is one SQUEEZE of [`squeeze_bytes_len`]`(n)` elements, each carrying
+//!   [`bytes_per_squeezed_element`](crate::rng::bytes_per_squeezed_element) bytes as in [`SpongeRng`](crate::rng::SpongeRng).
+//! - `squeeze_bits(n)` and truncated `squeeze_field_elements_with_sizes` squeeze
+//!   [`squeeze_bits_len`] elements, the bits being those of the bytes, least significant
+//!   first.
+//! - `fork(domain)` clones the sponge and absorbs the little-endian bytes of `domain.len()`
+//!   followed by `domain`, as one byte vector: an ABSORB of [`absorb_len`] of that vector.
+//!
+//! A mismatch panics, like any misuse of [`Sponge`]:
+//!
+//! ```
+//! use ark_bn254::Fr;
+//! use ark_crypto_primitives::sponge::CryptographicSponge;
+//! use safe_api::ark_sponge::{absorb_len, ArkSponge, ArkSpongeConfig};
+//! use safe_api::domain::padded_domain_separator;
+//! use safe_api::poseidon2::Poseidon2;
+//! use safe_api::IoPattern;
+//!
+//! let commitment = vec![Fr::from(1u64), Fr::from(2u64)];
+//! let config = ArkSpongeConfig {
+//!     io_pattern: IoPattern::new()
+//!         .absorb(absorb_len::<Fr>(&commitment) as u32)
+//!         .squeeze(1),
+//!     domain_separator: padded_domain_separator(b"my-app/challenge"),
+//! };
+//! let mut sponge = ArkSponge::<Poseidon2>::new(&config);
+//! sponge.absorb(&commitment);
+//! let challenge: Vec<Fr> = sponge.squeeze_field_elements(1);
+//! sponge.finish();
+//! # assert_eq!(challenge.len(), 1);
+//! ```
+
+use alloc::vec::Vec;
+
+use ark_crypto_primitives::sponge::{
+    Absorb, CryptographicSponge, FieldBasedCryptographicSponge, FieldElementSize,
+};
+use ark_ff::{BigInteger, PrimeField};
+
+use crate::io_pattern::IoPattern;
+use crate::permutation::Permutation;
+pub use crate::rng::squeeze_bytes_len;
+use crate::sponge::Sponge;
+
+/// Parameters of [`ArkSponge::new`]: the whole IO pattern and the domain separator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArkSpongeConfig {
+    pub io_pattern: IoPattern,
+    pub domain_separator: [u8; 64],
+}
+
+/// Returns the number of elements of `F` that absorbing `input` takes.
+pub fn absorb_len<F: PrimeField>(input: &impl Absorb) -> usize {
+    input.to_sponge_field_elements_as_vec::<F>().len()
+}
+
+/// Returns the number of elements of `F` of the SQUEEZE behind `squeeze_bits(num_bits)`.
+pub fn squeeze_bits_len<F: PrimeField>(num_bits: usize) -> usize {
+    squeeze_bytes_len::<F>(num_bits.div_ceil(8))
+}
+
+/// A [`Sponge`] implementing [`CryptographicSponge`], see the [module documentation](self).
+#[derive(Clone, Debug)]
+pub struct ArkSponge<P: Permutation> {
+    sponge: Sponge<P>,
+}
+
+impl<P: Permutation> ArkSponge<P> {
+    /// Wraps a sponge, e.g. one that already absorbed part of its IO pattern.
+    pub fn from_sponge(sponge: Sponge<P>) -> Self {
+        ArkSponge { sponge }
+    }
+
+    /// Returns the wrapped sponge.
+    pub fn into_sponge(self) -> Sponge<P> {
+        self.sponge
+    }
+
+    /// Finalizes the sponge, see [`Sponge::finish`].
+    ///
+    /// # Panics
+    /// If operations of the IO pattern remain.
+    pub fn finish(&mut self) {
+        self.sponge.finish()
+    }
+}
+
+impl<P: Permutation + Clone + Default> CryptographicSponge for ArkSponge<P> {
+    type Config = ArkSpongeConfig;
+
+    fn new(config: &ArkSpongeConfig) -> Self {
+        ArkSponge {
+            sponge: Sponge::start(config.io_pattern.clone(), config.domain_separator),
+        }
+    }
+
+    fn absorb(&mut self, input: &impl Absorb) {
+        self.sponge
+            .absorb(&input.to_sponge_field_elements_as_vec::<P::Field>());
+    }
+
+    fn squeeze_bytes(&mut self, num_bytes: usize) -> Vec<u8> {
+        self.sponge.squeeze_bytes(num_bytes)
+    }
+
+    fn squeeze_bits(&mut self, num_bits: usize) -> Vec<bool> {
+        let mut bits: Vec<bool> = self
+            .squeeze_bytes(num_bits.div_ceil(8))
+            .iter()
+            .flat_map(|&byte| (0..8).map(move |bit| (byte >> bit) & 1 == 1))
+            .collect();
+        bits.truncate(num_bits);
+        bits
+    }
+
+    fn squeeze_field_elements_with_sizes<F: PrimeField>(
+        &mut self,
+        sizes: &[FieldElementSize],
+    ) -> Vec<F> {
+        if sizes.iter().all(|size| *size == FieldElementSize::Full) {
+            return self.squeeze_field_elements(sizes.len());
+        }
+        let bit_size = |size: &FieldElementSize| match *size {
+            FieldElementSize::Full => F::MODULUS_BIT_SIZE as usize - 1,
+            FieldElementSize::Truncated(bits) => bits,
+        };
+        let bits = self.squeeze_bits(sizes.iter().map(bit_size).sum());
+        let mut bits = bits.as_slice();
+        sizes
+            .iter()
+            .map(|size| {
+                let (element, rest) = bits.split_at(bit_size(size));
+                bits = rest;
+                F::from_le_bytes_mod_order(&F::BigInt::from_bits_le(element).to_bytes_le())
+            })
+            .collect()
+    }
+
+    fn squeeze_field_elements<F: PrimeField>(&mut self, num_elements: usize) -> Vec<F> {
+        let elements = self.squeeze_native_field_elements(num_elements);
+        elements
+            .iter()
+            .map(|element| F::from_le_bytes_mod_order(&element.into_bigint().to_bytes_le()))
+            .collect()
+    }
+}
+
+impl<P: Permutation + Clone + Default> FieldBasedCryptographicSponge<P::Field> for ArkSponge<P> {
+    fn squeeze_native_field_elements(&mut self, num_elements: usize) -> Vec<P::Field> {
+        let elements = self.sponge.squeeze();
+        assert!(elements.len() == num_elements, "Length mismatch");
+        elements
+    }
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::domain::padded_domain_separator;
+    use crate::poseidon2::Poseidon2;
+    use ark_bn254::Fr;
+
+    /// Arkworks-style code, generic over the sponge.
+    fn challenges<S: CryptographicSponge>(sponge: &mut S, message: &[u64]) -> (Vec<Fr>, Vec<u8>) {
+        sponge.absorb(&message.to_vec());
+        (sponge.squeeze_field_elements(2), sponge.squeeze_bytes(20))
+    }
+
+    #[test]
+    fn test_matches_sponge() {
+        let message = [1u64, 2, 3];
+        let pattern = IoPattern::new()
+            .absorb(absorb_len::<Fr>(&message.to_vec()) as u32)
+            .squeeze(2)
+            .squeeze(squeeze_bytes_len::<Fr>(20) as u32);
+        let config = ArkSpongeConfig {
+            io_pattern: pattern.clone(),
+            domain_separator: padded_domain_separator(b"test"),
+        };
+        let mut ark = ArkSponge::<Poseidon2>::new(&config);
+        let (elements, bytes) = challenges(&mut ark, &message);
+        ark.finish();
+
+        let mut sponge = Sponge::<Poseidon2>::start(pattern, padded_domain_separator(b"test"));
+        sponge.absorb(&[Fr::from(1u64), Fr::from(2u64), Fr::from(3u64)]);
+        assert_eq!(elements, sponge.squeeze());
+        let expected: Vec<u8> = sponge
+            .squeeze()
+            .iter()
+            .flat_map(|element| element.into_bigint().to_bytes_be()[17..].to_vec())
+            .take(20)
+            .collect();
+        assert_eq!(bytes, expected);
+        sponge.finish();
+    }
+
+    #[test]
+    fn test_truncated_sizes() {
+        let sizes = [FieldElementSize::Truncated(128), FieldElementSize::Full];
+        let bits = 128 + Fr::MODULUS_BIT_SIZE as usize - 1;
+        let config = ArkSpongeConfig {
+            io_pattern: IoPattern::new().squeeze(squeeze_bits_len::<Fr>(bits) as u32),
+            domain_separator: padded_domain_separator(b"test"),
+        };
+        let mut sponge = ArkSponge::<Poseidon2>::new(&config);
+        let elements: Vec<Fr> = sponge.squeeze_field_elements_with_sizes(&sizes);
+        sponge.finish();
+        assert!(elements[0].into_bigint().num_bits() <= 128);
+        assert_eq!(elements.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Length mismatch")]
+    fn test_pattern_enforced() {
+        let config = ArkSpongeConfig {
+            io_pattern: IoPattern::new().absorb(1),
+            domain_separator: padded_domain_separator(b"test"),
+        };
+        let mut sponge = ArkSponge::<Poseidon2>::new(&config);
+        sponge.absorb(&vec![Fr::from(1u64), Fr::from(2u64)]);
+    }
+}
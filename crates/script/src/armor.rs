@@ -0,0 +1,436 @@
+//! Checksummed text encodings of tags and envelopes, for values pasted into governance forums,
+//! chats and configuration files.
+//!
+//! - Tags are bech32m strings (BIP-350) with the human-readable part `stag`: the bytes of the
+//!   tag (16 for the tag of the spec), big-endian, then a 6-character checksum that detects
+//!   any error in up to 4 characters ([`encode_tag`], [`decode_tag`]):
+//!
+//! ```
+//! use safe_api::armor::{decode_tag, encode_tag};
+//! use safe_api::tag::Tag;
+//!
+//! let tag = Tag::from(0x0ea2aa7e178caa74de1f91e83ad43a81);
+//! assert_eq!(encode_tag(tag), "stag1p6325lsh3j48fhslj85r44p6syvkw6xv");
+//! assert_eq!(decode_tag("stag1p6325lsh3j48fhslj85r44p6syvkw6xv"), Ok(tag));
+//! assert!(decode_tag("stag1p6325lsh3j48fhslj85r44p6syvkw6xw").is_err());
+//! ```
+//!
+//! - Envelopes, the [`tag_preimage`](crate::tag_preimage) bytes a relayer submits along with a
+//!   proof, are armored: base64url without padding in lines of 64 characters, then `=` and the
+//!   base64url of the first 4 bytes of their SHA-256, which are also the first 4 bytes of the
+//!   tag, between a header and a footer ([`armor_envelope`], [`dearmor_envelope`]):
+//!
+//! ```text
+//! -----BEGIN SAFE ENVELOPE-----
+//! gAAAAwAAAAFBQkNEAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA
+//! AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA
+//! =DqKqfg
+//! -----END SAFE ENVELOPE-----
+//! ```
+//!
+//! Decoding ignores surrounding whitespace and line endings, and fails on any altered
+//! character.
+//!
+//! An armored envelope may carry headers, `Name: value` lines after the header line and before
+//! an empty line, such as the [`PUBLIC_INPUTS_HEADER`] of the proof it comes with
+//! ([`armor_envelope_with_headers`], [`dearmor_envelope_with_headers`]). The checksum covers
+//! the tag preimage only: the headers are not protected against typos.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use sha2::{Digest, Sha256};
+
+use crate::tag::Tag;
+
+/// Human-readable part of the bech32m tags.
+pub const TAG_HRP: &str = "stag";
+
+/// First line of an armored envelope.
+pub const ENVELOPE_HEADER: &str = "-----BEGIN SAFE ENVELOPE-----";
+
+/// Last line of an armored envelope.
+pub const ENVELOPE_FOOTER: &str = "-----END SAFE ENVELOPE-----";
+
+/// Header of an armored envelope carrying the public inputs of its proof, the absorbed field
+/// elements as comma-separated `0x` hex, one group per ABSORB separated by `;`.
+pub const PUBLIC_INPUTS_HEADER: &str = "Public-Inputs";
+
+/// The `(name, value)` headers of an armored envelope, in order.
+pub type Headers = Vec<(String, String)>;
+
+/// Characters per line of an armored envelope.
+const LINE_LENGTH: usize = 64;
+
+const BECH32_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const BECH32M_CONSTANT: u32 = 0x2bc830a3;
+const BASE64URL_CHARSET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Errors raised while decoding a tag or an envelope.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArmorError {
+    /// A character outside the alphabet of the encoding.
+    InvalidCharacter,
+    /// A bech32m string mixing upper and lower case.
+    MixedCase,
+    /// A missing separator, header, footer or checksum line.
+    Malformed,
+    /// A bech32m string whose human-readable part is not [`TAG_HRP`].
+    WrongPrefix,
+    /// The checksum does not match: the input was altered.
+    Checksum,
+    /// A payload of the wrong length: a tag of other than 16 bytes, or an envelope that is not
+    /// whole words followed by a 64-byte domain separator.
+    InvalidLength,
+}
+
+impl fmt::Display for ArmorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ArmorError::InvalidCharacter => "invalid character",
+            ArmorError::MixedCase => "mixed upper and lower case",
+            ArmorError::Malformed => "malformed encoding",
+            ArmorError::WrongPrefix => "not an `stag` tag",
+            ArmorError::Checksum => "invalid checksum",
+            ArmorError::InvalidLength => "invalid payload length",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ArmorError {}
+
+/// Encodes a tag as a bech32m string with the human-readable part [`TAG_HRP`].
+pub fn encode_tag(tag: Tag) -> String {
+    let data = to_groups(tag.as_bytes());
+    let checksum = bech32m_checksum(TAG_HRP, &data);
+    let mut encoded = String::with_capacity(TAG_HRP.len() + 1 + data.len() + 6);
+    encoded.push_str(TAG_HRP);
+    encoded.push('1');
+    for &group in data.iter().chain(&checksum) {
+        encoded.push(BECH32_CHARSET[group as usize] as char);
+    }
+    encoded
+}
+
+/// Decodes a tag encoded by [`encode_tag`], in lower or upper case.
+pub fn decode_tag(input: &str) -> Result<Tag, ArmorError> {
+    let input = input.trim();
+    let has_lower = input.bytes().any(|byte| byte.is_ascii_lowercase());
+    let has_upper = input.bytes().any(|byte| byte.is_ascii_uppercase());
+    if has_lower && has_upper {
+        return Err(ArmorError::MixedCase);
+    }
+    let input = input.to_ascii_lowercase();
+    let (hrp, data) = input.rsplit_once('1').ok_or(ArmorError::Malformed)?;
+    if hrp != TAG_HRP {
+        return Err(ArmorError::WrongPrefix);
+    }
+    let groups = data
+        .bytes()
+        .map(|character| {
+            BECH32_CHARSET
+                .iter()
+                .position(|&c| c == character)
+                .map(|group| group as u8)
+                .ok_or(ArmorError::InvalidCharacter)
+        })
+        .collect::<Result<Vec<u8>, ArmorError>>()?;
+    if groups.len() < 6 {
+        return Err(ArmorError::Malformed);
+    }
+    if polymod(hrp, &groups) != BECH32M_CONSTANT {
+        return Err(ArmorError::Checksum);
+    }
+    let bytes = from_groups(&groups[..groups.len() - 6]).ok_or(ArmorError::InvalidLength)?;
+    Tag::from_bytes(&bytes).ok_or(ArmorError::InvalidLength)
+}
+
+/// Armors an envelope, see the [module documentation](self).
+pub fn armor_envelope(envelope: &[u8]) -> String {
+    armor_envelope_with_headers(envelope, &[])
+}
+
+/// Armors an envelope with the `(name, value)` pairs of `headers`, see the
+/// [module documentation](self).
+///
+/// # Panics
+/// If a name is empty or contains a `:`, or a name or a value contains a line break.
+pub fn armor_envelope_with_headers(envelope: &[u8], headers: &[(&str, &str)]) -> String {
+    let body = base64url_encode(envelope);
+    let mut armored = String::from(ENVELOPE_HEADER);
+    armored.push('\n');
+    for &(name, value) in headers {
+        assert!(
+            !name.is_empty() && !name.contains([':', '\n', '\r']) && !value.contains(['\n', '\r']),
+            "invalid envelope header `{}`",
+            name
+        );
+        armored.push_str(name);
+        armored.push_str(": ");
+        armored.push_str(value);
+        armored.push('\n');
+    }
+    if !headers.is_empty() {
+        armored.push('\n');
+    }
+    for line in body.as_bytes().chunks(LINE_LENGTH) {
+        armored.push_str(core::str::from_utf8(line).expect("base64url is ASCII"));
+        armored.push('\n');
+    }
+    armored.push('=');
+    armored.push_str(&base64url_encode(&envelope_checksum(envelope)));
+    armored.push('\n');
+    armored.push_str(ENVELOPE_FOOTER);
+    armored.push('\n');
+    armored
+}
+
+/// Decodes an envelope armored by [`armor_envelope`], checking its checksum and that it is
+/// made of whole words followed by a 64-byte domain separator. Headers are skipped.
+pub fn dearmor_envelope(input: &str) -> Result<Vec<u8>, ArmorError> {
+    dearmor_envelope_with_headers(input).map(|(envelope, _)| envelope)
+}
+
+/// Decodes an envelope like [`dearmor_envelope`], with its headers, their whitespace trimmed.
+pub fn dearmor_envelope_with_headers(input: &str) -> Result<(Vec<u8>, Headers), ArmorError> {
+    let mut lines = input.trim().lines().map(str::trim).peekable();
+    if lines.next() != Some(ENVELOPE_HEADER) || lines.next_back() != Some(ENVELOPE_FOOTER) {
+        return Err(ArmorError::Malformed);
+    }
+    let mut headers = Vec::new();
+    while let Some((name, value)) = lines.peek().copied().and_then(|line| line.split_once(':')) {
+        headers.push((String::from(name.trim()), String::from(value.trim())));
+        lines.next();
+    }
+    if !headers.is_empty() && lines.next() != Some("") {
+        return Err(ArmorError::Malformed);
+    }
+    let checksum = lines
+        .next_back()
+        .and_then(|line| line.strip_prefix('='))
+        .ok_or(ArmorError::Malformed)?;
+    let body: String = lines.collect();
+
+    let envelope = base64url_decode(&body)?;
+    if base64url_decode(checksum)? != envelope_checksum(&envelope) {
+        return Err(ArmorError::Checksum);
+    }
+    if envelope.len() < 64 || (envelope.len() - 64) % 4 != 0 {
+        return Err(ArmorError::InvalidLength);
+    }
+    Ok((envelope, headers))
+}
+
+fn envelope_checksum(envelope: &[u8]) -> [u8; 4] {
+    let digest = Sha256::digest(envelope);
+    [digest[0], digest[1], digest[2], digest[3]]
+}
+
+/// The BCH checksum of bech32, over the expanded human-readable part and the data.
+fn polymod(hrp: &str, data: &[u8]) -> u32 {
+    const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    let expanded = hrp
+        .bytes()
+        .map(|byte| byte >> 5)
+        .chain(core::iter::once(0))
+        .chain(hrp.bytes().map(|byte| byte & 31));
+    expanded
+        .chain(data.iter().copied())
+        .fold(1, |checksum, value| {
+            let top = checksum >> 25;
+            let mut checksum = ((checksum & 0x1ffffff) << 5) ^ value as u32;
+            for (bit, generator) in GENERATOR.iter().enumerate() {
+                if (top >> bit) & 1 == 1 {
+                    checksum ^= generator;
+                }
+            }
+            checksum
+        })
+}
+
+fn bech32m_checksum(hrp: &str, data: &[u8]) -> [u8; 6] {
+    let mut values = data.to_vec();
+    values.extend_from_slice(&[0; 6]);
+    let checksum = polymod(hrp, &values) ^ BECH32M_CONSTANT;
+    core::array::from_fn(|i| ((checksum >> (5 * (5 - i))) & 31) as u8)
+}
+
+/// Regroups bytes into 5-bit groups, padding the last one with zeros.
+fn to_groups(bytes: &[u8]) -> Vec<u8> {
+    convert_bits(bytes, 8, 5, true).expect("padding is allowed")
+}
+
+/// Regroups 5-bit groups into bytes, rejecting more than 4 bits of padding or nonzero padding.
+fn from_groups(groups: &[u8]) -> Option<Vec<u8>> {
+    convert_bits(groups, 5, 8, false)
+}
+
+fn convert_bits(values: &[u8], from: u32, to: u32, pad: bool) -> Option<Vec<u8>> {
+    let mut accumulator = 0u32;
+    let mut bits = 0;
+    let mut output = Vec::with_capacity(values.len() * from as usize / to as usize + 1);
+    for &value in values {
+        accumulator = (accumulator << from) | value as u32;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            output.push(((accumulator >> bits) & ((1 << to) - 1)) as u8);
+        }
+    }
+    if pad {
+        if bits > 0 {
+            output.push(((accumulator << (to - bits)) & ((1 << to) - 1)) as u8);
+        }
+    } else if bits >= from || (accumulator & ((1 << bits) - 1)) != 0 {
+        return None;
+    }
+    Some(output)
+}
+
+pub(crate) fn base64url_encode(bytes: &[u8]) -> String {
+    to_sextets(bytes)
+        .into_iter()
+        .map(|sextet| BASE64URL_CHARSET[sextet as usize] as char)
+        .collect()
+}
+
+pub(crate) fn base64url_decode(input: &str) -> Result<Vec<u8>, ArmorError> {
+    let sextets = input
+        .bytes()
+        .map(|character| {
+            BASE64URL_CHARSET
+                .iter()
+                .position(|&c| c == character)
+                .map(|sextet| sextet as u8)
+                .ok_or(ArmorError::InvalidCharacter)
+        })
+        .collect::<Result<Vec<u8>, ArmorError>>()?;
+    convert_bits(&sextets, 6, 8, false).ok_or(ArmorError::Malformed)
+}
+
+fn to_sextets(bytes: &[u8]) -> Vec<u8> {
+    convert_bits(bytes, 8, 6, true).expect("padding is allowed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io_pattern::IoPattern;
+    use crate::tag::TagLength;
+
+    #[test]
+    fn test_bech32m_vectors() {
+        // Valid bech32m strings of BIP-350.
+        let groups: Vec<u8> = (0..32).rev().collect();
+        let mut encoded = String::from("abcdef1");
+        for group in groups.iter().chain(&bech32m_checksum("abcdef", &groups)) {
+            encoded.push(BECH32_CHARSET[*group as usize] as char);
+        }
+        assert_eq!(encoded, "abcdef1l7aum6echk45nj3s0wdvt2fg8x9yrzpqzd3ryx");
+        assert_eq!(bech32m_checksum("a", &[]), [31, 0, 9, 19, 17, 29]);
+    }
+
+    #[test]
+    fn test_tag_round_trip() {
+        let tag = Tag::from(0x0ea2aa7e178caa74de1f91e83ad43a81);
+        let encoded = encode_tag(tag);
+        assert_eq!(encoded, "stag1p6325lsh3j48fhslj85r44p6syvkw6xv");
+        assert_eq!(decode_tag(&encoded), Ok(tag));
+        assert_eq!(decode_tag(&encoded.to_uppercase()), Ok(tag));
+        let zero = Tag::from(0);
+        assert_eq!(decode_tag(&encode_tag(zero)), Ok(zero));
+        let short = Tag::from_digest(&[0xff; 32], TagLength::Bits64);
+        assert_eq!(decode_tag(&encode_tag(short)), Ok(short));
+
+        // Every single-character substitution is caught.
+        for index in 5..encoded.len() {
+            for &character in BECH32_CHARSET {
+                let mut altered = encoded.clone().into_bytes();
+                if altered[index] == character {
+                    continue;
+                }
+                altered[index] = character;
+                let altered = String::from_utf8(altered).unwrap();
+                assert_eq!(decode_tag(&altered), Err(ArmorError::Checksum));
+            }
+        }
+
+        let mut mixed = encoded.clone();
+        mixed.replace_range(..1, "S");
+        assert_eq!(decode_tag(&mixed), Err(ArmorError::MixedCase));
+        assert_eq!(
+            decode_tag(&encoded.replacen("stag", "ptag", 1)),
+            Err(ArmorError::WrongPrefix)
+        );
+        assert_eq!(decode_tag("stag1b"), Err(ArmorError::InvalidCharacter));
+    }
+
+    #[test]
+    fn test_envelope_round_trip() {
+        let mut domain_separator = [0u8; 64];
+        domain_separator[..4].copy_from_slice(b"ABCD");
+        let envelope = IoPattern::new()
+            .absorb(3)
+            .squeeze(1)
+            .tag_preimage(domain_separator);
+
+        let armored = armor_envelope(&envelope);
+        assert_eq!(
+            armored,
+            "-----BEGIN SAFE ENVELOPE-----\n\
+             gAAAAwAAAAFBQkNEAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA\n\
+             AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA\n\
+             =DqKqfg\n\
+             -----END SAFE ENVELOPE-----\n"
+        );
+        assert_eq!(dearmor_envelope(&armored), Ok(envelope.clone()));
+        assert_eq!(
+            dearmor_envelope(&armored.replace('\n', "\r\n")),
+            Ok(envelope.clone())
+        );
+        assert_eq!(
+            dearmor_envelope(&armored.replacen("gAAAAw", "gAAABw", 1)),
+            Err(ArmorError::Checksum)
+        );
+        assert_eq!(
+            dearmor_envelope(&armored.replacen(ENVELOPE_FOOTER, "", 1)),
+            Err(ArmorError::Malformed)
+        );
+        assert_eq!(
+            dearmor_envelope(&armor_envelope(&envelope[1..])),
+            Err(ArmorError::InvalidLength)
+        );
+    }
+
+    #[test]
+    fn test_envelope_headers() {
+        let envelope = IoPattern::new()
+            .absorb(2)
+            .squeeze(1)
+            .tag_preimage([0x41; 64]);
+        let armored =
+            armor_envelope_with_headers(&envelope, &[(PUBLIC_INPUTS_HEADER, "0x01,0x02")]);
+        assert!(armored
+            .starts_with("-----BEGIN SAFE ENVELOPE-----\nPublic-Inputs: 0x01,0x02\n\ngAAAAg"));
+        assert_eq!(
+            dearmor_envelope_with_headers(&armored),
+            Ok((
+                envelope.clone(),
+                vec![(PUBLIC_INPUTS_HEADER.into(), "0x01,0x02".into())]
+            ))
+        );
+        // The checksum, and so the decoding, ignores the headers.
+        assert_eq!(dearmor_envelope(&armored), Ok(envelope.clone()));
+        assert_eq!(
+            dearmor_envelope_with_headers(&armor_envelope(&envelope)),
+            Ok((envelope, Vec::new()))
+        );
+        assert_eq!(
+            dearmor_envelope(&armored.replacen("\n\n", "\n", 1)),
+            Err(ArmorError::Malformed)
+        );
+    }
+}
@@ -0,0 +1,262 @@
+//! Attestations (`safe.attestation.json`) of the generator of committed artifacts.
+//!
+//! `safe-api attest --key attest.key src/tags.nr ...` signs, with an Ed25519 key, a statement
+//! of which generator (this crate and its version) produced the given artifacts from which
+//! manifest, by their SHA-256 hashes. Downstream repositories commit the attestation next to
+//! the artifacts, and `safe-api verify-attestation --public-key 0x...` checks the signature
+//! and that the manifest and artifacts on disk are still the attested ones:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "statement": {
+//!     "generator": "safe-api 0.1.0",
+//!     "manifest_hash": "0x9c0f...",
+//!     "artifacts": [
+//!       { "path": "src/tags.nr", "hash": "0x5e1b..." }
+//!     ]
+//!   },
+//!   "public_key": "0x3d40...",
+//!   "signature": "0x8a21..."
+//! }
+//! ```
+//!
+//! The signature covers `SAFE-ATTESTATION-V1` followed by the JSON of the statement, in the
+//! order above. Neither has a timestamp and Ed25519 signatures are deterministic, so attesting
+//! the same build twice gives the same file. The key file holds the 32-byte Ed25519 secret key
+//! as `0x` hex, e.g. from `echo 0x$(openssl rand -hex 32)`. Artifact paths are relative to the
+//! working directory.
+
+use std::path::{Path, PathBuf};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use safe_api::display;
+use safe_api::hex::{self, Mode};
+
+const VERSION: u32 = 1;
+
+/// Prefix of the signed message, versioning its layout.
+const SIGNING_PREFIX: &[u8] = b"SAFE-ATTESTATION-V1";
+
+/// This generator, as named in the statements it signs.
+pub const GENERATOR: &str = concat!("safe-api ", env!("CARGO_PKG_VERSION"));
+
+/// A signed statement.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Attestation {
+    pub version: u32,
+    pub statement: Statement,
+    /// Ed25519 public key of the signer.
+    pub public_key: String,
+    /// Ed25519 signature of the statement.
+    pub signature: String,
+}
+
+/// What was generated, by which generator, from which manifest.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Statement {
+    pub generator: String,
+    /// SHA-256 of the manifest file.
+    pub manifest_hash: String,
+    pub artifacts: Vec<Artifact>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Artifact {
+    pub path: String,
+    /// SHA-256 of the file.
+    pub hash: String,
+}
+
+impl Statement {
+    /// Hashes the manifest and the artifacts generated from it by this generator.
+    pub fn new(manifest: &Path, artifacts: &[PathBuf]) -> Result<Self, String> {
+        Ok(Statement {
+            generator: GENERATOR.to_string(),
+            manifest_hash: hash_file(manifest)?,
+            artifacts: artifacts
+                .iter()
+                .map(|path| {
+                    Ok(Artifact {
+                        path: path.display().to_string(),
+                        hash: hash_file(path)?,
+                    })
+                })
+                .collect::<Result<_, String>>()?,
+        })
+    }
+
+    fn signed_message(&self) -> Vec<u8> {
+        let json = serde_json::to_string(self).expect("statements always serialize");
+        [SIGNING_PREFIX, json.as_bytes()].concat()
+    }
+}
+
+impl Attestation {
+    /// Signs `statement` with `key`.
+    pub fn sign(statement: Statement, key: &SigningKey) -> Self {
+        let signature = key.sign(&statement.signed_message());
+        Attestation {
+            version: VERSION,
+            public_key: display::format_bytes(key.verifying_key().as_bytes()),
+            signature: display::format_bytes(&signature.to_bytes()),
+            statement,
+        }
+    }
+
+    /// Reads and parses an attestation.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|error| format!("cannot read {}: {}", path.display(), error))?;
+        Self::parse(&contents).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    /// Parses the contents of an attestation.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let attestation: Attestation =
+            serde_json::from_str(contents).map_err(|error| error.to_string())?;
+        if attestation.version != VERSION {
+            return Err(format!(
+                "unsupported attestation version {}",
+                attestation.version
+            ));
+        }
+        Ok(attestation)
+    }
+
+    /// Renders the attestation.
+    pub fn render(&self) -> String {
+        let mut json = serde_json::to_string_pretty(self).expect("attestations always serialize");
+        json.push('\n');
+        json
+    }
+
+    /// Checks that the attestation is signed by `public_key`.
+    pub fn verify(&self, public_key: &VerifyingKey) -> Result<(), String> {
+        if self.public_key != display::format_bytes(public_key.as_bytes()) {
+            return Err(format!(
+                "signed by {}, not by the trusted key",
+                self.public_key
+            ));
+        }
+        let signature = hex::decode_array::<64>(&self.signature, Mode::Strict)
+            .map_err(|error| format!("the signature is not 64 bytes of hex: {}", error))?;
+        public_key
+            .verify_strict(
+                &self.statement.signed_message(),
+                &Signature::from_bytes(&signature),
+            )
+            .map_err(|_| "invalid signature: the statement was altered".to_string())
+    }
+
+    /// Checks that the manifest and the artifacts on disk have the attested hashes, reporting
+    /// the first that changed.
+    pub fn check_files(&self, manifest: &Path) -> Result<(), String> {
+        if hash_file(manifest)? != self.statement.manifest_hash {
+            return Err(format!(
+                "{} changed since it was attested",
+                manifest.display()
+            ));
+        }
+        for artifact in &self.statement.artifacts {
+            if hash_file(Path::new(&artifact.path))? != artifact.hash {
+                return Err(format!("{} changed since it was attested", artifact.path));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads an Ed25519 secret key, as hex in the lenient mode of [`hex`].
+pub fn load_signing_key(path: &Path) -> Result<SigningKey, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|error| format!("cannot read {}: {}", path.display(), error))?;
+    let bytes = parse_key(&contents)
+        .map_err(|error| format!("{}: the secret key {}", path.display(), error))?;
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+/// Parses an Ed25519 public key, as hex in the lenient mode of [`hex`].
+pub fn parse_public_key(input: &str) -> Result<VerifyingKey, String> {
+    let bytes = parse_key(input).map_err(|error| format!("the public key {}", error))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| "the public key is not a curve point".to_string())
+}
+
+fn parse_key(input: &str) -> Result<[u8; 32], String> {
+    hex::decode_array(input, Mode::Lenient)
+        .map_err(|error| format!("is not 32 bytes of hex: {}", error))
+}
+
+fn hash_file(path: &Path) -> Result<String, String> {
+    let contents = std::fs::read(path)
+        .map_err(|error| format!("cannot read {}: {}", path.display(), error))?;
+    Ok(display::format_bytes(&Sha256::digest(contents)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statement() -> Statement {
+        Statement {
+            generator: GENERATOR.to_string(),
+            manifest_hash: display::format_bytes(&[1; 32]),
+            artifacts: vec![Artifact {
+                path: "src/tags.nr".to_string(),
+                hash: display::format_bytes(&[2; 32]),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let attestation = Attestation::sign(statement(), &key);
+        // Signing is deterministic.
+        assert_eq!(Attestation::sign(statement(), &key), attestation);
+        assert_eq!(
+            Attestation::parse(&attestation.render()).unwrap(),
+            attestation
+        );
+        let public_key = parse_public_key(&attestation.public_key).unwrap();
+        assert_eq!(attestation.verify(&public_key), Ok(()));
+
+        let other = SigningKey::from_bytes(&[8; 32]).verifying_key();
+        assert!(attestation
+            .verify(&other)
+            .unwrap_err()
+            .ends_with("not by the trusted key"));
+
+        let mut altered = Attestation::parse(&attestation.render()).unwrap();
+        altered.statement.artifacts[0].hash = display::format_bytes(&[3; 32]);
+        assert_eq!(
+            altered.verify(&public_key),
+            Err("invalid signature: the statement was altered".to_string())
+        );
+    }
+
+    #[test]
+    fn test_check_files() {
+        let directory = std::env::temp_dir().join(format!("safe-attest-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let (manifest, artifact) = (directory.join("safe.toml"), directory.join("tags.nr"));
+        std::fs::write(&manifest, "[[protocol]]\n").unwrap();
+        std::fs::write(&artifact, "global TAG: Field = 1;\n").unwrap();
+
+        let statement = Statement::new(&manifest, std::slice::from_ref(&artifact)).unwrap();
+        let attestation = Attestation::sign(statement, &SigningKey::from_bytes(&[7; 32]));
+        assert_eq!(attestation.check_files(&manifest), Ok(()));
+        std::fs::write(&artifact, "global TAG: Field = 2;\n").unwrap();
+        assert!(attestation
+            .check_files(&manifest)
+            .unwrap_err()
+            .ends_with("changed since it was attested"));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,245 @@
+//! Audit of the struct layouts that protocols absorb.
+//!
+//! A struct absorbed by several protocols, or defined both in Rust and in Noir, must be
+//! absorbed with the same fields in the same order and with the same encodings everywhere,
+//! or the two sides hash different transcripts. Copy-pasted definitions drift silently, so
+//! `safe-api audit` collects every layout of the manifest (the `[[protocol.absorb]]` tables)
+//! and of metadata files exported by the code that defines the structs, such as a derive
+//! macro or a Noir build script, and reports every struct whose layouts disagree:
+//!
+//! ```json
+//! [
+//!   {
+//!     "struct": "Note",
+//!     "source": "src/note.rs",
+//!     "fields": [
+//!       { "name": "owner", "encoding": "Field" },
+//!       { "name": "amount", "encoding": "u64" }
+//!     ]
+//!   }
+//! ]
+//! ```
+//!
+//! Each disagreement is reported against the first layout of the struct: missing or extra
+//! fields, the same fields in a different order, or a field with a different encoding.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::manifest::Manifest;
+
+/// Encoding of a manifest field declared without one.
+const DEFAULT_ENCODING: &str = "Field";
+
+/// Where a struct is absorbed, and how.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Layout {
+    #[serde(rename = "struct")]
+    pub name: String,
+    /// The protocol or file declaring the layout.
+    pub source: String,
+    /// Fields in absorption order.
+    pub fields: Vec<Field>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Field {
+    pub name: String,
+    pub encoding: String,
+}
+
+/// Returns the layouts declared by the protocols of the manifest.
+pub fn manifest_layouts(manifest: &Manifest) -> Result<Vec<Layout>, String> {
+    let mut layouts = Vec::new();
+    for protocol in &manifest.protocols {
+        for absorbed in &protocol.absorbs {
+            let fields = absorbed
+                .fields
+                .iter()
+                .map(|field| parse_field(field))
+                .collect::<Result<_, _>>()
+                .map_err(|error| format!("protocol `{}`: {}", protocol.name, error))?;
+            layouts.push(Layout {
+                name: absorbed.name.clone(),
+                source: format!("protocol `{}`", protocol.name),
+                fields,
+            });
+        }
+    }
+    Ok(layouts)
+}
+
+/// Reads the layouts of a metadata file, a JSON array of layouts.
+pub fn load_metadata(path: &Path) -> Result<Vec<Layout>, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|error| format!("cannot read {}: {}", path.display(), error))?;
+    serde_json::from_str(&contents).map_err(|error| format!("{}: {}", path.display(), error))
+}
+
+/// Parses a manifest field, `name` or `name: encoding`.
+fn parse_field(input: &str) -> Result<Field, String> {
+    let (name, encoding) = match input.split_once(':') {
+        Some((name, encoding)) => (name.trim(), encoding.trim()),
+        None => (input.trim(), DEFAULT_ENCODING),
+    };
+    if name.is_empty() || encoding.is_empty() {
+        return Err(format!("invalid field `{}`", input));
+    }
+    Ok(Field {
+        name: name.to_string(),
+        encoding: encoding.to_string(),
+    })
+}
+
+/// Returns the disagreements between the layouts of each struct, by struct name.
+pub fn audit(layouts: &[Layout]) -> Vec<String> {
+    let mut by_struct: BTreeMap<&str, Vec<&Layout>> = BTreeMap::new();
+    for layout in layouts {
+        by_struct.entry(&layout.name).or_default().push(layout);
+    }
+
+    let mut findings = Vec::new();
+    for (name, layouts) in by_struct {
+        let reference = layouts[0];
+        for layout in &layouts[1..] {
+            findings.extend(
+                compare(reference, layout)
+                    .into_iter()
+                    .map(|finding| format!("`{}`: {}", name, finding)),
+            );
+        }
+    }
+    findings
+}
+
+fn compare(reference: &Layout, layout: &Layout) -> Vec<String> {
+    let names = |layout: &Layout| -> Vec<String> {
+        layout
+            .fields
+            .iter()
+            .map(|field| field.name.clone())
+            .collect()
+    };
+    let (expected, actual) = (names(reference), names(layout));
+    let missing: Vec<&str> = expected
+        .iter()
+        .filter(|name| !actual.contains(name))
+        .map(String::as_str)
+        .collect();
+    let extra: Vec<&str> = actual
+        .iter()
+        .filter(|name| !expected.contains(name))
+        .map(String::as_str)
+        .collect();
+
+    let mut findings = Vec::new();
+    if !missing.is_empty() {
+        findings.push(format!(
+            "{} lacks {}, absorbed by {}",
+            layout.source,
+            missing.join(", "),
+            reference.source
+        ));
+    }
+    if !extra.is_empty() {
+        findings.push(format!(
+            "{} also absorbs {}, unlike {}",
+            layout.source,
+            extra.join(", "),
+            reference.source
+        ));
+    }
+    if missing.is_empty() && extra.is_empty() && expected != actual {
+        findings.push(format!(
+            "{} absorbs {}, but {} absorbs {}",
+            reference.source,
+            expected.join(", "),
+            layout.source,
+            actual.join(", ")
+        ));
+    }
+    for field in &layout.fields {
+        let Some(other) = reference
+            .fields
+            .iter()
+            .find(|other| other.name == field.name)
+        else {
+            continue;
+        };
+        if other.encoding != field.encoding {
+            findings.push(format!(
+                "`{}` is encoded as {} by {}, but as {} by {}",
+                field.name, other.encoding, reference.source, field.encoding, layout.source
+            ));
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(source: &str, fields: &[&str]) -> Layout {
+        Layout {
+            name: "Note".to_string(),
+            source: source.to_string(),
+            fields: fields
+                .iter()
+                .map(|field| parse_field(field).unwrap())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_manifest_layouts() {
+        let manifest = Manifest::parse(
+            "[[protocol]]\nname = \"spend\"\npattern = \"A3 S1\"\ndomain = \"0x01\"\n\n\
+             [[protocol.absorb]]\nstruct = \"Note\"\nfields = [\"owner\", \"amount: u64\"]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            manifest_layouts(&manifest).unwrap(),
+            vec![layout("protocol `spend`", &["owner: Field", "amount:u64"])]
+        );
+        assert_eq!(
+            parse_field("owner:"),
+            Err("invalid field `owner:`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_audit() {
+        let reference = layout("protocol `spend`", &["owner", "amount: u64", "blinding"]);
+        assert!(audit(&[
+            reference.clone(),
+            layout("src/note.rs", &["owner", "amount: u64", "blinding"])
+        ])
+        .is_empty());
+
+        let reordered = layout("src/note.rs", &["amount: u64", "owner", "blinding"]);
+        assert_eq!(
+            audit(&[reference.clone(), reordered]),
+            ["`Note`: protocol `spend` absorbs owner, amount, blinding, but src/note.rs absorbs amount, owner, blinding"]
+        );
+
+        let diverged = layout("src/note.nr", &["owner", "amount", "salt"]);
+        assert_eq!(
+            audit(&[reference.clone(), diverged]),
+            [
+                "`Note`: src/note.nr lacks blinding, absorbed by protocol `spend`",
+                "`Note`: src/note.nr also absorbs salt, unlike protocol `spend`",
+                "`Note`: `amount` is encoded as u64 by protocol `spend`, but as Field by src/note.nr",
+            ]
+        );
+
+        // Structs of different names are never compared.
+        let mut other = layout("src/other.rs", &["x"]);
+        other.name = "Other".to_string();
+        assert!(audit(&[reference, other]).is_empty());
+    }
+}
@@ -0,0 +1,507 @@
+//! Conformance certificates (`safe.certificate.json`) of a permutation backend.
+//!
+//! `safe-api certify --backend poseidon2-bn254 --key certify.key` runs the known-answer tests
+//! and the properties below against one backend, and signs, with an Ed25519 key as for
+//! attestations, a report of the generator, the backend, the hash of the tag vectors and the
+//! outcome of each check. Audits and partner integrations attach the certificate:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "report": {
+//!     "generator": "safe-api 0.1.0",
+//!     "backend": "poseidon2-bn254",
+//!     "vectors_schema": 1,
+//!     "vectors_hash": "0x4f6c...",
+//!     "checks": [
+//!       { "name": "permutation-kat", "passed": true },
+//!       { "name": "tag-vectors", "passed": true }
+//!     ],
+//!     "passed": true
+//!   },
+//!   "public_key": "0x3d40...",
+//!   "signature": "0x8a21..."
+//! }
+//! ```
+//!
+//! The checks:
+//!
+//! - `permutation-kat`: the permutation of a fixed state against the published answer of the
+//!   backend's reference implementation.
+//! - `tag-vectors`: the committed tag vectors (`vectors/tags.json`), whose SHA-256 is
+//!   `vectors_hash`.
+//! - `spec-reference`: the sponge against a direct transcription of spec 2.4 on patterns
+//!   spanning several permutations.
+//! - `determinism`, `domain-separation`, `input-sensitivity`: equal runs agree, other domain
+//!   separators or patterns give other outputs, and so does any changed input element.
+//!
+//! The signature covers `SAFE-CERTIFICATE-V1` followed by the JSON of the report. Only the
+//! BN254 backends compiled into the binary can be certified (see `BACKENDS`).
+
+use ark_bn254::Fr;
+use ark_ff::Zero;
+use ed25519_dalek::{Signer, SigningKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use safe_api::domain::padded_domain_separator;
+use safe_api::{display, vectors, IoPattern, Permutation, Sponge};
+
+use crate::attestation::GENERATOR;
+
+const VERSION: u32 = 1;
+
+/// Prefix of the signed message, versioning its layout.
+const SIGNING_PREFIX: &[u8] = b"SAFE-CERTIFICATE-V1";
+
+/// The committed tag vectors, shared with the Noir implementation.
+const TAG_VECTORS: &str = include_str!("../vectors/tags.json");
+
+/// A certifiable backend: its name and its suite.
+struct Backend {
+    name: &'static str,
+    run: fn() -> Vec<Check>,
+}
+
+/// The backends compiled into the binary.
+const BACKENDS: &[Backend] = &[
+    Backend {
+        name: "poseidon2-bn254",
+        run: || {
+            // Barretenberg and the ACVM blackbox solver on the zero state.
+            let expected = [
+                "0x18dfb8dc9b82229cff974efefc8df78b1ce96d9d844236b496785c698bc6732e",
+                "0x095c230d1d37a246e8d2d5a63b165fe0fade040d442f61e25f0590e5fb76f839",
+                "0x0bb9545846e1afa4fa3c97414a60a20fc4949f537a68cceca34c5ce71e28aa59",
+                "0x18a4f34c9c6f99335ff7638b82aeed9018026618358873c982bbdde265b2ed6d",
+            ];
+            suite(&safe_api::poseidon2::Poseidon2, [0, 0, 0, 0], &expected)
+        },
+    },
+    #[cfg(feature = "rescue-prime")]
+    Backend {
+        name: "rescue-prime-bn254",
+        run: || {
+            // The reference algorithm on [0, 1, 2, 3].
+            let expected = [
+                "17333178955977042199479936369437270786487137858152022215081994148672921158408",
+                "8006726097758931559973272907884845846453113410286106509125997875515397162743",
+                "9434084398625838466297011603701866751011358678150517537600748135805911517609",
+                "7622214221431152665281586291361980203350090867725753540724535009239019393868",
+            ];
+            suite(
+                &safe_api::rescue_prime::RescuePrime,
+                [0, 1, 2, 3],
+                &expected,
+            )
+        },
+    },
+    #[cfg(feature = "poseidon")]
+    Backend {
+        name: "poseidon-bn254",
+        run: || {
+            // circomlib Poseidon([1, 2, 3]), the first element of the permuted [0, 1, 2, 3].
+            let expected =
+                ["6542985608222806190361240322586112750744169038454362455181422643027100751666"];
+            suite(
+                &safe_api::poseidon::Poseidon::new(),
+                [0, 1, 2, 3],
+                &expected,
+            )
+        },
+    },
+    #[cfg(feature = "keccak-f")]
+    Backend {
+        name: "keccak-f-bn254",
+        run: || {
+            // Regression vector of the lane layout of `keccak_f`, on [0, 1, 2, 3].
+            let expected =
+                ["15966045533703852423420068988825635274301029667905055715895488337531548616777"];
+            suite(&safe_api::keccak_f::KeccakF, [0, 1, 2, 3], &expected)
+        },
+    },
+];
+
+/// A signed report.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Certificate {
+    pub version: u32,
+    pub report: Report,
+    /// Ed25519 public key of the signer.
+    pub public_key: String,
+    /// Ed25519 signature of the report.
+    pub signature: String,
+}
+
+/// The outcome of the suite of a backend.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Report {
+    pub generator: String,
+    pub backend: String,
+    /// Schema version of the tag vectors.
+    pub vectors_schema: u32,
+    /// SHA-256 of the tag vectors.
+    pub vectors_hash: String,
+    pub checks: Vec<Check>,
+    /// Whether every check passed.
+    pub passed: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Check {
+    pub name: String,
+    pub passed: bool,
+    /// Why the check failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<String>,
+}
+
+impl Check {
+    fn new(name: &str, outcome: Result<(), String>) -> Self {
+        Check {
+            name: name.to_string(),
+            passed: outcome.is_ok(),
+            failure: outcome.err(),
+        }
+    }
+}
+
+/// Returns the names of the backends that can be certified.
+pub fn backends() -> Vec<&'static str> {
+    BACKENDS.iter().map(|backend| backend.name).collect()
+}
+
+impl Report {
+    /// Runs the suite of the backend named `backend`.
+    pub fn run(backend: &str) -> Result<Self, String> {
+        let suite = BACKENDS
+            .iter()
+            .find(|candidate| candidate.name == backend)
+            .ok_or_else(|| {
+                format!(
+                    "unknown backend `{}` (available: {})",
+                    backend,
+                    backends().join(", ")
+                )
+            })?;
+        let checks = (suite.run)();
+        Ok(Report {
+            generator: GENERATOR.to_string(),
+            backend: backend.to_string(),
+            vectors_schema: vectors::SCHEMA_VERSION,
+            vectors_hash: display::format_bytes(&Sha256::digest(TAG_VECTORS)),
+            passed: checks.iter().all(|check| check.passed),
+            checks,
+        })
+    }
+
+    fn signed_message(&self) -> Vec<u8> {
+        let json = serde_json::to_string(self).expect("reports always serialize");
+        [SIGNING_PREFIX, json.as_bytes()].concat()
+    }
+}
+
+impl Certificate {
+    /// Signs `report` with `key`.
+    pub fn sign(report: Report, key: &SigningKey) -> Self {
+        let signature = key.sign(&report.signed_message());
+        Certificate {
+            version: VERSION,
+            public_key: display::format_bytes(key.verifying_key().as_bytes()),
+            signature: display::format_bytes(&signature.to_bytes()),
+            report,
+        }
+    }
+
+    /// Renders the certificate.
+    pub fn render(&self) -> String {
+        let mut json = serde_json::to_string_pretty(self).expect("certificates always serialize");
+        json.push('\n');
+        json
+    }
+}
+
+/// Runs every check against `permutation`, whose image of `input` starts with `expected`.
+fn suite<P: Permutation<Field = Fr> + Clone>(
+    permutation: &P,
+    input: [u64; 4],
+    expected: &[&str],
+) -> Vec<Check> {
+    vec![
+        Check::new(
+            "permutation-kat",
+            permutation_kat(permutation, input, expected),
+        ),
+        Check::new("tag-vectors", tag_vectors()),
+        Check::new("spec-reference", spec_reference(permutation)),
+        Check::new("determinism", determinism(permutation)),
+        Check::new("domain-separation", domain_separation(permutation)),
+        Check::new("input-sensitivity", input_sensitivity(permutation)),
+    ]
+}
+
+fn permutation_kat<P: Permutation<Field = Fr>>(
+    permutation: &P,
+    input: [u64; 4],
+    expected: &[&str],
+) -> Result<(), String> {
+    let mut state = input.map(Fr::from).to_vec();
+    permutation.permute(&mut state);
+    for (index, (element, expected)) in state.iter().zip(expected).enumerate() {
+        let expected = display::parse_element::<Fr>(expected).expect("valid known answer");
+        if *element != expected {
+            return Err(format!(
+                "element {} is {}, expected {}",
+                index,
+                display::format_element(element),
+                display::format_element(&expected)
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn tag_vectors() -> Result<(), String> {
+    vectors::verify_json(TAG_VECTORS)
+        .map(|_| ())
+        .map_err(|error| error.to_string())
+}
+
+/// Patterns of the property checks, with multi-permutation absorbs and squeezes.
+fn patterns() -> Vec<IoPattern> {
+    vec![
+        IoPattern::new().absorb(2).squeeze(1),
+        IoPattern::new().absorb(3).squeeze(1),
+        IoPattern::new().absorb(7).squeeze(5),
+        IoPattern::new()
+            .absorb(1)
+            .squeeze(1)
+            .absorb(4)
+            .squeeze(2)
+            .squeeze(3),
+    ]
+}
+
+/// Distinct inputs for the ABSORBs of `pattern`, from `seed`.
+fn inputs(pattern: &IoPattern, seed: u64) -> Vec<Vec<Fr>> {
+    let mut next = seed * 1000;
+    pattern
+        .operations()
+        .iter()
+        .filter(|operation| operation.is_absorb())
+        .map(|operation| {
+            (0..operation.len())
+                .map(|_| {
+                    next += 1;
+                    Fr::from(next)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Runs the sponge on `pattern` and returns everything it squeezes.
+fn run<P: Permutation<Field = Fr> + Clone>(
+    permutation: &P,
+    pattern: &IoPattern,
+    domain_separator: &[u8; 64],
+    inputs: &[Vec<Fr>],
+) -> Vec<Fr> {
+    let mut sponge =
+        Sponge::with_permutation(permutation.clone(), pattern.clone(), domain_separator);
+    let mut inputs = inputs.iter();
+    let mut output = Vec::new();
+    for operation in pattern.operations() {
+        match operation.is_absorb() {
+            true => sponge.absorb(inputs.next().expect("one input per ABSORB")),
+            false => output.extend(sponge.squeeze()),
+        }
+    }
+    sponge.finish();
+    output
+}
+
+/// Spec 2.4, transcribed without the optimizations of [`Sponge`].
+fn reference<P: Permutation<Field = Fr>>(
+    permutation: &P,
+    pattern: &IoPattern,
+    domain_separator: &[u8; 64],
+    inputs: &[Vec<Fr>],
+) -> Vec<Fr> {
+    let mut state = vec![Fr::zero(); P::WIDTH];
+    state[0] = pattern.tag_field::<Fr>(domain_separator);
+    let (mut absorb_pos, mut squeeze_pos) = (0, 0);
+    let mut inputs = inputs.iter();
+    let mut output = Vec::new();
+    for operation in pattern.operations() {
+        if operation.is_absorb() {
+            for &element in inputs.next().expect("one input per ABSORB") {
+                if absorb_pos == P::RATE {
+                    permutation.permute(&mut state);
+                    absorb_pos = 0;
+                }
+                state[absorb_pos + P::CAPACITY] += element;
+                absorb_pos += 1;
+            }
+            squeeze_pos = P::RATE;
+        } else {
+            for _ in 0..operation.len() {
+                if squeeze_pos == P::RATE {
+                    permutation.permute(&mut state);
+                    squeeze_pos = 0;
+                    absorb_pos = 0;
+                }
+                output.push(state[squeeze_pos + P::CAPACITY]);
+                squeeze_pos += 1;
+            }
+        }
+    }
+    output
+}
+
+fn domains() -> [[u8; 64]; 2] {
+    [
+        padded_domain_separator(b"SAFE-CERTIFY"),
+        padded_domain_separator(b"SAFE-CERTIFY-2"),
+    ]
+}
+
+fn spec_reference<P: Permutation<Field = Fr> + Clone>(permutation: &P) -> Result<(), String> {
+    for pattern in patterns() {
+        for domain_separator in domains() {
+            let inputs = inputs(&pattern, 1);
+            if run(permutation, &pattern, &domain_separator, &inputs)
+                != reference(permutation, &pattern, &domain_separator, &inputs)
+            {
+                return Err(format!("the outputs of `{}` differ from spec 2.4", pattern));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn determinism<P: Permutation<Field = Fr> + Clone>(permutation: &P) -> Result<(), String> {
+    let [domain_separator, _] = domains();
+    for pattern in patterns() {
+        let inputs = inputs(&pattern, 2);
+        if run(permutation, &pattern, &domain_separator, &inputs)
+            != run(permutation, &pattern, &domain_separator, &inputs)
+        {
+            return Err(format!("two runs of `{}` differ", pattern));
+        }
+    }
+    Ok(())
+}
+
+fn domain_separation<P: Permutation<Field = Fr> + Clone>(permutation: &P) -> Result<(), String> {
+    let [first, second] = domains();
+    for pattern in patterns() {
+        let inputs = inputs(&pattern, 3);
+        if run(permutation, &pattern, &first, &inputs)
+            == run(permutation, &pattern, &second, &inputs)
+        {
+            return Err(format!(
+                "`{}` gives the same outputs under two domain separators",
+                pattern
+            ));
+        }
+    }
+    // Patterns with the same inputs but different squeezes.
+    let inputs = [vec![Fr::from(1u64), Fr::from(2u64), Fr::from(3u64)]];
+    let one = run(
+        permutation,
+        &IoPattern::new().absorb(3).squeeze(1),
+        &first,
+        &inputs,
+    );
+    let two = run(
+        permutation,
+        &IoPattern::new().absorb(3).squeeze(2),
+        &first,
+        &inputs,
+    );
+    if one[0] == two[0] {
+        return Err("`A3 S1` and `A3 S2` squeeze the same first element".to_string());
+    }
+    Ok(())
+}
+
+fn input_sensitivity<P: Permutation<Field = Fr> + Clone>(permutation: &P) -> Result<(), String> {
+    let [domain_separator, _] = domains();
+    for pattern in patterns() {
+        let inputs = inputs(&pattern, 4);
+        let output = run(permutation, &pattern, &domain_separator, &inputs);
+        for operation in 0..inputs.len() {
+            for element in 0..inputs[operation].len() {
+                let mut changed = inputs.clone();
+                changed[operation][element] += Fr::from(1u64);
+                if run(permutation, &pattern, &domain_separator, &changed) == output {
+                    return Err(format!(
+                        "`{}` ignores element {} of ABSORB {}",
+                        pattern, element, operation
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signature, Verifier};
+    use safe_api::poseidon2::Poseidon2;
+
+    #[test]
+    fn test_backends_pass() {
+        for backend in backends() {
+            let report = Report::run(backend).unwrap();
+            assert!(report.passed, "{}: {:?}", backend, report.checks);
+            assert_eq!(report.checks.len(), 6);
+        }
+    }
+
+    #[test]
+    fn test_failures_are_reported() {
+        let check = Check::new(
+            "permutation-kat",
+            permutation_kat(&Poseidon2, [0, 0, 0, 1], &["0x01"]),
+        );
+        assert!(!check.passed);
+        assert!(check.failure.unwrap().starts_with("element 0 is 0x"));
+        assert_eq!(
+            Report::run("sha256").unwrap_err(),
+            format!(
+                "unknown backend `sha256` (available: {})",
+                backends().join(", ")
+            )
+        );
+    }
+
+    #[test]
+    fn test_signature() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let certificate = Certificate::sign(Report::run("poseidon2-bn254").unwrap(), &key);
+        let parsed: Certificate = serde_json::from_str(&certificate.render()).unwrap();
+        assert_eq!(parsed, certificate);
+
+        let signature: [u8; 64] = display::parse_bytes(&certificate.signature)
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let signature = Signature::from_bytes(&signature);
+        let verifying_key = key.verifying_key();
+        assert!(verifying_key
+            .verify(&certificate.report.signed_message(), &signature)
+            .is_ok());
+        let mut tampered = parsed.report;
+        tampered.passed = false;
+        assert!(verifying_key
+            .verify(&tampered.signed_message(), &signature)
+            .is_err());
+    }
+}
@@ -0,0 +1,298 @@
+//! The protocol changelog (`protocols-changelog.json`), a tamper-evident history of the
+//! manifest protocols.
+//!
+//! `safe-api codegen ... --changelog protocols-changelog.json` appends an entry whenever the
+//! generated protocols differ from those the changelog leads to: the protocols added, changed
+//! or removed with their old and new tags, and the SHA-256 of the manifest they were generated
+//! from. Each entry hashes the previous one, so that rewriting history changes every later
+//! hash, and `safe-api changelog` verifies the chain:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "entries": [
+//!     {
+//!       "timestamp": 1760000000,
+//!       "manifest_hash": "0x9c0f...",
+//!       "previous": "0x0000...",
+//!       "changes": [
+//!         {
+//!           "change": "added",
+//!           "new": { "name": "merkle_node", "pattern": "A1 A1 S1", "domain": "0x41424344",
+//!                    "tag": "0x08e2da1eb5257f918e9c15b5605a3516" }
+//!         }
+//!       ],
+//!       "hash": "0x5e1b..."
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! The hash of an entry is the SHA-256 of its JSON without the `hash` field, in the order
+//! above; `previous` of the first entry is zero.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use safe_api::display;
+
+use crate::lockfile::{LockedProtocol, Lockfile};
+
+const VERSION: u32 = 1;
+
+/// The history of the manifest protocols.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Changelog {
+    pub version: u32,
+    pub entries: Vec<ChangelogEntry>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChangelogEntry {
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// SHA-256 of the manifest file.
+    pub manifest_hash: String,
+    /// Hash of the previous entry.
+    pub previous: String,
+    pub changes: Vec<Change>,
+    /// Hash of this entry.
+    pub hash: String,
+}
+
+/// A protocol added, changed or removed: `old` is absent for additions and `new` for removals.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Change {
+    pub change: ChangeKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old: Option<LockedProtocol>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new: Option<LockedProtocol>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Changed,
+    Removed,
+}
+
+/// The fields of an entry covered by its hash.
+#[derive(Serialize)]
+struct HashedEntry<'a> {
+    timestamp: u64,
+    manifest_hash: &'a str,
+    previous: &'a str,
+    changes: &'a [Change],
+}
+
+impl ChangelogEntry {
+    fn compute_hash(&self) -> String {
+        let hashed = HashedEntry {
+            timestamp: self.timestamp,
+            manifest_hash: &self.manifest_hash,
+            previous: &self.previous,
+            changes: &self.changes,
+        };
+        let json = serde_json::to_string(&hashed).expect("entries always serialize");
+        display::format_bytes(&Sha256::digest(json))
+    }
+}
+
+impl Default for Changelog {
+    fn default() -> Self {
+        Changelog {
+            version: VERSION,
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl Changelog {
+    /// Reads and parses a changelog, empty if the file does not exist yet.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(Changelog::default());
+        }
+        let contents = std::fs::read_to_string(path)
+            .map_err(|error| format!("cannot read {}: {}", path.display(), error))?;
+        Self::parse(&contents).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    /// Parses the contents of a changelog.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let changelog: Changelog =
+            serde_json::from_str(contents).map_err(|error| error.to_string())?;
+        if changelog.version != VERSION {
+            return Err(format!(
+                "unsupported changelog version {}",
+                changelog.version
+            ));
+        }
+        Ok(changelog)
+    }
+
+    /// Renders the changelog.
+    pub fn render(&self) -> String {
+        let mut json = serde_json::to_string_pretty(self).expect("changelogs always serialize");
+        json.push('\n');
+        json
+    }
+
+    /// Replays the entries into the protocols they lead to, in order of addition.
+    pub fn protocols(&self) -> Lockfile {
+        let mut protocols: Vec<LockedProtocol> = Vec::new();
+        for change in self.entries.iter().flat_map(|entry| &entry.changes) {
+            let name = match (&change.old, &change.new) {
+                (Some(protocol), _) | (None, Some(protocol)) => &protocol.name,
+                (None, None) => continue,
+            };
+            let position = protocols.iter().position(|protocol| &protocol.name == name);
+            match (position, &change.new) {
+                (Some(position), Some(new)) => protocols[position] = new.clone(),
+                (Some(position), None) => {
+                    protocols.remove(position);
+                }
+                (None, Some(new)) => protocols.push(new.clone()),
+                (None, None) => {}
+            }
+        }
+        Lockfile { protocols }
+    }
+
+    /// Appends an entry for the differences between the protocols of the changelog and
+    /// `current`, generated from a manifest of SHA-256 `manifest_hash`. Returns `false`, and
+    /// appends nothing, if there are none.
+    pub fn record(&mut self, current: &Lockfile, manifest_hash: &[u8; 32], timestamp: u64) -> bool {
+        let previous = self.protocols();
+        let diff = previous.diff(current);
+        if diff.is_empty() {
+            return false;
+        }
+
+        let added = diff.added.iter().map(|&new| Change {
+            change: ChangeKind::Added,
+            old: None,
+            new: Some(new.clone()),
+        });
+        let changed = diff.changed.iter().map(|&(old, new)| Change {
+            change: ChangeKind::Changed,
+            old: Some(old.clone()),
+            new: Some(new.clone()),
+        });
+        let removed = diff.removed.iter().map(|&old| Change {
+            change: ChangeKind::Removed,
+            old: Some(old.clone()),
+            new: None,
+        });
+        let mut entry = ChangelogEntry {
+            timestamp,
+            manifest_hash: display::format_bytes(manifest_hash),
+            previous: self.last_hash(),
+            changes: added.chain(changed).chain(removed).collect(),
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+        self.entries.push(entry);
+        true
+    }
+
+    /// Checks the hash chain, reporting the first entry that was altered.
+    pub fn verify(&self) -> Result<(), String> {
+        let mut previous = display::format_bytes(&[0; 32]);
+        for (index, entry) in self.entries.iter().enumerate() {
+            if entry.previous != previous {
+                return Err(format!(
+                    "entry {} does not follow the previous entry: the history was rewritten",
+                    index
+                ));
+            }
+            if entry.hash != entry.compute_hash() {
+                return Err(format!("entry {} was altered after it was recorded", index));
+            }
+            previous.clone_from(&entry.hash);
+        }
+        Ok(())
+    }
+
+    fn last_hash(&self) -> String {
+        match self.entries.last() {
+            Some(entry) => entry.hash.clone(),
+            None => display::format_bytes(&[0; 32]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::Manifest;
+
+    fn lock(entries: &[(&str, &str)]) -> Lockfile {
+        let manifest: String = entries
+            .iter()
+            .map(|(name, pattern)| {
+                format!(
+                    "[[protocol]]\nname = \"{}\"\npattern = \"{}\"\ndomain = \"0x41424344\"\n",
+                    name, pattern
+                )
+            })
+            .collect();
+        Lockfile::from_manifest(&Manifest::parse(&manifest).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_records_history() {
+        let mut changelog = Changelog::default();
+        let first = lock(&[("kept", "A3 S1"), ("dropped", "A1 S1"), ("moved", "A3 S1")]);
+        assert!(changelog.record(&first, &[1; 32], 10));
+        assert!(!changelog.record(&first, &[1; 32], 20));
+        assert_eq!(changelog.protocols(), first);
+
+        let second = lock(&[("kept", "A3 S1"), ("moved", "A3 S2"), ("new", "A1 S1")]);
+        assert!(changelog.record(&second, &[2; 32], 30));
+        assert_eq!(changelog.entries.len(), 2);
+        let kinds: Vec<ChangeKind> = changelog.entries[1]
+            .changes
+            .iter()
+            .map(|change| change.change)
+            .collect();
+        assert_eq!(
+            kinds,
+            [ChangeKind::Added, ChangeKind::Changed, ChangeKind::Removed]
+        );
+        // Replaying the changelog gives the last protocols.
+        assert!(changelog.protocols().diff(&second).is_empty());
+
+        assert_eq!(Changelog::parse(&changelog.render()).unwrap(), changelog);
+        assert_eq!(changelog.verify(), Ok(()));
+    }
+
+    #[test]
+    fn test_detects_tampering() {
+        let mut changelog = Changelog::default();
+        changelog.record(&lock(&[("hash", "A3 S1")]), &[1; 32], 10);
+        changelog.record(&lock(&[("hash", "A3 S2")]), &[2; 32], 20);
+
+        let mut altered = Changelog::parse(&changelog.render()).unwrap();
+        altered.entries[0].changes[0].new.as_mut().unwrap().tag =
+            "0x00000000000000000000000000000000".to_string();
+        assert_eq!(
+            altered.verify(),
+            Err("entry 0 was altered after it was recorded".to_string())
+        );
+
+        let mut rewritten = Changelog::parse(&changelog.render()).unwrap();
+        rewritten.entries.remove(0);
+        assert!(rewritten
+            .verify()
+            .unwrap_err()
+            .starts_with("entry 0 does not follow"));
+    }
+}
@@ -0,0 +1,373 @@
+//! Checksummed chunked commitments to large data, with proofs for single chunks.
+//!
+//! The data is split into chunks of `chunk_size` bytes, the last one zero padded, then:
+//!
+//! 1. each chunk is hashed into a leaf with the pattern of [`leaf_pattern`]: its bytes packed
+//!    as in [`encode_bytes`], then SQUEEZE(1),
+//! 2. the leaves are hashed into a [`MerkleTree`] root,
+//! 3. the commitment is ABSORB(3) SQUEEZE(1) over `(root, byte_len, chunk_size)`, so the layout
+//!    of the data is bound as well as its content.
+//!
+//! Each step has its own domain separator. A circuit verifies a sub-range of the data by
+//! checking the [`ChunkProof`] of each chunk covering it, with the gadget emitted by
+//! [`noir_chunk_verifier`](crate::codegen::noir_chunk_verifier).
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::ops::Range;
+
+use ark_ff::PrimeField;
+
+use crate::encoding::encode_bytes;
+use crate::io_pattern::IoPattern;
+use crate::merkle::{self, MerkleProof, MerkleTree};
+use crate::permutation::Permutation;
+use crate::sponge::Sponge;
+
+/// Label of the domain separator of the chunk leaves.
+pub const LEAF_LABEL: &[u8] = b"safe-api/chunked/v1/leaf";
+
+/// Label of the domain separator of the Merkle nodes.
+pub const NODE_LABEL: &[u8] = b"safe-api/chunked/v1/node";
+
+/// Label of the domain separator of the final commitment.
+pub const ROOT_LABEL: &[u8] = b"safe-api/chunked/v1/root";
+
+/// Returns `label` zero padded to a domain separator.
+pub fn domain_separator(label: &[u8]) -> [u8; 64] {
+    let mut domain_separator = [0u8; 64];
+    domain_separator[..label.len()].copy_from_slice(label);
+    domain_separator
+}
+
+/// IO pattern of a chunk leaf: the packed chunk of `chunk_size` bytes, then the leaf.
+pub fn leaf_pattern<F: PrimeField>(chunk_size: usize) -> IoPattern {
+    IoPattern::new().absorb_bytes::<F>(chunk_size).squeeze(1)
+}
+
+/// IO pattern of a Merkle node: both children, then the node, as in [`merkle`](crate::merkle).
+pub fn node_pattern() -> IoPattern {
+    merkle::node_pattern()
+}
+
+/// IO pattern of the commitment: the root, the length and the chunk size, then the commitment.
+pub fn root_pattern() -> IoPattern {
+    IoPattern::new().absorb(3).squeeze(1)
+}
+
+/// Returns the depth of the tree over `byte_len` bytes split into `chunk_size`-byte chunks.
+pub fn tree_depth(chunk_size: usize, byte_len: u64) -> u32 {
+    chunk_count(chunk_size, byte_len)
+        .next_power_of_two()
+        .trailing_zeros()
+}
+
+fn chunk_count(chunk_size: usize, byte_len: u64) -> usize {
+    byte_len.div_ceil(chunk_size as u64) as usize
+}
+
+fn hash_leaf<P: Permutation + Default>(chunk_size: usize, chunk: &[u8]) -> P::Field {
+    let mut padded = vec![0u8; chunk_size];
+    padded[..chunk.len()].copy_from_slice(chunk);
+    let mut sponge = Sponge::<P>::start(
+        leaf_pattern::<P::Field>(chunk_size),
+        domain_separator(LEAF_LABEL),
+    );
+    sponge.absorb(&encode_bytes::<P::Field>(&padded));
+    let leaf = sponge.squeeze()[0];
+    sponge.finish();
+    leaf
+}
+
+fn hash_root<P: Permutation + Default>(
+    root: P::Field,
+    chunk_size: usize,
+    byte_len: u64,
+) -> P::Field {
+    let mut sponge = Sponge::<P>::start(root_pattern(), domain_separator(ROOT_LABEL));
+    sponge.absorb(&[
+        root,
+        P::Field::from(byte_len),
+        P::Field::from(chunk_size as u64),
+    ]);
+    let commitment = sponge.squeeze()[0];
+    sponge.finish();
+    commitment
+}
+
+/// Builds a [`ChunkedCommitment`] from data fed in pieces of any size.
+///
+/// Only the pending partial chunk and one leaf per chunk are kept in memory.
+#[derive(Clone, Debug)]
+pub struct ChunkedCommitmentBuilder<P: Permutation> {
+    chunk_size: usize,
+    byte_len: u64,
+    pending: Vec<u8>,
+    leaves: Vec<P::Field>,
+    permutation: PhantomData<P>,
+    /// All the data, to hash the leaves again at the end.
+    #[cfg(feature = "shadow")]
+    shadow: Vec<u8>,
+}
+
+impl<P: Permutation + Default> ChunkedCommitmentBuilder<P> {
+    /// Starts a commitment with chunks of `chunk_size` bytes.
+    ///
+    /// # Panics
+    /// If `chunk_size` is 0.
+    pub fn new(chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "Chunk size must be positive");
+        ChunkedCommitmentBuilder {
+            chunk_size,
+            byte_len: 0,
+            pending: Vec::with_capacity(chunk_size),
+            leaves: Vec::new(),
+            permutation: PhantomData,
+            #[cfg(feature = "shadow")]
+            shadow: Vec::new(),
+        }
+    }
+
+    /// Appends `data` to the committed bytes.
+    pub fn update(&mut self, mut data: &[u8]) {
+        #[cfg(feature = "shadow")]
+        self.shadow.extend_from_slice(data);
+        self.byte_len += data.len() as u64;
+        while !data.is_empty() {
+            let length = (self.chunk_size - self.pending.len()).min(data.len());
+            self.pending.extend_from_slice(&data[..length]);
+            data = &data[length..];
+            if self.pending.len() == self.chunk_size {
+                self.leaves
+                    .push(hash_leaf::<P>(self.chunk_size, &self.pending));
+                self.pending.clear();
+            }
+        }
+    }
+
+    /// Builds the tree and the commitment.
+    pub fn finish(mut self) -> ChunkedCommitment<P> {
+        if !self.pending.is_empty() {
+            self.leaves
+                .push(hash_leaf::<P>(self.chunk_size, &self.pending));
+        }
+        #[cfg(feature = "shadow")]
+        {
+            let reference: Vec<P::Field> = self
+                .shadow
+                .chunks(self.chunk_size)
+                .map(|chunk| hash_leaf::<P>(self.chunk_size, chunk))
+                .collect();
+            crate::shadow::compare("ChunkedCommitmentBuilder", &self.leaves, &reference);
+        }
+
+        let tree = MerkleTree::new(self.leaves, &domain_separator(NODE_LABEL));
+        ChunkedCommitment {
+            chunk_size: self.chunk_size,
+            byte_len: self.byte_len,
+            commitment: hash_root::<P>(tree.root(), self.chunk_size, self.byte_len),
+            tree,
+        }
+    }
+}
+
+/// A chunked commitment with its Merkle tree, see the [module documentation](self).
+#[derive(Clone, Debug)]
+pub struct ChunkedCommitment<P: Permutation> {
+    chunk_size: usize,
+    byte_len: u64,
+    commitment: P::Field,
+    tree: MerkleTree<P>,
+}
+
+impl<P: Permutation + Default> ChunkedCommitment<P> {
+    /// Commits to `data` with chunks of `chunk_size` bytes.
+    ///
+    /// # Panics
+    /// If `chunk_size` is 0.
+    pub fn commit(data: &[u8], chunk_size: usize) -> Self {
+        let mut builder = ChunkedCommitmentBuilder::new(chunk_size);
+        builder.update(data);
+        builder.finish()
+    }
+}
+
+impl<P: Permutation> ChunkedCommitment<P> {
+    /// Returns the commitment.
+    pub fn commitment(&self) -> P::Field {
+        self.commitment
+    }
+
+    /// Returns the Merkle root over the chunks.
+    pub fn root(&self) -> P::Field {
+        self.tree.root()
+    }
+
+    /// Returns the chunk size in bytes.
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Returns the length of the committed data in bytes.
+    pub fn byte_len(&self) -> u64 {
+        self.byte_len
+    }
+
+    /// Returns the number of chunks.
+    pub fn chunk_count(&self) -> usize {
+        chunk_count(self.chunk_size, self.byte_len)
+    }
+
+    /// Returns the depth of the Merkle tree, i.e. the number of siblings of a proof.
+    pub fn depth(&self) -> u32 {
+        self.tree.depth()
+    }
+
+    /// Returns the chunks covering the byte range `bytes`.
+    ///
+    /// # Panics
+    /// If `bytes` ends after the data.
+    pub fn chunk_range(&self, bytes: Range<u64>) -> Range<usize> {
+        assert!(bytes.end <= self.byte_len, "Range out of bounds");
+        if bytes.is_empty() {
+            return 0..0;
+        }
+        let chunk_size = self.chunk_size as u64;
+        (bytes.start / chunk_size) as usize..bytes.end.div_ceil(chunk_size) as usize
+    }
+
+    /// Returns the proof that chunk `index` belongs to the commitment.
+    ///
+    /// # Panics
+    /// If there is no chunk `index`.
+    pub fn open(&self, index: usize) -> ChunkProof<P::Field> {
+        assert!(index < self.chunk_count(), "Chunk index out of bounds");
+        self.tree.open(index)
+    }
+}
+
+/// Proof that a chunk belongs to a [`ChunkedCommitment`]: the [`MerkleProof`] of its leaf.
+pub type ChunkProof<F> = MerkleProof<F>;
+
+/// Checks that `chunk` is chunk `proof.index` of data of `byte_len` bytes committed to with
+/// chunks of `chunk_size` bytes. `chunk` is given without padding, so the last chunk may be
+/// shorter than `chunk_size`.
+pub fn verify_chunk<P: Permutation + Default>(
+    commitment: P::Field,
+    chunk_size: usize,
+    byte_len: u64,
+    chunk: &[u8],
+    proof: &ChunkProof<P::Field>,
+) -> bool {
+    if chunk_size == 0 || proof.index >= chunk_count(chunk_size, byte_len) {
+        return false;
+    }
+    let offset = proof.index as u64 * chunk_size as u64;
+    let expected_len = (byte_len - offset).min(chunk_size as u64);
+    if chunk.len() as u64 != expected_len
+        || proof.siblings.len() as u32 != tree_depth(chunk_size, byte_len)
+    {
+        return false;
+    }
+
+    let leaf = hash_leaf::<P>(chunk_size, chunk);
+    let root = proof.root::<P>(&domain_separator(NODE_LABEL), leaf);
+    hash_root::<P>(root, chunk_size, byte_len) == commitment
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::poseidon2::Poseidon2;
+    use ark_ff::Zero;
+
+    fn data(length: usize) -> Vec<u8> {
+        (0..length).map(|i| (i * 13 % 256) as u8).collect()
+    }
+
+    #[test]
+    fn test_builder_matches_commit() {
+        let data = data(1000);
+        let mut builder = ChunkedCommitmentBuilder::<Poseidon2>::new(64);
+        for piece in data.chunks(37) {
+            builder.update(piece);
+        }
+        let built = builder.finish();
+        let committed = ChunkedCommitment::<Poseidon2>::commit(&data, 64);
+        assert_eq!(built.commitment(), committed.commitment());
+        assert_eq!(committed.chunk_count(), 16);
+        assert_eq!(committed.depth(), 4);
+        assert_ne!(
+            committed.commitment(),
+            ChunkedCommitment::<Poseidon2>::commit(&data, 32).commitment()
+        );
+    }
+
+    #[test]
+    fn test_open_and_verify() {
+        let data = data(300);
+        let commitment = ChunkedCommitment::<Poseidon2>::commit(&data, 64);
+        assert_eq!(commitment.chunk_count(), 5);
+        assert_eq!(commitment.depth(), 3);
+        assert_eq!(commitment.chunk_range(100..200), 1..4);
+
+        for index in commitment.chunk_range(0..300) {
+            let chunk = &data[index * 64..(index * 64 + 64).min(300)];
+            let proof = commitment.open(index);
+            assert!(verify_chunk::<Poseidon2>(
+                commitment.commitment(),
+                64,
+                300,
+                chunk,
+                &proof
+            ));
+        }
+
+        let proof = commitment.open(1);
+        let chunk = &data[64..128];
+        // Wrong chunk, wrong index, wrong layout.
+        assert!(!verify_chunk::<Poseidon2>(
+            commitment.commitment(),
+            64,
+            300,
+            &data[..64],
+            &proof
+        ));
+        let moved = ChunkProof {
+            index: 2,
+            ..proof.clone()
+        };
+        assert!(!verify_chunk::<Poseidon2>(
+            commitment.commitment(),
+            64,
+            300,
+            chunk,
+            &moved
+        ));
+        assert!(!verify_chunk::<Poseidon2>(
+            commitment.commitment(),
+            64,
+            320,
+            chunk,
+            &proof
+        ));
+    }
+
+    #[test]
+    fn test_single_and_empty() {
+        let single = ChunkedCommitment::<Poseidon2>::commit(b"abc", 64);
+        assert_eq!(single.depth(), 0);
+        assert!(verify_chunk::<Poseidon2>(
+            single.commitment(),
+            64,
+            3,
+            b"abc",
+            &single.open(0)
+        ));
+
+        let empty = ChunkedCommitment::<Poseidon2>::commit(&[], 64);
+        assert_eq!(empty.chunk_count(), 0);
+        assert!(empty.root().is_zero());
+    }
+}
@@ -0,0 +1,1196 @@
+//! Source code generation for computed tags.
+//!
+//! Circuits need the tag of each of their IO patterns as a constant. Rather than pasting hex by
+//! hand, [`noir_module`] emits the constants from the patterns themselves:
+//!
+//! ```noir
+//! /// `merkle_node`, domain separator 0x41424344 (zero padded).
+//! pub global IO_PATTERN_MERKLE_NODE: [u32; 3] = [0x80000001, 0x80000001, 0x00000001];
+//! pub global TAG_MERKLE_NODE: Field = 0x08e2da1eb5257f918e9c15b5605a3516;
+//! ```
+//!
+//! A tag is 128 bits, as in the spec, unless the constant declares another
+//! [`TagLength`](TagConstant::with_tag_length), e.g. the whole 256-bit digest for a
+//! byte-oriented sponge, which has no field to fit the tag in. Every target emits the tag in a
+//! type of its length: a `Field` up to 248 bits in Noir, and `[u8; 32]` for the whole digest.
+//!
+//! The labeled squeezes of a pattern, such as `S1:alpha` in `A3 S1:alpha S2:beta`, are the
+//! challenges of the protocol. The Noir, [`rust`] and [`typescript`] targets emit a struct of
+//! the challenges with one field per label, in squeeze order, and one accessor squeezing each,
+//! e.g. `squeeze_merkle_node_alpha` in Noir, so that code refers to challenges by name and a
+//! renamed, removed or resized challenge fails to compile. An accessor fails unless its
+//! challenge is the next operation of the sponge, and one function, e.g. `merkle_node_challenges`
+//! in Noir, squeezes them all into the struct, taking the inputs of the ABSORBs between them.
+//!
+//! Artifacts of several applications can live in one repository: [`noir_module_in`] and
+//! [`prover_toml_in`] prefix every emitted identifier with a [`Namespace`] such as
+//! `acme/wallet`, giving `ACME_WALLET_TAG_MERKLE_NODE` and `acme_wallet_tag_merkle_node`.
+//!
+//! Each output language is a [`CodegenTarget`], looked up by name in a [`Targets`] registry
+//! (`safe-api codegen build --target <name>`): Noir, `Prover.toml`, [`cairo`], [`go`],
+//! [`move_lang`] (Aptos and Sui), [`rust`], [`solidity`] and [`typescript`] are built in. Other crates add
+//! languages by implementing the trait and registering the implementation, then running their
+//! registry on the constants of their manifest:
+//!
+//! ```
+//! use safe_api::codegen::{CodegenError, CodegenTarget, Namespace, TagConstant, Targets};
+//! use safe_api::display::format_tag;
+//! use safe_api::IoPattern;
+//!
+//! struct Python;
+//!
+//! impl CodegenTarget for Python {
+//!     fn name(&self) -> &str {
+//!         "python"
+//!     }
+//!
+//!     fn extension(&self) -> &str {
+//!         "py"
+//!     }
+//!
+//!     fn emit(
+//!         &self,
+//!         namespace: &Namespace,
+//!         constants: &[TagConstant],
+//!     ) -> Result<String, CodegenError> {
+//!         Ok(constants
+//!             .iter()
+//!             .map(|constant| {
+//!                 let name = namespace.prefix_upper(&format!("tag_{}", constant.name()));
+//!                 format!("{} = {}\n", name, format_tag(constant.tag()))
+//!             })
+//!             .collect())
+//!     }
+//! }
+//!
+//! let mut targets = Targets::builtin();
+//! targets.register(Box::new(Python))?;
+//! let constant = TagConstant::new("node", IoPattern::new().absorb(2).squeeze(1), [1u8; 64])?;
+//! let source = targets.emit("python", &Namespace::default(), &[constant])?;
+//! assert!(source.starts_with("TAG_NODE = 0x"));
+//! # Ok::<(), CodegenError>(())
+//! ```
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use ark_bn254::Fr;
+
+use crate::chunked;
+use crate::display::format_tag;
+use crate::domain::DomainSeparator;
+use crate::encoding::bytes_per_element;
+use crate::io_pattern::{IoPattern, Operation};
+use crate::tag::{Tag, TagLength};
+
+pub mod cairo;
+pub mod go;
+pub mod move_lang;
+pub mod rust;
+pub mod solidity;
+pub mod typescript;
+
+pub use cairo::CairoTarget;
+pub use go::GoTarget;
+pub use move_lang::{MoveDialect, MoveTarget};
+pub use rust::RustTarget;
+pub use solidity::SolidityTarget;
+pub use typescript::TypeScriptTarget;
+
+/// Errors raised when declaring tag constants.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CodegenError {
+    /// The name is not a lowercase identifier (`[a-z][a-z0-9_]*`).
+    InvalidName(String),
+    /// The label of a squeeze is not a lowercase identifier.
+    InvalidChallenge(String),
+    /// Two constants share a name.
+    DuplicateName(String),
+    /// A namespace segment is not a lowercase identifier.
+    InvalidNamespace(String),
+    /// No target of that name is registered.
+    UnknownTarget(String),
+    /// Two targets share a name.
+    DuplicateTarget(String),
+}
+
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodegenError::InvalidName(name) => write!(
+                f,
+                "`{}` is not a valid name (expected lowercase letters, digits and `_`)",
+                name
+            ),
+            CodegenError::InvalidChallenge(label) => write!(
+                f,
+                "`{}` is not a valid challenge name (expected lowercase letters, digits and `_`)",
+                label
+            ),
+            CodegenError::DuplicateName(name) => write!(f, "`{}` is declared twice", name),
+            CodegenError::InvalidNamespace(namespace) => write!(
+                f,
+                "`{}` is not a valid namespace (expected lowercase identifiers separated by `/`)",
+                namespace
+            ),
+            CodegenError::UnknownTarget(name) => write!(f, "unknown target `{}`", name),
+            CodegenError::DuplicateTarget(name) => {
+                write!(f, "target `{}` is registered twice", name)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CodegenError {}
+
+/// Prefix of the emitted identifiers, e.g. an organization and an application.
+///
+/// The default namespace is empty and leaves the identifiers as they are.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Namespace {
+    segments: Vec<String>,
+}
+
+impl Namespace {
+    /// Parses a namespace of lowercase identifiers separated by `/`, such as `acme/wallet`.
+    pub fn new(namespace: &str) -> Result<Self, CodegenError> {
+        let segments: Vec<String> = namespace.split('/').map(String::from).collect();
+        if !segments.iter().all(|segment| is_identifier(segment)) {
+            return Err(CodegenError::InvalidNamespace(namespace.to_string()));
+        }
+        Ok(Namespace { segments })
+    }
+
+    /// Returns the segments joined with `_`, such as `acme_wallet`, e.g. for module names.
+    pub fn snake_case(&self) -> String {
+        self.segments.join("_")
+    }
+
+    /// Returns the segments in `UpperCamelCase`, such as `AcmeWallet`, e.g. for type names.
+    pub fn camel_case(&self) -> String {
+        self.segments
+            .iter()
+            .map(|segment| camel_case(segment))
+            .collect()
+    }
+
+    /// Prefixes `identifier` with the namespace and `_`, in lowercase.
+    pub fn prefix(&self, identifier: &str) -> String {
+        match self.segments.is_empty() {
+            true => identifier.to_string(),
+            false => format!("{}_{}", self.snake_case(), identifier),
+        }
+    }
+
+    /// Prefixes `identifier` with the namespace and `_`, in uppercase.
+    pub fn prefix_upper(&self, identifier: &str) -> String {
+        self.prefix(identifier).to_uppercase()
+    }
+}
+
+/// A named IO pattern and domain separator whose tag is emitted as a constant.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TagConstant {
+    name: String,
+    io_pattern: IoPattern,
+    domain_separator: [u8; 64],
+    tag_length: TagLength,
+}
+
+impl TagConstant {
+    /// Declares the constant `name` (a lowercase identifier such as `merkle_node`). The labels
+    /// of the squeezes of `io_pattern`, its challenges, must be lowercase identifiers too.
+    pub fn new(
+        name: &str,
+        io_pattern: IoPattern,
+        domain_separator: [u8; 64],
+    ) -> Result<Self, CodegenError> {
+        if !is_identifier(name) {
+            return Err(CodegenError::InvalidName(name.to_string()));
+        }
+        if let Some((label, _)) = io_pattern
+            .squeeze_labels()
+            .into_iter()
+            .find(|(label, _)| !is_identifier(label))
+        {
+            return Err(CodegenError::InvalidChallenge(label.to_string()));
+        }
+
+        Ok(TagConstant {
+            name: name.to_string(),
+            io_pattern,
+            domain_separator,
+            tag_length: TagLength::default(),
+        })
+    }
+
+    /// Gives the tag `length` bits instead of the 128 of the spec.
+    pub fn with_tag_length(mut self, length: TagLength) -> Self {
+        self.tag_length = length;
+        self
+    }
+
+    /// Returns the name of the constant.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the IO pattern.
+    pub fn io_pattern(&self) -> &IoPattern {
+        &self.io_pattern
+    }
+
+    /// Returns the domain separator.
+    pub fn domain_separator(&self) -> &[u8; 64] {
+        &self.domain_separator
+    }
+
+    /// Returns the length of the tag.
+    pub fn tag_length(&self) -> TagLength {
+        self.tag_length
+    }
+
+    /// Returns the challenges, the labeled squeezes of the pattern, as `(label, length)` pairs
+    /// in squeeze order.
+    pub fn challenges(&self) -> Vec<(&str, u32)> {
+        self.io_pattern.squeeze_labels()
+    }
+
+    /// Returns the operations from the first challenge to the last, the ABSORBs between them
+    /// named by their label or `absorb_<index>`, or `None` if there is no challenge or an
+    /// unlabeled SQUEEZE lies between two, whose output the struct of the challenges has no
+    /// field for.
+    pub(crate) fn challenge_steps(&self) -> Option<Vec<ChallengeStep<'_>>> {
+        let pattern = &self.io_pattern;
+        let operations = pattern.operations();
+        let challenge =
+            |index: &usize| !operations[*index].is_absorb() && pattern.label(*index).is_some();
+        let first = (0..pattern.len()).find(challenge)?;
+        let last = (0..pattern.len()).rfind(challenge)?;
+        (first..=last)
+            .map(|index| match (operations[index], pattern.label(index)) {
+                (Operation::Squeeze(_), label) => label.map(ChallengeStep::Squeeze),
+                (Operation::Absorb(length), Some(label))
+                    if is_identifier(label) && label != "sponge" =>
+                {
+                    Some(ChallengeStep::Absorb(label.to_string(), length))
+                }
+                (Operation::Absorb(length), _) => {
+                    Some(ChallengeStep::Absorb(format!("absorb_{}", index), length))
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the tag of the pattern under the domain separator, of the length of the
+    /// constant.
+    pub fn tag(&self) -> Tag {
+        self.io_pattern
+            .tag_with_length(self.domain_separator, self.tag_length)
+    }
+
+    /// Returns the domain separator as hex, without its zero padding.
+    fn domain_hex(&self) -> String {
+        DomainSeparator::new(self.domain_separator).to_string()
+    }
+}
+
+/// An operation of [`TagConstant::challenge_steps`].
+pub(crate) enum ChallengeStep<'a> {
+    /// The SQUEEZE of the challenge of this label.
+    Squeeze(&'a str),
+    /// The ABSORB of the argument of this name and length.
+    Absorb(String, u32),
+}
+
+/// Emits a Noir source file declaring, for each constant, its IO pattern as
+/// `pub global IO_PATTERN_<NAME>: [u32; L]` and its tag as `pub global TAG_<NAME>: Field`, or
+/// `[u8; 32]` for the whole digest.
+///
+/// A constant with challenges also gets a `<Name>Challenges` struct with a `[Field; N]` field
+/// per challenge, in squeeze order, a `squeeze_<name>_<label>` function per challenge,
+/// squeezing it from a `SafeSponge` of the pattern whose next operation it is, and a
+/// `<name>_challenges` function squeezing them all into the struct.
+pub fn noir_module(constants: &[TagConstant]) -> Result<String, CodegenError> {
+    noir_module_in(&Namespace::default(), constants)
+}
+
+/// Emits the source of [`noir_module`] with the globals prefixed by `namespace`, as
+/// `<NAMESPACE>_IO_PATTERN_<NAME>` and `<NAMESPACE>_TAG_<NAME>`, the structs as
+/// `<Namespace><Name>Challenges` and the functions as `<namespace>_squeeze_<name>_<label>` and
+/// `<namespace>_<name>_challenges`.
+pub fn noir_module_in(
+    namespace: &Namespace,
+    constants: &[TagConstant],
+) -> Result<String, CodegenError> {
+    check_unique(constants)?;
+
+    let mut source = String::from("// Generated by `safe-api codegen noir`. Do not edit.\n");
+    if constants
+        .iter()
+        .any(|constant| !constant.challenges().is_empty())
+    {
+        source.push_str("\nuse dep::safe::safe::SafeSponge;\n");
+    }
+    for constant in constants {
+        let words: Vec<String> = constant
+            .io_pattern
+            .encoded_words()
+            .iter()
+            .map(|word| format!("0x{:08x}", word))
+            .collect();
+
+        source.push('\n');
+        source.push_str(&format!(
+            "/// `{}`, domain separator {} (zero padded).\n",
+            constant.name,
+            constant.domain_hex()
+        ));
+        source.push_str(&format!(
+            "pub global {}: [u32; {}] = [{}];\n",
+            namespace.prefix_upper(&format!("io_pattern_{}", constant.name)),
+            words.len(),
+            words.join(", ")
+        ));
+        let tag = constant.tag();
+        let (ty, value) = match constant.tag_length {
+            TagLength::Full => (
+                String::from("[u8; 32]"),
+                format!("[{}]", byte_list(tag.as_bytes())),
+            ),
+            _ => (String::from("Field"), format_tag(tag)),
+        };
+        source.push_str(&format!(
+            "pub global {}: {} = {};\n",
+            namespace.prefix_upper(&format!("tag_{}", constant.name)),
+            ty,
+            value
+        ));
+        noir_challenges(&mut source, namespace, constant, words.len());
+    }
+    Ok(source)
+}
+
+/// Appends the challenge struct and functions of `constant`, whose pattern has
+/// `pattern_len` operations, to the Noir `source`.
+fn noir_challenges(
+    source: &mut String,
+    namespace: &Namespace,
+    constant: &TagConstant,
+    pattern_len: usize,
+) {
+    let challenges = constant.challenges();
+    if challenges.is_empty() {
+        return;
+    }
+    let challenges_type = format!(
+        "{}{}Challenges",
+        namespace.camel_case(),
+        camel_case(&constant.name)
+    );
+    let accessor = |label: &str| namespace.prefix(&format!("squeeze_{}_{}", constant.name, label));
+    source.push_str(&format!(
+        "\n/// The challenges of `{}`, in squeeze order.\npub struct {} {{\n",
+        constant.name, challenges_type
+    ));
+    for (label, length) in &challenges {
+        source.push_str(&format!("    pub {}: [Field; {}],\n", label, length));
+    }
+    source.push_str("}\n");
+    for (label, length) in &challenges {
+        source.push_str(&format!(
+            "\n/// Squeezes `{}`, the SQUEEZE({}) of `{}`, which must be the next operation.\n\
+             pub fn {}(sponge: &mut SafeSponge<{}>) -> [Field; {}] {{\n\
+             \x20   assert(sponge.operations_done() == {}, \"the next operation is not `{}`\");\n\
+             \x20   let squeezed = sponge.squeeze();\n\
+             \x20   let mut challenge = [0; {}];\n\
+             \x20   for i in 0..{} {{\n\
+             \x20       challenge[i] = squeezed.get(i);\n\
+             \x20   }}\n\
+             \x20   challenge\n\
+             }}\n",
+            label,
+            length,
+            constant.name,
+            accessor(label),
+            pattern_len,
+            length,
+            constant
+                .io_pattern
+                .squeeze_index(label)
+                .expect("a challenge"),
+            label,
+            length,
+            length
+        ));
+    }
+
+    let Some(steps) = constant.challenge_steps() else {
+        return;
+    };
+    let mut arguments = format!("sponge: &mut SafeSponge<{}>", pattern_len);
+    let mut body = String::new();
+    for step in &steps {
+        match step {
+            ChallengeStep::Squeeze(label) => body.push_str(&format!(
+                "    let {} = {}(sponge);\n",
+                label,
+                accessor(label)
+            )),
+            ChallengeStep::Absorb(name, length) => {
+                arguments.push_str(&format!(", {}: [Field; {}]", name, length));
+                body.push_str(&format!("    sponge.absorb({});\n", name));
+            }
+        }
+    }
+    let labels: Vec<&str> = challenges.iter().map(|(label, _)| *label).collect();
+    source.push_str(&format!(
+        "\n/// Squeezes the challenges of `{}` in order, absorbing the inputs between them, from a\n\
+         /// sponge whose next operation is `{}`.\n\
+         pub fn {}({}) -> {} {{\n\
+         {}\
+         \x20   {} {{ {} }}\n\
+         }}\n",
+        constant.name,
+        labels[0],
+        namespace.prefix(&format!("{}_challenges", constant.name)),
+        arguments,
+        challenges_type,
+        body,
+        challenges_type,
+        labels.join(", ")
+    ));
+}
+
+/// Emits a `Prover.toml` fragment assigning each expected tag to the input `tag_<name>`, a
+/// field element, or its bytes for the whole digest.
+pub fn prover_toml(constants: &[TagConstant]) -> Result<String, CodegenError> {
+    prover_toml_in(&Namespace::default(), constants)
+}
+
+/// Emits the fragment of [`prover_toml`] with the inputs prefixed by `namespace`, as
+/// `<namespace>_tag_<name>`.
+pub fn prover_toml_in(
+    namespace: &Namespace,
+    constants: &[TagConstant],
+) -> Result<String, CodegenError> {
+    check_unique(constants)?;
+
+    Ok(constants
+        .iter()
+        .map(|constant| {
+            let tag = constant.tag();
+            let value = match constant.tag_length {
+                TagLength::Full => {
+                    let bytes: Vec<String> = tag.as_bytes().iter().map(u8::to_string).collect();
+                    format!("[{}]", bytes.join(", "))
+                }
+                _ => format!("\"{}\"", format_tag(tag)),
+            };
+            format!(
+                "{} = {}\n",
+                namespace.prefix(&format!("tag_{}", constant.name)),
+                value
+            )
+        })
+        .collect())
+}
+
+/// An output language of the code generation, see the [module documentation](self).
+pub trait CodegenTarget {
+    /// Returns the name of the target, a lowercase identifier such as `noir`.
+    fn name(&self) -> &str;
+
+    /// Returns the usual extension of the emitted files, without the dot.
+    fn extension(&self) -> &str;
+
+    /// Emits the source declaring `constants`, with identifiers prefixed by `namespace`.
+    fn emit(
+        &self,
+        namespace: &Namespace,
+        constants: &[TagConstant],
+    ) -> Result<String, CodegenError>;
+}
+
+/// The Noir globals of [`noir_module_in`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoirTarget;
+
+impl CodegenTarget for NoirTarget {
+    fn name(&self) -> &str {
+        "noir"
+    }
+
+    fn extension(&self) -> &str {
+        "nr"
+    }
+
+    fn emit(
+        &self,
+        namespace: &Namespace,
+        constants: &[TagConstant],
+    ) -> Result<String, CodegenError> {
+        noir_module_in(namespace, constants)
+    }
+}
+
+/// The `Prover.toml` inputs of [`prover_toml_in`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProverTomlTarget;
+
+impl CodegenTarget for ProverTomlTarget {
+    fn name(&self) -> &str {
+        "prover-toml"
+    }
+
+    fn extension(&self) -> &str {
+        "toml"
+    }
+
+    fn emit(
+        &self,
+        namespace: &Namespace,
+        constants: &[TagConstant],
+    ) -> Result<String, CodegenError> {
+        prover_toml_in(namespace, constants)
+    }
+}
+
+/// A registry of [`CodegenTarget`]s by name.
+#[derive(Default)]
+pub struct Targets {
+    targets: Vec<Box<dyn CodegenTarget>>,
+}
+
+impl Targets {
+    /// Returns an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the registry of the targets of this crate.
+    pub fn builtin() -> Self {
+        Targets {
+            targets: vec![
+                Box::new(NoirTarget),
+                Box::new(ProverTomlTarget),
+                Box::new(CairoTarget),
+                Box::new(GoTarget),
+                Box::new(MoveTarget {
+                    dialect: MoveDialect::Aptos,
+                }),
+                Box::new(MoveTarget {
+                    dialect: MoveDialect::Sui,
+                }),
+                Box::new(RustTarget),
+                Box::new(SolidityTarget),
+                Box::new(TypeScriptTarget),
+            ],
+        }
+    }
+
+    /// Adds `target`, which must not share its name with a registered one.
+    pub fn register(&mut self, target: Box<dyn CodegenTarget>) -> Result<(), CodegenError> {
+        if self.get(target.name()).is_some() {
+            return Err(CodegenError::DuplicateTarget(target.name().to_string()));
+        }
+        self.targets.push(target);
+        Ok(())
+    }
+
+    /// Returns the target `name`.
+    pub fn get(&self, name: &str) -> Option<&dyn CodegenTarget> {
+        self.targets
+            .iter()
+            .find(|target| target.name() == name)
+            .map(|target| target.as_ref())
+    }
+
+    /// Returns the names of the registered targets, in registration order.
+    pub fn names(&self) -> Vec<&str> {
+        self.targets.iter().map(|target| target.name()).collect()
+    }
+
+    /// Emits `constants` with the target `name`.
+    pub fn emit(
+        &self,
+        name: &str,
+        namespace: &Namespace,
+        constants: &[TagConstant],
+    ) -> Result<String, CodegenError> {
+        self.get(name)
+            .ok_or_else(|| CodegenError::UnknownTarget(name.to_string()))?
+            .emit(namespace, constants)
+    }
+}
+
+/// Emits a Noir module verifying chunks of a [`chunked`](crate::chunked) commitment over
+/// `chunk_size`-byte chunks and a tree of depth `depth`, exposing
+/// `verify_chunk(commitment, byte_len, index, chunk, siblings)`.
+///
+/// The chunk is passed zero padded to `chunk_size` bytes and `siblings` are the
+/// [`ChunkProof`](crate::chunked::ChunkProof) siblings, from the leaf level up.
+///
+/// # Panics
+/// If `chunk_size` is 0.
+pub fn noir_chunk_verifier(chunk_size: usize, depth: u32) -> String {
+    assert!(chunk_size > 0, "Chunk size must be positive");
+    let leaf_pattern = chunked::leaf_pattern::<Fr>(chunk_size);
+    let elements = leaf_pattern.operations()[0].len();
+    let bytes_per_element = bytes_per_element::<Fr>();
+
+    let mut source = format!(
+        "// Generated by `safe-api codegen chunked --chunk-size {} --depth {}`. Do not edit.\n\
+         \n\
+         use dep::safe::safe::SafeSponge;\n\
+         \n\
+         pub global CHUNK_SIZE: u32 = {};\n\
+         pub global DEPTH: u32 = {};\n\
+         global ELEMENTS: u32 = {};\n\
+         global BYTES_PER_ELEMENT: u32 = {};\n",
+        chunk_size, depth, chunk_size, depth, elements, bytes_per_element
+    );
+    for (name, pattern, label) in [
+        ("LEAF", &leaf_pattern, chunked::LEAF_LABEL),
+        ("NODE", &chunked::node_pattern(), chunked::NODE_LABEL),
+        ("ROOT", &chunked::root_pattern(), chunked::ROOT_LABEL),
+    ] {
+        let words: Vec<String> = pattern
+            .encoded_words()
+            .iter()
+            .map(|word| format!("0x{:08x}", word))
+            .collect();
+        let domain_separator = chunked::domain_separator(label);
+        let rows: Vec<String> = domain_separator
+            .chunks(16)
+            .map(|row| {
+                let bytes: Vec<String> = row.iter().map(|byte| format!("0x{:02x}", byte)).collect();
+                format!("    {},\n", bytes.join(", "))
+            })
+            .collect();
+        source.push_str(&format!(
+            "\nglobal IO_PATTERN_{}: [u32; {}] = [{}];\n\
+             global DOMAIN_{}: [u8; 64] = [\n{}];\n",
+            name,
+            words.len(),
+            words.join(", "),
+            name,
+            rows.concat()
+        ));
+    }
+    source.push_str(NOIR_CHUNK_VERIFIER);
+    source
+}
+
+const NOIR_CHUNK_VERIFIER: &str = r#"
+fn hash_leaf(chunk: [u8; CHUNK_SIZE]) -> Field {
+    // Length first, then big-endian chunks of BYTES_PER_ELEMENT bytes.
+    let mut elements = [0; ELEMENTS];
+    elements[0] = CHUNK_SIZE as Field;
+    for i in 0..CHUNK_SIZE {
+        let j = 1 + i / BYTES_PER_ELEMENT;
+        elements[j] = elements[j] * 256 + chunk[i] as Field;
+    }
+
+    let mut sponge = SafeSponge::start(IO_PATTERN_LEAF, DOMAIN_LEAF);
+    sponge.absorb(elements);
+    let leaf = sponge.squeeze().get(0);
+    sponge.finish();
+    leaf
+}
+
+fn hash_node(left: Field, right: Field) -> Field {
+    let mut sponge = SafeSponge::start(IO_PATTERN_NODE, DOMAIN_NODE);
+    sponge.absorb([left, right]);
+    let node = sponge.squeeze().get(0);
+    sponge.finish();
+    node
+}
+
+/// Asserts that `chunk` (zero padded) is chunk `index` of the data of `byte_len` bytes.
+pub fn verify_chunk(
+    commitment: Field,
+    byte_len: Field,
+    index: u32,
+    chunk: [u8; CHUNK_SIZE],
+    siblings: [Field; DEPTH],
+) {
+    let mut node = hash_leaf(chunk);
+    let mut position = index;
+    for level in 0..DEPTH {
+        let is_right = position % 2 == 1;
+        position = position / 2;
+        let (left, right) = if is_right {
+            (siblings[level], node)
+        } else {
+            (node, siblings[level])
+        };
+        node = hash_node(left, right);
+    }
+    assert(position == 0, "Chunk index out of range");
+
+    let mut sponge = SafeSponge::start(IO_PATTERN_ROOT, DOMAIN_ROOT);
+    sponge.absorb([node, byte_len, CHUNK_SIZE as Field]);
+    let expected = sponge.squeeze().get(0);
+    sponge.finish();
+    assert(expected == commitment, "Chunk not in commitment");
+}
+"#;
+
+/// Returns a `snake_case` identifier in `UpperCamelCase`.
+fn camel_case(identifier: &str) -> String {
+    identifier
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or(String::new(), |first| {
+                first.to_ascii_uppercase().to_string() + chars.as_str()
+            })
+        })
+        .collect()
+}
+
+/// Returns `bytes` as comma-separated `0x`-prefixed hex, e.g. for array literals.
+fn byte_list(bytes: &[u8]) -> String {
+    let bytes: Vec<String> = bytes.iter().map(|byte| format!("0x{:02x}", byte)).collect();
+    bytes.join(", ")
+}
+
+/// Returns whether `name` is a lowercase identifier (`[a-z][a-z0-9_]*`).
+pub(crate) fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn check_unique(constants: &[TagConstant]) -> Result<(), CodegenError> {
+    for (index, constant) in constants.iter().enumerate() {
+        if constants[..index]
+            .iter()
+            .any(|other| other.name == constant.name)
+        {
+            return Err(CodegenError::DuplicateName(constant.name.clone()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::padded_domain_separator;
+    use crate::tag::compute_tag_256;
+
+    /// The constant the tests of every target emit.
+    pub(crate) fn merkle_node() -> TagConstant {
+        let pattern = IoPattern::new().absorb(1).absorb(1).squeeze(1);
+        TagConstant::new("merkle_node", pattern, padded_domain_separator(b"ABCD")).unwrap()
+    }
+
+    /// Returns the bytes of the tag declared by the line of `source` containing `marker`: a
+    /// hex integer, possibly quoted, or a list of hex or decimal bytes.
+    fn declared_tag(source: &str, marker: &str, length: TagLength) -> Vec<u8> {
+        let line = source
+            .lines()
+            .find(|line| line.contains(marker))
+            .unwrap_or_else(|| panic!("missing `{}` in\n{}", marker, source));
+        let value = line[line.find(marker).unwrap() + marker.len()..]
+            .split_once('=')
+            .unwrap()
+            .1
+            .trim()
+            .trim_end_matches(';')
+            .trim_matches('"');
+        // Go declares `[N]byte{...}`.
+        match value.find('{').or_else(|| value.find('[')) {
+            Some(start) => value[start + 1..value.len() - 1]
+                .split(',')
+                .map(|byte| match byte.trim().strip_prefix("0x") {
+                    Some(digits) => u8::from_str_radix(digits, 16).unwrap(),
+                    None => byte.trim().parse().unwrap(),
+                })
+                .collect(),
+            None => {
+                let digits = value.strip_prefix("0x").unwrap();
+                let padded = format!("0x{:0>1$}", digits, 2 * length.bytes());
+                crate::hex::decode(&padded, crate::hex::Mode::Strict).unwrap()
+            }
+        }
+    }
+
+    #[test]
+    fn test_noir_module() {
+        assert_eq!(
+            noir_module(&[merkle_node()]).unwrap(),
+            "// Generated by `safe-api codegen noir`. Do not edit.\n\
+             \n\
+             /// `merkle_node`, domain separator 0x41424344 (zero padded).\n\
+             pub global IO_PATTERN_MERKLE_NODE: [u32; 3] = [0x80000001, 0x80000001, 0x00000001];\n\
+             pub global TAG_MERKLE_NODE: Field = 0x08e2da1eb5257f918e9c15b5605a3516;\n"
+        );
+    }
+
+    #[test]
+    fn test_prover_toml() {
+        assert_eq!(
+            prover_toml(&[merkle_node()]).unwrap(),
+            "tag_merkle_node = \"0x08e2da1eb5257f918e9c15b5605a3516\"\n"
+        );
+    }
+
+    #[test]
+    fn test_namespace() {
+        let namespace = Namespace::new("acme/hot_wallet").unwrap();
+        assert_eq!(namespace.snake_case(), "acme_hot_wallet");
+        assert_eq!(namespace.camel_case(), "AcmeHotWallet");
+        let source = noir_module_in(&namespace, &[merkle_node()]).unwrap();
+        assert!(source.contains("pub global ACME_HOT_WALLET_IO_PATTERN_MERKLE_NODE: [u32; 3]"));
+        assert!(source.contains("pub global ACME_HOT_WALLET_TAG_MERKLE_NODE: Field"));
+        assert_eq!(
+            prover_toml_in(&namespace, &[merkle_node()]).unwrap(),
+            "acme_hot_wallet_tag_merkle_node = \"0x08e2da1eb5257f918e9c15b5605a3516\"\n"
+        );
+
+        for namespace in ["", "acme/", "Acme/wallet", "acme-wallet"] {
+            assert_eq!(
+                Namespace::new(namespace),
+                Err(CodegenError::InvalidNamespace(namespace.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_noir_chunk_verifier() {
+        let source = noir_chunk_verifier(64, 3);
+        assert!(source.starts_with(
+            "// Generated by `safe-api codegen chunked --chunk-size 64 --depth 3`. Do not edit.\n"
+        ));
+        for line in [
+            "pub global CHUNK_SIZE: u32 = 64;",
+            "pub global DEPTH: u32 = 3;",
+            "global ELEMENTS: u32 = 4;",
+            "global BYTES_PER_ELEMENT: u32 = 31;",
+            "global IO_PATTERN_LEAF: [u32; 2] = [0x80000004, 0x00000001];",
+            "global IO_PATTERN_NODE: [u32; 2] = [0x80000002, 0x00000001];",
+            "global IO_PATTERN_ROOT: [u32; 2] = [0x80000003, 0x00000001];",
+        ] {
+            assert!(source.contains(line), "missing `{}`", line);
+        }
+        assert!(
+            source.ends_with("assert(expected == commitment, \"Chunk not in commitment\");\n}\n")
+        );
+    }
+
+    #[test]
+    fn test_targets() {
+        let mut targets = Targets::builtin();
+        assert_eq!(
+            targets.names(),
+            vec![
+                "noir",
+                "prover-toml",
+                "cairo",
+                "go",
+                "move-aptos",
+                "move-sui",
+                "rust",
+                "solidity",
+                "typescript"
+            ]
+        );
+        let namespace = Namespace::new("acme").unwrap();
+        assert_eq!(
+            targets.emit("noir", &namespace, &[merkle_node()]),
+            noir_module_in(&namespace, &[merkle_node()])
+        );
+        assert_eq!(
+            targets.emit("prover-toml", &namespace, &[merkle_node()]),
+            prover_toml_in(&namespace, &[merkle_node()])
+        );
+        assert_eq!(
+            targets.emit("cobol", &namespace, &[]),
+            Err(CodegenError::UnknownTarget("cobol".to_string()))
+        );
+        assert_eq!(
+            targets.register(Box::new(NoirTarget)),
+            Err(CodegenError::DuplicateTarget("noir".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_emitted_tags() {
+        let domain_separator = padded_domain_separator(b"ABCD");
+        let node = IoPattern::new().absorb(1).absorb(1).squeeze(1);
+        // The zero word is skipped, and the empty ABSORB splits the two SQUEEZEs.
+        let zero_length = IoPattern::new()
+            .absorb(2)
+            .squeeze(0)
+            .absorb(1)
+            .squeeze(1)
+            .absorb(0)
+            .squeeze(2);
+        let cases = [
+            (node.clone(), TagLength::Bits128),
+            (zero_length, TagLength::Bits128),
+            (node.clone(), TagLength::Bits64),
+            (node.clone(), TagLength::Bits248),
+            (node, TagLength::Full),
+        ];
+
+        let targets = Targets::builtin();
+        let markers = [
+            ("noir", "TAG_MERKLE_NODE:"),
+            ("prover-toml", "tag_merkle_node"),
+            ("cairo", "TAG_MERKLE_NODE:"),
+            ("go", "TagMerkleNode"),
+            ("move-aptos", "const TAG_MERKLE_NODE:"),
+            ("move-sui", "const TAG_MERKLE_NODE:"),
+            ("rust", "const TAG:"),
+            ("solidity", "TAG_MERKLE_NODE"),
+            ("typescript", "TAG_MERKLE_NODE"),
+        ];
+        assert_eq!(
+            markers
+                .iter()
+                .map(|(target, _)| *target)
+                .collect::<Vec<_>>(),
+            targets.names()
+        );
+        for (pattern, length) in cases {
+            let words = pattern.encoded_words();
+            let expected = &compute_tag_256(&words, domain_separator)[..length.bytes()];
+            let constant = TagConstant::new("merkle_node", pattern, domain_separator)
+                .unwrap()
+                .with_tag_length(length);
+            for (target, marker) in markers {
+                let source = targets
+                    .emit(
+                        target,
+                        &Namespace::default(),
+                        core::slice::from_ref(&constant),
+                    )
+                    .unwrap();
+                assert_eq!(
+                    declared_tag(&source, marker, length),
+                    expected,
+                    "{} with {:?} words {:x?}",
+                    target,
+                    length,
+                    words
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_tag_lengths() {
+        let full = merkle_node().with_tag_length(TagLength::Full);
+        let short = merkle_node().with_tag_length(TagLength::Bits64);
+        let digest = full.io_pattern().tag_256(full.domain_separator());
+        let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+        assert_eq!(full.tag().as_bytes(), digest);
+        assert_eq!(format_tag(short.tag()), format!("0x{}", &hex[..16]));
+
+        let targets = Targets::builtin();
+        let namespace = Namespace::default();
+        for (target, declarations) in [
+            (
+                "noir",
+                [
+                    "pub global TAG_MERKLE_NODE: [u8; 32] = [0x".to_string(),
+                    format!("pub global TAG_MERKLE_NODE: Field = 0x{};", &hex[..16]),
+                ],
+            ),
+            (
+                "prover-toml",
+                [
+                    format!("tag_merkle_node = [{},", digest[0]),
+                    format!("tag_merkle_node = \"0x{}\"", &hex[..16]),
+                ],
+            ),
+            (
+                "cairo",
+                [
+                    format!("pub const TAG_MERKLE_NODE: u256 = 0x{};", hex),
+                    format!("pub const TAG_MERKLE_NODE: u64 = 0x{};", &hex[..16]),
+                ],
+            ),
+            (
+                "go",
+                [
+                    "var TagMerkleNode = [32]byte{0x".to_string(),
+                    "var TagMerkleNode = [8]byte{0x".to_string(),
+                ],
+            ),
+            (
+                "move-sui",
+                [
+                    format!("const TAG_MERKLE_NODE: u256 = 0x{};", hex),
+                    "public fun tag_merkle_node(): u64 { TAG_MERKLE_NODE }".to_string(),
+                ],
+            ),
+            (
+                "rust",
+                [
+                    "pub const TAG: [u8; 32] = [0x".to_string(),
+                    format!("pub const TAG: u64 = 0x{};", &hex[..16]),
+                ],
+            ),
+            (
+                "typescript",
+                [
+                    format!("export const TAG_MERKLE_NODE = \"0x{}\";", hex),
+                    format!("export const TAG_MERKLE_NODE = \"0x{}\";", &hex[..16]),
+                ],
+            ),
+            (
+                "solidity",
+                [
+                    format!("bytes32 internal constant TAG_MERKLE_NODE = 0x{};", hex),
+                    format!(
+                        "bytes8 internal constant TAG_MERKLE_NODE = 0x{};",
+                        &hex[..16]
+                    ),
+                ],
+            ),
+        ] {
+            for (constant, declaration) in [&full, &short].into_iter().zip(declarations) {
+                let source = targets
+                    .emit(target, &namespace, core::slice::from_ref(constant))
+                    .unwrap();
+                assert!(
+                    source.contains(&declaration),
+                    "{}: missing `{}` in\n{}",
+                    target,
+                    declaration,
+                    source
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_challenges() {
+        let sigma = |first: &str, second: &str| {
+            let pattern = IoPattern::new()
+                .absorb(2)
+                .squeeze_labeled(first, 1)
+                .absorb(1)
+                .squeeze_labeled(second, 2);
+            TagConstant::new("sigma", pattern, [1u8; 64]).unwrap()
+        };
+        let source = noir_module(&[merkle_node(), sigma("alpha", "beta")]).unwrap();
+        assert!(source.contains("Do not edit.\n\nuse dep::safe::safe::SafeSponge;\n"));
+        assert!(source.contains(
+            "pub struct SigmaChallenges {\n    pub alpha: [Field; 1],\n    pub beta: [Field; 2],\n}\n"
+        ));
+        assert!(source.contains(
+            "/// Squeezes `beta`, the SQUEEZE(2) of `sigma`, which must be the next operation.\n\
+             pub fn squeeze_sigma_beta(sponge: &mut SafeSponge<4>) -> [Field; 2] {\n\
+             \x20   assert(sponge.operations_done() == 3, \"the next operation is not `beta`\");\n\
+             \x20   let squeezed = sponge.squeeze();\n\
+             \x20   let mut challenge = [0; 2];\n\
+             \x20   for i in 0..2 {\n\
+             \x20       challenge[i] = squeezed.get(i);\n\
+             \x20   }\n\
+             \x20   challenge\n\
+             }\n"
+        ));
+        assert!(source.ends_with(
+            "pub fn sigma_challenges(sponge: &mut SafeSponge<4>, absorb_2: [Field; 1]) -> SigmaChallenges {\n\
+             \x20   let alpha = squeeze_sigma_alpha(sponge);\n\
+             \x20   sponge.absorb(absorb_2);\n\
+             \x20   let beta = squeeze_sigma_beta(sponge);\n\
+             \x20   SigmaChallenges { alpha, beta }\n\
+             }\n"
+        ));
+        // Labeled ABSORBs are arguments of their name, and an unlabeled SQUEEZE between two
+        // challenges leaves only the accessors.
+        let labeled = IoPattern::new()
+            .squeeze_labeled("alpha", 1)
+            .absorb_labeled("commitment", 2)
+            .squeeze_labeled("beta", 1);
+        let source =
+            noir_module(&[TagConstant::new("sigma", labeled, [1u8; 64]).unwrap()]).unwrap();
+        assert!(source.contains(
+            "pub fn sigma_challenges(sponge: &mut SafeSponge<3>, commitment: [Field; 2]) -> SigmaChallenges {\n"
+        ));
+        let unlabeled = IoPattern::new()
+            .squeeze_labeled("alpha", 1)
+            .squeeze(1)
+            .squeeze_labeled("beta", 1);
+        let source =
+            noir_module(&[TagConstant::new("sigma", unlabeled, [1u8; 64]).unwrap()]).unwrap();
+        assert!(source.contains("pub fn squeeze_sigma_beta("));
+        assert!(!source.contains("pub fn sigma_challenges("));
+        // Without challenges, the module does not depend on the library.
+        assert!(!noir_module(&[merkle_node()])
+            .unwrap()
+            .contains("SafeSponge"));
+
+        // Reordering the labels keeps the tag but changes the API of every target: the beta
+        // accessor now returns one element.
+        let (ordered, reordered) = (sigma("alpha", "beta"), sigma("beta", "alpha"));
+        assert_eq!(ordered.tag(), reordered.tag());
+        let targets = Targets::builtin();
+        for (target, accessor) in [
+            (
+                "noir",
+                "pub fn squeeze_sigma_beta(sponge: &mut SafeSponge<4>) -> [Field; 1]",
+            ),
+            (
+                "rust",
+                "pub fn squeeze_beta<P: safe_api::Permutation>(sponge: &mut safe_api::Sponge<P>) -> [P::Field; 1]",
+            ),
+            (
+                "typescript",
+                "export function squeezeSigmaBeta(sponge: Sponge): [string]",
+            ),
+        ] {
+            let emit = |constant: &TagConstant| {
+                targets
+                    .emit(target, &Namespace::default(), core::slice::from_ref(constant))
+                    .unwrap()
+            };
+            let (source, reordered) = (emit(&ordered), emit(&reordered));
+            assert_ne!(source, reordered, "{}", target);
+            assert!(!source.contains(accessor), "{}", target);
+            assert!(reordered.contains(accessor), "{}: missing `{}`", target, accessor);
+            let fields = |source: &str| {
+                let alpha = source.find("alpha:").unwrap();
+                let beta = source.find("beta:").unwrap();
+                alpha < beta
+            };
+            assert!(fields(&source), "{}", target);
+            assert!(!fields(&reordered), "{}", target);
+        }
+
+        assert_eq!(
+            TagConstant::new(
+                "sigma",
+                IoPattern::new().squeeze_labeled("query-indices", 1),
+                [1u8; 64]
+            ),
+            Err(CodegenError::InvalidChallenge("query-indices".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_invalid_names() {
+        for name in ["", "Merkle", "1st", "merkle-node"] {
+            assert_eq!(
+                TagConstant::new(name, IoPattern::new().squeeze(1), [0u8; 64]),
+                Err(CodegenError::InvalidName(name.to_string()))
+            );
+        }
+        assert_eq!(
+            noir_module(&[merkle_node(), merkle_node()]),
+            Err(CodegenError::DuplicateName("merkle_node".to_string()))
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! The Cairo target, for contracts verifying proofs on Starknet.
+//!
+//! For each constant the module declares its IO pattern, its domain separator as 16 big-endian
+//! 32-bit words and its tag:
+//!
+//! ```cairo
+//! /// `merkle_node`, domain separator 0x41424344 (zero padded).
+//! pub const IO_PATTERN_MERKLE_NODE: [u32; 3] = [0x80000001, 0x80000001, 0x00000001];
+//! pub const DOMAIN_MERKLE_NODE: [u32; 16] = [0x41424344, 0x00000000, ...];
+//! pub const TAG_MERKLE_NODE: u128 = 0x08e2da1eb5257f918e9c15b5605a3516;
+//! ```
+//!
+//! A tag of 64, 248 or 256 bits is a `u64`, a `felt252` or a `u256`. The constants are
+//! followed by a reference `compute_tag(io_pattern, domain_separator)`, which recomputes a tag
+//! with the SHA-256 of the Cairo core library, e.g. to check a pattern received at run time.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::{check_unique, CodegenError, CodegenTarget, Namespace, TagConstant};
+use crate::display::format_tag;
+use crate::tag::TagLength;
+
+/// The Cairo constants and tag function of the [module documentation](self).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CairoTarget;
+
+impl CodegenTarget for CairoTarget {
+    fn name(&self) -> &str {
+        "cairo"
+    }
+
+    fn extension(&self) -> &str {
+        "cairo"
+    }
+
+    fn emit(
+        &self,
+        namespace: &Namespace,
+        constants: &[TagConstant],
+    ) -> Result<String, CodegenError> {
+        check_unique(constants)?;
+
+        let mut source = String::from(
+            "// Generated by `safe-api codegen build --target cairo`. Do not edit.\n\
+             \n\
+             use core::sha256::compute_sha256_u32_array;\n",
+        );
+        for constant in constants {
+            let words: Vec<String> = constant
+                .io_pattern()
+                .encoded_words()
+                .iter()
+                .map(|word| format!("0x{:08x}", word))
+                .collect();
+            let domain: Vec<String> = constant
+                .domain_separator()
+                .chunks(4)
+                .map(|bytes| {
+                    let word = u32::from_be_bytes(bytes.try_into().expect("chunks of 4 bytes"));
+                    format!("0x{:08x}", word)
+                })
+                .collect();
+
+            source.push('\n');
+            source.push_str(&format!(
+                "/// `{}`, domain separator {} (zero padded).\n",
+                constant.name(),
+                constant.domain_hex()
+            ));
+            source.push_str(&format!(
+                "pub const {}: [u32; {}] = [{}];\n",
+                namespace.prefix_upper(&format!("io_pattern_{}", constant.name())),
+                words.len(),
+                words.join(", ")
+            ));
+            source.push_str(&format!(
+                "pub const {}: [u32; 16] = [\n    {},\n    {},\n];\n",
+                namespace.prefix_upper(&format!("domain_{}", constant.name())),
+                domain[..8].join(", "),
+                domain[8..].join(", ")
+            ));
+            let ty = match constant.tag_length() {
+                TagLength::Bits64 => "u64",
+                TagLength::Bits128 => "u128",
+                TagLength::Bits248 => "felt252",
+                TagLength::Full => "u256",
+            };
+            source.push_str(&format!(
+                "pub const {}: {} = {};\n",
+                namespace.prefix_upper(&format!("tag_{}", constant.name())),
+                ty,
+                format_tag(constant.tag())
+            ));
+        }
+        source.push_str(CAIRO_COMPUTE_TAG);
+        Ok(source)
+    }
+}
+
+const CAIRO_COMPUTE_TAG: &str = r#"
+/// Computes the SAFE tag (spec 2.3) of an IO pattern under a domain separator given as 16
+/// big-endian words: the first 128 bits of the SHA-256 of the aggregated pattern and the
+/// domain separator.
+pub fn compute_tag(io_pattern: Span<u32>, domain_separator: Span<u32>) -> u128 {
+    assert(domain_separator.len() == 16, 'domain separator: 16 words');
+
+    // Aggregate consecutive operations of the same type, skipping the zero word. The open
+    // group starts as an empty SQUEEZE, as in the Rust and Noir implementations.
+    let mut input: Array<u32> = array![];
+    let mut is_absorb = false;
+    let mut length: u32 = 0;
+    for word in io_pattern {
+        if *word != 0 {
+            let word_is_absorb = (*word & 0x80000000) != 0;
+            let word_length = *word & 0x7fffffff;
+            if word_is_absorb == is_absorb {
+                length += word_length;
+            } else {
+                if length != 0 {
+                    input.append(encode_operation(is_absorb, length));
+                }
+                is_absorb = word_is_absorb;
+                length = word_length;
+            }
+        }
+    };
+    if length != 0 {
+        input.append(encode_operation(is_absorb, length));
+    }
+    for word in domain_separator {
+        input.append(*word);
+    };
+
+    let [h0, h1, h2, h3, _, _, _, _] = compute_sha256_u32_array(input, 0, 0);
+    let h0: u128 = h0.into();
+    let h1: u128 = h1.into();
+    let h2: u128 = h2.into();
+    let h3: u128 = h3.into();
+    h0 * 0x1000000000000000000000000 + h1 * 0x10000000000000000 + h2 * 0x100000000 + h3
+}
+
+fn encode_operation(is_absorb: bool, length: u32) -> u32 {
+    if is_absorb {
+        0x80000000 | length
+    } else {
+        length
+    }
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::tests::merkle_node;
+    use crate::io_pattern::IoPattern;
+
+    #[test]
+    fn test_cairo_module() {
+        let mut domain_separator = *merkle_node().domain_separator();
+        domain_separator[63] = 1;
+        let pattern = IoPattern::new().absorb(1).absorb(1).squeeze(1);
+        let constant = TagConstant::new("merkle_node", pattern, domain_separator).unwrap();
+        let namespace = Namespace::new("acme").unwrap();
+        let source = CairoTarget
+            .emit(&namespace, core::slice::from_ref(&constant))
+            .unwrap();
+
+        assert!(source.contains(
+            "pub const ACME_IO_PATTERN_MERKLE_NODE: [u32; 3] = \
+             [0x80000001, 0x80000001, 0x00000001];\n"
+        ));
+        assert!(source.contains(
+            "pub const ACME_DOMAIN_MERKLE_NODE: [u32; 16] = [\n    \
+             0x41424344, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, \
+             0x00000000, 0x00000000,\n    \
+             0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, \
+             0x00000000, 0x00000001,\n];\n"
+        ));
+        assert!(source.ends_with(CAIRO_COMPUTE_TAG));
+    }
+}
@@ -0,0 +1,178 @@
+//! The Go target, a self-contained reference implementation for services such as relayers.
+//!
+//! The file declares `package safe` (the namespace without separators, e.g. `acmewallet`, in
+//! a namespace) with, for each constant, its IO pattern, its domain separator and its tag as
+//! big-endian bytes:
+//!
+//! ```go
+//! // merkle_node, domain separator 0x41424344 (zero padded).
+//! var IoPatternMerkleNode = []uint32{0x80000001, 0x80000001, 0x00000001}
+//! var DomainMerkleNode = [64]byte{0x41, 0x42, 0x43, 0x44}
+//! var TagMerkleNode = [16]byte{0x08, 0xe2, 0xda, 0x1e, ...}
+//! ```
+//!
+//! A tag of another length than 128 bits has as many bytes, e.g. `[32]byte` for the whole
+//! digest. The constants are followed by
+//! `ComputeTag(ioPattern []uint32, domainSeparator [64]byte, length int) []byte`, which returns
+//! the tag of `length` bytes, e.g. `len(TagMerkleNode)`, and `AggregateWords`. They only need
+//! the Go standard library.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::{
+    byte_list, camel_case, check_unique, CodegenError, CodegenTarget, Namespace, TagConstant,
+};
+
+/// The Go package of the [module documentation](self).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GoTarget;
+
+impl CodegenTarget for GoTarget {
+    fn name(&self) -> &str {
+        "go"
+    }
+
+    fn extension(&self) -> &str {
+        "go"
+    }
+
+    fn emit(
+        &self,
+        namespace: &Namespace,
+        constants: &[TagConstant],
+    ) -> Result<String, CodegenError> {
+        check_unique(constants)?;
+
+        let package = match namespace.snake_case().replace('_', "") {
+            package if package.is_empty() => String::from("safe"),
+            package => package,
+        };
+        let mut source = format!(
+            "// Code generated by `safe-api codegen build --target go`. DO NOT EDIT.\n\
+             \n\
+             // Package {} computes SAFE tags (spec 2.3) and declares those of the manifest.\n\
+             package {}\n\
+             \n\
+             import (\n\
+             \t\"crypto/sha256\"\n\
+             \t\"encoding/binary\"\n\
+             )\n",
+            package, package
+        );
+        for constant in constants {
+            let name = camel_case(constant.name());
+            let words: Vec<String> = constant
+                .io_pattern()
+                .encoded_words()
+                .iter()
+                .map(|word| format!("0x{:08x}", word))
+                .collect();
+            // Go zero-fills the rest of the array.
+            let domain_separator = constant.domain_separator();
+            let significant = domain_separator
+                .iter()
+                .rposition(|&byte| byte != 0)
+                .map_or(0, |index| index + 1);
+            let tag = constant.tag();
+            let tag = tag.as_bytes();
+
+            source.push_str(&format!(
+                "\n// {}, domain separator {} (zero padded).\n",
+                constant.name(),
+                constant.domain_hex()
+            ));
+            source.push_str(&format!(
+                "var IoPattern{} = []uint32{{{}}}\n",
+                name,
+                words.join(", ")
+            ));
+            source.push_str(&format!(
+                "var Domain{} = [64]byte{{{}}}\n",
+                name,
+                byte_list(&domain_separator[..significant])
+            ));
+            source.push_str(&format!(
+                "var Tag{} = [{}]byte{{{}}}\n",
+                name,
+                tag.len(),
+                byte_list(tag)
+            ));
+        }
+        source.push_str(GO_COMPUTE_TAG);
+        Ok(source)
+    }
+}
+
+const GO_COMPUTE_TAG: &str = r#"
+// ComputeTag returns the SAFE tag of an encoded IO pattern under a domain separator: the
+// first length bytes (16 in the spec, at most 32) of the SHA-256 of the aggregated words,
+// big-endian, and the domain separator.
+func ComputeTag(ioPattern []uint32, domainSeparator [64]byte, length int) []byte {
+	words := AggregateWords(ioPattern)
+	preimage := make([]byte, 0, 4*len(words)+len(domainSeparator))
+	for _, word := range words {
+		preimage = binary.BigEndian.AppendUint32(preimage, word)
+	}
+	preimage = append(preimage, domainSeparator[:]...)
+	digest := sha256.Sum256(preimage)
+	return digest[:length]
+}
+
+// AggregateWords merges consecutive operations of the same type, skipping the zero word. The
+// open group starts as an empty SQUEEZE, as in the Rust and Noir implementations.
+func AggregateWords(ioPattern []uint32) []uint32 {
+	var words []uint32
+	isAbsorb := false
+	length := uint32(0)
+	for _, word := range ioPattern {
+		if word == 0 {
+			continue
+		}
+		wordIsAbsorb := word&0x80000000 != 0
+		if wordIsAbsorb == isAbsorb {
+			length += word & 0x7fffffff
+			continue
+		}
+		if length != 0 {
+			words = append(words, encodeOperation(isAbsorb, length))
+		}
+		isAbsorb = wordIsAbsorb
+		length = word & 0x7fffffff
+	}
+	if length != 0 {
+		words = append(words, encodeOperation(isAbsorb, length))
+	}
+	return words
+}
+
+func encodeOperation(isAbsorb bool, length uint32) uint32 {
+	if isAbsorb {
+		return 0x80000000 | length
+	}
+	return length
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::tests::merkle_node;
+
+    #[test]
+    fn test_go_package() {
+        let source = GoTarget
+            .emit(&Namespace::new("acme/wallet").unwrap(), &[merkle_node()])
+            .unwrap();
+
+        assert!(source.contains("\npackage acmewallet\n"));
+        assert!(source
+            .contains("var IoPatternMerkleNode = []uint32{0x80000001, 0x80000001, 0x00000001}\n"));
+        assert!(source.contains("var DomainMerkleNode = [64]byte{0x41, 0x42, 0x43, 0x44}\n"));
+        assert!(source.ends_with(GO_COMPUTE_TAG));
+
+        let source = GoTarget.emit(&Namespace::default(), &[]).unwrap();
+        assert!(source.contains("\npackage safe\n"));
+    }
+}
@@ -0,0 +1,214 @@
+//! The Move target, for verifiers on Aptos and Sui.
+//!
+//! The module `safe_api::tags` (`safe_api::<namespace>_tags` in a namespace, `safe_api` being
+//! a named address of the package) declares, for each constant, its IO pattern, domain
+//! separator and tag with public accessors:
+//!
+//! ```move
+//! /// `merkle_node`, domain separator 0x41424344 (zero padded).
+//! const IO_PATTERN_MERKLE_NODE: vector<u32> = vector[0x80000001, 0x80000001, 0x00000001];
+//! const DOMAIN_MERKLE_NODE: vector<u8> = x"41424344...";
+//! const TAG_MERKLE_NODE: u128 = 0x08e2da1eb5257f918e9c15b5605a3516;
+//!
+//! public fun io_pattern_merkle_node(): vector<u32> { IO_PATTERN_MERKLE_NODE }
+//! ...
+//! ```
+//!
+//! A tag of 64 bits is a `u64`, and one of 248 or 256 bits a `u256`.
+//!
+//! It also parses *envelopes*, the bytes of [`tag_preimage`](crate::tag_preimage) that a
+//! relayer submits along with a proof: the aggregated IO pattern as big-endian 32-bit words,
+//! then the 64-byte domain separator. `tag_of_envelope` hashes one into its tag, and
+//! `envelope_io_pattern` and `envelope_domain_separator` split it.
+//!
+//! Sui packages of the 2024 edition need `let mut` for mutable locals, which Aptos Move does
+//! not accept, hence the two dialects of [`MoveTarget`].
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::{check_unique, CodegenError, CodegenTarget, Namespace, TagConstant};
+use crate::display::format_tag;
+use crate::tag::TagLength;
+
+/// The Move dialect of a [`MoveTarget`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveDialect {
+    /// Aptos Move, and Sui packages of the legacy edition.
+    Aptos,
+    /// Sui Move, 2024 edition.
+    Sui,
+}
+
+/// The Move module of the [module documentation](self).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MoveTarget {
+    pub dialect: MoveDialect,
+}
+
+impl CodegenTarget for MoveTarget {
+    fn name(&self) -> &str {
+        match self.dialect {
+            MoveDialect::Aptos => "move-aptos",
+            MoveDialect::Sui => "move-sui",
+        }
+    }
+
+    fn extension(&self) -> &str {
+        "move"
+    }
+
+    fn emit(
+        &self,
+        namespace: &Namespace,
+        constants: &[TagConstant],
+    ) -> Result<String, CodegenError> {
+        check_unique(constants)?;
+
+        let mut source = format!(
+            "// Generated by `safe-api codegen build --target {}`. Do not edit.\n\
+             \n\
+             module safe_api::{} {{\n\
+             \x20   /// The envelope is shorter than a domain separator or not made of whole words.\n\
+             \x20   const E_INVALID_ENVELOPE: u64 = 1;\n",
+            self.name(),
+            namespace.prefix("tags")
+        );
+        for constant in constants {
+            let name = constant.name();
+            let words: Vec<String> = constant
+                .io_pattern()
+                .encoded_words()
+                .iter()
+                .map(|word| format!("0x{:08x}", word))
+                .collect();
+            let domain: String = constant
+                .domain_separator()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            let upper = name.to_uppercase();
+            let ty = match constant.tag_length() {
+                TagLength::Bits64 => "u64",
+                TagLength::Bits128 => "u128",
+                TagLength::Bits248 | TagLength::Full => "u256",
+            };
+
+            source.push_str(&format!(
+                "\n    /// `{}`, domain separator {} (zero padded).\n",
+                name,
+                constant.domain_hex()
+            ));
+            source.push_str(&format!(
+                "    const IO_PATTERN_{}: vector<u32> = vector[{}];\n",
+                upper,
+                words.join(", ")
+            ));
+            source.push_str(&format!(
+                "    const DOMAIN_{}: vector<u8> = x\"{}\";\n",
+                upper, domain
+            ));
+            source.push_str(&format!(
+                "    const TAG_{}: {} = {};\n",
+                upper,
+                ty,
+                format_tag(constant.tag())
+            ));
+            source.push_str(&format!(
+                "\n    public fun io_pattern_{}(): vector<u32> {{ IO_PATTERN_{} }}\n\
+                 \n    public fun domain_{}(): vector<u8> {{ DOMAIN_{} }}\n\
+                 \n    public fun tag_{}(): {} {{ TAG_{} }}\n",
+                name, upper, name, upper, name, ty, upper
+            ));
+        }
+        let mutable = match self.dialect {
+            MoveDialect::Aptos => "",
+            MoveDialect::Sui => "mut ",
+        };
+        source.push_str(&MOVE_ENVELOPE.replace("let mut ", &format!("let {}", mutable)));
+        source.push_str("}\n");
+        Ok(source)
+    }
+}
+
+/// The envelope helpers, in the Sui dialect.
+const MOVE_ENVELOPE: &str = r#"
+    /// Returns the tag of an envelope: the first 128 bits of its SHA-256 (spec 2.3).
+    public fun tag_of_envelope(envelope: vector<u8>): u128 {
+        let digest = std::hash::sha2_256(envelope);
+        let mut tag: u128 = 0;
+        let mut i = 0;
+        while (i < 16) {
+            tag = (tag << 8) | (*std::vector::borrow(&digest, i) as u128);
+            i = i + 1;
+        };
+        tag
+    }
+
+    /// Returns the IO pattern words of an envelope.
+    public fun envelope_io_pattern(envelope: &vector<u8>): vector<u32> {
+        let length = std::vector::length(envelope);
+        assert!(length >= 64 && (length - 64) % 4 == 0, E_INVALID_ENVELOPE);
+        let mut words = std::vector::empty<u32>();
+        let mut i = 0;
+        while (i < length - 64) {
+            let mut word: u32 = 0;
+            let mut j = 0;
+            while (j < 4) {
+                word = (word << 8) | (*std::vector::borrow(envelope, i + j) as u32);
+                j = j + 1;
+            };
+            std::vector::push_back(&mut words, word);
+            i = i + 4;
+        };
+        words
+    }
+
+    /// Returns the 64-byte domain separator of an envelope.
+    public fun envelope_domain_separator(envelope: &vector<u8>): vector<u8> {
+        let length = std::vector::length(envelope);
+        assert!(length >= 64 && (length - 64) % 4 == 0, E_INVALID_ENVELOPE);
+        let mut domain_separator = std::vector::empty<u8>();
+        let mut i = length - 64;
+        while (i < length) {
+            std::vector::push_back(&mut domain_separator, *std::vector::borrow(envelope, i));
+            i = i + 1;
+        };
+        domain_separator
+    }
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::tests::merkle_node;
+
+    #[test]
+    fn test_move_module() {
+        let namespace = Namespace::new("acme").unwrap();
+        let target = MoveTarget {
+            dialect: MoveDialect::Aptos,
+        };
+        let source = target.emit(&namespace, &[merkle_node()]).unwrap();
+        assert!(source.contains("\nmodule safe_api::acme_tags {\n"));
+        assert!(source.contains(
+            "    const IO_PATTERN_MERKLE_NODE: vector<u32> = \
+             vector[0x80000001, 0x80000001, 0x00000001];\n"
+        ));
+        assert!(source.contains(&format!(
+            "    const DOMAIN_MERKLE_NODE: vector<u8> = x\"41424344{}\";\n",
+            "00".repeat(60)
+        )));
+        assert!(source.contains("    public fun tag_merkle_node(): u128 { TAG_MERKLE_NODE }\n"));
+        assert!(source.ends_with("        domain_separator\n    }\n}\n"));
+        assert!(!source.contains("let mut"));
+
+        let sui = MoveTarget {
+            dialect: MoveDialect::Sui,
+        };
+        let source = sui.emit(&Namespace::default(), &[merkle_node()]).unwrap();
+        assert!(source.contains("\nmodule safe_api::tags {\n"));
+        assert!(source.contains("        let mut tag: u128 = 0;\n"));
+    }
+}
@@ -0,0 +1,313 @@
+//! The Rust target, for applications built on this crate.
+//!
+//! Each protocol is a module behind the cargo feature of its name (prefixed by the namespace,
+//! e.g. `acme_wallet_merkle_node`), so that an application only compiles, and can only
+//! reference, the protocols it enables. The module declares the IO pattern, domain separator
+//! and tag, and starts sponges, or transcripts if every operation is labeled:
+//!
+//! ```rust,ignore
+//! /// `merkle_node`, domain separator 0x41424344 (zero padded).
+//! #[cfg(feature = "merkle_node")]
+//! pub mod merkle_node {
+//!     pub const IO_PATTERN: [u32; 3] = [0x80000001, 0x80000001, 0x00000001];
+//!     pub const DOMAIN_SEPARATOR: [u8; 64] = super::pad(&[0x41, 0x42, 0x43, 0x44]);
+//!     pub const TAG: u128 = 0x08e2da1eb5257f918e9c15b5605a3516;
+//!
+//!     /// Returns the IO pattern, with its labels.
+//!     pub fn io_pattern() -> safe_api::IoPattern { ... }
+//!
+//!     /// Starts a sponge of the protocol.
+//!     pub fn sponge<P: safe_api::Permutation + Default>() -> safe_api::Sponge<P> { ... }
+//! }
+//! ```
+//!
+//! The module of a protocol with challenges, such as `A1 A1 S1:alpha S2:beta`, also declares
+//! them in squeeze order, with an accessor per challenge checking its label and a function
+//! squeezing them all:
+//!
+//! ```rust,ignore
+//! /// The challenges of the protocol, in squeeze order.
+//! #[derive(Clone, Debug, PartialEq, Eq)]
+//! pub struct Challenges<F> {
+//!     pub alpha: [F; 1],
+//!     pub beta: [F; 2],
+//! }
+//!
+//! /// Squeezes `alpha`, the SQUEEZE(1) of the protocol.
+//! pub fn squeeze_alpha<P: safe_api::Permutation>(sponge: &mut safe_api::Sponge<P>) -> [P::Field; 1] { ... }
+//!
+//! /// Squeezes the challenges in order, absorbing the inputs between them, from a sponge whose
+//! /// next operation is `alpha`.
+//! pub fn challenges<P: safe_api::Permutation>(sponge: &mut safe_api::Sponge<P>) -> Challenges<P::Field> { ... }
+//! ```
+//!
+//! A tag of 64 bits is a `u64`, and one of 248 or 256 bits a byte array such as `[u8; 32]`.
+//!
+//! The application declares the features it may enable, e.g. `merkle_node = []`, which the
+//! header of the file lists.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::{
+    byte_list, check_unique, ChallengeStep, CodegenError, CodegenTarget, Namespace, TagConstant,
+};
+use crate::display::format_tag;
+use crate::tag::TagLength;
+
+/// Keywords that are not valid module or field names.
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+/// The Rust modules of the [module documentation](self).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RustTarget;
+
+impl CodegenTarget for RustTarget {
+    fn name(&self) -> &str {
+        "rust"
+    }
+
+    fn extension(&self) -> &str {
+        "rs"
+    }
+
+    fn emit(
+        &self,
+        namespace: &Namespace,
+        constants: &[TagConstant],
+    ) -> Result<String, CodegenError> {
+        check_unique(constants)?;
+
+        let features: Vec<String> = constants
+            .iter()
+            .map(|constant| namespace.prefix(constant.name()))
+            .collect();
+        let mut source = String::from(
+            "// Generated by `safe-api codegen build --target rust`. Do not edit.\n\
+             //\n\
+             // Each protocol is behind the cargo feature of its name:\n\
+             //\n\
+             // [features]\n",
+        );
+        for feature in &features {
+            source.push_str(&format!("// {} = []\n", feature));
+        }
+
+        for (constant, feature) in constants.iter().zip(&features) {
+            let words: Vec<String> = constant
+                .io_pattern()
+                .encoded_words()
+                .iter()
+                .map(|word| format!("0x{:08x}", word))
+                .collect();
+            let domain_separator = constant.domain_separator();
+            let significant = domain_separator
+                .iter()
+                .rposition(|&byte| byte != 0)
+                .map_or(0, |index| index + 1);
+            let domain_bytes: Vec<String> = domain_separator[..significant]
+                .iter()
+                .map(|byte| format!("0x{:02x}", byte))
+                .collect();
+            let tag = constant.tag();
+            let (tag_type, tag) = match constant.tag_length() {
+                TagLength::Bits64 => (String::from("u64"), format_tag(tag)),
+                TagLength::Bits128 => (String::from("u128"), format_tag(tag)),
+                TagLength::Bits248 | TagLength::Full => (
+                    format!("[u8; {}]", tag.as_bytes().len()),
+                    format!("[{}]", byte_list(tag.as_bytes())),
+                ),
+            };
+            let pattern = constant.io_pattern();
+            let labeled = (0..pattern.len()).all(|index| pattern.label(index).is_some());
+
+            source.push_str(&format!(
+                "\n/// `{}`, domain separator {} (zero padded).\n\
+                 #[cfg(feature = \"{}\")]\n\
+                 pub mod {} {{\n",
+                constant.name(),
+                constant.domain_hex(),
+                feature,
+                identifier(feature)
+            ));
+            source.push_str(&format!(
+                "    pub const IO_PATTERN: [u32; {}] = [{}];\n\
+                 \x20   pub const DOMAIN_SEPARATOR: [u8; 64] = super::pad(&[{}]);\n\
+                 \x20   pub const TAG: {} = {};\n\
+                 \n\
+                 \x20   /// Returns the IO pattern, with its labels.\n\
+                 \x20   pub fn io_pattern() -> safe_api::IoPattern {{\n\
+                 \x20       \"{}\".parse().expect(\"generated pattern\")\n\
+                 \x20   }}\n\
+                 \n\
+                 \x20   /// Starts a sponge of the protocol.\n\
+                 \x20   pub fn sponge<P: safe_api::Permutation + Default>() -> safe_api::Sponge<P> {{\n\
+                 \x20       safe_api::Sponge::start(io_pattern(), &DOMAIN_SEPARATOR)\n\
+                 \x20   }}\n",
+                words.len(),
+                words.join(", "),
+                domain_bytes.join(", "),
+                tag_type,
+                tag,
+                pattern
+            ));
+            if labeled {
+                source.push_str(
+                    "\n    /// Starts a transcript of the protocol.\n\
+                     \x20   pub fn transcript<P: safe_api::Permutation + Default>() -> safe_api::Transcript<P> {\n\
+                     \x20       safe_api::Transcript::new(io_pattern(), &DOMAIN_SEPARATOR)\n\
+                     \x20   }\n",
+                );
+            }
+            let challenges = constant.challenges();
+            if !challenges.is_empty() {
+                source.push_str(
+                    "\n    /// The challenges of the protocol, in squeeze order.\n\
+                     \x20   #[derive(Clone, Debug, PartialEq, Eq)]\n\
+                     \x20   pub struct Challenges<F> {\n",
+                );
+                for (label, length) in &challenges {
+                    source.push_str(&format!(
+                        "        pub {}: [F; {}],\n",
+                        identifier(label),
+                        length
+                    ));
+                }
+                source.push_str("    }\n");
+            }
+            for (label, length) in &challenges {
+                source.push_str(&format!(
+                    "\n    /// Squeezes `{}`, the SQUEEZE({}) of the protocol.\n\
+                     \x20   pub fn squeeze_{}<P: safe_api::Permutation>(sponge: &mut safe_api::Sponge<P>) -> [P::Field; {}] {{\n\
+                     \x20       let challenge = sponge.squeeze_labeled(\"{}\");\n\
+                     \x20       challenge.try_into().expect(\"generated length\")\n\
+                     \x20   }}\n",
+                    label, length, label, length, label
+                ));
+            }
+            if let Some(steps) = constant.challenge_steps() {
+                let mut arguments = String::from("sponge: &mut safe_api::Sponge<P>");
+                let mut body = String::new();
+                for step in &steps {
+                    match step {
+                        ChallengeStep::Squeeze(label) => body.push_str(&format!(
+                            "        let {} = squeeze_{}(sponge);\n",
+                            identifier(label),
+                            label
+                        )),
+                        ChallengeStep::Absorb(name, length) => {
+                            arguments.push_str(&format!(
+                                ", {}: &[P::Field; {}]",
+                                identifier(name),
+                                length
+                            ));
+                            body.push_str(&format!(
+                                "        sponge.absorb({});\n",
+                                identifier(name)
+                            ));
+                        }
+                    }
+                }
+                let labels: Vec<String> = challenges
+                    .iter()
+                    .map(|(label, _)| identifier(label))
+                    .collect();
+                source.push_str(&format!(
+                    "\n    /// Squeezes the challenges in order, absorbing the inputs between them, from a\n\
+                     \x20   /// sponge whose next operation is `{}`.\n\
+                     \x20   pub fn challenges<P: safe_api::Permutation>({}) -> Challenges<P::Field> {{\n\
+                     {}\
+                     \x20       Challenges {{ {} }}\n\
+                     \x20   }}\n",
+                    challenges[0].0,
+                    arguments,
+                    body,
+                    labels.join(", ")
+                ));
+            }
+            source.push_str("}\n");
+        }
+        source.push_str(RUST_PAD);
+        Ok(source)
+    }
+}
+
+/// Returns `name` as an identifier, e.g. of a module, a raw identifier if it is a keyword.
+fn identifier(name: &str) -> String {
+    match KEYWORDS.contains(&name) {
+        true => format!("r#{}", name),
+        false => name.into(),
+    }
+}
+
+const RUST_PAD: &str = "
+/// Zero pads a domain separator to 64 bytes.
+#[allow(dead_code)]
+const fn pad(bytes: &[u8]) -> [u8; 64] {
+    let mut padded = [0u8; 64];
+    let mut index = 0;
+    while index < bytes.len() {
+        padded[index] = bytes[index];
+        index += 1;
+    }
+    padded
+}
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::tests::merkle_node;
+    use crate::io_pattern::IoPattern;
+
+    #[test]
+    fn test_rust_modules() {
+        let node = merkle_node();
+        let transcript = IoPattern::new()
+            .absorb_labeled("commitment", 2)
+            .squeeze_labeled("challenge", 1);
+        let domain_separator = *node.domain_separator();
+        let constants = [
+            node,
+            TagConstant::new("type", transcript, domain_separator).unwrap(),
+        ];
+        let source = RustTarget.emit(&Namespace::default(), &constants).unwrap();
+
+        assert!(source.contains("// merkle_node = []\n// type = []\n"));
+        assert!(source.contains("#[cfg(feature = \"merkle_node\")]\npub mod merkle_node {\n"));
+        assert!(source.contains(
+            "    pub const IO_PATTERN: [u32; 3] = [0x80000001, 0x80000001, 0x00000001];\n"
+        ));
+        assert!(source.contains(
+            "    pub const DOMAIN_SEPARATOR: [u8; 64] = super::pad(&[0x41, 0x42, 0x43, 0x44]);\n"
+        ));
+        assert!(source.contains("#[cfg(feature = \"type\")]\npub mod r#type {\n"));
+        assert!(source.contains("        \"A2:commitment S1:challenge\".parse()"));
+        // Only the labeled protocol has a transcript.
+        assert_eq!(source.matches("pub fn transcript").count(), 1);
+        assert!(source
+            .contains("    pub struct Challenges<F> {\n        pub challenge: [F; 1],\n    }\n"));
+        assert!(source.contains("        let challenge = sponge.squeeze_labeled(\"challenge\");\n"));
+        assert!(source.contains(
+            "    pub fn challenges<P: safe_api::Permutation>(sponge: &mut safe_api::Sponge<P>) -> Challenges<P::Field> {\n\
+             \x20       let challenge = squeeze_challenge(sponge);\n\
+             \x20       Challenges { challenge }\n\
+             \x20   }\n"
+        ));
+        assert!(source.ends_with(RUST_PAD));
+
+        let source = RustTarget
+            .emit(&Namespace::new("acme").unwrap(), &constants[..1])
+            .unwrap();
+        assert!(
+            source.contains("#[cfg(feature = \"acme_merkle_node\")]\npub mod acme_merkle_node {")
+        );
+    }
+}
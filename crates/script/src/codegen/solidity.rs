@@ -0,0 +1,193 @@
+//! The Solidity target, for contracts recomputing or verifying tags on-chain
+//! (`safe-api codegen solidity`).
+//!
+//! The library `SafeTags` (`AcmeWalletSafeTags` in the namespace `acme/wallet`) declares, for
+//! each constant, its tag and domain separator, and a function returning its IO pattern:
+//!
+//! ```solidity
+//! /// @notice `merkle_node`, domain separator 0x41424344 (zero padded).
+//! bytes16 internal constant TAG_MERKLE_NODE = 0x08e2da1eb5257f918e9c15b5605a3516;
+//! bytes internal constant DOMAIN_MERKLE_NODE = hex"41424344";
+//!
+//! function ioPatternMerkleNode() internal pure returns (uint32[] memory ioPattern) { ... }
+//! ```
+//!
+//! A tag of another length than 128 bits has as many bytes, e.g. `bytes32` for the whole
+//! digest. The declarations are followed by `computeTag(ioPattern, domainSeparator)`, which hashes with the SHA-256
+//! precompile, and `verifyTag(ioPattern, domainSeparator, tag)`. Domain separators are given
+//! without their zero padding, up to 64 bytes.
+
+use alloc::format;
+use alloc::string::String;
+
+use super::{camel_case, check_unique, CodegenError, CodegenTarget, Namespace, TagConstant};
+use crate::display::format_tag;
+
+/// The Solidity library of the [module documentation](self).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SolidityTarget;
+
+impl CodegenTarget for SolidityTarget {
+    fn name(&self) -> &str {
+        "solidity"
+    }
+
+    fn extension(&self) -> &str {
+        "sol"
+    }
+
+    fn emit(
+        &self,
+        namespace: &Namespace,
+        constants: &[TagConstant],
+    ) -> Result<String, CodegenError> {
+        check_unique(constants)?;
+
+        let mut source = format!(
+            "// SPDX-License-Identifier: MIT\n\
+             // Generated by `safe-api codegen solidity`. Do not edit.\n\
+             pragma solidity ^0.8.20;\n\
+             \n\
+             /// @title SAFE tags (spec 2.3) of the protocols of the manifest.\n\
+             library {}SafeTags {{\n",
+            namespace.camel_case()
+        );
+        for constant in constants {
+            let upper = constant.name().to_uppercase();
+            let domain_separator = constant.domain_separator();
+            let significant = domain_separator
+                .iter()
+                .rposition(|&byte| byte != 0)
+                .map_or(0, |index| index + 1);
+            let domain: String = domain_separator[..significant]
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            let words = constant.io_pattern().encoded_words();
+
+            source.push_str(&format!(
+                "    /// @notice `{}`, domain separator {} (zero padded).\n",
+                constant.name(),
+                constant.domain_hex()
+            ));
+            source.push_str(&format!(
+                "    bytes{} internal constant TAG_{} = {};\n",
+                constant.tag_length().bytes(),
+                upper,
+                format_tag(constant.tag())
+            ));
+            source.push_str(&format!(
+                "    bytes internal constant DOMAIN_{} = hex\"{}\";\n\n",
+                upper, domain
+            ));
+            source.push_str(&format!(
+                "    function ioPattern{}() internal pure returns (uint32[] memory ioPattern) {{\n\
+                 \x20       ioPattern = new uint32[]({});\n",
+                camel_case(constant.name()),
+                words.len()
+            ));
+            for (index, word) in words.iter().enumerate() {
+                source.push_str(&format!("        ioPattern[{}] = 0x{:08x};\n", index, word));
+            }
+            source.push_str("    }\n\n");
+        }
+        source.push_str(SOLIDITY_COMPUTE_TAG);
+        source.push_str("}\n");
+        Ok(source)
+    }
+}
+
+const SOLIDITY_COMPUTE_TAG: &str = r#"    /// @notice Computes the SAFE tag of an encoded IO pattern under a domain separator of at
+    /// most 64 bytes, zero padded: the first 16 bytes of the SHA-256 of the aggregated words,
+    /// big-endian, and the padded domain separator.
+    function computeTag(uint32[] memory ioPattern, bytes memory domainSeparator)
+        internal
+        pure
+        returns (bytes16)
+    {
+        require(domainSeparator.length <= 64, "SafeTags: domain separator over 64 bytes");
+        uint32[] memory words = aggregateWords(ioPattern);
+        bytes memory preimage = new bytes(4 * words.length + 64);
+        for (uint256 i = 0; i < words.length; i++) {
+            for (uint256 j = 0; j < 4; j++) {
+                preimage[4 * i + j] = bytes1(uint8(words[i] >> (24 - 8 * j)));
+            }
+        }
+        for (uint256 i = 0; i < domainSeparator.length; i++) {
+            preimage[4 * words.length + i] = domainSeparator[i];
+        }
+        return bytes16(sha256(preimage));
+    }
+
+    /// @notice Returns whether `tag` is the tag of `ioPattern` under `domainSeparator`.
+    function verifyTag(uint32[] memory ioPattern, bytes memory domainSeparator, bytes16 tag)
+        internal
+        pure
+        returns (bool)
+    {
+        return computeTag(ioPattern, domainSeparator) == tag;
+    }
+
+    /// @notice Merges consecutive operations of the same type, skipping the zero word. The
+    /// open group starts as an empty SQUEEZE, as in the Rust and Noir implementations.
+    function aggregateWords(uint32[] memory ioPattern) internal pure returns (uint32[] memory words) {
+        words = new uint32[](ioPattern.length);
+        uint256 count = 0;
+        bool isAbsorb = false;
+        uint32 length = 0;
+        for (uint256 i = 0; i < ioPattern.length; i++) {
+            uint32 word = ioPattern[i];
+            if (word == 0) {
+                continue;
+            }
+            bool wordIsAbsorb = (word & 0x80000000) != 0;
+            if (wordIsAbsorb == isAbsorb) {
+                length += word & 0x7fffffff;
+                continue;
+            }
+            if (length != 0) {
+                words[count++] = encodeOperation(isAbsorb, length);
+            }
+            isAbsorb = wordIsAbsorb;
+            length = word & 0x7fffffff;
+        }
+        if (length != 0) {
+            words[count++] = encodeOperation(isAbsorb, length);
+        }
+        // Shrink the array to the aggregated words.
+        assembly {
+            mstore(words, count)
+        }
+    }
+
+    function encodeOperation(bool isAbsorb, uint32 length) private pure returns (uint32) {
+        return isAbsorb ? 0x80000000 | length : length;
+    }
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::tests::merkle_node;
+
+    #[test]
+    fn test_solidity_library() {
+        let source = SolidityTarget
+            .emit(&Namespace::new("acme/wallet").unwrap(), &[merkle_node()])
+            .unwrap();
+
+        assert!(source.contains("\nlibrary AcmeWalletSafeTags {\n"));
+        assert!(
+            source.contains("    bytes internal constant DOMAIN_MERKLE_NODE = hex\"41424344\";\n")
+        );
+        assert!(source.contains(
+            "    function ioPatternMerkleNode() internal pure returns (uint32[] memory ioPattern) {\n\
+             \x20       ioPattern = new uint32[](3);\n\
+             \x20       ioPattern[0] = 0x80000001;\n\
+             \x20       ioPattern[1] = 0x80000001;\n\
+             \x20       ioPattern[2] = 0x00000001;\n\
+             \x20   }\n"
+        ));
+        assert!(source.ends_with(&format!("{}}}\n", SOLIDITY_COMPUTE_TAG)));
+    }
+}
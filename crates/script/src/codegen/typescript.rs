@@ -0,0 +1,268 @@
+//! The TypeScript target, for frontends running sponges with `safe-api-wasm`.
+//!
+//! The module exports, for each constant, its IO pattern as the words taken by the `IoPattern`
+//! and `computeTag` bindings, and its domain separator and tag as the hex strings they take
+//! and return:
+//!
+//! ```ts
+//! /** `merkle_node`, domain separator 0x41424344 (zero padded). */
+//! export const IO_PATTERN_MERKLE_NODE: readonly number[] = [0x80000001, 0x80000001, 0x00000001];
+//! export const DOMAIN_MERKLE_NODE = "0x41424344";
+//! export const TAG_MERKLE_NODE = "0x08e2da1eb5257f918e9c15b5605a3516";
+//! ```
+//!
+//! A protocol with challenges also gets an interface of them, in squeeze order, a function
+//! squeezing each from a `Sponge` of the bindings, typed by its length and throwing unless it
+//! is the next operation, and a function squeezing them all:
+//!
+//! ```ts
+//! /** The challenges of `merkle_node`, in squeeze order. */
+//! export interface MerkleNodeChallenges {
+//!   readonly alpha: readonly [string];
+//! }
+//!
+//! /** Squeezes `alpha`, the SQUEEZE(1) of `merkle_node`, which must be the next operation. */
+//! export function squeezeMerkleNodeAlpha(sponge: Sponge): [string] { ... }
+//!
+//! /** Squeezes the challenges of `merkle_node` in order, absorbing the inputs between them, from a sponge whose next operation is `alpha`. */
+//! export function merkleNodeChallenges(sponge: Sponge): MerkleNodeChallenges { ... }
+//! ```
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::{
+    camel_case, check_unique, ChallengeStep, CodegenError, CodegenTarget, Namespace, TagConstant,
+};
+use crate::display::format_tag;
+
+/// The TypeScript module of the [module documentation](self).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TypeScriptTarget;
+
+impl CodegenTarget for TypeScriptTarget {
+    fn name(&self) -> &str {
+        "typescript"
+    }
+
+    fn extension(&self) -> &str {
+        "ts"
+    }
+
+    fn emit(
+        &self,
+        namespace: &Namespace,
+        constants: &[TagConstant],
+    ) -> Result<String, CodegenError> {
+        check_unique(constants)?;
+
+        let mut source = String::from(
+            "// Generated by `safe-api codegen build --target typescript`. Do not edit.\n",
+        );
+        if constants
+            .iter()
+            .any(|constant| !constant.challenges().is_empty())
+        {
+            source.push_str(TYPESCRIPT_SPONGE);
+        }
+        for constant in constants {
+            let words: Vec<String> = constant
+                .io_pattern()
+                .encoded_words()
+                .iter()
+                .map(|word| format!("0x{:08x}", word))
+                .collect();
+            source.push_str(&format!(
+                "\n/** `{}`, domain separator {} (zero padded). */\n\
+                 export const {}: readonly number[] = [{}];\n\
+                 export const {} = \"{}\";\n\
+                 export const {} = \"{}\";\n",
+                constant.name(),
+                constant.domain_hex(),
+                namespace.prefix_upper(&format!("io_pattern_{}", constant.name())),
+                words.join(", "),
+                namespace.prefix_upper(&format!("domain_{}", constant.name())),
+                constant.domain_hex(),
+                namespace.prefix_upper(&format!("tag_{}", constant.name())),
+                format_tag(constant.tag())
+            ));
+
+            let challenges = constant.challenges();
+            if challenges.is_empty() {
+                continue;
+            }
+            let challenges_type = format!(
+                "{}{}Challenges",
+                namespace.camel_case(),
+                camel_case(constant.name())
+            );
+            let accessor = |label: &str| {
+                lower_camel_case(&namespace.prefix(&format!(
+                    "squeeze_{}_{}",
+                    constant.name(),
+                    label
+                )))
+            };
+            source.push_str(&format!(
+                "\n/** The challenges of `{}`, in squeeze order. */\n\
+                 export interface {} {{\n",
+                constant.name(),
+                challenges_type
+            ));
+            for (label, length) in &challenges {
+                source.push_str(&format!(
+                    "  readonly {}: readonly {};\n",
+                    label,
+                    tuple(*length)
+                ));
+            }
+            source.push_str("}\n");
+            for (label, length) in &challenges {
+                source.push_str(&format!(
+                    "\n/** Squeezes `{}`, the SQUEEZE({}) of `{}`, which must be the next operation. */\n\
+                     export function {}(sponge: Sponge): {} {{\n\
+                     \x20 if (sponge.operationsDone() !== {}) {{\n\
+                     \x20   throw new Error(\"the next operation is not `{}`\");\n\
+                     \x20 }}\n\
+                     \x20 return sponge.squeeze() as {};\n\
+                     }}\n",
+                    label,
+                    length,
+                    constant.name(),
+                    accessor(label),
+                    tuple(*length),
+                    constant
+                        .io_pattern()
+                        .squeeze_index(label)
+                        .expect("a challenge"),
+                    label,
+                    tuple(*length)
+                ));
+            }
+
+            let Some(steps) = constant.challenge_steps() else {
+                continue;
+            };
+            let mut arguments = String::from("sponge: Sponge");
+            let mut body = String::new();
+            for step in &steps {
+                match step {
+                    ChallengeStep::Squeeze(label) => body.push_str(&format!(
+                        "  const {} = {}(sponge);\n",
+                        label,
+                        accessor(label)
+                    )),
+                    ChallengeStep::Absorb(name, length) => {
+                        let name = lower_camel_case(name);
+                        arguments.push_str(&format!(", {}: readonly {}", name, tuple(*length)));
+                        body.push_str(&format!("  sponge.absorb({});\n", name));
+                    }
+                }
+            }
+            let labels: Vec<&str> = challenges.iter().map(|(label, _)| *label).collect();
+            source.push_str(&format!(
+                "\n/** Squeezes the challenges of `{}` in order, absorbing the inputs between them, from a sponge whose next operation is `{}`. */\n\
+                 export function {}({}): {} {{\n\
+                 {}\
+                 \x20 return {{ {} }};\n\
+                 }}\n",
+                constant.name(),
+                labels[0],
+                lower_camel_case(&namespace.prefix(&format!("{}_challenges", constant.name()))),
+                arguments,
+                challenges_type,
+                body,
+                labels.join(", ")
+            ));
+        }
+        Ok(source)
+    }
+}
+
+/// Returns the tuple type of `length` hex field elements, e.g. `[string, string]`.
+fn tuple(length: u32) -> String {
+    format!("[{}]", vec!["string"; length as usize].join(", "))
+}
+
+/// Returns a `snake_case` identifier in `lowerCamelCase`.
+fn lower_camel_case(identifier: &str) -> String {
+    let camel = camel_case(identifier);
+    let mut chars = camel.chars();
+    chars.next().map_or(String::new(), |first| {
+        first.to_ascii_lowercase().to_string() + chars.as_str()
+    })
+}
+
+const TYPESCRIPT_SPONGE: &str = "
+/** The `Sponge` of `safe-api-wasm`, absorbing and squeezing hex field elements. */
+export interface Sponge {
+  operationsDone(): number;
+  absorb(input: readonly string[]): void;
+  squeeze(): string[];
+}
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::tests::merkle_node;
+    use crate::io_pattern::IoPattern;
+
+    #[test]
+    fn test_typescript_module() {
+        let node = merkle_node();
+        let challenged = IoPattern::new()
+            .absorb(2)
+            .squeeze_labeled("alpha", 1)
+            .absorb(1)
+            .squeeze_labeled("beta", 2);
+        let domain_separator = *node.domain_separator();
+        let constants = [
+            node,
+            TagConstant::new("sigma", challenged, domain_separator).unwrap(),
+        ];
+        let source = TypeScriptTarget
+            .emit(&Namespace::default(), &constants[..1])
+            .unwrap();
+        assert_eq!(
+            source,
+            "// Generated by `safe-api codegen build --target typescript`. Do not edit.\n\
+             \n\
+             /** `merkle_node`, domain separator 0x41424344 (zero padded). */\n\
+             export const IO_PATTERN_MERKLE_NODE: readonly number[] = [0x80000001, 0x80000001, 0x00000001];\n\
+             export const DOMAIN_MERKLE_NODE = \"0x41424344\";\n\
+             export const TAG_MERKLE_NODE = \"0x08e2da1eb5257f918e9c15b5605a3516\";\n"
+        );
+
+        let source = TypeScriptTarget
+            .emit(&Namespace::new("acme").unwrap(), &constants)
+            .unwrap();
+        assert!(source.contains(TYPESCRIPT_SPONGE));
+        assert!(source.contains(
+            "export interface AcmeSigmaChallenges {\n\
+             \x20 readonly alpha: readonly [string];\n\
+             \x20 readonly beta: readonly [string, string];\n\
+             }\n"
+        ));
+        assert!(source.contains(
+            "export function acmeSqueezeSigmaBeta(sponge: Sponge): [string, string] {\n\
+             \x20 if (sponge.operationsDone() !== 3) {\n\
+             \x20   throw new Error(\"the next operation is not `beta`\");\n\
+             \x20 }\n\
+             \x20 return sponge.squeeze() as [string, string];\n\
+             }\n"
+        ));
+        assert!(source.ends_with(
+            "export function acmeSigmaChallenges(sponge: Sponge, absorb2: readonly [string]): AcmeSigmaChallenges {\n\
+             \x20 const alpha = acmeSqueezeSigmaAlpha(sponge);\n\
+             \x20 sponge.absorb(absorb2);\n\
+             \x20 const beta = acmeSqueezeSigmaBeta(sponge);\n\
+             \x20 return { alpha, beta };\n\
+             }\n"
+        ));
+        // Only the protocol with challenges has accessors.
+        assert_eq!(source.matches("export function").count(), 3);
+    }
+}
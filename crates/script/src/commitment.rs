@@ -0,0 +1,155 @@
+//! Hiding commitments to vectors of field elements.
+//!
+//! [`commit`] absorbs the randomness, then the values, and squeezes the commitment, with the
+//! pattern of [`commitment_pattern`] under the domain separator of
+//! [`commitment_domain_separator`], which binds the application domain to this scheme. The
+//! commitment is binding as long as the sponge is collision resistant, and hiding when the
+//! randomness is uniform and secret, e.g. drawn by [`commit_random`]. The committer later
+//! reveals the [`Opening`], which anyone checks with [`verify`]:
+//!
+//! ```
+//! use ark_bn254::Fr;
+//! use safe_api::commitment::{commit, open, verify};
+//! use safe_api::poseidon2::Poseidon2;
+//!
+//! let values = [Fr::from(7u64), Fr::from(42u64)];
+//! let randomness = Fr::from(123456789u64); // Drawn at random in practice.
+//! let commitment = commit::<Poseidon2>(&values, randomness, b"my-app/bid");
+//! let opening = open(&values, randomness);
+//! assert!(verify::<Poseidon2>(&commitment, &opening, b"my-app/bid"));
+//! ```
+//!
+//! The Noir `verify_commitment` checks an opening in a circuit, given the derived domain
+//! separator.
+
+use alloc::vec::Vec;
+
+use ark_ff::UniformRand;
+use ark_std::rand::{CryptoRng, RngCore};
+use sha2::{Digest, Sha512};
+
+use crate::io_pattern::IoPattern;
+use crate::permutation::Permutation;
+use crate::secret::ct_eq;
+use crate::sponge::Sponge;
+
+/// Prefix of the hashed commitment domain separator, versioning the scheme.
+const COMMITMENT_DOMAIN_PREFIX: &[u8] = b"SAFE-COMMITMENT-V1";
+
+/// A commitment to a vector of field elements.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Commitment<F>(pub F);
+
+/// What the committer reveals to open a [`Commitment`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Opening<F> {
+    pub values: Vec<F>,
+    pub randomness: F,
+}
+
+/// IO pattern of a commitment to `count` values: the randomness, the values, then the
+/// commitment.
+pub fn commitment_pattern(count: usize) -> IoPattern {
+    IoPattern::new().absorb(1).absorb(count as u32).squeeze(1)
+}
+
+/// Derives the domain separator of commitments in the application `domain`: SHA-512 over a
+/// versioned prefix, the big-endian 32-bit length of `domain` and `domain`.
+pub fn commitment_domain_separator(domain: &[u8]) -> [u8; 64] {
+    let mut hasher = Sha512::new();
+    hasher.update(COMMITMENT_DOMAIN_PREFIX);
+    hasher.update((domain.len() as u32).to_be_bytes());
+    hasher.update(domain);
+    hasher.finalize().into()
+}
+
+/// Commits to `values` with `randomness` in the application `domain`.
+pub fn commit<P: Permutation + Default>(
+    values: &[P::Field],
+    randomness: P::Field,
+    domain: &[u8],
+) -> Commitment<P::Field> {
+    let mut sponge = Sponge::<P>::start(
+        commitment_pattern(values.len()),
+        commitment_domain_separator(domain),
+    );
+    sponge.absorb(&[randomness]);
+    sponge.absorb(values);
+    let commitment = sponge.squeeze()[0];
+    sponge.finish();
+    Commitment(commitment)
+}
+
+/// Commits to `values` with randomness drawn from `rng`, and returns the commitment with its
+/// opening.
+pub fn commit_random<P: Permutation + Default, R: RngCore + CryptoRng>(
+    values: &[P::Field],
+    domain: &[u8],
+    rng: &mut R,
+) -> (Commitment<P::Field>, Opening<P::Field>) {
+    let randomness = P::Field::rand(rng);
+    (
+        commit::<P>(values, randomness, domain),
+        open(values, randomness),
+    )
+}
+
+/// Returns the opening of the commitment to `values` with `randomness`.
+pub fn open<F: Copy>(values: &[F], randomness: F) -> Opening<F> {
+    Opening {
+        values: values.to_vec(),
+        randomness,
+    }
+}
+
+/// Checks that `opening` opens `commitment` in the application `domain`, in constant time.
+pub fn verify<P: Permutation + Default>(
+    commitment: &Commitment<P::Field>,
+    opening: &Opening<P::Field>,
+    domain: &[u8],
+) -> bool {
+    ct_eq(
+        &commit::<P>(&opening.values, opening.randomness, domain).0,
+        &commitment.0,
+    )
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::poseidon2::Poseidon2;
+    use ark_bn254::Fr;
+    use ark_std::rand::rngs::StdRng;
+    use ark_std::rand::SeedableRng;
+    use core::str::FromStr;
+
+    #[test]
+    fn test_binding() {
+        let values = [Fr::from(1u64), Fr::from(2u64)];
+        let (commitment, opening) =
+            commit_random::<Poseidon2, _>(&values, b"test", &mut StdRng::seed_from_u64(1));
+        assert!(verify::<Poseidon2>(&commitment, &opening, b"test"));
+
+        let mut other = opening.clone();
+        other.values[1] = Fr::from(3u64);
+        assert!(!verify::<Poseidon2>(&commitment, &other, b"test"));
+        let mut other = opening.clone();
+        other.randomness += Fr::from(1u64);
+        assert!(!verify::<Poseidon2>(&commitment, &other, b"test"));
+        assert!(!verify::<Poseidon2>(&commitment, &opening, b"other"));
+    }
+
+    #[test]
+    fn test_matches_noir() {
+        // Same values as `test_verify_commitment` of the Noir implementation, which takes the
+        // derived domain separator as input.
+        assert_eq!(commitment_domain_separator(b"test")[..4], [26, 30, 201, 74]);
+        let commitment =
+            commit::<Poseidon2>(&[Fr::from(1u64), Fr::from(2u64)], Fr::from(3u64), b"test");
+        let expected = Fr::from_str(
+            "5906845616908952731475010540500379463447291981535857983052685850386841573368",
+        )
+        .unwrap();
+        assert_eq!(commitment, Commitment(expected));
+    }
+}
@@ -0,0 +1,304 @@
+//! Explicit sponge parameters.
+//!
+//! The rate and capacity of a sponge are those of its [`Permutation`], so picking the wrong
+//! backend, or one whose capacity is too small for the field, weakens the sponge without any
+//! error. A [`SpongeConfig`] states the parameters an application expects, the rate, the
+//! capacity, the field modulus and the targeted security level, and
+//! [`SpongeBuilder::config`](crate::sponge::SpongeBuilder::config) checks them against the
+//! permutation when the builder is set up:
+//!
+//! ```
+//! use safe_api::config::{ConfigError, SpongeConfig};
+//! use safe_api::poseidon2::Poseidon2;
+//! use safe_api::{IoPattern, Sponge};
+//!
+//! // One BN254 element of capacity gives 127 bits of security.
+//! let config = SpongeConfig::of::<Poseidon2>(127);
+//! let sponge = Sponge::<Poseidon2>::builder()
+//!     .config(config)?
+//!     .start(IoPattern::new().absorb(3).squeeze(1), &[1u8; 64]);
+//! # let _ = sponge;
+//!
+//! assert_eq!(
+//!     Sponge::<Poseidon2>::builder().config(SpongeConfig::of::<Poseidon2>(128)).err(),
+//!     Some(ConfigError::InsufficientSecurity { provided: 127, required: 128 })
+//! );
+//! # Ok::<(), ConfigError>(())
+//! ```
+//!
+//! As the spec recommends for the parameters of the instance, the configuration is folded
+//! into the domain separator ([`SpongeConfig::domain_separator`]): the sponge runs under
+//! SHA-512 over a versioned prefix, the domain separator of the application and the
+//! parameters, big-endian:
+//!
+//! | Bytes | Content                              |
+//! |-------|--------------------------------------|
+//! | 14    | `SAFE-CONFIG-V1`                     |
+//! | 64    | the domain separator                 |
+//! | 4, 4  | rate, capacity                       |
+//! | 4     | length `k` of the modulus in bytes   |
+//! | `k`   | the modulus of the field             |
+//! | 4     | security level in bits               |
+//!
+//! A configured sponge therefore has another tag than an unconfigured one, and the circuit
+//! verifying it starts from the derived domain separator. For the same reason, a saved
+//! configured sponge does not resume with [`Sponge::resume`](crate::Sponge::resume) under the
+//! domain separator of the application, but with
+//! [`SpongeBuilder::resume`](crate::sponge::SpongeBuilder::resume) on a builder with the same
+//! configuration.
+//!
+//! The security level of a sponge is bounded by half the bits of its capacity, `c·⌈log₂ p⌉/2`
+//! for a capacity of `c` elements of a field of modulus `p` ([`SpongeConfig::provided_security_bits`]).
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use ark_ff::{BigInteger, PrimeField};
+use sha2::{Digest, Sha512};
+
+use crate::permutation::Permutation;
+
+/// Prefix of the hashed parameters of [`SpongeConfig::domain_separator`], versioning its
+/// layout.
+const CONFIG_DOMAIN_PREFIX: &[u8] = b"SAFE-CONFIG-V1";
+
+/// The parameters a sponge is expected to run with, see the [module documentation](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpongeConfig {
+    rate: usize,
+    capacity: usize,
+    modulus: Vec<u8>,
+    security_bits: u32,
+}
+
+/// Errors raised when a [`SpongeConfig`] does not fit a permutation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The rate and capacity of the configuration are not those of the permutation.
+    WrongSplit {
+        /// Rate and capacity of the configuration.
+        expected: (usize, usize),
+        /// Rate and capacity of the permutation.
+        found: (usize, usize),
+    },
+    /// The modulus of the configuration is not the one of the field of the permutation.
+    WrongField,
+    /// The capacity provides fewer bits of security than the configuration targets.
+    InsufficientSecurity {
+        /// Security level of the capacity, in bits.
+        provided: u32,
+        /// Security level of the configuration, in bits.
+        required: u32,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::WrongSplit { expected, found } => write!(
+                f,
+                "the permutation has rate {} and capacity {}, not rate {} and capacity {}",
+                found.0, found.1, expected.0, expected.1
+            ),
+            ConfigError::WrongField => {
+                f.write_str("the permutation runs over another field than the configuration")
+            }
+            ConfigError::InsufficientSecurity { provided, required } => write!(
+                f,
+                "the capacity provides {} bits of security, fewer than the {} required",
+                provided, required
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ConfigError {}
+
+impl SpongeConfig {
+    /// Returns the configuration of `rate` and `capacity` elements of the field of modulus
+    /// `modulus` (big-endian, without leading zeros), targeting `security_bits` bits of
+    /// security.
+    pub fn new(rate: usize, capacity: usize, modulus: &[u8], security_bits: u32) -> Self {
+        let leading_zeros = modulus.iter().take_while(|&&byte| byte == 0).count();
+        SpongeConfig {
+            rate,
+            capacity,
+            modulus: modulus[leading_zeros..].to_vec(),
+            security_bits,
+        }
+    }
+
+    /// Returns the configuration of the permutation `P`, targeting `security_bits` bits of
+    /// security.
+    pub fn of<P: Permutation>(security_bits: u32) -> Self {
+        Self::new(
+            P::RATE,
+            P::CAPACITY,
+            &P::Field::MODULUS.to_bytes_be(),
+            security_bits,
+        )
+    }
+
+    /// Returns the rate, in field elements.
+    pub fn rate(&self) -> usize {
+        self.rate
+    }
+
+    /// Returns the capacity, in field elements.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the modulus of the field, big-endian.
+    pub fn modulus(&self) -> &[u8] {
+        &self.modulus
+    }
+
+    /// Returns the targeted security level, in bits.
+    pub fn security_bits(&self) -> u32 {
+        self.security_bits
+    }
+
+    /// Returns the security level the capacity provides, in bits: half the bits of the
+    /// capacity.
+    pub fn provided_security_bits(&self) -> u32 {
+        let modulus_bits = match self.modulus.first() {
+            Some(&byte) => self.modulus.len() * 8 - byte.leading_zeros() as usize,
+            None => 0,
+        };
+        (self.capacity * modulus_bits / 2) as u32
+    }
+
+    /// Checks that the configuration is the one of the permutation `P` and that its capacity
+    /// reaches the targeted security level.
+    pub fn validate<P: Permutation>(&self) -> Result<(), ConfigError> {
+        if (self.rate, self.capacity) != (P::RATE, P::CAPACITY) {
+            return Err(ConfigError::WrongSplit {
+                expected: (self.rate, self.capacity),
+                found: (P::RATE, P::CAPACITY),
+            });
+        }
+        if self.modulus != Self::of::<P>(self.security_bits).modulus {
+            return Err(ConfigError::WrongField);
+        }
+        if self.provided_security_bits() < self.security_bits {
+            return Err(ConfigError::InsufficientSecurity {
+                provided: self.provided_security_bits(),
+                required: self.security_bits,
+            });
+        }
+        Ok(())
+    }
+
+    /// Derives the domain separator a sponge of this configuration runs under from the
+    /// domain separator of the application, see the [module documentation](self).
+    pub fn domain_separator(&self, domain_separator: &[u8; 64]) -> [u8; 64] {
+        let mut hasher = Sha512::new();
+        hasher.update(CONFIG_DOMAIN_PREFIX);
+        hasher.update(domain_separator);
+        hasher.update((self.rate as u32).to_be_bytes());
+        hasher.update((self.capacity as u32).to_be_bytes());
+        hasher.update((self.modulus.len() as u32).to_be_bytes());
+        hasher.update(&self.modulus);
+        hasher.update(self.security_bits.to_be_bytes());
+        hasher.finalize().into()
+    }
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::domain::padded_domain_separator;
+    use crate::poseidon2::Poseidon2;
+    use crate::state::StateError;
+    use crate::{IoPattern, Sponge};
+    use ark_bn254::Fr;
+
+    #[test]
+    fn test_validate() {
+        let config = SpongeConfig::of::<Poseidon2>(100);
+        assert_eq!((config.rate(), config.capacity()), (3, 1));
+        assert_eq!(config.security_bits(), 100);
+        assert_eq!(config.provided_security_bits(), 127);
+        assert_eq!(config.validate::<Poseidon2>(), Ok(()));
+
+        assert_eq!(
+            SpongeConfig::new(2, 2, config.modulus(), 100).validate::<Poseidon2>(),
+            Err(ConfigError::WrongSplit {
+                expected: (2, 2),
+                found: (3, 1)
+            })
+        );
+        // Leading zeros do not change the modulus.
+        let mut padded = vec![0u8];
+        padded.extend_from_slice(config.modulus());
+        assert_eq!(SpongeConfig::new(3, 1, &padded, 100), config);
+        assert_eq!(
+            SpongeConfig::new(3, 1, &config.modulus()[1..], 100).validate::<Poseidon2>(),
+            Err(ConfigError::WrongField)
+        );
+        assert_eq!(
+            SpongeConfig::of::<Poseidon2>(128).validate::<Poseidon2>(),
+            Err(ConfigError::InsufficientSecurity {
+                provided: 127,
+                required: 128
+            })
+        );
+    }
+
+    #[test]
+    fn test_folded_into_domain_separator() {
+        let domain_separator = padded_domain_separator(b"config");
+        let pattern = IoPattern::new().absorb(1).squeeze(1);
+        let config = SpongeConfig::of::<Poseidon2>(100);
+        let derived = config.domain_separator(&domain_separator);
+        assert_ne!(
+            derived,
+            SpongeConfig::of::<Poseidon2>(99).domain_separator(&domain_separator)
+        );
+
+        let run = |sponge: &mut Sponge<Poseidon2>| {
+            sponge.absorb(&[Fr::from(1u64)]);
+            sponge.squeeze()
+        };
+        let mut configured = Sponge::<Poseidon2>::builder()
+            .config(config)
+            .unwrap()
+            .start(pattern.clone(), domain_separator);
+        let mut derived = Sponge::<Poseidon2>::start(pattern.clone(), derived);
+        let mut unconfigured = Sponge::<Poseidon2>::start(pattern, domain_separator);
+        assert_eq!(configured.tag(), derived.tag());
+        assert_ne!(configured.tag(), unconfigured.tag());
+        assert_eq!(run(&mut configured), run(&mut derived));
+        run(&mut unconfigured);
+    }
+
+    #[test]
+    fn test_resume_configured() {
+        let domain_separator = padded_domain_separator(b"config");
+        let pattern = IoPattern::new().absorb(1).squeeze(1);
+        let builder = || {
+            Sponge::<Poseidon2>::builder()
+                .config(SpongeConfig::of::<Poseidon2>(100))
+                .unwrap()
+        };
+        let mut sponge = builder().start(pattern, domain_separator);
+        sponge.absorb(&[Fr::from(1u64)]);
+        let saved = sponge.save();
+        let snapshot = sponge.export_state();
+        let expected = sponge.squeeze();
+        sponge.finish();
+
+        assert_eq!(
+            Sponge::<Poseidon2>::resume(saved.clone(), domain_separator).err(),
+            Some(StateError::TagMismatch)
+        );
+        let mut resumed = builder().resume(saved, domain_separator).unwrap();
+        assert_eq!(resumed.squeeze(), expected);
+        resumed.finish();
+        let mut imported = builder().import_state(snapshot, domain_separator).unwrap();
+        assert_eq!(imported.squeeze(), expected);
+        imported.finish();
+    }
+}
@@ -0,0 +1,212 @@
+//! Coverage of the registered protocols by a test suite.
+//!
+//! Generating the tag constant of a new protocol does not test it: nothing fails when no test
+//! ever runs a transcript of its pattern. With the `test-utils` feature, every sponge that
+//! [`finish`](crate::Sponge::finish)es, i.e. runs its whole IO pattern, is recorded for the
+//! rest of the process, and [`report`] tells which of the protocols of a manifest (as the
+//! [`TagConstant`]s of [`codegen`](crate::codegen)) were run end to end:
+//!
+//! ```
+//! use safe_api::codegen::TagConstant;
+//! use safe_api::poseidon2::Poseidon2;
+//! use safe_api::{coverage, IoPattern, Sponge};
+//! # use ark_bn254::Fr;
+//!
+//! let mut domain_separator = [0u8; 64];
+//! domain_separator[..4].copy_from_slice(b"ABCD");
+//! let hashing = IoPattern::new().absorb(3).squeeze(1);
+//! let protocols = [
+//!     TagConstant::new("hashing", hashing.clone(), domain_separator).unwrap(),
+//!     TagConstant::new("untested", IoPattern::new().absorb(1).squeeze(7), domain_separator)
+//!         .unwrap(),
+//! ];
+//!
+//! let mut sponge = Sponge::<Poseidon2>::start(hashing, &domain_separator);
+//! sponge.absorb(&[Fr::from(1u64), Fr::from(2u64), Fr::from(3u64)]);
+//! sponge.squeeze();
+//! sponge.finish();
+//!
+//! let report = coverage::report(&protocols);
+//! assert_eq!(report.uncovered(), ["untested"]);
+//! println!("{}", report);
+//! ```
+//!
+//! A session counts for a protocol when the sponge was initialized with its tag, in any field
+//! and through any wrapper of the sponge. A [`Transcript`](crate::Transcript) runs under the
+//! domain separator it derives from the labels, so its protocol is declared with
+//! [`transcript_domain_separator`](crate::transcript::transcript_domain_separator).
+//! Sessions abandoned before the end of their pattern do not count, and neither do sponges
+//! with another tag derivation than the 128-bit SHA-256 tag of the spec, e.g. with
+//! [`SpongeBuilder::tag_length`](crate::sponge::SpongeBuilder::tag_length). Recording takes a
+//! lock on every `finish`, so the feature belongs in `dev-dependencies`.
+
+use std::fmt;
+use std::sync::Mutex;
+
+use ark_ff::PrimeField;
+
+use crate::codegen::TagConstant;
+use crate::tag::Tag;
+
+/// Whether a finished session was initialized with a tag, in the field of its sponge.
+type Session = Box<dyn Fn(&Tag) -> bool + Send>;
+
+static SESSIONS: Mutex<Vec<Session>> = Mutex::new(Vec::new());
+
+fn sessions() -> std::sync::MutexGuard<'static, Vec<Session>> {
+    // Sessions are only ever pushed, so a panic elsewhere leaves them usable.
+    SESSIONS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Records a finished session of a sponge initialized with `tag`.
+pub(crate) fn record<F: PrimeField>(tag: F) {
+    sessions().push(Box::new(move |protocol: &Tag| {
+        protocol.to_field::<F>() == tag
+    }));
+}
+
+/// The sessions run for one protocol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProtocolCoverage {
+    /// Name of the protocol.
+    pub name: String,
+    /// The tag of the protocol.
+    pub tag: Tag,
+    /// Number of finished sessions initialized with the tag.
+    pub sessions: usize,
+}
+
+/// The coverage of a list of protocols, see [`report`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CoverageReport {
+    /// One entry per protocol, in the order of the list.
+    pub protocols: Vec<ProtocolCoverage>,
+}
+
+impl CoverageReport {
+    /// Returns the names of the protocols without any finished session.
+    pub fn uncovered(&self) -> Vec<&str> {
+        self.protocols
+            .iter()
+            .filter(|protocol| protocol.sessions == 0)
+            .map(|protocol| protocol.name.as_str())
+            .collect()
+    }
+
+    /// Returns whether every protocol ran at least once.
+    pub fn is_complete(&self) -> bool {
+        self.protocols.iter().all(|protocol| protocol.sessions > 0)
+    }
+}
+
+impl fmt::Display for CoverageReport {
+    /// Prints one `name tag sessions` line per protocol, then the number of protocols covered.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .protocols
+            .iter()
+            .map(|protocol| protocol.name.len())
+            .max()
+            .unwrap_or(0);
+        for protocol in &self.protocols {
+            let status = if protocol.sessions == 0 {
+                "NOT COVERED".to_string()
+            } else {
+                format!("{} sessions", protocol.sessions)
+            };
+            writeln!(
+                f,
+                "{:width$}  {}  {}",
+                protocol.name,
+                protocol.tag,
+                status,
+                width = width
+            )?;
+        }
+        let covered = self.protocols.len() - self.uncovered().len();
+        write!(f, "{}/{} protocols covered", covered, self.protocols.len())
+    }
+}
+
+/// Returns how many sessions of each of `protocols` finished so far in the process.
+pub fn report(protocols: &[TagConstant]) -> CoverageReport {
+    let sessions = sessions();
+    CoverageReport {
+        protocols: protocols
+            .iter()
+            .map(|protocol| {
+                let tag = protocol.tag();
+                ProtocolCoverage {
+                    name: protocol.name().to_string(),
+                    tag,
+                    sessions: sessions.iter().filter(|session| session(&tag)).count(),
+                }
+            })
+            .collect(),
+    }
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::domain::padded_domain_separator;
+    use crate::poseidon2::Poseidon2;
+    use crate::transcript::{transcript_domain_separator, Transcript};
+    use crate::{IoPattern, Sponge};
+    use ark_bn254::Fr;
+
+    #[test]
+    fn test_report() {
+        // Domain separators of this test only, since the tests of the process all record.
+        let finished = padded_domain_separator(b"coverage/finished");
+        let abandoned = padded_domain_separator(b"coverage/abandoned");
+        let pattern = IoPattern::new().absorb(1).squeeze(1);
+        let protocols = [
+            TagConstant::new("finished", pattern.clone(), finished).unwrap(),
+            TagConstant::new("abandoned", pattern.clone(), abandoned).unwrap(),
+        ];
+        assert_eq!(report(&protocols).uncovered(), ["finished", "abandoned"]);
+
+        for _ in 0..2 {
+            let mut sponge = Sponge::<Poseidon2>::start(pattern.clone(), finished);
+            sponge.absorb(&[Fr::from(1u64)]);
+            sponge.squeeze();
+            sponge.finish();
+        }
+        let mut sponge = Sponge::<Poseidon2>::start(pattern.clone(), abandoned);
+        sponge.absorb(&[Fr::from(1u64)]);
+
+        let report = report(&protocols);
+        assert_eq!(report.protocols[0].sessions, 2);
+        assert_eq!(report.uncovered(), ["abandoned"]);
+        assert!(!report.is_complete());
+        assert_eq!(
+            report.to_string(),
+            format!(
+                "finished   {}  2 sessions\nabandoned  {}  NOT COVERED\n1/2 protocols covered",
+                protocols[0].tag(),
+                protocols[1].tag()
+            )
+        );
+    }
+
+    #[test]
+    fn test_transcripts_count() {
+        let domain_separator = padded_domain_separator(b"coverage/transcript");
+        let pattern = IoPattern::new()
+            .absorb_labeled("message", 1)
+            .squeeze_labeled("challenge", 1);
+        let derived = transcript_domain_separator(&pattern, &domain_separator);
+        let protocols = [
+            TagConstant::new("transcript", pattern.clone(), derived).unwrap(),
+            TagConstant::new("underived", pattern.clone(), domain_separator).unwrap(),
+        ];
+        let mut transcript = Transcript::<Poseidon2>::new(pattern, domain_separator);
+        transcript.append("message", &[Fr::from(1u64)]);
+        transcript.challenge("challenge", 1);
+        transcript.finish();
+        assert_eq!(report(&protocols).uncovered(), ["underived"]);
+    }
+}
@@ -0,0 +1,149 @@
+//! Process-wide defaults of the sponges and transcripts.
+//!
+//! Every sponge and transcript starts from the policies of a [`Defaults`]: whether the empty
+//! domain separator is accepted, whether transcripts are sequenced, and how the tag is
+//! derived. [`SpongeBuilder::new`], and through it the convenience constructors
+//! ([`Sponge::start`], [`Transcript::new`], ...) and the gadgets of the crate, take those of
+//! the process, which an application sets once at startup with [`set_defaults`], before any
+//! sponge starts, instead of threading a builder through every call site:
+//!
+//! ```
+//! use safe_api::defaults::{self, Defaults};
+//! use safe_api::tag::TagLength;
+//!
+//! fn main() {
+//!     defaults::set_defaults(Defaults::new().tag_length(TagLength::Bits248))
+//!         .expect("the defaults are set before any sponge starts");
+//!     // ...
+//! }
+//! ```
+//!
+//! The defaults are read, and thereby frozen, by the first sponge of the process, so every
+//! sponge of a process runs under the same policies and a late [`set_defaults`] fails rather
+//! than taking effect halfway. The setters of [`SpongeBuilder`] override them for one sponge,
+//! and [`SpongeBuilder::with_defaults`] ignores them, e.g. for a library whose outputs must
+//! not depend on the application (`Defaults::new()` gives the built-in policies). Without
+//! the `std` feature the process defaults are the built-in ones.
+//!
+//! The field and the permutation are type parameters of the sponge, so they are not part of
+//! the defaults; an application names its choice once with a type alias such as
+//! `type AppSponge = Sponge<Poseidon2>`.
+//!
+//! [`SpongeBuilder`]: crate::sponge::SpongeBuilder
+//! [`SpongeBuilder::new`]: crate::sponge::SpongeBuilder::new
+//! [`SpongeBuilder::with_defaults`]: crate::sponge::SpongeBuilder::with_defaults
+//! [`Sponge::start`]: crate::Sponge::start
+//! [`Transcript::new`]: crate::Transcript::new
+
+use crate::tag::{TagHasher, TagLength, TagParameters};
+
+/// Policies of the sponges and transcripts, see the [module documentation](self).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Defaults {
+    pub(crate) allow_empty_domain: bool,
+    pub(crate) sequenced: bool,
+    pub(crate) tag_parameters: TagParameters,
+}
+
+impl Defaults {
+    /// Returns the built-in policies: a non-empty domain separator, unsequenced transcripts
+    /// and the 128-bit SHA-256 tag of the spec.
+    pub fn new() -> Self {
+        Defaults::default()
+    }
+
+    /// Accepts the empty domain separator, see
+    /// [`SpongeBuilder::allow_empty_domain`](crate::sponge::SpongeBuilder::allow_empty_domain).
+    pub fn allow_empty_domain(mut self) -> Self {
+        self.allow_empty_domain = true;
+        self
+    }
+
+    /// Sequences the transcripts, see
+    /// [`SpongeBuilder::sequenced`](crate::sponge::SpongeBuilder::sequenced).
+    pub fn sequenced(mut self) -> Self {
+        self.sequenced = true;
+        self
+    }
+
+    /// Derives the tag from `tag_length` bits of the digest, see
+    /// [`SpongeBuilder::tag_length`](crate::sponge::SpongeBuilder::tag_length).
+    pub fn tag_length(mut self, tag_length: TagLength) -> Self {
+        self.tag_parameters.length = tag_length;
+        self
+    }
+
+    /// Derives the tag with the hash `H`, see
+    /// [`SpongeBuilder::tag_hasher`](crate::sponge::SpongeBuilder::tag_hasher).
+    pub fn tag_hasher<H: TagHasher>(mut self) -> Self {
+        self.tag_parameters.set_hasher::<H>();
+        self
+    }
+
+    /// Returns whether the empty domain separator is accepted.
+    pub fn allows_empty_domain(&self) -> bool {
+        self.allow_empty_domain
+    }
+
+    /// Returns whether transcripts are sequenced.
+    pub fn is_sequenced(&self) -> bool {
+        self.sequenced
+    }
+}
+
+#[cfg(feature = "std")]
+static DEFAULTS: std::sync::OnceLock<Defaults> = std::sync::OnceLock::new();
+
+/// Sets the defaults of the process.
+///
+/// # Errors
+/// Returns `defaults` back if the defaults were already set, or already read by a sponge.
+#[cfg(feature = "std")]
+pub fn set_defaults(defaults: Defaults) -> Result<(), Defaults> {
+    DEFAULTS.set(defaults)
+}
+
+/// Returns the defaults of the process, freezing them.
+pub fn defaults() -> Defaults {
+    #[cfg(feature = "std")]
+    return *DEFAULTS.get_or_init(Defaults::new);
+    #[cfg(not(feature = "std"))]
+    Defaults::new()
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::domain::padded_domain_separator;
+    use crate::poseidon2::Poseidon2;
+    use crate::sponge::SpongeBuilder;
+    use crate::IoPattern;
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_frozen_once_read() {
+        // The tests of this process run under the built-in defaults.
+        let _ = set_defaults(Defaults::new());
+        assert_eq!(defaults().tag_parameters.length, TagLength::Bits128);
+        assert!(set_defaults(Defaults::new().sequenced()).is_err());
+        assert!(!defaults().is_sequenced());
+    }
+
+    #[test]
+    fn test_with_defaults() {
+        let pattern = IoPattern::new().absorb(1).squeeze(1);
+        let domain_separator = padded_domain_separator(b"defaults");
+        let explicit =
+            SpongeBuilder::with_defaults(Poseidon2, Defaults::new().tag_length(TagLength::Bits64))
+                .start(pattern.clone(), domain_separator);
+        let overridden = SpongeBuilder::new(Poseidon2)
+            .tag_length(TagLength::Bits64)
+            .start(pattern, domain_separator);
+        assert_eq!(explicit.tag(), overridden.tag());
+
+        let defaults = Defaults::new().allow_empty_domain().sequenced();
+        assert!(defaults.allows_empty_domain() && defaults.is_sequenced());
+        SpongeBuilder::with_defaults(Poseidon2, defaults)
+            .start(IoPattern::new().absorb(1).squeeze(1), [0; 64]);
+    }
+}
@@ -0,0 +1,112 @@
+//! Differential testing of the tag computation against the Noir circuit.
+//!
+//! Each case draws a random IO pattern and domain separator, writes them to the `Prover.toml` of
+//! the companion Noir project (`crates/difftest`), runs `nargo execute` and compares the tag
+//! returned by the circuit with [`compute_tag`].
+
+use std::path::Path;
+use std::process::Command;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use safe_api::compute_tag;
+use safe_api::display::{format_tag, parse_tag};
+use safe_api::tag::Tag;
+
+/// Number of IO pattern words taken by the companion circuit.
+pub const MAX_OPERATIONS: usize = 8;
+
+/// A randomly drawn tag computation.
+#[derive(Debug)]
+pub struct Case {
+    /// Encoded words, padded with SQUEEZE(0) to [`MAX_OPERATIONS`].
+    pub words: [u32; MAX_OPERATIONS],
+    pub domain_separator: [u8; 64],
+}
+
+impl Case {
+    /// Draws a pattern of 1 to [`MAX_OPERATIONS`] operations and a domain separator of 0 to 64
+    /// non-zero-padded bytes.
+    pub fn random(rng: &mut impl Rng) -> Self {
+        let mut words = [0u32; MAX_OPERATIONS];
+        for word in words.iter_mut().take(rng.gen_range(1..=MAX_OPERATIONS)) {
+            // Mostly short operations, including zero-length ones, and a few long ones.
+            let length = match rng.gen_range(0..8) {
+                0 => rng.gen_range(0..1 << 16),
+                _ => rng.gen_range(0..8),
+            };
+            let flag = if rng.gen() { 0x80000000 } else { 0 };
+            *word = flag | length;
+        }
+
+        let mut domain_separator = [0u8; 64];
+        let length = rng.gen_range(0..=64);
+        rng.fill(&mut domain_separator[..length]);
+
+        Case {
+            words,
+            domain_separator,
+        }
+    }
+
+    /// Renders the inputs of the companion circuit.
+    fn prover_toml(&self) -> String {
+        let list = |values: Vec<String>| values.join(", ");
+        format!(
+            "io_pattern = [{}]\ndomain_separator = [{}]\n",
+            list(self.words.iter().map(u32::to_string).collect()),
+            list(self.domain_separator.iter().map(u8::to_string).collect())
+        )
+    }
+}
+
+/// Runs `cases` random cases through the circuit in `program_dir`, returning the number of
+/// cases that agree or the first divergence.
+pub fn run(nargo: &str, program_dir: &Path, cases: usize, seed: u64) -> Result<usize, String> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    for index in 0..cases {
+        let case = Case::random(&mut rng);
+        let expected = compute_tag(&case.words, case.domain_separator);
+        let actual = execute(nargo, program_dir, &case)?;
+        if actual != expected {
+            return Err(format!(
+                "case {} (seed {}) diverges: circuit {}, compute_tag {}\n{}",
+                index,
+                seed,
+                format_tag(actual),
+                format_tag(expected),
+                case.prover_toml()
+            ));
+        }
+    }
+    Ok(cases)
+}
+
+/// Executes the companion circuit on one case and parses the returned tag.
+fn execute(nargo: &str, program_dir: &Path, case: &Case) -> Result<Tag, String> {
+    std::fs::write(program_dir.join("Prover.toml"), case.prover_toml())
+        .map_err(|error| format!("cannot write Prover.toml: {}", error))?;
+
+    let output = Command::new(nargo)
+        .arg("execute")
+        .arg("--program-dir")
+        .arg(program_dir)
+        .output()
+        .map_err(|error| format!("cannot run {}: {}", nargo, error))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() {
+        return Err(format!(
+            "nargo execute failed:\n{}{}",
+            stdout,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    let value = stdout
+        .lines()
+        .find_map(|line| line.split_once("Circuit output:"))
+        .map(|(_, value)| value.trim())
+        .ok_or_else(|| format!("no circuit output in:\n{}", stdout))?;
+    // The circuit prints the tag as a field element, which must fit in 128 bits.
+    parse_tag(value).map_err(|_| format!("unexpected circuit output `{}`", value))
+}
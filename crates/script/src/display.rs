@@ -0,0 +1,217 @@
+//! Display and parsing of field elements and tags, shared by the command line tool, the
+//! bindings and the error messages.
+//!
+//! - Tags are `0x` followed by 32 lowercase hex digits ([`format_tag`], [`parse_tag`]).
+//! - Field elements are `0x` followed by the lowercase big-endian hex digits of their canonical
+//!   integer, two per byte of [`PrimeField::BigInt`] (64 for BN254) ([`format_element`]), or
+//!   decimal on request ([`format_element_decimal`]). [`parse_element`] reads either form and
+//!   [`parse_element_hex`] only hex; both reject values that are not reduced.
+//!
+//! - Byte strings are `0x` followed by two lowercase hex digits per byte ([`format_bytes`],
+//!   [`parse_bytes`]).
+//!
+//! The widths are fixed, so the outputs of different tools for the same values can be diffed
+//! line by line.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use ark_ff::{BigInteger, PrimeField};
+
+use crate::hex::{self, HexError, Mode};
+use crate::tag::Tag;
+
+/// Error of the parsing functions of this module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// A hex value without its `0x` prefix.
+    MissingPrefix,
+    /// No digits.
+    Empty,
+    /// A character that is not a digit of the expected base.
+    InvalidDigit,
+    /// More significant digits than the value can hold.
+    TooLong,
+    /// A field element not below the modulus.
+    NonCanonical,
+    /// A byte string with an odd number of hex digits.
+    OddLength,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ParseError::MissingPrefix => "missing 0x prefix",
+            ParseError::Empty => "no digits",
+            ParseError::InvalidDigit => "invalid digit",
+            ParseError::TooLong => "value too large",
+            ParseError::NonCanonical => "not a canonical field element",
+            ParseError::OddLength => "odd number of hex digits",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {}
+
+/// Formats a tag as `0x` and 32 hex digits, or two per byte for tags of other lengths (see
+/// [`Tag::to_hex`]).
+pub fn format_tag(tag: Tag) -> String {
+    tag.to_hex()
+}
+
+/// Parses a `0x`-prefixed hex 128-bit tag. Leading zeros are optional, e.g. for tags printed
+/// as field elements, unlike in [`Tag::from_hex`].
+pub fn parse_tag(input: &str) -> Result<Tag, ParseError> {
+    let digits = hex_digits(input)?;
+    let significant = digits.trim_start_matches('0');
+    if significant.len() > 32 {
+        return Err(ParseError::TooLong);
+    }
+    if significant.is_empty() {
+        return Ok(Tag::from(0));
+    }
+    u128::from_str_radix(significant, 16)
+        .map(Tag::from)
+        .map_err(|_| ParseError::InvalidDigit)
+}
+
+/// Formats bytes as `0x` and two hex digits per byte.
+pub fn format_bytes(bytes: &[u8]) -> String {
+    hex::encode(bytes)
+}
+
+/// Parses bytes formatted by [`format_bytes`], in upper or lower case. `0x` alone is the empty
+/// string. This is the strict mode of [`hex`](crate::hex).
+pub fn parse_bytes(input: &str) -> Result<Vec<u8>, ParseError> {
+    hex::decode(input, Mode::Strict).map_err(|error| match error {
+        HexError::MissingPrefix => ParseError::MissingPrefix,
+        HexError::InvalidDigit(_) => ParseError::InvalidDigit,
+        HexError::OddLength => ParseError::OddLength,
+        HexError::Length { .. } => unreachable!("no length is required"),
+    })
+}
+
+/// Formats a field element as `0x` and fixed-width big-endian hex.
+pub fn format_element<F: PrimeField>(element: &F) -> String {
+    format_bytes(&element.into_bigint().to_bytes_be())
+}
+
+/// Formats a field element as the decimal digits of its canonical integer.
+pub fn format_element_decimal<F: PrimeField>(element: &F) -> String {
+    element.into_bigint().to_string()
+}
+
+/// Parses a field element in `0x` hex or decimal.
+pub fn parse_element<F: PrimeField>(input: &str) -> Result<F, ParseError> {
+    if input.starts_with("0x") {
+        return parse_element_hex(input);
+    }
+    if input.is_empty() {
+        return Err(ParseError::Empty);
+    }
+    if !input.chars().all(|c| c.is_ascii_digit()) {
+        return Err(ParseError::InvalidDigit);
+    }
+    let element = F::from_str(input).map_err(|_| ParseError::NonCanonical)?;
+    // `from_str` reduces modulo p: the value is canonical only if it prints back the same.
+    let significant = match input.trim_start_matches('0') {
+        "" => "0",
+        digits => digits,
+    };
+    if format_element_decimal(&element) != significant {
+        return Err(ParseError::NonCanonical);
+    }
+    Ok(element)
+}
+
+/// Parses a field element in `0x` hex only.
+pub fn parse_element_hex<F: PrimeField>(input: &str) -> Result<F, ParseError> {
+    let digits = hex_digits(input)?;
+    let width = 2 * F::ZERO.into_bigint().to_bytes_be().len();
+    let significant = digits.trim_start_matches('0');
+    if significant.len() > width {
+        return Err(ParseError::TooLong);
+    }
+    let padded = format!("{:0>width$}", significant);
+    let bytes: Vec<u8> = (0..width / 2)
+        .map(|i| u8::from_str_radix(&padded[2 * i..2 * i + 2], 16).expect("digits were checked"))
+        .collect();
+    let element = F::from_be_bytes_mod_order(&bytes);
+    if element.into_bigint().to_bytes_be() != bytes {
+        return Err(ParseError::NonCanonical);
+    }
+    Ok(element)
+}
+
+/// Returns the hex digits after the `0x` prefix, if there is at least one and all are valid.
+fn hex_digits(input: &str) -> Result<&str, ParseError> {
+    let digits = input.strip_prefix("0x").ok_or(ParseError::MissingPrefix)?;
+    if digits.is_empty() {
+        return Err(ParseError::Empty);
+    }
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ParseError::InvalidDigit);
+    }
+    Ok(digits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bn254::Fr;
+
+    #[test]
+    fn test_tags() {
+        let integer = 0x0ea2aa7e178caa74de1f91e83ad43a81;
+        let tag = Tag::from(integer);
+        assert_eq!(format_tag(tag), "0x0ea2aa7e178caa74de1f91e83ad43a81");
+        assert_eq!(
+            format_tag(Tag::from(1)),
+            "0x00000000000000000000000000000001"
+        );
+        assert_eq!(parse_tag(&format_tag(tag)), Ok(tag));
+        assert_eq!(parse_tag("0x0001"), Ok(Tag::from(1)));
+        assert_eq!(parse_tag(&format!("0x{:064x}", integer)), Ok(tag));
+        assert_eq!(parse_tag("1"), Err(ParseError::MissingPrefix));
+        assert_eq!(
+            parse_tag(&format!("0x1{:032x}", 0)),
+            Err(ParseError::TooLong)
+        );
+    }
+
+    #[test]
+    fn test_elements() {
+        let element = Fr::from(42u64);
+        assert_eq!(format_element(&element), format!("0x{:064x}", 42));
+        assert_eq!(format_element_decimal(&element), "42");
+        assert_eq!(format_bytes(&[0x0a, 0xff]), "0x0aff");
+        assert_eq!(parse_bytes("0x0aFF"), Ok(vec![0x0a, 0xff]));
+        assert_eq!(parse_bytes("0x"), Ok(vec![]));
+        assert_eq!(parse_bytes("0x0af"), Err(ParseError::OddLength));
+        assert_eq!(parse_bytes("0aff"), Err(ParseError::MissingPrefix));
+        for input in ["42", "042", "0x2a", &format_element(&element)] {
+            assert_eq!(parse_element::<Fr>(input), Ok(element));
+        }
+        assert_eq!(parse_element::<Fr>("0"), Ok(Fr::from(0u64)));
+        assert_eq!(
+            parse_element_hex::<Fr>("42"),
+            Err(ParseError::MissingPrefix)
+        );
+        assert_eq!(parse_element::<Fr>("0x"), Err(ParseError::Empty));
+        assert_eq!(parse_element::<Fr>("4a"), Err(ParseError::InvalidDigit));
+
+        let modulus = Fr::MODULUS.to_string();
+        assert_eq!(parse_element::<Fr>(&modulus), Err(ParseError::NonCanonical));
+        assert_eq!(
+            parse_element::<Fr>(&format!("0x1{:064x}", 0)),
+            Err(ParseError::TooLong)
+        );
+        assert_eq!(
+            parse_element::<Fr>(&format!("0x{:f>64}", "")),
+            Err(ParseError::NonCanonical)
+        );
+    }
+}
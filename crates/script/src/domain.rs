@@ -0,0 +1,799 @@
+//! Domain separator layouts.
+//!
+//! [`DomainSeparator`] holds the 64 bytes, e.g. parsed from the hex of a configuration file with
+//! [`DomainSeparator::from_hex`]. The tag functions and the sponge and transcript constructors
+//! take `impl Into<DomainSeparator>`: a `DomainSeparator`, a reference to one, or the
+//! zero-padded `&[u8; 64]`.
+//!
+//! The simplest layout is a label zero padded to 64 bytes ([`DomainSeparator::from_label`],
+//! or [`padded_domain_separator`] for arbitrary bytes). By convention the label ends with the
+//! version of the protocol, as in `my-app/transfer/v2` ([`DomainSeparator::versioned`]):
+//!
+//! ```
+//! use safe_api::domain::DomainSeparator;
+//! use safe_api::IoPattern;
+//!
+//! let domain_separator = DomainSeparator::versioned("my-app/transfer", 2)?;
+//! assert_eq!(domain_separator, DomainSeparator::from_label("my-app/transfer/v2")?);
+//! assert_eq!(domain_separator.version(), Some(2));
+//! assert_eq!(domain_separator.protocol(), Some("my-app/transfer"));
+//! assert_eq!(domain_separator.to_string(), "0x6d792d6170702f7472616e736665722f7632");
+//! let tag = IoPattern::new().absorb(2).squeeze(1).tag(&domain_separator);
+//! # let _ = tag;
+//! # Ok::<(), safe_api::SafeError>(())
+//! ```
+//!
+//! The
+//! all-zero domain separator, which is what a forgotten one usually looks like, is *empty*
+//! ([`is_empty_domain`]) and rejected by the sponge and transcript constructors unless
+//! [`SpongeBuilder::allow_empty_domain`](crate::sponge::SpongeBuilder::allow_empty_domain) opts
+//! out.
+//!
+//! Labels assembled from parts, e.g. per application and circuit, come from a
+//! [`DomainTemplate`] such as `gnosisguild/{app}/{circuit}/v{version}`, whose rendering checks
+//! every substitution rather than concatenating strings: a value cannot contain a `/`, which
+//! would let `{app} = "a/b", {circuit} = "c"` collide with `{app} = "a", {circuit} = "b/c"`,
+//! and the label must fit in 64 bytes. For the same reason, two placeholders cannot share a
+//! `/`-separated segment of the template: in `{circuit}-{app}`, `("a-b", "c")` and
+//! `("a", "b-c")` would render the same label. With one placeholder per segment, the label
+//! determines every value.
+//!
+//! ```
+//! use safe_api::domain::{DomainSeparator, DomainTemplate};
+//!
+//! let template: DomainTemplate = "gnosisguild/{app}/{circuit}/v{version}".parse()?;
+//! assert_eq!(template.placeholders(), ["app", "circuit", "version"]);
+//! let domain_separator = template.render(|name| match name {
+//!     "app" => Some("wallet"),
+//!     "circuit" => Some("spend"),
+//!     "version" => Some("2"),
+//!     _ => None,
+//! })?;
+//! assert_eq!(domain_separator, DomainSeparator::versioned("gnosisguild/wallet/spend", 2)?);
+//! # Ok::<(), safe_api::SafeError>(())
+//! ```
+//!
+//! An *expiring* domain separator binds a protocol instance to an epoch (an auction, a voting
+//! round, ...). The epoch is part of the domain separator and therefore of the tag, so a
+//! transcript produced for one epoch cannot be replayed in another, and a verifier can reject
+//! instances whose epoch has passed with [`check_freshness`].
+//!
+//! Layout of the 64 bytes:
+//!
+//! | bytes    | content                                  |
+//! |----------|------------------------------------------|
+//! | `0..48`  | protocol label, zero padded              |
+//! | `48..56` | the marker `SAFE-EXP`                    |
+//! | `56..64` | expiry epoch as a big-endian `u64`       |
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Deref;
+use core::str::FromStr;
+
+use crate::error::SafeError;
+use crate::hex::{self, Mode};
+
+/// Maximum length of the protocol label of an expiring domain separator.
+pub const EXPIRING_LABEL_LEN: usize = 48;
+
+/// Marker identifying the expiring layout.
+pub const EXPIRY_MARKER: [u8; 8] = *b"SAFE-EXP";
+
+/// Errors raised by the freshness check.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExpiryError {
+    /// The domain separator does not use the expiring layout.
+    NotExpiring,
+    /// The instance expired at `expiry`, before the current epoch `now`.
+    Expired {
+        /// Last epoch at which the instance is valid.
+        expiry: u64,
+        /// Current epoch of the verifier.
+        now: u64,
+    },
+}
+
+impl fmt::Display for ExpiryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpiryError::NotExpiring => write!(f, "the domain separator carries no expiry epoch"),
+            ExpiryError::Expired { expiry, now } => {
+                write!(f, "the instance expired at epoch {} (now {})", expiry, now)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ExpiryError {}
+
+/// Errors raised while parsing or rendering a [`DomainTemplate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TemplateError {
+    /// An unmatched `{` or `}`, or a character that is not printable ASCII, at that byte
+    /// offset of the template.
+    Malformed(usize),
+    /// A placeholder name that is not a lowercase identifier (`[a-z0-9_]+`).
+    InvalidPlaceholder(String),
+    /// A `/`-separated segment of the template with several placeholders.
+    Ambiguous(String),
+    /// A placeholder without a substitution.
+    Missing(String),
+    /// The substitution of that placeholder is empty, or contains a `/`, a brace or a
+    /// character that is not printable ASCII.
+    InvalidValue(String),
+    /// The rendered label is longer than 64 bytes (its length).
+    TooLong(usize),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::Malformed(offset) => {
+                write!(f, "malformed domain template at offset {}", offset)
+            }
+            TemplateError::InvalidPlaceholder(name) => {
+                write!(f, "invalid placeholder name `{}`", name)
+            }
+            TemplateError::Ambiguous(segment) => write!(
+                f,
+                "the segment `{}` has several placeholders (separate them with `/`)",
+                segment
+            ),
+            TemplateError::Missing(name) => write!(f, "no value for placeholder `{}`", name),
+            TemplateError::InvalidValue(name) => write!(
+                f,
+                "the value of placeholder `{}` is empty or contains `/`, a brace or a non-printable character",
+                name
+            ),
+            TemplateError::TooLong(length) => write!(
+                f,
+                "the rendered domain separator is longer than 64 bytes ({} bytes)",
+                length
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TemplateError {}
+
+/// A 64-byte domain separator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DomainSeparator([u8; 64]);
+
+impl DomainSeparator {
+    /// Wraps the 64 bytes of a domain separator.
+    pub fn new(bytes: [u8; 64]) -> Self {
+        DomainSeparator(bytes)
+    }
+
+    /// Parses up to 64 bytes of hex in the lenient mode of [`hex`](crate::hex), with or
+    /// without a `0x` prefix, zero padding them. The bytes are in the order they are written,
+    /// as with [`from_be_hex`](Self::from_be_hex).
+    pub fn from_hex(input: &str) -> Result<Self, SafeError> {
+        let digits = decode_digits(input)?;
+        let mut bytes = [0u8; 64];
+        bytes[..digits.len()].copy_from_slice(&digits);
+        Ok(DomainSeparator(bytes))
+    }
+
+    /// Parses the hex of a domain separator written big-endian, its first byte first, as
+    /// [`from_hex`](Self::from_hex) and [`Display`](fmt::Display) do.
+    pub fn from_be_hex(input: &str) -> Result<Self, SafeError> {
+        Self::from_hex(input)
+    }
+
+    /// Parses the hex of a domain separator written little-endian, its first byte last, e.g.
+    /// by a client serializing the separator as a little-endian integer: `0x44434241` is the
+    /// domain separator of the label `ABCD`. Otherwise as [`from_hex`](Self::from_hex).
+    pub fn from_le_hex(input: &str) -> Result<Self, SafeError> {
+        let digits = decode_digits(input)?;
+        let mut bytes = [0u8; 64];
+        for (byte, digit) in bytes.iter_mut().zip(digits.iter().rev()) {
+            *byte = *digit;
+        }
+        Ok(DomainSeparator(bytes))
+    }
+
+    /// Builds the domain separator of `label`, zero padded to 64 bytes. The label must be
+    /// printable ASCII, so that it reads back with [`label`](Self::label).
+    pub fn from_label(label: &str) -> Result<Self, SafeError> {
+        if !label
+            .bytes()
+            .all(|byte| byte.is_ascii_graphic() || byte == b' ')
+        {
+            return Err(SafeError::InvalidLabel);
+        }
+        if label.len() > 64 {
+            return Err(SafeError::DomainSeparatorTooLong(label.len()));
+        }
+        Ok(DomainSeparator(padded_domain_separator(label.as_bytes())))
+    }
+
+    /// Builds the domain separator of version `version` of `protocol`, labeled
+    /// `<protocol>/v<version>`.
+    pub fn versioned(protocol: &str, version: u32) -> Result<Self, SafeError> {
+        Self::from_label(&format!("{}/v{}", protocol, version))
+    }
+
+    /// Returns the label of a domain separator built with [`from_label`](Self::from_label),
+    /// i.e. its bytes without the zero padding if they are printable ASCII.
+    pub fn label(&self) -> Option<&str> {
+        let label = &self.0[..significant_len(&self.0)];
+        if !label
+            .iter()
+            .all(|&byte| byte.is_ascii_graphic() || byte == b' ')
+        {
+            return None;
+        }
+        core::str::from_utf8(label).ok()
+    }
+
+    /// Returns the protocol of a [`versioned`](Self::versioned) domain separator.
+    pub fn protocol(&self) -> Option<&str> {
+        self.split_version().map(|(protocol, _)| protocol)
+    }
+
+    /// Returns the version of a [`versioned`](Self::versioned) domain separator.
+    pub fn version(&self) -> Option<u32> {
+        self.split_version().map(|(_, version)| version)
+    }
+
+    /// Returns the 64 bytes.
+    pub fn as_bytes(&self) -> &[u8; 64] {
+        &self.0
+    }
+
+    /// Returns whether the domain separator is empty, see [`is_empty_domain`].
+    pub fn is_empty(&self) -> bool {
+        is_empty_domain(&self.0)
+    }
+
+    /// Returns the first of `known` which this domain separator is the byte reversal of,
+    /// either of its bytes without the zero padding or of all 64 bytes, without being equal
+    /// to it.
+    ///
+    /// Such a domain separator was most likely written in the wrong byte order, e.g. parsed
+    /// with [`from_be_hex`](Self::from_be_hex) from the little-endian hex of a client, and
+    /// gives another tag than the protocol it was meant for. The tools of the crate warn
+    /// about it rather than reject it, since nothing forbids a protocol from using it.
+    pub fn find_reversed<'a>(
+        &self,
+        known: impl IntoIterator<Item = &'a DomainSeparator>,
+    ) -> Option<&'a DomainSeparator> {
+        let mut padded = self.0;
+        padded[..significant_len(&self.0)].reverse();
+        let mut whole = self.0;
+        whole.reverse();
+        known
+            .into_iter()
+            .find(|known| known.0 != self.0 && (known.0 == padded || known.0 == whole))
+    }
+
+    fn split_version(&self) -> Option<(&str, u32)> {
+        let (protocol, version) = self.label()?.rsplit_once("/v")?;
+        // Only canonical decimal versions, so that `versioned` builds the same label back.
+        if version.is_empty()
+            || !version.bytes().all(|byte| byte.is_ascii_digit())
+            || (version.len() > 1 && version.starts_with('0'))
+        {
+            return None;
+        }
+        Some((protocol, version.parse().ok()?))
+    }
+}
+
+/// A zero-padded prefix of arbitrary bytes, possibly empty or all 64 bytes.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for DomainSeparator {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut bytes = [0u8; 64];
+        let length = u.int_in_range(0..=64)?;
+        u.fill_buffer(&mut bytes[..length])?;
+        Ok(DomainSeparator(bytes))
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (1, Some(65))
+    }
+}
+
+impl Deref for DomainSeparator {
+    type Target = [u8; 64];
+
+    fn deref(&self) -> &[u8; 64] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8; 64]> for DomainSeparator {
+    fn as_ref(&self) -> &[u8; 64] {
+        &self.0
+    }
+}
+
+/// Formats the domain separator as `0x` and the hex of its bytes without the zero padding,
+/// which [`DomainSeparator::from_hex`] parses back.
+impl fmt::Display for DomainSeparator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("0x")?;
+        for byte in &self.0[..significant_len(&self.0)] {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<[u8; 64]> for DomainSeparator {
+    fn from(bytes: [u8; 64]) -> Self {
+        DomainSeparator(bytes)
+    }
+}
+
+impl From<&[u8; 64]> for DomainSeparator {
+    fn from(bytes: &[u8; 64]) -> Self {
+        DomainSeparator(*bytes)
+    }
+}
+
+impl From<&DomainSeparator> for DomainSeparator {
+    fn from(domain_separator: &DomainSeparator) -> Self {
+        *domain_separator
+    }
+}
+
+impl From<DomainSeparator> for [u8; 64] {
+    fn from(domain_separator: DomainSeparator) -> Self {
+        domain_separator.0
+    }
+}
+
+/// One piece of a [`DomainTemplate`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Segment {
+    Literal(String),
+    Placeholder(String),
+}
+
+/// A label with `{name}` placeholders, rendered into a domain separator, see the
+/// [module documentation](self). Placeholder names are `[a-z0-9_]+`, at most one per
+/// `/`-separated segment; the literal text is printable ASCII without braces.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DomainTemplate {
+    segments: Vec<Segment>,
+    /// The distinct placeholder names, in order of first occurrence.
+    placeholders: Vec<String>,
+}
+
+impl DomainTemplate {
+    /// Returns the distinct placeholder names, in order of first occurrence.
+    pub fn placeholders(&self) -> &[String] {
+        &self.placeholders
+    }
+
+    /// Renders the label, substituting every placeholder with the value of `lookup`, and
+    /// zero pads it into a domain separator.
+    pub fn render<'a>(
+        &self,
+        lookup: impl Fn(&str) -> Option<&'a str>,
+    ) -> Result<DomainSeparator, TemplateError> {
+        let mut label = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => label.push_str(text),
+                Segment::Placeholder(name) => {
+                    let value = lookup(name).ok_or_else(|| TemplateError::Missing(name.clone()))?;
+                    if value.is_empty()
+                        || !value.bytes().all(|byte| {
+                            (byte.is_ascii_graphic() || byte == b' ')
+                                && !matches!(byte, b'/' | b'{' | b'}')
+                        })
+                    {
+                        return Err(TemplateError::InvalidValue(name.clone()));
+                    }
+                    label.push_str(value);
+                }
+            }
+        }
+        if label.len() > 64 {
+            return Err(TemplateError::TooLong(label.len()));
+        }
+        Ok(DomainSeparator(padded_domain_separator(label.as_bytes())))
+    }
+}
+
+impl FromStr for DomainTemplate {
+    type Err = TemplateError;
+
+    fn from_str(template: &str) -> Result<Self, TemplateError> {
+        let mut segments = Vec::new();
+        let mut placeholders: Vec<String> = Vec::new();
+        let mut rest = template;
+        while !rest.is_empty() {
+            let offset = template.len() - rest.len();
+            let literal = rest.find(['{', '}']).unwrap_or(rest.len());
+            if let Some(index) = rest[..literal]
+                .bytes()
+                .position(|byte| !(byte.is_ascii_graphic() || byte == b' '))
+            {
+                return Err(TemplateError::Malformed(offset + index));
+            }
+            if literal > 0 {
+                segments.push(Segment::Literal(rest[..literal].to_string()));
+            }
+            rest = &rest[literal..];
+            if rest.is_empty() {
+                break;
+            }
+            let offset = template.len() - rest.len();
+            let end = match rest.strip_prefix('{') {
+                Some(placeholder) => placeholder
+                    .find(['{', '}'])
+                    .filter(|&end| placeholder.as_bytes()[end] == b'}')
+                    .ok_or(TemplateError::Malformed(offset))?,
+                None => return Err(TemplateError::Malformed(offset)),
+            };
+            let name = &rest[1..end + 1];
+            if name.is_empty()
+                || !name
+                    .bytes()
+                    .all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'_')
+            {
+                return Err(TemplateError::InvalidPlaceholder(name.to_string()));
+            }
+            if !placeholders.iter().any(|known| known == name) {
+                placeholders.push(name.to_string());
+            }
+            segments.push(Segment::Placeholder(name.to_string()));
+            rest = &rest[end + 2..];
+        }
+        if let Some(segment) = template
+            .split('/')
+            .find(|segment| segment.matches('{').count() > 1)
+        {
+            return Err(TemplateError::Ambiguous(segment.to_string()));
+        }
+        Ok(DomainTemplate {
+            segments,
+            placeholders,
+        })
+    }
+}
+
+/// Formats the template as it parses.
+impl fmt::Display for DomainTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => f.write_str(text)?,
+                Segment::Placeholder(name) => write!(f, "{{{}}}", name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decodes the hex of at most 64 bytes of a domain separator.
+fn decode_digits(input: &str) -> Result<Vec<u8>, SafeError> {
+    let digits = hex::decode(input, Mode::Lenient).map_err(|_| SafeError::InvalidHex)?;
+    if digits.len() > 64 {
+        return Err(SafeError::DomainSeparatorTooLong(digits.len()));
+    }
+    Ok(digits)
+}
+
+/// Returns the length of `domain_separator` without its trailing zeros.
+fn significant_len(domain_separator: &[u8; 64]) -> usize {
+    domain_separator
+        .iter()
+        .rposition(|&byte| byte != 0)
+        .map_or(0, |index| index + 1)
+}
+
+/// Returns whether `domain_separator` is empty, i.e. all zeros.
+pub fn is_empty_domain(domain_separator: &[u8; 64]) -> bool {
+    domain_separator.iter().all(|&byte| byte == 0)
+}
+
+/// Builds a domain separator from `label`, zero padded to 64 bytes.
+///
+/// # Panics
+/// If `label` is longer than 64 bytes.
+pub fn padded_domain_separator(label: &[u8]) -> [u8; 64] {
+    assert!(label.len() <= 64, "Label too long for a domain separator");
+    let mut domain_separator = [0u8; 64];
+    domain_separator[..label.len()].copy_from_slice(label);
+    domain_separator
+}
+
+/// Builds a domain separator for `label` that is valid up to and including epoch `expiry`.
+///
+/// # Panics
+/// If `label` is longer than [`EXPIRING_LABEL_LEN`] bytes.
+pub fn expiring_domain_separator(label: &[u8], expiry: u64) -> [u8; 64] {
+    assert!(
+        label.len() <= EXPIRING_LABEL_LEN,
+        "Label too long for an expiring domain separator"
+    );
+
+    let mut domain_separator = [0u8; 64];
+    domain_separator[..label.len()].copy_from_slice(label);
+    domain_separator[EXPIRING_LABEL_LEN..56].copy_from_slice(&EXPIRY_MARKER);
+    domain_separator[56..].copy_from_slice(&expiry.to_be_bytes());
+    domain_separator
+}
+
+/// Returns the expiry epoch of a domain separator, or `None` if it does not use the expiring
+/// layout.
+pub fn expiry(domain_separator: &[u8; 64]) -> Option<u64> {
+    if domain_separator[EXPIRING_LABEL_LEN..56] != EXPIRY_MARKER {
+        return None;
+    }
+    let mut epoch = [0u8; 8];
+    epoch.copy_from_slice(&domain_separator[56..]);
+    Some(u64::from_be_bytes(epoch))
+}
+
+/// Verifier-side freshness check: accepts an expiring domain separator whose epoch is not
+/// before `now`.
+pub fn check_freshness(domain_separator: &[u8; 64], now: u64) -> Result<(), ExpiryError> {
+    let expiry = expiry(domain_separator).ok_or(ExpiryError::NotExpiring)?;
+    if now > expiry {
+        return Err(ExpiryError::Expired { expiry, now });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IoPattern;
+
+    #[test]
+    fn test_expiry_round_trip() {
+        let domain_separator = expiring_domain_separator(b"auction", 1_700_000_000);
+        assert_eq!(&domain_separator[..7], b"auction");
+        assert_eq!(expiry(&domain_separator), Some(1_700_000_000));
+
+        let plain = padded_domain_separator(b"ABCD");
+        assert_eq!(expiry(&plain), None);
+        assert_eq!(check_freshness(&plain, 0), Err(ExpiryError::NotExpiring));
+    }
+
+    #[test]
+    fn test_from_hex() {
+        let expected = padded_domain_separator(b"ABCD");
+        for input in ["0x41424344", "41424344", "0x4142434400", " 0X41 42 43 44\n"] {
+            assert_eq!(
+                DomainSeparator::from_hex(input).unwrap().as_bytes(),
+                &expected
+            );
+        }
+        assert!(DomainSeparator::from_hex("").unwrap().is_empty());
+        assert_eq!(
+            DomainSeparator::from_hex(&"ab".repeat(64)).unwrap(),
+            DomainSeparator::new([0xab; 64])
+        );
+        for input in ["0x123", "0xzz", "0x 1"] {
+            assert_eq!(DomainSeparator::from_hex(input), Err(SafeError::InvalidHex));
+        }
+        assert_eq!(
+            DomainSeparator::from_hex(&"00".repeat(65)),
+            Err(SafeError::DomainSeparatorTooLong(65))
+        );
+    }
+
+    #[test]
+    fn test_byte_order() {
+        let abcd = DomainSeparator::from_label("ABCD").unwrap();
+        assert_eq!(DomainSeparator::from_be_hex("0x41424344").unwrap(), abcd);
+        assert_eq!(DomainSeparator::from_le_hex("0x44434241").unwrap(), abcd);
+        assert_eq!(DomainSeparator::from_le_hex("0x0044434241").unwrap(), abcd);
+        assert_eq!(
+            DomainSeparator::from_le_hex(&"00".repeat(65)),
+            Err(SafeError::DomainSeparatorTooLong(65))
+        );
+
+        let other = DomainSeparator::from_label("other").unwrap();
+        let known = [other, abcd];
+        let reversed = DomainSeparator::from_hex("0x44434241").unwrap();
+        assert_eq!(reversed.find_reversed(&known), Some(&abcd));
+        let mut whole = *abcd.as_bytes();
+        whole.reverse();
+        assert_eq!(
+            DomainSeparator::new(whole).find_reversed(&known),
+            Some(&abcd)
+        );
+        assert_eq!(abcd.find_reversed(&known), None);
+        // A palindrome is its own reversal, and equal to the known separator.
+        let palindrome = DomainSeparator::from_label("abba").unwrap();
+        assert_eq!(palindrome.find_reversed(&[palindrome]), None);
+    }
+
+    #[test]
+    fn test_template() {
+        let template: DomainTemplate = "acme/{app}/{circuit}/{app}/v{version}".parse().unwrap();
+        assert_eq!(template.placeholders(), ["app", "circuit", "version"]);
+        assert_eq!(
+            template.to_string(),
+            "acme/{app}/{circuit}/{app}/v{version}"
+        );
+        let values = [("app", "wallet"), ("circuit", "spend"), ("version", "3")];
+        let lookup = |name: &str| {
+            values
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| *value)
+        };
+        let domain_separator = template.render(lookup).unwrap();
+        assert_eq!(
+            domain_separator.label(),
+            Some("acme/wallet/spend/wallet/v3")
+        );
+        assert_eq!(domain_separator.version(), Some(3));
+
+        assert_eq!(
+            template.render(|name| lookup(name).filter(|_| name != "circuit")),
+            Err(TemplateError::Missing("circuit".to_string()))
+        );
+        for value in ["", "a/b", "{x}", "caf\u{e9}"] {
+            assert_eq!(
+                template.render(|name| if name == "app" {
+                    Some(value)
+                } else {
+                    lookup(name)
+                }),
+                Err(TemplateError::InvalidValue("app".to_string()))
+            );
+        }
+        let long = "x".repeat(30);
+        assert_eq!(
+            template.render(|name| if name == "app" {
+                Some(&long)
+            } else {
+                lookup(name)
+            }),
+            Err(TemplateError::TooLong(75))
+        );
+        // A template without placeholders renders as its label.
+        assert_eq!(
+            "plain".parse::<DomainTemplate>().unwrap().render(|_| None),
+            Ok(DomainSeparator::from_label("plain").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_template() {
+        // Both would render `a-b-c` from ("a-b", "c") and ("a", "b-c").
+        for (template, segment) in [
+            ("acme/{circuit}-{app}/v1", "{circuit}-{app}"),
+            ("{a}{b}", "{a}{b}"),
+            ("acme/v{version}.{patch}", "v{version}.{patch}"),
+        ] {
+            assert_eq!(
+                template.parse::<DomainTemplate>(),
+                Err(TemplateError::Ambiguous(segment.to_string()))
+            );
+        }
+        let template: DomainTemplate = "acme/{circuit}/{app}".parse().unwrap();
+        let render = |circuit: &str, app: &str| {
+            template.render(|name| match name {
+                "circuit" => Some(circuit),
+                _ => Some(app),
+            })
+        };
+        assert_ne!(render("a-b", "c"), render("a", "b-c"));
+        assert_eq!(
+            render("a/b", "c"),
+            Err(TemplateError::InvalidValue("circuit".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_malformed_template() {
+        for (template, error) in [
+            ("a/{app", TemplateError::Malformed(2)),
+            ("a}", TemplateError::Malformed(1)),
+            ("{a{b}}", TemplateError::Malformed(0)),
+            ("caf\u{e9}/{app}", TemplateError::Malformed(3)),
+            ("a/{}", TemplateError::InvalidPlaceholder(String::new())),
+            (
+                "{App}",
+                TemplateError::InvalidPlaceholder("App".to_string()),
+            ),
+        ] {
+            assert_eq!(
+                template.parse::<DomainTemplate>(),
+                Err(error),
+                "{}",
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_label() {
+        let domain_separator = DomainSeparator::from_label("ABCD").unwrap();
+        assert_eq!(
+            domain_separator.as_bytes(),
+            &padded_domain_separator(b"ABCD")
+        );
+        assert_eq!(domain_separator.label(), Some("ABCD"));
+        assert_eq!(domain_separator.version(), None);
+        assert_eq!(domain_separator.to_string(), "0x41424344");
+        assert_eq!(
+            DomainSeparator::from_hex(&domain_separator.to_string()),
+            Ok(domain_separator)
+        );
+        assert_eq!(DomainSeparator::new([0xab; 64]).label(), None);
+        assert_eq!(DomainSeparator::new([0; 64]).to_string(), "0x");
+
+        let pattern = IoPattern::from_words(&[0x80000002, 0x00000001]);
+        let bytes = padded_domain_separator(b"ABCD");
+        assert_eq!(pattern.tag(domain_separator), pattern.tag(bytes));
+        assert_eq!(domain_separator, bytes.into());
+
+        assert_eq!(
+            DomainSeparator::from_label("caf\u{e9}"),
+            Err(SafeError::InvalidLabel)
+        );
+        assert_eq!(
+            DomainSeparator::from_label("a\0b"),
+            Err(SafeError::InvalidLabel)
+        );
+        assert_eq!(
+            DomainSeparator::from_label(&"a".repeat(65)),
+            Err(SafeError::DomainSeparatorTooLong(65))
+        );
+    }
+
+    #[test]
+    fn test_versioned() {
+        let domain_separator = DomainSeparator::versioned("my-app/v1beta", 10).unwrap();
+        assert_eq!(domain_separator.label(), Some("my-app/v1beta/v10"));
+        assert_eq!(domain_separator.protocol(), Some("my-app/v1beta"));
+        assert_eq!(domain_separator.version(), Some(10));
+        for label in [
+            "my-app/v",
+            "my-app/v01",
+            "my-app/v1beta",
+            "my-app/v99999999999",
+        ] {
+            assert_eq!(DomainSeparator::from_label(label).unwrap().version(), None);
+        }
+    }
+
+    #[test]
+    fn test_empty_domain() {
+        assert!(is_empty_domain(&[0u8; 64]));
+        assert!(is_empty_domain(&padded_domain_separator(b"")));
+        assert!(!is_empty_domain(&padded_domain_separator(b"\0\0\x01")));
+    }
+
+    #[test]
+    fn test_freshness() {
+        let domain_separator = expiring_domain_separator(b"round", 10);
+        assert_eq!(check_freshness(&domain_separator, 9), Ok(()));
+        assert_eq!(check_freshness(&domain_separator, 10), Ok(()));
+        assert_eq!(
+            check_freshness(&domain_separator, 11),
+            Err(ExpiryError::Expired {
+                expiry: 10,
+                now: 11
+            })
+        );
+    }
+
+    #[test]
+    fn test_epochs_change_the_tag() {
+        let pattern = IoPattern::new().absorb(2).squeeze(1);
+        assert_ne!(
+            pattern.tag(expiring_domain_separator(b"round", 1)),
+            pattern.tag(expiring_domain_separator(b"round", 2))
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Injective encoding of byte strings into field elements.
+//!
+//! A byte string of `m` bytes is encoded as `1 + ceil(m / k)` field elements, where `k` is the
+//! largest number of bytes that always fits below the modulus (31 bytes for BN254):
+//!
+//! - the first element is the length `m`,
+//! - then each chunk of `k` bytes, read as a big-endian integer; the last chunk may be shorter.
+//!
+//! The length framing makes the encoding injective, so `[0x01]` and `[0x00, 0x01]` (or two
+//! different splits of the same bytes across absorbs) never collide.
+
+use alloc::vec::Vec;
+
+use ark_ff::PrimeField;
+
+/// Returns the number of bytes packed into each field element of `F`.
+pub fn bytes_per_element<F: PrimeField>() -> usize {
+    (F::MODULUS_BIT_SIZE as usize - 1) / 8
+}
+
+/// Returns the number of field elements of `F` encoding a byte string of `byte_len` bytes.
+pub fn encoded_len<F: PrimeField>(byte_len: usize) -> usize {
+    1 + byte_len.div_ceil(bytes_per_element::<F>())
+}
+
+/// How a byte stream absorbed with [`Sponge::absorb_reader`](crate::Sponge::absorb_reader) is
+/// split into field elements. Both encodings absorb the length of the stream first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChunkEncoding {
+    /// [`bytes_per_element`] bytes per element, as in [`encode_bytes`].
+    Packed,
+    /// One byte per element, for circuits operating on byte arrays.
+    Bytes,
+}
+
+impl ChunkEncoding {
+    /// Returns the number of bytes read into each field element of `F`.
+    pub fn chunk_size<F: PrimeField>(self) -> usize {
+        match self {
+            ChunkEncoding::Packed => bytes_per_element::<F>(),
+            ChunkEncoding::Bytes => 1,
+        }
+    }
+
+    /// Returns the number of field elements of `F` encoding a stream of `byte_len` bytes, i.e.
+    /// the length of its ABSORB in the IO pattern.
+    pub fn encoded_len<F: PrimeField>(self, byte_len: u64) -> usize {
+        1 + byte_len.div_ceil(self.chunk_size::<F>() as u64) as usize
+    }
+}
+
+/// Encodes `bytes` as field elements of `F`, length first.
+pub fn encode_bytes<F: PrimeField>(bytes: &[u8]) -> Vec<F> {
+    let mut elements = Vec::with_capacity(encoded_len::<F>(bytes.len()));
+    elements.push(F::from(bytes.len() as u64));
+    elements.extend(
+        bytes
+            .chunks(bytes_per_element::<F>())
+            .map(F::from_be_bytes_mod_order),
+    );
+    elements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bn254::Fr;
+    use ark_ff::BigInteger;
+
+    #[test]
+    fn test_chunking() {
+        assert_eq!(bytes_per_element::<Fr>(), 31);
+        assert_eq!(encoded_len::<Fr>(0), 1);
+        assert_eq!(encoded_len::<Fr>(31), 2);
+        assert_eq!(encoded_len::<Fr>(32), 3);
+
+        let bytes: Vec<u8> = (1..=32).collect();
+        let elements = encode_bytes::<Fr>(&bytes);
+        assert_eq!(elements.len(), 3);
+        assert_eq!(elements[0], Fr::from(32u64));
+        assert_eq!(elements[1], Fr::from_be_bytes_mod_order(&bytes[..31]));
+        assert_eq!(elements[2], Fr::from(32u64));
+
+        // The largest chunk stays below the modulus.
+        let max = [0xffu8; 31];
+        assert_eq!(
+            Fr::from_be_bytes_mod_order(&max)
+                .into_bigint()
+                .to_bytes_be()[1..],
+            max
+        );
+    }
+
+    #[test]
+    fn test_injective() {
+        assert_ne!(encode_bytes::<Fr>(&[1]), encode_bytes::<Fr>(&[0, 1]));
+        assert_ne!(encode_bytes::<Fr>(&[]), encode_bytes::<Fr>(&[0]));
+    }
+}
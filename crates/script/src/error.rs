@@ -0,0 +1,258 @@
+//! The crate-wide error type.
+//!
+//! Each module reports failures with its own error type ([`ParseError`], [`StateError`],
+//! ...). [`SafeError`] gathers them, and the errors of the fallible constructors
+//! ([`DomainSeparator::from_hex`](crate::domain::DomainSeparator::from_hex),
+//! [`IoPattern::try_from_words`](crate::IoPattern::try_from_words)), so applications can
+//! propagate any of them with `?`:
+//!
+//! ```
+//! use safe_api::domain::DomainSeparator;
+//! use safe_api::tag::Tag;
+//! use safe_api::{IoPattern, SafeError};
+//!
+//! fn tag(words: &[u32], domain_separator: &str) -> Result<Tag, SafeError> {
+//!     let domain_separator = DomainSeparator::from_hex(domain_separator)?;
+//!     Ok(IoPattern::try_from_words(words)?.tag(domain_separator.as_bytes()))
+//! }
+//!
+//! let tag_of = |words: &[u32]| tag(words, "0x41424344");
+//! assert_eq!(tag_of(&[0x80000003, 0x00000001]), Ok(Tag::from(0x0ea2aa7e178caa74de1f91e83ad43a81)));
+//! assert_eq!(tag_of(&[0x80000003, 0x00000000]), Err(SafeError::ZeroLengthOperation(1)));
+//! assert_eq!(tag(&[0x80000003], "0x4142434"), Err(SafeError::InvalidHex));
+//! ```
+
+use alloc::string::String;
+use core::fmt;
+
+use crate::aead::AuthenticationError;
+use crate::armor::ArmorError;
+use crate::codegen::CodegenError;
+use crate::config::ConfigError;
+use crate::display::ParseError;
+use crate::domain::{ExpiryError, TemplateError};
+use crate::evm::EvmError;
+use crate::flatten::FlattenError;
+use crate::hex::HexError;
+use crate::queries::QueryError;
+use crate::sigma::ProofError;
+use crate::state::StateError;
+
+/// Any error of this crate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SafeError {
+    /// Hex input with an odd number of digits, or a character that is not a hex digit.
+    InvalidHex,
+    /// A domain separator of more than 64 bytes, here of that many bytes.
+    DomainSeparatorTooLong(usize),
+    /// A domain separator label with a character that is not printable ASCII.
+    InvalidLabel,
+    /// An encoded word of length zero, at that index. The tag computation skips such words,
+    /// so a pattern containing one has the tag of a different pattern.
+    ZeroLengthOperation(usize),
+    /// An IO pattern without operations.
+    EmptyPattern,
+    /// A token of the text form of an IO pattern that is not an operation, see
+    /// [`IoPattern`](crate::IoPattern).
+    InvalidPatternToken(String),
+    /// A label given to two operations of an IO pattern.
+    DuplicateLabel(String),
+    /// An operation without a label, at that index, in an IO pattern that needs one for each,
+    /// see [`InteractiveProtocol`](crate::interactive::InteractiveProtocol).
+    UnlabeledOperation(usize),
+    /// An operation whose length, alone or aggregated with the preceding operations of the
+    /// same type, does not fit the 31 bits of an encoded word, at that index.
+    LengthOverflow(usize),
+    /// An IO pattern ending with an ABSORB, at that index, instead of a SQUEEZE.
+    MissingFinalSqueeze(usize),
+    /// An IO pattern with more operations than a
+    /// [`FixedIoPattern`](crate::fixed::FixedIoPattern) of that capacity holds.
+    PatternTooLong(usize),
+    /// See [`ParseError`].
+    Parse(ParseError),
+    /// See [`StateError`].
+    State(StateError),
+    /// See [`CodegenError`].
+    Codegen(CodegenError),
+    /// See [`FlattenError`].
+    Flatten(FlattenError),
+    /// See [`QueryError`].
+    Query(QueryError),
+    /// See [`ProofError`].
+    Proof(ProofError),
+    /// See [`ExpiryError`].
+    Expiry(ExpiryError),
+    /// See [`AuthenticationError`].
+    Authentication(AuthenticationError),
+    /// See [`ArmorError`].
+    Armor(ArmorError),
+    /// See [`EvmError`].
+    Evm(EvmError),
+    /// See [`HexError`].
+    Hex(HexError),
+    /// See [`TemplateError`].
+    Template(TemplateError),
+    /// See [`ConfigError`].
+    Config(ConfigError),
+}
+
+impl fmt::Display for SafeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SafeError::InvalidHex => f.write_str("expected an even number of hex digits"),
+            SafeError::DomainSeparatorTooLong(length) => write!(
+                f,
+                "the domain separator is longer than 64 bytes ({} bytes)",
+                length
+            ),
+            SafeError::InvalidLabel => f.write_str("the label is not printable ASCII"),
+            SafeError::ZeroLengthOperation(index) => {
+                write!(f, "operation {} has length zero", index)
+            }
+            SafeError::EmptyPattern => f.write_str("the IO pattern has no operations"),
+            SafeError::InvalidPatternToken(token) => {
+                write!(f, "invalid pattern token `{}`", token)
+            }
+            SafeError::DuplicateLabel(label) => write!(f, "the label `{}` is used twice", label),
+            SafeError::UnlabeledOperation(index) => {
+                write!(f, "operation {} has no label", index)
+            }
+            SafeError::LengthOverflow(index) => write!(
+                f,
+                "the length of operation {} does not fit in 31 bits once aggregated",
+                index
+            ),
+            SafeError::MissingFinalSqueeze(index) => write!(
+                f,
+                "the IO pattern ends with an ABSORB (operation {}) instead of a SQUEEZE",
+                index
+            ),
+            SafeError::PatternTooLong(capacity) => {
+                write!(f, "the IO pattern has more than {} operations", capacity)
+            }
+            SafeError::Parse(error) => error.fmt(f),
+            SafeError::State(error) => error.fmt(f),
+            SafeError::Codegen(error) => error.fmt(f),
+            SafeError::Flatten(error) => error.fmt(f),
+            SafeError::Query(error) => error.fmt(f),
+            SafeError::Proof(error) => error.fmt(f),
+            SafeError::Expiry(error) => error.fmt(f),
+            SafeError::Authentication(error) => error.fmt(f),
+            SafeError::Armor(error) => error.fmt(f),
+            SafeError::Evm(error) => error.fmt(f),
+            SafeError::Hex(error) => error.fmt(f),
+            SafeError::Template(error) => error.fmt(f),
+            SafeError::Config(error) => error.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SafeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SafeError::InvalidHex
+            | SafeError::DomainSeparatorTooLong(_)
+            | SafeError::InvalidLabel
+            | SafeError::ZeroLengthOperation(_)
+            | SafeError::EmptyPattern
+            | SafeError::InvalidPatternToken(_)
+            | SafeError::DuplicateLabel(_)
+            | SafeError::UnlabeledOperation(_)
+            | SafeError::LengthOverflow(_)
+            | SafeError::MissingFinalSqueeze(_)
+            | SafeError::PatternTooLong(_) => None,
+            SafeError::Parse(error) => Some(error),
+            SafeError::State(error) => Some(error),
+            SafeError::Codegen(error) => Some(error),
+            SafeError::Flatten(error) => Some(error),
+            SafeError::Query(error) => Some(error),
+            SafeError::Proof(error) => Some(error),
+            SafeError::Expiry(error) => Some(error),
+            SafeError::Authentication(error) => Some(error),
+            SafeError::Armor(error) => Some(error),
+            SafeError::Evm(error) => Some(error),
+            SafeError::Hex(error) => Some(error),
+            SafeError::Template(error) => Some(error),
+            SafeError::Config(error) => Some(error),
+        }
+    }
+}
+
+impl From<ParseError> for SafeError {
+    fn from(error: ParseError) -> Self {
+        SafeError::Parse(error)
+    }
+}
+
+impl From<StateError> for SafeError {
+    fn from(error: StateError) -> Self {
+        SafeError::State(error)
+    }
+}
+
+impl From<CodegenError> for SafeError {
+    fn from(error: CodegenError) -> Self {
+        SafeError::Codegen(error)
+    }
+}
+
+impl From<FlattenError> for SafeError {
+    fn from(error: FlattenError) -> Self {
+        SafeError::Flatten(error)
+    }
+}
+
+impl From<QueryError> for SafeError {
+    fn from(error: QueryError) -> Self {
+        SafeError::Query(error)
+    }
+}
+
+impl From<ProofError> for SafeError {
+    fn from(error: ProofError) -> Self {
+        SafeError::Proof(error)
+    }
+}
+
+impl From<ExpiryError> for SafeError {
+    fn from(error: ExpiryError) -> Self {
+        SafeError::Expiry(error)
+    }
+}
+
+impl From<AuthenticationError> for SafeError {
+    fn from(error: AuthenticationError) -> Self {
+        SafeError::Authentication(error)
+    }
+}
+
+impl From<ArmorError> for SafeError {
+    fn from(error: ArmorError) -> Self {
+        SafeError::Armor(error)
+    }
+}
+
+impl From<EvmError> for SafeError {
+    fn from(error: EvmError) -> Self {
+        SafeError::Evm(error)
+    }
+}
+
+impl From<HexError> for SafeError {
+    fn from(error: HexError) -> Self {
+        SafeError::Hex(error)
+    }
+}
+
+impl From<TemplateError> for SafeError {
+    fn from(error: TemplateError) -> Self {
+        SafeError::Template(error)
+    }
+}
+
+impl From<ConfigError> for SafeError {
+    fn from(error: ConfigError) -> Self {
+        SafeError::Config(error)
+    }
+}
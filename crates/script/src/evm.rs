@@ -0,0 +1,435 @@
+//! Canonical field encodings of Ethereum types, to absorb calldata into a BN254 sponge.
+//!
+//! Every value becomes the BN254 elements below, which a Noir circuit computes from the same
+//! calldata bytes:
+//!
+//! | Type                       | Elements                                                   |
+//! |----------------------------|------------------------------------------------------------|
+//! | `address`                  | 1: the 160-bit big-endian integer                          |
+//! | `bool`                     | 1: 0 or 1                                                  |
+//! | `uint<N>`, N ≤ 248         | 1: the integer                                             |
+//! | `int<N>`, N ≤ 248          | 1: the N-bit two's complement, read as an unsigned integer |
+//! | `bytes<N>`, N ≤ 31         | 1: the N bytes, big-endian                                 |
+//! | `uint256`, `int256`, `bytes32` | 2: the high then the low 16 bytes of the 32-byte word  |
+//! | `bytes`, `string`          | the length framed chunks of [`encode_bytes`]               |
+//!
+//! 256-bit words do not fit below the BN254 modulus, hence the two 128-bit halves. [`encode_abi`]
+//! decodes an ABI-encoded tuple of these types, e.g. the arguments of a call without its
+//! selector, and rejects non-canonical encodings (dirty high bits or padding, a `bool` other
+//! than 0 or 1, an offset out of bounds), so that two calldata only absorb the same elements if
+//! they encode the same values. Arrays and nested tuples are not supported.
+//!
+//! ```
+//! use safe_api::domain::padded_domain_separator;
+//! use safe_api::evm::{self, AbiType};
+//! use safe_api::poseidon2::Poseidon2;
+//! use safe_api::{IoPattern, Sponge};
+//!
+//! let types = evm::parse_types("address,uint256")?;
+//! let mut calldata = [0u8; 64];
+//! calldata[12..32].copy_from_slice(&[0x11; 20]);
+//! calldata[63] = 42;
+//!
+//! let elements = evm::encode_abi(&types, &calldata)?;
+//! assert_eq!(elements, [evm::encode_address(&[0x11; 20])].into_iter()
+//!     .chain(evm::encode_word(&calldata[32..].try_into().unwrap()))
+//!     .collect::<Vec<_>>());
+//!
+//! let pattern = IoPattern::new().absorb(3).squeeze(1);
+//! let mut sponge = Sponge::<Poseidon2>::start(pattern, &padded_domain_separator(b"transfer"));
+//! sponge.absorb_abi(&types, &calldata)?;
+//! let digest = sponge.squeeze();
+//! sponge.finish();
+//! # assert_eq!(digest.len(), 1);
+//! # Ok::<(), safe_api::evm::EvmError>(())
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use ark_bn254::Fr;
+use ark_ff::PrimeField;
+
+use crate::encoding::encode_bytes;
+use crate::permutation::Permutation;
+use crate::sponge::Sponge;
+
+/// A type of an ABI tuple, see the [module documentation](self).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AbiType {
+    Address,
+    Bool,
+    /// `uint<N>`, for N a multiple of 8 up to 256.
+    Uint(u16),
+    /// `int<N>`, for N a multiple of 8 up to 256.
+    Int(u16),
+    /// `bytes<N>`, for N from 1 to 32.
+    FixedBytes(u8),
+    Bytes,
+    String,
+}
+
+impl AbiType {
+    /// Returns the number of elements encoding a value of the type, `None` for the dynamic
+    /// `bytes` and `string`, whose length depends on the value.
+    pub fn encoded_len(self) -> Option<usize> {
+        match self {
+            AbiType::Uint(256) | AbiType::Int(256) | AbiType::FixedBytes(32) => Some(2),
+            AbiType::Bytes | AbiType::String => None,
+            _ => Some(1),
+        }
+    }
+
+    fn is_dynamic(self) -> bool {
+        self.encoded_len().is_none()
+    }
+}
+
+impl FromStr for AbiType {
+    type Err = EvmError;
+
+    /// Parses a Solidity type name, e.g. `uint256` or `bytes4`; `uint` and `int` are
+    /// `uint256` and `int256`.
+    fn from_str(input: &str) -> Result<Self, EvmError> {
+        let invalid = || EvmError::InvalidType(input.to_string());
+        let bits = |digits: &str| -> Result<u16, EvmError> {
+            match digits {
+                "" => Ok(256),
+                _ => digits
+                    .parse()
+                    .ok()
+                    .filter(|bits| (8..=256).contains(bits) && bits % 8 == 0)
+                    .ok_or_else(invalid),
+            }
+        };
+        Ok(match input {
+            "address" => AbiType::Address,
+            "bool" => AbiType::Bool,
+            "bytes" => AbiType::Bytes,
+            "string" => AbiType::String,
+            _ => {
+                if let Some(digits) = input.strip_prefix("uint") {
+                    AbiType::Uint(bits(digits)?)
+                } else if let Some(digits) = input.strip_prefix("int") {
+                    AbiType::Int(bits(digits)?)
+                } else if let Some(digits) = input.strip_prefix("bytes") {
+                    AbiType::FixedBytes(
+                        digits
+                            .parse()
+                            .ok()
+                            .filter(|length| (1..=32).contains(length))
+                            .ok_or_else(invalid)?,
+                    )
+                } else {
+                    return Err(invalid());
+                }
+            }
+        })
+    }
+}
+
+impl fmt::Display for AbiType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AbiType::Address => f.write_str("address"),
+            AbiType::Bool => f.write_str("bool"),
+            AbiType::Uint(bits) => write!(f, "uint{}", bits),
+            AbiType::Int(bits) => write!(f, "int{}", bits),
+            AbiType::FixedBytes(length) => write!(f, "bytes{}", length),
+            AbiType::Bytes => f.write_str("bytes"),
+            AbiType::String => f.write_str("string"),
+        }
+    }
+}
+
+/// Errors raised while decoding ABI-encoded data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EvmError {
+    /// A type name that is not a supported Solidity type.
+    InvalidType(String),
+    /// Data shorter than the encoding of the types requires.
+    Truncated,
+    /// The offset of the dynamic value at that index points outside the data.
+    InvalidOffset(usize),
+    /// The value at that index is not canonically encoded: its unused bits or padding are
+    /// not zero, or it is a `bool` other than 0 or 1.
+    NonCanonical(usize),
+}
+
+impl fmt::Display for EvmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvmError::InvalidType(name) => write!(f, "unsupported ABI type `{}`", name),
+            EvmError::Truncated => f.write_str("the ABI data is truncated"),
+            EvmError::InvalidOffset(index) => {
+                write!(f, "the offset of ABI value {} is out of bounds", index)
+            }
+            EvmError::NonCanonical(index) => {
+                write!(f, "ABI value {} is not canonically encoded", index)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EvmError {}
+
+/// Parses a comma-separated list of Solidity types, e.g. `address,uint256,bytes`.
+pub fn parse_types(input: &str) -> Result<Vec<AbiType>, EvmError> {
+    input.split(',').map(|name| name.trim().parse()).collect()
+}
+
+/// Encodes an address as one element.
+pub fn encode_address(address: &[u8; 20]) -> Fr {
+    Fr::from_be_bytes_mod_order(address)
+}
+
+/// Encodes a 32-byte word, a `uint256`, `int256` or `bytes32`, as its high then low halves.
+pub fn encode_word(word: &[u8; 32]) -> [Fr; 2] {
+    [
+        Fr::from_be_bytes_mod_order(&word[..16]),
+        Fr::from_be_bytes_mod_order(&word[16..]),
+    ]
+}
+
+/// Decodes the ABI-encoded tuple `data` of `types` and encodes its values, in order.
+pub fn encode_abi(types: &[AbiType], data: &[u8]) -> Result<Vec<Fr>, EvmError> {
+    let mut elements = Vec::new();
+    for (index, &abi_type) in types.iter().enumerate() {
+        let head = word(data, 32 * index).ok_or(EvmError::Truncated)?;
+        match abi_type.is_dynamic() {
+            true => {
+                let bytes = dynamic_bytes(data, head, index)?;
+                if abi_type == AbiType::String && core::str::from_utf8(bytes).is_err() {
+                    return Err(EvmError::NonCanonical(index));
+                }
+                elements.extend(encode_bytes::<Fr>(bytes));
+            }
+            false => encode_static(abi_type, head, index, &mut elements)?,
+        }
+    }
+    Ok(elements)
+}
+
+impl<P: Permutation<Field = Fr>> Sponge<P> {
+    /// Absorbs the values of the ABI-encoded tuple `data` of `types` as one ABSORB, see
+    /// [`encode_abi`].
+    ///
+    /// # Errors
+    /// The [`EvmError`] of [`encode_abi`], before anything is absorbed.
+    ///
+    /// # Panics
+    /// If the next operation of the IO pattern is not an ABSORB of as many elements.
+    pub fn absorb_abi(&mut self, types: &[AbiType], data: &[u8]) -> Result<(), EvmError> {
+        self.absorb(&encode_abi(types, data)?);
+        Ok(())
+    }
+}
+
+fn word(data: &[u8], offset: usize) -> Option<&[u8; 32]> {
+    data.get(offset..offset.checked_add(32)?)?.try_into().ok()
+}
+
+/// Returns the bytes of `data` at the offset `head`, a length word followed by the bytes,
+/// zero padded to whole words.
+fn dynamic_bytes<'a>(data: &'a [u8], head: &[u8; 32], index: usize) -> Result<&'a [u8], EvmError> {
+    let offset = small_integer(head).ok_or(EvmError::InvalidOffset(index))?;
+    let length = word(data, offset).ok_or(EvmError::InvalidOffset(index))?;
+    let length = small_integer(length).ok_or(EvmError::Truncated)?;
+    let start = offset + 32;
+    let padded = length
+        .div_ceil(32)
+        .checked_mul(32)
+        .ok_or(EvmError::Truncated)?;
+    let end = start.checked_add(padded).ok_or(EvmError::Truncated)?;
+    let bytes = data.get(start..end).ok_or(EvmError::Truncated)?;
+    if bytes[length..].iter().any(|&byte| byte != 0) {
+        return Err(EvmError::NonCanonical(index));
+    }
+    Ok(&bytes[..length])
+}
+
+/// Returns the word as an integer if it fits in a `usize`.
+fn small_integer(word: &[u8; 32]) -> Option<usize> {
+    let (high, low) = word.split_at(24);
+    if high.iter().any(|&byte| byte != 0) {
+        return None;
+    }
+    usize::try_from(u64::from_be_bytes(low.try_into().expect("8 bytes"))).ok()
+}
+
+fn encode_static(
+    abi_type: AbiType,
+    word: &[u8; 32],
+    index: usize,
+    elements: &mut Vec<Fr>,
+) -> Result<(), EvmError> {
+    let non_canonical = || EvmError::NonCanonical(index);
+    let zeros = |bytes: &[u8]| bytes.iter().all(|&byte| byte == 0);
+    match abi_type {
+        AbiType::Uint(256) | AbiType::Int(256) | AbiType::FixedBytes(32) => {
+            elements.extend(encode_word(word));
+        }
+        AbiType::Address | AbiType::Bool | AbiType::Uint(_) => {
+            let bytes = match abi_type {
+                AbiType::Address => 20,
+                AbiType::Bool => 1,
+                AbiType::Uint(bits) => bits as usize / 8,
+                _ => unreachable!(),
+            };
+            let (high, value) = word.split_at(32 - bytes);
+            if !zeros(high) || (abi_type == AbiType::Bool && value[0] > 1) {
+                return Err(non_canonical());
+            }
+            elements.push(Fr::from_be_bytes_mod_order(value));
+        }
+        AbiType::Int(bits) => {
+            // The high bytes sign-extend the value.
+            let (high, value) = word.split_at(32 - bits as usize / 8);
+            let extension = match value[0] & 0x80 {
+                0 => 0x00,
+                _ => 0xff,
+            };
+            if high.iter().any(|&byte| byte != extension) {
+                return Err(non_canonical());
+            }
+            elements.push(Fr::from_be_bytes_mod_order(value));
+        }
+        AbiType::FixedBytes(length) => {
+            let (value, padding) = word.split_at(length as usize);
+            if !zeros(padding) {
+                return Err(non_canonical());
+            }
+            elements.push(Fr::from_be_bytes_mod_order(value));
+        }
+        AbiType::Bytes | AbiType::String => unreachable!("dynamic types have no static encoding"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word_of(value: u64) -> [u8; 32] {
+        let mut word = [0u8; 32];
+        word[24..].copy_from_slice(&value.to_be_bytes());
+        word
+    }
+
+    #[test]
+    fn test_parse_types() {
+        assert_eq!(
+            parse_types("address, uint, int8,bytes4,bool,bytes,string"),
+            Ok(vec![
+                AbiType::Address,
+                AbiType::Uint(256),
+                AbiType::Int(8),
+                AbiType::FixedBytes(4),
+                AbiType::Bool,
+                AbiType::Bytes,
+                AbiType::String,
+            ])
+        );
+        for name in [
+            "uint7",
+            "uint264",
+            "bytes0",
+            "bytes33",
+            "address[]",
+            "(uint8)",
+        ] {
+            assert_eq!(
+                name.parse::<AbiType>(),
+                Err(EvmError::InvalidType(name.to_string()))
+            );
+        }
+        assert_eq!(AbiType::Uint(256).to_string(), "uint256");
+    }
+
+    #[test]
+    fn test_encode_static() {
+        let mut address = word_of(0);
+        address[12..].copy_from_slice(&[0xab; 20]);
+        let mut negative = [0xff; 32];
+        negative[31] = 0xfe;
+        let mut selector = [0u8; 32];
+        selector[..4].copy_from_slice(&[0xa9, 0x05, 0x9c, 0xbb]);
+        let data = [
+            address,
+            word_of(1),
+            word_of(1000),
+            negative,
+            selector,
+            negative,
+        ]
+        .concat();
+        let types = parse_types("address,bool,uint16,int8,bytes4,uint256").unwrap();
+
+        let elements = encode_abi(&types, &data).unwrap();
+        assert_eq!(elements.len(), 7);
+        assert_eq!(elements[0], encode_address(&[0xab; 20]));
+        assert_eq!(elements[1..4], [1u64, 1000, 0xfe].map(Fr::from));
+        assert_eq!(elements[4], Fr::from(0xa9059cbbu64));
+        assert_eq!(elements[5..], encode_word(&negative));
+        assert_eq!(elements[5], Fr::from(u128::MAX));
+
+        // Dirty high bits, bools other than 0 or 1, bad sign extensions and padding.
+        let reject = |types: &str, word: [u8; 32]| encode_abi(&parse_types(types).unwrap(), &word);
+        assert_eq!(reject("address", [1; 32]), Err(EvmError::NonCanonical(0)));
+        assert_eq!(reject("bool", word_of(2)), Err(EvmError::NonCanonical(0)));
+        assert_eq!(
+            reject("uint8", word_of(256)),
+            Err(EvmError::NonCanonical(0))
+        );
+        assert_eq!(
+            reject("int8", word_of(0x80)),
+            Err(EvmError::NonCanonical(0))
+        );
+        assert_eq!(reject("bytes4", word_of(1)), Err(EvmError::NonCanonical(0)));
+        assert_eq!(encode_abi(&types, &data[..64]), Err(EvmError::Truncated));
+    }
+
+    #[test]
+    fn test_encode_dynamic() {
+        let text = b"hello, calldata";
+        let mut padded = [0u8; 32];
+        padded[..text.len()].copy_from_slice(text);
+        // (uint8, bytes, string): the heads, then the tails of the dynamic values.
+        let data = [
+            word_of(7),
+            word_of(96),
+            word_of(160),
+            word_of(text.len() as u64),
+            padded,
+            word_of(text.len() as u64),
+            padded,
+        ]
+        .concat();
+        let types = parse_types("uint8,bytes,string").unwrap();
+        let elements = encode_abi(&types, &data).unwrap();
+        let bytes = encode_bytes::<Fr>(text);
+        assert_eq!(
+            elements,
+            [vec![Fr::from(7u64)], bytes.clone(), bytes].concat()
+        );
+
+        let mut dirty = data.clone();
+        dirty[160 - 1] = 1;
+        assert_eq!(encode_abi(&types, &dirty), Err(EvmError::NonCanonical(1)));
+        let mut out_of_bounds = data.clone();
+        out_of_bounds[63] = 250;
+        assert_eq!(
+            encode_abi(&types, &out_of_bounds),
+            Err(EvmError::InvalidOffset(1))
+        );
+        let mut invalid_utf8 = data;
+        invalid_utf8[192] = 0xff;
+        assert_eq!(
+            encode_abi(&types, &invalid_utf8),
+            Err(EvmError::NonCanonical(2))
+        );
+    }
+}
@@ -0,0 +1,60 @@
+//! Small prime fields for the sponge, for interoperability with Plonky3 and RISC Zero.
+//!
+//! Both are arkworks fields, so [`Sponge`](crate::Sponge), [`Transcript`](crate::Transcript)
+//! and the encodings work over them unchanged, with the Poseidon2 instances of
+//! [`poseidon2::goldilocks`](crate::poseidon2::goldilocks) and
+//! [`poseidon2::baby_bear`](crate::poseidon2::baby_bear). A 128-bit tag does not fit a single
+//! element of these fields: the sponge spreads it over the capacity, see
+//! [`compute_tag_limbs`](crate::tag::compute_tag_limbs).
+//!
+//! No byte of an element of these fields is close enough to uniform, so deriving bytes from
+//! them, with [`Sponge::squeeze_bytes`](crate::Sponge::squeeze_bytes) or the RNGs of
+//! [`rng`](crate::rng), fails to compile:
+//!
+//! ```compile_fail
+//! use safe_api::fields::Goldilocks;
+//! use safe_api::rng::bytes_per_squeezed_element;
+//!
+//! let _ = bytes_per_squeezed_element::<Goldilocks>();
+//! ```
+
+use ark_ff::{Fp64, MontBackend, MontConfig};
+
+/// The parameters of [`Goldilocks`].
+#[derive(MontConfig)]
+#[modulus = "18446744069414584321"]
+#[generator = "7"]
+pub struct GoldilocksConfig;
+
+/// The 64-bit Goldilocks field, of characteristic 2^64 - 2^32 + 1.
+pub type Goldilocks = Fp64<MontBackend<GoldilocksConfig, 1>>;
+
+/// The parameters of [`BabyBear`].
+#[derive(MontConfig)]
+#[modulus = "2013265921"]
+#[generator = "31"]
+pub struct BabyBearConfig;
+
+/// The 31-bit BabyBear field, of characteristic 2^31 - 2^27 + 1.
+pub type BabyBear = Fp64<MontBackend<BabyBearConfig, 1>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ff::{Field, PrimeField};
+
+    #[test]
+    fn test_moduli() {
+        assert_eq!(Goldilocks::MODULUS_BIT_SIZE, 64);
+        assert_eq!(BabyBear::MODULUS_BIT_SIZE, 31);
+        assert_eq!(
+            -Goldilocks::from(1u64),
+            Goldilocks::from(u64::MAX - u32::MAX as u64)
+        );
+        assert_eq!(-BabyBear::from(1u64), BabyBear::from(2013265920u64));
+        assert_eq!(
+            BabyBear::from(2u64).inverse().unwrap(),
+            BabyBear::from(1006632961u64)
+        );
+    }
+}
@@ -0,0 +1,406 @@
+//! Fixed-capacity tags and sponge for embedded targets, e.g. hardware signers computing
+//! transcript tags.
+//!
+//! [`FixedIoPattern`] holds at most `MAX_OPS` operations inline, and builds in `const`
+//! context, so a pattern that exceeds its capacity fails to compile:
+//!
+//! ```
+//! use safe_api::fixed::FixedIoPattern;
+//!
+//! const PATTERN: FixedIoPattern<2> = FixedIoPattern::new().absorb(3).squeeze(1);
+//!
+//! let mut domain_separator = [0u8; 64];
+//! domain_separator[..4].copy_from_slice(b"ABCD");
+//! assert_eq!(PATTERN.tag(&domain_separator).to_string(), "0x0ea2aa7e178caa74de1f91e83ad43a81");
+//! ```
+//!
+//! ```compile_fail
+//! use safe_api::fixed::FixedIoPattern;
+//!
+//! const PATTERN: FixedIoPattern<1> = FixedIoPattern::new().absorb(3).squeeze(1);
+//! ```
+//!
+//! [`compute_tag_fixed`] and [`FixedSponge`] are the heapless counterparts of
+//! [`compute_tag`](crate::compute_tag) and [`Sponge`](crate::Sponge), with the same outputs:
+//! the pattern, the aggregated words and the state stay on the stack, the preimage is hashed
+//! as it is produced, and squeezed elements are written to a buffer of the caller. Nothing in
+//! this module allocates, so it runs under a global allocator that always fails, which the
+//! crate still needs to link since it depends on `alloc`.
+
+use ark_ff::Zero;
+use sha2::{Digest, Sha256};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::domain::is_empty_domain;
+use crate::error::SafeError;
+use crate::io_pattern::{aggregate, Operation};
+use crate::permutation::Permutation;
+use crate::tag::{digest_limbs, Tag, TagLength};
+
+/// An IO pattern of at most `MAX_OPS` operations, stored inline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixedIoPattern<const MAX_OPS: usize> {
+    operations: [Operation; MAX_OPS],
+    len: usize,
+}
+
+impl<const MAX_OPS: usize> Default for FixedIoPattern<MAX_OPS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const MAX_OPS: usize> FixedIoPattern<MAX_OPS> {
+    /// Creates an empty IO pattern.
+    pub const fn new() -> Self {
+        FixedIoPattern {
+            operations: [Operation::Squeeze(0); MAX_OPS],
+            len: 0,
+        }
+    }
+
+    /// Decodes an IO pattern from 32-bit encoded words, one operation per word, like
+    /// [`IoPattern::from_words`](crate::IoPattern::from_words).
+    ///
+    /// # Errors
+    /// [`SafeError::PatternTooLong`] if there are more than `MAX_OPS` words.
+    pub fn from_words(words: &[u32]) -> Result<Self, SafeError> {
+        if words.len() > MAX_OPS {
+            return Err(SafeError::PatternTooLong(MAX_OPS));
+        }
+        let mut pattern = Self::new();
+        for (operation, &word) in pattern.operations.iter_mut().zip(words) {
+            *operation = Operation::from_word(word);
+        }
+        pattern.len = words.len();
+        Ok(pattern)
+    }
+
+    /// Appends an ABSORB(`length`) operation.
+    ///
+    /// # Panics
+    /// If the pattern already has `MAX_OPS` operations; in `const` context, this is a
+    /// compilation error.
+    pub const fn absorb(self, length: u32) -> Self {
+        self.push(Operation::Absorb(length))
+    }
+
+    /// Appends a SQUEEZE(`length`) operation.
+    ///
+    /// # Panics
+    /// If the pattern already has `MAX_OPS` operations; in `const` context, this is a
+    /// compilation error.
+    pub const fn squeeze(self, length: u32) -> Self {
+        self.push(Operation::Squeeze(length))
+    }
+
+    const fn push(mut self, operation: Operation) -> Self {
+        assert!(self.len < MAX_OPS, "the IO pattern exceeds its capacity");
+        self.operations[self.len] = operation;
+        self.len += 1;
+        self
+    }
+
+    /// Returns the operations of the pattern.
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations[..self.len]
+    }
+
+    /// Returns the number of operations.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the pattern has no operations.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Computes the tag of a sponge instance using this IO pattern (following SAFE spec 2.3),
+    /// like [`IoPattern::tag`](crate::IoPattern::tag).
+    pub fn tag(&self, domain_separator: &[u8; 64]) -> Tag {
+        Tag::from_digest(&self.digest(domain_separator), TagLength::Bits128)
+    }
+
+    /// Returns the SHA-256 digest of the tag preimage, hashing the aggregated words as they
+    /// are produced.
+    fn digest(&self, domain_separator: &[u8; 64]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        aggregate(self.operations(), |operation| {
+            hasher.update(operation.encoded_word().to_be_bytes())
+        });
+        hasher.update(domain_separator);
+        hasher.finalize().into()
+    }
+}
+
+/// Computes the tag of a pattern of at most `MAX_OPS` words without allocating, like
+/// [`compute_tag`](crate::compute_tag).
+///
+/// # Errors
+/// [`SafeError::PatternTooLong`] if `io_pattern` has more than `MAX_OPS` words.
+pub fn compute_tag_fixed<const MAX_OPS: usize>(
+    io_pattern: &[u32],
+    domain_separator: &[u8; 64],
+) -> Result<Tag, SafeError> {
+    Ok(FixedIoPattern::<MAX_OPS>::from_words(io_pattern)?.tag(domain_separator))
+}
+
+/// A [`Sponge`](crate::Sponge) whose state of `WIDTH` elements and IO pattern of at most
+/// `MAX_OPS` operations are stored inline.
+///
+/// `WIDTH` must be the [`Permutation::WIDTH`] of `P`, which is checked at compile time. With
+/// the same pattern, domain separator and inputs, it produces the outputs of
+/// [`Sponge`](crate::Sponge), and misuse panics the same way. Elements are squeezed into a
+/// buffer of the caller with [`FixedSponge::squeeze_into`].
+///
+/// ```
+/// use ark_bn254::Fr;
+/// use safe_api::fixed::{FixedIoPattern, FixedSponge};
+/// use safe_api::poseidon2::{Poseidon2, STATE_WIDTH};
+///
+/// const PATTERN: FixedIoPattern<2> = FixedIoPattern::new().absorb(2).squeeze(1);
+///
+/// let mut domain_separator = [0u8; 64];
+/// domain_separator[..4].copy_from_slice(b"ABCD");
+/// let mut sponge = FixedSponge::<Poseidon2, STATE_WIDTH, 2>::start(PATTERN, &domain_separator);
+/// sponge.absorb(&[Fr::from(1u64), Fr::from(2u64)]);
+/// let mut challenge = [Fr::from(0u64)];
+/// sponge.squeeze_into(&mut challenge);
+/// sponge.finish();
+/// ```
+///
+/// The state is erased when the sponge is dropped.
+#[derive(Clone, Debug)]
+pub struct FixedSponge<P: Permutation, const WIDTH: usize, const MAX_OPS: usize> {
+    permutation: P,
+    state: [P::Field; WIDTH],
+    tag: P::Field,
+    absorb_pos: usize,
+    squeeze_pos: usize,
+    io_pattern: FixedIoPattern<MAX_OPS>,
+    io_count: usize,
+}
+
+impl<P: Permutation + Default, const WIDTH: usize, const MAX_OPS: usize>
+    FixedSponge<P, WIDTH, MAX_OPS>
+{
+    /// Initializes a new sponge instance, like [`Sponge::start`](crate::Sponge::start).
+    ///
+    /// # Panics
+    /// If `domain_separator` is empty.
+    pub fn start(io_pattern: FixedIoPattern<MAX_OPS>, domain_separator: &[u8; 64]) -> Self {
+        Self::with_permutation(P::default(), io_pattern, domain_separator)
+    }
+}
+
+impl<P: Permutation, const WIDTH: usize, const MAX_OPS: usize> FixedSponge<P, WIDTH, MAX_OPS> {
+    /// Initializes a new sponge instance running on the given permutation instance.
+    ///
+    /// # Panics
+    /// If `domain_separator` is empty.
+    pub fn with_permutation(
+        permutation: P,
+        io_pattern: FixedIoPattern<MAX_OPS>,
+        domain_separator: &[u8; 64],
+    ) -> Self {
+        const {
+            assert!(
+                WIDTH == P::WIDTH,
+                "WIDTH must be the state width of the permutation"
+            )
+        };
+        assert!(!is_empty_domain(domain_separator), "Empty domain separator");
+
+        // Initialize capacity with tag (spec 2.4), in several limbs over small fields.
+        let digest = io_pattern.digest(domain_separator);
+        let mut state = [P::Field::zero(); WIDTH];
+        for (element, limb) in
+            state
+                .iter_mut()
+                .zip(digest_limbs(&digest, TagLength::Bits128, P::CAPACITY))
+        {
+            *element = limb;
+        }
+
+        FixedSponge {
+            permutation,
+            tag: state[0],
+            state,
+            absorb_pos: 0,
+            squeeze_pos: 0,
+            io_pattern,
+            io_count: 0,
+        }
+    }
+
+    /// Returns the tag of the sponge, see [`Sponge::tag`](crate::Sponge::tag).
+    pub fn tag(&self) -> P::Field {
+        self.tag
+    }
+
+    /// Returns the IO pattern the sponge was started with.
+    pub fn io_pattern(&self) -> &FixedIoPattern<MAX_OPS> {
+        &self.io_pattern
+    }
+
+    /// Absorbs field elements into the sponge state (following spec 2.4).
+    ///
+    /// # Panics
+    /// If the next operation of the IO pattern is not an ABSORB of `input.len()` elements.
+    pub fn absorb(&mut self, input: &[P::Field]) {
+        let expected = self.next_operation();
+        assert!(expected.is_absorb(), "Expected ABSORB operation");
+        assert!(expected.len() as usize == input.len(), "Length mismatch");
+        self.io_count += 1;
+
+        for &element in input {
+            if self.absorb_pos == P::RATE {
+                self.permutation.permute(&mut self.state);
+                self.absorb_pos = 0;
+            }
+            self.state[self.absorb_pos + P::CAPACITY] += element;
+            self.absorb_pos += 1;
+        }
+
+        // Force permute at start of next SQUEEZE (spec 2.4).
+        self.squeeze_pos = P::RATE;
+    }
+
+    /// Squeezes the next operation of the IO pattern into `output` (following spec 2.4).
+    ///
+    /// # Panics
+    /// If the next operation of the IO pattern is not a SQUEEZE of `output.len()` elements.
+    pub fn squeeze_into(&mut self, output: &mut [P::Field]) {
+        let expected = self.next_operation();
+        assert!(!expected.is_absorb(), "Expected SQUEEZE operation");
+        assert!(expected.len() as usize == output.len(), "Length mismatch");
+        self.io_count += 1;
+
+        for element in output {
+            if self.squeeze_pos == P::RATE {
+                self.permutation.permute(&mut self.state);
+                self.squeeze_pos = 0;
+                self.absorb_pos = 0;
+            }
+            *element = self.state[self.squeeze_pos + P::CAPACITY];
+            self.squeeze_pos += 1;
+        }
+    }
+
+    /// Finalizes the sponge instance, verifying that all expected operations have been
+    /// performed and clearing the internal state (following spec 2.4).
+    ///
+    /// # Panics
+    /// If operations of the IO pattern remain.
+    pub fn finish(&mut self) {
+        assert!(
+            self.io_count == self.io_pattern.len(),
+            "IO pattern not completed"
+        );
+        self.zeroize();
+    }
+
+    fn next_operation(&self) -> Operation {
+        assert!(
+            self.io_count < self.io_pattern.len(),
+            "IO pattern already completed"
+        );
+        self.io_pattern.operations()[self.io_count]
+    }
+}
+
+impl<P: Permutation, const WIDTH: usize, const MAX_OPS: usize> Zeroize
+    for FixedSponge<P, WIDTH, MAX_OPS>
+{
+    fn zeroize(&mut self) {
+        self.state.iter_mut().for_each(Zeroize::zeroize);
+        self.absorb_pos = 0;
+        self.squeeze_pos = 0;
+        self.io_count = 0;
+    }
+}
+
+impl<P: Permutation, const WIDTH: usize, const MAX_OPS: usize> Drop
+    for FixedSponge<P, WIDTH, MAX_OPS>
+{
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<P: Permutation, const WIDTH: usize, const MAX_OPS: usize> ZeroizeOnDrop
+    for FixedSponge<P, WIDTH, MAX_OPS>
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tag::compute_tag;
+
+    fn abcd_domain() -> [u8; 64] {
+        crate::domain::padded_domain_separator(b"ABCD")
+    }
+
+    #[test]
+    fn test_compute_tag_fixed() {
+        let patterns: [&[u32]; 4] = [
+            &[0x80000003, 0x00000001],
+            &[0x80000001, 0x80000001, 0x00000000, 0x00000002],
+            &[0x00000000, 0x80000000, 0x00000001, 0x80000002],
+            &[],
+        ];
+        for words in patterns {
+            assert_eq!(
+                compute_tag_fixed::<4>(words, &abcd_domain()),
+                Ok(compute_tag(words, abcd_domain()))
+            );
+        }
+        assert_eq!(
+            compute_tag_fixed::<1>(&[0x80000003, 0x00000001], &abcd_domain()),
+            Err(SafeError::PatternTooLong(1))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "the IO pattern exceeds its capacity")]
+    fn test_pattern_capacity() {
+        let _ = FixedIoPattern::<1>::new().absorb(1).squeeze(1);
+    }
+
+    #[cfg(feature = "poseidon2")]
+    #[test]
+    fn test_matches_sponge() {
+        use crate::poseidon2::{Poseidon2, STATE_WIDTH};
+        use crate::{IoPattern, Sponge};
+        use ark_bn254::Fr;
+
+        const PATTERN: FixedIoPattern<4> = FixedIoPattern::new()
+            .absorb(4)
+            .squeeze(2)
+            .absorb(1)
+            .squeeze(5);
+        let inputs: [Fr; 5] = core::array::from_fn(|i| Fr::from(i as u64 + 1));
+
+        let mut fixed = FixedSponge::<Poseidon2, STATE_WIDTH, 4>::start(PATTERN, &abcd_domain());
+        let mut sponge = Sponge::<Poseidon2>::start(
+            IoPattern::new().absorb(4).squeeze(2).absorb(1).squeeze(5),
+            abcd_domain(),
+        );
+        assert_eq!(fixed.tag(), sponge.tag());
+
+        fixed.absorb(&inputs[..4]);
+        sponge.absorb(&inputs[..4]);
+        let mut output = [Fr::zero(); 2];
+        fixed.squeeze_into(&mut output);
+        assert_eq!(output.to_vec(), sponge.squeeze());
+        fixed.absorb(&inputs[4..]);
+        sponge.absorb(&inputs[4..]);
+        let mut output = [Fr::zero(); 5];
+        fixed.squeeze_into(&mut output);
+        assert_eq!(output.to_vec(), sponge.squeeze());
+        fixed.finish();
+        sponge.finish();
+    }
+}
@@ -0,0 +1,379 @@
+//! Arguments of the Noir gadgets, flattened from the Rust structures.
+//!
+//! The gadgets of the Noir library take fixed-size arrays in a fixed order. The types here
+//! convert [`MerkleProof`]s, commitment [`Opening`]s, chunk proofs and challenge lists into
+//! exactly those arguments, checking each length against the size the circuit was compiled
+//! for, so a mismatch is reported here instead of failing at proving time:
+//!
+//! - [`MerkleRootInputs`]: `merkle_root(leaf, index, siblings, domain_separator)`,
+//! - [`CommitmentInputs`]: `verify_commitment(commitment, values, randomness, ...)`,
+//! - [`ChunkInputs`]: `verify_chunk(commitment, byte_len, index, chunk, siblings)` of
+//!   [`noir_chunk_verifier`](crate::codegen::noir_chunk_verifier),
+//! - [`to_array`]: a challenge list, e.g. for `derive_queries`.
+//!
+//! [`ProverInputs`] then writes them as a `Prover.toml`:
+//!
+//! ```
+//! use ark_bn254::Fr;
+//! use safe_api::domain::padded_domain_separator;
+//! use safe_api::flatten::{MerkleRootInputs, ProverInputs};
+//! use safe_api::merkle::MerkleTree;
+//! use safe_api::poseidon2::Poseidon2;
+//!
+//! let leaves = (1..=4u64).map(Fr::from).collect();
+//! let tree = MerkleTree::<Poseidon2>::new(leaves, &padded_domain_separator(b"my-app/tree"));
+//! // For a circuit taking `merkle_root::<2>` arguments prefixed with `member`.
+//! let inputs = MerkleRootInputs::<Fr, 2>::new(tree.leaf(2), &tree.open(2))?;
+//! let mut toml = ProverInputs::new();
+//! toml.merkle_root("member", &inputs);
+//! let toml = toml.finish()?;
+//! assert!(toml.starts_with("member_leaf = \"0x0000"));
+//! assert!(toml.contains("member_index = 2\n"));
+//! # Ok::<(), safe_api::flatten::FlattenError>(())
+//! ```
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use ark_ff::PrimeField;
+
+use crate::chunked::{ChunkProof, ChunkedCommitment};
+use crate::codegen::is_identifier;
+use crate::commitment::{Commitment, Opening};
+use crate::display::format_element;
+use crate::merkle::MerkleProof;
+use crate::permutation::Permutation;
+
+/// Error of the conversions of this module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FlattenError {
+    /// An array of `actual` elements where the circuit takes `expected`.
+    LengthMismatch { expected: usize, actual: usize },
+    /// A leaf or chunk index that does not fit a tree of the depth of the circuit.
+    IndexOutOfRange { index: usize, depth: usize },
+    /// Chunk data of that length, where the chunk at the index of the proof has another
+    /// length, or there is no such chunk.
+    InvalidChunk(usize),
+    /// An input name that is not a lowercase identifier.
+    InvalidName(String),
+    /// Two inputs with the same name.
+    DuplicateName(String),
+}
+
+impl fmt::Display for FlattenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlattenError::LengthMismatch { expected, actual } => write!(
+                f,
+                "expected an array of {} elements, got {}",
+                expected, actual
+            ),
+            FlattenError::IndexOutOfRange { index, depth } => {
+                write!(f, "index {} out of range for depth {}", index, depth)
+            }
+            FlattenError::InvalidChunk(length) => {
+                write!(f, "chunk data of {} bytes does not match the chunk", length)
+            }
+            FlattenError::InvalidName(name) => {
+                write!(f, "`{}` is not a lowercase identifier", name)
+            }
+            FlattenError::DuplicateName(name) => write!(f, "duplicate input `{}`", name),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FlattenError {}
+
+/// Returns `elements` as an array of `N` elements, e.g. a challenge list of the size the
+/// circuit takes.
+pub fn to_array<F: Copy, const N: usize>(elements: &[F]) -> Result<[F; N], FlattenError> {
+    elements
+        .try_into()
+        .map_err(|_| FlattenError::LengthMismatch {
+            expected: N,
+            actual: elements.len(),
+        })
+}
+
+/// The arguments of the Noir `merkle_root::<D>`, in order, but the domain separator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MerkleRootInputs<F, const D: usize> {
+    pub leaf: F,
+    pub index: u32,
+    pub siblings: [F; D],
+}
+
+impl<F: Copy, const D: usize> MerkleRootInputs<F, D> {
+    /// Flattens the proof of `leaf` for a tree of depth `D`.
+    pub fn new(leaf: F, proof: &MerkleProof<F>) -> Result<Self, FlattenError> {
+        Ok(MerkleRootInputs {
+            leaf,
+            index: tree_index(proof.index, D)?,
+            siblings: to_array(&proof.siblings)?,
+        })
+    }
+}
+
+/// The arguments of the Noir `verify_commitment::<N>`, in order, but the domain separator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CommitmentInputs<F, const N: usize> {
+    pub commitment: F,
+    pub values: [F; N],
+    pub randomness: F,
+}
+
+impl<F: Copy, const N: usize> CommitmentInputs<F, N> {
+    /// Flattens the opening of `commitment` to `N` values.
+    pub fn new(commitment: &Commitment<F>, opening: &Opening<F>) -> Result<Self, FlattenError> {
+        Ok(CommitmentInputs {
+            commitment: commitment.0,
+            values: to_array(&opening.values)?,
+            randomness: opening.randomness,
+        })
+    }
+}
+
+/// The arguments of `verify_chunk` emitted by
+/// [`noir_chunk_verifier`](crate::codegen::noir_chunk_verifier)`(C, D)`, in order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkInputs<F, const C: usize, const D: usize> {
+    pub commitment: F,
+    pub byte_len: u64,
+    pub index: u32,
+    /// The chunk, zero padded to `C` bytes.
+    pub chunk: [u8; C],
+    pub siblings: [F; D],
+}
+
+impl<F: PrimeField, const C: usize, const D: usize> ChunkInputs<F, C, D> {
+    /// Flattens the proof of the chunk of `commitment` at `proof.index`, given `chunk` without
+    /// padding (i.e. as the bytes of the committed data it covers).
+    pub fn new<P: Permutation<Field = F>>(
+        commitment: &ChunkedCommitment<P>,
+        chunk: &[u8],
+        proof: &ChunkProof<F>,
+    ) -> Result<Self, FlattenError> {
+        if commitment.chunk_size() != C {
+            return Err(FlattenError::LengthMismatch {
+                expected: C,
+                actual: commitment.chunk_size(),
+            });
+        }
+        let index = tree_index(proof.index, D)?;
+        let start = (proof.index as u64).saturating_mul(C as u64);
+        let expected = commitment.byte_len().saturating_sub(start).min(C as u64);
+        if proof.index >= commitment.chunk_count() || chunk.len() as u64 != expected {
+            return Err(FlattenError::InvalidChunk(chunk.len()));
+        }
+        let mut padded = [0u8; C];
+        padded[..chunk.len()].copy_from_slice(chunk);
+        Ok(ChunkInputs {
+            commitment: commitment.commitment(),
+            byte_len: commitment.byte_len(),
+            index,
+            chunk: padded,
+            siblings: to_array(&proof.siblings)?,
+        })
+    }
+}
+
+/// Returns `index` as the `u32` index of a tree of depth `depth`.
+fn tree_index(index: usize, depth: usize) -> Result<u32, FlattenError> {
+    let error = FlattenError::IndexOutOfRange { index, depth };
+    let index = u32::try_from(index).map_err(|_| error.clone())?;
+    if depth < 32 && index >> depth != 0 {
+        return Err(error);
+    }
+    Ok(index)
+}
+
+/// Builder of a `Prover.toml`: field elements as `0x`-prefixed hex strings, integers and byte
+/// arrays as numbers, one input per line in the order added.
+#[derive(Clone, Debug, Default)]
+pub struct ProverInputs {
+    inputs: Vec<(String, String)>,
+}
+
+impl ProverInputs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the field element `name`.
+    pub fn field<F: PrimeField>(&mut self, name: &str, element: &F) -> &mut Self {
+        self.push(name, format!("\"{}\"", format_element(element)))
+    }
+
+    /// Adds the array of field elements `name`.
+    pub fn fields<F: PrimeField>(&mut self, name: &str, elements: &[F]) -> &mut Self {
+        let elements: Vec<String> = elements
+            .iter()
+            .map(|element| format!("\"{}\"", format_element(element)))
+            .collect();
+        self.push(name, format!("[{}]", elements.join(", ")))
+    }
+
+    /// Adds the integer `name`.
+    pub fn integer(&mut self, name: &str, value: u64) -> &mut Self {
+        self.push(name, value.to_string())
+    }
+
+    /// Adds the byte array `name`.
+    pub fn bytes(&mut self, name: &str, bytes: &[u8]) -> &mut Self {
+        let bytes: Vec<String> = bytes.iter().map(u8::to_string).collect();
+        self.push(name, format!("[{}]", bytes.join(", ")))
+    }
+
+    /// Adds `<prefix>_leaf`, `<prefix>_index` and `<prefix>_siblings`.
+    pub fn merkle_root<F: PrimeField, const D: usize>(
+        &mut self,
+        prefix: &str,
+        inputs: &MerkleRootInputs<F, D>,
+    ) -> &mut Self {
+        self.field(&format!("{}_leaf", prefix), &inputs.leaf)
+            .integer(&format!("{}_index", prefix), inputs.index.into())
+            .fields(&format!("{}_siblings", prefix), &inputs.siblings)
+    }
+
+    /// Adds `<prefix>_commitment`, `<prefix>_values` and `<prefix>_randomness`.
+    pub fn commitment<F: PrimeField, const N: usize>(
+        &mut self,
+        prefix: &str,
+        inputs: &CommitmentInputs<F, N>,
+    ) -> &mut Self {
+        self.field(&format!("{}_commitment", prefix), &inputs.commitment)
+            .fields(&format!("{}_values", prefix), &inputs.values)
+            .field(&format!("{}_randomness", prefix), &inputs.randomness)
+    }
+
+    /// Adds `<prefix>_commitment`, `<prefix>_byte_len`, `<prefix>_index`, `<prefix>_chunk`
+    /// and `<prefix>_siblings`.
+    pub fn chunk<F: PrimeField, const C: usize, const D: usize>(
+        &mut self,
+        prefix: &str,
+        inputs: &ChunkInputs<F, C, D>,
+    ) -> &mut Self {
+        self.field(&format!("{}_commitment", prefix), &inputs.commitment)
+            .integer(&format!("{}_byte_len", prefix), inputs.byte_len)
+            .integer(&format!("{}_index", prefix), inputs.index.into())
+            .bytes(&format!("{}_chunk", prefix), &inputs.chunk)
+            .fields(&format!("{}_siblings", prefix), &inputs.siblings)
+    }
+
+    /// Returns the `Prover.toml` source.
+    pub fn finish(&self) -> Result<String, FlattenError> {
+        for (index, (name, _)) in self.inputs.iter().enumerate() {
+            if !is_identifier(name) {
+                return Err(FlattenError::InvalidName(name.clone()));
+            }
+            if self.inputs[..index].iter().any(|(other, _)| other == name) {
+                return Err(FlattenError::DuplicateName(name.clone()));
+            }
+        }
+        Ok(self
+            .inputs
+            .iter()
+            .map(|(name, value)| format!("{} = {}\n", name, value))
+            .collect())
+    }
+
+    fn push(&mut self, name: &str, value: String) -> &mut Self {
+        self.inputs.push((name.to_string(), value));
+        self
+    }
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::commitment::{commit, open};
+    use crate::domain::padded_domain_separator;
+    use crate::merkle::MerkleTree;
+    use crate::poseidon2::Poseidon2;
+    use ark_bn254::Fr;
+
+    #[test]
+    fn test_merkle_root() {
+        let leaves = (1..=4u64).map(Fr::from).collect();
+        let tree = MerkleTree::<Poseidon2>::new(leaves, &padded_domain_separator(b"ABCD"));
+        let proof = tree.open(2);
+        let inputs = MerkleRootInputs::<Fr, 2>::new(tree.leaf(2), &proof).unwrap();
+        assert_eq!(inputs.index, 2);
+        assert_eq!(inputs.siblings.as_slice(), proof.siblings.as_slice());
+        assert_eq!(
+            MerkleRootInputs::<Fr, 3>::new(tree.leaf(2), &proof),
+            Err(FlattenError::LengthMismatch {
+                expected: 3,
+                actual: 2
+            })
+        );
+        let moved = MerkleProof { index: 4, ..proof };
+        assert_eq!(
+            MerkleRootInputs::<Fr, 2>::new(tree.leaf(2), &moved),
+            Err(FlattenError::IndexOutOfRange { index: 4, depth: 2 })
+        );
+    }
+
+    #[test]
+    fn test_chunk() {
+        let data = b"hello, chunked world";
+        let commitment = ChunkedCommitment::<Poseidon2>::commit(data, 8);
+        let proof = commitment.open(2);
+        let inputs = ChunkInputs::<Fr, 8, 2>::new(&commitment, &data[16..], &proof).unwrap();
+        assert_eq!(&inputs.chunk, b"orld\0\0\0\0");
+        assert_eq!((inputs.byte_len, inputs.index), (20, 2));
+        assert_eq!(
+            ChunkInputs::<Fr, 8, 2>::new(&commitment, &data[8..16], &proof),
+            Err(FlattenError::InvalidChunk(8))
+        );
+        assert_eq!(
+            ChunkInputs::<Fr, 16, 2>::new(&commitment, &data[16..], &proof),
+            Err(FlattenError::LengthMismatch {
+                expected: 16,
+                actual: 8
+            })
+        );
+    }
+
+    #[test]
+    fn test_prover_inputs() {
+        let values = [Fr::from(1u64), Fr::from(2u64)];
+        let commitment = commit::<Poseidon2>(&values, Fr::from(3u64), b"test");
+        let inputs =
+            CommitmentInputs::<Fr, 2>::new(&commitment, &open(&values, Fr::from(3u64))).unwrap();
+        let challenges: [Fr; 1] = to_array(&[Fr::from(5u64)]).unwrap();
+        let mut toml = ProverInputs::new();
+        toml.commitment("bid", &inputs)
+            .fields("challenges", &challenges)
+            .bytes("tag", &[1, 255]);
+        let one = format_element(&Fr::from(1u64));
+        assert_eq!(
+            toml.finish().unwrap(),
+            format!(
+                "bid_commitment = \"{}\"\n\
+                 bid_values = [\"{}\", \"{}\"]\n\
+                 bid_randomness = \"{}\"\n\
+                 challenges = [\"{}\"]\n\
+                 tag = [1, 255]\n",
+                format_element(&commitment.0),
+                one,
+                format_element(&Fr::from(2u64)),
+                format_element(&Fr::from(3u64)),
+                format_element(&Fr::from(5u64)),
+            )
+        );
+
+        toml.integer("tag", 1);
+        assert_eq!(
+            toml.finish(),
+            Err(FlattenError::DuplicateName("tag".to_string()))
+        );
+        assert_eq!(
+            ProverInputs::new().integer("Tag", 1).finish(),
+            Err(FlattenError::InvalidName("Tag".to_string()))
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Invariants of the tag computation, checked by the cargo-fuzz targets in `fuzz` on the
+//! [`Arbitrary`](arbitrary::Arbitrary) patterns and domain separators of the `arbitrary`
+//! feature:
+//!
+//! ```sh
+//! cargo +nightly fuzz run tag
+//! cargo +nightly fuzz run text_form
+//! ```
+//!
+//! Each check panics on a violation, which the fuzzer reports with the failing input.
+
+use crate::domain::DomainSeparator;
+use crate::error::SafeError;
+use crate::fixed::compute_tag_fixed;
+use crate::io_pattern::{IoPattern, LENGTH_MASK};
+use crate::tag::{compute_tag, Tag, TagLength};
+
+/// Operations of the patterns also run through [`compute_tag_fixed`].
+const FIXED_OPS: usize = 32;
+
+/// Checks that the tag of `pattern` never panics and agrees across its computations: from the
+/// pattern and from its words, heapless, and truncated from the 256-bit tag. Patterns with
+/// the lengths of the spec, none zero nor overflowing once aggregated, also have the tag of
+/// their aggregated pattern; the others do not in general, e.g. `A1 S1 A0 S1` aggregates to
+/// `A1 S1 S1` as in Noir, which aggregates further to `A1 S2`.
+pub fn check_tag(pattern: &IoPattern, domain_separator: &DomainSeparator) {
+    let tag = pattern.tag(domain_separator);
+    let words = pattern.encoded_words();
+    assert_eq!(compute_tag(&words, domain_separator), tag, "from the words");
+
+    let normalized = pattern.normalized();
+    let operations = normalized.operations();
+    assert!(
+        operations.iter().all(|operation| !operation.is_empty()),
+        "aggregated zero operation"
+    );
+    let spec_lengths = matches!(
+        pattern.validate(),
+        Ok(()) | Err(SafeError::EmptyPattern | SafeError::MissingFinalSqueeze(_))
+    );
+    if spec_lengths {
+        assert_eq!(normalized.tag(domain_separator), tag, "aggregated");
+        assert_eq!(
+            normalized.normalized(),
+            normalized,
+            "aggregation is idempotent"
+        );
+        assert!(
+            operations
+                .windows(2)
+                .all(|pair| pair[0].is_absorb() != pair[1].is_absorb()),
+            "aggregated operations of the same type"
+        );
+        assert!(
+            operations
+                .iter()
+                .all(|operation| operation.len() <= LENGTH_MASK),
+            "aggregate overflowing 31 bits"
+        );
+    }
+
+    match compute_tag_fixed::<FIXED_OPS>(&words, domain_separator) {
+        Ok(fixed) => assert_eq!(fixed, tag, "heapless"),
+        Err(_) => assert!(words.len() > FIXED_OPS, "heapless rejected a short pattern"),
+    }
+    let full = pattern.tag_256(domain_separator);
+    assert_eq!(
+        Tag::from_digest(&full, TagLength::Bits128),
+        tag,
+        "truncated 256-bit tag"
+    );
+}
+
+/// Checks that a non-empty `pattern`, with its labels, parses back from its text form.
+pub fn check_text_form(pattern: &IoPattern) {
+    if pattern.is_empty() {
+        return;
+    }
+    let text = pattern.to_string();
+    assert_eq!(text.parse::<IoPattern>().as_ref(), Ok(pattern), "{}", text);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arbitrary::{Arbitrary, Unstructured};
+    use sha2::{Digest, Sha256};
+
+    /// A short deterministic run of the fuzz targets, on pseudo-random inputs.
+    #[test]
+    fn test_invariants() {
+        for seed in 0u32..500 {
+            let input: Vec<u8> = (0u32..8)
+                .flat_map(|block| {
+                    Sha256::digest([seed.to_be_bytes(), block.to_be_bytes()].concat())
+                })
+                .collect();
+            let mut u = Unstructured::new(&input);
+            let pattern = IoPattern::arbitrary(&mut u).unwrap();
+            let domain_separator = DomainSeparator::arbitrary(&mut u).unwrap();
+            check_tag(&pattern, &domain_separator);
+            check_text_form(&pattern);
+        }
+    }
+
+    #[test]
+    fn test_overflowing_aggregate() {
+        // Three maximal ABSORBs overflow 32 bits once aggregated.
+        let pattern = IoPattern::new()
+            .absorb(LENGTH_MASK)
+            .absorb(LENGTH_MASK)
+            .absorb(LENGTH_MASK)
+            .squeeze(1);
+        check_tag(&pattern, &DomainSeparator::new([1; 64]));
+    }
+}
@@ -0,0 +1,426 @@
+//! Halo2 transcripts over the SAFE sponge.
+//!
+//! [`SafeWrite`] and [`SafeRead`] implement the `halo2_proofs` [`TranscriptWrite`] and
+//! [`TranscriptRead`], the counterparts of its `Blake2bWrite` and `Blake2bRead`, so a Halo2
+//! prover and verifier derive their challenges from a SAFE-tagged [`Sponge`] instead of
+//! BLAKE2b. Points and scalars of the curve enter the sponge as byte strings, with the
+//! injective encoding of [`Sponge::absorb_bytes`], whatever the field of the sponge:
+//!
+//! - a point, written or common, is one ABSORB of its compressed encoding (`to_bytes`, the
+//!   bytes written to the proof), declared with [`IoPattern::absorb_bytes`];
+//! - a scalar is one ABSORB of its canonical encoding (`to_repr`);
+//! - a challenge is one SQUEEZE of the 64 uniform bytes of [`Sponge::squeeze_bytes`], from
+//!   which [`Challenge255`] reduces the challenge as for BLAKE2b, declared with
+//!   [`IoPattern::squeeze_bytes`].
+//!
+//! The IO pattern of a proof depends on the circuit only, not on the witness, so it is
+//! recorded once by running the prover on a [`PatternRecorder`], e.g. when generating the
+//! keys, and then fixed for the prover and the verifier of the circuit:
+//!
+//! ```
+//! use halo2_proofs::pasta::group::prime::PrimeCurveAffine;
+//! use halo2_proofs::pasta::{EqAffine, Fp};
+//! use halo2_proofs::transcript::{Challenge255, Transcript, TranscriptRead, TranscriptWrite};
+//! use safe_api::domain::padded_domain_separator;
+//! use safe_api::halo2::{PatternRecorder, SafeRead, SafeWrite};
+//! use safe_api::poseidon2::Poseidon2;
+//! use safe_api::Sponge;
+//!
+//! // A prover sending a point and a scalar, then squeezing a challenge.
+//! fn prove<T: TranscriptWrite<EqAffine, Challenge255<EqAffine>>>(transcript: &mut T) -> Fp {
+//!     transcript.write_point(EqAffine::generator()).unwrap();
+//!     transcript.write_scalar(Fp::from(7)).unwrap();
+//!     *transcript.squeeze_challenge_scalar::<()>()
+//! }
+//!
+//! let mut recorder = PatternRecorder::<EqAffine, ark_bn254::Fr>::new();
+//! prove(&mut recorder);
+//! let pattern = recorder.into_io_pattern();
+//! let domain_separator = padded_domain_separator(b"my-app/halo2");
+//!
+//! let sponge = Sponge::<Poseidon2>::start(pattern.clone(), &domain_separator);
+//! let mut transcript = SafeWrite::<_, EqAffine, _>::init(Vec::new(), sponge);
+//! let challenge = prove(&mut transcript);
+//! let proof = transcript.finalize();
+//!
+//! let sponge = Sponge::<Poseidon2>::start(pattern, &domain_separator);
+//! let mut transcript = SafeRead::<_, EqAffine, _>::init(&proof[..], sponge);
+//! assert_eq!(transcript.read_point().unwrap(), EqAffine::generator());
+//! assert_eq!(transcript.read_scalar().unwrap(), Fp::from(7));
+//! assert_eq!(*transcript.squeeze_challenge_scalar::<()>(), challenge);
+//! transcript.finish();
+//! ```
+//!
+//! A call that is not the next operation of the IO pattern panics, like any misuse of
+//! [`Sponge`]. Unlike BLAKE2b, the point at infinity is absorbed like any other point, since
+//! its compressed encoding is well defined.
+
+use core::marker::PhantomData;
+use std::io::{self, Read, Write};
+
+use halo2_proofs::arithmetic::CurveAffine;
+use halo2_proofs::pasta::group::ff::{self, FromUniformBytes, PrimeField as _};
+use halo2_proofs::transcript::{
+    Challenge255, EncodedChallenge, Transcript, TranscriptRead, TranscriptWrite,
+};
+
+use crate::io_pattern::IoPattern;
+use crate::permutation::Permutation;
+use crate::sponge::Sponge;
+
+/// Number of uniform bytes squeezed for a [`Challenge255`].
+pub const CHALLENGE_BYTES: usize = 64;
+
+fn absorb_point<P: Permutation, C: CurveAffine>(sponge: &mut Sponge<P>, point: &C) {
+    sponge.absorb_bytes(point.to_bytes().as_ref());
+}
+
+fn absorb_scalar<P: Permutation, C: CurveAffine>(sponge: &mut Sponge<P>, scalar: &C::Scalar) {
+    sponge.absorb_bytes(scalar.to_repr().as_ref());
+}
+
+fn squeeze_challenge<P: Permutation, C: CurveAffine>(sponge: &mut Sponge<P>) -> Challenge255<C>
+where
+    C::Scalar: FromUniformBytes<64>,
+{
+    let bytes = sponge.squeeze_bytes(CHALLENGE_BYTES);
+    Challenge255::new(&bytes.try_into().expect("64 squeezed bytes"))
+}
+
+/// A transcript writing a proof to `W`, over a [`Sponge`], see the
+/// [module documentation](self).
+#[derive(Clone, Debug)]
+pub struct SafeWrite<W: Write, C: CurveAffine, P: Permutation> {
+    sponge: Sponge<P>,
+    writer: W,
+    _marker: PhantomData<C>,
+}
+
+impl<W: Write, C: CurveAffine, P: Permutation> SafeWrite<W, C, P> {
+    /// Starts a transcript writing to `writer` on a sponge started with the IO pattern of the
+    /// proof.
+    pub fn init(writer: W, sponge: Sponge<P>) -> Self {
+        SafeWrite {
+            sponge,
+            writer,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Checks that the whole IO pattern has been run, as [`Sponge::finish`], and returns the
+    /// writer.
+    pub fn finalize(mut self) -> W {
+        self.sponge.finish();
+        self.writer
+    }
+}
+
+impl<W: Write, C: CurveAffine, P: Permutation> Transcript<C, Challenge255<C>> for SafeWrite<W, C, P>
+where
+    C::Scalar: FromUniformBytes<64>,
+{
+    fn squeeze_challenge(&mut self) -> Challenge255<C> {
+        squeeze_challenge(&mut self.sponge)
+    }
+
+    fn common_point(&mut self, point: C) -> io::Result<()> {
+        absorb_point(&mut self.sponge, &point);
+        Ok(())
+    }
+
+    fn common_scalar(&mut self, scalar: C::Scalar) -> io::Result<()> {
+        absorb_scalar::<_, C>(&mut self.sponge, &scalar);
+        Ok(())
+    }
+}
+
+impl<W: Write, C: CurveAffine, P: Permutation> TranscriptWrite<C, Challenge255<C>>
+    for SafeWrite<W, C, P>
+where
+    C::Scalar: FromUniformBytes<64>,
+{
+    fn write_point(&mut self, point: C) -> io::Result<()> {
+        self.common_point(point)?;
+        self.writer.write_all(point.to_bytes().as_ref())
+    }
+
+    fn write_scalar(&mut self, scalar: C::Scalar) -> io::Result<()> {
+        self.common_scalar(scalar)?;
+        self.writer.write_all(scalar.to_repr().as_ref())
+    }
+}
+
+/// A transcript reading a proof from `R`, over a [`Sponge`], see the
+/// [module documentation](self).
+#[derive(Clone, Debug)]
+pub struct SafeRead<R: Read, C: CurveAffine, P: Permutation> {
+    sponge: Sponge<P>,
+    reader: R,
+    _marker: PhantomData<C>,
+}
+
+impl<R: Read, C: CurveAffine, P: Permutation> SafeRead<R, C, P> {
+    /// Starts a transcript reading from `reader` on a sponge started with the IO pattern of
+    /// the proof.
+    pub fn init(reader: R, sponge: Sponge<P>) -> Self {
+        SafeRead {
+            sponge,
+            reader,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Checks that the whole IO pattern has been run, as [`Sponge::finish`], e.g. after the
+    /// verifier accepted the proof.
+    pub fn finish(&mut self) {
+        self.sponge.finish();
+    }
+}
+
+impl<R: Read, C: CurveAffine, P: Permutation> Transcript<C, Challenge255<C>> for SafeRead<R, C, P>
+where
+    C::Scalar: FromUniformBytes<64>,
+{
+    fn squeeze_challenge(&mut self) -> Challenge255<C> {
+        squeeze_challenge(&mut self.sponge)
+    }
+
+    fn common_point(&mut self, point: C) -> io::Result<()> {
+        absorb_point(&mut self.sponge, &point);
+        Ok(())
+    }
+
+    fn common_scalar(&mut self, scalar: C::Scalar) -> io::Result<()> {
+        absorb_scalar::<_, C>(&mut self.sponge, &scalar);
+        Ok(())
+    }
+}
+
+impl<R: Read, C: CurveAffine, P: Permutation> TranscriptRead<C, Challenge255<C>>
+    for SafeRead<R, C, P>
+where
+    C::Scalar: FromUniformBytes<64>,
+{
+    fn read_point(&mut self) -> io::Result<C> {
+        let mut compressed = C::Repr::default();
+        self.reader.read_exact(compressed.as_mut())?;
+        let point: C = Option::from(C::from_bytes(&compressed)).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid point encoding in proof",
+            )
+        })?;
+        self.common_point(point)?;
+        Ok(point)
+    }
+
+    fn read_scalar(&mut self) -> io::Result<C::Scalar> {
+        let mut repr = <C::Scalar as ff::PrimeField>::Repr::default();
+        self.reader.read_exact(repr.as_mut())?;
+        let scalar: C::Scalar = Option::from(C::Scalar::from_repr(repr)).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid field element encoding in proof",
+            )
+        })?;
+        self.common_scalar(scalar)?;
+        Ok(scalar)
+    }
+}
+
+/// A transcript recording the IO pattern of the calls of a prover, over elements of `F`.
+///
+/// It writes nothing and its challenges are arbitrary (but never zero), so the proof of a
+/// recording run is meaningless; only the sequence of calls, which the circuit determines,
+/// matters.
+#[derive(Clone, Debug)]
+pub struct PatternRecorder<C: CurveAffine, F: ark_ff::PrimeField> {
+    io_pattern: IoPattern,
+    challenges: u8,
+    _marker: PhantomData<(C, F)>,
+}
+
+impl<C: CurveAffine, F: ark_ff::PrimeField> PatternRecorder<C, F> {
+    /// Starts recording an empty IO pattern.
+    pub fn new() -> Self {
+        PatternRecorder {
+            io_pattern: IoPattern::new(),
+            challenges: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the recorded IO pattern.
+    pub fn into_io_pattern(self) -> IoPattern {
+        self.io_pattern
+    }
+
+    fn push(&mut self, extend: impl FnOnce(IoPattern) -> IoPattern) {
+        self.io_pattern = extend(core::mem::take(&mut self.io_pattern));
+    }
+}
+
+impl<C: CurveAffine, F: ark_ff::PrimeField> Default for PatternRecorder<C, F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: CurveAffine, F: ark_ff::PrimeField> Transcript<C, Challenge255<C>> for PatternRecorder<C, F>
+where
+    C::Scalar: FromUniformBytes<64>,
+{
+    fn squeeze_challenge(&mut self) -> Challenge255<C> {
+        self.push(|io_pattern| io_pattern.squeeze_bytes::<F>(CHALLENGE_BYTES));
+        self.challenges = self.challenges.wrapping_add(1).max(1);
+        Challenge255::new(&[self.challenges; CHALLENGE_BYTES])
+    }
+
+    fn common_point(&mut self, _point: C) -> io::Result<()> {
+        let len = C::Repr::default().as_ref().len();
+        self.push(|io_pattern| io_pattern.absorb_bytes::<F>(len));
+        Ok(())
+    }
+
+    fn common_scalar(&mut self, _scalar: C::Scalar) -> io::Result<()> {
+        let len = <C::Scalar as ff::PrimeField>::Repr::default()
+            .as_ref()
+            .len();
+        self.push(|io_pattern| io_pattern.absorb_bytes::<F>(len));
+        Ok(())
+    }
+}
+
+impl<C: CurveAffine, F: ark_ff::PrimeField> TranscriptWrite<C, Challenge255<C>>
+    for PatternRecorder<C, F>
+where
+    C::Scalar: FromUniformBytes<64>,
+{
+    fn write_point(&mut self, point: C) -> io::Result<()> {
+        self.common_point(point)
+    }
+
+    fn write_scalar(&mut self, scalar: C::Scalar) -> io::Result<()> {
+        self.common_scalar(scalar)
+    }
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::domain::padded_domain_separator;
+    use crate::poseidon2::Poseidon2;
+    use crate::rng::SafeSeededRng;
+    use ark_std::rand::SeedableRng;
+    use halo2_proofs::circuit::{Layouter, SimpleFloorPlanner, Value};
+    use halo2_proofs::pasta::{EqAffine, Fp};
+    use halo2_proofs::plonk::{
+        create_proof, keygen_pk, keygen_vk, verify_proof, Advice, Circuit, Column,
+        ConstraintSystem, Error, ProvingKey, Selector, SingleVerifier,
+    };
+    use halo2_proofs::poly::commitment::Params;
+    use halo2_proofs::poly::Rotation;
+
+    /// Proves knowledge of a square root of the value on the second row.
+    #[derive(Clone, Default)]
+    struct Square {
+        root: Value<Fp>,
+    }
+
+    impl Circuit<Fp> for Square {
+        type Config = (Column<Advice>, Selector);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Square::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let advice = meta.advice_column();
+            let selector = meta.selector();
+            meta.create_gate("square", |meta| {
+                let selector = meta.query_selector(selector);
+                let root = meta.query_advice(advice, Rotation::cur());
+                let square = meta.query_advice(advice, Rotation::next());
+                vec![selector * (root.clone() * root - square)]
+            });
+            (advice, selector)
+        }
+
+        fn synthesize(
+            &self,
+            (advice, selector): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "square",
+                |mut region| {
+                    selector.enable(&mut region, 0)?;
+                    region.assign_advice(|| "root", advice, 0, || self.root)?;
+                    region.assign_advice(|| "square", advice, 1, || self.root * self.root)?;
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    fn prove<T: TranscriptWrite<EqAffine, Challenge255<EqAffine>>>(
+        params: &Params<EqAffine>,
+        pk: &ProvingKey<EqAffine>,
+        transcript: &mut T,
+    ) {
+        let circuit = Square {
+            root: Value::known(Fp::from(3)),
+        };
+        let rng = SafeSeededRng::<Poseidon2>::seed_from_u64(1);
+        create_proof(params, pk, &[circuit], &[&[]], rng, transcript).unwrap();
+    }
+
+    #[test]
+    fn test_proof() {
+        let params = Params::<EqAffine>::new(4);
+        let vk = keygen_vk(&params, &Square::default()).unwrap();
+        let pk = keygen_pk(&params, vk, &Square::default()).unwrap();
+
+        let mut recorder = PatternRecorder::<EqAffine, ark_bn254::Fr>::new();
+        prove(&params, &pk, &mut recorder);
+        let pattern = recorder.into_io_pattern();
+        let domain_separator = padded_domain_separator(b"safe-api/halo2-test");
+        let start = |domain_separator: &[u8; 64]| {
+            Sponge::<Poseidon2>::start(pattern.clone(), domain_separator)
+        };
+
+        let mut transcript = SafeWrite::init(Vec::new(), start(&domain_separator));
+        prove(&params, &pk, &mut transcript);
+        let proof = transcript.finalize();
+
+        let verify = |domain_separator: &[u8; 64]| {
+            let mut transcript = SafeRead::init(&proof[..], start(domain_separator));
+            let strategy = SingleVerifier::new(&params);
+            let accepted = verify_proof(&params, pk.get_vk(), strategy, &[&[]], &mut transcript);
+            if accepted.is_ok() {
+                transcript.finish();
+            }
+            accepted.is_ok()
+        };
+        assert!(verify(&domain_separator));
+        // The challenges depend on the tag.
+        assert!(!verify(&padded_domain_separator(b"safe-api/other")));
+    }
+
+    #[test]
+    fn test_recorded_pattern() {
+        let mut recorder = PatternRecorder::<EqAffine, ark_bn254::Fr>::new();
+        recorder.write_point(EqAffine::default()).unwrap();
+        recorder.write_scalar(Fp::from(1)).unwrap();
+        let challenge = recorder.squeeze_challenge();
+        assert_ne!(challenge.get_scalar(), Fp::from(0));
+        assert_eq!(
+            recorder.into_io_pattern(),
+            IoPattern::new()
+                .absorb_bytes::<ark_bn254::Fr>(32)
+                .absorb_bytes::<ark_bn254::Fr>(32)
+                .squeeze_bytes::<ark_bn254::Fr>(CHALLENGE_BYTES)
+        );
+    }
+}
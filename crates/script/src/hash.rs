@@ -0,0 +1,198 @@
+//! Order-independent hashing helpers with a canonical encoding shared by the Rust and Noir implementations.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::io_pattern::IoPattern;
+use crate::permutation::Permutation;
+use crate::sponge::Sponge;
+
+/// IO pattern of [`hash_map_like`] for `count` entries: the count, the sorted key/value pairs,
+/// then the digest.
+pub fn hash_map_like_pattern(count: usize) -> IoPattern {
+    IoPattern::new()
+        .absorb(1)
+        .absorb(2 * count as u32)
+        .squeeze(1)
+}
+
+/// Hashes key/value pairs independently of their order, e.g. the entries of a map.
+///
+/// The pairs are sorted by the canonical integer value of their keys, then the sponge absorbs
+/// the number of pairs followed by `key_0, value_0, key_1, value_1, ...`. The Noir
+/// `hash_map_like` takes the pairs already sorted and computes the same digest.
+///
+/// # Panics
+/// If two pairs share a key.
+pub fn hash_map_like<P: Permutation + Default>(
+    pairs: &[(P::Field, P::Field)],
+    domain_separator: &[u8; 64],
+) -> P::Field {
+    let mut pairs = pairs.to_vec();
+    pairs.sort_by_key(|&(key, _)| key);
+    assert!(
+        pairs.windows(2).all(|pair| pair[0].0 != pair[1].0),
+        "Duplicate key"
+    );
+
+    let entries: Vec<P::Field> = pairs
+        .iter()
+        .flat_map(|&(key, value)| [key, value])
+        .collect();
+    let mut sponge = Sponge::<P>::start(hash_map_like_pattern(pairs.len()), domain_separator);
+    sponge.absorb(&[P::Field::from(pairs.len() as u64)]);
+    sponge.absorb(&entries);
+    let digest = sponge.squeeze()[0];
+    sponge.finish();
+    digest
+}
+
+/// How [`commit_set`] treats an element that occurs more than once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Fail with [`DuplicateElement`]: the input was expected to be a set.
+    Reject,
+    /// Keep a single copy: the input is a multiset whose support is committed.
+    Deduplicate,
+}
+
+/// Error of [`commit_set`] under [`DuplicatePolicy::Reject`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DuplicateElement<F>(pub F);
+
+impl<F: fmt::Display> fmt::Display for DuplicateElement<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "element {} occurs more than once", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<F: fmt::Debug + fmt::Display> std::error::Error for DuplicateElement<F> {}
+
+/// A set commitment with the IO pattern it was computed under.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SetCommitment<F> {
+    /// The commitment.
+    pub commitment: F,
+    /// The IO pattern of the sponge, to be used by the circuit.
+    pub io_pattern: IoPattern,
+}
+
+/// IO pattern of [`commit_set`] for a set of `count` elements: the count, the sorted elements,
+/// then the commitment.
+pub fn commit_set_pattern(count: usize) -> IoPattern {
+    IoPattern::new().absorb(1).absorb(count as u32).squeeze(1)
+}
+
+/// Commits to a set of field elements independently of their order.
+///
+/// The elements are sorted by canonical integer value and duplicates are handled according to
+/// `policy`; the sponge then absorbs the number of distinct elements followed by the elements.
+/// The Noir `commit_set` takes the elements already sorted and computes the same commitment.
+pub fn commit_set<P: Permutation + Default>(
+    elements: &[P::Field],
+    policy: DuplicatePolicy,
+    domain_separator: &[u8; 64],
+) -> Result<SetCommitment<P::Field>, DuplicateElement<P::Field>> {
+    let mut elements = elements.to_vec();
+    elements.sort();
+    if let Some(pair) = elements.windows(2).find(|pair| pair[0] == pair[1]) {
+        if policy == DuplicatePolicy::Reject {
+            return Err(DuplicateElement(pair[0]));
+        }
+    }
+    elements.dedup();
+
+    let io_pattern = commit_set_pattern(elements.len());
+    let mut sponge = Sponge::<P>::start(io_pattern.clone(), domain_separator);
+    sponge.absorb(&[P::Field::from(elements.len() as u64)]);
+    sponge.absorb(&elements);
+    let commitment = sponge.squeeze()[0];
+    sponge.finish();
+
+    Ok(SetCommitment {
+        commitment,
+        io_pattern,
+    })
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::poseidon2::Poseidon2;
+    use ark_bn254::Fr;
+    use std::str::FromStr;
+
+    fn abcd_domain() -> [u8; 64] {
+        crate::domain::padded_domain_separator(b"ABCD")
+    }
+
+    fn pairs(entries: &[(u64, u64)]) -> Vec<(Fr, Fr)> {
+        entries
+            .iter()
+            .map(|&(key, value)| (Fr::from(key), Fr::from(value)))
+            .collect()
+    }
+
+    #[test]
+    fn test_order_independence() {
+        let digest =
+            hash_map_like::<Poseidon2>(&pairs(&[(1, 10), (2, 20), (3, 30)]), &abcd_domain());
+        assert_eq!(
+            digest,
+            hash_map_like::<Poseidon2>(&pairs(&[(3, 30), (1, 10), (2, 20)]), &abcd_domain())
+        );
+        // Swapping values between keys changes the digest.
+        assert_ne!(
+            digest,
+            hash_map_like::<Poseidon2>(&pairs(&[(1, 20), (2, 10), (3, 30)]), &abcd_domain())
+        );
+    }
+
+    #[test]
+    fn test_matches_noir() {
+        // Same inputs as `test_hash_map_like` of the Noir implementation.
+        let digest = hash_map_like::<Poseidon2>(&pairs(&[(2, 20), (1, 10)]), &abcd_domain());
+        let expected = Fr::from_str(
+            "20048032456115463183685417079535844260575988510660665266324690440820693034862",
+        )
+        .unwrap();
+        assert_eq!(digest, expected);
+    }
+
+    #[test]
+    fn test_commit_set() {
+        let elements = [3u64, 1, 2].map(Fr::from);
+        let commitment =
+            commit_set::<Poseidon2>(&elements, DuplicatePolicy::Reject, &abcd_domain()).unwrap();
+        // Same value as `test_commit_set` of the Noir implementation.
+        let expected = Fr::from_str(
+            "10476147603832945607951842284965524376777855277759439940392007453743444982729",
+        )
+        .unwrap();
+        assert_eq!(commitment.commitment, expected);
+        assert_eq!(commitment.io_pattern, commit_set_pattern(3));
+
+        let sorted = [1u64, 2, 3].map(Fr::from);
+        assert_eq!(
+            commit_set::<Poseidon2>(&sorted, DuplicatePolicy::Reject, &abcd_domain()),
+            Ok(commitment.clone())
+        );
+
+        let repeated = [1u64, 3, 2, 3].map(Fr::from);
+        assert_eq!(
+            commit_set::<Poseidon2>(&repeated, DuplicatePolicy::Reject, &abcd_domain()),
+            Err(DuplicateElement(Fr::from(3u64)))
+        );
+        assert_eq!(
+            commit_set::<Poseidon2>(&repeated, DuplicatePolicy::Deduplicate, &abcd_domain()),
+            Ok(commitment)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Duplicate key")]
+    fn test_duplicate_keys_panic() {
+        hash_map_like::<Poseidon2>(&pairs(&[(1, 10), (1, 20)]), &abcd_domain());
+    }
+}
@@ -0,0 +1,282 @@
+//! Hash-to-field and hash-to-curve squeezed from the sponge.
+//!
+//! Generators and other points with no known discrete logarithm relation are derived from the
+//! transcript of the protocol, so that their domain separation is the tag of its IO pattern
+//! like for any other squeezed value:
+//!
+//! - [`hash_to_field`] squeezes uniformly distributed elements of any prime field `T`. Each
+//!   takes [`field_bytes`]`::<T>()` uniform bytes, i.e. the bit size of `T` plus 128 bits,
+//!   reduced modulo its order (the wide reduction of RFC 9380, section 5), so that it is within
+//!   `2^-128` of uniform whether `T` is smaller or larger than the field of the sponge.
+//! - [`hash_to_curve`] maps pairs of such elements of the base field to points of a short
+//!   Weierstrass curve, e.g. BN254 G1: each point is `map(u0) + map(u1)`, cofactor cleared,
+//!   where `map` is the Shallue–van de Woestijne map of RFC 9380, section 6.6.1. It applies to
+//!   every curve, `a = 0` ones like BN254 included, which the simplified SWU map excludes.
+//!
+//! Either is one SQUEEZE of the IO pattern, of [`hash_to_field_len`] or [`hash_to_curve_len`]
+//! elements, and the bytes of the squeezed elements are drawn as in
+//! [`SpongeRng`](crate::rng::SpongeRng):
+//!
+//! ```
+//! use ark_bn254::{Fr, G1Affine};
+//! use safe_api::domain::padded_domain_separator;
+//! use safe_api::hash_to_curve::{hash_to_curve, hash_to_curve_len};
+//! use safe_api::poseidon2::Poseidon2;
+//! use safe_api::{IoPattern, Sponge};
+//!
+//! // Two Pedersen generators, bound to the statement.
+//! let length = hash_to_curve_len::<Fr, ark_bn254::g1::Config>(2) as u32;
+//! let pattern = IoPattern::new().absorb(1).squeeze_labeled("generators", length);
+//! let domain_separator = padded_domain_separator(b"my-app/pedersen");
+//! let mut sponge = Sponge::<Poseidon2>::start(pattern, &domain_separator);
+//! sponge.absorb(&[Fr::from(42u64)]);
+//! let generators: Vec<G1Affine> = hash_to_curve(&mut sponge, 2);
+//! sponge.finish();
+//! assert!(generators.iter().all(|point| point.is_on_curve()));
+//! ```
+
+use alloc::vec::Vec;
+
+use ark_ec::short_weierstrass::{Affine, SWCurveConfig};
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_ff::{BigInteger, Field, One, PrimeField, Zero};
+
+use crate::permutation::Permutation;
+use crate::rng::bytes_per_squeezed_element;
+use crate::sponge::Sponge;
+
+/// Statistical distance to uniform, in bits, of the wide reduction.
+const SECURITY_BITS: usize = 128;
+
+/// Returns the number of uniform bytes reduced into each element of `T` (48 for the BN254 base
+/// field).
+pub fn field_bytes<T: PrimeField>() -> usize {
+    (T::MODULUS_BIT_SIZE as usize + SECURITY_BITS).div_ceil(8)
+}
+
+/// Returns the number of elements of `F` of the SQUEEZE behind `count` elements of `T`.
+pub fn hash_to_field_len<F: PrimeField, T: PrimeField>(count: usize) -> usize {
+    (count * field_bytes::<T>()).div_ceil(bytes_per_squeezed_element::<F>())
+}
+
+/// Returns the number of elements of `F` of the SQUEEZE behind `count` points of the curve `C`.
+pub fn hash_to_curve_len<F: PrimeField, C: SWCurveConfig>(count: usize) -> usize
+where
+    C::BaseField: PrimeField,
+{
+    hash_to_field_len::<F, C::BaseField>(2 * count)
+}
+
+/// Squeezes `count` uniformly distributed elements of `T`, see the
+/// [module documentation](self).
+///
+/// # Panics
+/// If the next operation of the IO pattern is not a SQUEEZE of
+/// [`hash_to_field_len`]`(count)` elements.
+pub fn hash_to_field<P: Permutation, T: PrimeField>(
+    sponge: &mut Sponge<P>,
+    count: usize,
+) -> Vec<T> {
+    let length = bytes_per_squeezed_element::<P::Field>();
+    let elements = sponge.squeeze();
+    assert!(
+        elements.len() == hash_to_field_len::<P::Field, T>(count),
+        "Length mismatch"
+    );
+    let bytes: Vec<u8> = elements
+        .iter()
+        .flat_map(|element| {
+            let element = element.into_bigint().to_bytes_be();
+            element[element.len() - length..].to_vec()
+        })
+        .collect();
+    bytes
+        .chunks_exact(field_bytes::<T>())
+        .take(count)
+        .map(T::from_be_bytes_mod_order)
+        .collect()
+}
+
+/// Squeezes `count` points of the prime-order subgroup of the curve `C`, see the
+/// [module documentation](self).
+///
+/// # Panics
+/// If the next operation of the IO pattern is not a SQUEEZE of
+/// [`hash_to_curve_len`]`(count)` elements.
+pub fn hash_to_curve<P: Permutation, C: SWCurveConfig>(
+    sponge: &mut Sponge<P>,
+    count: usize,
+) -> Vec<Affine<C>>
+where
+    C::BaseField: PrimeField,
+{
+    let map = SvdwMap::<C>::new();
+    hash_to_field::<P, C::BaseField>(sponge, 2 * count)
+        .chunks_exact(2)
+        .map(|u| {
+            (map.map(u[0]) + map.map(u[1]))
+                .into_affine()
+                .clear_cofactor()
+        })
+        .collect()
+}
+
+/// Maps an element of the base field to a point of the curve `C` with the Shallue–van de
+/// Woestijne map (RFC 9380, section 6.6.1). The point is not in the prime-order subgroup of
+/// curves with a cofactor.
+pub fn map_to_curve<C: SWCurveConfig>(u: C::BaseField) -> Affine<C>
+where
+    C::BaseField: PrimeField,
+{
+    SvdwMap::<C>::new().map(u)
+}
+
+/// The constants of the Shallue–van de Woestijne map of a curve.
+struct SvdwMap<C: SWCurveConfig> {
+    z: C::BaseField,
+    c1: C::BaseField,
+    c2: C::BaseField,
+    c3: C::BaseField,
+    c4: C::BaseField,
+}
+
+impl<C: SWCurveConfig> SvdwMap<C>
+where
+    C::BaseField: PrimeField,
+{
+    fn new() -> Self {
+        let z = find_z::<C>();
+        let three = C::BaseField::from(3u64);
+        let four = C::BaseField::from(4u64);
+        let h = three * z.square() + four * C::COEFF_A;
+        let mut c3 = (-g::<C>(z) * h)
+            .sqrt()
+            .expect("Z makes -g(Z) * (3 Z^2 + 4 A) a square");
+        if sgn0(c3) {
+            c3 = -c3;
+        }
+        SvdwMap {
+            z,
+            c1: g::<C>(z),
+            c2: -z / C::BaseField::from(2u64),
+            c3,
+            c4: -four * g::<C>(z) / h,
+        }
+    }
+
+    /// The straight-line map of RFC 9380, appendix F.1.
+    fn map(&self, u: C::BaseField) -> Affine<C> {
+        let tv1 = u.square() * self.c1;
+        let tv2 = C::BaseField::one() + tv1;
+        let tv1 = C::BaseField::one() - tv1;
+        let tv3 = (tv1 * tv2).inverse().unwrap_or_else(C::BaseField::zero);
+        let tv4 = u * tv1 * tv3 * self.c3;
+        let x1 = self.c2 - tv4;
+        let x2 = self.c2 + tv4;
+        let x3 = (tv2.square() * tv3).square() * self.c4 + self.z;
+        let x = if is_square(g::<C>(x1)) {
+            x1
+        } else if is_square(g::<C>(x2)) {
+            x2
+        } else {
+            x3
+        };
+        let mut y = g::<C>(x)
+            .sqrt()
+            .expect("one of g(x1), g(x2), g(x3) is a square");
+        if sgn0(u) != sgn0(y) {
+            y = -y;
+        }
+        Affine::new_unchecked(x, y)
+    }
+}
+
+/// Returns the first Z of the search of RFC 9380, appendix H.1.
+fn find_z<C: SWCurveConfig>() -> C::BaseField
+where
+    C::BaseField: PrimeField,
+{
+    let h = |z: C::BaseField| {
+        -(C::BaseField::from(3u64) * z.square() + C::BaseField::from(4u64) * C::COEFF_A)
+            / (C::BaseField::from(4u64) * g::<C>(z))
+    };
+    (1u64..)
+        .flat_map(|counter| {
+            let z = C::BaseField::from(counter);
+            [z, -z]
+        })
+        .find(|&z| {
+            !g::<C>(z).is_zero()
+                && !h(z).is_zero()
+                && is_square(h(z))
+                && (is_square(g::<C>(z)) || is_square(g::<C>(-z / C::BaseField::from(2u64))))
+        })
+        .expect("the search is unbounded")
+}
+
+/// The curve equation `x^3 + A x + B`.
+fn g<C: SWCurveConfig>(x: C::BaseField) -> C::BaseField {
+    (x.square() + C::COEFF_A) * x + C::COEFF_B
+}
+
+fn is_square<F: Field>(x: F) -> bool {
+    !x.legendre().is_qnr()
+}
+
+/// The sign of an element of a prime field, i.e. the parity of its canonical integer.
+fn sgn0<F: PrimeField>(x: F) -> bool {
+    x.into_bigint().is_odd()
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::domain::padded_domain_separator;
+    use crate::io_pattern::IoPattern;
+    use crate::poseidon2::Poseidon2;
+    use ark_bn254::{g1, Fq, Fr, G1Affine};
+
+    #[test]
+    fn test_map_to_curve() {
+        // Z = 1 over BN254 G1, as in other implementations of the map for this curve.
+        assert_eq!(find_z::<g1::Config>(), Fq::one());
+        for u in [0u64, 1, 2, 3, 1 << 40] {
+            for u in [Fq::from(u), -Fq::from(u)] {
+                let point: G1Affine = map_to_curve(u);
+                assert!(point.is_on_curve());
+                assert_eq!(sgn0(point.y), sgn0(u));
+            }
+        }
+    }
+
+    #[test]
+    fn test_hash_to_curve() {
+        assert_eq!(field_bytes::<Fq>(), 48);
+        assert_eq!(hash_to_curve_len::<Fr, g1::Config>(2), 13);
+
+        let points = |statement: u64| {
+            let length = hash_to_curve_len::<Fr, g1::Config>(2) as u32;
+            let pattern = IoPattern::new().absorb(1).squeeze(length);
+            let mut sponge = Sponge::<Poseidon2>::start(pattern, padded_domain_separator(b"test"));
+            sponge.absorb(&[Fr::from(statement)]);
+            let points: Vec<G1Affine> = hash_to_curve(&mut sponge, 2);
+            sponge.finish();
+            points
+        };
+        let first = points(1);
+        assert!(first
+            .iter()
+            .all(|point| point.is_on_curve() && point.is_in_correct_subgroup_assuming_on_curve()));
+        assert_ne!(first[0], first[1]);
+        assert_eq!(points(1), first);
+        assert_ne!(points(2), first);
+    }
+
+    #[test]
+    #[should_panic(expected = "Length mismatch")]
+    fn test_rejects_other_length() {
+        let pattern = IoPattern::new().squeeze(3);
+        let mut sponge = Sponge::<Poseidon2>::start(pattern, padded_domain_separator(b"test"));
+        let _: Vec<Fq> = hash_to_field(&mut sponge, 1);
+    }
+}
@@ -0,0 +1,208 @@
+//! Hex encoding and decoding of byte strings, shared by the parsers of the crate, the command
+//! line tool and the service.
+//!
+//! [`encode`] writes `0x` and two lowercase digits per byte. [`decode`] reads in one of two
+//! [`Mode`]s:
+//!
+//! - [`Mode::Strict`], for machine-written values such as test vectors and snapshots: the
+//!   `0x` prefix, then an even number of digits and nothing else.
+//! - [`Mode::Lenient`], for values typed or pasted by a user: the prefix is optional (`0x` or
+//!   `0X`) and ASCII whitespace anywhere is ignored, e.g. `de ad be ef` or a hex dump wrapped
+//!   over several lines.
+//!
+//! Digits are upper or lower case in both modes. [`decode_array`] also requires an exact
+//! number of bytes.
+//!
+//! ```
+//! use safe_api::hex::{self, HexError, Mode};
+//!
+//! assert_eq!(hex::decode("0xdeadBEEF", Mode::Strict), Ok(vec![0xde, 0xad, 0xbe, 0xef]));
+//! assert_eq!(hex::decode("de ad\nbe ef", Mode::Lenient), Ok(vec![0xde, 0xad, 0xbe, 0xef]));
+//! assert_eq!(hex::decode("deadbeef", Mode::Strict), Err(HexError::MissingPrefix));
+//! assert_eq!(hex::decode_array::<4>("0xdead", Mode::Strict),
+//!     Err(HexError::Length { expected: 4, found: 2 }));
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::fmt::Write;
+
+/// How strictly [`decode`] reads its input, see the [module documentation](self).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// The `0x` prefix and the digits, nothing else.
+    #[default]
+    Strict,
+    /// An optional `0x` or `0X` prefix, and ASCII whitespace anywhere.
+    Lenient,
+}
+
+/// Errors of the decoding functions of this module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HexError {
+    /// No `0x` prefix, in [`Mode::Strict`].
+    MissingPrefix,
+    /// A character that is not a hex digit, at that byte offset of the input.
+    InvalidDigit(usize),
+    /// An odd number of digits.
+    OddLength,
+    /// Not the number of bytes required by [`decode_array`].
+    Length { expected: usize, found: usize },
+}
+
+impl fmt::Display for HexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HexError::MissingPrefix => f.write_str("missing 0x prefix"),
+            HexError::InvalidDigit(offset) => write!(f, "invalid hex digit at offset {}", offset),
+            HexError::OddLength => f.write_str("odd number of hex digits"),
+            HexError::Length { expected, found } => {
+                write!(f, "expected {} bytes of hex, found {}", expected, found)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for HexError {}
+
+/// Encodes bytes as `0x` and two lowercase hex digits per byte.
+pub fn encode(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(2 + 2 * bytes.len());
+    output.push_str("0x");
+    for byte in bytes {
+        write!(output, "{:02x}", byte).expect("writing to a string");
+    }
+    output
+}
+
+/// Decodes hex read in `mode`. `0x` alone, or in [`Mode::Lenient`] the empty string, is the
+/// empty byte string.
+pub fn decode(input: &str, mode: Mode) -> Result<Vec<u8>, HexError> {
+    let (offset, digits) = match mode {
+        Mode::Strict => (2, input.strip_prefix("0x").ok_or(HexError::MissingPrefix)?),
+        Mode::Lenient => {
+            let trimmed = input.trim_start();
+            let start = input.len() - trimmed.len();
+            match trimmed
+                .strip_prefix("0x")
+                .or_else(|| trimmed.strip_prefix("0X"))
+            {
+                Some(digits) => (start + 2, digits),
+                None => (start, trimmed),
+            }
+        }
+    };
+    let mut nibbles = Vec::with_capacity(digits.len());
+    for (index, c) in digits.char_indices() {
+        match c.to_digit(16) {
+            Some(nibble) => nibbles.push(nibble as u8),
+            None if mode == Mode::Lenient && c.is_ascii_whitespace() => {}
+            None => return Err(HexError::InvalidDigit(offset + index)),
+        }
+    }
+    if nibbles.len() % 2 != 0 {
+        return Err(HexError::OddLength);
+    }
+    Ok(nibbles
+        .chunks(2)
+        .map(|pair| (pair[0] << 4) | pair[1])
+        .collect())
+}
+
+/// Decodes exactly `N` bytes of hex read in `mode`.
+pub fn decode_array<const N: usize>(input: &str, mode: Mode) -> Result<[u8; N], HexError> {
+    let bytes = decode(input, mode)?;
+    bytes.try_into().map_err(|bytes: Vec<u8>| HexError::Length {
+        expected: N,
+        found: bytes.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let encoded = encode(&bytes);
+        assert_eq!(encoded.len(), 2 + 512);
+        assert_eq!(decode(&encoded, Mode::Strict), Ok(bytes.clone()));
+        assert_eq!(
+            decode(&encoded.to_uppercase()[2..], Mode::Lenient),
+            Ok(bytes)
+        );
+        assert_eq!(encode(&[]), "0x");
+        assert_eq!(decode("0x", Mode::Strict), Ok(vec![]));
+        assert_eq!(decode("", Mode::Lenient), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_strict() {
+        assert_eq!(decode("abcd", Mode::Strict), Err(HexError::MissingPrefix));
+        assert_eq!(decode("0Xabcd", Mode::Strict), Err(HexError::MissingPrefix));
+        assert_eq!(
+            decode(" 0xabcd", Mode::Strict),
+            Err(HexError::MissingPrefix)
+        );
+        assert_eq!(
+            decode("0xab cd", Mode::Strict),
+            Err(HexError::InvalidDigit(4))
+        );
+        assert_eq!(
+            decode("0xabcd\n", Mode::Strict),
+            Err(HexError::InvalidDigit(6))
+        );
+        assert_eq!(decode("0xabc", Mode::Strict), Err(HexError::OddLength));
+        assert_eq!(
+            decode("0xabzz", Mode::Strict),
+            Err(HexError::InvalidDigit(4))
+        );
+        assert_eq!(decode("0x+1", Mode::Strict), Err(HexError::InvalidDigit(2)));
+    }
+
+    #[test]
+    fn test_lenient() {
+        let expected = Ok(vec![0xab, 0xcd, 0xef]);
+        for input in [
+            "abcdef",
+            "0xabcdef",
+            "0XABCDEF",
+            "  0xab cd\n\tef\n",
+            "a b c d e f",
+        ] {
+            assert_eq!(decode(input, Mode::Lenient), expected, "{:?}", input);
+        }
+        assert_eq!(decode("abc", Mode::Lenient), Err(HexError::OddLength));
+        assert_eq!(
+            decode(" 0x ab-cd", Mode::Lenient),
+            Err(HexError::InvalidDigit(6))
+        );
+        assert_eq!(
+            decode("0x0xab", Mode::Lenient),
+            Err(HexError::InvalidDigit(3))
+        );
+    }
+
+    #[test]
+    fn test_decode_array() {
+        assert_eq!(decode_array::<2>("0xabcd", Mode::Strict), Ok([0xab, 0xcd]));
+        assert_eq!(decode_array::<2>("ab cd", Mode::Lenient), Ok([0xab, 0xcd]));
+        assert_eq!(
+            decode_array::<2>("0xabcdef", Mode::Strict),
+            Err(HexError::Length {
+                expected: 2,
+                found: 3
+            })
+        );
+        assert_eq!(
+            decode_array::<32>("", Mode::Lenient),
+            Err(HexError::Length {
+                expected: 32,
+                found: 0
+            })
+        );
+    }
+}
@@ -0,0 +1,70 @@
+//! `tracing` instrumentation of the sponge, enabled by the `tracing` feature.
+//!
+//! Services that aggregate traces see, per request, where the time of a sponge goes:
+//!
+//! - a `safe.tag` span (level `DEBUG`) around each tag derivation of a sponge or of
+//!   [`IoPattern::tag`](crate::IoPattern::tag), with the number of operations of the pattern;
+//! - a `safe.sponge.start` span (`DEBUG`) around the construction of a sponge, with the
+//!   [identifier](crate::Permutation::ID) of the permutation and the IO pattern;
+//! - a `safe.permutation` span (`TRACE`) around every permutation call;
+//! - `safe.absorb` and `safe.squeeze` events (`TRACE`) for the operations, with their index,
+//!   label and length, and a `safe.sponge.finish` event (`DEBUG`).
+//!
+//! Absorbed and squeezed elements may be secrets, so the events carry them as `redacted`
+//! unless [`set_reveal_values`] turned on the debug output of the values, e.g. while
+//! comparing a transcript with another implementation. Nothing is recorded without a
+//! subscriber.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use ark_ff::PrimeField;
+
+use crate::display::format_element;
+
+static REVEAL_VALUES: AtomicBool = AtomicBool::new(false);
+
+/// Makes the events carry the absorbed and squeezed elements instead of `redacted`, or stops
+/// them from doing so. For debugging only: the values of secret inputs end up in the traces.
+pub fn set_reveal_values(reveal: bool) {
+    REVEAL_VALUES.store(reveal, Ordering::Relaxed);
+}
+
+/// Returns whether the events carry the absorbed and squeezed elements.
+pub fn reveals_values() -> bool {
+    REVEAL_VALUES.load(Ordering::Relaxed)
+}
+
+/// Returns the `values` field of an event over `elements`.
+pub(crate) fn values<F: PrimeField>(elements: &[F]) -> String {
+    if !reveals_values() {
+        return String::from("redacted");
+    }
+    let elements: Vec<String> = elements.iter().map(format_element).collect();
+    alloc::format!("[{}]", elements.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bn254::Fr;
+
+    #[test]
+    fn test_values_redacted() {
+        let elements = [Fr::from(1u64), Fr::from(2u64)];
+        assert_eq!(values(&elements), "redacted");
+        set_reveal_values(true);
+        let revealed = values(&elements);
+        set_reveal_values(false);
+        assert_eq!(
+            revealed,
+            alloc::format!(
+                "[{}, {}]",
+                format_element(&elements[0]),
+                format_element(&elements[1])
+            )
+        );
+        assert!(!reveals_values());
+    }
+}
@@ -0,0 +1,371 @@
+//! A driver for multi-round public-coin protocols, interactive or made non-interactive with
+//! Fiat-Shamir.
+//!
+//! An [`InteractiveProtocol`] is a labeled IO pattern, in which each ABSORB is a prover message
+//! and each SQUEEZE a verifier challenge, followed by a final prover response that no challenge
+//! depends on. The parties are callbacks, a [`Prover`] and a [`Verifier`], called in the order of
+//! the pattern:
+//!
+//! - [`InteractiveProtocol::run`] plays the interaction: every message goes from the prover to
+//!   the verifier, and each party derives the challenges from its own [`Transcript`], so the two
+//!   sponges stay synchronized operation by operation.
+//! - [`InteractiveProtocol::prove`] runs the prover alone and compiles the interaction into an
+//!   [`InteractiveProof`], its messages and response, which
+//!   [`InteractiveProtocol::verify`] replays to the verifier alone.
+//!
+//! Both derive the same challenges, so a protocol can be tested interactively before it gets a
+//! circuit, then proved non-interactively with the same callbacks:
+//!
+//! ```
+//! use ark_bn254::Fr;
+//! use safe_api::domain::padded_domain_separator;
+//! use safe_api::interactive::{InteractiveProtocol, Prover, Verifier};
+//! use safe_api::poseidon2::Poseidon2;
+//! use safe_api::IoPattern;
+//!
+//! /// Evaluates the polynomial `a + b x` it sent at the challenge `x`.
+//! struct Evaluator { coefficients: [Fr; 2], x: Fr }
+//!
+//! impl Prover<Fr> for Evaluator {
+//!     fn message(&mut self, _label: &str, _length: usize) -> Vec<Fr> {
+//!         self.coefficients.to_vec()
+//!     }
+//!     fn challenge(&mut self, _label: &str, challenge: &[Fr]) {
+//!         self.x = challenge[0];
+//!     }
+//!     fn respond(&mut self) -> Vec<Fr> {
+//!         vec![self.coefficients[0] + self.coefficients[1] * self.x]
+//!     }
+//! }
+//!
+//! /// Checks the evaluation against the coefficients it received.
+//! #[derive(Default)]
+//! struct Checker { coefficients: Vec<Fr>, x: Fr }
+//!
+//! impl Verifier<Fr> for Checker {
+//!     fn message(&mut self, _label: &str, message: &[Fr]) {
+//!         self.coefficients = message.to_vec();
+//!     }
+//!     fn challenge(&mut self, _label: &str, challenge: &[Fr]) {
+//!         self.x = challenge[0];
+//!     }
+//!     fn decide(&mut self, response: &[Fr]) -> bool {
+//!         response[0] == self.coefficients[0] + self.coefficients[1] * self.x
+//!     }
+//! }
+//!
+//! let pattern = IoPattern::new()
+//!     .absorb_labeled("coefficients", 2)
+//!     .squeeze_labeled("x", 1);
+//! let protocol =
+//!     InteractiveProtocol::new(pattern, &padded_domain_separator(b"my-app/evaluation"), 1)?;
+//! let prover = || Evaluator { coefficients: [Fr::from(3u64), Fr::from(5u64)], x: Fr::from(0u64) };
+//!
+//! assert!(protocol.run::<Poseidon2, _, _>(&mut prover(), &mut Checker::default()));
+//! let proof = protocol.prove::<Poseidon2, _>(&mut prover());
+//! assert!(protocol.verify::<Poseidon2, _>(&mut Checker::default(), &proof));
+//! # Ok::<(), safe_api::SafeError>(())
+//! ```
+//!
+//! The transcripts are [`Transcript`]s, so the labels of the pattern are bound into the
+//! domain separator and a circuit rederives the challenges like for any other transcript.
+
+use alloc::vec::Vec;
+
+use crate::error::SafeError;
+use crate::io_pattern::{IoPattern, Operation};
+use crate::permutation::Permutation;
+use crate::transcript::Transcript;
+
+/// The prover of an [`InteractiveProtocol`].
+pub trait Prover<F> {
+    /// Returns the message `label` of `length` elements.
+    fn message(&mut self, label: &str, length: usize) -> Vec<F>;
+    /// Receives the challenge `label`.
+    fn challenge(&mut self, label: &str, challenge: &[F]);
+    /// Returns the final response, once every challenge is known.
+    fn respond(&mut self) -> Vec<F>;
+}
+
+/// The verifier of an [`InteractiveProtocol`].
+pub trait Verifier<F> {
+    /// Receives the prover message `label`.
+    fn message(&mut self, label: &str, message: &[F]);
+    /// Receives the challenge `label`.
+    fn challenge(&mut self, label: &str, challenge: &[F]);
+    /// Accepts or rejects the final response.
+    fn decide(&mut self, response: &[F]) -> bool;
+}
+
+/// A non-interactive proof: the prover messages, in pattern order, and the response.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InteractiveProof<F> {
+    pub messages: Vec<Vec<F>>,
+    pub response: Vec<F>,
+}
+
+/// A multi-round public-coin protocol, see the [module documentation](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InteractiveProtocol {
+    pattern: IoPattern,
+    domain_separator: [u8; 64],
+    response_len: usize,
+}
+
+impl InteractiveProtocol {
+    /// Declares a protocol running `pattern` under `domain_separator`, with a final response of
+    /// `response_len` elements.
+    ///
+    /// # Errors
+    /// - Any error of [`IoPattern::validate`]: in particular the pattern must end with a
+    ///   challenge, messages after it belonging in the response.
+    /// - [`SafeError::UnlabeledOperation`] with the index of an operation without a label.
+    pub fn new(
+        pattern: IoPattern,
+        domain_separator: &[u8; 64],
+        response_len: usize,
+    ) -> Result<Self, SafeError> {
+        pattern.validate()?;
+        if let Some(index) = (0..pattern.len()).find(|&index| pattern.label(index).is_none()) {
+            return Err(SafeError::UnlabeledOperation(index));
+        }
+        Ok(InteractiveProtocol {
+            pattern,
+            domain_separator: *domain_separator,
+            response_len,
+        })
+    }
+
+    /// Returns the IO pattern of the protocol.
+    pub fn io_pattern(&self) -> &IoPattern {
+        &self.pattern
+    }
+
+    /// Plays the interaction between `prover` and `verifier`, each with its own transcript
+    /// running on `P`, and returns the decision of the verifier.
+    ///
+    /// # Panics
+    /// If the prover returns a message or response of the wrong length.
+    pub fn run<P, A, B>(&self, prover: &mut A, verifier: &mut B) -> bool
+    where
+        P: Permutation + Default,
+        A: Prover<P::Field>,
+        B: Verifier<P::Field>,
+    {
+        let mut prover_transcript = self.transcript::<P>();
+        let mut verifier_transcript = self.transcript::<P>();
+        for (index, &operation) in self.pattern.operations().iter().enumerate() {
+            let label = self.label(index);
+            match operation {
+                Operation::Absorb(length) => {
+                    let message = prover.message(label, length as usize);
+                    prover_transcript.append(label, &message);
+                    verifier_transcript.append(label, &message);
+                    verifier.message(label, &message);
+                }
+                Operation::Squeeze(length) => {
+                    let challenge = prover_transcript.challenge(label, length as usize);
+                    prover.challenge(label, &challenge);
+                    let challenge = verifier_transcript.challenge(label, length as usize);
+                    verifier.challenge(label, &challenge);
+                }
+            }
+        }
+        prover_transcript.finish();
+        verifier_transcript.finish();
+        let response = prover.respond();
+        assert!(response.len() == self.response_len, "Length mismatch");
+        verifier.decide(&response)
+    }
+
+    /// Runs `prover` against the Fiat-Shamir challenges of a transcript running on `P`, and
+    /// returns its messages and response as a proof.
+    ///
+    /// # Panics
+    /// If the prover returns a message or response of the wrong length.
+    pub fn prove<P, A>(&self, prover: &mut A) -> InteractiveProof<P::Field>
+    where
+        P: Permutation + Default,
+        A: Prover<P::Field>,
+    {
+        let mut transcript = self.transcript::<P>();
+        let mut messages = Vec::new();
+        for (index, &operation) in self.pattern.operations().iter().enumerate() {
+            let label = self.label(index);
+            match operation {
+                Operation::Absorb(length) => {
+                    let message = prover.message(label, length as usize);
+                    transcript.append(label, &message);
+                    messages.push(message);
+                }
+                Operation::Squeeze(length) => {
+                    prover.challenge(label, &transcript.challenge(label, length as usize));
+                }
+            }
+        }
+        transcript.finish();
+        let response = prover.respond();
+        assert!(response.len() == self.response_len, "Length mismatch");
+        InteractiveProof { messages, response }
+    }
+
+    /// Replays `proof` to `verifier` with the challenges of a transcript running on `P`, and
+    /// returns its decision. A proof with the wrong number or lengths of messages is rejected.
+    pub fn verify<P, B>(&self, verifier: &mut B, proof: &InteractiveProof<P::Field>) -> bool
+    where
+        P: Permutation + Default,
+        B: Verifier<P::Field>,
+    {
+        let lengths = self
+            .pattern
+            .operations()
+            .iter()
+            .filter(|operation| operation.is_absorb())
+            .map(|operation| operation.len() as usize);
+        if proof.messages.len() != lengths.clone().count()
+            || !proof
+                .messages
+                .iter()
+                .zip(lengths)
+                .all(|(message, length)| message.len() == length)
+            || proof.response.len() != self.response_len
+        {
+            return false;
+        }
+
+        let mut transcript = self.transcript::<P>();
+        let mut messages = proof.messages.iter();
+        for (index, &operation) in self.pattern.operations().iter().enumerate() {
+            let label = self.label(index);
+            match operation {
+                Operation::Absorb(_) => {
+                    let message = messages.next().expect("the lengths were checked");
+                    transcript.append(label, message);
+                    verifier.message(label, message);
+                }
+                Operation::Squeeze(length) => {
+                    verifier.challenge(label, &transcript.challenge(label, length as usize));
+                }
+            }
+        }
+        transcript.finish();
+        verifier.decide(&proof.response)
+    }
+
+    fn transcript<P: Permutation + Default>(&self) -> Transcript<P> {
+        Transcript::new(self.pattern.clone(), self.domain_separator)
+    }
+
+    fn label(&self, index: usize) -> &str {
+        self.pattern.label(index).expect("the labels were checked")
+    }
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::domain::padded_domain_separator;
+    use crate::poseidon2::Poseidon2;
+    use ark_bn254::Fr;
+
+    /// Two rounds: the prover sends `a`, then `a * alpha`, and answers `a * alpha * beta`.
+    struct Multiplier {
+        a: Fr,
+        challenges: Vec<Fr>,
+    }
+
+    impl Prover<Fr> for Multiplier {
+        fn message(&mut self, label: &str, _: usize) -> Vec<Fr> {
+            match label {
+                "a" => vec![self.a],
+                _ => vec![self.a * self.challenges[0]],
+            }
+        }
+
+        fn challenge(&mut self, _: &str, challenge: &[Fr]) {
+            self.challenges.extend_from_slice(challenge);
+        }
+
+        fn respond(&mut self) -> Vec<Fr> {
+            vec![self.a * self.challenges[0] * self.challenges[1]]
+        }
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        messages: Vec<Fr>,
+        challenges: Vec<Fr>,
+    }
+
+    impl Verifier<Fr> for Recorder {
+        fn message(&mut self, _: &str, message: &[Fr]) {
+            self.messages.extend_from_slice(message);
+        }
+
+        fn challenge(&mut self, _: &str, challenge: &[Fr]) {
+            self.challenges.extend_from_slice(challenge);
+        }
+
+        fn decide(&mut self, response: &[Fr]) -> bool {
+            self.messages[1] == self.messages[0] * self.challenges[0]
+                && response[0] == self.messages[1] * self.challenges[1]
+        }
+    }
+
+    fn protocol() -> InteractiveProtocol {
+        let pattern = IoPattern::new()
+            .absorb_labeled("a", 1)
+            .squeeze_labeled("alpha", 1)
+            .absorb_labeled("a alpha", 1)
+            .squeeze_labeled("beta", 1);
+        InteractiveProtocol::new(pattern, &padded_domain_separator(b"test"), 1).unwrap()
+    }
+
+    fn prover() -> Multiplier {
+        Multiplier {
+            a: Fr::from(7u64),
+            challenges: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_run_matches_proof() {
+        let protocol = protocol();
+        let mut interactive = Recorder::default();
+        assert!(protocol.run::<Poseidon2, _, _>(&mut prover(), &mut interactive));
+
+        let proof = protocol.prove::<Poseidon2, _>(&mut prover());
+        let mut replayed = Recorder::default();
+        assert!(protocol.verify::<Poseidon2, _>(&mut replayed, &proof));
+        assert_eq!(replayed.challenges, interactive.challenges);
+        assert_eq!(
+            proof.messages,
+            [vec![Fr::from(7u64)], vec![interactive.messages[1]]]
+        );
+
+        let mut tampered = proof.clone();
+        tampered.messages[0][0] += Fr::from(1u64);
+        assert!(!protocol.verify::<Poseidon2, _>(&mut Recorder::default(), &tampered));
+        let mut truncated = proof;
+        truncated.messages.pop();
+        assert!(!protocol.verify::<Poseidon2, _>(&mut Recorder::default(), &truncated));
+    }
+
+    #[test]
+    fn test_validates_pattern() {
+        let domain_separator = padded_domain_separator(b"test");
+        let unlabeled = IoPattern::new().absorb_labeled("a", 1).squeeze(1);
+        assert_eq!(
+            InteractiveProtocol::new(unlabeled, &domain_separator, 0),
+            Err(SafeError::UnlabeledOperation(1))
+        );
+        let trailing = IoPattern::new()
+            .absorb_labeled("a", 1)
+            .squeeze_labeled("alpha", 1)
+            .absorb_labeled("b", 1);
+        assert_eq!(
+            InteractiveProtocol::new(trailing, &domain_separator, 0),
+            Err(SafeError::MissingFinalSqueeze(2))
+        );
+    }
+}
@@ -0,0 +1,646 @@
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use ark_ff::PrimeField;
+
+use crate::absorb::Absorb;
+use crate::encoding::encoded_len;
+use crate::error::SafeError;
+use crate::rng::squeeze_bytes_len;
+
+/// IO Pattern encoding constants (from SAFE spec 2.3).
+///
+/// These constants are used for encoding operation types in the 32-bit word format:
+/// - MSB set to 1 for ABSORB operations
+/// - MSB set to 0 for SQUEEZE operations
+///
+/// Flag for ABSORB operations (MSB = 1)
+pub const ABSORB_FLAG: u32 = 0x80000000;
+
+/// Flag for SQUEEZE operations (MSB = 0)
+pub const SQUEEZE_FLAG: u32 = 0x00000000;
+
+/// Mask selecting the 31-bit length field of an encoded word.
+pub const LENGTH_MASK: u32 = 0x7FFFFFFF;
+
+/// A single sponge operation of an IO pattern.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// ABSORB(n): absorb `n` field elements.
+    Absorb(u32),
+    /// SQUEEZE(n): squeeze `n` field elements.
+    Squeeze(u32),
+}
+
+impl Operation {
+    /// Decodes an operation from its 32-bit word (MSB=1 for ABSORB, MSB=0 for SQUEEZE).
+    pub fn from_word(word: u32) -> Self {
+        let length = word & LENGTH_MASK;
+        if (word & ABSORB_FLAG) != 0 {
+            Operation::Absorb(length)
+        } else {
+            Operation::Squeeze(length)
+        }
+    }
+
+    /// Encodes the operation as a 32-bit word (following SAFE spec 2.3).
+    pub fn encoded_word(&self) -> u32 {
+        match *self {
+            Operation::Absorb(length) => ABSORB_FLAG | length,
+            Operation::Squeeze(length) => SQUEEZE_FLAG | length,
+        }
+    }
+
+    /// Returns the number of field elements handled by the operation.
+    pub fn len(&self) -> u32 {
+        match *self {
+            Operation::Absorb(length) | Operation::Squeeze(length) => length,
+        }
+    }
+
+    /// Returns `true` if the operation handles no field elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` for ABSORB operations.
+    pub fn is_absorb(&self) -> bool {
+        matches!(self, Operation::Absorb(_))
+    }
+}
+
+/// The IO pattern of a sponge instance: the ordered sequence of ABSORB/SQUEEZE calls it expects.
+///
+/// Patterns are built fluently and take care of the MSB flag encoding:
+///
+/// ```
+/// use safe_api::IoPattern;
+///
+/// let pattern = IoPattern::new().absorb(3).squeeze(1);
+/// assert_eq!(pattern.encoded_words(), vec![0x80000003, 0x00000001]);
+/// ```
+///
+/// Operations can be given a label (e.g. a named prover message or Fiat-Shamir challenge).
+/// Labels are part of the protocol description but do not change the encoding or the tag.
+///
+/// Patterns also parse from, and display as, a compact text form for the command line,
+/// configuration files and tests: one token per operation, `A<n>` or `S<n>` for ABSORB(n) or
+/// SQUEEZE(n), optionally followed by `:<label>`, or an encoded `0x` word, separated by spaces
+/// or commas:
+///
+/// ```
+/// use safe_api::IoPattern;
+///
+/// let pattern: IoPattern = "A2:commitment S1:challenge A1 S1".parse().unwrap();
+/// assert_eq!(
+///     pattern,
+///     IoPattern::new()
+///         .absorb_labeled("commitment", 2)
+///         .squeeze_labeled("challenge", 1)
+///         .absorb(1)
+///         .squeeze(1)
+/// );
+/// assert_eq!(pattern.to_string(), "A2:commitment S1:challenge A1 S1");
+/// assert_eq!("0x80000003, 0x00000001".parse(), Ok(IoPattern::new().absorb(3).squeeze(1)));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct IoPattern {
+    operations: Vec<Operation>,
+    /// Optional label of each operation, indexed like `operations`.
+    labels: Vec<Option<String>>,
+}
+
+impl IoPattern {
+    /// Creates an empty IO pattern.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decodes an IO pattern from 32-bit encoded words, one operation per word.
+    ///
+    /// Words of length zero are kept, although the tag computation skips them (see
+    /// [`IoPattern::normalized`]); [`IoPattern::try_from_words`] rejects them.
+    pub fn from_words(words: &[u32]) -> Self {
+        IoPattern {
+            operations: words
+                .iter()
+                .map(|&word| Operation::from_word(word))
+                .collect(),
+            labels: vec![None; words.len()],
+        }
+    }
+
+    /// Decodes an IO pattern from 32-bit encoded words like [`IoPattern::from_words`], failing
+    /// on a word of length zero. [`IoPattern::validate`] checks the other rules of the spec.
+    pub fn try_from_words(words: &[u32]) -> Result<Self, SafeError> {
+        let pattern = Self::from_words(words);
+        match pattern.operations.iter().position(Operation::is_empty) {
+            Some(index) => Err(SafeError::ZeroLengthOperation(index)),
+            None => Ok(pattern),
+        }
+    }
+
+    /// Checks that the pattern follows SAFE spec 2.3, reporting the first violation.
+    ///
+    /// The tag computation accepts any pattern, but some of them do not describe what they
+    /// seem to: zero-length operations are skipped, so the pattern gets the tag of the pattern
+    /// without them, and a length over 31 bits, alone or summed with the operations of the
+    /// same type it aggregates with, spills into the ABSORB flag of the encoded word.
+    ///
+    /// # Errors
+    /// - [`SafeError::EmptyPattern`] if the pattern has no operations.
+    /// - [`SafeError::ZeroLengthOperation`] with the index of an operation of length zero.
+    /// - [`SafeError::LengthOverflow`] with the index of the operation whose length, or the
+    ///   aggregated length up to it, exceeds [`LENGTH_MASK`].
+    /// - [`SafeError::MissingFinalSqueeze`] with the index of the last operation if it is an
+    ///   ABSORB: the sponge would discard the absorbed elements.
+    pub fn validate(&self) -> Result<(), SafeError> {
+        let last = match self.operations.len() {
+            0 => return Err(SafeError::EmptyPattern),
+            length => length - 1,
+        };
+        let mut group: Option<Operation> = None;
+        for (index, &operation) in self.operations.iter().enumerate() {
+            if operation.is_empty() {
+                return Err(SafeError::ZeroLengthOperation(index));
+            }
+            let length = match group {
+                Some(previous) if previous.is_absorb() == operation.is_absorb() => {
+                    previous.len().checked_add(operation.len())
+                }
+                _ => Some(operation.len()),
+            };
+            let length = length
+                .filter(|&length| length <= LENGTH_MASK)
+                .ok_or(SafeError::LengthOverflow(index))?;
+            group = Some(match operation {
+                Operation::Absorb(_) => Operation::Absorb(length),
+                Operation::Squeeze(_) => Operation::Squeeze(length),
+            });
+        }
+        if self.operations[last].is_absorb() {
+            return Err(SafeError::MissingFinalSqueeze(last));
+        }
+        Ok(())
+    }
+
+    /// Assembles an IO pattern from its operations and their labels, one per operation.
+    pub(crate) fn from_parts(operations: Vec<Operation>, labels: Vec<Option<String>>) -> Self {
+        debug_assert_eq!(operations.len(), labels.len());
+        IoPattern { operations, labels }
+    }
+
+    /// Appends an ABSORB(`length`) operation.
+    pub fn absorb(self, length: u32) -> Self {
+        self.push(Operation::Absorb(length), None)
+    }
+
+    /// Appends the ABSORB of a `byte_len`-byte string encoded into elements of `F` (see
+    /// [`encoding`](crate::encoding)).
+    pub fn absorb_bytes<F: PrimeField>(self, byte_len: usize) -> Self {
+        self.absorb(encoded_len::<F>(byte_len) as u32)
+    }
+
+    /// Appends the ABSORB of the canonical encoding of `value` into elements of `F` (see
+    /// [`absorb`](crate::absorb)). Only the length of the encoding matters, so any value of
+    /// a fixed-size type declares the ABSORB of every other.
+    pub fn absorb_value<F: PrimeField, T: Absorb<F> + ?Sized>(self, value: &T) -> Self {
+        self.absorb(value.absorb_len() as u32)
+    }
+
+    /// Appends the SQUEEZE of `byte_len` uniform bytes from elements of `F`, see
+    /// [`Sponge::squeeze_bytes`](crate::Sponge::squeeze_bytes).
+    pub fn squeeze_bytes<F: PrimeField>(self, byte_len: usize) -> Self {
+        self.squeeze(squeeze_bytes_len::<F>(byte_len) as u32)
+    }
+
+    /// Appends the ABSORB of a fork label, at which [`Sponge::fork`](crate::Sponge::fork)
+    /// branches the sponge into sub-protocols, over elements of `F`.
+    pub fn fork<F: PrimeField>(self) -> Self {
+        self.absorb_bytes::<F>(32)
+    }
+
+    /// Appends a SQUEEZE(`length`) operation.
+    pub fn squeeze(self, length: u32) -> Self {
+        self.push(Operation::Squeeze(length), None)
+    }
+
+    /// Appends an ABSORB(`length`) operation named `label`, e.g. a prover message.
+    ///
+    /// # Panics
+    /// If another operation of the pattern already uses `label`.
+    pub fn absorb_labeled(self, label: &str, length: u32) -> Self {
+        assert!(self.label_index(label).is_none(), "Duplicate label");
+        self.push(Operation::Absorb(length), Some(label.to_string()))
+    }
+
+    /// Appends a SQUEEZE(`length`) operation named `label`, e.g. a Fiat-Shamir challenge.
+    ///
+    /// # Panics
+    /// If another operation of the pattern already uses `label`.
+    pub fn squeeze_labeled(self, label: &str, length: u32) -> Self {
+        assert!(self.label_index(label).is_none(), "Duplicate label");
+        self.push(Operation::Squeeze(length), Some(label.to_string()))
+    }
+
+    /// Returns the label of the operation at `index`, if any.
+    pub fn label(&self, index: usize) -> Option<&str> {
+        self.labels.get(index).and_then(Option::as_deref)
+    }
+
+    /// Returns the position in the pattern of the operation, ABSORB or SQUEEZE, named `label`.
+    pub fn label_index(&self, label: &str) -> Option<usize> {
+        self.labels
+            .iter()
+            .position(|candidate| candidate.as_deref() == Some(label))
+    }
+
+    /// Returns the position in the pattern of the SQUEEZE named `label`.
+    pub fn squeeze_index(&self, label: &str) -> Option<usize> {
+        self.label_index(label)
+            .filter(|&index| !self.operations[index].is_absorb())
+    }
+
+    /// Returns the labeled squeezes as `(label, length)` pairs, in pattern order.
+    pub fn squeeze_labels(&self) -> Vec<(&str, u32)> {
+        self.operations
+            .iter()
+            .zip(&self.labels)
+            .filter(|(operation, _)| !operation.is_absorb())
+            .filter_map(|(operation, label)| Some((label.as_deref()?, operation.len())))
+            .collect()
+    }
+
+    fn push(mut self, operation: Operation, label: Option<String>) -> Self {
+        self.operations.push(operation);
+        self.labels.push(label);
+        self
+    }
+
+    /// Returns the operations in the order they were added.
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    /// Returns the number of operations in the pattern.
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    /// Returns `true` if the pattern has no operations.
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Returns the 32-bit encoded words of the pattern, one per operation (no aggregation).
+    pub fn encoded_words(&self) -> Vec<u32> {
+        self.operations
+            .iter()
+            .map(Operation::encoded_word)
+            .collect()
+    }
+
+    /// Returns the pattern of a sequenced run, where every ABSORB is one element longer for the
+    /// sequence number of [`Sponge::absorb_sequenced`](crate::Sponge::absorb_sequenced). Labels
+    /// are kept.
+    pub fn sequenced(&self) -> IoPattern {
+        IoPattern {
+            operations: self
+                .operations
+                .iter()
+                .map(|operation| match *operation {
+                    Operation::Absorb(length) => Operation::Absorb(length + 1),
+                    squeeze => squeeze,
+                })
+                .collect(),
+            labels: self.labels.clone(),
+        }
+    }
+
+    /// Returns the aggregated pattern used for tag computation (following SAFE spec 2.3).
+    ///
+    /// Consecutive operations of the same type are merged into one and zero-length groups are
+    /// dropped. This mirrors the Noir `compute_tag` exactly, including its handling of zero
+    /// words: a SQUEEZE(0) is skipped entirely, while an ABSORB(0) still ends a running
+    /// SQUEEZE group (so `[S1, A0, S1]` normalizes to `[S1, S1]`).
+    ///
+    /// Labels are dropped, since merged operations no longer map to a single name.
+    pub fn normalized(&self) -> IoPattern {
+        let mut operations = Vec::new();
+        self.normalize_into(&mut operations);
+        IoPattern {
+            labels: vec![None; operations.len()],
+            operations,
+        }
+    }
+
+    /// Replaces the contents of `operations` with those of [`IoPattern::normalized`], reusing
+    /// its allocation.
+    pub(crate) fn normalize_into(&self, operations: &mut Vec<Operation>) {
+        operations.clear();
+        aggregate(&self.operations, |operation| operations.push(operation));
+    }
+}
+
+/// Aggregates consecutive operations of the same type, skipping the zero word, and passes each
+/// group to `emit` (following SAFE spec 2.3). Used by [`IoPattern::normalized`] and by the
+/// heapless [`fixed`](crate::fixed) module, which cannot collect the groups.
+pub(crate) fn aggregate(operations: &[Operation], mut emit: impl FnMut(Operation)) {
+    // The open group starts as an empty SQUEEZE, like `last_was_absorb = false` in Noir.
+    let mut current = Operation::Squeeze(0);
+
+    for &operation in operations {
+        if operation == Operation::Squeeze(0) {
+            // The zero word carries no information and is skipped.
+            continue;
+        }
+
+        current = match (current, operation) {
+            // Aggregate consecutive operations of the same type. Lengths beyond 32 bits, which
+            // `validate` rejects, wrap instead of panicking, as they always did in release
+            // builds.
+            (Operation::Absorb(sum), Operation::Absorb(length)) => {
+                Operation::Absorb(sum.wrapping_add(length))
+            }
+            (Operation::Squeeze(sum), Operation::Squeeze(length)) => {
+                Operation::Squeeze(sum.wrapping_add(length))
+            }
+            // Flush the previous group when the operation type changes.
+            _ => {
+                if !current.is_empty() {
+                    emit(current);
+                }
+                operation
+            }
+        };
+    }
+
+    // Flush remaining operations.
+    if !current.is_empty() {
+        emit(current);
+    }
+}
+
+impl FromStr for IoPattern {
+    type Err = SafeError;
+
+    /// Parses the text form of the [type documentation](IoPattern).
+    ///
+    /// # Errors
+    /// - [`SafeError::InvalidPatternToken`] with the token that is neither `A<n>`, `S<n>` nor
+    ///   an encoded word, or whose length does not fit in 31 bits.
+    /// - [`SafeError::DuplicateLabel`] with a label used twice.
+    /// - [`SafeError::EmptyPattern`] if there are no tokens.
+    fn from_str(input: &str) -> Result<Self, SafeError> {
+        let mut pattern = IoPattern::new();
+        for token in input
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|token| !token.is_empty())
+        {
+            let invalid = || SafeError::InvalidPatternToken(token.to_string());
+            let (operation, label) = match token.split_once(':') {
+                Some((_, "")) => return Err(invalid()),
+                Some((operation, label)) => (operation, Some(label)),
+                None => (token, None),
+            };
+            let operation = if let Some(hex) = operation.strip_prefix("0x") {
+                Operation::from_word(u32::from_str_radix(hex, 16).map_err(|_| invalid())?)
+            } else {
+                let mut chars = operation.chars();
+                let kind = chars.next();
+                let length: u32 = match chars.as_str().parse() {
+                    Ok(length) if length <= LENGTH_MASK => length,
+                    _ => return Err(invalid()),
+                };
+                match kind {
+                    Some('A' | 'a') => Operation::Absorb(length),
+                    Some('S' | 's') => Operation::Squeeze(length),
+                    _ => return Err(invalid()),
+                }
+            };
+            if let Some(label) = label {
+                if pattern.label_index(label).is_some() {
+                    return Err(SafeError::DuplicateLabel(label.to_string()));
+                }
+            }
+            pattern = pattern.push(operation, label.map(str::to_string));
+        }
+
+        if pattern.is_empty() {
+            return Err(SafeError::EmptyPattern);
+        }
+        Ok(pattern)
+    }
+}
+
+impl fmt::Display for IoPattern {
+    /// Writes the text form of the [type documentation](IoPattern), e.g. `A3 S1`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, operation) in self.operations.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" ")?;
+            }
+            match operation {
+                Operation::Absorb(length) => write!(f, "A{}", length)?,
+                Operation::Squeeze(length) => write!(f, "S{}", length)?,
+            }
+            if let Some(label) = self.label(index) {
+                write!(f, ":{}", label)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<&[u32]> for IoPattern {
+    fn from(words: &[u32]) -> Self {
+        IoPattern::from_words(words)
+    }
+}
+
+/// Mostly short operations, which aggregate, with zero and near-overflow lengths to reach the
+/// edge cases of the aggregation.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Operation {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let length = match u.int_in_range(0..=7u8)? {
+            0 => 0,
+            1 => LENGTH_MASK - u.int_in_range(0..=3)?,
+            2 => u.int_in_range(0..=LENGTH_MASK)?,
+            _ => u.int_in_range(1..=8)?,
+        };
+        Ok(match u.arbitrary::<bool>()? {
+            true => Operation::Absorb(length),
+            false => Operation::Squeeze(length),
+        })
+    }
+}
+
+/// Any sequence of arbitrary [`Operation`]s, valid or not, some of them labeled.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for IoPattern {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut pattern = IoPattern::new();
+        for (index, operation) in u.arbitrary_iter::<(Operation, bool)>()?.enumerate() {
+            let (operation, labeled) = operation?;
+            let label = labeled.then(|| alloc::format!("m{}", index));
+            pattern = pattern.push(operation, label);
+        }
+        Ok(pattern)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_encoding() {
+        let pattern = IoPattern::new().absorb(3).squeeze(1).absorb(2).squeeze(2);
+        assert_eq!(
+            pattern.encoded_words(),
+            vec![0x80000003, 0x00000001, 0x80000002, 0x00000002]
+        );
+        assert_eq!(IoPattern::from_words(&pattern.encoded_words()), pattern);
+    }
+
+    #[test]
+    fn test_consecutive_aggregation() {
+        // ABSORB(3), ABSORB(3), SQUEEZE(3) aggregates to ABSORB(6), SQUEEZE(3) (SAFE spec example).
+        let pattern = IoPattern::new().absorb(3).absorb(3).squeeze(3);
+        assert_eq!(pattern.normalized(), IoPattern::new().absorb(6).squeeze(3));
+
+        let pattern = IoPattern::new().absorb(2).squeeze(1).squeeze(1).absorb(1);
+        assert_eq!(
+            pattern.normalized(),
+            IoPattern::new().absorb(2).squeeze(2).absorb(1)
+        );
+    }
+
+    #[test]
+    fn test_squeeze_labels() {
+        let pattern = IoPattern::new()
+            .absorb(3)
+            .squeeze_labeled("alpha", 1)
+            .absorb(1)
+            .squeeze_labeled("query_indices", 4);
+
+        assert_eq!(pattern.squeeze_index("query_indices"), Some(3));
+        // An ABSORB has a label index but no squeeze index.
+        let absorbed = pattern.clone().absorb_labeled("commitment", 2);
+        assert_eq!(absorbed.label_index("commitment"), Some(4));
+        assert_eq!(absorbed.squeeze_index("commitment"), None);
+        assert_eq!(pattern.label(1), Some("alpha"));
+        assert_eq!(pattern.label(0), None);
+        assert_eq!(
+            pattern.squeeze_labels(),
+            vec![("alpha", 1), ("query_indices", 4)]
+        );
+
+        // Labels do not change the encoding.
+        assert_eq!(
+            pattern.encoded_words(),
+            IoPattern::new()
+                .absorb(3)
+                .squeeze(1)
+                .absorb(1)
+                .squeeze(4)
+                .encoded_words()
+        );
+    }
+
+    #[test]
+    fn test_zero_length_operations() {
+        // Zero-length groups are dropped.
+        let pattern = IoPattern::new().absorb(0).squeeze(1);
+        assert_eq!(pattern.normalized(), IoPattern::new().squeeze(1));
+
+        // SQUEEZE(0) is skipped, so the surrounding absorbs merge.
+        let pattern = IoPattern::new().absorb(1).squeeze(0).absorb(1);
+        assert_eq!(pattern.normalized(), IoPattern::new().absorb(2));
+
+        // ABSORB(0) still splits squeeze groups, matching the Noir implementation.
+        let pattern = IoPattern::new().squeeze(1).absorb(0).squeeze(1);
+        assert_eq!(pattern.normalized(), IoPattern::new().squeeze(1).squeeze(1));
+
+        assert_eq!(
+            IoPattern::try_from_words(&[0x80000001, 0x00000001]),
+            Ok(IoPattern::new().absorb(1).squeeze(1))
+        );
+        assert_eq!(
+            IoPattern::try_from_words(&[0x80000001, 0x00000000, 0x80000001]),
+            Err(SafeError::ZeroLengthOperation(1))
+        );
+        assert_eq!(
+            IoPattern::try_from_words(&[0x80000000]),
+            Err(SafeError::ZeroLengthOperation(0))
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(IoPattern::new().absorb(3).squeeze(1).validate(), Ok(()));
+        assert_eq!(IoPattern::new().validate(), Err(SafeError::EmptyPattern));
+        assert_eq!(
+            IoPattern::new().absorb(1).squeeze(0).squeeze(1).validate(),
+            Err(SafeError::ZeroLengthOperation(1))
+        );
+        assert_eq!(
+            IoPattern::new().absorb(1).squeeze(1).absorb(2).validate(),
+            Err(SafeError::MissingFinalSqueeze(2))
+        );
+        assert_eq!(
+            IoPattern::new().absorb(1 << 31).squeeze(1).validate(),
+            Err(SafeError::LengthOverflow(0))
+        );
+        // Lengths that only overflow once aggregated.
+        assert_eq!(
+            IoPattern::new()
+                .absorb(1)
+                .squeeze(LENGTH_MASK)
+                .squeeze(1)
+                .validate(),
+            Err(SafeError::LengthOverflow(2))
+        );
+        assert_eq!(
+            IoPattern::new()
+                .absorb(LENGTH_MASK)
+                .squeeze(LENGTH_MASK)
+                .validate(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_text_form() {
+        let pattern: IoPattern = "A3 S1 a2,s2".parse().unwrap();
+        assert_eq!(
+            pattern,
+            IoPattern::new().absorb(3).squeeze(1).absorb(2).squeeze(2)
+        );
+        assert_eq!(pattern.to_string(), "A3 S1 A2 S2");
+        assert_eq!(
+            "0x80000003 S1".parse(),
+            Ok(IoPattern::new().absorb(3).squeeze(1))
+        );
+
+        let labeled = IoPattern::new()
+            .absorb_labeled("commitment", 2)
+            .squeeze_labeled("challenge", 1);
+        assert_eq!(labeled.to_string().parse(), Ok(labeled));
+
+        let invalid = |token: &str| Err(SafeError::InvalidPatternToken(token.to_string()));
+        assert_eq!("A3 X1".parse::<IoPattern>(), invalid("X1"));
+        assert_eq!("A3 S".parse::<IoPattern>(), invalid("S"));
+        assert_eq!("A2147483648".parse::<IoPattern>(), invalid("A2147483648"));
+        assert_eq!("A1:".parse::<IoPattern>(), invalid("A1:"));
+        assert_eq!(
+            "A1:x S1:x".parse::<IoPattern>(),
+            Err(SafeError::DuplicateLabel("x".to_string()))
+        );
+        assert_eq!(" , ".parse::<IoPattern>(), Err(SafeError::EmptyPattern));
+    }
+}
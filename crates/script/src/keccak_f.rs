@@ -0,0 +1,143 @@
+//! Keccak-f[1600] as a sponge permutation over the BN254 scalar field, for services that only
+//! need the SAFE semantics off-chain.
+//!
+//! [`KeccakF`] has the state of the other BN254 backends (width 4, rate 3, capacity 1), so
+//! protocol code, IO patterns and tags are unchanged, but runs a bit permutation instead of
+//! field arithmetic: over ten times faster than Poseidon2, and useless in a circuit. Its
+//! outputs differ from those of the other backends, so both sides of a protocol must agree on
+//! it, and a proof system checking the transcript needs one of those instead.
+//!
+//! The 25 lanes of the Keccak state hold the four elements in blocks of 6 lanes, each element as
+//! the four 64-bit limbs of its canonical integer, least significant first, followed by two
+//! zero lanes; the last lane is zero. After Keccak-f[1600], each block, read the same way as a
+//! 384-bit integer, is reduced modulo the field order, to within `2^-128` of uniform. The map is
+//! therefore not a permutation of the field elements but a random function on the state, which
+//! the sponge only relies on.
+//!
+//! ```
+//! use ark_bn254::Fr;
+//! use safe_api::domain::padded_domain_separator;
+//! use safe_api::keccak_f::KeccakF;
+//! use safe_api::{IoPattern, Sponge};
+//!
+//! let pattern = IoPattern::new().absorb(2).squeeze(1);
+//! let domain_separator = padded_domain_separator(b"my-app/session");
+//! let mut sponge = Sponge::<KeccakF>::start(pattern, &domain_separator);
+//! sponge.absorb(&[Fr::from(1u64), Fr::from(2u64)]);
+//! let challenge = sponge.squeeze();
+//! sponge.finish();
+//! # assert_eq!(challenge.len(), 1);
+//! ```
+
+use ark_bn254::Fr;
+use ark_ff::{BigInt, BigInteger, MontFp, PrimeField};
+
+use crate::permutation::Permutation;
+
+/// Width of the permutation state in field elements.
+pub const STATE_WIDTH: usize = 4;
+
+/// Lanes of the Keccak state holding each element.
+const BLOCK_LANES: usize = 6;
+
+/// `2^256` modulo the field order, the weight of the two high lanes of a block.
+const TWO_POW_256: Fr =
+    MontFp!("6350874878119819312338956282401532410528162663560392320966563075034087161851");
+
+/// Keccak-f[1600] on four BN254 elements as a sponge [`Permutation`] (rate 3, capacity 1), see
+/// the [module documentation](self).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct KeccakF;
+
+impl Permutation for KeccakF {
+    type Field = Fr;
+
+    const WIDTH: usize = STATE_WIDTH;
+
+    const RATE: usize = 3;
+
+    const ID: &'static str = "keccak-f-bn254";
+
+    fn permute(&self, state: &mut [Fr]) {
+        assert_eq!(
+            state.len(),
+            STATE_WIDTH,
+            "Keccak-f state must have 4 elements"
+        );
+
+        let mut lanes = [0u64; 25];
+        for (block, element) in lanes.chunks_exact_mut(BLOCK_LANES).zip(state.iter()) {
+            block[..4].copy_from_slice(&element.into_bigint().0);
+        }
+
+        ::keccak::f1600(&mut lanes);
+
+        for (element, block) in state.iter_mut().zip(lanes.chunks_exact(BLOCK_LANES)) {
+            *element = reduce(block);
+        }
+    }
+}
+
+/// Reduces the 384-bit integer of a block modulo the field order.
+fn reduce(block: &[u64]) -> Fr {
+    let mut low = BigInt::new([block[0], block[1], block[2], block[3]]);
+    while low >= Fr::MODULUS {
+        low.sub_with_borrow(&Fr::MODULUS);
+    }
+    let high = ((block[5] as u128) << 64) | block[4] as u128;
+    Fr::from(high) * TWO_POW_256 + Fr::from_bigint(low).expect("reduced below the modulus")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::padded_domain_separator;
+    use crate::io_pattern::IoPattern;
+    use crate::sponge::Sponge;
+    use ark_ff::Field;
+
+    #[test]
+    fn test_zero_state() {
+        // The zero state is all-zero lanes, whose Keccak-f image starts with these lanes
+        // (the Keccak team's KeccakF-1600 intermediate values).
+        let mut lanes = [0u64; 25];
+        ::keccak::f1600(&mut lanes);
+        assert_eq!(lanes[..2], [0xf1258f7940e1dde7, 0x84d5ccf933c0478a]);
+
+        let mut state = [Fr::from(0u64); STATE_WIDTH];
+        KeccakF.permute(&mut state);
+        let bytes: Vec<u8> = lanes[..BLOCK_LANES]
+            .iter()
+            .flat_map(|lane| lane.to_le_bytes())
+            .collect();
+        assert_eq!(state[0], Fr::from_le_bytes_mod_order(&bytes));
+    }
+
+    #[test]
+    fn test_two_pow_256() {
+        assert_eq!(TWO_POW_256, Fr::from(2u64).pow([256]));
+    }
+
+    #[test]
+    fn test_runs_the_sponge() {
+        let pattern = IoPattern::new().absorb(4).squeeze(2);
+        let run = |message: u64| {
+            let mut sponge =
+                Sponge::<KeccakF>::start(pattern.clone(), padded_domain_separator(b"test"));
+            sponge.absorb(&[1, 2, 3, message].map(Fr::from));
+            let output = sponge.squeeze();
+            sponge.finish();
+            output
+        };
+        assert_eq!(run(4), run(4));
+        assert_ne!(run(4), run(5));
+
+        // Regression vector of this layout.
+        let mut state = [0u64, 1, 2, 3].map(Fr::from);
+        KeccakF.permute(&mut state);
+        let expected: Fr = MontFp!(
+            "15966045533703852423420068988825635274301029667905055715895488337531548616777"
+        );
+        assert_eq!(state[0], expected);
+    }
+}
@@ -0,0 +1,221 @@
+//! SAFE (Sponge API for Field Elements) helpers matching the Noir implementation.
+//!
+//! - [`IoPattern`]: builder for the 32-bit encoded IO patterns of SAFE spec 2.3.
+//! - [`compute_tag`]: tag computation from an IO pattern and a domain separator, as an integer,
+//!   as a field element ([`compute_tag_field`]), or untruncated ([`compute_tag_256`]) for
+//!   byte-oriented sponges.
+//! - [`compute_tag_field_with_length`]: the tag from 64, 128 or 248 bits of the digest, or the
+//!   whole digest reduced into the field ([`TagLength`]).
+//! - [`TagHasher`]: the hash of the tag derivation, SHA-256 or, behind features, Keccak-256
+//!   and Blake2s ([`compute_tag_with_hasher`]).
+//! - [`compute_tags_batch`]: the tags of many patterns at once, in parallel with the `rayon`
+//!   feature.
+//! - [`compute_tag_verbose`]: the tag with every intermediate value, as shown by
+//!   `safe-api tag --verbose`.
+//! - [`tag_preimage`] / [`tag_from_preimage`]: the serialization and hashing steps of the tag,
+//!   exposed separately as a test oracle for other implementations.
+//! - [`Sponge`]: the SAFE sponge, generic over a [`Permutation`] and thereby over any
+//!   [`PrimeField`]. With
+//!   [`Poseidon2`](poseidon2::Poseidon2) it produces the same outputs as the Noir `SafeSponge`.
+//!   Over fields smaller than the tag, [`compute_tag_limbs`] spreads the tag over the capacity.
+//! - [`state`]: checkpoints of a sponge or transcript, to pause it and resume it later, with a
+//!   stable binary encoding.
+//! - [`absorb`]: the canonical encoding of structured values absorbed with
+//!   [`Sponge::absorb_value`], with `#[derive(Absorb)]` for structs.
+//! - [`fixed`]: heapless IO patterns, [`compute_tag_fixed`] and a sponge with its state inline,
+//!   for embedded targets that cannot allocate.
+//! - [`typed::TypedSponge`]: a sponge whose IO pattern is checked at compile time.
+//! - [`Transcript`]: a Fiat-Shamir transcript over the sponge, with labeled messages and
+//!   challenges, optionally bound to their sequence numbers.
+//! - [`hash`]: hashing helpers with a canonical encoding, e.g. [`hash_map_like`] and
+//!   [`commit_set`].
+//! - [`codegen`]: Noir source generation for computed tags and gadgets.
+//! - [`flatten`]: the arguments of the Noir gadgets flattened from Merkle proofs, commitment
+//!   openings and challenge lists, with their `Prover.toml`.
+//! - [`presets`]: ready-made transcripts, e.g. a Bulletproofs-style range proof.
+//! - [`encoding`]: the injective encoding of byte strings absorbed with
+//!   [`Sponge::absorb_bytes`].
+//! - [`rng`]: a deterministic randomness source squeezed from the sponge, e.g. for nonces, and
+//!   a seeded one for simulations.
+//! - [`queries`]: query indices derived from transcript challenges, with or without repeats.
+//! - [`commitment`]: hiding commitments to field elements, with openings.
+//! - [`secret`]: secret inputs such as keys, erased after use, and constant-time comparisons.
+//! - [`aead`]: authenticated encryption of field-element payloads with the sponge in duplex
+//!   mode.
+//! - [`ark_sponge`]: the sponge behind the arkworks `CryptographicSponge` trait, for arkworks
+//!   Fiat-Shamir and SNARK code.
+//! - [`neptune`]: sponges agreeing with the `neptune` SAFE implementation of lurk-lab and
+//!   Filecoin over BLS12-381.
+//! - [`halo2`]: Halo2 prover and verifier transcripts over the sponge.
+//! - [`interactive`]: a driver for multi-round public-coin protocols, played interactively or
+//!   compiled into a non-interactive proof.
+//! - [`sigma`]: sigma protocols made non-interactive with a [`Transcript`].
+//! - [`subprotocol`]: child sponges spawned under a domain separator derived from their
+//!   parent, whose output the parent absorbs, to compose gadgets.
+//! - [`hash_to_curve`]: uniform field elements and curve points, e.g. generators, squeezed from
+//!   the transcript.
+//! - [`merkle`]: binary Merkle trees with sponge nodes under a per-tree domain separator.
+//! - [`chunked`]: checksummed chunked commitments to large data, with per-chunk proofs.
+//! - [`coverage`]: which protocols of a manifest the sessions of a test suite ran end to end.
+//! - [`config`]: the expected rate, capacity, field and security level of a sponge, checked
+//!   against its permutation and folded into the domain separator.
+//! - [`defaults`]: the process-wide policies of the sponges, set once at startup.
+//! - [`SafeError`]: the crate-wide error type, which every module error converts into.
+//! - [`hex`]: hex byte strings, read strictly from machine-written inputs or leniently from
+//!   user inputs.
+//! - [`display`]: the fixed-width display and parsing of field elements and tags used by all
+//!   the tools.
+//! - [`armor`]: checksummed text encodings, bech32m tags (`stag1...`) and armored envelopes,
+//!   for values pasted by hand.
+//! - [`evm`]: canonical encodings of Ethereum addresses, words and ABI-encoded calldata, to
+//!   absorb them as the Noir circuits do.
+//! - [`domain`]: domain separator layouts, e.g. expiring separators bound to an epoch.
+//! - [`trace_context`]: the trace id of requests to services running sponge sessions.
+//! - [`vectors`]: JSON test vectors of the tag computation, with every intermediate value.
+//! - [`instrument`]: `tracing` spans and events of the sponge, with the values redacted by
+//!   default.
+//! - [`recorder`]: a debug recorder of sponge sessions, with every state after a permutation,
+//!   and a diff against a trace recorded from another implementation.
+//!
+//! # Features
+//! - `std` (default): implements `std::error::Error` for the error types. Without it the crate
+//!   is `#![no_std]` and only needs `alloc`; `poseidon`, `vectors` and `cli` require it.
+//! - `poseidon2` (default): Poseidon2 over BN254, the permutation of the Noir circuit.
+//! - `small-fields`: the Goldilocks and BabyBear [`fields`], with their Plonky3 Poseidon2
+//!   instances (pulls in `poseidon2`).
+//! - `poseidon`: Poseidon over BN254 with the circomlib parameters.
+//! - `rescue-prime`: Rescue-Prime over BN254.
+//! - `keccak-f`: [`keccak_f`], Keccak-f[1600] on BN254 elements, a fast backend for off-chain
+//!   services that have no circuit.
+//! - `rayon`: [`compute_tags_batch`] and the batch Poseidon2 permutation of Merkle levels
+//!   ([`Permutation::permute_batch`]) on the rayon thread pool.
+//! - `arbitrary`: `arbitrary::Arbitrary` for [`IoPattern`] and [`domain::DomainSeparator`], and
+//!   the [`fuzzing`] invariants of the cargo-fuzz targets.
+//! - `tokio`: [`Sponge::absorb_async_reader`], streaming absorption from a tokio `AsyncRead`.
+//! - `keccak`, `blake2s`: the [`TagHasher`]s `Keccak256Hasher` and `Blake2sHasher`.
+//! - `derive`: `#[derive(Absorb)]`, the [`absorb::Absorb`] derive macro (pulls in
+//!   `safe-api-derive`).
+//! - `serde`: `Serialize` and `Deserialize` for [`state::SpongeState`].
+//! - `vectors` (default): the JSON test vector format (pulls in `serde`).
+//! - `test-utils`: the [`coverage`] report of the protocols exercised by a test suite.
+//! - `recorder`: the debug [`recorder`] and its JSON traces (pulls in `vectors`).
+//! - `cli` (default): the `safe-api` command line tool.
+//! - `ark-sponge`: [`ark_sponge`], the arkworks `CryptographicSponge` implementation (pulls in
+//!   `ark-crypto-primitives`).
+//! - `spongefish`: [`spongefish`], the sponge as a spongefish (formerly nimue) duplex sponge for
+//!   its prover and verifier states.
+//! - `neptune`: [`neptune`], interoperability with the `neptune` sponge (pulls in `neptune`,
+//!   `blstrs` and `ark-bls12-381`).
+//! - `halo2`: [`halo2`], the `halo2_proofs` transcript traits (pulls in `halo2_proofs`).
+//! - `hash-to-curve`: [`hash_to_curve`] (pulls in `ark-ec` and the BN254 curve).
+//! - `tracing`: [`instrument`], spans around the tag derivations, sponge constructions and
+//!   permutations, and events for the operations (pulls in `tracing`).
+//! - `shadow`: a debug mode where the optimized paths also run their naive reference and panic
+//!   on a mismatch. Slow and memory hungry, for tests only.
+//! - `unstable`: the experimental modules below, each behind its own feature as well: `erasure`
+//!   (Reed–Solomon extended commitments for data availability sampling, pulls in `ark-poly`).
+//!
+//! # Stability
+//! The crate follows semver, checked in CI against the target branch, and supports Rust
+//! 1.89 and later; raising the minimum supported version is a minor release. Tags, digests and
+//! the encodings they depend on are part of the API: a change to any output of a stable module
+//! is a breaking change.
+//!
+//! Experimental modules live under `unstable`, which is hidden from the documentation and
+//! enabled by the `unstable` feature. They may change in any release and get no such
+//! guarantee until they move to the crate root.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+// The code generated by `#[derive(Absorb)]` names the crate, also in its own tests.
+extern crate self as safe_api;
+
+pub mod absorb;
+pub mod aead;
+#[cfg(feature = "ark-sponge")]
+pub mod ark_sponge;
+pub mod armor;
+pub mod chunked;
+pub mod codegen;
+pub mod commitment;
+pub mod config;
+#[cfg(feature = "test-utils")]
+pub mod coverage;
+pub mod defaults;
+pub mod display;
+pub mod domain;
+pub mod encoding;
+pub mod error;
+pub mod evm;
+#[cfg(feature = "small-fields")]
+pub mod fields;
+pub mod fixed;
+pub mod flatten;
+#[cfg(feature = "arbitrary")]
+pub mod fuzzing;
+#[cfg(feature = "halo2")]
+pub mod halo2;
+pub mod hash;
+#[cfg(feature = "hash-to-curve")]
+pub mod hash_to_curve;
+pub mod hex;
+#[cfg(feature = "tracing")]
+pub mod instrument;
+pub mod interactive;
+pub mod io_pattern;
+#[cfg(feature = "keccak-f")]
+pub mod keccak_f;
+pub mod merkle;
+#[cfg(feature = "neptune")]
+pub mod neptune;
+pub mod permutation;
+#[cfg(feature = "poseidon")]
+pub mod poseidon;
+#[cfg(feature = "poseidon2")]
+pub mod poseidon2;
+pub mod presets;
+pub mod queries;
+#[cfg(feature = "recorder")]
+pub mod recorder;
+#[cfg(feature = "rescue-prime")]
+pub mod rescue_prime;
+pub mod rng;
+pub mod secret;
+#[cfg(feature = "shadow")]
+mod shadow;
+pub mod sigma;
+pub mod sponge;
+#[cfg(feature = "spongefish")]
+pub mod spongefish;
+pub mod state;
+pub mod subprotocol;
+pub mod tag;
+#[cfg(feature = "std")]
+pub mod tag_cache;
+pub mod trace_context;
+pub mod transcript;
+pub mod typed;
+#[cfg(feature = "unstable")]
+#[doc(hidden)]
+pub mod unstable;
+#[cfg(feature = "vectors")]
+pub mod vectors;
+
+pub use error::SafeError;
+pub use fixed::compute_tag_fixed;
+pub use hash::{commit_set, hash_map_like};
+pub use io_pattern::{IoPattern, Operation};
+pub use permutation::Permutation;
+pub use sponge::Sponge;
+pub use tag::{
+    compute_tag, compute_tag_256, compute_tag_field, compute_tag_field_with_length,
+    compute_tag_limbs, compute_tag_verbose, compute_tag_with_hasher, compute_tags_batch,
+    tag_field_from_preimage, tag_from_preimage, tag_preimage, Sha256Hasher, Tag, TagBreakdown,
+    TagHasher, TagLength,
+};
+#[cfg(feature = "std")]
+pub use tag_cache::{compute_tag_cached, TagCache};
+pub use transcript::Transcript;
+
+pub use ark_ff::PrimeField;
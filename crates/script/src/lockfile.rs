@@ -0,0 +1,326 @@
+//! The lockfile (`safe.lock`) pinning the tags of the manifest protocols.
+//!
+//! A tag is consensus critical: once a circuit or a verifier contract relies on it, any change
+//! (of the pattern, the domain separator or the tag computation itself) breaks compatibility.
+//! `safe-api freeze` records the tag of every protocol, and `safe-api freeze --check` (and the
+//! test below for this repository) fails as long as the lockfile has not been regenerated.
+//!
+//! ```toml
+//! [[protocol]]
+//! name = "merkle_node"
+//! pattern = "A1 A1 S1"
+//! domain = "0x41424344"
+//! tag = "0x08e2da1eb5257f918e9c15b5605a3516"
+//! ```
+//!
+//! A protocol with another tag length than 128 bits also records its `tag_length`.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use safe_api::display;
+use safe_api::domain::DomainSeparator;
+use safe_api::tag::TagLength;
+
+use crate::manifest::Manifest;
+
+const HEADER: &str = "# Generated by `safe-api freeze`. Do not edit.\n\n";
+
+/// The tags of the manifest protocols.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Lockfile {
+    #[serde(rename = "protocol", default)]
+    pub protocols: Vec<LockedProtocol>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LockedProtocol {
+    pub name: String,
+    pub pattern: String,
+    pub domain: String,
+    /// Tag as 0x-prefixed hex, 32 digits for a 128-bit tag.
+    pub tag: String,
+    /// Bits of the tag, omitted for 128.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag_length: Option<usize>,
+}
+
+impl LockedProtocol {
+    /// Returns the bits of the tag.
+    pub fn tag_bits(&self) -> usize {
+        self.tag_length.unwrap_or(TagLength::default().bits())
+    }
+}
+
+impl Lockfile {
+    /// Computes the lockfile of a manifest.
+    pub fn from_manifest(manifest: &Manifest) -> Result<Self, String> {
+        let constants = manifest.tag_constants()?;
+        Ok(Lockfile {
+            protocols: manifest
+                .protocols
+                .iter()
+                .zip(constants)
+                .map(|(protocol, constant)| LockedProtocol {
+                    name: protocol.name.clone(),
+                    pattern: protocol.pattern.clone(),
+                    // Templated protocols pin the domain separator they render.
+                    domain: protocol.domain.clone().unwrap_or_else(|| {
+                        DomainSeparator::from(*constant.domain_separator()).to_string()
+                    }),
+                    tag: display::format_tag(constant.tag()),
+                    tag_length: (constant.tag_length() != TagLength::default())
+                        .then(|| constant.tag_length().bits()),
+                })
+                .collect(),
+        })
+    }
+
+    /// Reads and parses a lockfile.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|error| format!("cannot read {}: {}", path.display(), error))?;
+        Self::parse(&contents).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    /// Parses the contents of a lockfile.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        toml::from_str(contents).map_err(|error| error.to_string())
+    }
+
+    /// Renders the lockfile.
+    pub fn render(&self) -> String {
+        let body = toml::to_string(self).expect("lockfiles always serialize");
+        format!("{}{}", HEADER, body)
+    }
+
+    /// Compares this lockfile with a newer one, matching protocols by name.
+    pub fn diff<'a>(&'a self, new: &'a Lockfile) -> LockDiff<'a> {
+        let mut diff = LockDiff::default();
+        for protocol in &new.protocols {
+            match self.protocols.iter().find(|old| old.name == protocol.name) {
+                None => diff.added.push(protocol),
+                Some(old) if old != protocol => diff.changed.push((old, protocol)),
+                Some(_) => {}
+            }
+        }
+        for old in &self.protocols {
+            if !new
+                .protocols
+                .iter()
+                .any(|protocol| protocol.name == old.name)
+            {
+                diff.removed.push(old);
+            }
+        }
+        diff
+    }
+
+    /// Describes the protocols whose tag differs from `expected` (the freshly computed lockfile).
+    pub fn stale_entries(&self, expected: &Lockfile) -> Vec<String> {
+        let diff = self.diff(expected);
+        let added = diff
+            .added
+            .iter()
+            .map(|protocol| format!("`{}` is not locked", protocol.name));
+        let changed = diff.changed.iter().map(|(locked, protocol)| {
+            format!(
+                "`{}` changed: locked {} ({} under {}), now {} ({} under {})",
+                protocol.name,
+                locked.tag,
+                locked.pattern,
+                locked.domain,
+                protocol.tag,
+                protocol.pattern,
+                protocol.domain
+            )
+        });
+        let removed = diff
+            .removed
+            .iter()
+            .map(|locked| format!("`{}` is no longer in the manifest", locked.name));
+        added.chain(changed).chain(removed).collect()
+    }
+}
+
+/// The differences between two lockfiles (see [`Lockfile::diff`]).
+#[derive(Debug, Default)]
+pub struct LockDiff<'a> {
+    pub added: Vec<&'a LockedProtocol>,
+    pub removed: Vec<&'a LockedProtocol>,
+    /// Old and new entry of the protocols present in both lockfiles.
+    pub changed: Vec<(&'a LockedProtocol, &'a LockedProtocol)>,
+}
+
+impl LockDiff<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Renders the differences as Markdown, for release notes and governance proposals.
+    pub fn report(&self) -> String {
+        if self.is_empty() {
+            return "No protocol changes.\n".to_string();
+        }
+
+        let mut report = String::from("## Protocol changes\n");
+        if !self.added.is_empty() {
+            report.push_str("\n### Added\n\n");
+            for protocol in &self.added {
+                report.push_str(&format!(
+                    "- `{}`: `{}` under `{}`, tag `{}`\n",
+                    protocol.name, protocol.pattern, protocol.domain, protocol.tag
+                ));
+            }
+        }
+        if !self.removed.is_empty() {
+            report.push_str("\n### Removed\n\n");
+            for protocol in &self.removed {
+                report.push_str(&format!(
+                    "- `{}`: `{}` under `{}`, tag `{}`\n",
+                    protocol.name, protocol.pattern, protocol.domain, protocol.tag
+                ));
+            }
+        }
+        if !self.changed.is_empty() {
+            report.push_str("\n### Changed\n\n");
+            for (old, new) in &self.changed {
+                if old.tag == new.tag {
+                    report.push_str(&format!(
+                        "- `{}`: tag unchanged (`{}`)\n",
+                        new.name, new.tag
+                    ));
+                } else {
+                    report.push_str(&format!(
+                        "- `{}`: tag `{}` -> `{}`\n",
+                        new.name, old.tag, new.tag
+                    ));
+                }
+                if old.pattern != new.pattern {
+                    report.push_str(&format!(
+                        "  - pattern: `{}` -> `{}`\n",
+                        old.pattern, new.pattern
+                    ));
+                }
+                if old.domain != new.domain {
+                    report.push_str(&format!(
+                        "  - domain: `{}` -> `{}`\n",
+                        old.domain, new.domain
+                    ));
+                }
+                if old.tag_bits() != new.tag_bits() {
+                    report.push_str(&format!(
+                        "  - tag length: {} -> {} bits\n",
+                        old.tag_bits(),
+                        new.tag_bits()
+                    ));
+                }
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repository_lockfile_is_fresh() {
+        // Regenerate with `safe-api freeze --manifest ../../safe.toml --lockfile ../../safe.lock`
+        // when a protocol change is intended.
+        let manifest = Manifest::parse(include_str!("../../../safe.toml")).unwrap();
+        let locked = Lockfile::parse(include_str!("../../../safe.lock")).unwrap();
+        let stale = locked.stale_entries(&Lockfile::from_manifest(&manifest).unwrap());
+        assert!(
+            stale.is_empty(),
+            "safe.lock is stale:\n{}",
+            stale.join("\n")
+        );
+    }
+
+    #[test]
+    fn test_detects_changes() {
+        let manifest = Manifest::parse(
+            "[[protocol]]\nname = \"hash\"\npattern = \"A3 S1\"\ndomain = \"0x41424344\"\n",
+        )
+        .unwrap();
+        let lockfile = Lockfile::from_manifest(&manifest).unwrap();
+        assert_eq!(Lockfile::parse(&lockfile.render()).unwrap(), lockfile);
+        assert!(lockfile.stale_entries(&lockfile).is_empty());
+
+        let changed = Manifest::parse(
+            "[[protocol]]\nname = \"hash\"\npattern = \"A3 S2\"\ndomain = \"0x41424344\"\n",
+        )
+        .unwrap();
+        let stale = lockfile.stale_entries(&Lockfile::from_manifest(&changed).unwrap());
+        assert_eq!(stale.len(), 1);
+        assert!(stale[0].starts_with("`hash` changed"));
+    }
+
+    #[test]
+    fn test_tag_length() {
+        let manifest = |tag_length: &str| {
+            Manifest::parse(&format!(
+                "[[protocol]]\nname = \"bytes\"\npattern = \"A3 S1\"\ndomain = \"0x41424344\"\n{}",
+                tag_length
+            ))
+            .unwrap()
+        };
+        // As `freeze`, then `freeze --check`.
+        let expected = Lockfile::from_manifest(&manifest("tag_length = 256\n")).unwrap();
+        let rendered = expected.render();
+        assert!(rendered.contains("\ntag_length = 256\n"));
+        let locked = Lockfile::parse(&rendered).unwrap();
+        assert_eq!(locked, expected);
+        assert!(locked.stale_entries(&expected).is_empty());
+        assert_eq!(locked.protocols[0].tag.len(), 2 + 64);
+        assert!(locked.protocols[0]
+            .tag
+            .starts_with(&Lockfile::from_manifest(&manifest("")).unwrap().protocols[0].tag));
+
+        // The default length is not recorded, and changing the length makes the lock stale.
+        let truncated = Lockfile::from_manifest(&manifest("tag_length = 128\n")).unwrap();
+        assert!(!truncated.render().contains("tag_length"));
+        assert_eq!(truncated, Lockfile::from_manifest(&manifest("")).unwrap());
+        assert_eq!(locked.stale_entries(&truncated).len(), 1);
+        assert!(locked
+            .diff(&truncated)
+            .report()
+            .ends_with("  - tag length: 256 -> 128 bits\n"));
+    }
+
+    #[test]
+    fn test_release_report() {
+        let lock = |entries: &[(&str, &str)]| {
+            let manifest: String = entries
+                .iter()
+                .map(|(name, pattern)| {
+                    format!(
+                        "[[protocol]]\nname = \"{}\"\npattern = \"{}\"\ndomain = \"0x41424344\"\n",
+                        name, pattern
+                    )
+                })
+                .collect();
+            Lockfile::from_manifest(&Manifest::parse(&manifest).unwrap()).unwrap()
+        };
+        let old = lock(&[("kept", "A3 S1"), ("dropped", "A1 S1"), ("moved", "A3 S1")]);
+        let new = lock(&[("kept", "A3 S1"), ("moved", "A3 S2"), ("new", "A0 S1")]);
+
+        assert_eq!(old.diff(&old).report(), "No protocol changes.\n");
+        assert_eq!(
+            old.diff(&new).report(),
+            "## Protocol changes\n\
+             \n### Added\n\n\
+             - `new`: `A0 S1` under `0x41424344`, tag `0x854c618487e2b6f4df879698e178ab80`\n\
+             \n### Removed\n\n\
+             - `dropped`: `A1 S1` under `0x41424344`, tag `0x1ec158dc6b07c80cdd2a46c2d5142c0e`\n\
+             \n### Changed\n\n\
+             - `moved`: tag `0x0ea2aa7e178caa74de1f91e83ad43a81` -> `0x4b73794cad2d984072943f91b69f20a0`\n\
+             \x20 - pattern: `A3 S1` -> `A3 S2`\n"
+        );
+    }
+}
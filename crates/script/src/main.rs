@@ -1,220 +1,1164 @@
+mod attestation;
+mod audit;
+mod certify;
+mod changelog;
+mod difftest;
+mod lockfile;
+mod manifest;
+mod verify_tags;
+
+use std::path::{Path, PathBuf};
+
+use ark_bn254::Fr;
+use clap::{Args, Parser, Subcommand};
+use safe_api::domain::DomainSeparator;
+use safe_api::poseidon2::Poseidon2;
+use safe_api::{armor, codegen, display, recorder, vectors};
+use safe_api::{IoPattern, Operation, SafeError, Sponge};
 use sha2::{Digest, Sha256};
 
-/// SAFE tag computation constants (matching Noir implementation)
-const ABSORB_FLAG: u32 = 0x80000000;
-const SQUEEZE_FLAG: u32 = 0x00000000;
+/// Command line tools for SAFE IO patterns and tags.
+#[derive(Parser)]
+#[command(name = "safe-api", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
 
-/// Computes a unique tag for a sponge instance based on its IO pattern and domain separator.
-/// This matches the Noir implementation exactly.
-///
-/// # Arguments
-/// - `io_pattern`: Vector of 32-bit encoded operations defining the sponge's usage pattern.
-///               Each word has MSB=1 for ABSORB operations, MSB=0 for SQUEEZE operations.
-/// - `domain_separator`: 64-byte domain separator for cross-protocol security.
-///
-/// # Returns
-/// A u128 representing the 128-bit tag (equivalent to Field in Noir).
-pub fn compute_tag(io_pattern: &[u32], domain_separator: &[u8; 64]) -> u128 {
-    // Step 1: Parse and aggregate consecutive operations of the same type
-    let mut encoded_words = Vec::new();
-    let mut current_absorb_sum = 0;
-    let mut current_squeeze_sum = 0;
-    let mut last_was_absorb = false;
-
-    for &encoded_word in io_pattern {
-        if encoded_word > 0 {
-            // Parse operation type from MSB and length from lower 31 bits
-            let is_absorb = (encoded_word & ABSORB_FLAG) != 0;
-            let length = encoded_word & 0x7FFFFFFF; // Clear MSB to get length
-
-            if is_absorb {
-                if last_was_absorb {
-                    // Aggregate consecutive ABSORB operations
-                    current_absorb_sum += length;
-                } else {
-                    // Start new ABSORB sequence
-                    if current_squeeze_sum > 0 {
-                        // Flush previous SQUEEZE sequence
-                        encoded_words.push(SQUEEZE_FLAG | current_squeeze_sum);
-                        current_squeeze_sum = 0;
-                    }
-                    current_absorb_sum = length;
+#[derive(Subcommand)]
+enum Command {
+    /// Computes the tag of an IO pattern under a domain separator.
+    Tag(TagArgs),
+    /// Prints the aggregated form of an IO pattern used for tag computation.
+    Normalize(PatternArgs),
+    /// Prints every step of the tag computation: aggregation, serialized bytes and hash. Same
+    /// as `tag --verbose`.
+    Explain(PatternDomainArgs),
+    /// Replays a Poseidon2 sponge session from an armored envelope with its public inputs, or
+    /// from an IO pattern, a domain separator and the absorbed messages, printing each step.
+    Replay(ReplayArgs),
+    /// Armors the envelope (tag preimage) of an IO pattern, or decodes an armored envelope or
+    /// a bech32m tag.
+    #[command(subcommand)]
+    Armor(ArmorCommand),
+    /// Records a Poseidon2 sponge session as a JSON trace, or compares two traces.
+    #[command(subcommand)]
+    Trace(TraceCommand),
+    /// Exports or verifies JSON test vectors of the tag computation.
+    #[command(subcommand)]
+    Vectors(VectorsCommand),
+    /// Generates source code declaring the tags of the manifest protocols.
+    #[command(subcommand)]
+    Codegen(CodegenCommand),
+    /// Verifies the hash chain of a protocol changelog and prints its history.
+    Changelog {
+        /// Path to the changelog.
+        #[arg(default_value = "protocols-changelog.json")]
+        path: PathBuf,
+    },
+    /// Records the tags of the manifest protocols in a lockfile, or checks that it is fresh.
+    Freeze(FreezeArgs),
+    /// Reports the protocols added, removed or changed between two lockfiles, as Markdown.
+    ReleaseDiff {
+        /// Lockfile of the previous release.
+        old: PathBuf,
+        /// Lockfile of the new release.
+        new: PathBuf,
+    },
+    /// Reports structs that protocols or their Rust and Noir definitions absorb with different
+    /// field orders or encodings.
+    Audit {
+        /// Path to the protocol manifest.
+        #[arg(long, default_value = "safe.toml")]
+        manifest: PathBuf,
+        /// JSON layouts exported by the code defining the structs (see the `audit` module).
+        #[arg(long)]
+        metadata: Vec<PathBuf>,
+    },
+    /// Recomputes the annotated tag constants of a Noir project and reports stale ones.
+    VerifyTags {
+        /// Root of the Noir project, scanned recursively for `.nr` files.
+        #[arg(default_value = ".")]
+        project: PathBuf,
+    },
+    /// Compares `compute_tag` with the Noir circuit on random patterns (requires nargo).
+    Difftest(DifftestArgs),
+    /// Signs a statement that this generator produced the given artifacts from the manifest.
+    Attest(AttestArgs),
+    /// Runs the conformance suite against one backend and signs the report.
+    Certify {
+        /// Backend to certify, e.g. poseidon2-bn254.
+        #[arg(long)]
+        backend: String,
+        /// File holding the Ed25519 secret key, as hex.
+        #[arg(long)]
+        key: PathBuf,
+        /// Path of the signed report.
+        #[arg(long, default_value = "safe.certificate.json")]
+        output: PathBuf,
+    },
+    /// Checks the signature of an attestation and that the files it covers are unchanged.
+    VerifyAttestation {
+        /// Path to the attestation.
+        #[arg(default_value = "safe.attestation.json")]
+        attestation: PathBuf,
+        /// Trusted Ed25519 public key of the signer, as hex.
+        #[arg(long)]
+        public_key: String,
+        /// Path to the protocol manifest.
+        #[arg(long, default_value = "safe.toml")]
+        manifest: PathBuf,
+    },
+}
+
+#[derive(Args)]
+struct AttestArgs {
+    /// Generated files to attest, e.g. the output of `codegen`.
+    #[arg(required = true)]
+    artifacts: Vec<PathBuf>,
+    /// File holding the Ed25519 secret key, as hex.
+    #[arg(long)]
+    key: PathBuf,
+    /// Path to the protocol manifest.
+    #[arg(long, default_value = "safe.toml")]
+    manifest: PathBuf,
+    /// Output file for the attestation.
+    #[arg(long, default_value = "safe.attestation.json")]
+    output: PathBuf,
+}
+
+#[derive(Args)]
+struct FreezeArgs {
+    /// Path to the protocol manifest.
+    #[arg(long, default_value = "safe.toml")]
+    manifest: PathBuf,
+    /// Path to the lockfile.
+    #[arg(long, default_value = "safe.lock")]
+    lockfile: PathBuf,
+    /// Fails if the lockfile does not match the manifest, instead of rewriting it.
+    #[arg(long)]
+    check: bool,
+}
+
+#[derive(Args)]
+struct DifftestArgs {
+    /// Companion Noir project returning the tag of its inputs.
+    #[arg(long, default_value = "../difftest")]
+    program_dir: PathBuf,
+    /// Number of random cases.
+    #[arg(long, default_value_t = 100)]
+    cases: usize,
+    /// Seed of the case generator (random if omitted).
+    #[arg(long)]
+    seed: Option<u64>,
+    /// The nargo executable.
+    #[arg(long, default_value = "nargo")]
+    nargo: String,
+}
+
+#[derive(Subcommand)]
+enum CodegenCommand {
+    /// Emits a Noir file with the IO pattern and tag of each protocol as globals.
+    Noir {
+        /// Path to the protocol manifest.
+        #[arg(long, default_value = "safe.toml")]
+        manifest: PathBuf,
+        /// Output file for the Noir globals (stdout if omitted).
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Also writes the expected tags as `tag_<name>` inputs to this `Prover.toml`, prefixed
+        /// by the namespace of the manifest if any.
+        #[arg(long)]
+        prover_toml: Option<PathBuf>,
+        /// Appends the protocol changes since the last build to this changelog, e.g.
+        /// `protocols-changelog.json`.
+        #[arg(long)]
+        changelog: Option<PathBuf>,
+    },
+    /// Emits the tags of each protocol with a registered target, e.g. `noir` or `prover-toml`.
+    Build {
+        /// Name of the target.
+        #[arg(long)]
+        target: String,
+        /// Path to the protocol manifest.
+        #[arg(long, default_value = "safe.toml")]
+        manifest: PathBuf,
+        /// Output file (stdout if omitted).
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Appends the protocol changes since the last build to this changelog, e.g.
+        /// `protocols-changelog.json`.
+        #[arg(long)]
+        changelog: Option<PathBuf>,
+    },
+    /// Emits a Solidity library with the tag of each protocol and an on-chain `computeTag`.
+    Solidity {
+        /// Path to the protocol manifest.
+        #[arg(long, default_value = "safe.toml")]
+        manifest: PathBuf,
+        /// Output file for the Solidity library (stdout if omitted).
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Appends the protocol changes since the last build to this changelog, e.g.
+        /// `protocols-changelog.json`.
+        #[arg(long)]
+        changelog: Option<PathBuf>,
+    },
+    /// Emits a Noir module verifying chunks of a chunked commitment.
+    Chunked {
+        /// Chunk size in bytes.
+        #[arg(long)]
+        chunk_size: usize,
+        /// Depth of the Merkle tree over the chunks.
+        #[arg(long)]
+        depth: u32,
+        /// Output file for the Noir module (stdout if omitted).
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum VectorsCommand {
+    /// Prints the reference test vectors as JSON.
+    Export,
+    /// Verifies every vector of a JSON file against this implementation.
+    Verify {
+        /// Path to the JSON vector file.
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum ArmorCommand {
+    /// Prints the armored envelope of an IO pattern under a domain separator.
+    Envelope(EnvelopeArgs),
+    /// Decodes a bech32m tag or an armored envelope and prints its contents.
+    Decode {
+        /// A tag `stag1...`, an armored envelope, or the path to an armored envelope.
+        #[arg(allow_hyphen_values = true)]
+        input: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum TraceCommand {
+    /// Replays a session like `replay` and prints its trace, with the state after every
+    /// permutation.
+    Record(ReplayArgs),
+    /// Reports the first point at which two traces differ, e.g. a recorded Noir witness trace
+    /// and the one of `trace record`.
+    Diff {
+        /// The reference trace.
+        expected: PathBuf,
+        /// The trace to check.
+        actual: PathBuf,
+    },
+    /// Steps backward and forward through a recorded trace, showing the state changed by
+    /// each operation. Reads commands from stdin: `n [count]`, `b [count]`, `g <step>`,
+    /// `s` (whole state), `q`.
+    Debug {
+        /// The trace to step through.
+        trace: PathBuf,
+    },
+}
+
+#[derive(Args)]
+struct PatternArgs {
+    /// IO pattern, e.g. "A3 S1" (ABSORB(3), SQUEEZE(1)) or encoded words "0x80000003 0x00000001".
+    #[arg(long, value_parser = parse_pattern)]
+    pattern: IoPattern,
+}
+
+#[derive(Args)]
+struct PatternDomainArgs {
+    #[command(flatten)]
+    pattern: PatternArgs,
+    /// Domain separator as hex (up to 64 bytes, zero padded), e.g. 0x41424344.
+    #[arg(long, value_parser = parse_domain)]
+    domain: [u8; 64],
+}
+
+#[derive(Args)]
+struct TagArgs {
+    #[command(flatten)]
+    pattern: PatternDomainArgs,
+    /// Prints every step of the computation, not only the tag.
+    #[arg(long)]
+    verbose: bool,
+    /// Prints the tag as a checksummed bech32m string (`stag1...`) instead of hex.
+    #[arg(long, conflicts_with = "verbose")]
+    bech32: bool,
+    /// Protocol manifest to check the domain separator against: warns if it is the
+    /// byte-reversed domain separator of one of its protocols.
+    #[arg(long)]
+    manifest: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct EnvelopeArgs {
+    #[command(flatten)]
+    pattern: PatternDomainArgs,
+    /// Public inputs of the proof to record in the envelope, as `--inputs` of `replay`.
+    #[arg(long)]
+    inputs: Option<String>,
+}
+
+#[derive(Args)]
+struct ReplayArgs {
+    /// Armored envelope of the session, or the path to one: replays its IO pattern and domain
+    /// separator with its public inputs.
+    #[arg(
+        allow_hyphen_values = true,
+        conflicts_with_all = ["pattern", "domain"],
+        required_unless_present_all = ["pattern", "domain"]
+    )]
+    envelope: Option<String>,
+    /// IO pattern, e.g. "A3 S1" (ABSORB(3), SQUEEZE(1)) or encoded words "0x80000003 0x00000001".
+    #[arg(long, value_parser = parse_pattern, requires = "domain")]
+    pattern: Option<IoPattern>,
+    /// Domain separator as hex (up to 64 bytes, zero padded), e.g. 0x41424344.
+    #[arg(long, value_parser = parse_domain, requires = "pattern")]
+    domain: Option<[u8; 64]>,
+    /// Absorbed field elements (decimal or 0x hex), comma separated, one group per ABSORB
+    /// separated by `;`, e.g. "1,2,3;4". Overrides the public inputs of the envelope.
+    #[arg(long)]
+    inputs: Option<String>,
+}
+
+/// A session to replay.
+struct Session {
+    pattern: IoPattern,
+    domain_separator: [u8; 64],
+    /// The values of each ABSORB.
+    inputs: Vec<Vec<Fr>>,
+}
+
+impl ReplayArgs {
+    /// Returns the session of the envelope, or of `--pattern` and `--domain`, with the
+    /// `--inputs` if given and otherwise the public inputs of the envelope.
+    fn session(&self) -> Result<Session, String> {
+        let (pattern, domain_separator, public_inputs) = match &self.envelope {
+            Some(input) => {
+                let (envelope, headers) = read_envelope(input)?;
+                let (words, domain_separator) = split_envelope(&envelope);
+                let public_inputs = headers
+                    .into_iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(armor::PUBLIC_INPUTS_HEADER))
+                    .map(|(_, value)| value);
+                (
+                    IoPattern::from_words(&words),
+                    domain_separator,
+                    public_inputs,
+                )
+            }
+            None => (
+                self.pattern.clone().expect("required without an envelope"),
+                self.domain.expect("required without an envelope"),
+                None,
+            ),
+        };
+        let inputs = self
+            .inputs
+            .as_deref()
+            .or(public_inputs.as_deref())
+            .unwrap_or("");
+        Ok(Session {
+            inputs: absorbed_inputs(&pattern, &domain_separator, inputs)?,
+            pattern,
+            domain_separator,
+        })
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    // The tag is defined for any pattern, but one that breaks the spec likely has a typo.
+    if let Command::Tag(args) = &cli.command {
+        if let Err(error) = args.pattern.pattern.pattern.validate() {
+            eprintln!("warning: {}", error);
+        }
+        if let Some(path) = &args.manifest {
+            match reversed_protocol(path, &args.pattern.domain) {
+                Ok(Some(warning)) => eprintln!("warning: {}", warning),
+                Ok(None) => {}
+                Err(error) => {
+                    eprintln!("error: {}", error);
+                    std::process::exit(1);
                 }
-                last_was_absorb = true;
-            } else {
-                if !last_was_absorb {
-                    // Aggregate consecutive SQUEEZE operations
-                    current_squeeze_sum += length;
-                } else {
-                    // Start new SQUEEZE sequence
-                    if current_absorb_sum > 0 {
-                        // Flush previous ABSORB sequence
-                        encoded_words.push(ABSORB_FLAG | current_absorb_sum);
-                        current_absorb_sum = 0;
-                    }
-                    current_squeeze_sum = length;
+            }
+        }
+    }
+
+    match cli.command {
+        Command::Tag(args) if args.verbose => {
+            explain(&args.pattern.pattern.pattern, &args.pattern.domain)
+        }
+        Command::Tag(args) => {
+            let tag = args.pattern.pattern.pattern.tag(args.pattern.domain);
+            match args.bech32 {
+                true => println!("{}", armor::encode_tag(tag)),
+                false => println!("{}", display::format_tag(tag)),
+            }
+        }
+        Command::Normalize(args) => {
+            let normalized = args.pattern.normalized();
+            println!("{}", normalized);
+            println!("{}", format_words(&normalized.encoded_words()));
+        }
+        Command::Explain(args) => explain(&args.pattern.pattern, &args.domain),
+        Command::Replay(args) => {
+            if let Err(error) = replay(&args) {
+                eprintln!("error: {}", error);
+                std::process::exit(1);
+            }
+        }
+        Command::Armor(ArmorCommand::Envelope(args)) => match armor_envelope(&args) {
+            Ok(armored) => print!("{}", armored),
+            Err(error) => {
+                eprintln!("error: {}", error);
+                std::process::exit(1);
+            }
+        },
+        Command::Armor(ArmorCommand::Decode { input }) => {
+            if let Err(error) = decode_armor(&input) {
+                eprintln!("error: {}", error);
+                std::process::exit(1);
+            }
+        }
+        Command::Trace(command) => {
+            if let Err(error) = trace(&command) {
+                eprintln!("error: {}", error);
+                std::process::exit(1);
+            }
+        }
+        Command::Vectors(VectorsCommand::Export) => {
+            println!("{}", vectors::to_json(&vectors::reference_vectors()))
+        }
+        Command::Codegen(CodegenCommand::Noir {
+            manifest,
+            output,
+            prover_toml,
+            changelog,
+        }) => {
+            let result = codegen_noir(&manifest, output.as_deref(), prover_toml.as_deref())
+                .and_then(|()| record_changelog(changelog.as_deref(), &manifest));
+            if let Err(error) = result {
+                eprintln!("error: {}", error);
+                std::process::exit(1);
+            }
+        }
+        Command::Codegen(CodegenCommand::Build {
+            target,
+            manifest,
+            output,
+            changelog,
+        }) => {
+            let result = codegen_build(&target, &manifest, output.as_deref())
+                .and_then(|()| record_changelog(changelog.as_deref(), &manifest));
+            if let Err(error) = result {
+                eprintln!("error: {}", error);
+                std::process::exit(1);
+            }
+        }
+        Command::Codegen(CodegenCommand::Solidity {
+            manifest,
+            output,
+            changelog,
+        }) => {
+            let result = codegen_build("solidity", &manifest, output.as_deref())
+                .and_then(|()| record_changelog(changelog.as_deref(), &manifest));
+            if let Err(error) = result {
+                eprintln!("error: {}", error);
+                std::process::exit(1);
+            }
+        }
+        Command::Codegen(CodegenCommand::Chunked {
+            chunk_size,
+            depth,
+            output,
+        }) => {
+            if chunk_size == 0 {
+                eprintln!("error: the chunk size must be positive");
+                std::process::exit(1);
+            }
+            let source = codegen::noir_chunk_verifier(chunk_size, depth);
+            let result = match output {
+                Some(path) => write_file(&path, &source),
+                None => {
+                    print!("{}", source);
+                    Ok(())
+                }
+            };
+            if let Err(error) = result {
+                eprintln!("error: {}", error);
+                std::process::exit(1);
+            }
+        }
+        Command::Changelog { path } => {
+            if let Err(error) = show_changelog(&path) {
+                eprintln!("error: {}", error);
+                std::process::exit(1);
+            }
+        }
+        Command::Freeze(args) => {
+            if let Err(error) = freeze(&args) {
+                eprintln!("error: {}", error);
+                std::process::exit(1);
+            }
+        }
+        Command::ReleaseDiff { old, new } => {
+            let result = lockfile::Lockfile::load(&old).and_then(|old| {
+                let new = lockfile::Lockfile::load(&new)?;
+                Ok(old.diff(&new).report())
+            });
+            match result {
+                Ok(report) => print!("{}", report),
+                Err(error) => {
+                    eprintln!("error: {}", error);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::Audit { manifest, metadata } => {
+            if let Err(error) = run_audit(&manifest, &metadata) {
+                eprintln!("error: {}", error);
+                std::process::exit(1);
+            }
+        }
+        Command::VerifyTags { project } => {
+            if let Err(error) = run_verify_tags(&project) {
+                eprintln!("error: {}", error);
+                std::process::exit(1);
+            }
+        }
+        Command::Difftest(args) => {
+            let seed = args.seed.unwrap_or_else(rand::random);
+            println!("Seed: {}", seed);
+            match difftest::run(&args.nargo, &args.program_dir, args.cases, seed) {
+                Ok(cases) => println!("{} cases agree", cases),
+                Err(error) => {
+                    eprintln!("error: {}", error);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::Attest(args) => {
+            if let Err(error) = attest(&args) {
+                eprintln!("error: {}", error);
+                std::process::exit(1);
+            }
+        }
+        Command::Certify {
+            backend,
+            key,
+            output,
+        } => {
+            if let Err(error) = run_certify(&backend, &key, &output) {
+                eprintln!("error: {}", error);
+                std::process::exit(1);
+            }
+        }
+        Command::VerifyAttestation {
+            attestation,
+            public_key,
+            manifest,
+        } => {
+            if let Err(error) = verify_attestation(&attestation, &public_key, &manifest) {
+                eprintln!("error: {}", error);
+                std::process::exit(1);
+            }
+        }
+        Command::Vectors(VectorsCommand::Verify { file }) => {
+            let result = std::fs::read_to_string(&file)
+                .map_err(|error| format!("cannot read {}: {}", file.display(), error))
+                .and_then(|json| vectors::verify_json(&json).map_err(|error| error.to_string()));
+            match result {
+                Ok(vectors) => println!("{} vectors verified", vectors.len()),
+                Err(error) => {
+                    eprintln!("error: {}", error);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+}
+
+/// Generates the Noir globals (and optionally the `Prover.toml` inputs) of a manifest.
+fn codegen_noir(
+    manifest: &Path,
+    output: Option<&Path>,
+    prover_toml: Option<&Path>,
+) -> Result<(), String> {
+    codegen_build("noir", manifest, output)?;
+    if let Some(path) = prover_toml {
+        codegen_build("prover-toml", manifest, Some(path))?;
+    }
+    Ok(())
+}
+
+/// Generates the output of the target `target` for a manifest.
+fn codegen_build(target: &str, manifest: &Path, output: Option<&Path>) -> Result<(), String> {
+    let targets = codegen::Targets::builtin();
+    if targets.get(target).is_none() {
+        return Err(format!(
+            "unknown target `{}` (expected one of {})",
+            target,
+            targets.names().join(", ")
+        ));
+    }
+    let manifest = manifest::Manifest::load(manifest)?;
+    let (namespace, constants) = (manifest.namespace()?, manifest.tag_constants()?);
+    let source = targets
+        .emit(target, &namespace, &constants)
+        .map_err(|error| error.to_string())?;
+    match output {
+        Some(path) => write_file(path, &source),
+        None => {
+            print!("{}", source);
+            Ok(())
+        }
+    }
+}
+
+/// Appends the changes of the manifest protocols to the changelog, if one is given.
+fn record_changelog(path: Option<&Path>, manifest_path: &Path) -> Result<(), String> {
+    let Some(path) = path else {
+        return Ok(());
+    };
+    let contents = std::fs::read(manifest_path)
+        .map_err(|error| format!("cannot read {}: {}", manifest_path.display(), error))?;
+    let manifest = manifest::Manifest::load(manifest_path)?;
+    let current = lockfile::Lockfile::from_manifest(&manifest)?;
+
+    let mut changelog = changelog::Changelog::load(path)?;
+    changelog.verify()?;
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    if changelog.record(&current, &Sha256::digest(&contents).into(), timestamp) {
+        write_file(path, &changelog.render())?;
+    }
+    Ok(())
+}
+
+/// Verifies a changelog and prints one line per change.
+fn show_changelog(path: &Path) -> Result<(), String> {
+    let changelog = changelog::Changelog::load(path)?;
+    changelog
+        .verify()
+        .map_err(|error| format!("{}: {}", path.display(), error))?;
+    for (index, entry) in changelog.entries.iter().enumerate() {
+        println!(
+            "[{}] {} (manifest {})",
+            index, entry.timestamp, entry.manifest_hash
+        );
+        for change in &entry.changes {
+            let tag = |protocol: &Option<lockfile::LockedProtocol>| {
+                protocol
+                    .as_ref()
+                    .map_or("-".to_string(), |protocol| protocol.tag.clone())
+            };
+            let name = change
+                .new
+                .as_ref()
+                .or(change.old.as_ref())
+                .map_or("", |p| &p.name);
+            println!(
+                "    {:?} `{}`: {} -> {}",
+                change.change,
+                name,
+                tag(&change.old),
+                tag(&change.new)
+            );
+        }
+    }
+    println!("hash chain intact ({} entries)", changelog.entries.len());
+    Ok(())
+}
+
+/// Writes the lockfile of the manifest, or checks the existing one.
+fn freeze(args: &FreezeArgs) -> Result<(), String> {
+    let manifest = manifest::Manifest::load(&args.manifest)?;
+    let expected = lockfile::Lockfile::from_manifest(&manifest)?;
+    if !args.check {
+        write_file(&args.lockfile, &expected.render())?;
+        println!("{} protocols locked", expected.protocols.len());
+        return Ok(());
+    }
+
+    let stale = lockfile::Lockfile::load(&args.lockfile)?.stale_entries(&expected);
+    if !stale.is_empty() {
+        return Err(format!(
+            "{} is stale, run `safe-api freeze` if the change is intended:\n{}",
+            args.lockfile.display(),
+            stale.join("\n")
+        ));
+    }
+    println!(
+        "{} protocols match {}",
+        expected.protocols.len(),
+        args.lockfile.display()
+    );
+    Ok(())
+}
+
+/// Audits the struct layouts of the manifest and metadata files.
+fn run_audit(manifest: &Path, metadata: &[PathBuf]) -> Result<(), String> {
+    let mut layouts = audit::manifest_layouts(&manifest::Manifest::load(manifest)?)?;
+    for path in metadata {
+        layouts.extend(audit::load_metadata(path)?);
+    }
+    let findings = audit::audit(&layouts);
+    if !findings.is_empty() {
+        return Err(format!(
+            "{} inconsistent layouts:\n{}",
+            findings.len(),
+            findings.join("\n")
+        ));
+    }
+    println!("{} layouts consistent", layouts.len());
+    Ok(())
+}
+
+/// Verifies the tag constants of the Noir project at `project`.
+fn run_verify_tags(project: &Path) -> Result<(), String> {
+    let report = verify_tags::verify_project(project)?;
+    if !report.findings.is_empty() {
+        return Err(format!(
+            "{} problems in {} tag constants:\n{}",
+            report.findings.len(),
+            report.checked,
+            report.findings.join("\n")
+        ));
+    }
+    if report.checked == 0 {
+        return Err(format!(
+            "no annotated tag constants found under {}",
+            project.display()
+        ));
+    }
+    println!("{} tag constants up to date", report.checked);
+    Ok(())
+}
+
+/// Certifies `backend`, writing the signed report even if a check fails.
+fn run_certify(backend: &str, key: &Path, output: &Path) -> Result<(), String> {
+    let key = attestation::load_signing_key(key)?;
+    let report = certify::Report::run(backend)?;
+    for check in &report.checks {
+        match &check.failure {
+            None => println!("pass  {}", check.name),
+            Some(failure) => println!("FAIL  {}: {}", check.name, failure),
+        }
+    }
+    let passed = report.passed;
+    write_file(output, &certify::Certificate::sign(report, &key).render())?;
+    if !passed {
+        return Err(format!("{} does not conform", backend));
+    }
+    println!("{} certified", backend);
+    Ok(())
+}
+
+/// Signs the attestation of the manifest and artifacts.
+fn attest(args: &AttestArgs) -> Result<(), String> {
+    let key = attestation::load_signing_key(&args.key)?;
+    let statement = attestation::Statement::new(&args.manifest, &args.artifacts)?;
+    let attestation = attestation::Attestation::sign(statement, &key);
+    write_file(&args.output, &attestation.render())?;
+    println!(
+        "{} artifacts attested, signed by {}",
+        args.artifacts.len(),
+        attestation.public_key
+    );
+    Ok(())
+}
+
+/// Verifies an attestation against the trusted key and the files on disk.
+fn verify_attestation(path: &Path, public_key: &str, manifest: &Path) -> Result<(), String> {
+    let attestation = attestation::Attestation::load(path)?;
+    attestation
+        .verify(&attestation::parse_public_key(public_key)?)
+        .map_err(|error| format!("{}: {}", path.display(), error))?;
+    attestation.check_files(manifest)?;
+    let statement = &attestation.statement;
+    println!(
+        "{} artifacts generated by {} from manifest {}",
+        statement.artifacts.len(),
+        statement.generator,
+        statement.manifest_hash
+    );
+    if statement.generator != attestation::GENERATOR {
+        eprintln!(
+            "warning: attested by {}, this is {}",
+            statement.generator,
+            attestation::GENERATOR
+        );
+    }
+    Ok(())
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<(), String> {
+    std::fs::write(path, contents)
+        .map_err(|error| format!("cannot write {}: {}", path.display(), error))
+}
+
+/// Replays a sponge session as the verifier sees it: every absorb with its values, then every
+/// squeeze with the derived outputs.
+fn replay(args: &ReplayArgs) -> Result<(), String> {
+    let Session {
+        pattern,
+        domain_separator,
+        inputs,
+    } = args.session()?;
+    let mut inputs = inputs.into_iter();
+
+    let mut sponge = Sponge::<Poseidon2>::start(pattern.clone(), domain_separator);
+    println!("Tag: {}", display::format_element(&sponge.tag()));
+
+    for (index, &operation) in pattern.operations().iter().enumerate() {
+        match operation {
+            Operation::Absorb(length) => {
+                let values = inputs.next().expect("one input per ABSORB");
+                sponge.absorb(&values);
+                println!("[{}] ABSORB({})", index, length);
+                for value in &values {
+                    println!("      {}", display::format_element(value));
+                }
+            }
+            Operation::Squeeze(length) => {
+                let output = sponge.squeeze();
+                match pattern.label(index) {
+                    Some(label) => println!("[{}] SQUEEZE({}) {}", index, length, label),
+                    None => println!("[{}] SQUEEZE({})", index, length),
+                }
+                for value in &output {
+                    println!("   -> {}", display::format_element(value));
                 }
-                last_was_absorb = false;
             }
         }
     }
+    sponge.finish();
+    Ok(())
+}
 
-    // Flush remaining operations
-    if current_absorb_sum > 0 {
-        encoded_words.push(ABSORB_FLAG | current_absorb_sum);
+/// Parses the inputs of a replayed session into the values of each ABSORB of `pattern`,
+/// checking their lengths and that the domain separator is not empty.
+fn absorbed_inputs(
+    pattern: &IoPattern,
+    domain_separator: &[u8; 64],
+    inputs: &str,
+) -> Result<Vec<Vec<Fr>>, String> {
+    if safe_api::domain::is_empty_domain(domain_separator) {
+        return Err("the domain separator is empty".to_string());
     }
-    if current_squeeze_sum > 0 {
-        encoded_words.push(SQUEEZE_FLAG | current_squeeze_sum);
+    let mut groups = inputs
+        .split(';')
+        .filter(|group| !group.trim().is_empty())
+        .map(parse_field_elements);
+
+    let mut inputs = Vec::new();
+    for (index, &operation) in pattern.operations().iter().enumerate() {
+        let Operation::Absorb(length) = operation else {
+            continue;
+        };
+        let values = match length {
+            0 => Vec::new(),
+            _ => groups
+                .next()
+                .ok_or_else(|| format!("missing input group for operation {}", index))??,
+        };
+        if values.len() != length as usize {
+            return Err(format!(
+                "operation {} absorbs {} elements, got {}",
+                index,
+                length,
+                values.len()
+            ));
+        }
+        inputs.push(values);
     }
+    if groups.next().is_some() {
+        return Err("more input groups than ABSORB operations".to_string());
+    }
+    Ok(inputs)
+}
 
-    // Step 3: Serialize to byte string and append domain separator (following SAFE spec 2.3).
-    let mut input_bytes = Vec::new();
+/// Armors the envelope of `args`, with its public inputs if any.
+fn armor_envelope(args: &EnvelopeArgs) -> Result<String, String> {
+    let pattern = &args.pattern.pattern.pattern;
+    let envelope = pattern.tag_preimage(args.pattern.domain);
+    let Some(inputs) = &args.inputs else {
+        return Ok(armor::armor_envelope(&envelope));
+    };
+    let groups: Vec<String> = absorbed_inputs(pattern, &args.pattern.domain, inputs)?
+        .iter()
+        .map(|values| {
+            let values: Vec<String> = values.iter().map(display::format_element).collect();
+            values.join(",")
+        })
+        .collect();
+    Ok(armor::armor_envelope_with_headers(
+        &envelope,
+        &[(armor::PUBLIC_INPUTS_HEADER, &groups.join(";"))],
+    ))
+}
 
-    // Serialize encoded words to bytes (big-endian as per SAFE spec).
-    for &word in &encoded_words {
-        input_bytes.extend_from_slice(&word.to_be_bytes());
+/// Decodes an armored envelope, or the armored envelope of the file at `input`, with its
+/// headers.
+fn read_envelope(input: &str) -> Result<(Vec<u8>, armor::Headers), String> {
+    if input.trim_start().starts_with(armor::ENVELOPE_HEADER) {
+        return armor::dearmor_envelope_with_headers(input)
+            .map_err(|error| format!("invalid envelope: {}", error));
     }
+    let armored = std::fs::read_to_string(input)
+        .map_err(|error| format!("cannot read {}: {}", input, error))?;
+    armor::dearmor_envelope_with_headers(&armored).map_err(|error| format!("{}: {}", input, error))
+}
 
-    // Append domain separator.
-    input_bytes.extend_from_slice(domain_separator);
+/// Splits a decoded envelope into the encoded words of its IO pattern and its domain
+/// separator.
+fn split_envelope(envelope: &[u8]) -> (Vec<u32>, [u8; 64]) {
+    let (words, domain_separator) = envelope.split_at(envelope.len() - 64);
+    let words = words
+        .chunks(4)
+        .map(|word| u32::from_be_bytes(word.try_into().expect("whole words")))
+        .collect();
+    (words, domain_separator.try_into().expect("64 bytes"))
+}
 
-    // Step 4: Hash with SHA256 and truncate to 128 bits (following SAFE spec 2.3).
-    let mut hasher = Sha256::new();
-    hasher.update(&input_bytes);
-    let hash_bytes = hasher.finalize();
+/// Prints a bech32m tag as hex, or the IO pattern, domain separator, tag and headers of an
+/// armored envelope.
+fn decode_armor(input: &str) -> Result<(), String> {
+    if input.to_ascii_lowercase().starts_with(armor::TAG_HRP) {
+        let tag = armor::decode_tag(input).map_err(|error| format!("{}: {}", input, error))?;
+        println!("{}", display::format_tag(tag));
+        return Ok(());
+    }
+    let (envelope, headers) = read_envelope(input)?;
+    let (words, domain_separator) = split_envelope(&envelope);
+    println!("Pattern:          {}", format_words(&words));
+    println!(
+        "Domain separator: {}",
+        display::format_bytes(&domain_separator)
+    );
+    println!(
+        "Tag:              {}",
+        display::format_tag(safe_api::tag_from_preimage(&envelope))
+    );
+    for (name, value) in headers {
+        println!("{:<18}{}", format!("{}:", name), value);
+    }
+    Ok(())
+}
 
-    // Convert first 128 bits (16 bytes) to u128 (equivalent to Field in Noir).
-    let mut tag_value: u128 = 0;
-    for i in 0..16 {
-        tag_value = tag_value * 256 + (hash_bytes[i] as u128);
+/// Records a session with the inputs of `replay`, or compares two recorded traces.
+fn trace(command: &TraceCommand) -> Result<(), String> {
+    match command {
+        TraceCommand::Record(args) => {
+            let Session {
+                pattern,
+                domain_separator,
+                inputs,
+            } = args.session()?;
+            let mut inputs = inputs.into_iter();
+            let mut recorder =
+                recorder::Recorder::<Poseidon2>::start(pattern.clone(), &domain_separator);
+            for operation in pattern.operations() {
+                match operation {
+                    Operation::Absorb(_) => {
+                        recorder.absorb(&inputs.next().expect("one input per ABSORB"));
+                    }
+                    Operation::Squeeze(_) => {
+                        recorder.squeeze();
+                    }
+                }
+            }
+            println!("{}", recorder.finish().to_json());
+            Ok(())
+        }
+        TraceCommand::Diff { expected, actual } => {
+            match recorder::diff(&load_trace(expected)?, &load_trace(actual)?) {
+                Some(divergence) => Err(divergence.to_string()),
+                None => {
+                    println!("traces match");
+                    Ok(())
+                }
+            }
+        }
+        TraceCommand::Debug { trace } => debug_trace(&load_trace(trace)?),
     }
+}
 
-    tag_value
+fn load_trace(path: &Path) -> Result<recorder::Trace, String> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|error| format!("cannot read {}: {}", path.display(), error))?;
+    recorder::Trace::from_json(&json).map_err(|error| format!("{}: {}", path.display(), error))
 }
 
-/// Helper function to convert hex string to bytes
-fn hex_to_bytes(hex: &str) -> [u8; 64] {
-    let mut bytes = [0u8; 64];
-    let hex_clean = hex.replace("0x", "");
-    for (i, chunk) in hex_clean.as_bytes().chunks(2).enumerate() {
-        if i < 64 {
-            let byte_str = std::str::from_utf8(chunk).unwrap();
-            bytes[i] = u8::from_str_radix(byte_str, 16).unwrap();
+/// Runs the commands of stdin on a cursor over `trace`, printing each step reached.
+fn debug_trace(trace: &recorder::Trace) -> Result<(), String> {
+    use std::io::BufRead;
+
+    let mut cursor = recorder::TraceCursor::new(trace);
+    print_step(&cursor);
+    for line in std::io::stdin().lock().lines() {
+        let line = line.map_err(|error| format!("cannot read stdin: {}", error))?;
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            continue;
+        };
+        let argument = match words.next().map(str::parse::<usize>) {
+            Some(Ok(argument)) => Some(argument),
+            Some(Err(_)) => {
+                println!("expected a number");
+                continue;
+            }
+            None => None,
+        };
+        let moved = match command {
+            "n" | "next" => (0..argument.unwrap_or(1)).all(|_| cursor.forward()),
+            "b" | "back" => (0..argument.unwrap_or(1)).all(|_| cursor.back()),
+            "g" | "goto" => match argument {
+                Some(step) => cursor.seek(step),
+                None => {
+                    println!("usage: g <step>");
+                    continue;
+                }
+            },
+            "s" | "state" => {
+                match cursor.state() {
+                    Some(state) => {
+                        for (index, element) in state.iter().enumerate() {
+                            println!("  state[{}] = {}", index, element);
+                        }
+                    }
+                    None => println!("  state not recorded"),
+                }
+                continue;
+            }
+            "q" | "quit" => break,
+            _ => {
+                println!("commands: n [count], b [count], g <step>, s (state), q (quit)");
+                continue;
+            }
+        };
+        if !moved {
+            println!("(trace bounds reached)");
         }
+        print_step(&cursor);
     }
-    bytes
+    Ok(())
 }
 
-fn main() {
-    println!("SAFE Tag Computation Test (Rust)\n");
-
-    // Test cases matching the Noir implementation examples
-
-    // Test 1: Basic hashing pattern [3, 1] (ABSORB(3), SQUEEZE(1))
-    let io_pattern1 = vec![0x80000003, 0x00000001];
-    let domain_separator1 =
-        hex_to_bytes("414243440000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000");
-    let tag1 = compute_tag(&io_pattern1, &domain_separator1);
-    println!("Test 1: Pattern [0x80000003, 0x00000001] (ABSORB(3), SQUEEZE(1))");
-    println!("Domain separator: 0x41424344...");
-    println!("Tag: 0x{:032x}", tag1);
-    println!();
-
-    // Test 2: Merkle tree pattern [1, 1, 1] (ABSORB(1), ABSORB(1), SQUEEZE(1))
-    let io_pattern2 = vec![0x80000001, 0x80000001, 0x00000001];
-    let domain_separator2 =
-        hex_to_bytes("414243440000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000");
-    let tag2 = compute_tag(&io_pattern2, &domain_separator2);
+/// Prints the operation run to reach the current step of `cursor` and the state it changed.
+fn print_step(cursor: &recorder::TraceCursor) {
+    let step = format!("step {}/{}", cursor.step(), cursor.last_step());
+    let Some(event) = cursor.event() else {
+        println!("{}: initial state", step);
+        return;
+    };
+    println!(
+        "{}: {:?} of operation {}, {} elements, {} permutations",
+        step,
+        event.kind,
+        event.operation,
+        event.values.len(),
+        event.permutations.len()
+    );
+    for (index, value) in event.values.iter().enumerate() {
+        println!("  value[{}] = {}", index, value);
+    }
+    for change in cursor.changes() {
+        println!(
+            "  state[{}]: {} -> {}",
+            change.index, change.before, change.after
+        );
+    }
+}
+
+/// Prints the intermediate values of the tag computation (following SAFE spec 2.3).
+fn explain(pattern: &IoPattern, domain_separator: &[u8; 64]) {
+    let breakdown = pattern.tag_breakdown(domain_separator);
+    let aggregated = IoPattern::from_words(&breakdown.aggregated_words);
+
+    println!("Pattern:          {}", pattern);
+    println!("Encoded words:    {}", format_words(&breakdown.words));
+    println!("Aggregated:       {}", aggregated);
+    println!(
+        "Aggregated words: {}",
+        format_words(&breakdown.aggregated_words)
+    );
     println!(
-        "Test 2: Pattern [0x80000001, 0x80000001, 0x00000001] (ABSORB(1), ABSORB(1), SQUEEZE(1))"
+        "Domain separator: {}",
+        display::format_bytes(domain_separator)
     );
-    println!("Domain separator: 0x41424344...");
-    println!("Tag: 0x{:032x}", tag2);
-    println!();
-
-    // Test 3: Commitment pattern [3, 1] (ABSORB(3), SQUEEZE(1))
-    let io_pattern3 = vec![0x80000003, 0x00000001];
-    let domain_separator3 =
-        hex_to_bytes("4142434400000000000000000000000000000000000000000000000000000000");
-    let tag3 = compute_tag(&io_pattern3, &domain_separator3);
-    println!("Test 3: Pattern [0x80000003, 0x00000001] (ABSORB(3), SQUEEZE(1)) - Commitment");
-    println!("Domain separator: 0x41424344...");
-    println!("Tag: 0x{:032x}", tag3);
-    println!();
-
-    // Test 4: Multiple squeeze pattern [3, 2] (ABSORB(3), SQUEEZE(2))
-    let io_pattern4 = vec![0x80000003, 0x00000002];
-    let domain_separator4 =
-        hex_to_bytes("4142434400000000000000000000000000000000000000000000000000000000");
-    let tag4 = compute_tag(&io_pattern4, &domain_separator4);
-    println!("Test 4: Pattern [0x80000003, 0x00000002] (ABSORB(3), SQUEEZE(2))");
-    println!("Domain separator: 0x41424344...");
-    println!("Tag: 0x{:032x}", tag4);
-    println!();
-
-    // Test 5: Zero length pattern [0, 1] (ABSORB(0), SQUEEZE(1))
-    let io_pattern5 = vec![0x80000000, 0x00000001];
-    let domain_separator5 =
-        hex_to_bytes("4142434400000000000000000000000000000000000000000000000000000000");
-    let tag5 = compute_tag(&io_pattern5, &domain_separator5);
-    println!("Test 5: Pattern [0x80000000, 0x00000001] (ABSORB(0), SQUEEZE(1))");
-    println!("Domain separator: 0x41424344...");
-    println!("Tag: 0x{:032x}", tag5);
-    println!();
-
-    // Test 6: Different domain separators (should produce different tags)
-    let io_pattern6 = vec![0x80000003, 0x00000001]; // ABSORB(3), SQUEEZE(1)
-    let domain_separator6a =
-        hex_to_bytes("4142434400000000000000000000000000000000000000000000000000000000");
-    let domain_separator6b =
-        hex_to_bytes("4243444500000000000000000000000000000000000000000000000000000000");
-    let tag6a = compute_tag(&io_pattern6, &domain_separator6a);
-    let tag6b = compute_tag(&io_pattern6, &domain_separator6b);
-    println!("Test 6: Different domain separators");
     println!(
-        "Pattern [0x80000003, 0x00000001] with domain 0x41424344... -> Tag: 0x{:032x}",
-        tag6a
+        "Preimage:         {} ({} bytes)",
+        display::format_bytes(&breakdown.preimage),
+        breakdown.preimage.len()
     );
     println!(
-        "Pattern [0x80000003, 0x00000001] with domain 0x42434445... -> Tag: 0x{:032x}",
-        tag6b
+        "SHA-256:          {}",
+        display::format_bytes(&breakdown.digest)
     );
-    println!("Tags are different: {}", tag6a != tag6b);
-    println!();
-
-    // Test 7: Aggregation example from SAFE spec [3, 3, 3] -> [6, 3]
-    let io_pattern7 = vec![0x80000003, 0x80000003, 0x00000003];
-    let domain_separator7 =
-        hex_to_bytes("414200000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000");
-    let tag7 = compute_tag(&io_pattern7, &domain_separator7);
-    println!("Test 7: Aggregation pattern [0x80000003, 0x80000003, 0x00000003] (ABSORB(3), ABSORB(3), SQUEEZE(3))");
-    println!("Should aggregate to: ABSORB(6), SQUEEZE(3)");
-    println!("Domain separator: 0x4142...");
-    println!("Tag: 0x{:032x}", tag7);
-    println!();
-
-    // Test 8: Your specific pattern [2, 2, 2] (ABSORB(2), SQUEEZE(2), ABSORB(2))
-    let io_pattern8 = vec![0x80000002, 0x00000002, 0x80000002];
-    let domain_separator8 =
-        hex_to_bytes("4142434400000000000000000000000000000000000000000000000000000000");
-    let tag8 = compute_tag(&io_pattern8, &domain_separator8);
-    println!("Test 8: Your pattern [0x80000002, 0x00000002, 0x80000002] (ABSORB(2), SQUEEZE(2), ABSORB(2))");
-    println!("Domain separator: 0x41424344...");
-    println!("Tag: 0x{:032x}", tag8);
-    println!();
-
-    // Test 9: Aggregation demonstration - your example
-    let io_pattern9a = vec![0x80000001, 0x80000001, 0x00000001];
-    let io_pattern9b = vec![0x80000002, 0x00000001];
-    let domain_separator9 =
-        hex_to_bytes("4142434400000000000000000000000000000000000000000000000000000000");
-    let tag9a = compute_tag(&io_pattern9a, &domain_separator9);
-    let tag9b = compute_tag(&io_pattern9b, &domain_separator9);
-    println!("Test 9: Aggregation demonstration");
-    println!("Original: [0x80000001, 0x80000001, 0x00000001] (ABSORB(1), ABSORB(1), SQUEEZE(1))");
-    println!("Aggregated: [0x80000002, 0x00000001] (ABSORB(2), SQUEEZE(1))");
-    println!("Original tag: 0x{:032x}", tag9a);
-    println!("Aggregated tag: 0x{:032x}", tag9b);
-    println!("Tags match: {}", tag9a == tag9b);
-    println!();
+    println!("Tag:              {}", display::format_tag(breakdown.tag));
+}
+
+/// Parses an IO pattern from its text form, whitespace or comma separated tokens.
+///
+/// Each token is either `A<n>`/`S<n>` for ABSORB(n)/SQUEEZE(n), optionally followed by
+/// `:<label>`, or a `0x`-prefixed encoded word.
+fn parse_pattern(input: &str) -> Result<IoPattern, String> {
+    input.parse().map_err(|error: SafeError| error.to_string())
+}
+
+/// Describes the protocol of the manifest at `path` whose domain separator is the byte
+/// reversal of `domain_separator`, if any.
+fn reversed_protocol(path: &Path, domain_separator: &[u8; 64]) -> Result<Option<String>, String> {
+    let constants = manifest::Manifest::load(path)?.tag_constants()?;
+    let known: Vec<DomainSeparator> = constants
+        .iter()
+        .map(|constant| DomainSeparator::new(*constant.domain_separator()))
+        .collect();
+    let domain_separator = DomainSeparator::new(*domain_separator);
+    Ok(domain_separator.find_reversed(&known).map(|reversed| {
+        let constant = constants
+            .iter()
+            .find(|constant| constant.domain_separator() == reversed.as_bytes())
+            .expect("a known domain separator");
+        format!(
+            "the domain separator {} is the byte reversal of {}, that of protocol `{}`; was it \
+             written little-endian?",
+            domain_separator,
+            reversed,
+            constant.name()
+        )
+    }))
+}
+
+/// Parses a domain separator from hex, zero padding it to 64 bytes.
+fn parse_domain(input: &str) -> Result<[u8; 64], String> {
+    DomainSeparator::from_hex(input)
+        .map(Into::into)
+        .map_err(|error| error.to_string())
+}
+
+/// Parses comma separated field elements, each in decimal or `0x` hex, rejecting non-canonical values.
+fn parse_field_elements(input: &str) -> Result<Vec<Fr>, String> {
+    input
+        .split(',')
+        .map(str::trim)
+        .map(|token| {
+            display::parse_element(token)
+                .map_err(|error| format!("invalid field element `{}`: {}", token, error))
+        })
+        .collect()
+}
+
+fn format_words(words: &[u32]) -> String {
+    let words: Vec<String> = words.iter().map(|word| format!("0x{:08x}", word)).collect();
+    format!("[{}]", words.join(", "))
 }
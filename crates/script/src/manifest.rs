@@ -0,0 +1,383 @@
+//! The protocol manifest (`safe.toml`) read by the code generation and lockfile commands.
+//!
+//! ```toml
+//! namespace = "acme/wallet" # Optional prefix of the generated identifiers.
+//!
+//! [variables]                # Optional values of the template placeholders.
+//! app = "wallet"
+//! version = "1"
+//!
+//! [[protocol]]
+//! name = "merkle_node"
+//! pattern = "A1 A1 S1"
+//! domain = "0x41424344"
+//! challenges = ["parent"]   # Optional names of the squeezes, in squeeze order.
+//!
+//! [[protocol]]
+//! name = "spend"
+//! pattern = "A3 S1"
+//! template = "acme/{app}/{circuit}/v{version}"  # Instead of `domain`.
+//! variables = { circuit = "spend" }             # Overrides those of the manifest.
+//! tag_length = 256                              # Bits of the tag, 128 by default.
+//!
+//! [[protocol.absorb]]       # Optional layouts of the absorbed structs, see `audit`.
+//! struct = "Node"
+//! fields = ["left", "right"]
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use safe_api::codegen::{Namespace, TagConstant};
+use safe_api::domain::DomainTemplate;
+use safe_api::tag::TagLength;
+use safe_api::IoPattern;
+use serde::Deserialize;
+
+use crate::{parse_domain, parse_pattern};
+
+/// The protocols of an application, each an IO pattern under a domain separator.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Organization and application prefix of the generated identifiers, e.g. "acme/wallet".
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// Values of the placeholders of the domain templates, shared by every protocol.
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+    #[serde(rename = "protocol", default)]
+    pub protocols: Vec<Protocol>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Protocol {
+    /// Lowercase identifier, used for the generated constant names.
+    pub name: String,
+    /// IO pattern in the CLI syntax, e.g. "A3 S1".
+    pub pattern: String,
+    /// Labels of the squeezes, the challenges of the protocol, in squeeze order, e.g.
+    /// ["alpha", "beta"], unless `pattern` labels them itself.
+    #[serde(default)]
+    pub challenges: Vec<String>,
+    /// Domain separator as hex (up to 64 bytes, zero padded), unless `template` is given.
+    #[serde(default)]
+    pub domain: Option<String>,
+    /// Domain separator template, e.g. "acme/{app}/{circuit}/v{version}".
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Values of the placeholders of `template`, overriding those of the manifest.
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+    /// Bits of the tag: 64, 128 (the default, as in the spec), 248, or 256 for the whole
+    /// digest, e.g. for byte-oriented sponges.
+    #[serde(default)]
+    pub tag_length: Option<usize>,
+    /// Structs the protocol absorbs, in absorption order.
+    #[serde(rename = "absorb", default)]
+    pub absorbs: Vec<AbsorbedStruct>,
+}
+
+/// The layout of an absorbed struct: its fields in absorption order, each `name` or
+/// `name: encoding` (`Field` if omitted).
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AbsorbedStruct {
+    #[serde(rename = "struct")]
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+impl Manifest {
+    /// Reads and parses a manifest file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|error| format!("cannot read {}: {}", path.display(), error))?;
+        Self::parse(&contents).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    /// Parses the contents of a manifest file.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        toml::from_str(contents).map_err(|error| error.to_string())
+    }
+
+    /// Returns the namespace of the generated identifiers, empty if none is declared.
+    pub fn namespace(&self) -> Result<Namespace, String> {
+        match &self.namespace {
+            Some(namespace) => Namespace::new(namespace).map_err(|error| error.to_string()),
+            None => Ok(Namespace::default()),
+        }
+    }
+
+    /// Resolves every protocol into a tag constant. Protocols whose templates render the same
+    /// domain separator are rejected, as a template is meant to give each its own.
+    pub fn tag_constants(&self) -> Result<Vec<TagConstant>, String> {
+        let mut rendered: Vec<([u8; 64], &str)> = Vec::new();
+        self.protocols
+            .iter()
+            .map(|protocol| {
+                let context = |error: String| format!("protocol `{}`: {}", protocol.name, error);
+                let pattern = protocol.io_pattern().map_err(context)?;
+                let domain = protocol.domain(&self.variables).map_err(context)?;
+                if protocol.template.is_some() {
+                    if let Some((_, other)) = rendered.iter().find(|(known, _)| *known == domain) {
+                        return Err(context(format!(
+                            "the template renders the domain separator of protocol `{}`",
+                            other
+                        )));
+                    }
+                    rendered.push((domain, &protocol.name));
+                }
+                let tag_length = protocol.tag_length().map_err(context)?;
+                TagConstant::new(&protocol.name, pattern, domain)
+                    .map(|constant| constant.with_tag_length(tag_length))
+                    .map_err(|error| context(error.to_string()))
+            })
+            .collect()
+    }
+}
+
+impl Protocol {
+    /// Returns the IO pattern, its squeezes labeled with `challenges` if given.
+    pub fn io_pattern(&self) -> Result<IoPattern, String> {
+        let pattern = parse_pattern(&self.pattern)?;
+        if self.challenges.is_empty() {
+            return Ok(pattern);
+        }
+        if !pattern.squeeze_labels().is_empty() {
+            return Err(
+                "expected the squeezes labeled in `pattern` or in `challenges`, not both"
+                    .to_string(),
+            );
+        }
+        let squeezes = pattern
+            .operations()
+            .iter()
+            .filter(|operation| !operation.is_absorb())
+            .count();
+        if squeezes != self.challenges.len() {
+            return Err(format!(
+                "`challenges` name {} squeezes, but the pattern has {}",
+                self.challenges.len(),
+                squeezes
+            ));
+        }
+
+        let mut labeled = IoPattern::new();
+        let mut challenges = self.challenges.iter();
+        for (index, operation) in pattern.operations().iter().enumerate() {
+            labeled = match (operation.is_absorb(), pattern.label(index)) {
+                (true, None) => labeled.absorb(operation.len()),
+                (true, Some(label)) => labeled.absorb_labeled(label, operation.len()),
+                (false, _) => {
+                    let label = challenges.next().expect("one challenge per squeeze");
+                    if pattern.label_index(label).is_some() || labeled.label_index(label).is_some()
+                    {
+                        return Err(format!("the label `{}` is used twice", label));
+                    }
+                    labeled.squeeze_labeled(label, operation.len())
+                }
+            };
+        }
+        Ok(labeled)
+    }
+
+    /// Returns the length of the tag, 128 bits if `tag_length` is omitted.
+    pub fn tag_length(&self) -> Result<TagLength, String> {
+        match self.tag_length {
+            None => Ok(TagLength::default()),
+            Some(bits) => TagLength::from_bits(bits)
+                .ok_or_else(|| format!("`tag_length` must be 64, 128, 248 or 256, not {}", bits)),
+        }
+    }
+
+    /// Returns the domain separator, parsed from `domain` or rendered from `template` with
+    /// the variables of the protocol, then the `shared` ones of the manifest.
+    pub fn domain(&self, shared: &BTreeMap<String, String>) -> Result<[u8; 64], String> {
+        let template = match (&self.domain, &self.template) {
+            (Some(domain), None) if self.variables.is_empty() => return parse_domain(domain),
+            (Some(_), None) => return Err("`variables` require a `template`".to_string()),
+            (None, Some(template)) => template,
+            _ => return Err("expected exactly one of `domain` and `template`".to_string()),
+        };
+        let template: DomainTemplate = template
+            .parse()
+            .map_err(|error| format!("template `{}`: {}", template, error))?;
+        if let Some(unused) = self
+            .variables
+            .keys()
+            .find(|name| !template.placeholders().contains(name))
+        {
+            return Err(format!(
+                "template `{}` has no placeholder `{}`",
+                template, unused
+            ));
+        }
+        let domain = template
+            .render(|name| {
+                self.variables
+                    .get(name)
+                    .or(shared.get(name))
+                    .map(String::as_str)
+            })
+            .map_err(|error| format!("template `{}`: {}", template, error))?;
+        Ok(domain.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use safe_api::domain::DomainSeparator;
+
+    const TEMPLATED: &str = r#"
+        [variables]
+        app = "wallet"
+        version = "2"
+
+        [[protocol]]
+        name = "spend"
+        pattern = "A3 S1"
+        template = "acme/{app}/{circuit}/v{version}"
+        variables = { circuit = "spend" }
+
+        [[protocol]]
+        name = "mint"
+        pattern = "A3 S1"
+        template = "acme/{app}/{circuit}/v{version}"
+        variables = { circuit = "mint", version = "1" }
+    "#;
+
+    #[test]
+    fn test_templates() {
+        let manifest = Manifest::parse(TEMPLATED).unwrap();
+        let domains: Vec<_> = manifest
+            .protocols
+            .iter()
+            .map(|protocol| protocol.domain(&manifest.variables).unwrap())
+            .collect();
+        assert_eq!(
+            domains,
+            [
+                *DomainSeparator::from_label("acme/wallet/spend/v2").unwrap(),
+                *DomainSeparator::from_label("acme/wallet/mint/v1").unwrap(),
+            ]
+        );
+        assert_eq!(manifest.tag_constants().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        let error = |contents: &str| {
+            Manifest::parse(contents)
+                .unwrap()
+                .tag_constants()
+                .unwrap_err()
+        };
+        assert_eq!(
+            error(
+                &TEMPLATED
+                    .replace("\"mint\", version", "\"spend\", version")
+                    .replace("= \"1\"", "= \"2\"")
+            ),
+            "protocol `mint`: the template renders the domain separator of protocol `spend`"
+        );
+        assert_eq!(
+            error(&TEMPLATED.replace("circuit = \"spend\"", "circuit = \"a/b\"")),
+            "protocol `spend`: template `acme/{app}/{circuit}/v{version}`: the value of placeholder `circuit` is empty or contains `/`, a brace or a non-printable character"
+        );
+        assert_eq!(
+            error(&TEMPLATED.replace("circuit = \"spend\"", "circuit = \"spend\", typo = \"x\"")),
+            "protocol `spend`: template `acme/{app}/{circuit}/v{version}` has no placeholder `typo`"
+        );
+        assert_eq!(
+            error(&TEMPLATED.replace("app = \"wallet\"", &format!("app = \"{}\"", "w".repeat(60)))),
+            "protocol `spend`: template `acme/{app}/{circuit}/v{version}`: the rendered domain separator is longer than 64 bytes (74 bytes)"
+        );
+        assert_eq!(
+            error(&TEMPLATED.replace("version = \"2\"\n", "")),
+            "protocol `spend`: template `acme/{app}/{circuit}/v{version}`: no value for placeholder `version`"
+        );
+        assert_eq!(
+            error("[[protocol]]\nname = \"a\"\npattern = \"A1 S1\"\n"),
+            "protocol `a`: expected exactly one of `domain` and `template`"
+        );
+        assert_eq!(
+            error("[[protocol]]\nname = \"a\"\npattern = \"A1 S1\"\ndomain = \"0x41\"\ntag_length = 100\n"),
+            "protocol `a`: `tag_length` must be 64, 128, 248 or 256, not 100"
+        );
+    }
+
+    #[test]
+    fn test_tag_length() {
+        let manifest = Manifest::parse(
+            "[[protocol]]\nname = \"bytes\"\npattern = \"A3 S1\"\ndomain = \"0x41424344\"\n\
+             tag_length = 256\n",
+        )
+        .unwrap();
+        let constant = &manifest.tag_constants().unwrap()[0];
+        assert_eq!(constant.tag_length(), TagLength::Full);
+        assert_eq!(
+            constant.tag().as_bytes(),
+            constant.io_pattern().tag_256(constant.domain_separator())
+        );
+    }
+
+    #[test]
+    fn test_challenges() {
+        let manifest = |pattern: &str, challenges: &str| {
+            Manifest::parse(&format!(
+                "[[protocol]]\nname = \"sigma\"\npattern = \"{}\"\ndomain = \"0x41\"\n\
+                 challenges = [{}]\n",
+                pattern, challenges
+            ))
+            .unwrap()
+            .tag_constants()
+        };
+        let constants = manifest("A2:commitment S1 A1 S2", "\"alpha\", \"beta\"").unwrap();
+        assert_eq!(
+            constants[0].io_pattern().to_string(),
+            "A2:commitment S1:alpha A1 S2:beta"
+        );
+        assert_eq!(constants[0].challenges(), [("alpha", 1), ("beta", 2)]);
+        // Labels do not change the tag.
+        assert_eq!(
+            constants[0].tag(),
+            manifest("A2 S1 A1 S2", "").unwrap()[0].tag()
+        );
+
+        for (pattern, challenges, error) in [
+            (
+                "A2 S1",
+                "\"alpha\", \"beta\"",
+                "`challenges` name 2 squeezes, but the pattern has 1",
+            ),
+            (
+                "A2 S1:alpha",
+                "\"beta\"",
+                "expected the squeezes labeled in `pattern` or in `challenges`, not both",
+            ),
+            (
+                "A2:alpha S1",
+                "\"alpha\"",
+                "the label `alpha` is used twice",
+            ),
+            (
+                "A2 S1 S1",
+                "\"alpha\", \"alpha\"",
+                "the label `alpha` is used twice",
+            ),
+            (
+                "A2 S1",
+                "\"Alpha\"",
+                "`Alpha` is not a valid challenge name (expected lowercase letters, digits and `_`)",
+            ),
+        ] {
+            assert_eq!(
+                manifest(pattern, challenges).unwrap_err(),
+                format!("protocol `sigma`: {}", error)
+            );
+        }
+    }
+}
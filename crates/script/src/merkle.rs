@@ -0,0 +1,279 @@
+//! Binary Merkle trees hashed with the sponge.
+//!
+//! Each node is ABSORB(2) SQUEEZE(1) over its children under the domain separator of the tree,
+//! so trees of different applications never share nodes. The leaves are padded with zeros up
+//! to a power of two. [`MerkleProof::root`] recomputes the root exactly like `merkle_root` of
+//! the Noir library, which circuits use to check membership.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use ark_ff::Zero;
+
+use crate::io_pattern::IoPattern;
+use crate::permutation::Permutation;
+use crate::sponge::Sponge;
+
+/// IO pattern of a node: both children, then the node.
+pub fn node_pattern() -> IoPattern {
+    IoPattern::new().absorb(2).squeeze(1)
+}
+
+/// Hashes the children of a node of the tree under `domain_separator`.
+pub fn hash_node<P: Permutation + Default>(
+    domain_separator: &[u8; 64],
+    left: P::Field,
+    right: P::Field,
+) -> P::Field {
+    let mut sponge = Sponge::<P>::start(node_pattern(), domain_separator);
+    sponge.absorb(&[left, right]);
+    let node = sponge.squeeze()[0];
+    sponge.finish();
+    node
+}
+
+/// Hashes the pairs of `level` into the level above, as [`hash_node`] on each pair but with
+/// one [`Permutation::permute_batch`] over all of them.
+fn hash_level<P: Permutation + Default>(
+    domain_separator: &[u8; 64],
+    level: &[P::Field],
+) -> Vec<P::Field> {
+    if P::RATE < 2 {
+        // Both children do not fit in one permutation.
+        return level
+            .chunks(2)
+            .map(|pair| hash_node::<P>(domain_separator, pair[0], pair[1]))
+            .collect();
+    }
+    // A node absorbs both children into the initial state, then squeezes after a permutation.
+    let sponge = Sponge::<P>::start(node_pattern(), domain_separator);
+    let initial = sponge.state();
+    let mut states = Vec::with_capacity(level.len() / 2 * P::WIDTH);
+    for pair in level.chunks(2) {
+        let start = states.len();
+        states.extend_from_slice(initial);
+        states[start + P::CAPACITY] += pair[0];
+        states[start + P::CAPACITY + 1] += pair[1];
+    }
+    P::default().permute_batch(&mut states);
+    states
+        .chunks(P::WIDTH)
+        .map(|state| state[P::CAPACITY])
+        .collect()
+}
+
+/// A Merkle tree with all its levels.
+#[derive(Clone, Debug)]
+pub struct MerkleTree<P: Permutation> {
+    domain_separator: [u8; 64],
+    leaf_count: usize,
+    /// The padded leaves first, the root last.
+    levels: Vec<Vec<P::Field>>,
+    permutation: PhantomData<P>,
+}
+
+impl<P: Permutation + Default> MerkleTree<P> {
+    /// Builds the tree over `leaves` under `domain_separator`. The tree without leaves has the
+    /// root zero.
+    pub fn new(leaves: Vec<P::Field>, domain_separator: &[u8; 64]) -> Self {
+        let leaf_count = leaves.len();
+        let mut level = leaves;
+        level.resize(leaf_count.next_power_of_two(), P::Field::zero());
+        let mut levels = vec![level];
+        while levels[levels.len() - 1].len() > 1 {
+            let next = hash_level::<P>(domain_separator, &levels[levels.len() - 1]);
+            levels.push(next);
+        }
+        MerkleTree {
+            domain_separator: *domain_separator,
+            leaf_count,
+            levels,
+            permutation: PhantomData,
+        }
+    }
+}
+
+impl<P: Permutation> MerkleTree<P> {
+    /// Returns the root.
+    pub fn root(&self) -> P::Field {
+        self.levels[self.levels.len() - 1][0]
+    }
+
+    /// Returns the domain separator of the nodes.
+    pub fn domain_separator(&self) -> &[u8; 64] {
+        &self.domain_separator
+    }
+
+    /// Returns the number of leaves, without padding.
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    /// Returns the depth of the tree, i.e. the number of siblings of a proof.
+    pub fn depth(&self) -> u32 {
+        self.levels.len() as u32 - 1
+    }
+
+    /// Returns leaf `index`.
+    pub fn leaf(&self, index: usize) -> P::Field {
+        self.levels[0][index]
+    }
+
+    /// Returns the proof of leaf `index`.
+    ///
+    /// # Panics
+    /// If there is no leaf `index`.
+    pub fn open(&self, index: usize) -> MerkleProof<P::Field> {
+        assert!(index < self.leaf_count, "Leaf index out of bounds");
+        MerkleProof {
+            index,
+            siblings: self.levels[..self.levels.len() - 1]
+                .iter()
+                .enumerate()
+                .map(|(level, nodes)| nodes[(index >> level) ^ 1])
+                .collect(),
+        }
+    }
+}
+
+/// Proof that a leaf belongs to a [`MerkleTree`]: its index and the siblings on its path to
+/// the root, from the leaf level up.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleProof<F> {
+    pub index: usize,
+    pub siblings: Vec<F>,
+}
+
+impl<F: Copy> MerkleProof<F> {
+    /// Recomputes the root of the tree under `domain_separator` from the leaf.
+    pub fn root<P: Permutation<Field = F> + Default>(
+        &self,
+        domain_separator: &[u8; 64],
+        leaf: F,
+    ) -> F {
+        let mut node = leaf;
+        for (level, &sibling) in self.siblings.iter().enumerate() {
+            node = match (self.index >> level) & 1 {
+                0 => hash_node::<P>(domain_separator, node, sibling),
+                _ => hash_node::<P>(domain_separator, sibling, node),
+            };
+        }
+        node
+    }
+}
+
+/// Checks that `leaf` belongs to the tree of depth `depth` with root `root` under
+/// `domain_separator`.
+pub fn verify<P: Permutation + Default>(
+    root: P::Field,
+    depth: u32,
+    domain_separator: &[u8; 64],
+    leaf: P::Field,
+    proof: &MerkleProof<P::Field>,
+) -> bool {
+    proof.siblings.len() as u32 == depth
+        && (proof.index >> depth) == 0
+        && proof.root::<P>(domain_separator, leaf) == root
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::poseidon2::Poseidon2;
+    use ark_bn254::Fr;
+    use core::str::FromStr;
+
+    fn domain_separator(label: &[u8]) -> [u8; 64] {
+        let mut domain_separator = [0u8; 64];
+        domain_separator[..label.len()].copy_from_slice(label);
+        domain_separator
+    }
+
+    fn leaves(count: u64) -> Vec<Fr> {
+        (1..=count).map(Fr::from).collect()
+    }
+
+    #[test]
+    fn test_batched_levels() {
+        let domain_separator = domain_separator(b"ABCD");
+        let tree = MerkleTree::<Poseidon2>::new(leaves(200), &domain_separator);
+        for pair in tree.levels.windows(2) {
+            let expected: Vec<Fr> = pair[0]
+                .chunks(2)
+                .map(|children| hash_node::<Poseidon2>(&domain_separator, children[0], children[1]))
+                .collect();
+            assert_eq!(pair[1], expected);
+        }
+    }
+
+    #[test]
+    fn test_open_and_verify() {
+        let domain_separator = domain_separator(b"ABCD");
+        let tree = MerkleTree::<Poseidon2>::new(leaves(5), &domain_separator);
+        assert_eq!(tree.depth(), 3);
+        assert_eq!(tree.leaf_count(), 5);
+
+        for index in 0..5 {
+            let proof = tree.open(index);
+            assert!(verify::<Poseidon2>(
+                tree.root(),
+                3,
+                &domain_separator,
+                tree.leaf(index),
+                &proof
+            ));
+        }
+
+        let proof = tree.open(1);
+        assert!(!verify::<Poseidon2>(
+            tree.root(),
+            3,
+            &domain_separator,
+            Fr::from(1u64),
+            &proof
+        ));
+        let moved = MerkleProof { index: 9, ..proof };
+        assert!(!verify::<Poseidon2>(
+            tree.root(),
+            3,
+            &domain_separator,
+            Fr::from(2u64),
+            &moved
+        ));
+    }
+
+    #[test]
+    fn test_domain_separation() {
+        let first = MerkleTree::<Poseidon2>::new(leaves(4), &domain_separator(b"first"));
+        let second = MerkleTree::<Poseidon2>::new(leaves(4), &domain_separator(b"second"));
+        assert_ne!(first.root(), second.root());
+        assert!(!verify::<Poseidon2>(
+            second.root(),
+            2,
+            &domain_separator(b"first"),
+            Fr::from(1u64),
+            &first.open(0)
+        ));
+
+        let empty = MerkleTree::<Poseidon2>::new(Vec::new(), &domain_separator(b"first"));
+        assert_eq!(empty.depth(), 0);
+        assert!(empty.root().is_zero());
+    }
+
+    #[test]
+    fn test_matches_noir() {
+        // Same values as `test_merkle_root` of the Noir library, which checks leaf 3.
+        let tree = MerkleTree::<Poseidon2>::new(leaves(4), &domain_separator(b"ABCD"));
+        let expected = Fr::from_str(
+            "968728196072243524927176425931071865908779439946330731405963488349583769429",
+        )
+        .unwrap();
+        assert_eq!(tree.root(), expected);
+        let sibling = Fr::from_str(
+            "3679247303099852319026409154999212018728134177185686104834288833204532192776",
+        )
+        .unwrap();
+        assert_eq!(tree.open(2).siblings, vec![Fr::from(4u64), sibling]);
+    }
+}
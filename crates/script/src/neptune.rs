@@ -0,0 +1,298 @@
+//! Interoperability with the `neptune` SAFE sponge of lurk-lab and Filecoin.
+//!
+//! `neptune` implements the SAFE API over BLS12-381 with its own Poseidon instance and its
+//! own tag derivation:
+//!
+//! - its `IOPattern` is a list of `SpongeOp::Absorb(n)` and `SpongeOp::Squeeze(n)`, the same
+//!   operations as an [`IoPattern`], see [`neptune_pattern`] and [`from_neptune_pattern`];
+//! - its domain separator is a `u32` rather than 64 bytes. [`domain_separator`] pads its
+//!   big-endian bytes with zeros, and [`neptune_domain`] reads it back;
+//! - its tag is a polynomial hash modulo `2^128` of the aggregated operation words and the
+//!   domain separator, not the SHA-256 derivation of the spec, see [`neptune_tag`].
+//!
+//! The sponge itself follows the same rules for the capacity (the tag, first element of the
+//! state), the absorb and squeeze positions and the permutation calls, so [`start`] runs a
+//! [`Sponge`] on [`NeptunePoseidon`] which absorbs and squeezes exactly as a `neptune` sponge
+//! of arity 3 with the standard strength:
+//!
+//! ```
+//! use ark_bls12_381::Fr;
+//! use safe_api::neptune;
+//! use safe_api::IoPattern;
+//!
+//! let pattern = IoPattern::new().absorb(2).squeeze(1);
+//! let mut sponge = neptune::start(pattern, 7);
+//! sponge.absorb(&[Fr::from(1u64), Fr::from(2u64)]);
+//! let output = sponge.squeeze();
+//! sponge.finish();
+//! # assert_eq!(output.len(), 1);
+//! ```
+//!
+//! The outputs are pinned by the shared vectors of `vectors/neptune.json`, computed with the
+//! `neptune` sponge itself and checked against both implementations by the tests of this
+//! module.
+
+use std::sync::OnceLock;
+
+use ::neptune::poseidon::{Poseidon, PoseidonConstants};
+use ::neptune::sponge::api::{IOPattern, SpongeOp};
+use ::neptune::sponge::vanilla::{Sponge as NeptuneSponge, SpongeTrait};
+use ::neptune::Strength;
+use ark_bls12_381::Fr;
+use ark_ff::{BigInteger, PrimeField};
+use blstrs::Scalar;
+use generic_array::typenum::U3;
+
+use crate::io_pattern::{IoPattern, Operation};
+use crate::permutation::Permutation;
+use crate::sponge::Sponge;
+use crate::tag::Tag;
+
+/// The Poseidon instance of the `neptune` sponge API of arity 3 over BLS12-381, with the
+/// standard strength.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NeptunePoseidon;
+
+/// Returns the round constants of [`NeptunePoseidon`], generated once.
+fn constants() -> &'static PoseidonConstants<Scalar, U3> {
+    static CONSTANTS: OnceLock<PoseidonConstants<Scalar, U3>> = OnceLock::new();
+    CONSTANTS.get_or_init(|| NeptuneSponge::<Scalar, U3>::api_constants(Strength::Standard))
+}
+
+impl Permutation for NeptunePoseidon {
+    type Field = Fr;
+
+    const WIDTH: usize = 4;
+
+    const RATE: usize = 3;
+
+    const ID: &'static str = "neptune-poseidon-bls12-381";
+
+    fn permute(&self, state: &mut [Fr]) {
+        assert_eq!(
+            state.len(),
+            4,
+            "Neptune Poseidon state must have 4 elements"
+        );
+        let mut poseidon = Poseidon::new(constants());
+        for (element, input) in poseidon.elements.iter_mut().zip(state.iter()) {
+            *element = to_scalar(input);
+        }
+        poseidon.hash();
+        for (output, element) in state.iter_mut().zip(poseidon.elements.iter()) {
+            *output = from_scalar(element);
+        }
+    }
+}
+
+/// Converts an arkworks element of BLS12-381 to its `blstrs` counterpart.
+pub fn to_scalar(element: &Fr) -> Scalar {
+    let bytes: [u8; 32] = element
+        .into_bigint()
+        .to_bytes_le()
+        .try_into()
+        .expect("a BLS12-381 scalar is 32 bytes");
+    Option::from(Scalar::from_bytes_le(&bytes)).expect("a canonical BLS12-381 scalar")
+}
+
+/// Converts a `blstrs` element of BLS12-381 to its arkworks counterpart.
+pub fn from_scalar(element: &Scalar) -> Fr {
+    Fr::from_le_bytes_mod_order(&element.to_bytes_le())
+}
+
+/// Returns the `neptune` IO pattern of the same operations as `io_pattern`. Labels have no
+/// counterpart and are dropped.
+pub fn neptune_pattern(io_pattern: &IoPattern) -> IOPattern {
+    IOPattern(
+        io_pattern
+            .operations()
+            .iter()
+            .map(|operation| match *operation {
+                Operation::Absorb(length) => SpongeOp::Absorb(length),
+                Operation::Squeeze(length) => SpongeOp::Squeeze(length),
+            })
+            .collect(),
+    )
+}
+
+/// Returns the IO pattern of the same operations as the `neptune` IO pattern `pattern`.
+pub fn from_neptune_pattern(pattern: &IOPattern) -> IoPattern {
+    pattern
+        .0
+        .iter()
+        .fold(IoPattern::new(), |io_pattern, operation| match *operation {
+            SpongeOp::Absorb(length) => io_pattern.absorb(length),
+            SpongeOp::Squeeze(length) => io_pattern.squeeze(length),
+        })
+}
+
+/// Returns the 64-byte domain separator standing for the `neptune` domain separator `domain`:
+/// its big-endian bytes, zero padded. `0`, the default of `neptune`, is the empty domain.
+pub fn domain_separator(domain: u32) -> [u8; 64] {
+    let mut domain_separator = [0u8; 64];
+    domain_separator[..4].copy_from_slice(&domain.to_be_bytes());
+    domain_separator
+}
+
+/// Returns the `neptune` domain separator behind `domain_separator`, if it is one of those of
+/// [`domain_separator`].
+pub fn neptune_domain(domain_separator: &[u8; 64]) -> Option<u32> {
+    let (domain, padding) = domain_separator.split_at(4);
+    padding
+        .iter()
+        .all(|&byte| byte == 0)
+        .then(|| u32::from_be_bytes(domain.try_into().expect("4 bytes")))
+}
+
+/// Returns the tag `neptune` derives for `io_pattern` under the domain separator `domain`.
+pub fn neptune_tag(io_pattern: &IoPattern, domain: u32) -> Tag {
+    Tag::from(neptune_pattern(io_pattern).value(domain))
+}
+
+/// Starts a sponge absorbing and squeezing like a `neptune` sponge started with the pattern
+/// and the domain separator `domain`, see the [module documentation](self).
+pub fn start(io_pattern: IoPattern, domain: u32) -> Sponge<NeptunePoseidon> {
+    let tag = neptune_tag(&io_pattern, domain).to_field();
+    Sponge::with_tag_limbs(NeptunePoseidon, io_pattern, &[tag], Default::default())
+}
+
+#[cfg(all(test, feature = "vectors"))]
+mod tests {
+    use super::*;
+    use crate::display;
+    use ::neptune::sponge::api::SpongeAPI;
+    use ::neptune::sponge::vanilla::Mode;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Vectors {
+        version: u32,
+        vectors: Vec<Vector>,
+    }
+
+    #[derive(Deserialize)]
+    struct Vector {
+        name: String,
+        io_pattern: String,
+        domain: u32,
+        tag: String,
+        inputs: Vec<Vec<String>>,
+        outputs: Vec<Vec<String>>,
+    }
+
+    fn elements(values: &[String]) -> Vec<Fr> {
+        values
+            .iter()
+            .map(|value| display::parse_element(value).unwrap())
+            .collect()
+    }
+
+    /// Runs the `neptune` sponge itself.
+    fn run_neptune(pattern: &IoPattern, domain: u32, inputs: &[Vec<Fr>]) -> Vec<Vec<Fr>> {
+        let mut sponge = NeptuneSponge::new_with_constants(constants(), Mode::Simplex);
+        let acc = &mut ();
+        sponge.start(neptune_pattern(pattern), Some(domain), acc);
+        let mut inputs = inputs.iter();
+        let mut outputs = Vec::new();
+        for operation in pattern.operations() {
+            match *operation {
+                Operation::Absorb(length) => {
+                    let input: Vec<Scalar> = inputs.next().unwrap().iter().map(to_scalar).collect();
+                    SpongeAPI::absorb(&mut sponge, length, &input, acc);
+                }
+                Operation::Squeeze(length) => {
+                    let output = SpongeAPI::squeeze(&mut sponge, length, acc);
+                    outputs.push(output.iter().map(from_scalar).collect());
+                }
+            }
+        }
+        SpongeAPI::finish(&mut sponge, acc).unwrap();
+        outputs
+    }
+
+    /// Runs a [`Sponge`] started with [`start`].
+    fn run_safe(pattern: &IoPattern, domain: u32, inputs: &[Vec<Fr>]) -> Vec<Vec<Fr>> {
+        let mut sponge = start(pattern.clone(), domain);
+        let mut inputs = inputs.iter();
+        let mut outputs = Vec::new();
+        for operation in pattern.operations() {
+            match operation {
+                Operation::Absorb(_) => sponge.absorb(inputs.next().unwrap()),
+                Operation::Squeeze(_) => outputs.push(sponge.squeeze()),
+            }
+        }
+        sponge.finish();
+        outputs
+    }
+
+    #[test]
+    fn test_shared_vectors() {
+        let vectors: Vectors =
+            serde_json::from_str(include_str!("../vectors/neptune.json")).unwrap();
+        assert_eq!(vectors.version, 1);
+        assert!(!vectors.vectors.is_empty());
+        for vector in &vectors.vectors {
+            let pattern: IoPattern = vector.io_pattern.parse().unwrap();
+            assert_eq!(
+                display::format_tag(neptune_tag(&pattern, vector.domain)),
+                vector.tag,
+                "{}",
+                vector.name
+            );
+            let inputs: Vec<Vec<Fr>> = vector.inputs.iter().map(|input| elements(input)).collect();
+            let expected: Vec<Vec<Fr>> = vector
+                .outputs
+                .iter()
+                .map(|output| elements(output))
+                .collect();
+            assert_eq!(
+                run_neptune(&pattern, vector.domain, &inputs),
+                expected,
+                "{}",
+                vector.name
+            );
+            assert_eq!(
+                run_safe(&pattern, vector.domain, &inputs),
+                expected,
+                "{}",
+                vector.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_tags() {
+        // The values of the tests of `neptune`.
+        let pattern = IoPattern::new().absorb(2).squeeze(2);
+        assert_eq!(
+            neptune_tag(&pattern, 0),
+            Tag::from(340282366920938463463374607090318361668)
+        );
+        assert_eq!(
+            neptune_tag(&pattern, 1),
+            Tag::from(340282366920938463463374607090314341989)
+        );
+        // Consecutive operations of the same kind aggregate.
+        let split = IoPattern::new().absorb(1).absorb(1).squeeze(1).squeeze(1);
+        assert_eq!(neptune_tag(&split, 0), neptune_tag(&pattern, 0));
+        assert_eq!(start(split, 0).tag(), neptune_tag(&pattern, 0).to_field());
+    }
+
+    #[test]
+    fn test_mappings() {
+        let pattern = IoPattern::new().absorb(3).squeeze(1).absorb(2).squeeze(4);
+        assert_eq!(from_neptune_pattern(&neptune_pattern(&pattern)), pattern);
+
+        let separator = domain_separator(0xdeadbeef);
+        assert_eq!(separator[..4], [0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(neptune_domain(&separator), Some(0xdeadbeef));
+        assert_eq!(neptune_domain(&domain_separator(0)), Some(0));
+        assert_eq!(
+            neptune_domain(&crate::domain::padded_domain_separator(b"ABCDE")),
+            None
+        );
+
+        let element = Fr::from(-1i64);
+        assert_eq!(from_scalar(&to_scalar(&element)), element);
+    }
+}
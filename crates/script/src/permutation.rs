@@ -0,0 +1,44 @@
+use ark_ff::PrimeField;
+
+/// A cryptographic permutation over a prime field, used as the core of the SAFE sponge.
+///
+/// The sponge keeps the capacity part of the state in the first [`Permutation::CAPACITY`]
+/// elements (where the tag is added, spec 2.4) and absorbs/squeezes through the following
+/// [`Permutation::RATE`] elements.
+pub trait Permutation {
+    /// Field the state elements live in.
+    type Field: PrimeField;
+
+    /// Total state size (rate + capacity) in field elements.
+    const WIDTH: usize;
+
+    /// Number of field elements that can be absorbed or squeezed per permutation call.
+    const RATE: usize;
+
+    /// Security parameter of the sponge, in field elements.
+    const CAPACITY: usize = Self::WIDTH - Self::RATE;
+
+    /// Identifier of the permutation and its parameters, e.g. `poseidon2-bn254`, recorded in
+    /// [snapshots](crate::state#snapshots) so that a sponge never continues the state of
+    /// another backend. Backends that leave it empty only resume each other's snapshots.
+    const ID: &'static str = "";
+
+    /// Applies the permutation to `state`, which holds exactly [`Permutation::WIDTH`] elements.
+    fn permute(&self, state: &mut [Self::Field]);
+
+    /// Applies the permutation to each of the independent states laid out one after the
+    /// other in `states`, e.g. the nodes of a Merkle level. Backends override it to share
+    /// the work between the states; by default they are permuted one by one.
+    ///
+    /// # Panics
+    /// If the length of `states` is not a multiple of [`Permutation::WIDTH`].
+    fn permute_batch(&self, states: &mut [Self::Field]) {
+        assert!(
+            states.len().is_multiple_of(Self::WIDTH),
+            "Batch length must be a multiple of the state width"
+        );
+        for state in states.chunks_exact_mut(Self::WIDTH) {
+            self.permute(state);
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! Poseidon permutation over the BN254 scalar field.
+//!
+//! Uses the circomlib parameters (x^5 S-box, 8 full rounds, width-dependent partial rounds)
+//! provided by `light-poseidon`, which are also the ones of the Noir `poseidon` library
+//! (`poseidon::bn254`).
+
+use ark_bn254::Fr;
+use light_poseidon::parameters::bn254_x5::get_poseidon_parameters;
+
+use crate::permutation::Permutation;
+
+/// Width of the permutation state in field elements.
+pub const STATE_WIDTH: usize = 4;
+
+/// Poseidon over BN254 with state width 4 as a sponge [`Permutation`] (rate 3, capacity 1).
+#[derive(Clone, Debug)]
+pub struct Poseidon {
+    /// Round constants, [`STATE_WIDTH`] per round.
+    ark: Vec<Fr>,
+    /// MDS matrix, row by row.
+    mds: Vec<Vec<Fr>>,
+    full_rounds: usize,
+    partial_rounds: usize,
+}
+
+impl Poseidon {
+    /// Loads the circomlib parameters for a state of [`STATE_WIDTH`] elements.
+    pub fn new() -> Self {
+        let params = get_poseidon_parameters::<Fr>(STATE_WIDTH as u8)
+            .expect("circomlib parameters exist for width 4");
+        Poseidon {
+            ark: params.ark,
+            mds: params.mds,
+            full_rounds: params.full_rounds,
+            partial_rounds: params.partial_rounds,
+        }
+    }
+}
+
+impl Default for Poseidon {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Permutation for Poseidon {
+    type Field = Fr;
+
+    const WIDTH: usize = STATE_WIDTH;
+
+    const RATE: usize = 3;
+
+    const ID: &'static str = "poseidon-bn254";
+
+    fn permute(&self, state: &mut [Fr]) {
+        assert_eq!(
+            state.len(),
+            STATE_WIDTH,
+            "Poseidon state must have 4 elements"
+        );
+        let half_rounds = self.full_rounds / 2;
+
+        for round in 0..self.full_rounds + self.partial_rounds {
+            // Add round constants.
+            for (i, element) in state.iter_mut().enumerate() {
+                *element += self.ark[round * STATE_WIDTH + i];
+            }
+
+            // Full rounds apply the S-box to the whole state, partial rounds to the first element.
+            if round < half_rounds || round >= half_rounds + self.partial_rounds {
+                for element in state.iter_mut() {
+                    *element = s_box(*element);
+                }
+            } else {
+                state[0] = s_box(state[0]);
+            }
+
+            // Multiply by the MDS matrix.
+            let mut mixed = [Fr::from(0u64); STATE_WIDTH];
+            for (output, row) in mixed.iter_mut().zip(&self.mds) {
+                *output = state.iter().zip(row).map(|(a, m)| *a * m).sum();
+            }
+            state.copy_from_slice(&mixed);
+        }
+    }
+}
+
+fn s_box(x: Fr) -> Fr {
+    let s = x * x;
+    s * s * x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ff::MontFp;
+
+    #[test]
+    fn test_matches_circomlib_hash() {
+        // circomlib Poseidon([1, 2, 3]) is the first element of the permuted state [0, 1, 2, 3].
+        let mut state = [0u64, 1, 2, 3].map(Fr::from);
+        Poseidon::new().permute(&mut state);
+
+        let expected: Fr =
+            MontFp!("6542985608222806190361240322586112750744169038454362455181422643027100751666");
+        assert_eq!(state[0], expected);
+    }
+}
@@ -0,0 +1,263 @@
+//! Poseidon2 over the 31-bit BabyBear field.
+//!
+//! The parameters (state width 16, 8 full rounds, 13 partial rounds, x^7 S-box) and constants
+//! are those of Plonky3's `default_babybear_poseidon2_16`, so a sponge over it can be checked by a
+//! Plonky3 circuit.
+
+use ark_ff::MontFp;
+
+use super::plonky3;
+use crate::fields::BabyBear;
+use crate::permutation::Permutation;
+
+/// Width of the permutation state in field elements.
+pub const STATE_WIDTH: usize = 16;
+
+/// Number of full (external) rounds, split evenly before and after the partial rounds.
+pub const ROUNDS_F: usize = 8;
+
+/// Number of partial (internal) rounds.
+pub const ROUNDS_P: usize = 13;
+
+/// Round constants of the first 4 full rounds.
+const EXTERNAL_INITIAL_CONSTANTS: [[BabyBear; STATE_WIDTH]; ROUNDS_F / 2] = [
+    [
+        MontFp!("1774958255"),
+        MontFp!("1185780729"),
+        MontFp!("1621102414"),
+        MontFp!("1796380621"),
+        MontFp!("588815102"),
+        MontFp!("1932426223"),
+        MontFp!("1925334750"),
+        MontFp!("747903232"),
+        MontFp!("89648862"),
+        MontFp!("360728943"),
+        MontFp!("977184635"),
+        MontFp!("1425273457"),
+        MontFp!("256487465"),
+        MontFp!("1200041953"),
+        MontFp!("572403254"),
+        MontFp!("448208942"),
+    ],
+    [
+        MontFp!("1215789478"),
+        MontFp!("944884184"),
+        MontFp!("953948096"),
+        MontFp!("547326025"),
+        MontFp!("646827752"),
+        MontFp!("889997530"),
+        MontFp!("1536873262"),
+        MontFp!("86189867"),
+        MontFp!("1065944411"),
+        MontFp!("32019634"),
+        MontFp!("333311454"),
+        MontFp!("456061748"),
+        MontFp!("1963448500"),
+        MontFp!("1827584334"),
+        MontFp!("1391160226"),
+        MontFp!("1348741381"),
+    ],
+    [
+        MontFp!("88424255"),
+        MontFp!("104111868"),
+        MontFp!("1763866748"),
+        MontFp!("79691676"),
+        MontFp!("1988915530"),
+        MontFp!("1050669594"),
+        MontFp!("359890076"),
+        MontFp!("573163527"),
+        MontFp!("222820492"),
+        MontFp!("159256268"),
+        MontFp!("669703072"),
+        MontFp!("763177444"),
+        MontFp!("889367200"),
+        MontFp!("256335831"),
+        MontFp!("704371273"),
+        MontFp!("25886717"),
+    ],
+    [
+        MontFp!("51754520"),
+        MontFp!("1833211857"),
+        MontFp!("454499742"),
+        MontFp!("1384520381"),
+        MontFp!("777848065"),
+        MontFp!("1053320300"),
+        MontFp!("1851729162"),
+        MontFp!("344647910"),
+        MontFp!("401996362"),
+        MontFp!("1046925956"),
+        MontFp!("5351995"),
+        MontFp!("1212119315"),
+        MontFp!("754867989"),
+        MontFp!("36972490"),
+        MontFp!("751272725"),
+        MontFp!("506915399"),
+    ],
+];
+
+/// Round constants of the partial rounds, added to the first element.
+const INTERNAL_CONSTANTS: [BabyBear; ROUNDS_P] = [
+    MontFp!("1518359488"),
+    MontFp!("1765533241"),
+    MontFp!("945325693"),
+    MontFp!("422793067"),
+    MontFp!("311365592"),
+    MontFp!("1311448267"),
+    MontFp!("1629555936"),
+    MontFp!("1009879353"),
+    MontFp!("190525218"),
+    MontFp!("786108885"),
+    MontFp!("557776863"),
+    MontFp!("212616710"),
+    MontFp!("605745517"),
+];
+
+/// Round constants of the last 4 full rounds.
+const EXTERNAL_TERMINAL_CONSTANTS: [[BabyBear; STATE_WIDTH]; ROUNDS_F / 2] = [
+    [
+        MontFp!("1922082829"),
+        MontFp!("1870549801"),
+        MontFp!("1502529704"),
+        MontFp!("1990744480"),
+        MontFp!("1700391016"),
+        MontFp!("1702593455"),
+        MontFp!("321330495"),
+        MontFp!("528965731"),
+        MontFp!("183414327"),
+        MontFp!("1886297254"),
+        MontFp!("1178602734"),
+        MontFp!("1923111974"),
+        MontFp!("744004766"),
+        MontFp!("549271463"),
+        MontFp!("1781349648"),
+        MontFp!("542259047"),
+    ],
+    [
+        MontFp!("1536158148"),
+        MontFp!("715456982"),
+        MontFp!("503426110"),
+        MontFp!("340311124"),
+        MontFp!("1558555932"),
+        MontFp!("1226350925"),
+        MontFp!("742828095"),
+        MontFp!("1338992758"),
+        MontFp!("1641600456"),
+        MontFp!("1843351545"),
+        MontFp!("301835475"),
+        MontFp!("43203215"),
+        MontFp!("386838401"),
+        MontFp!("1520185679"),
+        MontFp!("1235297680"),
+        MontFp!("904680097"),
+    ],
+    [
+        MontFp!("1491801617"),
+        MontFp!("1581784677"),
+        MontFp!("913384905"),
+        MontFp!("247083962"),
+        MontFp!("532844013"),
+        MontFp!("107190701"),
+        MontFp!("213827818"),
+        MontFp!("1979521776"),
+        MontFp!("1358282574"),
+        MontFp!("1681743681"),
+        MontFp!("1867507480"),
+        MontFp!("1530706910"),
+        MontFp!("507181886"),
+        MontFp!("695185447"),
+        MontFp!("1172395131"),
+        MontFp!("1250800299"),
+    ],
+    [
+        MontFp!("1503161625"),
+        MontFp!("817684387"),
+        MontFp!("498481458"),
+        MontFp!("494676004"),
+        MontFp!("1404253825"),
+        MontFp!("108246855"),
+        MontFp!("59414691"),
+        MontFp!("744214112"),
+        MontFp!("890862029"),
+        MontFp!("1342765939"),
+        MontFp!("1417398904"),
+        MontFp!("1897591937"),
+        MontFp!("1066647396"),
+        MontFp!("1682806907"),
+        MontFp!("1015795079"),
+        MontFp!("1619482808"),
+    ],
+];
+
+/// Diagonal of the internal matrix (`M_I = diag(...) + J`): -2, 1, 2, 1/2, 3, 4, -1/2, -3, -4,
+/// 1/2^8, 1/4, 1/8, 1/2^27, -1/2^8, -1/16, -1/2^27.
+const INTERNAL_MATRIX_DIAGONAL: [BabyBear; STATE_WIDTH] = [
+    MontFp!("2013265919"),
+    MontFp!("1"),
+    MontFp!("2"),
+    MontFp!("1006632961"),
+    MontFp!("3"),
+    MontFp!("4"),
+    MontFp!("1006632960"),
+    MontFp!("2013265918"),
+    MontFp!("2013265917"),
+    MontFp!("2005401601"),
+    MontFp!("1509949441"),
+    MontFp!("1761607681"),
+    MontFp!("2013265906"),
+    MontFp!("7864320"),
+    MontFp!("125829120"),
+    MontFp!("15"),
+];
+
+/// Applies the Poseidon2 permutation to `state` in place.
+///
+/// Matches Plonky3's `default_babybear_poseidon2_16().permute_mut(state)` element for element.
+pub fn poseidon2_permutation(state: &mut [BabyBear; STATE_WIDTH]) {
+    plonky3::permute(
+        state,
+        &EXTERNAL_INITIAL_CONSTANTS,
+        &INTERNAL_CONSTANTS,
+        &EXTERNAL_TERMINAL_CONSTANTS,
+        &INTERNAL_MATRIX_DIAGONAL,
+    );
+}
+
+/// Poseidon2 over BabyBear as a sponge [`Permutation`] (rate 8, capacity 8, 248 bits).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Poseidon2BabyBear;
+
+impl Permutation for Poseidon2BabyBear {
+    type Field = BabyBear;
+
+    const WIDTH: usize = STATE_WIDTH;
+
+    const RATE: usize = 8;
+
+    const ID: &'static str = "poseidon2-babybear";
+
+    fn permute(&self, state: &mut [BabyBear]) {
+        let state: &mut [BabyBear; STATE_WIDTH] = state
+            .try_into()
+            .expect("Poseidon2 state must have 16 elements");
+        poseidon2_permutation(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counting_state_vector() {
+        // Known answer from Plonky3 0.8.
+        let mut state: [BabyBear; STATE_WIDTH] = core::array::from_fn(|i| BabyBear::from(i as u64));
+        poseidon2_permutation(&mut state);
+
+        let expected: [u64; STATE_WIDTH] = [
+            1906786279, 1737026427, 1959749225, 700325316, 1638050605, 1021608788, 1726691001,
+            1761127344, 1552405120, 417318995, 36799261, 1215172152, 614923223, 1300746575,
+            957311597, 304856115,
+        ];
+        assert_eq!(state, expected.map(BabyBear::from));
+    }
+}
@@ -0,0 +1,205 @@
+//! Poseidon2 over the 64-bit Goldilocks field.
+//!
+//! The parameters (state width 8, 8 full rounds, 22 partial rounds, x^7 S-box) and constants
+//! are those of Plonky3's `default_goldilocks_poseidon2_8`, so a sponge over it can be checked by a
+//! Plonky3 circuit.
+
+use ark_ff::MontFp;
+
+use super::plonky3;
+use crate::fields::Goldilocks;
+use crate::permutation::Permutation;
+
+/// Width of the permutation state in field elements.
+pub const STATE_WIDTH: usize = 8;
+
+/// Number of full (external) rounds, split evenly before and after the partial rounds.
+pub const ROUNDS_F: usize = 8;
+
+/// Number of partial (internal) rounds.
+pub const ROUNDS_P: usize = 22;
+
+/// Round constants of the first 4 full rounds.
+const EXTERNAL_INITIAL_CONSTANTS: [[Goldilocks; STATE_WIDTH]; ROUNDS_F / 2] = [
+    [
+        MontFp!("15949291268843349465"),
+        MontFp!("14644164809401934923"),
+        MontFp!("18420360874837380316"),
+        MontFp!("4756469047455716334"),
+        MontFp!("8685499049481102115"),
+        MontFp!("3799221349720045367"),
+        MontFp!("13676397835037157930"),
+        MontFp!("6566439050423619635"),
+    ],
+    [
+        MontFp!("17428268347612331188"),
+        MontFp!("2833135872454503769"),
+        MontFp!("4767009016213040191"),
+        MontFp!("2797635963551733652"),
+        MontFp!("5312339450141126694"),
+        MontFp!("5356668452102813289"),
+        MontFp!("1234059326449530173"),
+        MontFp!("7724302552453704877"),
+    ],
+    [
+        MontFp!("14868588146468890290"),
+        MontFp!("12825281145595371185"),
+        MontFp!("13097885453579304196"),
+        MontFp!("7905326782341128063"),
+        MontFp!("14167525334039893569"),
+        MontFp!("2082169701994688927"),
+        MontFp!("12190787523818595537"),
+        MontFp!("12602917751946636"),
+    ],
+    [
+        MontFp!("14890907856876319003"),
+        MontFp!("16552240149997473409"),
+        MontFp!("5634093690795187558"),
+        MontFp!("4883714163685656967"),
+        MontFp!("12440776365164557866"),
+        MontFp!("3923800234666204307"),
+        MontFp!("9858064884105950259"),
+        MontFp!("16040043470428402038"),
+    ],
+];
+
+/// Round constants of the partial rounds, added to the first element.
+const INTERNAL_CONSTANTS: [Goldilocks; ROUNDS_P] = [
+    MontFp!("5226594323142090582"),
+    MontFp!("1243120476974621208"),
+    MontFp!("12100812801659301173"),
+    MontFp!("11228203327983058121"),
+    MontFp!("13891617888374767564"),
+    MontFp!("5742893160230537107"),
+    MontFp!("3763472116988983643"),
+    MontFp!("2466655769425769160"),
+    MontFp!("6254574254498162968"),
+    MontFp!("14183251225809189357"),
+    MontFp!("11565357354521717084"),
+    MontFp!("17300657704266685688"),
+    MontFp!("310485250821938281"),
+    MontFp!("16853586468012618118"),
+    MontFp!("1978800426240373849"),
+    MontFp!("6948188224235462572"),
+    MontFp!("1486402152218690509"),
+    MontFp!("5669161690283398991"),
+    MontFp!("17943970877073781734"),
+    MontFp!("17926851897715769433"),
+    MontFp!("13052837496695000666"),
+    MontFp!("18138113741095562305"),
+];
+
+/// Round constants of the last 4 full rounds.
+const EXTERNAL_TERMINAL_CONSTANTS: [[Goldilocks; STATE_WIDTH]; ROUNDS_F / 2] = [
+    [
+        MontFp!("94277733998400326"),
+        MontFp!("10891359798487446420"),
+        MontFp!("18280773820738154043"),
+        MontFp!("13714589910668449566"),
+        MontFp!("10639034072771185213"),
+        MontFp!("14148790895768484219"),
+        MontFp!("18341268649720100165"),
+        MontFp!("3096672942770686236"),
+    ],
+    [
+        MontFp!("12277596046563557393"),
+        MontFp!("400461754528604020"),
+        MontFp!("12955488253560265444"),
+        MontFp!("11773677676764285572"),
+        MontFp!("4833837465239476573"),
+        MontFp!("17645852643693996619"),
+        MontFp!("6605134696140007471"),
+        MontFp!("588040525114200273"),
+    ],
+    [
+        MontFp!("11001741536026769411"),
+        MontFp!("17917086578469406776"),
+        MontFp!("14893530806420712543"),
+        MontFp!("727997185253761138"),
+        MontFp!("3443873847340254325"),
+        MontFp!("13095911531247069692"),
+        MontFp!("8330737046680948619"),
+        MontFp!("6014364575875986011"),
+    ],
+    [
+        MontFp!("16851679856681761121"),
+        MontFp!("17817965496543149594"),
+        MontFp!("12823640325246269760"),
+        MontFp!("13685256787930775147"),
+        MontFp!("4682652317564502291"),
+        MontFp!("4233879762155685988"),
+        MontFp!("11097258179564187322"),
+        MontFp!("10804761421745472094"),
+    ],
+];
+
+/// Diagonal of the internal matrix (`M_I = diag(...) + J`): -2, 1, 2, 1/2, 3, -1/2, -3, -4.
+const INTERNAL_MATRIX_DIAGONAL: [Goldilocks; STATE_WIDTH] = [
+    MontFp!("18446744069414584319"),
+    MontFp!("1"),
+    MontFp!("2"),
+    MontFp!("9223372034707292161"),
+    MontFp!("3"),
+    MontFp!("9223372034707292160"),
+    MontFp!("18446744069414584318"),
+    MontFp!("18446744069414584317"),
+];
+
+/// Applies the Poseidon2 permutation to `state` in place.
+///
+/// Matches Plonky3's `default_goldilocks_poseidon2_8().permute_mut(state)` element for element.
+pub fn poseidon2_permutation(state: &mut [Goldilocks; STATE_WIDTH]) {
+    plonky3::permute(
+        state,
+        &EXTERNAL_INITIAL_CONSTANTS,
+        &INTERNAL_CONSTANTS,
+        &EXTERNAL_TERMINAL_CONSTANTS,
+        &INTERNAL_MATRIX_DIAGONAL,
+    );
+}
+
+/// Poseidon2 over Goldilocks as a sponge [`Permutation`] (rate 4, capacity 4, 256 bits).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Poseidon2Goldilocks;
+
+impl Permutation for Poseidon2Goldilocks {
+    type Field = Goldilocks;
+
+    const WIDTH: usize = STATE_WIDTH;
+
+    const RATE: usize = 4;
+
+    const ID: &'static str = "poseidon2-goldilocks";
+
+    fn permute(&self, state: &mut [Goldilocks]) {
+        let state: &mut [Goldilocks; STATE_WIDTH] = state
+            .try_into()
+            .expect("Poseidon2 state must have 8 elements");
+        poseidon2_permutation(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counting_state_vector() {
+        // Known answer from Plonky3 0.8.
+        let mut state: [Goldilocks; STATE_WIDTH] =
+            core::array::from_fn(|i| Goldilocks::from(i as u64));
+        poseidon2_permutation(&mut state);
+
+        let expected: [u64; STATE_WIDTH] = [
+            147757088869797140,
+            9575016380710448421,
+            1936818598401832023,
+            11876560440185116837,
+            933995228215617535,
+            8686652366030325867,
+            8297621998173199092,
+            17588152731440920923,
+        ];
+        assert_eq!(state, expected.map(Goldilocks::from));
+    }
+}
@@ -0,0 +1,65 @@
+//! The Poseidon2 rounds of Plonky3, shared by the small-field instances.
+//!
+//! The S-box is x^7, the external matrix applies a 4x4 MDS matrix `M_4` to each block of four
+//! elements then adds the sums of the elements of same index modulo 4 (Plonky3's
+//! `mds_light_permutation`), and the internal matrix is `diag(...) + J`.
+
+use ark_ff::PrimeField;
+
+/// Applies Poseidon2 to `state`: the external matrix, the initial full rounds, the partial
+/// rounds and the terminal full rounds.
+pub(super) fn permute<F: PrimeField, const WIDTH: usize>(
+    state: &mut [F; WIDTH],
+    initial_constants: &[[F; WIDTH]],
+    internal_constants: &[F],
+    terminal_constants: &[[F; WIDTH]],
+    internal_diagonal: &[F; WIDTH],
+) {
+    external_matrix_multiplication(state);
+    for constants in initial_constants {
+        full_round(state, constants);
+    }
+    for constant in internal_constants {
+        state[0] = single_box(state[0] + constant);
+        let sum: F = state.iter().sum();
+        for (element, diagonal) in state.iter_mut().zip(internal_diagonal) {
+            *element = *element * diagonal + sum;
+        }
+    }
+    for constants in terminal_constants {
+        full_round(state, constants);
+    }
+}
+
+fn full_round<F: PrimeField, const WIDTH: usize>(state: &mut [F; WIDTH], constants: &[F; WIDTH]) {
+    for (element, constant) in state.iter_mut().zip(constants) {
+        *element = single_box(*element + constant);
+    }
+    external_matrix_multiplication(state);
+}
+
+fn single_box<F: PrimeField>(x: F) -> F {
+    let x2 = x * x;
+    let x3 = x2 * x;
+    x3 * x2 * x2
+}
+
+/// Multiplies the state by `circ(2 M_4, M_4, ..., M_4)`.
+fn external_matrix_multiplication<F: PrimeField, const WIDTH: usize>(state: &mut [F; WIDTH]) {
+    for chunk in state.chunks_exact_mut(4) {
+        // [[2, 3, 1, 1], [1, 2, 3, 1], [1, 1, 2, 3], [3, 1, 1, 2]]
+        let t01 = chunk[0] + chunk[1];
+        let t23 = chunk[2] + chunk[3];
+        let t0123 = t01 + t23;
+        let t01123 = t0123 + chunk[1];
+        let t01233 = t0123 + chunk[3];
+        chunk[3] = t01233 + chunk[0].double();
+        chunk[1] = t01123 + chunk[2].double();
+        chunk[0] = t01123 + t01;
+        chunk[2] = t01233 + t23;
+    }
+    let sums: [F; 4] = core::array::from_fn(|k| state.iter().skip(k).step_by(4).copied().sum());
+    for (i, element) in state.iter_mut().enumerate() {
+        *element += sums[i % 4];
+    }
+}
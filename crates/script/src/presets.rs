@@ -0,0 +1,212 @@
+//! Ready-made transcripts for common protocols.
+//!
+//! # Range proof
+//!
+//! [`RangeProofTranscript`] follows the Fiat-Shamir flow of a Bulletproofs-style range proof
+//! for an `n`-bit value, with group elements given as their two affine coordinates (e.g.
+//! Grumpkin points over the BN254 scalar field):
+//!
+//! 1. absorb the value commitment `V`, then the bit commitments `A` and `S`,
+//! 2. squeeze the challenges `y` and `z`,
+//! 3. absorb the polynomial commitments `T1` and `T2`, squeeze `x`,
+//! 4. for each of the `log2(n)` rounds of the inner product argument, absorb `L_i` and `R_i`
+//!    and squeeze `u_i`.
+//!
+//! The IO pattern, and therefore the tag, depends on the bit length (see
+//! [`range_proof_pattern`] and [`range_proof_tags`]).
+
+use alloc::format;
+use alloc::vec::Vec;
+
+use crate::io_pattern::IoPattern;
+use crate::permutation::Permutation;
+use crate::tag::Tag;
+use crate::transcript::{transcript_domain_separator, Transcript};
+
+/// Number of field elements of an absorbed group element.
+pub const POINT_SIZE: u32 = 2;
+
+/// Bit lengths for which the range proof tags are published.
+pub const RANGE_PROOF_BIT_LENGTHS: [u32; 4] = [8, 16, 32, 64];
+
+/// Domain separator of the range proof preset.
+pub fn range_proof_domain_separator() -> [u8; 64] {
+    let mut domain_separator = [0u8; 64];
+    let label = b"safe-api/range-proof/v1";
+    domain_separator[..label.len()].copy_from_slice(label);
+    domain_separator
+}
+
+/// Returns the labeled IO pattern of a range proof over `bits`-bit values.
+///
+/// # Panics
+/// If `bits` is not a power of two.
+pub fn range_proof_pattern(bits: u32) -> IoPattern {
+    assert!(bits.is_power_of_two(), "Bit length must be a power of two");
+
+    let mut pattern = IoPattern::new()
+        .absorb_labeled("V", POINT_SIZE)
+        .absorb_labeled("A", POINT_SIZE)
+        .absorb_labeled("S", POINT_SIZE)
+        .squeeze_labeled("y", 1)
+        .squeeze_labeled("z", 1)
+        .absorb_labeled("T1", POINT_SIZE)
+        .absorb_labeled("T2", POINT_SIZE)
+        .squeeze_labeled("x", 1);
+    for round in 0..bits.trailing_zeros() {
+        pattern = pattern
+            .absorb_labeled(&format!("L_{}", round), POINT_SIZE)
+            .absorb_labeled(&format!("R_{}", round), POINT_SIZE)
+            .squeeze_labeled(&format!("u_{}", round), 1);
+    }
+    pattern
+}
+
+/// Returns the tag of the range proof transcript for each of [`RANGE_PROOF_BIT_LENGTHS`], as
+/// used by the circuit (the labels are bound into the domain separator).
+pub fn range_proof_tags() -> Vec<(u32, Tag)> {
+    RANGE_PROOF_BIT_LENGTHS
+        .iter()
+        .map(|&bits| {
+            let pattern = range_proof_pattern(bits);
+            let domain_separator =
+                transcript_domain_separator(&pattern, &range_proof_domain_separator());
+            (bits, pattern.tag(domain_separator))
+        })
+        .collect()
+}
+
+/// The transcript of a Bulletproofs-style range proof, see the [module documentation](self).
+///
+/// Each step absorbs the prover messages and returns the challenges derived from them; calling
+/// the steps out of order panics.
+#[derive(Clone, Debug)]
+pub struct RangeProofTranscript<P: Permutation> {
+    transcript: Transcript<P>,
+    rounds: u32,
+    round: u32,
+}
+
+impl<P: Permutation + Default> RangeProofTranscript<P> {
+    /// Starts the transcript of a range proof over `bits`-bit values.
+    ///
+    /// # Panics
+    /// If `bits` is not a power of two.
+    pub fn new(bits: u32) -> Self {
+        RangeProofTranscript {
+            transcript: Transcript::new(range_proof_pattern(bits), range_proof_domain_separator()),
+            rounds: bits.trailing_zeros(),
+            round: 0,
+        }
+    }
+}
+
+impl<P: Permutation> RangeProofTranscript<P> {
+    /// Absorbs the commitments `V`, `A` and `S`, and returns the challenges `(y, z)`.
+    pub fn commit_bits(
+        &mut self,
+        v: &[P::Field; 2],
+        a: &[P::Field; 2],
+        s: &[P::Field; 2],
+    ) -> (P::Field, P::Field) {
+        self.transcript.append("V", v);
+        self.transcript.append("A", a);
+        self.transcript.append("S", s);
+        let y = self.transcript.challenge("y", 1)[0];
+        let z = self.transcript.challenge("z", 1)[0];
+        (y, z)
+    }
+
+    /// Absorbs the commitments `T1` and `T2`, and returns the challenge `x`.
+    pub fn commit_polynomial(&mut self, t1: &[P::Field; 2], t2: &[P::Field; 2]) -> P::Field {
+        self.transcript.append("T1", t1);
+        self.transcript.append("T2", t2);
+        self.transcript.challenge("x", 1)[0]
+    }
+
+    /// Absorbs `L_i` and `R_i` of the next inner product round, and returns its challenge `u_i`.
+    ///
+    /// # Panics
+    /// If all `log2(bits)` rounds have been run.
+    pub fn ipa_round(&mut self, l: &[P::Field; 2], r: &[P::Field; 2]) -> P::Field {
+        assert!(self.round < self.rounds, "All rounds already run");
+        let round = self.round;
+        self.round += 1;
+
+        self.transcript.append(&format!("L_{}", round), l);
+        self.transcript.append(&format!("R_{}", round), r);
+        self.transcript.challenge(&format!("u_{}", round), 1)[0]
+    }
+
+    /// Checks that every round has been run and erases the state.
+    pub fn finish(&mut self) {
+        self.transcript.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_proof_pattern() {
+        let pattern = range_proof_pattern(8);
+        assert_eq!(pattern.len(), 8 + 3 * 3);
+        assert_eq!(pattern.squeeze_index("u_2"), Some(16));
+        assert_eq!(
+            pattern.normalized(),
+            IoPattern::new()
+                .absorb(6)
+                .squeeze(2)
+                .absorb(4)
+                .squeeze(1)
+                .absorb(4)
+                .squeeze(1)
+                .absorb(4)
+                .squeeze(1)
+                .absorb(4)
+                .squeeze(1)
+        );
+
+        let tags = range_proof_tags();
+        assert_eq!(tags.len(), RANGE_PROOF_BIT_LENGTHS.len());
+        for (index, &(_, tag)) in tags.iter().enumerate() {
+            assert!(tags[..index].iter().all(|&(_, other)| other != tag));
+        }
+    }
+
+    #[cfg(feature = "poseidon2")]
+    #[test]
+    fn test_range_proof_flow() {
+        use crate::poseidon2::Poseidon2;
+        use ark_bn254::Fr;
+
+        let point = |seed: u64| [Fr::from(seed), Fr::from(seed + 1)];
+        let mut transcript = RangeProofTranscript::<Poseidon2>::new(4);
+        let (y, z) = transcript.commit_bits(&point(1), &point(3), &point(5));
+        let x = transcript.commit_polynomial(&point(7), &point(9));
+        let u0 = transcript.ipa_round(&point(11), &point(13));
+        let u1 = transcript.ipa_round(&point(15), &point(17));
+        transcript.finish();
+
+        let challenges = [y, z, x, u0, u1];
+        for (index, challenge) in challenges.iter().enumerate() {
+            assert!(challenges[..index].iter().all(|other| other != challenge));
+        }
+    }
+
+    #[cfg(feature = "poseidon2")]
+    #[test]
+    #[should_panic(expected = "IO pattern not completed")]
+    fn test_missing_rounds_panic() {
+        use crate::poseidon2::Poseidon2;
+        use ark_bn254::Fr;
+
+        let point = [Fr::from(1u64), Fr::from(2u64)];
+        let mut transcript = RangeProofTranscript::<Poseidon2>::new(4);
+        transcript.commit_bits(&point, &point, &point);
+        transcript.commit_polynomial(&point, &point);
+        transcript.ipa_round(&point, &point);
+        transcript.finish();
+    }
+}
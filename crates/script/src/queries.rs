@@ -0,0 +1,173 @@
+//! Query indices derived from transcript challenges, e.g. for FRI-like protocols or data
+//! availability sampling.
+//!
+//! The domain has a power-of-two size `2^k`, and each challenge element yields the index made
+//! of its `k` low bits. Since an IO pattern fixes the length of each squeeze, the protocol
+//! declares the challenge with [`QueryPolicy::challenge_len`] elements; under
+//! [`QueryPolicy::Resample`], the extra elements replace duplicate indices in order. The Noir
+//! `derive_queries` implements the same logic over the squeezed elements:
+//!
+//! ```
+//! use safe_api::domain::padded_domain_separator;
+//! use safe_api::poseidon2::Poseidon2;
+//! use safe_api::queries::{derive_queries, QueryPolicy};
+//! use safe_api::{IoPattern, Transcript};
+//!
+//! let policy = QueryPolicy::Resample { extra: 8 };
+//! let protocol = IoPattern::new()
+//!     .absorb_labeled("root", 1)
+//!     .squeeze_labeled("queries", policy.challenge_len(16) as u32);
+//! let domain_separator = padded_domain_separator(b"my-app/queries");
+//! let mut transcript = Transcript::<Poseidon2>::new(protocol, &domain_separator);
+//! transcript.append("root", &[ark_bn254::Fr::from(42u64)]);
+//! let queries = derive_queries(&mut transcript, "queries", 1024, 16, policy)?;
+//! transcript.finish();
+//! # assert_eq!(queries.len(), 16);
+//! # Ok::<(), safe_api::queries::QueryError>(())
+//! ```
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use ark_ff::PrimeField;
+
+use crate::permutation::Permutation;
+use crate::transcript::Transcript;
+
+/// How [`derive_queries`] treats an index that was already drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueryPolicy {
+    /// Keep it: the queries are independent samples and may repeat.
+    AllowDuplicates,
+    /// Skip it and use the next of `extra` additional challenge elements, so the queries are
+    /// distinct.
+    Resample { extra: usize },
+}
+
+impl QueryPolicy {
+    /// Returns the number of challenge elements the protocol declares for `n_queries` queries.
+    pub fn challenge_len(&self, n_queries: usize) -> usize {
+        match self {
+            QueryPolicy::AllowDuplicates => n_queries,
+            QueryPolicy::Resample { extra } => n_queries + extra,
+        }
+    }
+}
+
+/// Error of [`derive_queries`] under [`QueryPolicy::Resample`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueryError {
+    /// Number of distinct indices drawn before the challenge ran out.
+    pub distinct: usize,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "only {} distinct queries before the challenge ran out",
+            self.distinct
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for QueryError {}
+
+/// Returns the index in a domain of `domain_size` elements drawn from `element`.
+///
+/// # Panics
+/// If `domain_size` is not a power of two.
+pub fn query_index<F: PrimeField>(element: F, domain_size: usize) -> usize {
+    assert!(
+        domain_size.is_power_of_two(),
+        "Domain size must be a power of two"
+    );
+    let low = element.into_bigint().as_ref()[0];
+    (low & (domain_size as u64 - 1)) as usize
+}
+
+/// Derives `n_queries` indices in a domain of `domain_size` elements from the challenge
+/// `label`, which the protocol declares with `policy.challenge_len(n_queries)` elements.
+///
+/// The indices are returned in the order they were drawn. Fails if the policy resamples and the
+/// challenge yields fewer than `n_queries` distinct indices, which is certain if `n_queries`
+/// exceeds `domain_size`.
+///
+/// # Panics
+/// If `domain_size` is not a power of two, or if the protocol does not expect the challenge
+/// `label` of that length next.
+pub fn derive_queries<P: Permutation>(
+    transcript: &mut Transcript<P>,
+    label: &str,
+    domain_size: usize,
+    n_queries: usize,
+    policy: QueryPolicy,
+) -> Result<Vec<usize>, QueryError> {
+    let challenge = transcript.challenge(label, policy.challenge_len(n_queries));
+    let mut queries = Vec::with_capacity(n_queries);
+    for element in challenge {
+        let index = query_index(element, domain_size);
+        if queries.len() < n_queries
+            && (policy == QueryPolicy::AllowDuplicates || !queries.contains(&index))
+        {
+            queries.push(index);
+        }
+    }
+
+    if queries.len() < n_queries {
+        return Err(QueryError {
+            distinct: queries.len(),
+        });
+    }
+    Ok(queries)
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::domain::padded_domain_separator;
+    use crate::io_pattern::IoPattern;
+    use crate::poseidon2::Poseidon2;
+    use ark_bn254::Fr;
+
+    fn transcript(policy: QueryPolicy, n_queries: usize) -> Transcript<Poseidon2> {
+        let protocol = IoPattern::new()
+            .absorb_labeled("root", 1)
+            .squeeze_labeled("queries", policy.challenge_len(n_queries) as u32);
+        let mut transcript =
+            Transcript::<Poseidon2>::new(protocol, padded_domain_separator(b"test"));
+        transcript.append("root", &[Fr::from(42u64)]);
+        transcript
+    }
+
+    #[test]
+    fn test_query_index() {
+        assert_eq!(query_index(Fr::from(0x1234u64), 256), 0x34);
+        assert_eq!(query_index(-Fr::from(1u64), 1), 0);
+    }
+
+    #[test]
+    fn test_policies() {
+        // Every element is used, repeated or not.
+        let mut allowed = transcript(QueryPolicy::AllowDuplicates, 4);
+        let queries = derive_queries(&mut allowed, "queries", 4, 4, QueryPolicy::AllowDuplicates);
+        assert_eq!(queries.as_ref().map(Vec::len), Ok(4));
+
+        let policy = QueryPolicy::Resample { extra: 28 };
+        let mut resampled = transcript(policy, 4);
+        let mut queries = derive_queries(&mut resampled, "queries", 4, 4, policy).unwrap();
+        queries.sort();
+        assert_eq!(queries, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_exhausted() {
+        let policy = QueryPolicy::Resample { extra: 2 };
+        let mut transcript = transcript(policy, 3);
+        assert_eq!(
+            derive_queries(&mut transcript, "queries", 2, 3, policy),
+            Err(QueryError { distinct: 2 })
+        );
+    }
+}
@@ -0,0 +1,594 @@
+//! A debug recorder of sponge sessions, to find where an implementation diverges from this one.
+//!
+//! A [`Recorder`] drives a [`Sponge`] and logs every absorb and squeeze: its position in the IO
+//! pattern, its values, and the full state after each permutation it triggers. The resulting
+//! [`Trace`] serializes to JSON:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "io_pattern": ["0x80000002", "0x00000001"],
+//!   "tag": "0x000000000000000000000000000000000ea2aa7e178caa74de1f91e83ad43a81",
+//!   "initial_state": ["0x...0ea2aa7e178caa74de1f91e83ad43a81", "0x...00", "0x...00", "0x...00"],
+//!   "events": [
+//!     { "operation": 0, "kind": "absorb", "values": ["0x...7b", "0x...01c8"], "permutations": [] },
+//!     { "operation": 1, "kind": "squeeze", "values": ["0x...ab"], "permutations": [["0x...", ...]] }
+//!   ]
+//! }
+//! ```
+//!
+//! Field elements are 0x-prefixed hex strings, so a trace does not depend on the field, and
+//! [`diff`] accepts any width and case: a Noir test can print the same document from the
+//! state of its `SafeSponge` (e.g. with `println`) and [`diff`] reports the first element at
+//! which the two sessions differ. `safe-api trace record` and `safe-api trace diff` do the
+//! same from the command line.
+//!
+//! Each event also holds the whole `state` after the operation, so a [`TraceCursor`] steps
+//! backward and forward through a recorded session and shows which elements every operation
+//! changed; `safe-api trace debug` does so interactively. Traces without states, e.g. printed
+//! by Noir, still load and compare.
+
+use core::cell::RefCell;
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::display::format_element;
+use crate::io_pattern::IoPattern;
+use crate::permutation::Permutation;
+use crate::sponge::{Sponge, SpongeBuilder};
+
+/// Version of the JSON schema written by [`Trace::to_json`].
+pub const TRACE_VERSION: u32 = 1;
+
+/// A recorded sponge session.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trace {
+    /// Schema version, [`TRACE_VERSION`].
+    pub version: u32,
+    /// The IO pattern, one encoded word per operation.
+    #[serde(with = "crate::vectors::hex_words")]
+    pub io_pattern: Vec<u32>,
+    /// The tag the sponge was initialized with.
+    pub tag: String,
+    /// The state before the first operation, capacity first.
+    pub initial_state: Vec<String>,
+    /// The operations performed, in order.
+    pub events: Vec<TraceEvent>,
+}
+
+/// One absorb or squeeze of a [`Trace`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceEvent {
+    /// Index of the operation in the IO pattern.
+    pub operation: usize,
+    /// Whether the operation absorbs or squeezes.
+    pub kind: EventKind,
+    /// The absorbed or squeezed elements.
+    pub values: Vec<String>,
+    /// The whole state after each permutation run during the operation.
+    pub permutations: Vec<Vec<String>>,
+    /// The whole state after the operation, empty if not recorded.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub state: Vec<String>,
+}
+
+/// The kind of a [`TraceEvent`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Absorb,
+    Squeeze,
+}
+
+/// Errors raised while loading a trace.
+#[derive(Debug)]
+pub enum TraceError {
+    /// The JSON document is malformed or does not follow the schema.
+    Json(serde_json::Error),
+    /// The document was written with an unsupported schema version.
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceError::Json(error) => write!(f, "invalid trace JSON: {}", error),
+            TraceError::UnsupportedVersion(version) => {
+                write!(f, "unsupported trace schema version {}", version)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TraceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TraceError::Json(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for TraceError {
+    fn from(error: serde_json::Error) -> Self {
+        TraceError::Json(error)
+    }
+}
+
+impl Trace {
+    /// Serializes the trace to JSON (pretty printed).
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("traces always serialize")
+    }
+
+    /// Loads a trace from JSON.
+    pub fn from_json(json: &str) -> Result<Trace, TraceError> {
+        let trace: Trace = serde_json::from_str(json)?;
+        if trace.version != TRACE_VERSION {
+            return Err(TraceError::UnsupportedVersion(trace.version));
+        }
+        Ok(trace)
+    }
+}
+
+/// A permutation that logs the state after each call.
+struct Recording<P: Permutation> {
+    inner: P,
+    states: RefCell<Vec<Vec<String>>>,
+}
+
+impl<P: Permutation> Permutation for Recording<P> {
+    type Field = P::Field;
+    const WIDTH: usize = P::WIDTH;
+    const RATE: usize = P::RATE;
+    const CAPACITY: usize = P::CAPACITY;
+    const ID: &'static str = P::ID;
+
+    fn permute(&self, state: &mut [Self::Field]) {
+        self.inner.permute(state);
+        self.states
+            .borrow_mut()
+            .push(state.iter().map(format_element).collect());
+    }
+}
+
+/// A [`Sponge`] that records its session as a [`Trace`].
+///
+/// It panics on IO pattern violations exactly like the sponge it wraps.
+pub struct Recorder<P: Permutation> {
+    sponge: Sponge<Recording<P>>,
+    trace: Trace,
+}
+
+impl<P: Permutation + Default> Recorder<P> {
+    /// Starts recording a session, as [`Sponge::start`].
+    pub fn start(io_pattern: IoPattern, domain_separator: &[u8; 64]) -> Self {
+        Self::with_permutation(P::default(), io_pattern, domain_separator)
+    }
+}
+
+impl<P: Permutation> Recorder<P> {
+    /// Starts recording a session with an explicit permutation instance.
+    pub fn with_permutation(
+        permutation: P,
+        io_pattern: IoPattern,
+        domain_separator: &[u8; 64],
+    ) -> Self {
+        let recording = Recording {
+            inner: permutation,
+            states: RefCell::new(Vec::new()),
+        };
+        let words = io_pattern.encoded_words();
+        let sponge = SpongeBuilder::new(recording).start(io_pattern, domain_separator);
+        let trace = Trace {
+            version: TRACE_VERSION,
+            io_pattern: words,
+            tag: format_element(&sponge.tag()),
+            initial_state: sponge.state().iter().map(format_element).collect(),
+            events: Vec::new(),
+        };
+        Recorder { sponge, trace }
+    }
+
+    /// Absorbs `input`, as [`Sponge::absorb`].
+    pub fn absorb(&mut self, input: &[P::Field]) {
+        let operation = self.sponge.operations_done();
+        self.sponge.absorb(input);
+        self.record(operation, EventKind::Absorb, input);
+    }
+
+    /// Squeezes the next operation, as [`Sponge::squeeze`].
+    pub fn squeeze(&mut self) -> Vec<P::Field> {
+        let operation = self.sponge.operations_done();
+        let output = self.sponge.squeeze();
+        self.record(operation, EventKind::Squeeze, &output);
+        output
+    }
+
+    /// The trace recorded so far.
+    pub fn trace(&self) -> &Trace {
+        &self.trace
+    }
+
+    /// Finishes the session, as [`Sponge::finish`], and returns its trace.
+    ///
+    /// # Panics
+    /// If operations of the IO pattern remain.
+    pub fn finish(self) -> Trace {
+        let Recorder { mut sponge, trace } = self;
+        sponge.finish();
+        trace
+    }
+
+    fn record(&mut self, operation: usize, kind: EventKind, values: &[P::Field]) {
+        let permutations = self.sponge.permutation().states.take();
+        self.trace.events.push(TraceEvent {
+            operation,
+            kind,
+            values: values.iter().map(format_element).collect(),
+            permutations,
+            state: self.sponge.state().iter().map(format_element).collect(),
+        });
+    }
+}
+
+/// A position in a [`Trace`], from the initial state (step 0) to the state after its last
+/// event, to step through a recorded session.
+#[derive(Clone, Debug)]
+pub struct TraceCursor<'a> {
+    trace: &'a Trace,
+    step: usize,
+}
+
+/// An element of the state changed by an operation, found by [`TraceCursor::changes`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StateChange {
+    /// Index of the element in the state, capacity first.
+    pub index: usize,
+    /// The element before the operation.
+    pub before: String,
+    /// The element after the operation.
+    pub after: String,
+}
+
+impl<'a> TraceCursor<'a> {
+    /// Returns a cursor at the initial state of `trace`.
+    pub fn new(trace: &'a Trace) -> Self {
+        TraceCursor { trace, step: 0 }
+    }
+
+    /// Returns the current step: the number of events run.
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    /// Returns the last step, the number of events of the trace.
+    pub fn last_step(&self) -> usize {
+        self.trace.events.len()
+    }
+
+    /// Moves to the next step, returning `false` at the last one.
+    pub fn forward(&mut self) -> bool {
+        self.seek(self.step + 1)
+    }
+
+    /// Moves to the previous step, returning `false` at the initial state.
+    pub fn back(&mut self) -> bool {
+        self.step > 0 && self.seek(self.step - 1)
+    }
+
+    /// Moves to `step`, returning `false`, without moving, past the last step.
+    pub fn seek(&mut self, step: usize) -> bool {
+        let valid = step <= self.last_step();
+        if valid {
+            self.step = step;
+        }
+        valid
+    }
+
+    /// Returns the event run to reach the current step, `None` at the initial state.
+    pub fn event(&self) -> Option<&'a TraceEvent> {
+        self.step
+            .checked_sub(1)
+            .map(|index| &self.trace.events[index])
+    }
+
+    /// Returns the state at the current step, `None` if the trace does not record it.
+    pub fn state(&self) -> Option<&'a [String]> {
+        state_at(self.trace, self.step)
+    }
+
+    /// Returns the elements changed by the event run to reach the current step, empty at the
+    /// initial state or if the trace lacks a state.
+    pub fn changes(&self) -> Vec<StateChange> {
+        let (Some(before), Some(after)) = (
+            self.step
+                .checked_sub(1)
+                .and_then(|step| state_at(self.trace, step)),
+            self.state(),
+        ) else {
+            return Vec::new();
+        };
+        before
+            .iter()
+            .zip(after)
+            .enumerate()
+            .filter(|(_, (before, after))| normalize(before) != normalize(after))
+            .map(|(index, (before, after))| StateChange {
+                index,
+                before: before.clone(),
+                after: after.clone(),
+            })
+            .collect()
+    }
+}
+
+fn state_at(trace: &Trace, step: usize) -> Option<&[String]> {
+    let state = match step {
+        0 => &trace.initial_state,
+        step => &trace.events[step - 1].state,
+    };
+    (!state.is_empty()).then_some(state.as_slice())
+}
+
+/// The first point at which two traces differ, found by [`diff`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Where the traces differ, as a path into the JSON document, e.g.
+    /// `events[1].permutations[0][2]`.
+    pub path: String,
+    /// The value in the expected trace, `none` if it is missing.
+    pub expected: String,
+    /// The value in the actual trace, `none` if it is missing.
+    pub actual: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "traces diverge at `{}`: expected {}, got {}",
+            self.path, self.expected, self.actual
+        )
+    }
+}
+
+/// Compares two traces and returns the first point at which they differ, if any.
+///
+/// Elements are compared as numbers, whatever their width and case, so that a trace printed
+/// by Noir compares equal to the one of the same session recorded here.
+pub fn diff(expected: &Trace, actual: &Trace) -> Option<Divergence> {
+    if expected.io_pattern != actual.io_pattern {
+        return Some(Divergence {
+            path: String::from("io_pattern"),
+            expected: format!("{:08x?}", expected.io_pattern),
+            actual: format!("{:08x?}", actual.io_pattern),
+        });
+    }
+    if let Some(divergence) = diff_element("tag", &expected.tag, &actual.tag) {
+        return Some(divergence);
+    }
+    if let Some(divergence) = diff_elements(
+        "initial_state",
+        &expected.initial_state,
+        &actual.initial_state,
+    ) {
+        return Some(divergence);
+    }
+
+    let length = expected.events.len().max(actual.events.len());
+    for index in 0..length {
+        let path = format!("events[{}]", index);
+        let (expected, actual) = match (expected.events.get(index), actual.events.get(index)) {
+            (Some(expected), Some(actual)) => (expected, actual),
+            (expected, actual) => {
+                return Some(Divergence {
+                    path,
+                    expected: describe_event(expected),
+                    actual: describe_event(actual),
+                })
+            }
+        };
+        if (expected.operation, expected.kind) != (actual.operation, actual.kind) {
+            return Some(Divergence {
+                path,
+                expected: describe_event(Some(expected)),
+                actual: describe_event(Some(actual)),
+            });
+        }
+        if let Some(divergence) = diff_elements(
+            &format!("{}.values", path),
+            &expected.values,
+            &actual.values,
+        ) {
+            return Some(divergence);
+        }
+        if !expected.state.is_empty() && !actual.state.is_empty() {
+            if let Some(divergence) =
+                diff_elements(&format!("{}.state", path), &expected.state, &actual.state)
+            {
+                return Some(divergence);
+            }
+        }
+        let permutations = expected.permutations.len().max(actual.permutations.len());
+        for permutation in 0..permutations {
+            let path = format!("{}.permutations[{}]", path, permutation);
+            match (
+                expected.permutations.get(permutation),
+                actual.permutations.get(permutation),
+            ) {
+                (Some(expected), Some(actual)) => {
+                    if let Some(divergence) = diff_elements(&path, expected, actual) {
+                        return Some(divergence);
+                    }
+                }
+                (expected, actual) => {
+                    return Some(Divergence {
+                        path,
+                        expected: describe_state(expected),
+                        actual: describe_state(actual),
+                    })
+                }
+            }
+        }
+    }
+    None
+}
+
+fn diff_elements(path: &str, expected: &[String], actual: &[String]) -> Option<Divergence> {
+    let length = expected.len().max(actual.len());
+    (0..length).find_map(|index| {
+        let path = format!("{}[{}]", path, index);
+        match (expected.get(index), actual.get(index)) {
+            (Some(expected), Some(actual)) => diff_element(&path, expected, actual),
+            (expected, actual) => Some(Divergence {
+                path,
+                expected: expected.cloned().unwrap_or_else(|| String::from("none")),
+                actual: actual.cloned().unwrap_or_else(|| String::from("none")),
+            }),
+        }
+    })
+}
+
+fn diff_element(path: &str, expected: &str, actual: &str) -> Option<Divergence> {
+    (normalize(expected) != normalize(actual)).then(|| Divergence {
+        path: String::from(path),
+        expected: String::from(expected),
+        actual: String::from(actual),
+    })
+}
+
+/// The digits of a hex element without prefix, case or leading zeros.
+fn normalize(element: &str) -> String {
+    let digits = element.strip_prefix("0x").unwrap_or(element);
+    let digits = digits.trim_start_matches('0').to_ascii_lowercase();
+    match digits.is_empty() {
+        true => String::from("0"),
+        false => digits,
+    }
+}
+
+fn describe_event(event: Option<&TraceEvent>) -> String {
+    match event {
+        Some(event) => format!("{:?} of operation {}", event.kind, event.operation),
+        None => String::from("none"),
+    }
+}
+
+fn describe_state(state: Option<&Vec<String>>) -> String {
+    match state {
+        Some(state) => format!("a state of {} elements", state.len()),
+        None => String::from("none"),
+    }
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::poseidon2::Poseidon2;
+    use ark_bn254::Fr;
+
+    fn record() -> (Trace, Vec<Fr>) {
+        let mut domain_separator = [0u8; 64];
+        domain_separator[..4].copy_from_slice(b"ABCD");
+        let pattern = IoPattern::new().absorb(4).squeeze(2);
+        let mut recorder = Recorder::<Poseidon2>::start(pattern, &domain_separator);
+        recorder.absorb(&[
+            Fr::from(1u64),
+            Fr::from(2u64),
+            Fr::from(3u64),
+            Fr::from(4u64),
+        ]);
+        let output = recorder.squeeze();
+        (recorder.finish(), output)
+    }
+
+    #[test]
+    fn test_records_session() {
+        let (trace, output) = record();
+
+        let mut domain_separator = [0u8; 64];
+        domain_separator[..4].copy_from_slice(b"ABCD");
+        let mut sponge =
+            Sponge::<Poseidon2>::start(IoPattern::new().absorb(4).squeeze(2), domain_separator);
+        sponge.absorb(&[
+            Fr::from(1u64),
+            Fr::from(2u64),
+            Fr::from(3u64),
+            Fr::from(4u64),
+        ]);
+        assert_eq!(sponge.squeeze(), output);
+
+        assert_eq!(trace.io_pattern, vec![0x80000004, 0x00000002]);
+        assert_eq!(trace.tag, trace.initial_state[0]);
+        assert_eq!(trace.events.len(), 2);
+        // The fourth element fills a second block, and the squeeze permutes it.
+        assert_eq!(trace.events[0].kind, EventKind::Absorb);
+        assert_eq!(trace.events[0].permutations.len(), 1);
+        assert_eq!(trace.events[1].operation, 1);
+        assert_eq!(trace.events[1].permutations.len(), 1);
+        assert_eq!(
+            trace.events[1].values,
+            output.iter().map(format_element).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            trace.events[1].values[0],
+            trace.events[1].permutations[0][1]
+        );
+
+        assert_eq!(Trace::from_json(&trace.to_json()).unwrap(), trace);
+    }
+
+    #[test]
+    fn test_diff() {
+        let (expected, _) = record();
+        let mut actual = expected.clone();
+        // Width and case do not matter.
+        actual.tag = format!("0x{}", actual.tag[8..].to_uppercase());
+        assert_eq!(diff(&expected, &actual), None);
+
+        actual.events[1].permutations[0][2] = String::from("0x01");
+        let divergence = diff(&expected, &actual).unwrap();
+        assert_eq!(divergence.path, "events[1].permutations[0][2]");
+        assert_eq!(divergence.actual, "0x01");
+
+        actual.events.truncate(1);
+        assert_eq!(diff(&expected, &actual).unwrap().path, "events[1]");
+
+        // States are compared when both traces record them.
+        let mut actual = expected.clone();
+        actual.events[0].state[3] = String::from("0x01");
+        assert_eq!(diff(&expected, &actual).unwrap().path, "events[0].state[3]");
+        actual.events[0].state.clear();
+        assert_eq!(diff(&expected, &actual), None);
+    }
+
+    #[test]
+    fn test_cursor() {
+        let (trace, _) = record();
+        let mut cursor = TraceCursor::new(&trace);
+        assert_eq!((cursor.step(), cursor.last_step()), (0, 2));
+        assert!(cursor.event().is_none() && cursor.changes().is_empty());
+        assert!(!cursor.back());
+
+        assert!(cursor.forward());
+        assert_eq!(cursor.event().unwrap().kind, EventKind::Absorb);
+        assert_eq!(cursor.state(), Some(trace.events[0].state.as_slice()));
+        // The absorb permutes the first block and leaves the fourth element in the rate.
+        let changes = cursor.changes();
+        assert!(!changes.is_empty());
+        assert!(changes
+            .iter()
+            .all(|change| change.after == trace.events[0].state[change.index]
+                && change.before == trace.initial_state[change.index]));
+
+        assert!(cursor.forward() && !cursor.forward());
+        assert_eq!(cursor.event().unwrap().kind, EventKind::Squeeze);
+        assert!(cursor.back());
+        assert_eq!(cursor.step(), 1);
+        assert!(!cursor.seek(3) && cursor.seek(0));
+        assert_eq!(cursor.state(), Some(trace.initial_state.as_slice()));
+    }
+}
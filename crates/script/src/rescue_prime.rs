@@ -0,0 +1,231 @@
+//! Rescue-Prime permutation over the BN254 scalar field.
+//!
+//! State width 4 (rate 3, capacity 1) at a 128-bit security level, with S-box exponent
+//! `alpha = 5` and its inverse. The round count, round constants and MDS matrix are derived with
+//! the parameter generation algorithm of the Rescue-Prime reference implementation
+//! (`get_number_of_rounds`, `get_round_constants`, `get_mds_matrix`).
+
+use ark_bn254::Fr;
+use ark_ff::{Field, MontFp};
+
+use crate::permutation::Permutation;
+
+/// Width of the permutation state in field elements.
+pub const STATE_WIDTH: usize = 4;
+
+/// Number of rounds, each made of a forward and an inverse S-box step.
+pub const ROUNDS: usize = 11;
+
+/// S-box exponent.
+const ALPHA: u64 = 5;
+
+/// Inverse S-box exponent `1/alpha mod (p - 1)`, as little-endian 64-bit limbs.
+const ALPHA_INV: [u64; 4] = [
+    0xcfe7f7a98ccccccd,
+    0x535cb9d394945a0d,
+    0x93736af8679aad17,
+    0x26b6a528b427b354,
+];
+
+/// MDS matrix: transpose of the right half of the echelon form of `[g^(i*j)]` (m x 2m, g = 5).
+const MDS: [[Fr; STATE_WIDTH]; STATE_WIDTH] = [
+    [
+        MontFp!("21888242871839275222246405745257275088548364400416034343698204186575808479992"),
+        MontFp!("19500"),
+        MontFp!("21888242871839275222246405745257275088548364400416034343698204186575808491587"),
+        MontFp!("156"),
+    ],
+    [
+        MontFp!("21888242871839275222246405745257275088548364400416034343698204186575806058117"),
+        MontFp!("3026375"),
+        MontFp!("21888242871839275222246405745257275088548364400416034343698204186575807886437"),
+        MontFp!("20306"),
+    ],
+    [
+        MontFp!("21888242871839275222246405745257275088548364400416034343698204186575491214367"),
+        MontFp!("393529500"),
+        MontFp!("21888242871839275222246405745257275088548364400416034343698204186575729688812"),
+        MontFp!("2558556"),
+    ],
+    [
+        MontFp!("21888242871839275222246405745257275088548364400416034343698204186535831058117"),
+        MontFp!("49574560750"),
+        MontFp!("21888242871839275222246405745257275088548364400416034343698204186565891044437"),
+        MontFp!("320327931"),
+    ],
+];
+
+/// Round constants, `2 * STATE_WIDTH` per round (SHAKE256 of `Rescue-XLIX(p,4,1,128)`).
+const ROUND_CONSTANTS: [Fr; 2 * STATE_WIDTH * ROUNDS] = [
+    MontFp!("19372445876017403532017686159715766973255657576148543734955949270184900210002"),
+    MontFp!("7426763599231497886592559462516288340908653525034938070104560966990732678923"),
+    MontFp!("16227096663057697707681973486604624170556416568254588957327566861710044294261"),
+    MontFp!("5396506290678449526077103393587497744976266618320194857438751073557737111533"),
+    MontFp!("12080663677905305570931916036293511355260965924378109280443749833133982643540"),
+    MontFp!("6325403729749365281614097625153212474269972369403480900076856266174668173359"),
+    MontFp!("2990223187548543200467930787589910332838593902115596110136295155454246747368"),
+    MontFp!("7419088758379979120780041814482289035512127749708359477920949071242529632592"),
+    MontFp!("796290053232825203528420792007569842882771731648603847268777033582438213814"),
+    MontFp!("107700996211483207129241122734063113508329429545324529434765460940610725732"),
+    MontFp!("19809887129488313577537043487776017825973921443131597793083335175071035526105"),
+    MontFp!("21552479607046275957509732948639674868486021861282338133863024011711486639811"),
+    MontFp!("7130159268442012385005116279212444548241392409317911800226296858467872426902"),
+    MontFp!("18942460214556290744713716739585440005069802337481697720502931277702553514520"),
+    MontFp!("7438888057560995796650193930519434364986464002405681462034043760926820872423"),
+    MontFp!("7238833465064002796881222827472421393480209848254307252819374919971704481205"),
+    MontFp!("2245952007875595797349887988315324513279180881890425773330317719083008181697"),
+    MontFp!("11355478564793241533052274598430178840006737009582989196254920053222996297728"),
+    MontFp!("19203905438798885326391979284670472473261107413032489821657157350998577585714"),
+    MontFp!("4379641704183170124845347900301755684994220638919534389623494926870489987481"),
+    MontFp!("10495390659355598373221718712813053434919509137757376430289360136987651072678"),
+    MontFp!("13872782143331410411038073408874538924145292038739450497717189515482418321897"),
+    MontFp!("20282700654992945102004216596641144682518155230308185935701189241589085465681"),
+    MontFp!("12363668224909337811253623518477026751371331741686455853055354901360810996334"),
+    MontFp!("13769148441064564251563645680605923456720935479737936318729728297534372539744"),
+    MontFp!("9889479103858777711616683667200647223347556439677773199719181295743025027116"),
+    MontFp!("15316687726756359806580003047427695004077985276802786000573639506031011571708"),
+    MontFp!("14976673585470277665915860590580347199315176299511978608935593918987545423295"),
+    MontFp!("4230431559775033734841444547760733149815320143633138987901910450353846497800"),
+    MontFp!("8620136224159480079106911725341340701571898838285862146131375731039499541771"),
+    MontFp!("4771770272537372161764233200291274000872519610410710515257439938413673154763"),
+    MontFp!("20852239114524006047531183297074004252191234794613497303150074771421578631573"),
+    MontFp!("482711392368254233975341026506590885220323417680624408677828016488195737325"),
+    MontFp!("4791278448791547229467772867852652644173255286072692370013632124395034724323"),
+    MontFp!("7299341995978445440768899691663727171775439221744283813527720493757147619523"),
+    MontFp!("6347959145051057270647889882458125615342648946214679500996193875550728377353"),
+    MontFp!("15814496477759227770518826829395963003474272999933502741803040387966495428389"),
+    MontFp!("17365109536056936001135177064954581721029076027549923399826470515904716960051"),
+    MontFp!("7738687164820787571946336148150567454294615180348921150189711425829507479580"),
+    MontFp!("20611568631794463925090893497891596826571713618434535408138655434196983649465"),
+    MontFp!("14215459287349262798568742824264347787834179374415932370256448256348472245399"),
+    MontFp!("18491043675051020516594724037907629198243905642441039591354368584973788373007"),
+    MontFp!("8801472641108488057734280733329630827453736360407262423040842096179436291843"),
+    MontFp!("18998460306766452503916369136437489217139627370876364018456875348273372554814"),
+    MontFp!("1135095752828165500152974247651998199625141739064625633532358423749434137402"),
+    MontFp!("18374074576063940190068413402013463102165601432325963588563813667957990062740"),
+    MontFp!("370615520746697431507729431711157362994678916834776552763953573840371002446"),
+    MontFp!("12890020154011672337304126883852366860995039940042032340707566426603242367062"),
+    MontFp!("1340774927083417649740985522429269915524420002063172920790686325044464577390"),
+    MontFp!("5509128839005208966697048430934746226642435641308864765516422592903138063812"),
+    MontFp!("14619489192176763747088518279379345991937620433154658864753630103366758420695"),
+    MontFp!("18421555558336166390305476349830891140606337788109846445466842018267433573665"),
+    MontFp!("20950047476156755679330479530374011255888066753523408609003084063690826343889"),
+    MontFp!("18464558920506242765769048311159743056905737609506370631834463532270046572265"),
+    MontFp!("15712074264037496844325075854258012548582223075612668279898748630131373122245"),
+    MontFp!("9666807230217861976285868741405031601542212704427437377507651215586384899290"),
+    MontFp!("15507381999143745380642761629124732642006583094633130049661454435993529476501"),
+    MontFp!("12394879279586643045424343154474953136997582514065863622709881070747945453844"),
+    MontFp!("7645656940241391278217272665021896123100400137374962950903624133389877902075"),
+    MontFp!("7413043429875359634710291503963416719228325981575301139688072105047499354717"),
+    MontFp!("10954151727231604975840936942665348706817484292263495692043493315190463748499"),
+    MontFp!("8924788518777321370074254206192728616860332312759820921136240677918020003908"),
+    MontFp!("11133007717754106590994875884078695598650442920565434471849269182817886474415"),
+    MontFp!("11552272923734706465730113912780574014688479923380164511210001933203796059993"),
+    MontFp!("3485721347005915860252488469543783206279062570037666921416654655559485222917"),
+    MontFp!("16755243584615564280767922355563024688318137900145523748992581913614415041317"),
+    MontFp!("991682769202709639402702785599324149576774282856021454974544713072660183855"),
+    MontFp!("10543855004938746033108489493894309951661032916637763658957391718336873635338"),
+    MontFp!("8598728467778722138338822925864519222781479699126320664326621369287666295144"),
+    MontFp!("13053050538745131557327128609825380063339112604147427753169861396240853466301"),
+    MontFp!("6049560411725721888693200604650758973295108717195844585050729973398725595475"),
+    MontFp!("15892338981435124129078555979342980375104029272823924484323248784116517004459"),
+    MontFp!("4096420441391615257266959742355474441030849355194496076100026443387148080032"),
+    MontFp!("12426753334428555404390339550163623241525314251360934018612334311090929660998"),
+    MontFp!("2405293467086478534056295463994128614108377009435607620979781507918033868520"),
+    MontFp!("10945214521702777392923374331633873709360459344719867660268630655428946520237"),
+    MontFp!("11365024302243301214559915210212914576067851041016925512812710087321933487473"),
+    MontFp!("16223080381065424769457764489825648163157966287092950677225593594048151739637"),
+    MontFp!("15545390210617202758569557739732311438806994425444957886229511451127407231445"),
+    MontFp!("12321767390002361982403248396265266449231623938356666957979693043101915258308"),
+    MontFp!("16446441361084688504592811842437842715908703475355517037017740818076504657362"),
+    MontFp!("5039558212614565296325070353303003409992802343082785034102456349455006427267"),
+    MontFp!("6404439459712480401818725183421779799102624097574206568399188927574542003181"),
+    MontFp!("11230175469123781943249783516413823992356606295273361058391220547929064047398"),
+    MontFp!("422617519667519059766253699223283144017903883546384152293529608635739039903"),
+    MontFp!("18049409017754082478368607657002504808466470416402082196953647306465813919570"),
+    MontFp!("13967113430175237641924950865737728539406288490530374511763076843858043486698"),
+    MontFp!("14155596480547683668929450253161450338268031366467866939917543338335005020984"),
+];
+
+/// Rescue-Prime over BN254 with state width 4 as a sponge [`Permutation`] (rate 3, capacity 1).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RescuePrime;
+
+impl Permutation for RescuePrime {
+    type Field = Fr;
+
+    const WIDTH: usize = STATE_WIDTH;
+
+    const RATE: usize = 3;
+
+    const ID: &'static str = "rescue-prime-bn254";
+
+    fn permute(&self, state: &mut [Fr]) {
+        assert_eq!(
+            state.len(),
+            STATE_WIDTH,
+            "Rescue-Prime state must have 4 elements"
+        );
+
+        for round in 0..ROUNDS {
+            let constants =
+                &ROUND_CONSTANTS[2 * STATE_WIDTH * round..2 * STATE_WIDTH * (round + 1)];
+
+            // Forward half-round: x^alpha, MDS, constants.
+            for element in state.iter_mut() {
+                *element = element.pow([ALPHA]);
+            }
+            mds_multiplication(state);
+            add_constants(state, &constants[..STATE_WIDTH]);
+
+            // Inverse half-round: x^(1/alpha), MDS, constants.
+            for element in state.iter_mut() {
+                *element = element.pow(ALPHA_INV);
+            }
+            mds_multiplication(state);
+            add_constants(state, &constants[STATE_WIDTH..]);
+        }
+    }
+}
+
+fn mds_multiplication(state: &mut [Fr]) {
+    let mut mixed = [Fr::from(0u64); STATE_WIDTH];
+    for (output, row) in mixed.iter_mut().zip(&MDS) {
+        *output = state.iter().zip(row).map(|(a, m)| *a * m).sum();
+    }
+    state.copy_from_slice(&mixed);
+}
+
+fn add_constants(state: &mut [Fr], constants: &[Fr]) {
+    for (element, constant) in state.iter_mut().zip(constants) {
+        *element += constant;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inverse_s_box() {
+        let x = Fr::from(123456789u64);
+        assert_eq!(x.pow([ALPHA]).pow(ALPHA_INV), x);
+    }
+
+    #[test]
+    fn test_reference_vector() {
+        // Output of the reference algorithm on the state [0, 1, 2, 3].
+        let mut state = [0u64, 1, 2, 3].map(Fr::from);
+        RescuePrime.permute(&mut state);
+
+        let expected: [Fr; STATE_WIDTH] = [
+            MontFp!(
+                "17333178955977042199479936369437270786487137858152022215081994148672921158408"
+            ),
+            MontFp!("8006726097758931559973272907884845846453113410286106509125997875515397162743"),
+            MontFp!("9434084398625838466297011603701866751011358678150517537600748135805911517609"),
+            MontFp!("7622214221431152665281586291361980203350090867725753540724535009239019393868"),
+        ];
+        assert_eq!(state, expected);
+    }
+}
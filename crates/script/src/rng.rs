@@ -0,0 +1,431 @@
+//! A deterministic, domain-separated randomness source squeezed from the sponge.
+//!
+//! Once the absorb phase has bound everything the randomness must depend on (e.g. a secret key
+//! and a message when deriving a nonce), [`SpongeRng`] turns the final SQUEEZE of the IO pattern
+//! into an [`RngCore`] + [`CryptoRng`]:
+//!
+//! ```
+//! use ark_bn254::Fr;
+//! use ark_std::rand::RngCore;
+//! use safe_api::domain::padded_domain_separator;
+//! use safe_api::poseidon2::Poseidon2;
+//! use safe_api::rng::SpongeRng;
+//! use safe_api::{IoPattern, Sponge};
+//!
+//! // Up to 4 elements of randomness, i.e. 4 * 15 bytes over BN254.
+//! let pattern = IoPattern::new().absorb(2).squeeze(4);
+//! let domain_separator = padded_domain_separator(b"my-app/nonce");
+//! let mut sponge = Sponge::<Poseidon2>::start(pattern, &domain_separator);
+//! sponge.absorb(&[Fr::from(7u64), Fr::from(42u64)]);
+//!
+//! let mut rng = SpongeRng::new(sponge);
+//! let nonce = rng.next_u64();
+//! # let _ = nonce;
+//! ```
+//!
+//! The number of squeezed elements is part of the IO pattern, and therefore of the tag, like
+//! any other operation: the RNG fails once it is exhausted. Each element contributes the
+//! [`bytes_per_squeezed_element`] least significant bytes of its canonical integer, big-endian,
+//! which keeps them within `2^-128` of uniform.
+//!
+//! Simulations need a stream without a length fixed in advance rather than one SQUEEZE:
+//! [`SafeSeededRng`] is a [`SeedableRng`] drawing reproducible randomness from a seed, separated
+//! per protocol by its tag:
+//!
+//! ```
+//! use ark_std::rand::{Rng, SeedableRng};
+//! use safe_api::poseidon2::Poseidon2;
+//! use safe_api::rng::SafeSeededRng;
+//! use safe_api::IoPattern;
+//!
+//! let tag = IoPattern::new().absorb(2).squeeze(1).tag(&[0x41; 64]);
+//! let mut rng = SafeSeededRng::<Poseidon2>::from_tag_and_seed(tag, [7; 32]);
+//! let sample: u64 = rng.gen();
+//! assert_eq!(SafeSeededRng::<Poseidon2>::from_tag_and_seed(tag, [7; 32]).gen::<u64>(), sample);
+//! ```
+
+use alloc::vec::Vec;
+
+use ark_ff::{BigInteger, PrimeField};
+use ark_std::rand::{CryptoRng, Error, RngCore, SeedableRng};
+use core::marker::PhantomData;
+use core::num::NonZeroU32;
+
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::domain::padded_domain_separator;
+use crate::io_pattern::IoPattern;
+use crate::permutation::Permutation;
+use crate::sponge::Sponge;
+use crate::tag::Tag;
+
+/// Domain separator of the sponges behind [`SafeSeededRng`].
+const SEEDED_DOMAIN: &[u8] = b"safe-api/seeded-rng";
+
+/// Number of elements squeezed by each sponge behind [`SafeSeededRng`].
+const SEEDED_BLOCK: u32 = 16;
+
+/// Statistical distance to uniform, in bits, tolerated when converting elements to bytes.
+const SECURITY_BITS: u32 = 128;
+
+/// Returns the number of uniform bytes taken from each squeezed element of `F` (15 over BN254).
+///
+/// An element of a field of fewer than 137 bits, such as Goldilocks or BabyBear, has no byte
+/// within `2^-128` of uniform: calling this function over one, and therefore
+/// [`Sponge::squeeze_bytes`], [`SpongeRng`], [`SafeSeededRng`] or
+/// [`hash_to_field`](crate::hash_to_curve::hash_to_field), fails to compile.
+pub fn bytes_per_squeezed_element<F: PrimeField>() -> usize {
+    UniformBytes::<F>::PER_ELEMENT
+}
+
+/// Evaluates [`bytes_per_squeezed_element`] at compile time, to reject small fields there.
+struct UniformBytes<F>(PhantomData<F>);
+
+impl<F: PrimeField> UniformBytes<F> {
+    // A uniform element reduced modulo 2^k is within 2^(k - (MODULUS_BIT_SIZE - 1)) of uniform.
+    const PER_ELEMENT: usize = match F::MODULUS_BIT_SIZE.checked_sub(1 + SECURITY_BITS) {
+        Some(bits) if bits >= 8 => (bits / 8) as usize,
+        _ => panic!("Field too small for uniform bytes"),
+    };
+}
+
+/// Returns the number of elements of `F` squeezed for `num_bytes` uniform bytes, see
+/// [`Sponge::squeeze_bytes`].
+pub fn squeeze_bytes_len<F: PrimeField>(num_bytes: usize) -> usize {
+    num_bytes.div_ceil(bytes_per_squeezed_element::<F>())
+}
+
+/// Returns the [`bytes_per_squeezed_element`] uniform bytes of a squeezed element.
+pub(crate) fn squeezed_bytes<F: PrimeField>(element: F) -> Vec<u8> {
+    let bytes = element.into_bigint().to_bytes_be();
+    bytes[bytes.len() - bytes_per_squeezed_element::<F>()..].to_vec()
+}
+
+/// Randomness squeezed from a sponge whose remaining IO pattern is a single SQUEEZE, see the
+/// [module documentation](self).
+#[derive(Clone, Debug)]
+pub struct SpongeRng<P: Permutation> {
+    sponge: Sponge<P>,
+    /// Elements of the SQUEEZE not drawn yet.
+    remaining: usize,
+    /// Bytes of the current element, of which `buffer[position..]` are unused.
+    buffer: [u8; 64],
+    position: usize,
+    /// The sponge before the SQUEEZE and the elements drawn so far.
+    #[cfg(feature = "shadow")]
+    shadow: (crate::sponge::Snapshot<P::Field>, Vec<P::Field>),
+}
+
+impl<P: Permutation> SpongeRng<P> {
+    /// Wraps `sponge`, which must be left with exactly one operation, a SQUEEZE.
+    ///
+    /// # Panics
+    /// If the remaining IO pattern is not a single SQUEEZE.
+    pub fn new(mut sponge: Sponge<P>) -> Self {
+        #[cfg(feature = "shadow")]
+        let shadow = (sponge.snapshot(), Vec::new());
+        let remaining = sponge.begin_squeeze();
+        assert!(
+            sponge.io_pattern().len() == sponge.operations_done(),
+            "Expected the final operation"
+        );
+        SpongeRng {
+            sponge,
+            remaining,
+            buffer: [0u8; 64],
+            position: 64,
+            #[cfg(feature = "shadow")]
+            shadow,
+        }
+    }
+
+    /// Returns the number of bytes that can still be drawn.
+    pub fn remaining_bytes(&self) -> usize {
+        (64 - self.position) + self.remaining * bytes_per_squeezed_element::<P::Field>()
+    }
+
+    /// Erases the state of the sponge, whether or not the randomness was used up.
+    pub fn finish(mut self) {
+        #[cfg(feature = "shadow")]
+        {
+            // Reference: rewind and squeeze the whole operation at once.
+            let (before, drawn) = &self.shadow;
+            self.sponge.restore(before);
+            let squeezed = self.sponge.squeeze();
+            crate::shadow::compare("SpongeRng", drawn, &squeezed[..drawn.len()].to_vec());
+        }
+        self.buffer.zeroize();
+        self.sponge.finish();
+    }
+
+    fn refill(&mut self) {
+        let element = self.sponge.squeeze_element();
+        #[cfg(feature = "shadow")]
+        self.shadow.1.push(element);
+        self.remaining -= 1;
+
+        let bytes = squeezed_bytes(element);
+        self.position = 64 - bytes.len();
+        self.buffer[self.position..].copy_from_slice(&bytes);
+    }
+}
+
+impl<P: Permutation> Drop for SpongeRng<P> {
+    fn drop(&mut self) {
+        // The sponge erases itself.
+        self.buffer.zeroize();
+    }
+}
+
+impl<P: Permutation> ZeroizeOnDrop for SpongeRng<P> {}
+
+impl<P: Permutation> RngCore for SpongeRng<P> {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    /// # Panics
+    /// If fewer than `dest.len()` bytes remain.
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest)
+            .unwrap_or_else(|_| panic!("Randomness exhausted"))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        if dest.len() > self.remaining_bytes() {
+            return Err(Error::from(
+                NonZeroU32::new(Error::CUSTOM_START).expect("CUSTOM_START is non-zero"),
+            ));
+        }
+        let mut written = 0;
+        while written < dest.len() {
+            if self.position == 64 {
+                self.refill();
+            }
+            let length = (64 - self.position).min(dest.len() - written);
+            dest[written..written + length]
+                .copy_from_slice(&self.buffer[self.position..self.position + length]);
+            self.position += length;
+            written += length;
+        }
+        Ok(())
+    }
+}
+
+impl<P: Permutation> CryptoRng for SpongeRng<P> {}
+
+/// Unbounded randomness from a seed and the tag of a protocol, see the
+/// [module documentation](self).
+///
+/// The stream is cut into blocks of [`SpongeRng`] randomness. Block `i` is squeezed by a sponge
+/// under a fixed domain separator which absorbs the tag, the seed and `i`, so the streams of
+/// two protocols, or of two seeds, are independent.
+#[derive(Clone, Debug)]
+pub struct SafeSeededRng<P: Permutation + Default> {
+    tag: Tag,
+    seed: [u8; 32],
+    /// Index of the next block.
+    counter: u64,
+    block: SpongeRng<P>,
+}
+
+impl<P: Permutation + Default> SafeSeededRng<P> {
+    /// Returns the randomness of `seed` for the protocol of tag `tag`.
+    pub fn from_tag_and_seed(tag: Tag, seed: [u8; 32]) -> Self {
+        let block = Self::block(tag, &seed, 0);
+        SafeSeededRng {
+            tag,
+            seed,
+            counter: 1,
+            block,
+        }
+    }
+
+    /// Returns the tag the randomness is separated by.
+    pub fn tag(&self) -> Tag {
+        self.tag
+    }
+
+    fn block(tag: Tag, seed: &[u8; 32], counter: u64) -> SpongeRng<P> {
+        let pattern = IoPattern::new()
+            .absorb(1)
+            .absorb_bytes::<P::Field>(seed.len())
+            .absorb(1)
+            .squeeze(SEEDED_BLOCK);
+        let mut sponge = Sponge::<P>::start(pattern, padded_domain_separator(SEEDED_DOMAIN));
+        sponge.absorb(&[tag.to_field()]);
+        sponge.absorb_bytes(seed);
+        sponge.absorb(&[P::Field::from(counter)]);
+        SpongeRng::new(sponge)
+    }
+}
+
+impl<P: Permutation + Default> Drop for SafeSeededRng<P> {
+    fn drop(&mut self) {
+        // The block erases itself.
+        self.seed.zeroize();
+    }
+}
+
+impl<P: Permutation + Default> ZeroizeOnDrop for SafeSeededRng<P> {}
+
+impl<P: Permutation + Default> RngCore for SafeSeededRng<P> {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, mut dest: &mut [u8]) {
+        while !dest.is_empty() {
+            if self.block.remaining_bytes() == 0 {
+                let next = Self::block(self.tag, &self.seed, self.counter);
+                self.counter += 1;
+                core::mem::replace(&mut self.block, next).finish();
+            }
+            let length = self.block.remaining_bytes().min(dest.len());
+            let (head, tail) = dest.split_at_mut(length);
+            self.block.fill_bytes(head);
+            dest = tail;
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl<P: Permutation + Default> CryptoRng for SafeSeededRng<P> {}
+
+impl<P: Permutation + Default> SeedableRng for SafeSeededRng<P> {
+    type Seed = [u8; 32];
+
+    /// Returns the randomness of `seed` under the tag 0, which no IO pattern has in practice;
+    /// prefer [`SafeSeededRng::from_tag_and_seed`] to separate the randomness per protocol.
+    fn from_seed(seed: Self::Seed) -> Self {
+        Self::from_tag_and_seed(Tag::from(0), seed)
+    }
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::domain::padded_domain_separator;
+    use crate::io_pattern::IoPattern;
+    use crate::poseidon2::Poseidon2;
+    use ark_bn254::Fr;
+
+    fn rng(seed: u64, elements: u32) -> SpongeRng<Poseidon2> {
+        let pattern = IoPattern::new().absorb(1).squeeze(elements);
+        let mut sponge = Sponge::<Poseidon2>::start(pattern, padded_domain_separator(b"test"));
+        sponge.absorb(&[Fr::from(seed)]);
+        SpongeRng::new(sponge)
+    }
+
+    #[test]
+    fn test_bytes_match_squeeze() {
+        assert_eq!(bytes_per_squeezed_element::<Fr>(), 15);
+
+        let mut bytes = [0u8; 45];
+        let mut generator = rng(1, 3);
+        assert_eq!(generator.remaining_bytes(), 45);
+        generator.fill_bytes(&mut bytes[..20]);
+        generator.fill_bytes(&mut bytes[20..]);
+        assert_eq!(generator.remaining_bytes(), 0);
+        generator.finish();
+
+        let pattern = IoPattern::new().absorb(1).squeeze(3);
+        let mut sponge = Sponge::<Poseidon2>::start(pattern, padded_domain_separator(b"test"));
+        sponge.absorb(&[Fr::from(1u64)]);
+        let expected: Vec<u8> = sponge
+            .squeeze()
+            .iter()
+            .flat_map(|element| element.into_bigint().to_bytes_be()[17..].to_vec())
+            .collect();
+        assert_eq!(bytes.to_vec(), expected);
+    }
+
+    #[test]
+    fn test_deterministic_and_separated() {
+        assert_eq!(rng(1, 2).next_u64(), rng(1, 2).next_u64());
+        assert_ne!(rng(1, 2).next_u64(), rng(2, 2).next_u64());
+        // The squeezed length is part of the tag.
+        assert_ne!(rng(1, 2).next_u64(), rng(1, 3).next_u64());
+    }
+
+    #[test]
+    fn test_exhaustion() {
+        let mut generator = rng(1, 1);
+        let mut bytes = [0u8; 16];
+        assert!(generator.try_fill_bytes(&mut bytes).is_err());
+        assert!(generator.try_fill_bytes(&mut bytes[..15]).is_ok());
+        assert!(generator.try_fill_bytes(&mut bytes[..1]).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected the final operation")]
+    fn test_requires_final_squeeze() {
+        let pattern = IoPattern::new().squeeze(1).absorb(1);
+        SpongeRng::new(Sponge::<Poseidon2>::start(
+            pattern,
+            padded_domain_separator(b"test"),
+        ));
+    }
+
+    #[test]
+    fn test_seeded() {
+        let seeded =
+            |tag: Tag, seed: u8| SafeSeededRng::<Poseidon2>::from_tag_and_seed(tag, [seed; 32]);
+
+        // Reads across blocks of 16 * 15 bytes, in any split.
+        let mut whole = [0u8; 1000];
+        seeded(Tag::from(5), 1).fill_bytes(&mut whole);
+        let mut split = [0u8; 1000];
+        let mut generator = seeded(Tag::from(5), 1);
+        for chunk in split.chunks_mut(7) {
+            generator.fill_bytes(chunk);
+        }
+        assert_eq!(whole, split);
+
+        // The first block is that of a sponge absorbing the tag, the seed and 0.
+        let pattern = IoPattern::new()
+            .absorb(1)
+            .absorb_bytes::<Fr>(32)
+            .absorb(1)
+            .squeeze(16);
+        let mut sponge =
+            Sponge::<Poseidon2>::start(pattern, padded_domain_separator(SEEDED_DOMAIN));
+        sponge.absorb(&[Fr::from(5u64)]);
+        sponge.absorb_bytes(&[1; 32]);
+        sponge.absorb(&[Fr::from(0u64)]);
+        assert_eq!(whole[..240], sponge.squeeze_bytes(240)[..]);
+
+        assert_ne!(
+            seeded(Tag::from(5), 1).next_u64(),
+            seeded(Tag::from(6), 1).next_u64()
+        );
+        assert_ne!(
+            seeded(Tag::from(5), 1).next_u64(),
+            seeded(Tag::from(5), 2).next_u64()
+        );
+        assert_eq!(
+            SafeSeededRng::<Poseidon2>::seed_from_u64(3).next_u64(),
+            SafeSeededRng::<Poseidon2>::seed_from_u64(3).next_u64()
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! Secret inputs: keys, blinding factors and the keys derived from them.
+//!
+//! [`Sponge`] erases its state when it is dropped, not only on [`Sponge::finish`], and so do
+//! the [`Transcript`](crate::Transcript), the [`SpongeRng`](crate::rng::SpongeRng) and the
+//! [`SpongeState`](crate::state::SpongeState) built on it. [`SecretAbsorb`] also erases what
+//! the sponge is given and what it returns, e.g. to derive keys:
+//!
+//! ```
+//! use ark_bn254::Fr;
+//! use ark_ff::Zero;
+//! use safe_api::domain::padded_domain_separator;
+//! use safe_api::poseidon2::Poseidon2;
+//! use safe_api::secret::{SecretAbsorb, Zeroizing};
+//! use safe_api::{IoPattern, Sponge};
+//!
+//! let mut master_key = Zeroizing::new(vec![Fr::from(7u64)]); // Loaded from storage in practice.
+//! let pattern = IoPattern::new().absorb(1).absorb_bytes::<Fr>(7).squeeze(1);
+//! let domain_separator = padded_domain_separator(b"my-app/kdf");
+//! let mut sponge = Sponge::<Poseidon2>::start(pattern, &domain_separator);
+//! sponge.absorb_secret(&mut master_key);
+//! sponge.absorb_bytes(b"session");
+//! let session_key = sponge.squeeze_secret();
+//! sponge.finish();
+//! assert!(master_key[0].is_zero());
+//! # assert_eq!(session_key.len(), 1);
+//! ```
+//!
+//! [`ct_eq`] compares field elements in constant time, as [`aead::decrypt`](crate::aead::decrypt)
+//! and [`commitment::verify`](crate::commitment::verify) do, and [`Tag`](crate::tag::Tag)s
+//! compare in constant time, including when a sponge is resumed from its state.
+//!
+//! Erasure is best effort: copies the compiler keeps in registers or on the stack, and the
+//! memory a vector leaves behind when it grows, are out of reach.
+
+use alloc::vec::Vec;
+
+use ark_ff::{BigInteger, PrimeField};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroize;
+pub use zeroize::Zeroizing;
+
+use crate::encoding::encode_bytes;
+use crate::permutation::Permutation;
+use crate::sponge::Sponge;
+
+/// Operations of a sponge on secret values, which erase their inputs and outputs.
+pub trait SecretAbsorb<F: Zeroize> {
+    /// Absorbs `secret` as [`Sponge::absorb`], then erases it.
+    ///
+    /// # Panics
+    /// If the next operation of the IO pattern is not an ABSORB of `secret.len()` elements.
+    fn absorb_secret(&mut self, secret: &mut [F]);
+
+    /// Absorbs the byte string `secret` as [`Sponge::absorb_bytes`], then erases it and its
+    /// encoding.
+    ///
+    /// # Panics
+    /// If the next operation of the IO pattern is not the ABSORB of `secret.len()` bytes.
+    fn absorb_secret_bytes(&mut self, secret: &mut [u8]);
+
+    /// Squeezes as [`Sponge::squeeze`], into a vector erased when dropped.
+    ///
+    /// # Panics
+    /// If the next operation of the IO pattern is not a SQUEEZE.
+    fn squeeze_secret(&mut self) -> Zeroizing<Vec<F>>;
+}
+
+impl<P: Permutation> SecretAbsorb<P::Field> for Sponge<P> {
+    fn absorb_secret(&mut self, secret: &mut [P::Field]) {
+        self.absorb(secret);
+        secret.iter_mut().for_each(Zeroize::zeroize);
+    }
+
+    fn absorb_secret_bytes(&mut self, secret: &mut [u8]) {
+        let encoded = Zeroizing::new(encode_bytes::<P::Field>(secret));
+        self.absorb(&encoded);
+        secret.zeroize();
+    }
+
+    fn squeeze_secret(&mut self) -> Zeroizing<Vec<P::Field>> {
+        Zeroizing::new(self.squeeze())
+    }
+}
+
+/// Compares two field elements in time independent of their values.
+pub fn ct_eq<F: PrimeField>(a: &F, b: &F) -> bool {
+    let a = Zeroizing::new(a.into_bigint().to_bytes_le());
+    let b = Zeroizing::new(b.into_bigint().to_bytes_le());
+    a.ct_eq(&b).into()
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::domain::padded_domain_separator;
+    use crate::io_pattern::IoPattern;
+    use crate::poseidon2::Poseidon2;
+    use ark_bn254::Fr;
+    use ark_ff::Zero;
+
+    #[test]
+    fn test_matches_sponge() {
+        let pattern = IoPattern::new().absorb(2).absorb_bytes::<Fr>(3).squeeze(2);
+        let domain_separator = padded_domain_separator(b"test");
+        let mut key = [Fr::from(1u64), Fr::from(2u64)];
+        let mut bytes = *b"abc";
+
+        let mut sponge = Sponge::<Poseidon2>::start(pattern.clone(), domain_separator);
+        sponge.absorb_secret(&mut key);
+        sponge.absorb_secret_bytes(&mut bytes);
+        let output = sponge.squeeze_secret();
+        sponge.finish();
+        assert!(key.iter().all(Zero::is_zero));
+        assert_eq!(bytes, [0u8; 3]);
+
+        let mut expected = Sponge::<Poseidon2>::start(pattern, domain_separator);
+        expected.absorb(&[Fr::from(1u64), Fr::from(2u64)]);
+        expected.absorb_bytes(b"abc");
+        assert_eq!(*output, expected.squeeze());
+    }
+
+    #[test]
+    fn test_ct_eq() {
+        assert!(ct_eq(&Fr::from(42u64), &Fr::from(42u64)));
+        assert!(!ct_eq(&Fr::from(42u64), &Fr::from(43u64)));
+        assert!(!ct_eq(&Fr::from(0u64), &-Fr::from(1u64)));
+    }
+}
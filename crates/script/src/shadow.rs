@@ -0,0 +1,37 @@
+//! Shadow execution of the optimized paths, enabled by the `shadow` debug feature.
+//!
+//! Each optimized path also runs its naive reference on the same inputs and panics if the
+//! results differ, so a bug in an optimization shows up in integration tests rather than as a
+//! proof that fails to verify. The checked paths are:
+//!
+//! - [`Sponge::absorb_reader`](crate::Sponge::absorb_reader), against
+//!   [`Sponge::absorb`](crate::Sponge::absorb) on the whole encoded stream,
+//! - the element-by-element squeeze of [`SpongeRng`](crate::rng::SpongeRng), against
+//!   [`Sponge::squeeze`](crate::Sponge::squeeze), when the RNG is finished,
+//! - the streaming [`ChunkedCommitmentBuilder`](crate::chunked::ChunkedCommitmentBuilder),
+//!   against leaves hashed from the whole data.
+//!
+//! The references keep whole inputs in memory and rerun the work, so the feature is meant for
+//! testing, never for production builds.
+
+use core::fmt::Debug;
+
+/// Panics unless the result of the optimized `path` equals the one of its reference.
+#[track_caller]
+pub(crate) fn compare<T: PartialEq + Debug>(path: &str, optimized: &T, reference: &T) {
+    assert!(
+        optimized == reference,
+        "Shadow execution mismatch in {path}: optimized {optimized:?}, reference {reference:?}"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "Shadow execution mismatch in test: optimized 1, reference 2")]
+    fn test_mismatch() {
+        compare("test", &1, &2);
+    }
+}
@@ -0,0 +1,374 @@
+//! Sigma protocols made non-interactive with a SAFE [`Transcript`].
+//!
+//! A [`SigmaProtocol`] only describes its three moves (commit, challenge, respond) and the
+//! verification equation. The Fiat-Shamir transform, the IO pattern and the proof encoding are
+//! derived from it:
+//!
+//! - the transcript absorbs the statement (`"statement"`) and the commitment (`"commitment"`),
+//!   then squeezes the challenge (`"challenge"`), see [`sigma_pattern`],
+//! - the domain separator is the protocol identifier, zero padded to 64 bytes (or its SHA-512
+//!   digest if longer, as for composed protocols),
+//! - a [`SigmaProof`] is the commitment followed by the response, each element as fixed-width
+//!   big-endian bytes.
+//!
+//! The Noir `sigma_challenge` rederives the challenge in a circuit from the transcript domain
+//! separator returned by [`sigma_domain_separator`].
+//!
+//! Protocols are composed with the [`And`] and [`Or`] combinators of [`compose`].
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use ark_ff::{BigInteger, PrimeField};
+use ark_std::rand::RngCore;
+use sha2::{Digest, Sha512};
+
+use crate::io_pattern::IoPattern;
+use crate::permutation::Permutation;
+use crate::transcript::{transcript_domain_separator, Transcript};
+
+pub mod compose;
+
+pub use compose::{And, Or, OrWitness};
+
+/// A three-move public-coin proof of knowledge over the field `Field`.
+pub trait SigmaProtocol {
+    /// Field of the statement, messages and challenge.
+    type Field: PrimeField;
+    /// Public statement.
+    type Statement;
+    /// Secret witness.
+    type Witness;
+    /// Prover randomness kept between commit and respond.
+    type ProverState;
+
+    /// Name of the protocol, used as domain separator.
+    const NAME: &'static str;
+    /// Number of field elements of an encoded statement.
+    const STATEMENT_LEN: u32;
+    /// Number of field elements of a commitment.
+    const COMMITMENT_LEN: u32;
+    /// Number of field elements of a response.
+    const RESPONSE_LEN: u32;
+    /// Number of field elements of a challenge.
+    const CHALLENGE_LEN: u32 = 1;
+
+    /// Identifies the protocol in the domain separator: its [`NAME`](Self::NAME), or the
+    /// structure of a composed protocol.
+    fn protocol_id() -> String {
+        Self::NAME.to_string()
+    }
+
+    /// Encodes the statement as [`STATEMENT_LEN`](Self::STATEMENT_LEN) field elements.
+    fn statement(&self, statement: &Self::Statement) -> Vec<Self::Field>;
+
+    /// First move: the prover commits to fresh randomness.
+    fn commit<R: RngCore>(
+        &self,
+        statement: &Self::Statement,
+        witness: &Self::Witness,
+        rng: &mut R,
+    ) -> (Vec<Self::Field>, Self::ProverState);
+
+    /// Third move: the prover answers the challenge.
+    fn respond(
+        &self,
+        witness: &Self::Witness,
+        state: Self::ProverState,
+        challenge: &[Self::Field],
+    ) -> Vec<Self::Field>;
+
+    /// Checks the verification equation.
+    fn check(
+        &self,
+        statement: &Self::Statement,
+        commitment: &[Self::Field],
+        challenge: &[Self::Field],
+        response: &[Self::Field],
+    ) -> bool;
+}
+
+/// A sigma protocol with a special honest-verifier zero-knowledge simulator, as required by the
+/// [`Or`] composition.
+pub trait Simulate: SigmaProtocol {
+    /// Returns a commitment and response that pass [`check`](SigmaProtocol::check) for
+    /// `challenge`, without a witness.
+    fn simulate<R: RngCore>(
+        &self,
+        statement: &Self::Statement,
+        challenge: &[Self::Field],
+        rng: &mut R,
+    ) -> (Vec<Self::Field>, Vec<Self::Field>);
+}
+
+/// A non-interactive proof: the commitment and the response.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SigmaProof<F> {
+    pub commitment: Vec<F>,
+    pub response: Vec<F>,
+}
+
+/// Errors raised when decoding a [`SigmaProof`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProofError {
+    /// The encoding does not have the length of a proof of the protocol.
+    InvalidLength { expected: usize, actual: usize },
+    /// An element is not the canonical encoding of a field element.
+    NonCanonical { index: usize },
+}
+
+impl fmt::Display for ProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProofError::InvalidLength { expected, actual } => {
+                write!(
+                    f,
+                    "expected a {}-byte proof, got {} bytes",
+                    expected, actual
+                )
+            }
+            ProofError::NonCanonical { index } => {
+                write!(f, "element {} is not a canonical field element", index)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ProofError {}
+
+impl<F: PrimeField> SigmaProof<F> {
+    /// Number of bytes of an encoded field element.
+    fn element_size() -> usize {
+        (F::MODULUS_BIT_SIZE as usize).div_ceil(8)
+    }
+
+    /// Encodes the proof: the commitment then the response, as fixed-width big-endian bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let size = Self::element_size();
+        self.commitment
+            .iter()
+            .chain(&self.response)
+            .flat_map(|element| encode_element(element, size))
+            .collect()
+    }
+
+    /// Decodes a proof of protocol `S`, rejecting non-canonical elements.
+    pub fn from_bytes<S: SigmaProtocol<Field = F>>(bytes: &[u8]) -> Result<Self, ProofError> {
+        let size = Self::element_size();
+        let commitment_len = S::COMMITMENT_LEN as usize;
+        let expected = (commitment_len + S::RESPONSE_LEN as usize) * size;
+        if bytes.len() != expected {
+            return Err(ProofError::InvalidLength {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+
+        let mut elements = Vec::with_capacity(bytes.len() / size);
+        for (index, chunk) in bytes.chunks(size).enumerate() {
+            let element = F::from_be_bytes_mod_order(chunk);
+            if encode_element(&element, size) != chunk {
+                return Err(ProofError::NonCanonical { index });
+            }
+            elements.push(element);
+        }
+        let response = elements.split_off(commitment_len);
+        Ok(SigmaProof {
+            commitment: elements,
+            response,
+        })
+    }
+}
+
+/// Encodes a field element as `size` big-endian bytes.
+fn encode_element<F: PrimeField>(element: &F, size: usize) -> Vec<u8> {
+    let bytes = element.into_bigint().to_bytes_be();
+    bytes[bytes.len() - size..].to_vec()
+}
+
+/// Returns the labeled IO pattern of the Fiat-Shamir transform of `S`.
+pub fn sigma_pattern<S: SigmaProtocol>() -> IoPattern {
+    IoPattern::new()
+        .absorb_labeled("statement", S::STATEMENT_LEN)
+        .absorb_labeled("commitment", S::COMMITMENT_LEN)
+        .squeeze_labeled("challenge", S::CHALLENGE_LEN)
+}
+
+/// Returns the domain separator of the transcript of `S`, with the labels bound in, for
+/// circuits that rederive the challenge.
+pub fn sigma_domain_separator<S: SigmaProtocol>() -> [u8; 64] {
+    transcript_domain_separator(&sigma_pattern::<S>(), &protocol_domain::<S>())
+}
+
+/// The protocol identifier, zero padded, or hashed if longer than 64 bytes.
+fn protocol_domain<S: SigmaProtocol>() -> [u8; 64] {
+    let id = S::protocol_id();
+    if id.len() > 64 {
+        return Sha512::digest(id.as_bytes()).into();
+    }
+    let mut domain_separator = [0u8; 64];
+    domain_separator[..id.len()].copy_from_slice(id.as_bytes());
+    domain_separator
+}
+
+fn challenge<S, P>(protocol: &S, statement: &S::Statement, commitment: &[S::Field]) -> Vec<S::Field>
+where
+    S: SigmaProtocol,
+    P: Permutation<Field = S::Field> + Default,
+{
+    let mut transcript = Transcript::<P>::new(sigma_pattern::<S>(), protocol_domain::<S>());
+    transcript.append("statement", &protocol.statement(statement));
+    transcript.append("commitment", commitment);
+    let challenge = transcript.challenge("challenge", S::CHALLENGE_LEN as usize);
+    transcript.finish();
+    challenge
+}
+
+/// Proves knowledge of `witness` for `statement`, running the transcript on `P`.
+pub fn prove<S, P, R>(
+    protocol: &S,
+    statement: &S::Statement,
+    witness: &S::Witness,
+    rng: &mut R,
+) -> SigmaProof<S::Field>
+where
+    S: SigmaProtocol,
+    P: Permutation<Field = S::Field> + Default,
+    R: RngCore,
+{
+    let (commitment, state) = protocol.commit(statement, witness, rng);
+    let challenge = challenge::<S, P>(protocol, statement, &commitment);
+    let response = protocol.respond(witness, state, &challenge);
+    SigmaProof {
+        commitment,
+        response,
+    }
+}
+
+/// Verifies a proof for `statement`, running the transcript on `P`.
+pub fn verify<S, P>(protocol: &S, statement: &S::Statement, proof: &SigmaProof<S::Field>) -> bool
+where
+    S: SigmaProtocol,
+    P: Permutation<Field = S::Field> + Default,
+{
+    if proof.commitment.len() != S::COMMITMENT_LEN as usize
+        || proof.response.len() != S::RESPONSE_LEN as usize
+    {
+        return false;
+    }
+    let challenge = challenge::<S, P>(protocol, statement, &proof.commitment);
+    protocol.check(statement, &proof.commitment, &challenge, &proof.response)
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+pub(crate) mod tests {
+    use super::*;
+    use crate::poseidon2::Poseidon2;
+    use ark_bn254::Fr;
+    use ark_ff::UniformRand;
+    use ark_std::test_rng;
+    use std::str::FromStr;
+
+    /// Knowledge of `w` such that `y = g * w`, a Schnorr proof in the additive group of the
+    /// field. Not zero-knowledge (division recovers `w`), but exercises the framework.
+    pub(crate) struct ToySchnorr {
+        pub(crate) g: Fr,
+    }
+
+    impl SigmaProtocol for ToySchnorr {
+        type Field = Fr;
+        type Statement = Fr;
+        type Witness = Fr;
+        type ProverState = Fr;
+
+        const NAME: &'static str = "safe-api/toy-schnorr";
+        const STATEMENT_LEN: u32 = 1;
+        const COMMITMENT_LEN: u32 = 1;
+        const RESPONSE_LEN: u32 = 1;
+
+        fn statement(&self, y: &Fr) -> Vec<Fr> {
+            vec![*y]
+        }
+
+        fn commit<R: RngCore>(&self, _: &Fr, _: &Fr, rng: &mut R) -> (Vec<Fr>, Fr) {
+            let r = Fr::rand(rng);
+            (vec![self.g * r], r)
+        }
+
+        fn respond(&self, w: &Fr, r: Fr, challenge: &[Fr]) -> Vec<Fr> {
+            vec![r + challenge[0] * w]
+        }
+
+        fn check(&self, y: &Fr, commitment: &[Fr], challenge: &[Fr], response: &[Fr]) -> bool {
+            self.g * response[0] == commitment[0] + challenge[0] * y
+        }
+    }
+
+    impl Simulate for ToySchnorr {
+        fn simulate<R: RngCore>(
+            &self,
+            y: &Fr,
+            challenge: &[Fr],
+            rng: &mut R,
+        ) -> (Vec<Fr>, Vec<Fr>) {
+            let response = Fr::rand(rng);
+            (vec![self.g * response - challenge[0] * y], vec![response])
+        }
+    }
+
+    #[test]
+    fn test_prove_and_verify() {
+        let protocol = ToySchnorr { g: Fr::from(5u64) };
+        let w = Fr::from(42u64);
+        let y = protocol.g * w;
+
+        let proof = prove::<_, Poseidon2, _>(&protocol, &y, &w, &mut test_rng());
+        assert!(verify::<_, Poseidon2>(&protocol, &y, &proof));
+        assert!(!verify::<_, Poseidon2>(
+            &protocol,
+            &(y + Fr::from(1u64)),
+            &proof
+        ));
+
+        let bytes = proof.to_bytes();
+        assert_eq!(bytes.len(), 64);
+        assert_eq!(SigmaProof::from_bytes::<ToySchnorr>(&bytes), Ok(proof));
+    }
+
+    #[test]
+    fn test_matches_noir() {
+        let protocol = ToySchnorr { g: Fr::from(5u64) };
+        let challenge = challenge::<_, Poseidon2>(&protocol, &Fr::from(210u64), &[Fr::from(7u64)]);
+        // Same value as `test_sigma_challenge` of the Noir implementation, which takes the
+        // transcript domain separator as input.
+        assert_eq!(
+            sigma_domain_separator::<ToySchnorr>()[..4],
+            [171, 192, 121, 43]
+        );
+        let expected = Fr::from_str(
+            "250960766517557275424527190324239135089207647241280502601801336848555011223",
+        )
+        .unwrap();
+        assert_eq!(challenge, vec![expected]);
+    }
+
+    #[test]
+    fn test_decoding_errors() {
+        assert_eq!(
+            SigmaProof::<Fr>::from_bytes::<ToySchnorr>(&[0u8; 63]),
+            Err(ProofError::InvalidLength {
+                expected: 64,
+                actual: 63
+            })
+        );
+        // The modulus itself is not canonical.
+        let mut bytes = vec![0u8; 32];
+        bytes.extend(Fr::MODULUS.to_bytes_be());
+        assert_eq!(
+            SigmaProof::<Fr>::from_bytes::<ToySchnorr>(&bytes),
+            Err(ProofError::NonCanonical { index: 1 })
+        );
+    }
+}
@@ -0,0 +1,346 @@
+//! AND and OR composition of sigma protocols.
+//!
+//! Both combinators are sigma protocols themselves, so they nest and go through the same
+//! Fiat-Shamir transform: the composed statement and commitment are the concatenations of the
+//! components', the IO pattern lengths are derived from the components, and the protocol
+//! identifier records the structure (e.g. `and(or(a,b),c)`), so that a composed proof is never
+//! accepted for another composition of the same components.
+//!
+//! - [`And`] proves both statements under the same challenge.
+//! - [`Or`] proves one of two statements without revealing which (Cramer-Damgård-Schoenmakers):
+//!   the prover simulates the branch it has no witness for with a challenge of its choice, and
+//!   the two branch challenges must add up to the transcript challenge. The response starts
+//!   with the left branch challenge, from which the verifier derives the right one.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use ark_ff::UniformRand;
+use ark_std::rand::RngCore;
+
+use super::{SigmaProtocol, Simulate};
+
+/// Proves knowledge of witnesses for both statements.
+#[derive(Clone, Debug)]
+pub struct And<A, B>(pub A, pub B);
+
+/// Proves knowledge of a witness for one of the two statements.
+#[derive(Clone, Debug)]
+pub struct Or<A, B>(pub A, pub B);
+
+/// The witness of an [`Or`] composition: for the left or for the right statement.
+#[derive(Clone, Debug)]
+pub enum OrWitness<L, R> {
+    Left(L),
+    Right(R),
+}
+
+/// Prover state of an [`Or`] composition: the state of the real branch, and the challenge and
+/// response simulated for the other one.
+pub enum OrState<A: SigmaProtocol, B: SigmaProtocol> {
+    Left {
+        state: A::ProverState,
+        simulated_challenge: Vec<A::Field>,
+        simulated_response: Vec<A::Field>,
+    },
+    Right {
+        state: B::ProverState,
+        simulated_challenge: Vec<A::Field>,
+        simulated_response: Vec<A::Field>,
+    },
+}
+
+/// Returns the common challenge length of two protocols, failing at compile time if they differ.
+const fn challenge_len(a: u32, b: u32) -> u32 {
+    assert!(
+        a == b,
+        "Composed protocols must have the same challenge length"
+    );
+    a
+}
+
+impl<A, B> SigmaProtocol for And<A, B>
+where
+    A: SigmaProtocol,
+    B: SigmaProtocol<Field = A::Field>,
+{
+    type Field = A::Field;
+    type Statement = (A::Statement, B::Statement);
+    type Witness = (A::Witness, B::Witness);
+    type ProverState = (A::ProverState, B::ProverState);
+
+    const NAME: &'static str = "and";
+    const STATEMENT_LEN: u32 = A::STATEMENT_LEN + B::STATEMENT_LEN;
+    const COMMITMENT_LEN: u32 = A::COMMITMENT_LEN + B::COMMITMENT_LEN;
+    const RESPONSE_LEN: u32 = A::RESPONSE_LEN + B::RESPONSE_LEN;
+    const CHALLENGE_LEN: u32 = challenge_len(A::CHALLENGE_LEN, B::CHALLENGE_LEN);
+
+    fn protocol_id() -> String {
+        format!("and({},{})", A::protocol_id(), B::protocol_id())
+    }
+
+    fn statement(&self, statement: &Self::Statement) -> Vec<Self::Field> {
+        let mut elements = self.0.statement(&statement.0);
+        elements.extend(self.1.statement(&statement.1));
+        elements
+    }
+
+    fn commit<R: RngCore>(
+        &self,
+        statement: &Self::Statement,
+        witness: &Self::Witness,
+        rng: &mut R,
+    ) -> (Vec<Self::Field>, Self::ProverState) {
+        let (mut commitment, left) = self.0.commit(&statement.0, &witness.0, rng);
+        let (right_commitment, right) = self.1.commit(&statement.1, &witness.1, rng);
+        commitment.extend(right_commitment);
+        (commitment, (left, right))
+    }
+
+    fn respond(
+        &self,
+        witness: &Self::Witness,
+        state: Self::ProverState,
+        challenge: &[Self::Field],
+    ) -> Vec<Self::Field> {
+        let mut response = self.0.respond(&witness.0, state.0, challenge);
+        response.extend(self.1.respond(&witness.1, state.1, challenge));
+        response
+    }
+
+    fn check(
+        &self,
+        statement: &Self::Statement,
+        commitment: &[Self::Field],
+        challenge: &[Self::Field],
+        response: &[Self::Field],
+    ) -> bool {
+        let (left_commitment, right_commitment) = commitment.split_at(A::COMMITMENT_LEN as usize);
+        let (left_response, right_response) = response.split_at(A::RESPONSE_LEN as usize);
+        self.0
+            .check(&statement.0, left_commitment, challenge, left_response)
+            && self
+                .1
+                .check(&statement.1, right_commitment, challenge, right_response)
+    }
+}
+
+impl<A, B> Simulate for And<A, B>
+where
+    A: Simulate,
+    B: Simulate<Field = A::Field>,
+{
+    fn simulate<R: RngCore>(
+        &self,
+        statement: &Self::Statement,
+        challenge: &[Self::Field],
+        rng: &mut R,
+    ) -> (Vec<Self::Field>, Vec<Self::Field>) {
+        let (mut commitment, mut response) = self.0.simulate(&statement.0, challenge, rng);
+        let (right_commitment, right_response) = self.1.simulate(&statement.1, challenge, rng);
+        commitment.extend(right_commitment);
+        response.extend(right_response);
+        (commitment, response)
+    }
+}
+
+impl<A, B> SigmaProtocol for Or<A, B>
+where
+    A: Simulate,
+    B: Simulate<Field = A::Field>,
+{
+    type Field = A::Field;
+    type Statement = (A::Statement, B::Statement);
+    type Witness = OrWitness<A::Witness, B::Witness>;
+    type ProverState = OrState<A, B>;
+
+    const NAME: &'static str = "or";
+    const STATEMENT_LEN: u32 = A::STATEMENT_LEN + B::STATEMENT_LEN;
+    const COMMITMENT_LEN: u32 = A::COMMITMENT_LEN + B::COMMITMENT_LEN;
+    // The left branch challenge, then both branch responses.
+    const RESPONSE_LEN: u32 = Self::CHALLENGE_LEN + A::RESPONSE_LEN + B::RESPONSE_LEN;
+    const CHALLENGE_LEN: u32 = challenge_len(A::CHALLENGE_LEN, B::CHALLENGE_LEN);
+
+    fn protocol_id() -> String {
+        format!("or({},{})", A::protocol_id(), B::protocol_id())
+    }
+
+    fn statement(&self, statement: &Self::Statement) -> Vec<Self::Field> {
+        let mut elements = self.0.statement(&statement.0);
+        elements.extend(self.1.statement(&statement.1));
+        elements
+    }
+
+    fn commit<R: RngCore>(
+        &self,
+        statement: &Self::Statement,
+        witness: &Self::Witness,
+        rng: &mut R,
+    ) -> (Vec<Self::Field>, Self::ProverState) {
+        let simulated_challenge: Vec<Self::Field> = (0..Self::CHALLENGE_LEN)
+            .map(|_| Self::Field::rand(rng))
+            .collect();
+        match witness {
+            OrWitness::Left(witness) => {
+                let (mut commitment, state) = self.0.commit(&statement.0, witness, rng);
+                let (simulated_commitment, simulated_response) =
+                    self.1.simulate(&statement.1, &simulated_challenge, rng);
+                commitment.extend(simulated_commitment);
+                let state = OrState::Left {
+                    state,
+                    simulated_challenge,
+                    simulated_response,
+                };
+                (commitment, state)
+            }
+            OrWitness::Right(witness) => {
+                let (mut commitment, simulated_response) =
+                    self.0.simulate(&statement.0, &simulated_challenge, rng);
+                let (real_commitment, state) = self.1.commit(&statement.1, witness, rng);
+                commitment.extend(real_commitment);
+                let state = OrState::Right {
+                    state,
+                    simulated_challenge,
+                    simulated_response,
+                };
+                (commitment, state)
+            }
+        }
+    }
+
+    fn respond(
+        &self,
+        witness: &Self::Witness,
+        state: Self::ProverState,
+        challenge: &[Self::Field],
+    ) -> Vec<Self::Field> {
+        match (witness, state) {
+            (
+                OrWitness::Left(witness),
+                OrState::Left {
+                    state,
+                    simulated_challenge,
+                    simulated_response,
+                },
+            ) => {
+                let left_challenge = subtract(challenge, &simulated_challenge);
+                let mut response = left_challenge.clone();
+                response.extend(self.0.respond(witness, state, &left_challenge));
+                response.extend(simulated_response);
+                response
+            }
+            (
+                OrWitness::Right(witness),
+                OrState::Right {
+                    state,
+                    simulated_challenge,
+                    simulated_response,
+                },
+            ) => {
+                let right_challenge = subtract(challenge, &simulated_challenge);
+                let mut response = simulated_challenge;
+                response.extend(simulated_response);
+                response.extend(self.1.respond(witness, state, &right_challenge));
+                response
+            }
+            _ => panic!("Witness does not match the prover state"),
+        }
+    }
+
+    fn check(
+        &self,
+        statement: &Self::Statement,
+        commitment: &[Self::Field],
+        challenge: &[Self::Field],
+        response: &[Self::Field],
+    ) -> bool {
+        let (left_commitment, right_commitment) = commitment.split_at(A::COMMITMENT_LEN as usize);
+        let (left_challenge, responses) = response.split_at(Self::CHALLENGE_LEN as usize);
+        let (left_response, right_response) = responses.split_at(A::RESPONSE_LEN as usize);
+        let right_challenge = subtract(challenge, left_challenge);
+
+        self.0
+            .check(&statement.0, left_commitment, left_challenge, left_response)
+            && self.1.check(
+                &statement.1,
+                right_commitment,
+                &right_challenge,
+                right_response,
+            )
+    }
+}
+
+/// Element-wise difference of two challenges.
+fn subtract<F: ark_ff::PrimeField>(a: &[F], b: &[F]) -> Vec<F> {
+    a.iter().zip(b).map(|(a, b)| *a - b).collect()
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::poseidon2::Poseidon2;
+    use crate::sigma::tests::ToySchnorr;
+    use crate::sigma::{prove, sigma_pattern, verify};
+    use ark_bn254::Fr;
+    use ark_std::test_rng;
+
+    fn schnorr(g: u64) -> ToySchnorr {
+        ToySchnorr { g: Fr::from(g) }
+    }
+
+    #[test]
+    fn test_and() {
+        let protocol = And(schnorr(5), schnorr(7));
+        let witness = (Fr::from(2u64), Fr::from(3u64));
+        let statement = (protocol.0.g * witness.0, protocol.1.g * witness.1);
+
+        let proof = prove::<_, Poseidon2, _>(&protocol, &statement, &witness, &mut test_rng());
+        assert!(verify::<_, Poseidon2>(&protocol, &statement, &proof));
+
+        let wrong = (statement.0, statement.1 + Fr::from(1u64));
+        assert!(!verify::<_, Poseidon2>(&protocol, &wrong, &proof));
+
+        // The IO pattern follows the components.
+        assert_eq!(
+            sigma_pattern::<And<ToySchnorr, ToySchnorr>>().encoded_words(),
+            vec![0x80000002, 0x80000002, 0x00000001]
+        );
+    }
+
+    #[test]
+    fn test_or() {
+        let protocol = Or(schnorr(5), schnorr(7));
+        // Only the second discrete log is known.
+        let statement = (Fr::from(11u64), protocol.1.g * Fr::from(3u64));
+
+        let witness = OrWitness::Right(Fr::from(3u64));
+        let proof = prove::<_, Poseidon2, _>(&protocol, &statement, &witness, &mut test_rng());
+        assert_eq!(proof.response.len(), 3);
+        assert!(verify::<_, Poseidon2>(&protocol, &statement, &proof));
+
+        // Tampering with the challenge split breaks verification.
+        let mut tampered = proof.clone();
+        tampered.response[0] += Fr::from(1u64);
+        assert!(!verify::<_, Poseidon2>(&protocol, &statement, &tampered));
+
+        // Knowing the left witness works as well.
+        let statement = (protocol.0.g * Fr::from(4u64), Fr::from(13u64));
+        let witness = OrWitness::Left(Fr::from(4u64));
+        let proof = prove::<_, Poseidon2, _>(&protocol, &statement, &witness, &mut test_rng());
+        assert!(verify::<_, Poseidon2>(&protocol, &statement, &proof));
+    }
+
+    #[test]
+    fn test_compositions_are_separated() {
+        // The structure of the composition reaches the domain separator.
+        assert_eq!(
+            Or::<ToySchnorr, And<ToySchnorr, ToySchnorr>>::protocol_id(),
+            "or(safe-api/toy-schnorr,and(safe-api/toy-schnorr,safe-api/toy-schnorr))"
+        );
+        assert_ne!(
+            crate::sigma::sigma_domain_separator::<And<ToySchnorr, ToySchnorr>>(),
+            crate::sigma::sigma_domain_separator::<Or<ToySchnorr, ToySchnorr>>()
+        );
+    }
+}
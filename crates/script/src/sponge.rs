@@ -0,0 +1,1339 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use ark_ff::{PrimeField, Zero};
+use sha2::{Digest, Sha256};
+#[cfg(feature = "std")]
+use zeroize::Zeroizing;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::absorb::Absorb;
+use crate::config::{ConfigError, SpongeConfig};
+use crate::defaults::{self, Defaults};
+use crate::domain::{is_empty_domain, DomainSeparator};
+use crate::encoding::encode_bytes;
+#[cfg(feature = "std")]
+use crate::encoding::ChunkEncoding;
+use crate::io_pattern::{IoPattern, Operation};
+use crate::permutation::Permutation;
+use crate::rng::{squeeze_bytes_len, squeezed_bytes};
+use crate::state::{SpongeState, StateError, StateSnapshot};
+use crate::tag::{TagHasher, TagLength, TagParameters};
+use crate::transcript::Transcript;
+
+/// Prefix of the hashed label absorbed by [`Sponge::fork`], versioning its layout.
+const FORK_LABEL_PREFIX: &[u8] = b"SAFE-FORK-V1";
+
+/// Chunks read at once by [`Sponge::absorb_reader`].
+#[cfg(feature = "std")]
+const READER_BUFFER_CHUNKS: usize = 2048;
+
+/// SAFE Sponge State (following spec 2.2)
+///
+/// Rust counterpart of the Noir `SafeSponge`, generic over the [`Permutation`] it runs on. With
+/// the [`Poseidon2`](crate::poseidon2::Poseidon2) permutation it uses the same rate/capacity split
+/// and the same absorb/squeeze schedule as the circuit, so both produce identical outputs for the
+/// same IO pattern, domain separator and inputs.
+///
+/// Misuse (an operation that does not match the IO pattern) panics, like the `assert`s of the
+/// circuit.
+///
+/// The state is erased when the sponge is dropped, see [`secret`](crate::secret).
+///
+/// # Fields
+/// - `state`: Permutation state V in F^n (rate + capacity elements)
+/// - `tag`: Parameter tag T used for instance differentiation
+/// - `absorb_pos`: Current absorb position (<= n-c)
+/// - `squeeze_pos`: Current squeeze position (<= n-c)
+/// - `io_pattern`: Expected IO pattern for validation
+/// - `io_count`: Current operation count for pattern tracking
+/// - `tag_parameters`: Length and hash of the tag, recorded when the sponge is saved
+#[derive(Clone, Debug)]
+pub struct Sponge<P: Permutation> {
+    permutation: P,
+    state: Vec<P::Field>,
+    tag: P::Field,
+    absorb_pos: usize,
+    squeeze_pos: usize,
+    io_pattern: IoPattern,
+    io_count: usize,
+    tag_parameters: TagParameters,
+}
+
+impl<P: Permutation + Default> Sponge<P> {
+    /// Initializes a new SAFE sponge instance with the given IO pattern and domain separator (following spec 2.4).
+    ///
+    /// # Arguments
+    /// - `io_pattern`: Expected sequence of ABSORB/SQUEEZE calls.
+    /// - `domain_separator`: 64-byte domain separator for cross-protocol security.
+    ///
+    /// # Panics
+    /// If `domain_separator` is empty (all zeros), see [`SpongeBuilder::allow_empty_domain`].
+    pub fn start(io_pattern: IoPattern, domain_separator: impl Into<DomainSeparator>) -> Self {
+        Self::builder().start(io_pattern, domain_separator)
+    }
+
+    /// Returns a builder to set options of the sponge before it starts.
+    pub fn builder() -> SpongeBuilder<P> {
+        SpongeBuilder::new(P::default())
+    }
+
+    /// Resumes a sponge saved with [`Sponge::save`], e.g. in another process.
+    ///
+    /// Fails if the state does not fit the permutation, or if its tag is not the one of its IO
+    /// pattern under `domain_separator`, of the [`TagLength`] and [`TagHasher`] the saved
+    /// sponge was started with.
+    ///
+    /// A sponge started with [`SpongeBuilder::config`] runs under the configuration folded
+    /// into its domain separator, so it fails here with [`StateError::TagMismatch`] under the
+    /// domain separator of the application: resume it with [`SpongeBuilder::resume`] on a
+    /// builder with the same configuration.
+    pub fn resume(
+        state: SpongeState<P::Field>,
+        domain_separator: impl Into<DomainSeparator>,
+    ) -> Result<Self, StateError> {
+        Self::resume_with_permutation(P::default(), state, domain_separator)
+    }
+
+    /// Continues a sponge exported with [`Sponge::export_state`], e.g. on another machine.
+    ///
+    /// Fails if the snapshot was exported from another permutation, or one of another rate and
+    /// capacity, and otherwise like [`Sponge::resume`].
+    pub fn import_state(
+        snapshot: StateSnapshot<P::Field>,
+        domain_separator: impl Into<DomainSeparator>,
+    ) -> Result<Self, StateError> {
+        Self::import_state_with_permutation(P::default(), snapshot, domain_separator)
+    }
+}
+
+impl<P: Permutation> Sponge<P> {
+    /// Initializes a new SAFE sponge instance running on the given permutation instance.
+    ///
+    /// See [`Sponge::start`].
+    pub fn with_permutation(
+        permutation: P,
+        io_pattern: IoPattern,
+        domain_separator: impl Into<DomainSeparator>,
+    ) -> Self {
+        SpongeBuilder::new(permutation).start(io_pattern, domain_separator)
+    }
+
+    /// Initializes the sponge, whatever the domain separator.
+    pub(crate) fn init(
+        permutation: P,
+        io_pattern: IoPattern,
+        domain_separator: &[u8; 64],
+        tag_parameters: TagParameters,
+    ) -> Self {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "safe.sponge.start",
+            permutation = P::ID,
+            io_pattern = %io_pattern
+        )
+        .entered();
+        // Compute tag from IO pattern and domain separator (spec 2.3), in several limbs over
+        // fields smaller than the tag.
+        let tag_limbs =
+            tag_parameters.tag_limbs::<P::Field>(&io_pattern, domain_separator, P::CAPACITY);
+        Self::with_tag_limbs(permutation, io_pattern, &tag_limbs, tag_parameters)
+    }
+
+    /// Initializes a sponge whose capacity starts with `tag_limbs`, e.g. a tag derived the way
+    /// of another implementation, recording `tag_parameters` as the way it was derived.
+    pub(crate) fn with_tag_limbs(
+        permutation: P,
+        io_pattern: IoPattern,
+        tag_limbs: &[P::Field],
+        tag_parameters: TagParameters,
+    ) -> Self {
+        let mut state = vec![P::Field::zero(); P::WIDTH];
+        // Initialize capacity with tag (spec 2.4).
+        state[..tag_limbs.len()].copy_from_slice(tag_limbs);
+        let tag = tag_limbs[0];
+
+        Sponge {
+            permutation,
+            state,
+            tag,
+            absorb_pos: 0,
+            squeeze_pos: 0,
+            io_pattern,
+            io_count: 0,
+            tag_parameters,
+        }
+    }
+
+    /// Resumes a sponge saved with [`Sponge::save`], running on the given permutation
+    /// instance.
+    ///
+    /// See [`Sponge::resume`].
+    pub fn resume_with_permutation(
+        permutation: P,
+        mut state: SpongeState<P::Field>,
+        domain_separator: impl Into<DomainSeparator>,
+    ) -> Result<Self, StateError> {
+        if state.state.len() != P::WIDTH {
+            return Err(StateError::WrongWidth);
+        }
+        if state.absorb_pos > P::RATE
+            || state.squeeze_pos > P::RATE
+            || state.io_count > state.io_pattern.len()
+        {
+            return Err(StateError::Inconsistent);
+        }
+        let tag_parameters = state.tag_parameters()?;
+        let domain_separator = domain_separator.into();
+        if !tag_parameters.has_tag(
+            &state.io_pattern,
+            &domain_separator,
+            &state.tag,
+            P::CAPACITY,
+        ) {
+            return Err(StateError::TagMismatch);
+        }
+        Ok(Sponge {
+            permutation,
+            state: core::mem::take(&mut state.state),
+            tag: state.tag,
+            absorb_pos: state.absorb_pos,
+            squeeze_pos: state.squeeze_pos,
+            io_pattern: core::mem::take(&mut state.io_pattern),
+            io_count: state.io_count,
+            tag_parameters,
+        })
+    }
+
+    /// Continues a sponge exported with [`Sponge::export_state`], running on the given
+    /// permutation instance.
+    ///
+    /// See [`Sponge::import_state`].
+    pub fn import_state_with_permutation(
+        permutation: P,
+        snapshot: StateSnapshot<P::Field>,
+        domain_separator: impl Into<DomainSeparator>,
+    ) -> Result<Self, StateError> {
+        if snapshot.permutation != P::ID {
+            return Err(StateError::WrongPermutation);
+        }
+        if snapshot.rate != P::RATE || snapshot.capacity != P::CAPACITY {
+            return Err(StateError::WrongSplit);
+        }
+        Self::resume_with_permutation(permutation, snapshot.state, domain_separator)
+    }
+
+    /// Exports the state of this instance with the identifier, rate and capacity of its
+    /// permutation, to
+    /// continue it elsewhere with [`Sponge::import_state`] (see
+    /// [snapshots](crate::state#snapshots)).
+    pub fn export_state(&self) -> StateSnapshot<P::Field> {
+        StateSnapshot {
+            permutation: P::ID.into(),
+            rate: P::RATE,
+            capacity: P::CAPACITY,
+            state: self.save(),
+        }
+    }
+
+    /// Saves the state of this instance, to resume it later with [`Sponge::resume`] (see
+    /// [`state`](crate::state)).
+    pub fn save(&self) -> SpongeState<P::Field> {
+        SpongeState {
+            state: self.state.clone(),
+            tag: self.tag,
+            absorb_pos: self.absorb_pos,
+            squeeze_pos: self.squeeze_pos,
+            io_pattern: self.io_pattern.clone(),
+            io_count: self.io_count,
+            tag_length: self.tag_parameters.length,
+            tag_hasher: self.tag_parameters.hasher.into(),
+        }
+    }
+
+    /// Returns the tag T of this instance as a field element, or its first limb over fields
+    /// smaller than the tag (see [`compute_tag_limbs`](crate::tag::compute_tag_limbs)).
+    pub fn tag(&self) -> P::Field {
+        self.tag
+    }
+
+    /// Returns the IO pattern this instance validates against.
+    pub fn io_pattern(&self) -> &IoPattern {
+        &self.io_pattern
+    }
+
+    /// Absorbs field elements into the sponge state, interleaving permutation calls as needed (following spec 2.4).
+    ///
+    /// # Panics
+    /// If the next operation of the IO pattern is not an ABSORB of `input.len()` elements.
+    pub fn absorb(&mut self, input: &[P::Field]) {
+        self.begin_absorb(input.len());
+        #[cfg(feature = "tracing")]
+        self.trace_absorb(input.len(), input);
+        for &element in input {
+            self.absorb_element(element);
+        }
+    }
+
+    /// Absorbs the sequence number of this operation, its index in the IO pattern, followed by
+    /// `input`, as one ABSORB of `input.len() + 1` elements, like the Noir `absorb_sequenced`.
+    ///
+    /// Over a pattern of [`IoPattern::sequenced`], this binds every input to its position in
+    /// the protocol, whatever the framing of the inputs themselves.
+    ///
+    /// # Panics
+    /// If the next operation of the IO pattern is not an ABSORB of `input.len() + 1` elements.
+    pub fn absorb_sequenced(&mut self, input: &[P::Field]) {
+        let sequence = P::Field::from(self.io_count as u64);
+        self.begin_absorb(input.len() + 1);
+        #[cfg(feature = "tracing")]
+        self.trace_absorb(input.len() + 1, input);
+        self.absorb_element(sequence);
+        for &element in input {
+            self.absorb_element(element);
+        }
+    }
+
+    /// Absorbs a byte string, encoded into field elements with its length (see
+    /// [`encoding`](crate::encoding)).
+    ///
+    /// # Panics
+    /// If the next operation of the IO pattern is not the ABSORB declared with
+    /// [`IoPattern::absorb_bytes`] for `bytes.len()` bytes.
+    pub fn absorb_bytes(&mut self, bytes: &[u8]) {
+        self.absorb(&encode_bytes::<P::Field>(bytes));
+    }
+
+    /// Absorbs the canonical encoding of `value` (see [`absorb`](crate::absorb)).
+    ///
+    /// # Panics
+    /// If the next operation of the IO pattern is not the ABSORB declared with
+    /// [`IoPattern::absorb_value`] for `value`.
+    pub fn absorb_value<T: Absorb<P::Field> + ?Sized>(&mut self, value: &T) {
+        self.absorb(&value.to_elements());
+    }
+
+    /// Absorbs the first `byte_len` bytes of `reader` as a single ABSORB, in constant memory:
+    /// the length first, then the chunks of `encoding`. With [`ChunkEncoding::Packed`] this
+    /// absorbs the same elements as [`Sponge::absorb_bytes`] on the whole stream.
+    ///
+    /// On a read error (including a stream shorter than `byte_len`) the sponge is left partway
+    /// through the operation and must be discarded.
+    ///
+    /// # Panics
+    /// If the next operation of the IO pattern is not an ABSORB of
+    /// `encoding.encoded_len(byte_len)` elements.
+    #[cfg(feature = "std")]
+    pub fn absorb_reader<R: std::io::Read>(
+        &mut self,
+        reader: R,
+        byte_len: u64,
+        encoding: ChunkEncoding,
+    ) -> std::io::Result<()> {
+        self.absorb_reader_from(reader, byte_len, encoding, false)
+    }
+
+    /// Runs [`Sponge::absorb_reader`], preceded by the sequence number of the operation as in
+    /// [`Sponge::absorb_sequenced`] if `sequenced` is set.
+    #[cfg(feature = "std")]
+    pub(crate) fn absorb_reader_from<R: std::io::Read>(
+        &mut self,
+        mut reader: R,
+        byte_len: u64,
+        encoding: ChunkEncoding,
+        sequenced: bool,
+    ) -> std::io::Result<()> {
+        #[cfg(feature = "shadow")]
+        let (before, mut read) = (self.snapshot(), Vec::new());
+
+        let mut buffer = self.begin_stream(byte_len, encoding, sequenced);
+        let mut remaining = byte_len;
+        while remaining > 0 {
+            let length = remaining.min(buffer.len() as u64) as usize;
+            reader.read_exact(&mut buffer[..length])?;
+            self.absorb_stream_chunks(&buffer[..length], encoding);
+            #[cfg(feature = "shadow")]
+            read.extend_from_slice(&buffer[..length]);
+            remaining -= length as u64;
+        }
+
+        #[cfg(feature = "shadow")]
+        self.check_stream(&before, &read, encoding, sequenced);
+        Ok(())
+    }
+
+    /// Same as [`Sponge::absorb_reader`], reading from an asynchronous `reader`.
+    ///
+    /// # Panics
+    /// If the next operation of the IO pattern is not an ABSORB of
+    /// `encoding.encoded_len(byte_len)` elements.
+    #[cfg(feature = "tokio")]
+    pub async fn absorb_async_reader<R: tokio::io::AsyncRead + Unpin>(
+        &mut self,
+        reader: R,
+        byte_len: u64,
+        encoding: ChunkEncoding,
+    ) -> std::io::Result<()> {
+        self.absorb_async_reader_from(reader, byte_len, encoding, false)
+            .await
+    }
+
+    /// Runs [`Sponge::absorb_async_reader`], sequenced as in [`Sponge::absorb_reader_from`].
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn absorb_async_reader_from<R: tokio::io::AsyncRead + Unpin>(
+        &mut self,
+        mut reader: R,
+        byte_len: u64,
+        encoding: ChunkEncoding,
+        sequenced: bool,
+    ) -> std::io::Result<()> {
+        use tokio::io::AsyncReadExt;
+
+        #[cfg(feature = "shadow")]
+        let (before, mut read) = (self.snapshot(), Vec::new());
+
+        let mut buffer = self.begin_stream(byte_len, encoding, sequenced);
+        let mut remaining = byte_len;
+        while remaining > 0 {
+            let length = remaining.min(buffer.len() as u64) as usize;
+            reader.read_exact(&mut buffer[..length]).await?;
+            self.absorb_stream_chunks(&buffer[..length], encoding);
+            #[cfg(feature = "shadow")]
+            read.extend_from_slice(&buffer[..length]);
+            remaining -= length as u64;
+        }
+
+        #[cfg(feature = "shadow")]
+        self.check_stream(&before, &read, encoding, sequenced);
+        Ok(())
+    }
+
+    /// Starts the ABSORB of a stream of `byte_len` bytes and absorbs its length, returning the
+    /// read buffer: a whole number of chunks, so only the last read ends with a partial chunk.
+    #[cfg(feature = "std")]
+    fn begin_stream(
+        &mut self,
+        byte_len: u64,
+        encoding: ChunkEncoding,
+        sequenced: bool,
+    ) -> Zeroizing<Vec<u8>> {
+        let sequence = P::Field::from(self.io_count as u64);
+        let encoded_len = encoding.encoded_len::<P::Field>(byte_len);
+        self.begin_absorb(encoded_len + sequenced as usize);
+        if sequenced {
+            self.absorb_element(sequence);
+        }
+        self.absorb_element(P::Field::from(byte_len));
+        Zeroizing::new(vec![
+            0u8;
+            encoding.chunk_size::<P::Field>() * READER_BUFFER_CHUNKS
+        ])
+    }
+
+    /// Absorbs the chunks of `bytes`, read from a stream started by [`Sponge::begin_stream`].
+    #[cfg(feature = "std")]
+    fn absorb_stream_chunks(&mut self, bytes: &[u8], encoding: ChunkEncoding) {
+        for chunk in bytes.chunks(encoding.chunk_size::<P::Field>()) {
+            self.absorb_element(PrimeField::from_be_bytes_mod_order(chunk));
+        }
+    }
+
+    /// Reference of a streamed ABSORB: undoes it from `before` and absorbs the whole encoded
+    /// stream `read` at once.
+    #[cfg(all(feature = "std", feature = "shadow"))]
+    fn check_stream(
+        &mut self,
+        before: &Snapshot<P::Field>,
+        read: &[u8],
+        encoding: ChunkEncoding,
+        sequenced: bool,
+    ) {
+        let streamed = self.snapshot();
+        self.restore(before);
+        let elements = match encoding {
+            ChunkEncoding::Packed => encode_bytes::<P::Field>(read),
+            ChunkEncoding::Bytes => {
+                let mut elements = vec![P::Field::from(read.len() as u64)];
+                elements.extend(read.iter().map(|&byte| P::Field::from(byte)));
+                elements
+            }
+        };
+        match sequenced {
+            true => self.absorb_sequenced(&elements),
+            false => self.absorb(&elements),
+        }
+        crate::shadow::compare("Sponge::absorb_reader", &streamed, &self.snapshot());
+    }
+
+    /// Extracts field elements from the sponge state, interleaving permutation calls as needed (following spec 2.4).
+    ///
+    /// The number of elements to squeeze is determined from the IO pattern.
+    ///
+    /// # Panics
+    /// If the next operation of the IO pattern is not a SQUEEZE.
+    pub fn squeeze(&mut self) -> Vec<P::Field> {
+        let length = self.begin_squeeze();
+        // If length==0, no element is squeezed (spec 2.4).
+        let output: Vec<P::Field> = (0..length).map(|_| self.squeeze_element()).collect();
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            name: "safe.squeeze",
+            operation = self.io_count - 1,
+            label = self.io_pattern.label(self.io_count - 1).unwrap_or_default(),
+            length,
+            values = %crate::instrument::values(&output)
+        );
+        output
+    }
+
+    /// Squeezes `num_bytes` uniform bytes, as the SQUEEZE appended by
+    /// [`IoPattern::squeeze_bytes`].
+    ///
+    /// A field element is not a uniform byte string, and its top bits are biased by the
+    /// modulus. Each squeezed element contributes only its
+    /// [`bytes_per_squeezed_element`](crate::rng::bytes_per_squeezed_element) least
+    /// significant bytes, big-endian, which are within `2^-128` of uniform (15 bytes over
+    /// BN254), and the bytes of the last element beyond `num_bytes` are dropped. These are
+    /// the bytes of [`SpongeRng`](crate::rng::SpongeRng).
+    ///
+    /// # Panics
+    /// If the next operation of the IO pattern is not a SQUEEZE of
+    /// [`squeeze_bytes_len`]`(num_bytes)` elements.
+    pub fn squeeze_bytes(&mut self, num_bytes: usize) -> Vec<u8> {
+        assert!(
+            self.next_operation()
+                == Operation::Squeeze(squeeze_bytes_len::<P::Field>(num_bytes) as u32),
+            "Length mismatch"
+        );
+        let mut bytes: Vec<u8> = self
+            .squeeze()
+            .into_iter()
+            .flat_map(squeezed_bytes)
+            .collect();
+        bytes.truncate(num_bytes);
+        bytes
+    }
+
+    /// Absorbs `input` as the operation named `label` in the IO pattern.
+    ///
+    /// # Panics
+    /// If the next operation of the IO pattern is not the ABSORB labeled `label`, of
+    /// `input.len()` elements.
+    pub fn absorb_labeled(&mut self, label: &str, input: &[P::Field]) {
+        assert!(self.next_label() == Some(label), "Unexpected absorb label");
+        self.absorb(input)
+    }
+
+    /// Squeezes the operation named `label` in the IO pattern.
+    ///
+    /// # Panics
+    /// If the next operation of the IO pattern is not the SQUEEZE labeled `label`.
+    pub fn squeeze_labeled(&mut self, label: &str) -> Vec<P::Field> {
+        assert!(self.next_label() == Some(label), "Unexpected squeeze label");
+        self.squeeze()
+    }
+
+    /// Finalizes the sponge instance, verifying that all expected operations have been performed and clearing the internal state (following spec 2.4).
+    ///
+    /// # Panics
+    /// If operations of the IO pattern remain.
+    pub fn finish(&mut self) {
+        // Check that io_count equals the length of the IO pattern expected (spec 2.4).
+        assert!(
+            self.io_count == self.io_pattern.len(),
+            "IO pattern not completed"
+        );
+        #[cfg(feature = "test-utils")]
+        crate::coverage::record(self.tag);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            name: "safe.sponge.finish",
+            permutation = P::ID,
+            operations = self.io_count
+        );
+
+        // Erase the state and its variables (spec 2.4).
+        self.zeroize();
+    }
+
+    /// Returns the label of the next operation of the IO pattern, if any.
+    pub(crate) fn next_label(&self) -> Option<&str> {
+        self.io_pattern.label(self.io_count)
+    }
+
+    /// Starts the next operation, an ABSORB of `length` elements which the caller then feeds
+    /// one by one to [`Sponge::absorb_element`].
+    fn begin_absorb(&mut self, length: usize) {
+        // Validate against IO pattern.
+        let expected = self.next_operation();
+        assert!(expected.is_absorb(), "Expected ABSORB operation");
+        assert!(expected.len() as usize == length, "Length mismatch");
+
+        self.io_count += 1;
+
+        // Force permute at start of next SQUEEZE (spec 2.4).
+        self.squeeze_pos = P::RATE;
+    }
+
+    /// Records the ABSORB of `length` elements just started, ending with `elements` (all of
+    /// them but the sequence number of a sequenced ABSORB).
+    #[cfg(feature = "tracing")]
+    fn trace_absorb(&self, length: usize, elements: &[P::Field]) {
+        tracing::trace!(
+            name: "safe.absorb",
+            operation = self.io_count - 1,
+            label = self.io_pattern.label(self.io_count - 1).unwrap_or_default(),
+            length,
+            values = %crate::instrument::values(elements)
+        );
+    }
+
+    fn absorb_element(&mut self, element: P::Field) {
+        // If absorb_pos == (n-c) then permute and reset (spec 2.4).
+        if self.absorb_pos == P::RATE {
+            self.permute();
+            self.absorb_pos = 0;
+        }
+
+        // Add X[i] to state at absorb_pos (spec 2.4).
+        self.state[self.absorb_pos + P::CAPACITY] += element;
+        self.absorb_pos += 1;
+    }
+
+    /// Starts the next operation, a SQUEEZE whose elements the caller then takes one by one
+    /// from [`Sponge::squeeze_element`]. Returns the number of elements.
+    pub(crate) fn begin_squeeze(&mut self) -> usize {
+        let expected = self.next_operation();
+        assert!(!expected.is_absorb(), "Expected SQUEEZE operation");
+
+        self.io_count += 1;
+        expected.len() as usize
+    }
+
+    /// Returns the number of operations of the IO pattern already started.
+    pub(crate) fn operations_done(&self) -> usize {
+        self.io_count
+    }
+
+    /// Returns the permutation of the sponge.
+    pub(crate) fn permutation(&self) -> &P {
+        &self.permutation
+    }
+
+    /// Returns the whole state, capacity first.
+    pub(crate) fn state(&self) -> &[P::Field] {
+        &self.state
+    }
+
+    pub(crate) fn squeeze_element(&mut self) -> P::Field {
+        // If squeeze_pos==(n-c) then permute and reset (spec 2.4).
+        if self.squeeze_pos == P::RATE {
+            self.permute();
+            self.squeeze_pos = 0;
+            self.absorb_pos = 0;
+        }
+        // Set Y[i] to state element at squeeze_pos (spec 2.4).
+        let element = self.state[self.squeeze_pos + P::CAPACITY];
+        self.squeeze_pos += 1;
+        element
+    }
+
+    /// Captures the mutable part of the state, for shadow execution.
+    #[cfg(feature = "shadow")]
+    pub(crate) fn snapshot(&self) -> Snapshot<P::Field> {
+        Snapshot {
+            state: self.state.clone(),
+            absorb_pos: self.absorb_pos,
+            squeeze_pos: self.squeeze_pos,
+            io_count: self.io_count,
+        }
+    }
+
+    /// Rewinds to a state captured by [`Sponge::snapshot`].
+    #[cfg(feature = "shadow")]
+    pub(crate) fn restore(&mut self, snapshot: &Snapshot<P::Field>) {
+        self.state.clone_from(&snapshot.state);
+        self.absorb_pos = snapshot.absorb_pos;
+        self.squeeze_pos = snapshot.squeeze_pos;
+        self.io_count = snapshot.io_count;
+    }
+
+    fn next_operation(&self) -> Operation {
+        assert!(
+            self.io_count < self.io_pattern.len(),
+            "IO pattern already completed"
+        );
+        self.io_pattern.operations()[self.io_count]
+    }
+
+    fn permute(&mut self) {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("safe.permutation", permutation = P::ID).entered();
+        self.permutation.permute(&mut self.state);
+    }
+}
+
+impl<P: Permutation + Clone> Sponge<P> {
+    /// Returns a copy of the sponge that absorbed the fork label `label` as the next operation,
+    /// declared with [`IoPattern::fork`]. This sponge is left as it was, so it can fork again.
+    ///
+    /// Sub-protocols that share a parent transcript fork it under distinct labels and derive
+    /// independent challenges, in parallel if need be, where plain clones would squeeze the
+    /// same ones. The label is absorbed as the SHA-256 of `SAFE-FORK-V1` and the label, encoded
+    /// as bytes, so that labels of any length fit the same operation:
+    ///
+    /// ```
+    /// use ark_bn254::Fr;
+    /// use safe_api::domain::padded_domain_separator;
+    /// use safe_api::poseidon2::Poseidon2;
+    /// use safe_api::{IoPattern, Sponge};
+    ///
+    /// let pattern = IoPattern::new().absorb(2).fork::<Fr>().squeeze(1);
+    /// let domain_separator = padded_domain_separator(b"my-app/batch");
+    /// let mut parent = Sponge::<Poseidon2>::start(pattern, &domain_separator);
+    /// parent.absorb(&[Fr::from(1u64), Fr::from(2u64)]);
+    ///
+    /// let mut left = parent.fork("left");
+    /// let mut right = parent.fork("right");
+    /// assert_ne!(left.squeeze(), right.squeeze());
+    /// # left.finish();
+    /// # right.finish();
+    /// ```
+    ///
+    /// # Panics
+    /// If the next operation of the IO pattern is not the ABSORB of [`IoPattern::fork`].
+    pub fn fork(&self, label: &str) -> Self {
+        let mut child = self.clone();
+        child.absorb(&fork_label::<P::Field>(label));
+        child
+    }
+}
+
+impl<P: Permutation> Zeroize for Sponge<P> {
+    fn zeroize(&mut self) {
+        self.state.iter_mut().for_each(Zeroize::zeroize);
+        self.absorb_pos = 0;
+        self.squeeze_pos = 0;
+        self.io_count = 0;
+    }
+}
+
+impl<P: Permutation> Drop for Sponge<P> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<P: Permutation> ZeroizeOnDrop for Sponge<P> {}
+
+/// Options of a [`Sponge`] or a [`Transcript`] before it starts.
+///
+/// ```
+/// use safe_api::poseidon2::Poseidon2;
+/// use safe_api::{IoPattern, Sponge};
+///
+/// // Interoperating with a circuit that uses no domain separator.
+/// let sponge = Sponge::<Poseidon2>::builder()
+///     .allow_empty_domain()
+///     .start(IoPattern::new().absorb(1).squeeze(1), &[0u8; 64]);
+/// # let _ = sponge;
+/// ```
+#[derive(Clone, Debug)]
+pub struct SpongeBuilder<P: Permutation> {
+    permutation: P,
+    allow_empty_domain: bool,
+    sequenced: bool,
+    tag_parameters: TagParameters,
+    config: Option<SpongeConfig>,
+}
+
+impl<P: Permutation> SpongeBuilder<P> {
+    /// Returns a builder for sponges running on the given permutation instance, with the
+    /// [defaults](crate::defaults) of the process.
+    pub fn new(permutation: P) -> Self {
+        Self::with_defaults(permutation, defaults::defaults())
+    }
+
+    /// Returns a builder for sponges running on the given permutation instance, with
+    /// `defaults` instead of those of the process.
+    pub fn with_defaults(permutation: P, defaults: Defaults) -> Self {
+        SpongeBuilder {
+            permutation,
+            allow_empty_domain: defaults.allow_empty_domain,
+            sequenced: defaults.sequenced,
+            tag_parameters: defaults.tag_parameters,
+            config: None,
+        }
+    }
+
+    /// Accepts the empty (all-zero) domain separator, e.g. to reproduce values of an
+    /// implementation that does not use one.
+    ///
+    /// Without a domain separator, sponges of different applications with the same IO pattern
+    /// share their tag, and their outputs can be replayed from one application into the other.
+    pub fn allow_empty_domain(mut self) -> Self {
+        self.allow_empty_domain = true;
+        self
+    }
+
+    /// Makes the transcripts [sequenced](Transcript#sequenced-transcripts): every message is
+    /// preceded by its position in the protocol. Has no effect on [`SpongeBuilder::start`].
+    pub fn sequenced(mut self) -> Self {
+        self.sequenced = true;
+        self
+    }
+
+    /// Derives the tag from `tag_length` bits of the digest instead of 128, see
+    /// [`compute_tag_field_with_length`](crate::tag::compute_tag_field_with_length). The Noir
+    /// sponge must then start with `SafeSponge::start_with_tag_length`.
+    pub fn tag_length(mut self, tag_length: TagLength) -> Self {
+        self.tag_parameters.length = tag_length;
+        self
+    }
+
+    /// Derives the tag with the hash `H` instead of SHA-256, see [`TagHasher`]. The Noir
+    /// sponge only supports SHA-256.
+    pub fn tag_hasher<H: TagHasher>(mut self) -> Self {
+        self.tag_parameters.set_hasher::<H>();
+        self
+    }
+
+    /// Checks that the permutation has the parameters of `config`, and folds them into the
+    /// domain separator of the sponges, see [`config`](crate::config).
+    pub fn config(mut self, config: SpongeConfig) -> Result<Self, ConfigError> {
+        config.validate::<P>()?;
+        self.config = Some(config);
+        Ok(self)
+    }
+
+    /// Starts the sponge, see [`Sponge::start`].
+    ///
+    /// # Panics
+    /// If `domain_separator` is empty and [`SpongeBuilder::allow_empty_domain`] was not called.
+    pub fn start(
+        self,
+        io_pattern: IoPattern,
+        domain_separator: impl Into<DomainSeparator>,
+    ) -> Sponge<P> {
+        let domain_separator = self.domain_separator(&domain_separator.into());
+        Sponge::init(
+            self.permutation,
+            io_pattern,
+            &domain_separator,
+            self.tag_parameters,
+        )
+    }
+
+    /// Resumes a sponge saved with [`Sponge::save`], folding the configuration of the builder
+    /// into `domain_separator` as [`SpongeBuilder::start`] does, see [`Sponge::resume`].
+    ///
+    /// The other options of the builder do not apply: the tag length and hasher are those
+    /// recorded in `state`.
+    pub fn resume(
+        self,
+        state: SpongeState<P::Field>,
+        domain_separator: impl Into<DomainSeparator>,
+    ) -> Result<Sponge<P>, StateError> {
+        let domain_separator = self.configured_domain_separator(&domain_separator.into());
+        Sponge::resume_with_permutation(self.permutation, state, domain_separator)
+    }
+
+    /// Continues a sponge exported with [`Sponge::export_state`], folding the configuration of
+    /// the builder into `domain_separator`, see [`SpongeBuilder::resume`].
+    pub fn import_state(
+        self,
+        snapshot: StateSnapshot<P::Field>,
+        domain_separator: impl Into<DomainSeparator>,
+    ) -> Result<Sponge<P>, StateError> {
+        let domain_separator = self.configured_domain_separator(&domain_separator.into());
+        Sponge::import_state_with_permutation(self.permutation, snapshot, domain_separator)
+    }
+
+    /// Resumes a transcript saved with [`Transcript::save`], folding the configuration of the
+    /// builder into `domain_separator`, see [`SpongeBuilder::resume`].
+    pub fn resume_transcript(
+        self,
+        state: SpongeState<P::Field>,
+        domain_separator: impl Into<DomainSeparator>,
+    ) -> Result<Transcript<P>, StateError> {
+        let domain_separator = self.configured_domain_separator(&domain_separator.into());
+        Transcript::resume_with_permutation(self.permutation, state, domain_separator)
+    }
+
+    /// Starts a transcript, see [`Transcript::new`].
+    ///
+    /// # Panics
+    /// If an operation of `protocol` has no label, or if `domain_separator` is empty and
+    /// [`SpongeBuilder::allow_empty_domain`] was not called.
+    pub fn transcript(
+        self,
+        protocol: IoPattern,
+        domain_separator: impl Into<DomainSeparator>,
+    ) -> Transcript<P> {
+        let domain_separator = self.domain_separator(&domain_separator.into());
+        Transcript::init(
+            self.permutation,
+            protocol,
+            &domain_separator,
+            self.sequenced,
+            self.tag_parameters,
+        )
+    }
+
+    /// Checks the domain separator of the application and returns the one the sponge runs
+    /// under.
+    fn domain_separator(&self, domain_separator: &[u8; 64]) -> [u8; 64] {
+        assert!(
+            self.allow_empty_domain || !is_empty_domain(domain_separator),
+            "Empty domain separator"
+        );
+        self.configured_domain_separator(domain_separator)
+    }
+
+    /// Returns the domain separator the sponge runs under, with the configuration folded in.
+    fn configured_domain_separator(&self, domain_separator: &[u8; 64]) -> [u8; 64] {
+        match &self.config {
+            Some(config) => config.domain_separator(domain_separator),
+            None => *domain_separator,
+        }
+    }
+}
+
+/// Returns the elements absorbed by [`Sponge::fork`] for `label`.
+fn fork_label<F: PrimeField>(label: &str) -> Vec<F> {
+    let digest = Sha256::new()
+        .chain_update(FORK_LABEL_PREFIX)
+        .chain_update(label.as_bytes())
+        .finalize();
+    encode_bytes::<F>(&digest)
+}
+
+/// The mutable part of a [`Sponge`], compared by shadow execution.
+#[cfg(feature = "shadow")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Snapshot<F> {
+    state: Vec<F>,
+    absorb_pos: usize,
+    squeeze_pos: usize,
+    io_count: usize,
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::poseidon2::{poseidon2_permutation, Poseidon2};
+    use ark_bn254::Fr;
+    use core::str::FromStr;
+
+    type Poseidon2Sponge = Sponge<Poseidon2>;
+
+    fn abcd_domain() -> [u8; 64] {
+        crate::domain::padded_domain_separator(b"ABCD")
+    }
+
+    fn hash(io_pattern: IoPattern, domain_separator: &[u8; 64], inputs: &[&[Fr]]) -> Vec<Fr> {
+        let mut sponge = Poseidon2Sponge::start(io_pattern, domain_separator);
+        let mut output = Vec::new();
+        let mut inputs = inputs.iter();
+        for operation in sponge.io_pattern().operations().to_vec() {
+            if operation.is_absorb() {
+                sponge.absorb(inputs.next().unwrap());
+            } else {
+                output.extend(sponge.squeeze());
+            }
+        }
+        sponge.finish();
+        output
+    }
+
+    #[test]
+    fn test_safe_hashing() {
+        // Mirrors `test_safe_hashing` of the Noir implementation.
+        let elements = [Fr::from(1u64), Fr::from(2u64), Fr::from(3u64)];
+        let pattern = IoPattern::new().absorb(3).squeeze(1);
+
+        let output = hash(pattern.clone(), &abcd_domain(), &[&elements]);
+        assert_eq!(output.len(), 1);
+        assert_ne!(output[0], Fr::from(0u64));
+        assert_eq!(output, hash(pattern, &abcd_domain(), &[&elements]));
+    }
+
+    #[test]
+    fn test_output_matches_manual_permutation() {
+        // ABSORB(2), SQUEEZE(1): one permutation over [tag, x0, x1, 0], output is state[1].
+        let inputs = [Fr::from(123u64), Fr::from(456u64)];
+        let pattern = IoPattern::new().absorb(2).squeeze(1);
+        let tag = pattern.tag_field::<Fr>(&abcd_domain());
+
+        let mut state = [tag, inputs[0], inputs[1], Fr::from(0u64)];
+        poseidon2_permutation(&mut state);
+
+        assert_eq!(hash(pattern, &abcd_domain(), &[&inputs]), vec![state[1]]);
+    }
+
+    #[test]
+    fn test_absorb_sequenced() {
+        // Same value as `test_absorb_sequenced` of the Noir implementation.
+        let pattern = IoPattern::new().absorb(1).absorb(2).squeeze(1).sequenced();
+        assert_eq!(
+            pattern.encoded_words(),
+            vec![0x80000002, 0x80000003, 0x00000001]
+        );
+        let mut sponge = Poseidon2Sponge::start(pattern.clone(), abcd_domain());
+        sponge.absorb_sequenced(&[Fr::from(7u64)]);
+        sponge.absorb_sequenced(&[Fr::from(1u64), Fr::from(2u64)]);
+        let output = sponge.squeeze();
+        sponge.finish();
+
+        // The sequence numbers are the operation indices 0 and 1.
+        let first = [Fr::from(0u64), Fr::from(7u64)];
+        let second = [Fr::from(1u64), Fr::from(1u64), Fr::from(2u64)];
+        assert_eq!(output, hash(pattern, &abcd_domain(), &[&first, &second]));
+        let expected = Fr::from_str(
+            "16449588113115071961955843878794988601969067932216637004300557843976777566768",
+        )
+        .unwrap();
+        assert_eq!(output, vec![expected]);
+    }
+
+    #[test]
+    fn test_fork() {
+        let pattern = IoPattern::new().absorb(1).fork::<Fr>().squeeze(2);
+        let mut parent = Poseidon2Sponge::start(pattern.clone(), abcd_domain());
+        parent.absorb(&[Fr::from(1u64)]);
+
+        let squeeze = |mut child: Poseidon2Sponge| {
+            let output = child.squeeze();
+            child.finish();
+            output
+        };
+        let left = squeeze(parent.fork("left"));
+        assert_eq!(squeeze(parent.fork("left")), left);
+        assert_ne!(squeeze(parent.fork("right")), left);
+        assert_ne!(
+            squeeze(parent.fork("a much longer sub-protocol label")),
+            left
+        );
+
+        // A fork is an ABSORB of the encoded label digest.
+        let label = fork_label::<Fr>("left");
+        assert_eq!(label.len(), 3);
+        let mut manual = Poseidon2Sponge::start(pattern, abcd_domain());
+        manual.absorb(&[Fr::from(1u64)]);
+        manual.absorb(&label);
+        assert_eq!(squeeze(manual), left);
+    }
+
+    #[test]
+    #[should_panic(expected = "Length mismatch")]
+    fn test_fork_undeclared() {
+        let sponge = Poseidon2Sponge::start(IoPattern::new().absorb(1).squeeze(1), abcd_domain());
+        let _ = sponge.fork("left");
+    }
+
+    #[test]
+    fn test_domain_separation() {
+        let elements = [Fr::from(1u64), Fr::from(2u64), Fr::from(3u64)];
+        let mut other_domain = abcd_domain();
+        other_domain[3] = 0x45;
+
+        let pattern = IoPattern::new().absorb(3).squeeze(1);
+        assert_ne!(
+            hash(pattern.clone(), &abcd_domain(), &[&elements]),
+            hash(pattern, &other_domain, &[&elements])
+        );
+    }
+
+    /// The 64-bit Goldilocks field, to exercise the sponge away from BN254.
+    #[derive(ark_ff::MontConfig)]
+    #[modulus = "18446744069414584321"]
+    #[generator = "7"]
+    struct GoldilocksConfig;
+    type Goldilocks = ark_ff::Fp64<ark_ff::MontBackend<GoldilocksConfig, 1>>;
+
+    /// A toy width-3 permutation (not secure), enough to drive the sponge.
+    #[derive(Default)]
+    struct ToyPermutation;
+
+    impl Permutation for ToyPermutation {
+        type Field = Goldilocks;
+        const WIDTH: usize = 3;
+        const RATE: usize = 2;
+
+        fn permute(&self, state: &mut [Goldilocks]) {
+            let sum: Goldilocks = state.iter().sum();
+            for (i, element) in state.iter_mut().enumerate() {
+                *element = (*element * *element * *element) + sum + Goldilocks::from(i as u64);
+            }
+        }
+    }
+
+    #[test]
+    fn test_generic_field() {
+        let pattern = IoPattern::new().absorb(3).squeeze(2);
+        let mut sponge = Sponge::<ToyPermutation>::start(pattern.clone(), abcd_domain());
+
+        // The 128-bit tag is reduced into the 64-bit field.
+        assert_eq!(sponge.tag(), pattern.tag(abcd_domain()).to_field());
+
+        sponge.absorb(&[1u64, 2, 3].map(Goldilocks::from));
+        assert_eq!(sponge.squeeze().len(), 2);
+        sponge.finish();
+    }
+
+    #[cfg(feature = "small-fields")]
+    #[test]
+    fn test_small_fields() {
+        use crate::fields::{BabyBear, Goldilocks};
+        use crate::poseidon2::baby_bear::Poseidon2BabyBear;
+        use crate::poseidon2::goldilocks::Poseidon2Goldilocks;
+
+        let pattern = IoPattern::new().absorb(10).squeeze(3);
+        let mut sponge = Sponge::<Poseidon2BabyBear>::start(pattern.clone(), abcd_domain());
+        // The 128-bit tag takes 5 elements of the capacity.
+        let limbs = pattern.tag_limbs::<BabyBear>(&abcd_domain(), TagLength::Bits128, 8);
+        assert_eq!(limbs.len(), 5);
+        assert_eq!(sponge.state[..5], limbs[..]);
+        assert_eq!(sponge.tag(), limbs[0]);
+
+        sponge.absorb(&[0u64, 1, 2, 3, 4, 5, 6, 7, 8, 9].map(BabyBear::from));
+        let saved = sponge.save();
+        let output = sponge.squeeze();
+        sponge.finish();
+        let mut resumed = Sponge::<Poseidon2BabyBear>::resume(saved, abcd_domain()).unwrap();
+        assert_eq!(resumed.squeeze(), output);
+        resumed.finish();
+
+        let mut sponge = Sponge::<Poseidon2Goldilocks>::start(pattern.clone(), abcd_domain());
+        assert_eq!(
+            sponge.state[..3],
+            pattern.tag_limbs::<Goldilocks>(&abcd_domain(), TagLength::Bits128, 4)[..]
+        );
+        assert_eq!(sponge.state[3], Goldilocks::from(0u64));
+        sponge.absorb(&[0u64, 1, 2, 3, 4, 5, 6, 7, 8, 9].map(Goldilocks::from));
+        assert_eq!(sponge.squeeze().len(), 3);
+        sponge.finish();
+    }
+
+    #[test]
+    fn test_labeled_squeezes() {
+        let pattern = IoPattern::new()
+            .absorb(1)
+            .squeeze_labeled("alpha", 1)
+            .squeeze_labeled("beta", 2);
+        let mut sponge = Poseidon2Sponge::start(pattern.clone(), abcd_domain());
+        sponge.absorb(&[Fr::from(7u64)]);
+        let alpha = sponge.squeeze_labeled("alpha");
+        let beta = sponge.squeeze_labeled("beta");
+        sponge.finish();
+
+        let mut unlabeled = Poseidon2Sponge::start(pattern, abcd_domain());
+        unlabeled.absorb(&[Fr::from(7u64)]);
+        assert_eq!(unlabeled.squeeze(), alpha);
+        assert_eq!(unlabeled.squeeze(), beta);
+    }
+
+    #[test]
+    fn test_absorb_bytes() {
+        let digest = [0xabu8; 32];
+        let pattern = IoPattern::new().absorb_bytes::<Fr>(digest.len()).squeeze(1);
+        assert_eq!(pattern, IoPattern::new().absorb(3).squeeze(1));
+
+        let mut sponge = Poseidon2Sponge::start(pattern.clone(), abcd_domain());
+        sponge.absorb_bytes(&digest);
+        let output = sponge.squeeze();
+        sponge.finish();
+
+        let elements = crate::encoding::encode_bytes::<Fr>(&digest);
+        assert_eq!(output, hash(pattern, &abcd_domain(), &[&elements]));
+    }
+
+    #[test]
+    fn test_squeeze_bytes() {
+        use ark_std::rand::RngCore;
+
+        // 32 bytes take 3 elements of 15 bytes over BN254.
+        let pattern = IoPattern::new().absorb(1).squeeze_bytes::<Fr>(32);
+        assert_eq!(pattern, IoPattern::new().absorb(1).squeeze(3));
+
+        let mut sponge = Poseidon2Sponge::start(pattern.clone(), abcd_domain());
+        sponge.absorb(&[Fr::from(7u64)]);
+        let mut rng = crate::rng::SpongeRng::new(sponge.clone());
+        let bytes = sponge.squeeze_bytes(32);
+        sponge.finish();
+
+        // The same bytes as the randomness of the sponge, the last element truncated.
+        let mut expected = [0u8; 45];
+        rng.fill_bytes(&mut expected);
+        assert_eq!(bytes, expected[..32]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Length mismatch")]
+    fn test_squeeze_bytes_length() {
+        let pattern = IoPattern::new().absorb(1).squeeze(3);
+        let mut sponge = Poseidon2Sponge::start(pattern, abcd_domain());
+        sponge.absorb(&[Fr::from(7u64)]);
+        sponge.squeeze_bytes(16);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_absorb_reader() {
+        use crate::encoding::ChunkEncoding;
+
+        // Spans several buffers and ends with a partial chunk.
+        let data: Vec<u8> = (0..150_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let length = ChunkEncoding::Packed.encoded_len::<Fr>(data.len() as u64);
+        let pattern = IoPattern::new().absorb(length as u32).squeeze(1);
+        assert_eq!(
+            pattern,
+            IoPattern::new().absorb_bytes::<Fr>(data.len()).squeeze(1)
+        );
+
+        let mut streamed = Poseidon2Sponge::start(pattern.clone(), abcd_domain());
+        streamed
+            .absorb_reader(&data[..], data.len() as u64, ChunkEncoding::Packed)
+            .unwrap();
+        let mut buffered = Poseidon2Sponge::start(pattern, abcd_domain());
+        buffered.absorb_bytes(&data);
+        assert_eq!(streamed.squeeze(), buffered.squeeze());
+
+        let pattern = IoPattern::new().absorb(4).squeeze(1);
+        let mut sponge = Poseidon2Sponge::start(pattern.clone(), abcd_domain());
+        sponge
+            .absorb_reader(&[1u8, 2, 3, 4][..], 3, ChunkEncoding::Bytes)
+            .unwrap();
+        let elements = [3u64, 1, 2, 3].map(Fr::from);
+        assert_eq!(
+            sponge.squeeze(),
+            hash(pattern, &abcd_domain(), &[&elements])
+        );
+
+        let pattern = IoPattern::new().absorb(2).squeeze(1);
+        let mut sponge = Poseidon2Sponge::start(pattern, abcd_domain());
+        let error = sponge
+            .absorb_reader(&[1u8][..], 2, ChunkEncoding::Packed)
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_absorb_async_reader() {
+        use crate::encoding::ChunkEncoding;
+        use core::future::Future;
+        use core::task::{Context, Poll, Waker};
+
+        let data: Vec<u8> = (0..70_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let pattern = IoPattern::new().absorb_bytes::<Fr>(data.len()).squeeze(1);
+        let mut streamed = Poseidon2Sponge::start(pattern.clone(), abcd_domain());
+        // Slices are always ready, so a single poll runs the future to completion.
+        {
+            let future =
+                streamed.absorb_async_reader(&data[..], data.len() as u64, ChunkEncoding::Packed);
+            let poll = core::pin::pin!(future).poll(&mut Context::from_waker(Waker::noop()));
+            assert!(matches!(poll, Poll::Ready(Ok(()))));
+        }
+
+        let mut buffered = Poseidon2Sponge::start(pattern, abcd_domain());
+        buffered.absorb_bytes(&data);
+        assert_eq!(streamed.squeeze(), buffered.squeeze());
+    }
+
+    #[test]
+    #[should_panic(expected = "Unexpected squeeze label")]
+    fn test_reordered_labels_panic() {
+        let pattern = IoPattern::new()
+            .squeeze_labeled("alpha", 1)
+            .squeeze_labeled("beta", 1);
+        let mut sponge = Poseidon2Sponge::start(pattern, abcd_domain());
+        sponge.squeeze_labeled("beta");
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected SQUEEZE operation")]
+    fn test_pattern_mismatch_panics() {
+        let mut sponge =
+            Poseidon2Sponge::start(IoPattern::new().absorb(1).squeeze(1), abcd_domain());
+        sponge.squeeze();
+    }
+
+    #[test]
+    #[should_panic(expected = "Empty domain separator")]
+    fn test_empty_domain_panics() {
+        Poseidon2Sponge::start(IoPattern::new().absorb(1).squeeze(1), [0u8; 64]);
+    }
+
+    #[test]
+    fn test_allow_empty_domain() {
+        let pattern = IoPattern::new().absorb(1).squeeze(1);
+        let mut sponge = Poseidon2Sponge::builder()
+            .allow_empty_domain()
+            .start(pattern.clone(), [0u8; 64]);
+        let mut unchecked =
+            Poseidon2Sponge::init(Poseidon2, pattern, &[0u8; 64], TagParameters::default());
+        sponge.absorb(&[Fr::from(1u64)]);
+        unchecked.absorb(&[Fr::from(1u64)]);
+        assert_eq!(sponge.squeeze(), unchecked.squeeze());
+
+        let protocol = IoPattern::new().absorb_labeled("x", 1);
+        Poseidon2Sponge::builder()
+            .allow_empty_domain()
+            .transcript(protocol, [0u8; 64]);
+    }
+
+    #[test]
+    fn test_tag_length() {
+        // Same value as `test_start_with_tag_length` of the Noir library.
+        let pattern = IoPattern::new().absorb(3).squeeze(1);
+        let mut sponge = Poseidon2Sponge::builder()
+            .tag_length(TagLength::Full)
+            .start(pattern.clone(), abcd_domain());
+        assert_eq!(
+            sponge.tag(),
+            pattern.tag_field_with_length::<Fr>(&abcd_domain(), TagLength::Full)
+        );
+        sponge.absorb(&[Fr::from(1u64), Fr::from(2u64), Fr::from(3u64)]);
+        let mut resumed = Poseidon2Sponge::resume(sponge.save(), abcd_domain()).unwrap();
+        let output = sponge.squeeze();
+        assert_eq!(resumed.squeeze(), output);
+        assert_eq!(
+            output[0],
+            Fr::from_str(
+                "3494418219034515150874562806990364434253492958998013001031158610280425558301"
+            )
+            .unwrap()
+        );
+        assert_ne!(
+            output,
+            hash(
+                pattern,
+                &abcd_domain(),
+                &[&[Fr::from(1u64), Fr::from(2u64), Fr::from(3u64)]]
+            )
+        );
+        resumed.finish();
+        sponge.finish();
+    }
+
+    #[cfg(feature = "blake2s")]
+    #[test]
+    fn test_tag_hasher() {
+        use crate::tag::Blake2sHasher;
+
+        let pattern = IoPattern::new().absorb(1).squeeze(1);
+        let mut sponge = Poseidon2Sponge::builder()
+            .tag_hasher::<Blake2sHasher>()
+            .start(pattern.clone(), abcd_domain());
+        assert_eq!(
+            sponge.tag(),
+            pattern
+                .tag_with_hasher::<Blake2sHasher>(&abcd_domain())
+                .to_field::<Fr>()
+        );
+        sponge.absorb(&[Fr::from(1u64)]);
+        let mut resumed = Poseidon2Sponge::resume(sponge.save(), abcd_domain()).unwrap();
+        assert_eq!(resumed.squeeze(), sponge.squeeze());
+    }
+}
@@ -0,0 +1,174 @@
+//! The SAFE sponge behind the `spongefish` (formerly nimue) duplex sponge interface.
+//!
+//! [`SafeDuplex`] implements [`DuplexSpongeInterface`], so protocols written against the
+//! spongefish [`ProverState`] and [`VerifierState`] run on SAFE-tagged sponges unchanged. Those
+//! protocols absorb and squeeze bytes (the arkworks codecs of spongefish encode field elements
+//! as bytes), while every call must still match the next operation of the IO pattern of the
+//! wrapped [`Sponge`]:
+//!
+//! - `absorb(bytes)`, i.e. a public or prover message, is one ABSORB of the bytes encoded into
+//!   field elements, declared with [`IoPattern::absorb_bytes`] and absorbed as
+//!   [`Sponge::absorb_bytes`]. An arkworks field element of BN254 is 32 bytes.
+//! - `squeeze(output)`, i.e. a verifier message, is one SQUEEZE of
+//!   [`squeeze_len`]`(output.len())` elements, each carrying [`bytes_per_squeezed_element`]
+//!   bytes as in [`SpongeRng`](crate::rng::SpongeRng). A BN254 challenge takes 64 bytes.
+//! - `ratchet` has no SAFE counterpart and panics. The prover and verifier states only
+//!   ratchet their private randomness, never the transcript sponge.
+//!
+//! A mismatch panics, like any misuse of [`Sponge`]:
+//!
+//! ```
+//! use ark_bn254::Fr;
+//! use safe_api::domain::padded_domain_separator;
+//! use safe_api::poseidon2::Poseidon2;
+//! use safe_api::spongefish::{squeeze_len, SafeDuplex};
+//! use safe_api::{IoPattern, Sponge};
+//! use spongefish::{ProverState, VerifierState};
+//!
+//! // A prover message of one field element, then a challenge.
+//! let pattern = IoPattern::new()
+//!     .absorb_bytes::<Fr>(32)
+//!     .squeeze(squeeze_len::<Fr>(64) as u32);
+//! let domain_separator = padded_domain_separator(b"my-app/schnorr");
+//! let start = || SafeDuplex::new(Sponge::<Poseidon2>::start(pattern.clone(), &domain_separator));
+//!
+//! let mut prover: ProverState<SafeDuplex<Poseidon2>> = ProverState::from(start());
+//! prover.prover_message(&Fr::from(42u64));
+//! let challenge: Fr = prover.verifier_message();
+//!
+//! let mut verifier = VerifierState::from_parts(start(), prover.narg_string());
+//! assert_eq!(verifier.prover_message::<Fr>().unwrap(), Fr::from(42u64));
+//! assert_eq!(verifier.verifier_message::<Fr>(), challenge);
+//! ```
+//!
+//! The states never call [`Sponge::finish`]: the IO pattern is enforced one operation at a
+//! time, and a protocol that stops early squeezes no further challenge.
+//!
+//! [`ProverState`]: ::spongefish::ProverState
+//! [`VerifierState`]: ::spongefish::VerifierState
+
+use alloc::vec::Vec;
+
+use ::spongefish::DuplexSpongeInterface;
+use ark_ff::{BigInteger, PrimeField};
+
+use crate::io_pattern::IoPattern;
+use crate::permutation::Permutation;
+use crate::rng::bytes_per_squeezed_element;
+use crate::sponge::Sponge;
+
+/// Returns the number of elements of `F` of the SQUEEZE behind a `num_bytes`-byte squeeze.
+pub fn squeeze_len<F: PrimeField>(num_bytes: usize) -> usize {
+    num_bytes.div_ceil(bytes_per_squeezed_element::<F>())
+}
+
+/// A [`Sponge`] implementing [`DuplexSpongeInterface`] over bytes, see the
+/// [module documentation](self).
+#[derive(Clone, Debug)]
+pub struct SafeDuplex<P: Permutation> {
+    sponge: Sponge<P>,
+}
+
+impl<P: Permutation> SafeDuplex<P> {
+    /// Wraps a sponge, e.g. one that already absorbed part of its IO pattern.
+    pub fn new(sponge: Sponge<P>) -> Self {
+        SafeDuplex { sponge }
+    }
+
+    /// Returns the IO pattern of the wrapped sponge.
+    pub fn io_pattern(&self) -> &IoPattern {
+        self.sponge.io_pattern()
+    }
+
+    /// Returns the wrapped sponge.
+    pub fn into_sponge(self) -> Sponge<P> {
+        self.sponge
+    }
+}
+
+impl<P: Permutation + Clone> DuplexSpongeInterface for SafeDuplex<P> {
+    type U = u8;
+
+    fn absorb(&mut self, input: &[u8]) -> &mut Self {
+        self.sponge.absorb_bytes(input);
+        self
+    }
+
+    fn squeeze(&mut self, output: &mut [u8]) -> &mut Self {
+        let length = bytes_per_squeezed_element::<P::Field>();
+        let elements = self.sponge.squeeze();
+        assert!(
+            elements.len() == squeeze_len::<P::Field>(output.len()),
+            "Length mismatch"
+        );
+        let bytes: Vec<u8> = elements
+            .iter()
+            .flat_map(|element| {
+                let element = element.into_bigint().to_bytes_be();
+                element[element.len() - length..].to_vec()
+            })
+            .collect();
+        output.copy_from_slice(&bytes[..output.len()]);
+        self
+    }
+
+    /// # Panics
+    /// Always: ratcheting is not an operation of the SAFE IO pattern.
+    fn ratchet(&mut self) -> &mut Self {
+        panic!("Ratchet is not part of the SAFE IO pattern")
+    }
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::domain::padded_domain_separator;
+    use crate::poseidon2::Poseidon2;
+    use ::spongefish::{ProverState, VerifierState};
+    use ark_bn254::Fr;
+
+    fn pattern() -> IoPattern {
+        IoPattern::new()
+            .absorb_bytes::<Fr>(8)
+            .absorb_bytes::<Fr>(32)
+            .squeeze(squeeze_len::<Fr>(64) as u32)
+    }
+
+    fn start() -> SafeDuplex<Poseidon2> {
+        SafeDuplex::new(Sponge::start(pattern(), padded_domain_separator(b"test")))
+    }
+
+    #[test]
+    fn test_matches_sponge() {
+        let mut prover: ProverState<SafeDuplex<Poseidon2>> = ProverState::from(start());
+        prover.public_message(&7u64);
+        prover.prover_message(&Fr::from(42u64));
+        let challenge: [u8; 64] = prover.verifier_message();
+
+        let mut sponge = Sponge::<Poseidon2>::start(pattern(), padded_domain_separator(b"test"));
+        sponge.absorb_bytes(&7u64.to_le_bytes());
+        sponge.absorb_bytes(&Fr::from(42u64).into_bigint().to_bytes_be());
+        let expected: Vec<u8> = sponge
+            .squeeze()
+            .iter()
+            .flat_map(|element| element.into_bigint().to_bytes_be()[17..].to_vec())
+            .take(64)
+            .collect();
+        sponge.finish();
+        assert_eq!(challenge.to_vec(), expected);
+
+        let mut verifier = VerifierState::from_parts(start(), prover.narg_string());
+        verifier.public_message(&7u64);
+        assert_eq!(verifier.prover_message::<Fr>().unwrap(), Fr::from(42u64));
+        assert_eq!(verifier.verifier_message::<[u8; 64]>(), challenge);
+        assert!(verifier.check_eof().is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "Length mismatch")]
+    fn test_rejects_other_pattern() {
+        let mut prover: ProverState<SafeDuplex<Poseidon2>> = ProverState::from(start());
+        // The pattern starts with an ABSORB of 8 bytes, not 32.
+        prover.prover_message(&Fr::from(42u64));
+    }
+}
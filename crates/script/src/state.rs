@@ -0,0 +1,845 @@
+//! Checkpoints of a sponge, to pause a protocol and resume it later or in another process.
+//!
+//! [`Sponge::save`] captures a [`SpongeState`]: the permutation state, the tag, the absorb and
+//! squeeze positions, the IO pattern with its cursor and the length and hash the tag was
+//! derived with. [`Sponge::resume`] continues from it exactly where the saved sponge stood,
+//! after checking that the state is consistent and that its tag is the one of its IO pattern
+//! under the given domain separator, derived with the recorded length and hash, so a
+//! checkpoint cannot be resumed under another protocol:
+//!
+//! ```
+//! use ark_bn254::Fr;
+//! use safe_api::domain::padded_domain_separator;
+//! use safe_api::poseidon2::Poseidon2;
+//! use safe_api::state::SpongeState;
+//! use safe_api::{IoPattern, Sponge};
+//!
+//! let pattern = IoPattern::new().absorb(1).absorb(1).squeeze(1);
+//! let domain_separator = padded_domain_separator(b"my-app/protocol");
+//! let mut sponge = Sponge::<Poseidon2>::start(pattern, &domain_separator);
+//! sponge.absorb(&[Fr::from(1u64)]);
+//! let checkpoint = sponge.save().to_bytes();
+//!
+//! // Later, e.g. after a restart.
+//! let state = SpongeState::<Fr>::from_bytes(&checkpoint)?;
+//! let mut sponge = Sponge::<Poseidon2>::resume(state, &domain_separator)?;
+//! sponge.absorb(&[Fr::from(2u64)]);
+//! let output = sponge.squeeze();
+//! sponge.finish();
+//! # assert_eq!(output.len(), 1);
+//! # Ok::<(), safe_api::state::StateError>(())
+//! ```
+//!
+//! The state holds everything absorbed so far in compressed form and must be stored as
+//! confidentially as the inputs themselves. A [`SpongeState`] is erased when dropped, but
+//! its encodings are plain bytes and strings.
+//!
+//! # Encoding
+//! [`SpongeState::to_bytes`] writes version 2 of a stable binary encoding, with all integers
+//! big-endian:
+//!
+//! | Bytes         | Content                                                           |
+//! |---------------|-------------------------------------------------------------------|
+//! | 4             | `SAFE`                                                            |
+//! | 1             | version, 2                                                        |
+//! | 4             | width `w` of a field element in bytes                             |
+//! | 4             | number `n` of state elements                                      |
+//! | `n * w`       | the state elements, canonical                                     |
+//! | `w`           | the tag                                                           |
+//! | 4, 4, 4       | absorb position, squeeze position, operations done                |
+//! | 4             | number `m` of operations                                          |
+//! | `4 * m`       | the encoded words of the operations                               |
+//! | per operation | 0 if it has no label, or 1, the 4-byte length and the UTF-8 label |
+//! | 4             | length of the tag in bits                                         |
+//! | 4             | length `h` of the identifier of the tag hasher                    |
+//! | `h`           | the UTF-8 [`TagHasher::ID`] of the tag hasher                     |
+//!
+//! Version 1, without the last three fields, is still read as a tag of 128 bits derived with
+//! SHA-256.
+//!
+//! With the `serde` feature, [`SpongeState`] also implements `Serialize` and `Deserialize`, as
+//! a structure of the same fields with field elements and words in fixed-width hex.
+//!
+//! # Snapshots
+//! To continue a transcript on another machine, e.g. a coordinator handing it to worker
+//! provers, [`Sponge::export_state`] captures a [`StateSnapshot`]: the state together with
+//! what it is only meaningful for, the field (its modulus), the permutation (its
+//! [`ID`](crate::permutation::Permutation::ID)) and the rate and capacity of the permutation.
+//! Decoding refuses a snapshot of another field, and [`Sponge::import_state`] one of another
+//! permutation or split, which would otherwise resume with elements of another backend or
+//! the capacity read as rate (or the reverse) as long as the widths agree, then resumes it
+//! like [`Sponge::resume`].
+//!
+//! A snapshot is written in version 4 of the encoding, which describes itself before the
+//! fields of version 2:
+//!
+//! | Bytes    | Content                                                        |
+//! |----------|----------------------------------------------------------------|
+//! | 4        | `SAFE`                                                         |
+//! | 1        | version, 4                                                     |
+//! | 4        | length `k` of the modulus in bytes                             |
+//! | `k`      | the modulus of the field                                       |
+//! | 4        | length `l` of the permutation identifier                       |
+//! | `l`      | the UTF-8 identifier of the permutation                        |
+//! | 4, 4     | rate, capacity                                                 |
+//! | the rest | the fields of version 2 after the version byte                 |
+//!
+//! Snapshots of version 3 hold the fields of version 1 and are read like them; snapshots of
+//! version 2, without the modulus and the identifier, are no longer read. A snapshot travels
+//! as bytes, `0x` hex or unpadded base64url:
+//!
+//! ```
+//! # use ark_bn254::Fr;
+//! # use safe_api::domain::padded_domain_separator;
+//! # use safe_api::poseidon2::Poseidon2;
+//! # use safe_api::{IoPattern, Sponge};
+//! use safe_api::state::StateSnapshot;
+//!
+//! # let pattern = IoPattern::new().absorb(1).absorb(1).squeeze(1);
+//! # let domain_separator = padded_domain_separator(b"my-app/protocol");
+//! # let mut sponge = Sponge::<Poseidon2>::start(pattern, &domain_separator);
+//! # sponge.absorb(&[Fr::from(1u64)]);
+//! // On the coordinator.
+//! let message = sponge.export_state().to_base64();
+//!
+//! // On a worker.
+//! let snapshot = StateSnapshot::<Fr>::from_base64(&message)?;
+//! let mut sponge = Sponge::<Poseidon2>::import_state(snapshot, &domain_separator)?;
+//! sponge.absorb(&[Fr::from(2u64)]);
+//! # let _ = sponge.squeeze();
+//! # sponge.finish();
+//! # Ok::<(), safe_api::state::StateError>(())
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use ark_ff::{BigInteger, PrimeField};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::armor::{base64url_decode, base64url_encode};
+use crate::display::{format_bytes, parse_bytes};
+use crate::io_pattern::{IoPattern, Operation};
+#[cfg(doc)]
+use crate::sponge::Sponge;
+use crate::tag::{Sha256Hasher, TagHasher, TagLength, TagParameters};
+
+/// Magic bytes opening the binary encoding.
+const MAGIC: &[u8; 4] = b"SAFE";
+
+/// Version of the binary encoding written by [`SpongeState::to_bytes`].
+pub const STATE_VERSION: u8 = 2;
+
+/// Version of the binary encoding written by [`StateSnapshot::to_bytes`].
+pub const SNAPSHOT_VERSION: u8 = 4;
+
+/// The versions written before the tag parameters were recorded, read as a tag of 128 bits
+/// derived with SHA-256.
+const STATE_VERSION_1: u8 = 1;
+const SNAPSHOT_VERSION_3: u8 = 3;
+
+/// Error of [`SpongeState::from_bytes`], [`StateSnapshot::from_bytes`], [`Sponge::resume`] and
+/// [`Sponge::import_state`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateError {
+    /// The bytes are not an encoded state: wrong magic, truncated, trailing, or with an
+    /// element that is not canonical or a label that is not UTF-8.
+    Malformed,
+    /// The state was encoded with an unsupported version.
+    UnsupportedVersion(u8),
+    /// The state elements do not have the width of the field, or the state the width of the
+    /// permutation.
+    WrongWidth,
+    /// The field of a snapshot is not the field of the state elements.
+    WrongField,
+    /// The permutation of a snapshot is not the permutation of the sponge.
+    WrongPermutation,
+    /// The rate and capacity of a snapshot are not those of the permutation.
+    WrongSplit,
+    /// A position lies outside the rate or the IO pattern.
+    Inconsistent,
+    /// The tag is not the one of the IO pattern under the domain separator.
+    TagMismatch,
+    /// The tag was derived with a hasher that is not enabled.
+    UnknownTagHasher,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::Malformed => f.write_str("malformed sponge state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "unsupported sponge state version {}", version)
+            }
+            StateError::WrongWidth => f.write_str("sponge state of the wrong width"),
+            StateError::WrongField => f.write_str("sponge state of another field"),
+            StateError::WrongPermutation => {
+                f.write_str("sponge state of another permutation than the sponge")
+            }
+            StateError::WrongSplit => {
+                f.write_str("sponge state of another rate and capacity than the permutation")
+            }
+            StateError::Inconsistent => f.write_str("inconsistent sponge state"),
+            StateError::TagMismatch => {
+                f.write_str("sponge state tag does not match its IO pattern and domain separator")
+            }
+            StateError::UnknownTagHasher => {
+                f.write_str("sponge state tag derived with a hasher that is not enabled")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StateError {}
+
+/// The saved state of a sponge, see the [module documentation](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpongeState<F: Zeroize> {
+    pub(crate) state: Vec<F>,
+    pub(crate) tag: F,
+    pub(crate) absorb_pos: usize,
+    pub(crate) squeeze_pos: usize,
+    pub(crate) io_pattern: IoPattern,
+    pub(crate) io_count: usize,
+    pub(crate) tag_length: TagLength,
+    pub(crate) tag_hasher: String,
+}
+
+impl<F: Zeroize> Zeroize for SpongeState<F> {
+    fn zeroize(&mut self) {
+        self.state.iter_mut().for_each(Zeroize::zeroize);
+        self.absorb_pos = 0;
+        self.squeeze_pos = 0;
+        self.io_count = 0;
+    }
+}
+
+impl<F: Zeroize> Drop for SpongeState<F> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<F: Zeroize> ZeroizeOnDrop for SpongeState<F> {}
+
+impl<F: PrimeField> SpongeState<F> {
+    /// Returns the IO pattern of the saved sponge.
+    pub fn io_pattern(&self) -> &IoPattern {
+        &self.io_pattern
+    }
+
+    /// Returns the number of operations of the IO pattern already performed.
+    pub fn operations_done(&self) -> usize {
+        self.io_count
+    }
+
+    /// Returns the tag of the saved sponge.
+    pub fn tag(&self) -> F {
+        self.tag
+    }
+
+    /// Returns the length of the tag of the saved sponge.
+    pub fn tag_length(&self) -> TagLength {
+        self.tag_length
+    }
+
+    /// Returns the [`TagHasher::ID`] of the hash the tag of the saved sponge was derived with.
+    pub fn tag_hasher(&self) -> &str {
+        &self.tag_hasher
+    }
+
+    /// Returns the parameters the tag was derived with, failing if its hasher is not enabled.
+    pub(crate) fn tag_parameters(&self) -> Result<TagParameters, StateError> {
+        TagParameters::find(self.tag_length, &self.tag_hasher).ok_or(StateError::UnknownTagHasher)
+    }
+
+    /// Encodes the state in the binary encoding of the [module documentation](self).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(STATE_VERSION);
+        self.write_body(&mut bytes);
+        bytes
+    }
+
+    /// Writes the fields after the version byte.
+    fn write_body(&self, bytes: &mut Vec<u8>) {
+        put_u32(bytes, element_width::<F>());
+        put_u32(bytes, self.state.len());
+        for element in self.state.iter().chain([&self.tag]) {
+            bytes.extend_from_slice(&element.into_bigint().to_bytes_be());
+        }
+        put_u32(bytes, self.absorb_pos);
+        put_u32(bytes, self.squeeze_pos);
+        put_u32(bytes, self.io_count);
+        put_u32(bytes, self.io_pattern.len());
+        for word in self.io_pattern.encoded_words() {
+            bytes.extend_from_slice(&word.to_be_bytes());
+        }
+        for index in 0..self.io_pattern.len() {
+            match self.io_pattern.label(index) {
+                None => bytes.push(0),
+                Some(label) => {
+                    bytes.push(1);
+                    put_u32(bytes, label.len());
+                    bytes.extend_from_slice(label.as_bytes());
+                }
+            }
+        }
+        put_u32(bytes, self.tag_length.bits());
+        put_u32(bytes, self.tag_hasher.len());
+        bytes.extend_from_slice(self.tag_hasher.as_bytes());
+    }
+
+    /// Decodes a state written by [`SpongeState::to_bytes`]. Whether it is consistent is only
+    /// checked by [`Sponge::resume`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, StateError> {
+        let (mut reader, version) = Reader::open(bytes, &[STATE_VERSION, STATE_VERSION_1])?;
+        let state = Self::read_body(&mut reader, version == STATE_VERSION)?;
+        reader.close()?;
+        Ok(state)
+    }
+
+    /// Reads the fields after the version byte, with the tag parameters if `tagged`.
+    fn read_body(reader: &mut Reader<'_>, tagged: bool) -> Result<Self, StateError> {
+        if reader.u32()? != element_width::<F>() as u32 {
+            return Err(StateError::WrongWidth);
+        }
+        let length = reader.u32()? as usize;
+        let state = (0..length)
+            .map(|_| reader.element())
+            .collect::<Result<Vec<F>, _>>()?;
+        let tag = reader.element()?;
+        let absorb_pos = reader.u32()? as usize;
+        let squeeze_pos = reader.u32()? as usize;
+        let io_count = reader.u32()? as usize;
+
+        let operations = (0..reader.u32()?)
+            .map(|_| reader.u32().map(Operation::from_word))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut labels = Vec::with_capacity(operations.len());
+        for _ in &operations {
+            labels.push(match reader.take(1)?[0] {
+                0 => None,
+                1 => {
+                    let length = reader.u32()? as usize;
+                    let label = core::str::from_utf8(reader.take(length)?)
+                        .map_err(|_| StateError::Malformed)?;
+                    Some(String::from(label))
+                }
+                _ => return Err(StateError::Malformed),
+            });
+        }
+        let (tag_length, tag_hasher) = match tagged {
+            false => (TagLength::default(), String::from(Sha256Hasher::ID)),
+            true => {
+                let tag_length =
+                    TagLength::from_bits(reader.u32()? as usize).ok_or(StateError::Malformed)?;
+                let length = reader.u32()? as usize;
+                let tag_hasher = core::str::from_utf8(reader.take(length)?)
+                    .map_err(|_| StateError::Malformed)?;
+                (tag_length, String::from(tag_hasher))
+            }
+        };
+
+        Ok(SpongeState {
+            state,
+            tag,
+            absorb_pos,
+            squeeze_pos,
+            io_pattern: IoPattern::from_parts(operations, labels),
+            io_count,
+            tag_length,
+            tag_hasher,
+        })
+    }
+}
+
+/// A [`SpongeState`] with the identifier, rate and capacity of its permutation, to move a
+/// transcript to another machine, see the [module documentation](self#snapshots).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StateSnapshot<F: Zeroize> {
+    pub(crate) permutation: String,
+    pub(crate) rate: usize,
+    pub(crate) capacity: usize,
+    pub(crate) state: SpongeState<F>,
+}
+
+impl<F: PrimeField> StateSnapshot<F> {
+    /// Returns the identifier of the permutation of the exported sponge.
+    pub fn permutation(&self) -> &str {
+        &self.permutation
+    }
+
+    /// Returns the rate of the permutation of the exported sponge.
+    pub fn rate(&self) -> usize {
+        self.rate
+    }
+
+    /// Returns the capacity of the permutation of the exported sponge.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the state of the exported sponge.
+    pub fn state(&self) -> &SpongeState<F> {
+        &self.state
+    }
+
+    /// Encodes the snapshot in version 4 of the binary encoding.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(SNAPSHOT_VERSION);
+        let modulus = F::MODULUS.to_bytes_be();
+        put_u32(&mut bytes, modulus.len());
+        bytes.extend_from_slice(&modulus);
+        put_u32(&mut bytes, self.permutation.len());
+        bytes.extend_from_slice(self.permutation.as_bytes());
+        put_u32(&mut bytes, self.rate);
+        put_u32(&mut bytes, self.capacity);
+        self.state.write_body(&mut bytes);
+        bytes
+    }
+
+    /// Decodes a snapshot written by [`StateSnapshot::to_bytes`], failing if it is of another
+    /// field than `F`. Whether it fits the permutation and is consistent is only checked by
+    /// [`Sponge::import_state`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, StateError> {
+        let (mut reader, version) = Reader::open(bytes, &[SNAPSHOT_VERSION, SNAPSHOT_VERSION_3])?;
+        let length = reader.u32()? as usize;
+        if reader.take(length)? != F::MODULUS.to_bytes_be() {
+            return Err(StateError::WrongField);
+        }
+        let length = reader.u32()? as usize;
+        let permutation = core::str::from_utf8(reader.take(length)?)
+            .map_err(|_| StateError::Malformed)?
+            .into();
+        let rate = reader.u32()? as usize;
+        let capacity = reader.u32()? as usize;
+        let state = SpongeState::read_body(&mut reader, version == SNAPSHOT_VERSION)?;
+        reader.close()?;
+        Ok(StateSnapshot {
+            permutation,
+            rate,
+            capacity,
+            state,
+        })
+    }
+
+    /// Encodes the snapshot as `0x` hex of [`StateSnapshot::to_bytes`].
+    pub fn to_hex(&self) -> String {
+        format_bytes(&self.to_bytes())
+    }
+
+    /// Decodes a snapshot written by [`StateSnapshot::to_hex`].
+    pub fn from_hex(input: &str) -> Result<Self, StateError> {
+        Self::from_bytes(&parse_bytes(input).map_err(|_| StateError::Malformed)?)
+    }
+
+    /// Encodes the snapshot as unpadded base64url of [`StateSnapshot::to_bytes`].
+    pub fn to_base64(&self) -> String {
+        base64url_encode(&self.to_bytes())
+    }
+
+    /// Decodes a snapshot written by [`StateSnapshot::to_base64`].
+    pub fn from_base64(input: &str) -> Result<Self, StateError> {
+        Self::from_bytes(&base64url_decode(input).map_err(|_| StateError::Malformed)?)
+    }
+}
+
+/// Returns the number of bytes of an encoded element of `F`.
+fn element_width<F: PrimeField>() -> usize {
+    F::ZERO.into_bigint().to_bytes_be().len()
+}
+
+fn put_u32(bytes: &mut Vec<u8>, value: usize) {
+    bytes.extend_from_slice(&(value as u32).to_be_bytes());
+}
+
+/// Cursor over the bytes being decoded.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    /// Checks the magic bytes and that the version is one of `versions`, returning the cursor
+    /// after them and the version.
+    fn open(bytes: &'a [u8], versions: &[u8]) -> Result<(Self, u8), StateError> {
+        let mut reader = Reader(bytes);
+        if reader.take(4)? != MAGIC {
+            return Err(StateError::Malformed);
+        }
+        match reader.take(1)?[0] {
+            found if versions.contains(&found) => Ok((reader, found)),
+            found => Err(StateError::UnsupportedVersion(found)),
+        }
+    }
+
+    /// Checks that all the bytes were read.
+    fn close(self) -> Result<(), StateError> {
+        match self.0.is_empty() {
+            true => Ok(()),
+            false => Err(StateError::Malformed),
+        }
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], StateError> {
+        if self.0.len() < length {
+            return Err(StateError::Malformed);
+        }
+        let (taken, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, StateError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes(bytes.try_into().expect("4 bytes")))
+    }
+
+    fn element<F: PrimeField>(&mut self) -> Result<F, StateError> {
+        let bytes = self.take(element_width::<F>())?;
+        let element = F::from_be_bytes_mod_order(bytes);
+        if element.into_bigint().to_bytes_be() != bytes {
+            return Err(StateError::Malformed);
+        }
+        Ok(element)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serialization {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    use ark_ff::PrimeField;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::SpongeState;
+    use crate::display::{format_element, parse_element_hex};
+    use crate::io_pattern::{IoPattern, Operation};
+    use crate::tag::{Sha256Hasher, TagHasher, TagLength};
+
+    /// The serde form of a [`SpongeState`].
+    #[derive(Serialize, Deserialize)]
+    struct Repr {
+        version: u8,
+        state: Vec<String>,
+        tag: String,
+        absorb_pos: usize,
+        squeeze_pos: usize,
+        io_pattern: Vec<String>,
+        labels: Vec<Option<String>>,
+        io_count: usize,
+        /// Absent in version 1, whose tags are of 128 bits derived with SHA-256.
+        #[serde(default)]
+        tag_length: Option<usize>,
+        #[serde(default)]
+        tag_hasher: Option<String>,
+    }
+
+    impl<F: PrimeField> Serialize for SpongeState<F> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Repr {
+                version: super::STATE_VERSION,
+                state: self.state.iter().map(format_element).collect(),
+                tag: format_element(&self.tag),
+                absorb_pos: self.absorb_pos,
+                squeeze_pos: self.squeeze_pos,
+                io_pattern: self
+                    .io_pattern
+                    .encoded_words()
+                    .iter()
+                    .map(|word| alloc::format!("0x{:08x}", word))
+                    .collect(),
+                labels: (0..self.io_pattern.len())
+                    .map(|index| self.io_pattern.label(index).map(String::from))
+                    .collect(),
+                io_count: self.io_count,
+                tag_length: Some(self.tag_length.bits()),
+                tag_hasher: Some(self.tag_hasher.clone()),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, F: PrimeField> Deserialize<'de> for SpongeState<F> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let repr = Repr::deserialize(deserializer)?;
+            if ![super::STATE_VERSION, super::STATE_VERSION_1].contains(&repr.version) {
+                return Err(D::Error::custom(super::StateError::UnsupportedVersion(
+                    repr.version,
+                )));
+            }
+            let tag_length = match repr.tag_length {
+                None => TagLength::default(),
+                Some(bits) => TagLength::from_bits(bits)
+                    .ok_or_else(|| D::Error::custom("invalid tag length"))?,
+            };
+            let element = |input: &str| parse_element_hex::<F>(input).map_err(D::Error::custom);
+            let operations = repr
+                .io_pattern
+                .iter()
+                .map(|word| {
+                    word.strip_prefix("0x")
+                        .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                        .map(Operation::from_word)
+                        .ok_or_else(|| D::Error::custom("invalid IO pattern word"))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if repr.labels.len() != operations.len() {
+                return Err(D::Error::custom("one label per operation expected"));
+            }
+            Ok(SpongeState {
+                state: repr
+                    .state
+                    .iter()
+                    .map(|input| element(input))
+                    .collect::<Result<_, _>>()?,
+                tag: element(&repr.tag)?,
+                absorb_pos: repr.absorb_pos,
+                squeeze_pos: repr.squeeze_pos,
+                io_pattern: IoPattern::from_parts(operations, repr.labels),
+                io_count: repr.io_count,
+                tag_length,
+                tag_hasher: repr
+                    .tag_hasher
+                    .unwrap_or_else(|| String::from(Sha256Hasher::ID)),
+            })
+        }
+    }
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::poseidon2::Poseidon2;
+    use crate::sponge::Sponge;
+    use ark_bn254::Fr;
+
+    fn pattern() -> IoPattern {
+        IoPattern::new()
+            .absorb_labeled("first", 4)
+            .squeeze(2)
+            .absorb_labeled("second", 1)
+            .squeeze_labeled("out", 1)
+    }
+
+    fn elements(values: &[u64]) -> Vec<Fr> {
+        values.iter().map(|&value| Fr::from(value)).collect()
+    }
+
+    #[test]
+    fn test_resume_matches_uninterrupted() {
+        let mut sponge = Sponge::<Poseidon2>::start(pattern(), [1u8; 64]);
+        sponge.absorb(&elements(&[1, 2, 3, 4]));
+        let first = sponge.squeeze();
+        let saved = sponge.save();
+        sponge.absorb(&elements(&[5]));
+        let expected = sponge.squeeze();
+
+        let decoded = SpongeState::<Fr>::from_bytes(&saved.to_bytes()).unwrap();
+        assert_eq!(decoded, saved);
+        assert_eq!(decoded.io_pattern().label(3), Some("out"));
+        let mut resumed = Sponge::<Poseidon2>::resume(decoded, [1u8; 64]).unwrap();
+        resumed.absorb(&elements(&[5]));
+        assert_eq!(resumed.squeeze(), expected);
+        resumed.finish();
+        assert_eq!(first.len(), 2);
+    }
+
+    #[test]
+    fn test_rejects_invalid_states() {
+        let mut sponge = Sponge::<Poseidon2>::start(pattern(), [1u8; 64]);
+        sponge.absorb(&elements(&[1, 2, 3, 4]));
+        let saved = sponge.save();
+        assert_eq!(
+            Sponge::<Poseidon2>::resume(saved.clone(), [2u8; 64]).err(),
+            Some(StateError::TagMismatch)
+        );
+        let mut inconsistent = saved.clone();
+        inconsistent.io_count = 5;
+        assert_eq!(
+            Sponge::<Poseidon2>::resume(inconsistent, [1u8; 64]).err(),
+            Some(StateError::Inconsistent)
+        );
+
+        let bytes = saved.to_bytes();
+        assert_eq!(
+            SpongeState::<Fr>::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(StateError::Malformed)
+        );
+        let mut version = bytes.clone();
+        version[4] = 5;
+        assert_eq!(
+            SpongeState::<Fr>::from_bytes(&version),
+            Err(StateError::UnsupportedVersion(5))
+        );
+        let mut non_canonical = bytes;
+        non_canonical[13..45].fill(0xff);
+        assert_eq!(
+            SpongeState::<Fr>::from_bytes(&non_canonical),
+            Err(StateError::Malformed)
+        );
+    }
+
+    #[test]
+    fn test_tag_parameters() {
+        let mut sponge = Sponge::<Poseidon2>::builder()
+            .tag_length(TagLength::Bits64)
+            .start(pattern(), [1u8; 64]);
+        sponge.absorb(&elements(&[1, 2, 3, 4]));
+        let saved = sponge.save();
+        let expected = sponge.squeeze();
+        assert_eq!(saved.tag_length(), TagLength::Bits64);
+        assert_eq!(saved.tag_hasher(), "sha-256");
+
+        let decoded = SpongeState::<Fr>::from_bytes(&saved.to_bytes()).unwrap();
+        assert_eq!(decoded, saved);
+        let mut resumed = Sponge::<Poseidon2>::resume(decoded, [1u8; 64]).unwrap();
+        assert_eq!(resumed.squeeze(), expected);
+
+        // The tag is only checked under the recorded length and hash.
+        let mut other_length = saved.clone();
+        other_length.tag_length = TagLength::Bits128;
+        assert_eq!(
+            Sponge::<Poseidon2>::resume(other_length, [1u8; 64]).err(),
+            Some(StateError::TagMismatch)
+        );
+        let mut unknown = saved.clone();
+        unknown.tag_hasher = "md5".into();
+        assert_eq!(
+            Sponge::<Poseidon2>::resume(unknown, [1u8; 64]).err(),
+            Some(StateError::UnknownTagHasher)
+        );
+
+        // Version 1 holds no tag parameters, and is read as the default ones.
+        let mut sponge = Sponge::<Poseidon2>::start(pattern(), [1u8; 64]);
+        sponge.absorb(&elements(&[1, 2, 3, 4]));
+        let saved = sponge.save();
+        let mut bytes = saved.to_bytes();
+        bytes.truncate(bytes.len() - 4 - 4 - "sha-256".len());
+        bytes[4] = 1;
+        assert_eq!(SpongeState::<Fr>::from_bytes(&bytes).unwrap(), saved);
+    }
+
+    #[cfg(feature = "blake2s")]
+    #[test]
+    fn test_tag_hasher() {
+        use crate::tag::Blake2sHasher;
+
+        let mut sponge = Sponge::<Poseidon2>::builder()
+            .tag_hasher::<Blake2sHasher>()
+            .start(pattern(), [1u8; 64]);
+        sponge.absorb(&elements(&[1, 2, 3, 4]));
+        let saved = StateSnapshot::<Fr>::from_bytes(&sponge.export_state().to_bytes()).unwrap();
+        assert_eq!(saved.state().tag_hasher(), "blake2s-256");
+        let mut imported = Sponge::<Poseidon2>::import_state(saved.clone(), [1u8; 64]).unwrap();
+        assert_eq!(imported.squeeze(), sponge.squeeze());
+
+        let mut other_hasher = saved;
+        other_hasher.state.tag_hasher = "sha-256".into();
+        assert_eq!(
+            Sponge::<Poseidon2>::import_state(other_hasher, [1u8; 64]).err(),
+            Some(StateError::TagMismatch)
+        );
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mut sponge = Sponge::<Poseidon2>::start(pattern(), [1u8; 64]);
+        sponge.absorb(&elements(&[1, 2, 3, 4]));
+        let snapshot = sponge.export_state();
+        assert_eq!(snapshot.permutation(), "poseidon2-bn254");
+        assert_eq!((snapshot.rate(), snapshot.capacity()), (3, 1));
+        let expected = sponge.squeeze();
+
+        for decoded in [
+            StateSnapshot::<Fr>::from_bytes(&snapshot.to_bytes()),
+            StateSnapshot::<Fr>::from_hex(&snapshot.to_hex()),
+            StateSnapshot::<Fr>::from_base64(&snapshot.to_base64()),
+        ] {
+            let decoded = decoded.unwrap();
+            assert_eq!(decoded, snapshot);
+            let mut imported = Sponge::<Poseidon2>::import_state(decoded, [1u8; 64]).unwrap();
+            assert_eq!(imported.squeeze(), expected);
+        }
+
+        // The body of a snapshot is the one of the saved state, after the modulus, the
+        // identifier, the rate and the capacity.
+        let bytes = snapshot.to_bytes();
+        assert_eq!(bytes[9..41], Fr::MODULUS.to_bytes_be());
+        assert_eq!(bytes[45..60], *b"poseidon2-bn254");
+        assert_eq!(bytes[68..], snapshot.state().to_bytes()[5..]);
+        assert_eq!(
+            SpongeState::<Fr>::from_bytes(&bytes),
+            Err(StateError::UnsupportedVersion(4))
+        );
+        let mut version = bytes;
+        version[4] = 2;
+        assert_eq!(
+            StateSnapshot::<Fr>::from_bytes(&version),
+            Err(StateError::UnsupportedVersion(2))
+        );
+    }
+
+    #[test]
+    fn test_rejects_other_split() {
+        let sponge = Sponge::<Poseidon2>::start(pattern(), [1u8; 64]);
+        let mut snapshot = sponge.export_state();
+        (snapshot.rate, snapshot.capacity) = (2, 2);
+        let decoded = StateSnapshot::<Fr>::from_bytes(&snapshot.to_bytes()).unwrap();
+        assert_eq!(
+            Sponge::<Poseidon2>::import_state(decoded, [1u8; 64]).err(),
+            Some(StateError::WrongSplit)
+        );
+
+        let mut snapshot = sponge.export_state();
+        snapshot.permutation = "rescue-prime-bn254".into();
+        let decoded = StateSnapshot::<Fr>::from_bytes(&snapshot.to_bytes()).unwrap();
+        assert_eq!(
+            Sponge::<Poseidon2>::import_state(decoded, [1u8; 64]).err(),
+            Some(StateError::WrongPermutation)
+        );
+        // A field of another modulus of the same width.
+        let mut bytes = sponge.export_state().to_bytes();
+        bytes[40] ^= 1;
+        assert_eq!(
+            StateSnapshot::<Fr>::from_bytes(&bytes),
+            Err(StateError::WrongField)
+        );
+        assert_eq!(
+            StateSnapshot::<Fr>::from_hex("0x5341464"),
+            Err(StateError::Malformed)
+        );
+        assert_eq!(
+            StateSnapshot::<Fr>::from_base64("U0FGRQI*"),
+            Err(StateError::Malformed)
+        );
+    }
+
+    #[cfg(feature = "vectors")]
+    #[test]
+    fn test_serde_round_trip() {
+        let mut sponge = Sponge::<Poseidon2>::start(pattern(), [1u8; 64]);
+        sponge.absorb(&elements(&[1, 2, 3, 4]));
+        let saved = sponge.save();
+        let json = serde_json::to_string(&saved).unwrap();
+        assert!(json.contains("\"labels\":[\"first\",null,\"second\",\"out\"]"));
+        assert!(json.contains("\"tag_length\":128,\"tag_hasher\":\"sha-256\""));
+        assert_eq!(
+            serde_json::from_str::<SpongeState<Fr>>(&json).unwrap(),
+            saved
+        );
+        // Version 1 holds no tag parameters.
+        let json = json
+            .replace("\"version\":2", "\"version\":1")
+            .replace(",\"tag_length\":128,\"tag_hasher\":\"sha-256\"", "");
+        assert_eq!(
+            serde_json::from_str::<SpongeState<Fr>>(&json).unwrap(),
+            saved
+        );
+    }
+}
@@ -0,0 +1,209 @@
+//! Sub-protocols: child sponges spawned by a parent sponge, whose output the parent absorbs.
+//!
+//! A protocol composed of gadgets, e.g. a Merkle proof, a commitment and a nullifier, runs each
+//! gadget on its own sponge with its own IO pattern. [`Sponge::spawn`] starts it under a domain
+//! separator derived from the parent's tag and a label, so that the same gadget gets a distinct
+//! tag in every protocol and at every place it is used, and [`SubProtocol::join`] absorbs its
+//! last squeeze back into the parent, at the ABSORB declared with [`IoPattern::sub_protocol`]:
+//!
+//! ```
+//! use ark_bn254::Fr;
+//! use safe_api::domain::padded_domain_separator;
+//! use safe_api::poseidon2::Poseidon2;
+//! use safe_api::{IoPattern, Sponge};
+//!
+//! let commitment = IoPattern::new().absorb(2).squeeze(1);
+//! let parent_pattern = IoPattern::new()
+//!     .absorb(1)
+//!     .sub_protocol(&commitment)
+//!     .squeeze(1);
+//! let domain_separator = padded_domain_separator(b"my-app/spend");
+//! let mut parent = Sponge::<Poseidon2>::start(parent_pattern, &domain_separator);
+//! parent.absorb(&[Fr::from(7u64)]);
+//!
+//! let mut child = parent.spawn("commitment", commitment);
+//! child.absorb(&[Fr::from(1u64), Fr::from(2u64)]);
+//! let [digest] = child.squeeze()[..] else { unreachable!() };
+//! assert_eq!(child.join(&mut parent), [digest]);
+//!
+//! let challenge = parent.squeeze();
+//! parent.finish();
+//! # assert_eq!(challenge.len(), 1);
+//! ```
+//!
+//! The domain separator of a child is the SHA-256 of `SAFE-SUB-V1`, the parent's tag as 32
+//! big-endian bytes and the label, zero padded to 64 bytes (see [`sub_domain_separator`]), so
+//! a circuit derives it from constants. Children start with the default options of
+//! [`SpongeBuilder`](crate::sponge::SpongeBuilder), whatever those of their parent.
+
+use alloc::vec::Vec;
+
+use ark_ff::{BigInteger, PrimeField};
+use sha2::{Digest, Sha256};
+
+use crate::io_pattern::IoPattern;
+use crate::permutation::Permutation;
+use crate::sponge::Sponge;
+
+/// Prefix of the hash deriving the domain separator of a child, versioning its layout.
+const SUB_PROTOCOL_PREFIX: &[u8] = b"SAFE-SUB-V1";
+
+/// Returns the domain separator of the child `label` of a parent of tag `parent_tag`.
+pub fn sub_domain_separator<F: PrimeField>(parent_tag: &F, label: &str) -> [u8; 64] {
+    let mut tag = parent_tag.into_bigint().to_bytes_be();
+    if tag.len() < 32 {
+        tag.splice(0..0, core::iter::repeat_n(0, 32 - tag.len()));
+    }
+    let digest = Sha256::new()
+        .chain_update(SUB_PROTOCOL_PREFIX)
+        .chain_update(&tag)
+        .chain_update(label.as_bytes())
+        .finalize();
+    let mut domain_separator = [0u8; 64];
+    domain_separator[..32].copy_from_slice(&digest);
+    domain_separator
+}
+
+/// A child sponge spawned by [`Sponge::spawn`], see the [module documentation](self).
+pub struct SubProtocol<P: Permutation> {
+    sponge: Sponge<P>,
+    /// The output of the last squeeze, absorbed by the parent on [`SubProtocol::join`].
+    output: Vec<P::Field>,
+}
+
+impl<P: Permutation + Clone> Sponge<P> {
+    /// Starts the child `label` of this sponge on `io_pattern`, under the domain separator
+    /// [`sub_domain_separator`] of the tag of this sponge.
+    ///
+    /// # Panics
+    /// If `io_pattern` does not end with a SQUEEZE, whose output [`SubProtocol::join`] absorbs.
+    pub fn spawn(&self, label: &str, io_pattern: IoPattern) -> SubProtocol<P> {
+        assert!(
+            io_pattern
+                .operations()
+                .last()
+                .is_some_and(|last| !last.is_absorb()),
+            "Sub-protocol must end with a SQUEEZE"
+        );
+        let domain_separator = sub_domain_separator(&self.tag(), label);
+        SubProtocol {
+            sponge: Sponge::with_permutation(
+                self.permutation().clone(),
+                io_pattern,
+                domain_separator,
+            ),
+            output: Vec::new(),
+        }
+    }
+}
+
+impl<P: Permutation> SubProtocol<P> {
+    /// Absorbs `input`, as [`Sponge::absorb`].
+    pub fn absorb(&mut self, input: &[P::Field]) {
+        self.sponge.absorb(input);
+    }
+
+    /// Squeezes the next operation, as [`Sponge::squeeze`].
+    pub fn squeeze(&mut self) -> Vec<P::Field> {
+        self.output = self.sponge.squeeze();
+        self.output.clone()
+    }
+
+    /// Returns the child sponge, e.g. for its tag.
+    pub fn sponge(&self) -> &Sponge<P> {
+        &self.sponge
+    }
+
+    /// Finishes the child, as [`Sponge::finish`], and absorbs the output of its last squeeze
+    /// into `parent`, returning it.
+    ///
+    /// # Panics
+    /// If operations of the child's IO pattern remain, or if the next operation of `parent`
+    /// is not an ABSORB of the length of the output.
+    pub fn join(self, parent: &mut Sponge<P>) -> Vec<P::Field> {
+        let SubProtocol { mut sponge, output } = self;
+        sponge.finish();
+        parent.absorb(&output);
+        output
+    }
+}
+
+impl IoPattern {
+    /// Appends the ABSORB of the output of the sub-protocol `child`, its last SQUEEZE, at
+    /// which [`SubProtocol::join`] absorbs it.
+    ///
+    /// # Panics
+    /// If `child` does not end with a SQUEEZE.
+    pub fn sub_protocol(self, child: &IoPattern) -> Self {
+        match child.operations().last() {
+            Some(last) if !last.is_absorb() => self.absorb(last.len()),
+            _ => panic!("Sub-protocol must end with a SQUEEZE"),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::domain::padded_domain_separator;
+    use crate::poseidon2::Poseidon2;
+    use ark_bn254::Fr;
+
+    fn parent(child: &IoPattern) -> Sponge<Poseidon2> {
+        let pattern = IoPattern::new().absorb(1).sub_protocol(child).squeeze(1);
+        let mut parent = Sponge::start(pattern, padded_domain_separator(b"spend"));
+        parent.absorb(&[Fr::from(7u64)]);
+        parent
+    }
+
+    #[test]
+    fn test_spawn_and_join() {
+        let child_pattern = IoPattern::new().absorb(2).squeeze(2);
+        let mut parent = parent(&child_pattern);
+        let mut child = parent.spawn("nullifier", child_pattern.clone());
+        let domain_separator = sub_domain_separator(&parent.tag(), "nullifier");
+        assert_eq!(
+            child.sponge().tag(),
+            Sponge::<Poseidon2>::start(child_pattern.clone(), domain_separator).tag()
+        );
+        // Labels and parents give distinct domain separators.
+        assert_ne!(
+            domain_separator,
+            sub_domain_separator(&parent.tag(), "commitment")
+        );
+        assert_ne!(
+            domain_separator,
+            sub_domain_separator(&(parent.tag() + Fr::from(1u64)), "nullifier")
+        );
+
+        child.absorb(&[Fr::from(1u64), Fr::from(2u64)]);
+        let output = child.squeeze();
+        assert_eq!(child.join(&mut parent), output);
+
+        // The same as absorbing the output by hand.
+        let mut expected = self::parent(&child_pattern);
+        let mut manual = Sponge::<Poseidon2>::start(child_pattern, domain_separator);
+        manual.absorb(&[Fr::from(1u64), Fr::from(2u64)]);
+        expected.absorb(&manual.squeeze());
+        manual.finish();
+        assert_eq!(parent.squeeze(), expected.squeeze());
+    }
+
+    #[test]
+    #[should_panic(expected = "Sub-protocol must end with a SQUEEZE")]
+    fn test_child_ends_with_squeeze() {
+        let parent = parent(&IoPattern::new().absorb(1).squeeze(1));
+        parent.spawn("commitment", IoPattern::new().squeeze(1).absorb(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "IO pattern not completed")]
+    fn test_join_incomplete_child() {
+        let child_pattern = IoPattern::new().absorb(1).squeeze(1).squeeze(1);
+        let mut parent = parent(&child_pattern);
+        let mut child = parent.spawn("commitment", child_pattern);
+        child.absorb(&[Fr::from(1u64)]);
+        child.squeeze();
+        child.join(&mut parent);
+    }
+}
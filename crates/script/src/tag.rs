@@ -0,0 +1,1069 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::str::FromStr;
+
+use ark_ff::PrimeField;
+use sha2::{Digest, Sha256};
+use subtle::{Choice, ConstantTimeEq};
+
+use crate::domain::DomainSeparator;
+use crate::hex::{self, HexError};
+use crate::io_pattern::{IoPattern, Operation};
+use crate::secret::ct_eq;
+
+/// Computes a unique tag for a sponge instance based on its IO pattern and domain separator.
+/// This matches the Noir implementation exactly.
+///
+/// # Arguments
+/// - `io_pattern`: Vector of 32-bit encoded operations defining the sponge's usage pattern.
+///   Each word has MSB=1 for ABSORB operations, MSB=0 for SQUEEZE operations.
+/// - `domain_separator`: 64-byte domain separator for cross-protocol security.
+///
+/// # Returns
+/// The 128-bit [`Tag`]. Use [`compute_tag_field`] for the field element the sponge works
+/// with.
+pub fn compute_tag(io_pattern: &[u32], domain_separator: impl Into<DomainSeparator>) -> Tag {
+    IoPattern::from_words(io_pattern).tag(domain_separator)
+}
+
+/// Computes the tag of a sponge instance as an element of the field `F` (following SAFE spec 2.3).
+///
+/// This is the value the Noir `compute_tag` returns as a `Field` over BN254. For fields smaller
+/// than 128 bits the truncated digest is reduced modulo the field characteristic.
+///
+/// # Arguments
+/// - `io_pattern`: Vector of 32-bit encoded operations defining the sponge's usage pattern.
+/// - `domain_separator`: 64-byte domain separator for cross-protocol security.
+pub fn compute_tag_field<F: PrimeField>(
+    io_pattern: &[u32],
+    domain_separator: impl Into<DomainSeparator>,
+) -> F {
+    IoPattern::from_words(io_pattern).tag_field(domain_separator)
+}
+
+/// Computes the tag of a sponge instance as an element of the field `F`, from the leading
+/// `length` bits of the digest instead of 128.
+///
+/// The bytes are folded in `F` like in [`compute_tag_field`], so they are reduced modulo the
+/// field characteristic if the field is smaller: [`TagLength::Full`] reduces the whole
+/// digest, for proof systems that want the tag to occupy a full field element. The Noir
+/// `compute_tag_with_length` and `SafeSponge::start_with_tag_length` take
+/// [`TagLength::bytes`].
+///
+/// # Arguments
+/// - `io_pattern`: Vector of 32-bit encoded operations defining the sponge's usage pattern.
+/// - `domain_separator`: 64-byte domain separator for cross-protocol security.
+/// - `length`: Number of leading bits of the digest in the tag.
+pub fn compute_tag_field_with_length<F: PrimeField>(
+    io_pattern: &[u32],
+    domain_separator: impl Into<DomainSeparator>,
+    length: TagLength,
+) -> F {
+    IoPattern::from_words(io_pattern).tag_field_with_length(domain_separator, length)
+}
+
+/// Computes the tag of a sponge instance as the leading elements of the capacity, for fields
+/// smaller than the tag such as [`fields`](crate::fields) Goldilocks and BabyBear.
+///
+/// The `length` leading bits of the digest are split into limbs of `F::MODULUS_BIT_SIZE - 1`
+/// bits, which any element of `F` holds, most significant first; the last of at most
+/// `capacity` limbs takes all the remaining bits, reduced like in
+/// [`compute_tag_field_with_length`]. The 128-bit tag thus takes 3 Goldilocks elements and 5
+/// BabyBear elements without reduction, while over BN254 tags of up to 248 bits, and any tag with
+/// a capacity of 1, are a single limb equal to [`compute_tag_field_with_length`].
+/// [`Sponge`](crate::Sponge) places the limbs at the start of its capacity.
+///
+/// # Arguments
+/// - `io_pattern`: Vector of 32-bit encoded operations defining the sponge's usage pattern.
+/// - `domain_separator`: 64-byte domain separator for cross-protocol security.
+/// - `length`: Number of leading bits of the digest in the tag.
+/// - `capacity`: Maximum number of limbs, at least 1.
+///
+/// # Panics
+/// If `capacity` is 0.
+pub fn compute_tag_limbs<F: PrimeField>(
+    io_pattern: &[u32],
+    domain_separator: impl Into<DomainSeparator>,
+    length: TagLength,
+    capacity: usize,
+) -> Vec<F> {
+    IoPattern::from_words(io_pattern).tag_limbs(domain_separator, length, capacity)
+}
+
+/// Computes the tag of a sponge instance with the hash `H` instead of SHA-256.
+///
+/// # Arguments
+/// - `io_pattern`: Vector of 32-bit encoded operations defining the sponge's usage pattern.
+/// - `domain_separator`: 64-byte domain separator for cross-protocol security.
+pub fn compute_tag_with_hasher<H: TagHasher>(
+    io_pattern: &[u32],
+    domain_separator: impl Into<DomainSeparator>,
+) -> Tag {
+    IoPattern::from_words(io_pattern).tag_with_hasher::<H>(domain_separator)
+}
+
+/// The hash of the tag derivation: the tag is made of the leading bytes of the digest of the
+/// [`tag_preimage`].
+///
+/// SAFE spec 2.3 and the Noir circuits use [`Sha256Hasher`]. The others, behind features,
+/// suit verifiers with other primitives at hand: `Keccak256Hasher` (`keccak`) for EVM
+/// contracts with the Keccak precompile, `Blake2sHasher` (`blake2s`) for circuits where
+/// Blake2s is cheaper. Sponges use another hash with
+/// [`SpongeBuilder::tag_hasher`](crate::sponge::SpongeBuilder::tag_hasher).
+///
+/// A saved sponge records the [`ID`](TagHasher::ID) of its hash, and only resumes where a
+/// hasher of this crate has that identifier.
+pub trait TagHasher {
+    /// Identifier of the hash, recorded in saved sponge states, e.g. `sha-256`.
+    const ID: &'static str;
+
+    /// Returns the 32-byte digest of `preimage`.
+    fn digest(preimage: &[u8]) -> [u8; 32];
+}
+
+/// SHA-256, the hash of SAFE spec 2.3.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Sha256Hasher;
+
+impl TagHasher for Sha256Hasher {
+    const ID: &'static str = "sha-256";
+
+    fn digest(preimage: &[u8]) -> [u8; 32] {
+        Sha256::digest(preimage).into()
+    }
+}
+
+/// Keccak-256, as the EVM `keccak256` (the original Keccak padding, not SHA3-256).
+#[cfg(feature = "keccak")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Keccak256Hasher;
+
+#[cfg(feature = "keccak")]
+impl TagHasher for Keccak256Hasher {
+    const ID: &'static str = "keccak-256";
+
+    fn digest(preimage: &[u8]) -> [u8; 32] {
+        sha3::Keccak256::digest(preimage).into()
+    }
+}
+
+/// Blake2s with a 256-bit digest and no key.
+#[cfg(feature = "blake2s")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Blake2sHasher;
+
+#[cfg(feature = "blake2s")]
+impl TagHasher for Blake2sHasher {
+    const ID: &'static str = "blake2s-256";
+
+    fn digest(preimage: &[u8]) -> [u8; 32] {
+        blake2::Blake2s256::digest(preimage).into()
+    }
+}
+
+/// The [`TagHasher::digest`] of a hash.
+type DigestFn = fn(&[u8]) -> [u8; 32];
+
+/// The identifiers and digests of every enabled [`TagHasher`], SHA-256 first.
+fn tag_hashers() -> Vec<(&'static str, DigestFn)> {
+    #[allow(unused_mut)]
+    let mut hashers: Vec<(&'static str, DigestFn)> = vec![(Sha256Hasher::ID, Sha256Hasher::digest)];
+    #[cfg(feature = "keccak")]
+    hashers.push((Keccak256Hasher::ID, Keccak256Hasher::digest));
+    #[cfg(feature = "blake2s")]
+    hashers.push((Blake2sHasher::ID, Blake2sHasher::digest));
+    hashers
+}
+
+/// How a sponge derives its tag: the hash and the length.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct TagParameters {
+    pub(crate) length: TagLength,
+    /// The [`TagHasher::ID`] of the hash.
+    pub(crate) hasher: &'static str,
+    digest: DigestFn,
+}
+
+impl Default for TagParameters {
+    fn default() -> Self {
+        TagParameters {
+            length: TagLength::default(),
+            hasher: Sha256Hasher::ID,
+            digest: Sha256Hasher::digest,
+        }
+    }
+}
+
+impl TagParameters {
+    /// Returns the parameters of `length` bits of the enabled hasher `hasher`, if any.
+    pub(crate) fn find(length: TagLength, hasher: &str) -> Option<Self> {
+        tag_hashers()
+            .into_iter()
+            .find(|(id, _)| *id == hasher)
+            .map(|(hasher, digest)| TagParameters {
+                length,
+                hasher,
+                digest,
+            })
+    }
+
+    /// Derives the tag with the hash `H`.
+    pub(crate) fn set_hasher<H: TagHasher>(&mut self) {
+        self.hasher = H::ID;
+        self.digest = H::digest;
+    }
+
+    /// Returns whether `tag` is the first limb of the tag of `io_pattern` under
+    /// `domain_separator` in a sponge of capacity `capacity`, compared in constant time.
+    pub(crate) fn has_tag<F: PrimeField>(
+        &self,
+        io_pattern: &IoPattern,
+        domain_separator: &[u8; 64],
+        tag: &F,
+        capacity: usize,
+    ) -> bool {
+        ct_eq(
+            &self.tag_limbs::<F>(io_pattern, domain_separator, capacity)[0],
+            tag,
+        )
+    }
+
+    /// Returns the tag of `io_pattern` under `domain_separator` in at most `capacity` limbs,
+    /// see [`compute_tag_limbs`].
+    pub(crate) fn tag_limbs<F: PrimeField>(
+        &self,
+        io_pattern: &IoPattern,
+        domain_separator: &[u8; 64],
+        capacity: usize,
+    ) -> Vec<F> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("safe.tag", operations = io_pattern.len()).entered();
+        split_digest(
+            &(self.digest)(&io_pattern.tag_preimage(domain_separator)),
+            self.length,
+            capacity,
+        )
+    }
+}
+
+/// Number of leading bits of the digest that make up a tag.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TagLength {
+    /// 64 bits.
+    Bits64,
+    /// 128 bits, as in SAFE spec 2.3 and [`compute_tag`].
+    #[default]
+    Bits128,
+    /// 248 bits, the most that fits in a BN254 element without reduction.
+    Bits248,
+    /// The whole 256-bit digest, reduced modulo the field characteristic.
+    Full,
+}
+
+impl TagLength {
+    /// Every tag length.
+    pub const ALL: [TagLength; 4] = [
+        TagLength::Bits64,
+        TagLength::Bits128,
+        TagLength::Bits248,
+        TagLength::Full,
+    ];
+
+    /// Returns the number of leading bytes of the digest in the tag.
+    pub fn bytes(self) -> usize {
+        match self {
+            TagLength::Bits64 => 8,
+            TagLength::Bits128 => 16,
+            TagLength::Bits248 => 31,
+            TagLength::Full => 32,
+        }
+    }
+
+    /// Returns the number of leading bits of the digest in the tag.
+    pub fn bits(self) -> usize {
+        8 * self.bytes()
+    }
+
+    /// Returns the tag length of `bits` bits, if any.
+    pub fn from_bits(bits: usize) -> Option<Self> {
+        TagLength::ALL
+            .into_iter()
+            .find(|length| length.bits() == bits)
+    }
+}
+
+/// Returns the exact SHA-256 preimage hashed by [`compute_tag`] (following SAFE spec 2.3).
+///
+/// The preimage is the aggregated IO pattern serialized as big-endian 32-bit words, followed by
+/// the 64-byte domain separator. It is exposed so that other implementations can check the
+/// aggregation and serialization steps independently of the hash.
+///
+/// # Arguments
+/// - `io_pattern`: Vector of 32-bit encoded operations defining the sponge's usage pattern.
+/// - `domain_separator`: 64-byte domain separator for cross-protocol security.
+pub fn tag_preimage(io_pattern: &[u32], domain_separator: impl Into<DomainSeparator>) -> Vec<u8> {
+    IoPattern::from_words(io_pattern).tag_preimage(domain_separator)
+}
+
+/// Computes the full 256-bit tag of a sponge instance: the SHA-256 digest without truncation.
+///
+/// Intended for byte-oriented sponges, whose state is not limited by the field size that
+/// motivates the 128-bit truncation of [`compute_tag`]. The first 16 bytes of the digest are
+/// the regular 128-bit tag.
+///
+/// # Arguments
+/// - `io_pattern`: Vector of 32-bit encoded operations defining the sponge's usage pattern.
+/// - `domain_separator`: 64-byte domain separator for cross-protocol security.
+pub fn compute_tag_256(
+    io_pattern: &[u32],
+    domain_separator: impl Into<DomainSeparator>,
+) -> [u8; 32] {
+    IoPattern::from_words(io_pattern).tag_256(domain_separator)
+}
+
+/// Every intermediate value of the tag computation (following SAFE spec 2.3), for tools that
+/// show how a tag comes about.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TagBreakdown {
+    /// The encoded words of the IO pattern, one per operation.
+    pub words: Vec<u32>,
+    /// Step 1: the words after aggregation of consecutive operations.
+    pub aggregated_words: Vec<u32>,
+    /// Step 2: the serialized aggregated words followed by the domain separator.
+    pub preimage: Vec<u8>,
+    /// Step 3: the SHA-256 digest of the preimage.
+    pub digest: [u8; 32],
+    /// Step 4: the first 128 bits of the digest, i.e. [`compute_tag`].
+    pub tag: Tag,
+}
+
+/// Computes the tag of a sponge instance with all its intermediate values.
+///
+/// # Arguments
+/// - `io_pattern`: Vector of 32-bit encoded operations defining the sponge's usage pattern.
+/// - `domain_separator`: 64-byte domain separator for cross-protocol security.
+pub fn compute_tag_verbose(
+    io_pattern: &[u32],
+    domain_separator: impl Into<DomainSeparator>,
+) -> TagBreakdown {
+    IoPattern::from_words(io_pattern).tag_breakdown(domain_separator)
+}
+
+/// Computes the tags of many IO patterns, each under its own domain separator, in order.
+///
+/// With the `rayon` feature the pairs are spread over the rayon thread pool. Either way the
+/// aggregation buffer and the hasher are reused from one pair to the next instead of being
+/// allocated for each tag, which makes this faster than calling [`IoPattern::tag`] in a loop.
+pub fn compute_tags_batch(items: &[(IoPattern, DomainSeparator)]) -> Vec<Tag> {
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        items
+            .par_iter()
+            .map_init(
+                BatchHasher::default,
+                |hasher, (io_pattern, domain_separator)| hasher.tag(io_pattern, domain_separator),
+            )
+            .collect()
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        let mut hasher = BatchHasher::default();
+        items
+            .iter()
+            .map(|(io_pattern, domain_separator)| hasher.tag(io_pattern, domain_separator))
+            .collect()
+    }
+}
+
+/// The state [`compute_tags_batch`] reuses across tags.
+#[derive(Default)]
+struct BatchHasher {
+    operations: Vec<Operation>,
+    hasher: Sha256,
+}
+
+impl BatchHasher {
+    /// Computes the tag of `io_pattern`, feeding the preimage of [`IoPattern::tag_preimage`] to
+    /// the hasher without materializing it.
+    fn tag(&mut self, io_pattern: &IoPattern, domain_separator: &DomainSeparator) -> Tag {
+        io_pattern.normalize_into(&mut self.operations);
+        for operation in &self.operations {
+            self.hasher.update(operation.encoded_word().to_be_bytes());
+        }
+        self.hasher.update(domain_separator.as_bytes());
+        truncate_digest(&self.hasher.finalize_reset().into())
+    }
+}
+
+/// Hashes a tag preimage with SHA256 and truncates the digest to 128 bits (following SAFE spec 2.3).
+///
+/// # Returns
+/// The 128-bit [`Tag`].
+pub fn tag_from_preimage(preimage: &[u8]) -> Tag {
+    truncate_digest(&Sha256::digest(preimage).into())
+}
+
+/// Returns the first 128 bits of a digest.
+fn truncate_digest(hash_bytes: &[u8; 32]) -> Tag {
+    Tag::from_digest(hash_bytes, TagLength::Bits128)
+}
+
+/// Hashes a tag preimage with SHA256 and folds the first 128 bits of the digest into a field element.
+///
+/// The result equals the fold of the Noir implementation (`tag * 256 + byte` in the field).
+pub fn tag_field_from_preimage<F: PrimeField>(preimage: &[u8]) -> F {
+    fold_digest(&Sha256::digest(preimage).into(), TagLength::Bits128)
+}
+
+/// Folds the leading bytes of a digest into a field element, see [`Tag::to_field`].
+fn fold_digest<F: PrimeField>(hash_bytes: &[u8; 32], length: TagLength) -> F {
+    Tag::from_digest(hash_bytes, length).to_field()
+}
+
+/// The leading bytes of a tag digest, truncated to a [`TagLength`].
+///
+/// Tags are big-endian: the first byte of the digest is the most significant byte of the tag.
+/// The conversions read the bytes as one integer, with [`u128::from_be_bytes`] or
+/// [`PrimeField::from_be_bytes_mod_order`], rather than folding them as `tag * 256 + byte` like
+/// the Noir circuit, which equals them but is easy to get wrong when the length changes.
+///
+/// The text form is `0x` followed by two lowercase hex digits per byte, 32 digits for the
+/// 128-bit tag of the spec; [`Tag::from_hex`] and [`FromStr`] read it back, and serde (with
+/// the `serde` feature) uses it:
+///
+/// ```
+/// use safe_api::tag::{Tag, TagLength};
+/// use safe_api::IoPattern;
+///
+/// let tag = IoPattern::new().absorb(3).squeeze(1).tag(&[0x41; 64]);
+/// assert_eq!(tag.length(), TagLength::Bits128);
+/// assert_eq!(tag.to_hex().len(), 2 + 32);
+/// assert_eq!(tag.to_string().parse::<Tag>(), Ok(tag));
+/// assert_eq!(Tag::from(tag.to_u128().unwrap()), tag);
+/// ```
+///
+/// Tags compare in time independent of their bytes, with `==` as with [`ConstantTimeEq`].
+#[derive(Clone, Copy, Debug)]
+pub struct Tag {
+    /// The digest, zeroed past the length.
+    bytes: [u8; 32],
+    length: TagLength,
+}
+
+impl Tag {
+    /// Truncates `digest` to its `length` leading bytes.
+    pub fn from_digest(digest: &[u8; 32], length: TagLength) -> Self {
+        let mut bytes = [0u8; 32];
+        bytes[..length.bytes()].copy_from_slice(&digest[..length.bytes()]);
+        Tag { bytes, length }
+    }
+
+    /// Returns the length of the tag.
+    pub fn length(&self) -> TagLength {
+        self.length
+    }
+
+    /// Returns the bytes of the tag, most significant first.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.length.bytes()]
+    }
+
+    /// Returns the tag as an integer, `None` if it is longer than 128 bits.
+    pub fn to_u128(&self) -> Option<u128> {
+        let bytes = self.as_bytes();
+        let mut padded = [0u8; 16];
+        padded
+            .get_mut(16usize.checked_sub(bytes.len())?..)?
+            .copy_from_slice(bytes);
+        Some(u128::from_be_bytes(padded))
+    }
+
+    /// Returns the tag as an element of `F`, reduced modulo the field characteristic if the
+    /// field is smaller.
+    pub fn to_field<F: PrimeField>(&self) -> F {
+        F::from_be_bytes_mod_order(self.as_bytes())
+    }
+
+    /// Returns the text form of the tag, see the [type documentation](Tag).
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.as_bytes())
+    }
+
+    /// Parses the text form of a tag, whose number of digits gives its [`TagLength`].
+    ///
+    /// # Errors
+    /// If `input` is not strict hex (see [`hex::Mode::Strict`]), or not the length of a tag,
+    /// reported as the length of a 128-bit tag.
+    pub fn from_hex(input: &str) -> Result<Self, HexError> {
+        let bytes = hex::decode(input, hex::Mode::Strict)?;
+        Tag::from_bytes(&bytes).ok_or(HexError::Length {
+            expected: TagLength::Bits128.bytes(),
+            found: bytes.len(),
+        })
+    }
+
+    /// Returns the tag of `bytes`, most significant first, if they are the number of bytes of
+    /// a [`TagLength`].
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let length = TagLength::ALL
+            .into_iter()
+            .find(|length| length.bytes() == bytes.len())?;
+        let mut padded = [0u8; 32];
+        padded[..bytes.len()].copy_from_slice(bytes);
+        Some(Tag::from_digest(&padded, length))
+    }
+}
+
+impl ConstantTimeEq for Tag {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.bytes.ct_eq(&other.bytes)
+            & (self.length.bytes() as u8).ct_eq(&(other.length.bytes() as u8))
+    }
+}
+
+impl PartialEq for Tag {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for Tag {}
+
+impl Hash for Tag {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bytes.hash(state);
+        self.length.hash(state);
+    }
+}
+
+impl From<u128> for Tag {
+    /// Returns the 128-bit tag of an integer.
+    fn from(tag: u128) -> Self {
+        let mut bytes = [0u8; 32];
+        bytes[..16].copy_from_slice(&tag.to_be_bytes());
+        Tag::from_digest(&bytes, TagLength::Bits128)
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+impl FromStr for Tag {
+    type Err = HexError;
+
+    fn from_str(input: &str) -> Result<Self, HexError> {
+        Tag::from_hex(input)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Tag {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Tag {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let input = <String as serde::Deserialize>::deserialize(deserializer)?;
+        Tag::from_hex(&input).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Splits the leading bits of a digest into limbs, see [`compute_tag_limbs`].
+fn split_digest<F: PrimeField>(hash_bytes: &[u8], length: TagLength, capacity: usize) -> Vec<F> {
+    digest_limbs(hash_bytes, length, capacity).collect()
+}
+
+/// The limbs of [`split_digest`], without collecting them.
+pub(crate) fn digest_limbs<F: PrimeField>(
+    hash_bytes: &[u8],
+    length: TagLength,
+    capacity: usize,
+) -> impl Iterator<Item = F> + '_ {
+    assert!(capacity > 0, "the tag needs at least one capacity element");
+    let limb_bits = F::MODULUS_BIT_SIZE as usize - 1;
+    let limbs = (8 * length.bytes()).div_ceil(limb_bits).min(capacity);
+    let mut bits = hash_bytes
+        .iter()
+        .take(length.bytes())
+        .flat_map(|byte| (0..8).rev().map(move |shift| (byte >> shift) & 1));
+    (0..limbs).map(move |limb| {
+        let count = if limb + 1 < limbs {
+            limb_bits
+        } else {
+            usize::MAX
+        };
+        bits.by_ref()
+            .take(count)
+            .fold(F::zero(), |limb_value, bit| {
+                limb_value.double() + F::from(bit)
+            })
+    })
+}
+
+impl IoPattern {
+    /// Computes the tag of a sponge instance using this IO pattern (following SAFE spec 2.3).
+    ///
+    /// # Arguments
+    /// - `domain_separator`: 64-byte domain separator for cross-protocol security.
+    ///
+    /// # Returns
+    /// The 128-bit [`Tag`].
+    pub fn tag(&self, domain_separator: impl Into<DomainSeparator>) -> Tag {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("safe.tag", operations = self.len()).entered();
+        tag_from_preimage(&self.tag_preimage(domain_separator))
+    }
+
+    /// Computes the tag of a sponge instance using this IO pattern from `length` bits of the
+    /// digest instead of 128.
+    pub fn tag_with_length(
+        &self,
+        domain_separator: impl Into<DomainSeparator>,
+        length: TagLength,
+    ) -> Tag {
+        Tag::from_digest(&self.tag_256(domain_separator), length)
+    }
+
+    /// Computes the tag of a sponge instance using this IO pattern as an element of the field `F`.
+    ///
+    /// See [`compute_tag_field`].
+    pub fn tag_field<F: PrimeField>(&self, domain_separator: impl Into<DomainSeparator>) -> F {
+        tag_field_from_preimage(&self.tag_preimage(domain_separator))
+    }
+
+    /// Computes the tag of a sponge instance using this IO pattern as an element of the field
+    /// `F`, from `length` bits of the digest.
+    ///
+    /// See [`compute_tag_field_with_length`].
+    pub fn tag_field_with_length<F: PrimeField>(
+        &self,
+        domain_separator: impl Into<DomainSeparator>,
+        length: TagLength,
+    ) -> F {
+        self.tag_field_with_hasher::<Sha256Hasher, F>(domain_separator, length)
+    }
+
+    /// Computes the tag of a sponge instance using this IO pattern with the hash `H` instead
+    /// of SHA-256.
+    ///
+    /// See [`compute_tag_with_hasher`].
+    pub fn tag_with_hasher<H: TagHasher>(
+        &self,
+        domain_separator: impl Into<DomainSeparator>,
+    ) -> Tag {
+        truncate_digest(&H::digest(&self.tag_preimage(domain_separator)))
+    }
+
+    /// Computes the tag of a sponge instance using this IO pattern as an element of the field
+    /// `F`, from `length` bits of the digest of `H`.
+    pub fn tag_field_with_hasher<H: TagHasher, F: PrimeField>(
+        &self,
+        domain_separator: impl Into<DomainSeparator>,
+        length: TagLength,
+    ) -> F {
+        fold_digest(&H::digest(&self.tag_preimage(domain_separator)), length)
+    }
+
+    /// Computes the tag of a sponge instance using this IO pattern in at most `capacity`
+    /// elements of the field `F`, from `length` bits of the digest.
+    ///
+    /// See [`compute_tag_limbs`].
+    pub fn tag_limbs<F: PrimeField>(
+        &self,
+        domain_separator: impl Into<DomainSeparator>,
+        length: TagLength,
+        capacity: usize,
+    ) -> Vec<F> {
+        split_digest(
+            &Sha256::digest(self.tag_preimage(domain_separator)),
+            length,
+            capacity,
+        )
+    }
+
+    /// Computes the full 256-bit tag of a sponge instance using this IO pattern.
+    ///
+    /// See [`compute_tag_256`].
+    pub fn tag_256(&self, domain_separator: impl Into<DomainSeparator>) -> [u8; 32] {
+        Sha256::digest(self.tag_preimage(domain_separator)).into()
+    }
+
+    /// Computes the tag of a sponge instance using this IO pattern with all its intermediate
+    /// values.
+    ///
+    /// See [`compute_tag_verbose`].
+    pub fn tag_breakdown(&self, domain_separator: impl Into<DomainSeparator>) -> TagBreakdown {
+        let preimage = self.tag_preimage(domain_separator);
+        TagBreakdown {
+            words: self.encoded_words(),
+            aggregated_words: self.normalized().encoded_words(),
+            digest: Sha256::digest(&preimage).into(),
+            tag: tag_from_preimage(&preimage),
+            preimage,
+        }
+    }
+
+    /// Returns the exact SHA-256 preimage hashed by [`IoPattern::tag`].
+    ///
+    /// See [`tag_preimage`] for the layout.
+    pub fn tag_preimage(&self, domain_separator: impl Into<DomainSeparator>) -> Vec<u8> {
+        // Step 1: Parse and aggregate consecutive operations of the same type.
+        let encoded_words = self.normalized().encoded_words();
+
+        // Step 2: Serialize to byte string and append domain separator (following SAFE spec 2.3).
+        let mut input_bytes = Vec::with_capacity(4 * encoded_words.len() + 64);
+
+        // Serialize encoded words to bytes (big-endian as per SAFE spec).
+        for &word in &encoded_words {
+            input_bytes.extend_from_slice(&word.to_be_bytes());
+        }
+
+        // Append domain separator.
+        input_bytes.extend_from_slice(domain_separator.into().as_bytes());
+
+        input_bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bn254::Fr;
+    use core::str::FromStr;
+
+    /// `0x41424344` followed by zero padding, as used throughout the Noir tests.
+    fn abcd_domain() -> [u8; 64] {
+        crate::domain::padded_domain_separator(b"ABCD")
+    }
+
+    #[test]
+    fn test_known_tags() {
+        // Values produced by the original script and checked against the Noir circuit.
+        let domain_separator = abcd_domain();
+        assert_eq!(
+            compute_tag(&[0x80000003, 0x00000001], domain_separator),
+            Tag::from(0x0ea2aa7e178caa74de1f91e83ad43a81)
+        );
+        assert_eq!(
+            compute_tag(&[0x80000003, 0x00000002], domain_separator),
+            Tag::from(0x4b73794cad2d984072943f91b69f20a0)
+        );
+        assert_eq!(
+            compute_tag(&[0x80000000, 0x00000001], domain_separator),
+            Tag::from(0x854c618487e2b6f4df879698e178ab80)
+        );
+        assert_eq!(
+            compute_tag(&[0x80000002, 0x00000002, 0x80000002], domain_separator),
+            Tag::from(0xb940a721917ac3f0940be7638f7a1d9d)
+        );
+    }
+
+    #[test]
+    fn test_aggregated_patterns_share_tag() {
+        let domain_separator = abcd_domain();
+        let tag = IoPattern::new()
+            .absorb(1)
+            .absorb(1)
+            .squeeze(1)
+            .tag(domain_separator);
+        assert_eq!(tag, Tag::from(0x08e2da1eb5257f918e9c15b5605a3516));
+        assert_eq!(
+            tag,
+            IoPattern::new().absorb(2).squeeze(1).tag(domain_separator)
+        );
+    }
+
+    #[test]
+    fn test_field_tag_matches_integer_tag() {
+        let domain_separator = abcd_domain();
+        let words = [0x80000003, 0x00000001];
+        assert_eq!(
+            compute_tag_field::<ark_bn254::Fr>(&words, &domain_separator),
+            compute_tag(&words, domain_separator).to_field::<ark_bn254::Fr>()
+        );
+    }
+
+    #[test]
+    fn test_tag_lengths() {
+        // Same values as `test_tag_lengths` of the Noir library.
+        let domain_separator = abcd_domain();
+        let words = [0x80000003, 0x00000001];
+        let tag = |length| compute_tag_field_with_length::<Fr>(&words, &domain_separator, length);
+        assert_eq!(tag(TagLength::Bits64), Fr::from(0x0ea2aa7e178caa74u64));
+        assert_eq!(
+            tag(TagLength::Bits128),
+            compute_tag_field::<Fr>(&words, &domain_separator)
+        );
+        let digest = compute_tag_256(&words, domain_separator);
+        assert_eq!(
+            tag(TagLength::Bits248),
+            Fr::from_be_bytes_mod_order(&digest[..31])
+        );
+        assert_eq!(tag(TagLength::Full), Fr::from_be_bytes_mod_order(&digest));
+        assert_eq!(
+            tag(TagLength::Full),
+            Fr::from_str(
+                "6619785800972111436384341274663886903541080054103311604714823929020630720831"
+            )
+            .unwrap()
+        );
+        let parameters = TagParameters::find(TagLength::Full, Sha256Hasher::ID).unwrap();
+        let pattern = IoPattern::from_words(&words);
+        assert!(parameters.has_tag(&pattern, &domain_separator, &tag(TagLength::Full), 1));
+        // Another length is another tag.
+        assert!(!TagParameters::default().has_tag(
+            &pattern,
+            &domain_separator,
+            &tag(TagLength::Full),
+            1
+        ));
+    }
+
+    /// The byte-by-byte fold `tag * 256 + byte` that [`Tag`] replaces.
+    fn fold_reference<F: PrimeField>(digest: &[u8], length: TagLength) -> F {
+        digest[..length.bytes()]
+            .iter()
+            .fold(F::zero(), |tag, &byte| {
+                tag * F::from(256u64) + F::from(byte)
+            })
+    }
+
+    #[test]
+    fn test_tag_matches_fold() {
+        for seed in 0u32..256 {
+            let mut digest: [u8; 32] = Sha256::digest(seed.to_be_bytes()).into();
+            // Extreme digests: all ones, and all zeros but the last byte.
+            if seed < 2 {
+                digest = [[0xff; 32], [0; 32]][seed as usize];
+                digest[31] = 1;
+            }
+            for length in TagLength::ALL {
+                let tag = Tag::from_digest(&digest, length);
+                assert_eq!(tag.as_bytes(), &digest[..length.bytes()]);
+                assert_eq!(tag.to_field::<Fr>(), fold_reference::<Fr>(&digest, length));
+                #[cfg(feature = "small-fields")]
+                assert_eq!(
+                    tag.to_field::<crate::fields::Goldilocks>(),
+                    fold_reference(&digest, length)
+                );
+                let integer = (length.bytes() <= 16).then(|| {
+                    digest[..length.bytes()]
+                        .iter()
+                        .fold(0u128, |tag, &byte| (tag << 8) | byte as u128)
+                });
+                assert_eq!(tag.to_u128(), integer);
+            }
+        }
+        // Bytes past the length do not matter.
+        let mut other = [0xff; 32];
+        other[..8].copy_from_slice(&[1; 8]);
+        assert_eq!(
+            Tag::from_digest(&other, TagLength::Bits64),
+            Tag::from_digest(&[1; 32], TagLength::Bits64)
+        );
+    }
+
+    #[test]
+    fn test_tag_limbs() {
+        let domain_separator = abcd_domain();
+        let words = [0x80000003, 0x00000001];
+        // A single limb in BN254 up to 248 bits, or with a capacity of 1.
+        for (length, capacity) in [
+            (TagLength::Bits64, 4),
+            (TagLength::Bits128, 4),
+            (TagLength::Bits248, 4),
+            (TagLength::Full, 1),
+        ] {
+            assert_eq!(
+                compute_tag_limbs::<Fr>(&words, &domain_separator, length, capacity),
+                vec![compute_tag_field_with_length::<Fr>(
+                    &words,
+                    &domain_separator,
+                    length
+                )]
+            );
+        }
+
+        #[cfg(feature = "small-fields")]
+        {
+            use crate::fields::{BabyBear, Goldilocks};
+
+            let tag: u128 = 0x0ea2aa7e178caa74de1f91e83ad43a81;
+            let limbs = |shifts: &[u32], bits: u32| -> Vec<u128> {
+                shifts
+                    .iter()
+                    .map(|&shift| (tag >> shift) & ((1 << bits) - 1))
+                    .collect()
+            };
+            let goldilocks = limbs(&[65, 2, 0], 63);
+            assert_eq!(
+                compute_tag_limbs::<Goldilocks>(&words, &domain_separator, TagLength::Bits128, 4),
+                [goldilocks[0], goldilocks[1], tag & 3].map(Goldilocks::from)
+            );
+            let baby_bear = limbs(&[98, 68, 38, 8], 30);
+            assert_eq!(
+                compute_tag_limbs::<BabyBear>(&words, &domain_separator, TagLength::Bits128, 8),
+                [
+                    baby_bear[0],
+                    baby_bear[1],
+                    baby_bear[2],
+                    baby_bear[3],
+                    tag & 0xff
+                ]
+                .map(BabyBear::from)
+            );
+            // A capacity too small for the limbs reduces the last one.
+            assert_eq!(
+                compute_tag_limbs::<BabyBear>(&words, &domain_separator, TagLength::Bits128, 1),
+                vec![BabyBear::from(tag)]
+            );
+        }
+    }
+
+    #[test]
+    fn test_tag_hashers() {
+        let domain_separator = abcd_domain();
+        let words = [0x80000003, 0x00000001];
+        assert_eq!(
+            compute_tag_with_hasher::<Sha256Hasher>(&words, &domain_separator),
+            compute_tag(&words, domain_separator)
+        );
+        #[cfg(feature = "blake2s")]
+        assert_eq!(
+            compute_tag_with_hasher::<Blake2sHasher>(&words, &domain_separator),
+            Tag::from(0x7bb62f0f7e03511e0e92f369ed2a2ac4)
+        );
+        #[cfg(feature = "keccak")]
+        {
+            // The Keccak-256 of the empty string, which differs from its SHA3-256.
+            assert_eq!(Keccak256Hasher::digest(b"")[..4], [0xc5, 0xd2, 0x46, 0x01]);
+            let tag = IoPattern::from_words(&words)
+                .tag_field_with_hasher::<Keccak256Hasher, Fr>(&domain_separator, TagLength::Full);
+            let pattern = IoPattern::from_words(&words);
+            let keccak = TagParameters::find(TagLength::Full, Keccak256Hasher::ID).unwrap();
+            assert!(keccak.has_tag(&pattern, &domain_separator, &tag, 1));
+            let sha256 = TagParameters::find(TagLength::Full, Sha256Hasher::ID).unwrap();
+            assert!(!sha256.has_tag(&pattern, &domain_separator, &tag, 1));
+        }
+    }
+
+    #[test]
+    fn test_tag_256_extends_truncated_tag() {
+        let domain_separator = abcd_domain();
+        let full = compute_tag_256(&[0x80000003, 0x00000001], domain_separator);
+
+        assert_eq!(
+            full[..16],
+            *compute_tag(&[0x80000003, 0x00000001], domain_separator).as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_breakdown_matches_steps() {
+        let domain_separator = abcd_domain();
+        let words = [0x80000003, 0x80000003, 0x00000003];
+        let breakdown = compute_tag_verbose(&words, domain_separator);
+        assert_eq!(breakdown.words, words.to_vec());
+        assert_eq!(breakdown.aggregated_words, vec![0x80000006, 0x00000003]);
+        assert_eq!(breakdown.preimage, tag_preimage(&words, domain_separator));
+        assert_eq!(breakdown.digest, compute_tag_256(&words, domain_separator));
+        assert_eq!(breakdown.tag, compute_tag(&words, domain_separator));
+    }
+
+    #[test]
+    fn test_batch_matches_single_tags() {
+        let mut other_domain = abcd_domain();
+        other_domain[4] = 0x45;
+        let items: Vec<(IoPattern, DomainSeparator)> = (0..40)
+            .map(|i| {
+                let pattern = IoPattern::new()
+                    .absorb(i % 5)
+                    .squeeze(0)
+                    .absorb(1)
+                    .squeeze(i % 3);
+                let domain_separator = if i % 2 == 0 {
+                    abcd_domain()
+                } else {
+                    other_domain
+                };
+                (pattern, domain_separator.into())
+            })
+            .collect();
+        let expected: Vec<Tag> = items
+            .iter()
+            .map(|(pattern, domain_separator)| pattern.tag(domain_separator))
+            .collect();
+        assert_eq!(compute_tags_batch(&items), expected);
+        assert_eq!(compute_tags_batch(&[]), Vec::<Tag>::new());
+    }
+
+    #[test]
+    fn test_tag_preimage_layout() {
+        // ABSORB(3), ABSORB(3), SQUEEZE(3) is serialized as [0x80000006, 0x00000003].
+        let domain_separator = abcd_domain();
+        let preimage = tag_preimage(&[0x80000003, 0x80000003, 0x00000003], domain_separator);
+
+        let mut expected = vec![0x80, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x03];
+        expected.extend_from_slice(&domain_separator);
+        assert_eq!(preimage, expected);
+        assert_eq!(
+            tag_from_preimage(&preimage),
+            compute_tag(&[0x80000003, 0x80000003, 0x00000003], domain_separator)
+        );
+    }
+
+    #[test]
+    fn test_tag_text_form() {
+        let tag = compute_tag(&[0x80000003, 0x00000001], abcd_domain());
+        assert_eq!(tag.to_string(), "0x0ea2aa7e178caa74de1f91e83ad43a81");
+        assert_eq!(Tag::from_str("0x0ea2aa7e178caa74de1f91e83ad43a81"), Ok(tag));
+        assert_eq!(Tag::from(1).to_hex(), "0x00000000000000000000000000000001");
+
+        // The number of digits gives the length.
+        let digest = compute_tag_256(&[0x80000003, 0x00000001], abcd_domain());
+        let full = Tag::from_digest(&digest, TagLength::Full);
+        assert_eq!(Tag::from_hex(&full.to_hex()), Ok(full));
+        assert_eq!(full.to_u128(), None);
+        assert_eq!(
+            Tag::from_hex("0x0ea2aa"),
+            Err(HexError::Length {
+                expected: 16,
+                found: 3
+            })
+        );
+        assert!(Tag::from_hex("0x0EA2AA7E178CAA74DE1F91E83AD43A8").is_err());
+
+        #[cfg(feature = "vectors")]
+        {
+            let json = serde_json::to_string(&tag).unwrap();
+            assert_eq!(json, "\"0x0ea2aa7e178caa74de1f91e83ad43a81\"");
+            assert_eq!(serde_json::from_str::<Tag>(&json).unwrap(), tag);
+        }
+    }
+
+    #[test]
+    fn test_tag_equality() {
+        let digest = compute_tag_256(&[0x80000003, 0x00000001], abcd_domain());
+        let tag = Tag::from_digest(&digest, TagLength::Bits128);
+        assert!(bool::from(tag.ct_eq(&Tag::from(tag.to_u128().unwrap()))));
+        assert!(!bool::from(
+            tag.ct_eq(&Tag::from(tag.to_u128().unwrap() ^ 1))
+        ));
+        // A truncation is not the tag it is the prefix of.
+        assert_ne!(tag, Tag::from_digest(&digest, TagLength::Full));
+        assert_ne!(
+            Tag::from_digest(&[0u8; 32], TagLength::Bits64),
+            Tag::from_digest(&[0u8; 32], TagLength::Bits128)
+        );
+    }
+}
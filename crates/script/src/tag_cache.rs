@@ -0,0 +1,271 @@
+//! A bounded, thread-safe cache of tags keyed by IO pattern and domain separator.
+//!
+//! A prover running one fixed protocol computes the same tag for every proof. [`TagCache`]
+//! remembers the tags of [`compute_tag`] for up to a given number of (pattern, domain
+//! separator) pairs, evicting the least recently used beyond that, and can be shared between
+//! threads behind an `Arc` or a `static`:
+//!
+//! ```
+//! use safe_api::tag_cache::TagCache;
+//! use safe_api::IoPattern;
+//!
+//! let cache = TagCache::new(64);
+//! let words = IoPattern::new().absorb(3).squeeze(1).encoded_words();
+//! let mut domain_separator = [0u8; 64];
+//! domain_separator[..4].copy_from_slice(b"ABCD");
+//!
+//! let tag = cache.compute_tag(&words, &domain_separator);
+//! assert_eq!(cache.compute_tag(&words, &domain_separator), tag);
+//! assert_eq!((cache.hits(), cache.misses()), (1, 1));
+//! ```
+//!
+//! [`compute_tag_cached`] is [`compute_tag`] through a process-wide cache of
+//! [`DEFAULT_CAPACITY`] entries. Lookups hash the words and the domain separator with the
+//! standard library hasher, much cheaper than the SHA-256 of the tag, and take a lock, so a
+//! cache contended by many threads is best split, e.g. one per worker.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use crate::domain::DomainSeparator;
+use crate::io_pattern::IoPattern;
+use crate::tag::{compute_tag, Tag};
+
+/// Capacity of the cache of [`compute_tag_cached`].
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// Tags by domain separator, then encoded IO pattern, see the [module documentation](self).
+#[derive(Debug)]
+pub struct TagCache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    tags: HashMap<[u8; 64], HashMap<Vec<u32>, Entry>>,
+    len: usize,
+    /// Incremented on every access, to order the entries by last use.
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    tag: Tag,
+    last_used: u64,
+}
+
+impl TagCache {
+    /// Returns an empty cache of at most `capacity` tags. A cache of capacity 0 keeps nothing.
+    pub fn new(capacity: usize) -> Self {
+        TagCache {
+            capacity,
+            entries: Mutex::new(Entries::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns [`compute_tag`]`(io_pattern, domain_separator)`, computing it only if it is not
+    /// cached.
+    pub fn compute_tag(
+        &self,
+        io_pattern: &[u32],
+        domain_separator: impl Into<DomainSeparator>,
+    ) -> Tag {
+        let domain_separator = domain_separator.into();
+        let domain_separator = domain_separator.as_bytes();
+        if let Some(tag) = self.entries().get(io_pattern, domain_separator) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return tag;
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        // Computed without the lock: two threads may both miss and compute the same tag.
+        let tag = compute_tag(io_pattern, domain_separator);
+        if self.capacity > 0 {
+            self.entries()
+                .insert(io_pattern, domain_separator, tag, self.capacity);
+        }
+        tag
+    }
+
+    /// Returns [`IoPattern::tag`], cached like [`TagCache::compute_tag`].
+    pub fn tag(&self, io_pattern: &IoPattern, domain_separator: impl Into<DomainSeparator>) -> Tag {
+        self.compute_tag(&io_pattern.encoded_words(), domain_separator)
+    }
+
+    /// Returns the maximum number of cached tags.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of cached tags.
+    pub fn len(&self) -> usize {
+        self.entries().len
+    }
+
+    /// Returns `true` if no tag is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of lookups answered from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of lookups that computed the tag.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Removes every cached tag, keeping the counters.
+    pub fn clear(&self) {
+        let mut entries = self.entries();
+        entries.tags.clear();
+        entries.len = 0;
+    }
+
+    fn entries(&self) -> std::sync::MutexGuard<'_, Entries> {
+        // The entries are consistent between statements, so a panic elsewhere leaves them
+        // usable.
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Entries {
+    fn get(&mut self, io_pattern: &[u32], domain_separator: &[u8; 64]) -> Option<Tag> {
+        self.clock += 1;
+        let entry = self.tags.get_mut(domain_separator)?.get_mut(io_pattern)?;
+        entry.last_used = self.clock;
+        Some(entry.tag)
+    }
+
+    fn insert(
+        &mut self,
+        io_pattern: &[u32],
+        domain_separator: &[u8; 64],
+        tag: Tag,
+        capacity: usize,
+    ) {
+        self.clock += 1;
+        let entry = Entry {
+            tag,
+            last_used: self.clock,
+        };
+        let patterns = self.tags.entry(*domain_separator).or_default();
+        if patterns.insert(io_pattern.to_vec(), entry).is_some() {
+            return;
+        }
+        self.len += 1;
+        if self.len > capacity {
+            self.evict_least_recently_used();
+        }
+    }
+
+    /// Removes the entry used least recently, in time linear in the number of entries: tags
+    /// are cached because they repeat, so evictions are rare.
+    fn evict_least_recently_used(&mut self) {
+        let oldest = self
+            .tags
+            .iter()
+            .flat_map(|(domain_separator, patterns)| {
+                patterns
+                    .iter()
+                    .map(move |(words, entry)| (entry.last_used, domain_separator, words))
+            })
+            .min_by_key(|(last_used, _, _)| *last_used)
+            .map(|(_, domain_separator, words)| (*domain_separator, words.clone()));
+        if let Some((domain_separator, words)) = oldest {
+            let patterns = self.tags.get_mut(&domain_separator).expect("found above");
+            patterns.remove(&words);
+            if patterns.is_empty() {
+                self.tags.remove(&domain_separator);
+            }
+            self.len -= 1;
+        }
+    }
+}
+
+/// Returns [`compute_tag`]`(io_pattern, domain_separator)` through a process-wide
+/// [`TagCache`] of [`DEFAULT_CAPACITY`] tags.
+pub fn compute_tag_cached(io_pattern: &[u32], domain_separator: impl Into<DomainSeparator>) -> Tag {
+    static CACHE: OnceLock<TagCache> = OnceLock::new();
+    CACHE
+        .get_or_init(|| TagCache::new(DEFAULT_CAPACITY))
+        .compute_tag(io_pattern, domain_separator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn domain(byte: u8) -> [u8; 64] {
+        [byte; 64]
+    }
+
+    #[test]
+    fn test_matches_compute_tag() {
+        let cache = TagCache::new(8);
+        let words = [0x80000003, 0x00000001];
+        for _ in 0..3 {
+            assert_eq!(
+                cache.compute_tag(&words, domain(1)),
+                compute_tag(&words, domain(1))
+            );
+        }
+        assert_eq!(
+            compute_tag_cached(&words, domain(2)),
+            compute_tag(&words, domain(2))
+        );
+        assert_eq!((cache.hits(), cache.misses(), cache.len()), (2, 1, 1));
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let cache = TagCache::new(2);
+        cache.compute_tag(&[1], domain(1));
+        cache.compute_tag(&[2], domain(1));
+        // Touch [1], so that [2] is the least recently used.
+        cache.compute_tag(&[1], domain(1));
+        cache.compute_tag(&[1], domain(2));
+        assert_eq!(cache.len(), 2);
+
+        let misses = cache.misses();
+        cache.compute_tag(&[1], domain(1));
+        cache.compute_tag(&[1], domain(2));
+        assert_eq!(cache.misses(), misses);
+        cache.compute_tag(&[2], domain(1));
+        assert_eq!(cache.misses(), misses + 1);
+
+        let empty = TagCache::new(0);
+        empty.compute_tag(&[1], domain(1));
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_shared_between_threads() {
+        let cache = TagCache::new(4);
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for word in 1..=4u32 {
+                        assert_eq!(
+                            cache.compute_tag(&[word], domain(1)),
+                            compute_tag(&[word], domain(1))
+                        );
+                    }
+                });
+            }
+        });
+        assert_eq!(cache.len(), 4);
+        assert_eq!(cache.hits() + cache.misses(), 16);
+    }
+}
@@ -0,0 +1,110 @@
+//! Trace context of requests to services running sponge sessions.
+//!
+//! A service that computes tags or drives sponges for other services should attach the trace
+//! id of each request to its spans, together with the session and tag it operates on, so that
+//! a verification failing in one service can be matched with the sponge operations that
+//! produced the transcript. Callers send it in a W3C [`TRACEPARENT`] header, or as a plain
+//! [`REQUEST_ID`] header when they do not trace:
+//!
+//! ```
+//! use safe_api::trace_context::trace_id;
+//!
+//! let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+//! assert_eq!(
+//!     trace_id(Some(traceparent), Some("request-7")),
+//!     Some("4bf92f3577b34da6a3ce929d0e0e4736")
+//! );
+//! assert_eq!(trace_id(Some("garbage"), Some("request-7")), Some("request-7"));
+//! ```
+//!
+//! Both headers should be echoed on the response, so that the caller can log them too.
+
+/// Name of the W3C trace context header.
+pub const TRACEPARENT: &str = "traceparent";
+
+/// Name of the request id header, the fallback for callers that do not trace.
+pub const REQUEST_ID: &str = "x-request-id";
+
+/// Returns the trace id of a `traceparent` header value, or `None` if it is malformed.
+///
+/// The value is `version-trace_id-parent_id-flags` in lowercase hex, with a 32-digit trace id
+/// and a 16-digit parent id that are not all zeros. Versions other than `00` may append
+/// further fields; version `ff` is invalid.
+pub fn parse_traceparent(traceparent: &str) -> Option<&str> {
+    let mut fields = traceparent.split('-');
+    let version = fields.next()?;
+    let trace_id = fields.next()?;
+    let parent_id = fields.next()?;
+    let flags = fields.next()?;
+    let well_formed = is_hex(version, 2)
+        && version != "ff"
+        && (version != "00" || fields.next().is_none())
+        && is_hex(trace_id, 32)
+        && is_hex(parent_id, 16)
+        && is_hex(flags, 2);
+    well_formed.then_some(trace_id)
+}
+
+/// Returns the trace id of a request: the trace id of its [`TRACEPARENT`] header, if well
+/// formed, or else its [`REQUEST_ID`] header.
+pub fn trace_id<'a>(traceparent: Option<&'a str>, request_id: Option<&'a str>) -> Option<&'a str> {
+    traceparent.and_then(parse_traceparent).or(request_id)
+}
+
+/// Whether `field` has `len` lowercase hex digits, not all zeros unless it is a version or
+/// flags byte.
+fn is_hex(field: &str, len: usize) -> bool {
+    field.len() == len
+        && field
+            .bytes()
+            .all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+        && (len == 2 || field.bytes().any(|byte| byte != b'0'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+    #[test]
+    fn test_parse_traceparent() {
+        assert_eq!(
+            parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+            Some(TRACE_ID)
+        );
+        // Later versions may carry more fields.
+        assert_eq!(
+            parse_traceparent("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-extra"),
+            Some(TRACE_ID)
+        );
+        for traceparent in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e47-00f067aa0ba902b7-01",
+        ] {
+            assert_eq!(parse_traceparent(traceparent), None, "{}", traceparent);
+        }
+    }
+
+    #[test]
+    fn test_trace_id() {
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        assert_eq!(
+            trace_id(Some(traceparent), Some("request-7")),
+            Some(TRACE_ID)
+        );
+        assert_eq!(trace_id(None, Some("request-7")), Some("request-7"));
+        assert_eq!(
+            trace_id(Some("00-0-0-0"), Some("request-7")),
+            Some("request-7")
+        );
+        assert_eq!(trace_id(Some("00-0-0-0"), None), None);
+        assert_eq!(trace_id(None, None), None);
+    }
+}
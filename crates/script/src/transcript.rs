@@ -0,0 +1,426 @@
+use alloc::vec::Vec;
+
+use sha2::{Digest, Sha512};
+
+use crate::domain::DomainSeparator;
+#[cfg(feature = "std")]
+use crate::encoding::ChunkEncoding;
+use crate::io_pattern::{IoPattern, Operation};
+use crate::permutation::Permutation;
+use crate::sponge::{Sponge, SpongeBuilder};
+use crate::state::{SpongeState, StateError};
+use crate::tag::TagParameters;
+
+/// Prefix of the hashed transcript domain separator, versioning its layout.
+const TRANSCRIPT_DOMAIN_PREFIX: &[u8] = b"SAFE-TRANSCRIPT-V1";
+
+/// Prefix of the hashed domain separator of sequenced transcripts.
+const SEQUENCED_TRANSCRIPT_DOMAIN_PREFIX: &[u8] = b"SAFE-TRANSCRIPT-SEQ-V1";
+
+/// A Fiat-Shamir transcript with labeled messages and challenges, built on the [`Sponge`].
+///
+/// The protocol is declared once as an [`IoPattern`] whose operations are all labeled. Prover
+/// and verifier then only name the message they append or the challenge they derive; the
+/// transcript checks every call against the declared protocol, so the sponge is never driven
+/// out of order.
+///
+/// The labels are bound into the domain separator (see [`transcript_domain_separator`]), which
+/// makes two protocols with the same shape but different labels produce independent
+/// challenges.
+///
+/// ```
+/// use ark_bn254::Fr;
+/// use safe_api::domain::padded_domain_separator;
+/// use safe_api::poseidon2::Poseidon2;
+/// use safe_api::{IoPattern, Transcript};
+///
+/// let protocol = IoPattern::new()
+///     .absorb_labeled("commitment", 2)
+///     .squeeze_labeled("alpha", 1);
+/// let domain_separator = padded_domain_separator(b"my-app/protocol");
+/// let mut transcript = Transcript::<Poseidon2>::new(protocol, &domain_separator);
+/// transcript.append("commitment", &[Fr::from(1u64), Fr::from(2u64)]);
+/// let alpha = transcript.challenge("alpha", 1);
+/// transcript.finish();
+/// # assert_eq!(alpha.len(), 1);
+/// ```
+///
+/// # Sequenced transcripts
+/// A transcript started with [`SpongeBuilder::sequenced`] absorbs, before every message, its
+/// sequence number: the index of the message in the protocol. The sponge runs the
+/// [`IoPattern::sequenced`] protocol, in which every message is one element longer, under
+/// [`sequenced_transcript_domain_separator`], and each message goes through
+/// [`Sponge::absorb_sequenced`], so a circuit checks the transcript with the Noir
+/// `absorb_sequenced`. This hardens protocols whose messages could otherwise be reframed, e.g.
+/// concatenations of variable-length parts.
+///
+/// ```
+/// use ark_bn254::Fr;
+/// use safe_api::domain::padded_domain_separator;
+/// use safe_api::poseidon2::Poseidon2;
+/// use safe_api::{IoPattern, Sponge};
+///
+/// let protocol = IoPattern::new()
+///     .absorb_labeled("commitment", 2)
+///     .squeeze_labeled("alpha", 1);
+/// let domain_separator = padded_domain_separator(b"my-app/protocol");
+/// let mut transcript = Sponge::<Poseidon2>::builder()
+///     .sequenced()
+///     .transcript(protocol, &domain_separator);
+/// transcript.append("commitment", &[Fr::from(1u64), Fr::from(2u64)]);
+/// let alpha = transcript.challenge("alpha", 1);
+/// transcript.finish();
+/// # assert_eq!(alpha.len(), 1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Transcript<P: Permutation> {
+    sponge: Sponge<P>,
+    sequenced: bool,
+}
+
+impl<P: Permutation + Default> Transcript<P> {
+    /// Starts a transcript for the labeled `protocol` under `domain_separator`.
+    ///
+    /// # Panics
+    /// If an operation of `protocol` has no label, or if `domain_separator` is empty (all
+    /// zeros), see [`SpongeBuilder::allow_empty_domain`].
+    pub fn new(protocol: IoPattern, domain_separator: impl Into<DomainSeparator>) -> Self {
+        Self::with_permutation(P::default(), protocol, domain_separator)
+    }
+
+    /// Resumes a transcript saved with [`Transcript::save`] under the same `domain_separator`
+    /// as the one it was started with.
+    ///
+    /// Fails like [`Sponge::resume`], including when the labels of the saved protocol differ,
+    /// since they are bound into the domain separator. A transcript started with
+    /// [`SpongeBuilder::config`] resumes with [`SpongeBuilder::resume_transcript`] instead.
+    pub fn resume(
+        state: SpongeState<P::Field>,
+        domain_separator: impl Into<DomainSeparator>,
+    ) -> Result<Self, StateError> {
+        Self::resume_with_permutation(P::default(), state, domain_separator)
+    }
+}
+
+impl<P: Permutation> Transcript<P> {
+    /// Starts a transcript running on the given permutation instance.
+    ///
+    /// See [`Transcript::new`].
+    pub fn with_permutation(
+        permutation: P,
+        protocol: IoPattern,
+        domain_separator: impl Into<DomainSeparator>,
+    ) -> Self {
+        SpongeBuilder::new(permutation).transcript(protocol, domain_separator)
+    }
+
+    /// Starts the transcript, whatever the domain separator.
+    pub(crate) fn init(
+        permutation: P,
+        protocol: IoPattern,
+        domain_separator: &[u8; 64],
+        sequenced: bool,
+        tag_parameters: TagParameters,
+    ) -> Self {
+        assert!(
+            (0..protocol.len()).all(|index| protocol.label(index).is_some()),
+            "Unlabeled transcript operation"
+        );
+        let (protocol, domain_separator) = match sequenced {
+            true => (
+                protocol.sequenced(),
+                sequenced_transcript_domain_separator(&protocol, domain_separator),
+            ),
+            false => {
+                let domain_separator = transcript_domain_separator(&protocol, domain_separator);
+                (protocol, domain_separator)
+            }
+        };
+        Transcript {
+            sponge: Sponge::init(permutation, protocol, &domain_separator, tag_parameters),
+            sequenced,
+        }
+    }
+
+    /// Resumes a transcript saved with [`Transcript::save`], running on the given permutation
+    /// instance.
+    ///
+    /// See [`Transcript::resume`].
+    pub fn resume_with_permutation(
+        permutation: P,
+        state: SpongeState<P::Field>,
+        domain_separator: impl Into<DomainSeparator>,
+    ) -> Result<Self, StateError> {
+        let domain_separator = domain_separator.into();
+        // Sequenced transcripts are told apart by their tag, since their domain separators
+        // differ.
+        let sequenced_domain_separator =
+            sequenced_transcript_domain_separator(state.io_pattern(), &domain_separator);
+        let sequenced = state.tag_parameters()?.has_tag(
+            state.io_pattern(),
+            &sequenced_domain_separator,
+            &state.tag(),
+            P::CAPACITY,
+        );
+        let domain_separator = match sequenced {
+            true => sequenced_domain_separator,
+            false => transcript_domain_separator(state.io_pattern(), &domain_separator),
+        };
+        Ok(Transcript {
+            sponge: Sponge::resume_with_permutation(permutation, state, domain_separator)?,
+            sequenced,
+        })
+    }
+
+    /// Saves the state of this transcript, to resume it later with [`Transcript::resume`].
+    pub fn save(&self) -> SpongeState<P::Field> {
+        self.sponge.save()
+    }
+
+    /// Returns the protocol this transcript follows, [`IoPattern::sequenced`] for a
+    /// [sequenced](Transcript#sequenced-transcripts) transcript.
+    pub fn protocol(&self) -> &IoPattern {
+        self.sponge.io_pattern()
+    }
+
+    /// Appends the message `label` to the transcript.
+    ///
+    /// # Panics
+    /// If the protocol does not expect the message `label` of `message.len()` elements next.
+    pub fn append(&mut self, label: &str, message: &[P::Field]) {
+        match self.sequenced {
+            true => {
+                assert!(
+                    self.sponge.next_label() == Some(label),
+                    "Unexpected absorb label"
+                );
+                self.sponge.absorb_sequenced(message);
+            }
+            false => self.sponge.absorb_labeled(label, message),
+        }
+    }
+
+    /// Appends the first `byte_len` bytes of `reader` as the message `label`, in constant
+    /// memory (see [`Sponge::absorb_reader`]). The protocol declares the message with
+    /// `encoding.encoded_len(byte_len)` elements:
+    ///
+    /// ```
+    /// use ark_bn254::Fr;
+    /// use safe_api::domain::padded_domain_separator;
+    /// use safe_api::encoding::ChunkEncoding;
+    /// use safe_api::poseidon2::Poseidon2;
+    /// use safe_api::{IoPattern, Transcript};
+    ///
+    /// let blob = vec![7u8; 100_000];
+    /// let length = ChunkEncoding::Packed.encoded_len::<Fr>(blob.len() as u64);
+    /// let protocol = IoPattern::new()
+    ///     .absorb_labeled("blob", length as u32)
+    ///     .squeeze_labeled("commitment", 1);
+    /// let domain_separator = padded_domain_separator(b"my-app/blob");
+    /// let mut transcript = Transcript::<Poseidon2>::new(protocol, &domain_separator);
+    /// transcript.absorb_reader("blob", &blob[..], blob.len() as u64, ChunkEncoding::Packed)?;
+    /// let commitment = transcript.challenge("commitment", 1);
+    /// transcript.finish();
+    /// # assert_eq!(commitment.len(), 1);
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    ///
+    /// # Panics
+    /// If the protocol does not expect the message `label` of that many elements next.
+    #[cfg(feature = "std")]
+    pub fn absorb_reader<R: std::io::Read>(
+        &mut self,
+        label: &str,
+        reader: R,
+        byte_len: u64,
+        encoding: ChunkEncoding,
+    ) -> std::io::Result<()> {
+        assert!(
+            self.sponge.next_label() == Some(label),
+            "Unexpected absorb label"
+        );
+        self.sponge
+            .absorb_reader_from(reader, byte_len, encoding, self.sequenced)
+    }
+
+    /// Same as [`Transcript::absorb_reader`], reading from an asynchronous `reader`.
+    ///
+    /// # Panics
+    /// If the protocol does not expect the message `label` of that many elements next.
+    #[cfg(feature = "tokio")]
+    pub async fn absorb_async_reader<R: tokio::io::AsyncRead + Unpin>(
+        &mut self,
+        label: &str,
+        reader: R,
+        byte_len: u64,
+        encoding: ChunkEncoding,
+    ) -> std::io::Result<()> {
+        assert!(
+            self.sponge.next_label() == Some(label),
+            "Unexpected absorb label"
+        );
+        self.sponge
+            .absorb_async_reader_from(reader, byte_len, encoding, self.sequenced)
+            .await
+    }
+
+    /// Derives the `length` elements of the challenge `label`.
+    ///
+    /// # Panics
+    /// If the protocol does not expect the challenge `label` of `length` elements next.
+    pub fn challenge(&mut self, label: &str, length: usize) -> Vec<P::Field> {
+        let challenge = self.sponge.squeeze_labeled(label);
+        assert!(challenge.len() == length, "Length mismatch");
+        challenge
+    }
+
+    /// Checks that the whole protocol has been run and erases the state.
+    pub fn finish(&mut self) {
+        self.sponge.finish();
+    }
+}
+
+/// Derives the domain separator actually used by a [`Transcript`]: SHA-512 over a versioned
+/// prefix, the application domain separator and the labels of the protocol.
+///
+/// Each operation contributes its kind (`A` or `S`), the big-endian 32-bit length of its
+/// label and the label bytes. The operation lengths are already bound by the tag.
+pub fn transcript_domain_separator(protocol: &IoPattern, domain_separator: &[u8; 64]) -> [u8; 64] {
+    derive_domain_separator(TRANSCRIPT_DOMAIN_PREFIX, protocol, domain_separator)
+}
+
+/// Derives the domain separator actually used by a
+/// [sequenced](Transcript#sequenced-transcripts) [`Transcript`], like
+/// [`transcript_domain_separator`] with another prefix. The lengths of `protocol` do not
+/// matter, so it may be the protocol or its [`IoPattern::sequenced`] form.
+pub fn sequenced_transcript_domain_separator(
+    protocol: &IoPattern,
+    domain_separator: &[u8; 64],
+) -> [u8; 64] {
+    derive_domain_separator(
+        SEQUENCED_TRANSCRIPT_DOMAIN_PREFIX,
+        protocol,
+        domain_separator,
+    )
+}
+
+fn derive_domain_separator(
+    prefix: &[u8],
+    protocol: &IoPattern,
+    domain_separator: &[u8; 64],
+) -> [u8; 64] {
+    let mut hasher = Sha512::new();
+    hasher.update(prefix);
+    hasher.update(domain_separator);
+    for (index, operation) in protocol.operations().iter().enumerate() {
+        let label = protocol.label(index).unwrap_or_default();
+        hasher.update(match operation {
+            Operation::Absorb(_) => b"A",
+            Operation::Squeeze(_) => b"S",
+        });
+        hasher.update((label.len() as u32).to_be_bytes());
+        hasher.update(label.as_bytes());
+    }
+    hasher.finalize().into()
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::domain::padded_domain_separator;
+    use crate::poseidon2::Poseidon2;
+    use ark_bn254::Fr;
+
+    fn protocol(challenge: &str) -> IoPattern {
+        IoPattern::new()
+            .absorb_labeled("commitment", 2)
+            .squeeze_labeled(challenge, 1)
+            .absorb_labeled("response", 1)
+            .squeeze_labeled("beta", 2)
+    }
+
+    fn run(protocol: IoPattern) -> (Vec<Fr>, Vec<Fr>) {
+        let mut transcript =
+            Transcript::<Poseidon2>::new(protocol.clone(), padded_domain_separator(b"test"));
+        let challenge = protocol.label(1).unwrap().to_string();
+        transcript.append("commitment", &[Fr::from(1u64), Fr::from(2u64)]);
+        let alpha = transcript.challenge(&challenge, 1);
+        transcript.append("response", &[Fr::from(3u64)]);
+        let beta = transcript.challenge("beta", 2);
+        transcript.finish();
+        (alpha, beta)
+    }
+
+    #[test]
+    fn test_matches_the_sponge() {
+        let protocol = protocol("alpha");
+        let domain_separator =
+            transcript_domain_separator(&protocol, &padded_domain_separator(b"test"));
+        let mut sponge = Sponge::<Poseidon2>::start(protocol.clone(), domain_separator);
+        sponge.absorb(&[Fr::from(1u64), Fr::from(2u64)]);
+        let alpha = sponge.squeeze();
+        sponge.absorb(&[Fr::from(3u64)]);
+        let beta = sponge.squeeze();
+
+        assert_eq!(run(protocol), (alpha, beta));
+    }
+
+    #[test]
+    fn test_labels_separate_protocols() {
+        // Same shape and tag, different challenge name.
+        assert_eq!(
+            protocol("alpha").tag([0u8; 64]),
+            protocol("gamma").tag([0u8; 64])
+        );
+        assert_ne!(run(protocol("alpha")).0, run(protocol("gamma")).0);
+    }
+
+    #[test]
+    fn test_sequenced() {
+        let protocol = protocol("alpha");
+        let application = padded_domain_separator(b"test");
+        let start = || {
+            Sponge::<Poseidon2>::builder()
+                .sequenced()
+                .transcript(protocol.clone(), application)
+        };
+        let mut transcript = start();
+        transcript.append("commitment", &[Fr::from(1u64), Fr::from(2u64)]);
+        let alpha = transcript.challenge("alpha", 1);
+        transcript.append("response", &[Fr::from(3u64)]);
+        let beta = transcript.challenge("beta", 2);
+        transcript.finish();
+
+        // The sequence numbers are the indices of the messages in the protocol.
+        let domain_separator = sequenced_transcript_domain_separator(&protocol, &application);
+        let mut sponge = Sponge::<Poseidon2>::start(protocol.sequenced(), domain_separator);
+        sponge.absorb(&[Fr::from(0u64), Fr::from(1u64), Fr::from(2u64)]);
+        assert_eq!(alpha, sponge.squeeze());
+        sponge.absorb(&[Fr::from(2u64), Fr::from(3u64)]);
+        assert_eq!(beta, sponge.squeeze());
+        assert_ne!((alpha.clone(), beta.clone()), run(protocol.clone()));
+
+        // Saved states resume in the same mode.
+        let mut transcript = start();
+        transcript.append("commitment", &[Fr::from(1u64), Fr::from(2u64)]);
+        let mut resumed = Transcript::<Poseidon2>::resume(transcript.save(), application).unwrap();
+        assert_eq!(resumed.challenge("alpha", 1), alpha);
+        resumed.append("response", &[Fr::from(3u64)]);
+        assert_eq!(resumed.challenge("beta", 2), beta);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unexpected absorb label")]
+    fn test_wrong_message_panics() {
+        let mut transcript =
+            Transcript::<Poseidon2>::new(protocol("alpha"), padded_domain_separator(b"test"));
+        transcript.append("response", &[Fr::from(3u64), Fr::from(4u64)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unlabeled transcript operation")]
+    fn test_unlabeled_protocol_panics() {
+        let protocol = IoPattern::new().absorb_labeled("x", 1).squeeze(1);
+        Transcript::<Poseidon2>::new(protocol, padded_domain_separator(b"test"));
+    }
+}
@@ -0,0 +1,177 @@
+//! A sponge whose IO pattern is part of its type.
+//!
+//! For fixed protocols, the IO pattern can be spelled out as a type such as
+//! `Absorb<3, Squeeze<1, Done>>`. Each call consumes the sponge and returns it in the next
+//! state, so calling an operation the pattern does not expect, passing an input of the wrong
+//! length or forgetting to run the whole pattern before [`TypedSponge::finish`] is a compile
+//! error instead of a panic:
+//!
+//! ```
+//! use ark_bn254::Fr;
+//! use safe_api::domain::padded_domain_separator;
+//! use safe_api::poseidon2::Poseidon2;
+//! use safe_api::typed::{Absorb, Done, Squeeze, TypedSponge};
+//!
+//! type Hash3 = Absorb<3, Squeeze<1, Done>>;
+//!
+//! let domain_separator = padded_domain_separator(b"my-app/hash");
+//! let sponge = TypedSponge::<Poseidon2, Hash3>::start(&domain_separator);
+//! let sponge = sponge.absorb(&[Fr::from(1u64), Fr::from(2u64), Fr::from(3u64)]);
+//! let ([digest], sponge) = sponge.squeeze();
+//! sponge.finish();
+//! # let _ = digest;
+//! ```
+//!
+//! ```compile_fail
+//! # use safe_api::domain::padded_domain_separator;
+//! # use safe_api::poseidon2::Poseidon2;
+//! # use safe_api::typed::{Absorb, Done, Squeeze, TypedSponge};
+//! let domain_separator = padded_domain_separator(b"my-app/hash");
+//! let sponge = TypedSponge::<Poseidon2, Absorb<3, Squeeze<1, Done>>>::start(&domain_separator);
+//! sponge.squeeze(); // the pattern expects an ABSORB first
+//! ```
+//!
+//! The runtime [`Sponge`] remains the choice for patterns only known at run time; both produce
+//! the same outputs for the same pattern.
+
+use core::marker::PhantomData;
+
+use crate::io_pattern::IoPattern;
+use crate::permutation::Permutation;
+use crate::sponge::Sponge;
+
+/// An IO pattern encoded as a type.
+pub trait Pattern {
+    /// Appends the operations of this type to `pattern`.
+    fn append_to(pattern: IoPattern) -> IoPattern;
+
+    /// Returns the runtime IO pattern.
+    fn io_pattern() -> IoPattern {
+        Self::append_to(IoPattern::new())
+    }
+}
+
+/// ABSORB(`N`), followed by the pattern `Next`.
+pub struct Absorb<const N: usize, Next>(PhantomData<Next>);
+
+/// SQUEEZE(`N`), followed by the pattern `Next`.
+pub struct Squeeze<const N: usize, Next>(PhantomData<Next>);
+
+/// The end of a pattern.
+pub struct Done;
+
+impl<const N: usize, Next: Pattern> Pattern for Absorb<N, Next> {
+    fn append_to(pattern: IoPattern) -> IoPattern {
+        Next::append_to(pattern.absorb(N as u32))
+    }
+}
+
+impl<const N: usize, Next: Pattern> Pattern for Squeeze<N, Next> {
+    fn append_to(pattern: IoPattern) -> IoPattern {
+        Next::append_to(pattern.squeeze(N as u32))
+    }
+}
+
+impl Pattern for Done {
+    fn append_to(pattern: IoPattern) -> IoPattern {
+        pattern
+    }
+}
+
+/// A [`Sponge`] in the state where the remaining operations are `S`.
+pub struct TypedSponge<P: Permutation, S> {
+    sponge: Sponge<P>,
+    state: PhantomData<S>,
+}
+
+impl<P: Permutation + Default, S: Pattern> TypedSponge<P, S> {
+    /// Starts a sponge for the pattern `S` under `domain_separator`.
+    pub fn start(domain_separator: &[u8; 64]) -> Self {
+        Self::with_permutation(P::default(), domain_separator)
+    }
+}
+
+impl<P: Permutation, S: Pattern> TypedSponge<P, S> {
+    /// Starts a sponge for the pattern `S` running on the given permutation instance.
+    pub fn with_permutation(permutation: P, domain_separator: &[u8; 64]) -> Self {
+        TypedSponge {
+            sponge: Sponge::with_permutation(permutation, S::io_pattern(), domain_separator),
+            state: PhantomData,
+        }
+    }
+
+    /// Returns the tag T of this instance as a field element.
+    pub fn tag(&self) -> P::Field {
+        self.sponge.tag()
+    }
+}
+
+impl<P: Permutation, S> TypedSponge<P, S> {
+    fn advance<Next>(self) -> TypedSponge<P, Next> {
+        TypedSponge {
+            sponge: self.sponge,
+            state: PhantomData,
+        }
+    }
+}
+
+impl<P: Permutation, const N: usize, Next> TypedSponge<P, Absorb<N, Next>> {
+    /// Absorbs the `N` elements of the next operation.
+    pub fn absorb(mut self, input: &[P::Field; N]) -> TypedSponge<P, Next> {
+        self.sponge.absorb(input);
+        self.advance()
+    }
+}
+
+impl<P: Permutation, const N: usize, Next> TypedSponge<P, Squeeze<N, Next>> {
+    /// Squeezes the `N` elements of the next operation.
+    pub fn squeeze(mut self) -> ([P::Field; N], TypedSponge<P, Next>) {
+        let output: [P::Field; N] = self
+            .sponge
+            .squeeze()
+            .try_into()
+            .unwrap_or_else(|_| unreachable!("the pattern fixes the length"));
+        (output, self.advance())
+    }
+}
+
+impl<P: Permutation> TypedSponge<P, Done> {
+    /// Finalizes the sponge once the whole pattern has run, erasing the state.
+    pub fn finish(mut self) {
+        self.sponge.finish();
+    }
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::poseidon2::Poseidon2;
+    use ark_bn254::Fr;
+
+    type MerkleNode = Absorb<1, Absorb<1, Squeeze<1, Done>>>;
+
+    #[test]
+    fn test_pattern_encoding() {
+        assert_eq!(
+            MerkleNode::io_pattern(),
+            IoPattern::new().absorb(1).absorb(1).squeeze(1)
+        );
+    }
+
+    #[test]
+    fn test_matches_runtime_sponge() {
+        let mut domain_separator = [0u8; 64];
+        domain_separator[..4].copy_from_slice(b"ABCD");
+        let (left, right) = (Fr::from(123u64), Fr::from(456u64));
+
+        let sponge = TypedSponge::<Poseidon2, MerkleNode>::start(&domain_separator);
+        let sponge = sponge.absorb(&[left]).absorb(&[right]);
+        let (digest, sponge) = sponge.squeeze();
+        sponge.finish();
+
+        let mut runtime = Sponge::<Poseidon2>::start(MerkleNode::io_pattern(), domain_separator);
+        runtime.absorb(&[left]);
+        runtime.absorb(&[right]);
+        assert_eq!(runtime.squeeze(), digest.to_vec());
+    }
+}
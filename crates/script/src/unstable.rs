@@ -0,0 +1,10 @@
+//! Experimental modules, outside the semver guarantees of the crate.
+//!
+//! Everything here may change or disappear in any release, including patch releases. The
+//! module is hidden from the documentation and only exists with the `unstable` feature, which
+//! the features of the individual modules enable. Modules move to the crate root once their
+//! format is settled; protocols whose outputs must stay reproducible should not depend on them
+//! before that.
+
+#[cfg(feature = "erasure")]
+pub mod erasure;
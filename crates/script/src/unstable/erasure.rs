@@ -0,0 +1,415 @@
+//! Reed–Solomon extended commitments for data availability sampling.
+//!
+//! The data is packed into field elements as in [`encode_bytes`] (length first) and laid out
+//! in `k` rows of `width` elements, `k` a power of two, zero padded. Each column is read as the
+//! evaluations of a polynomial of degree below `k` over the subgroup of order `k`, and extended
+//! by evaluating it over the subgroup of order `n = k * expansion`. Extended row `j` is the
+//! evaluation at `ω^j` (for `ω` of order `n`), so original row `i` is extended row
+//! `i * expansion`, and any `k` rows are enough to [`reconstruct`] the data.
+//!
+//! Each extended row is hashed into a leaf, ABSORB(`width`) SQUEEZE(1), and the leaves into a
+//! [`MerkleTree`] under [`NODE_LABEL`]. The commitment is
+//! ABSORB(5) SQUEEZE(1) over `(root, byte_len, width, k, n)`. A sampler checks a random row
+//! with its [`RowProof`] and [`verify_row`].
+//!
+//! Reconstruction interpolates in `O(k^2)` per column, which is fine for prototypes but not
+//! for large `k`.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use ark_ff::{BigInteger, FftField, Field, PrimeField, Zero};
+use ark_poly::{EvaluationDomain, Radix2EvaluationDomain};
+
+use crate::chunked::domain_separator;
+use crate::encoding::{bytes_per_element, encode_bytes};
+use crate::io_pattern::IoPattern;
+use crate::merkle::{MerkleProof, MerkleTree};
+use crate::permutation::Permutation;
+use crate::sponge::Sponge;
+
+/// Label of the domain separator of the row leaves.
+pub const ROW_LABEL: &[u8] = b"safe-api/erasure/v1/row";
+
+/// Label of the domain separator of the Merkle nodes.
+pub const NODE_LABEL: &[u8] = b"safe-api/erasure/v1/node";
+
+/// Label of the domain separator of the final commitment.
+pub const ROOT_LABEL: &[u8] = b"safe-api/erasure/v1/root";
+
+/// The shape of an extended commitment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ErasureParams {
+    /// Field elements per row.
+    pub width: usize,
+    /// Ratio between the number of extended and original rows, a power of two of at least 2.
+    pub expansion: usize,
+}
+
+impl ErasureParams {
+    /// Returns the number of original rows `k` for `byte_len` bytes of data.
+    pub fn data_rows<F: PrimeField>(&self, byte_len: usize) -> usize {
+        let elements = 1 + byte_len.div_ceil(bytes_per_element::<F>());
+        elements.div_ceil(self.width).next_power_of_two()
+    }
+
+    fn check(&self) {
+        assert!(self.width > 0, "Row width must be positive");
+        assert!(
+            self.expansion >= 2 && self.expansion.is_power_of_two(),
+            "Expansion must be a power of two of at least 2"
+        );
+    }
+}
+
+/// IO pattern of a row leaf.
+pub fn row_pattern(width: usize) -> IoPattern {
+    IoPattern::new().absorb(width as u32).squeeze(1)
+}
+
+/// IO pattern of the commitment: the root, the length, the width and both row counts.
+pub fn root_pattern() -> IoPattern {
+    IoPattern::new().absorb(5).squeeze(1)
+}
+
+fn hash_row<P: Permutation + Default>(row: &[P::Field]) -> P::Field {
+    let mut sponge = Sponge::<P>::start(row_pattern(row.len()), domain_separator(ROW_LABEL));
+    sponge.absorb(row);
+    let leaf = sponge.squeeze()[0];
+    sponge.finish();
+    leaf
+}
+
+fn hash_root<P: Permutation + Default>(
+    root: P::Field,
+    byte_len: u64,
+    width: usize,
+    data_rows: usize,
+    extended_rows: usize,
+) -> P::Field {
+    let mut sponge = Sponge::<P>::start(root_pattern(), domain_separator(ROOT_LABEL));
+    sponge.absorb(&[
+        root,
+        P::Field::from(byte_len),
+        P::Field::from(width as u64),
+        P::Field::from(data_rows as u64),
+        P::Field::from(extended_rows as u64),
+    ]);
+    let commitment = sponge.squeeze()[0];
+    sponge.finish();
+    commitment
+}
+
+/// An extended commitment with its rows and Merkle tree, see the
+/// [module documentation](self).
+#[derive(Clone, Debug)]
+pub struct ErasureCommitment<P: Permutation> {
+    params: ErasureParams,
+    byte_len: u64,
+    data_rows: usize,
+    rows: Vec<Vec<P::Field>>,
+    tree: MerkleTree<P>,
+    commitment: P::Field,
+}
+
+impl<P: Permutation + Default> ErasureCommitment<P>
+where
+    P::Field: FftField,
+{
+    /// Extends and commits to `data`.
+    ///
+    /// # Panics
+    /// If `params` is invalid, or if the field has no subgroup of order `n`.
+    pub fn commit(data: &[u8], params: ErasureParams) -> Self {
+        params.check();
+        let data_rows = params.data_rows::<P::Field>(data.len());
+        let extended_rows = data_rows * params.expansion;
+        let small = Radix2EvaluationDomain::<P::Field>::new(data_rows)
+            .expect("Field has no subgroup of this order");
+        let large = Radix2EvaluationDomain::<P::Field>::new(extended_rows)
+            .expect("Field has no subgroup of this order");
+
+        let mut elements = encode_bytes::<P::Field>(data);
+        elements.resize(data_rows * params.width, P::Field::zero());
+
+        let mut rows = vec![Vec::with_capacity(params.width); extended_rows];
+        for column in 0..params.width {
+            let values: Vec<P::Field> = (0..data_rows)
+                .map(|row| elements[row * params.width + column])
+                .collect();
+            let extended = large.fft(&small.ifft(&values));
+            for (row, value) in rows.iter_mut().zip(extended) {
+                row.push(value);
+            }
+        }
+
+        let leaves = rows.iter().map(|row| hash_row::<P>(row)).collect();
+        let tree = MerkleTree::new(leaves, &domain_separator(NODE_LABEL));
+        ErasureCommitment {
+            params,
+            byte_len: data.len() as u64,
+            data_rows,
+            commitment: hash_root::<P>(
+                tree.root(),
+                data.len() as u64,
+                params.width,
+                data_rows,
+                extended_rows,
+            ),
+            rows,
+            tree,
+        }
+    }
+}
+
+impl<P: Permutation> ErasureCommitment<P> {
+    /// Returns the commitment.
+    pub fn commitment(&self) -> P::Field {
+        self.commitment
+    }
+
+    /// Returns the shape of the commitment.
+    pub fn params(&self) -> ErasureParams {
+        self.params
+    }
+
+    /// Returns the length of the committed data in bytes.
+    pub fn byte_len(&self) -> u64 {
+        self.byte_len
+    }
+
+    /// Returns the number of original rows `k`.
+    pub fn data_rows(&self) -> usize {
+        self.data_rows
+    }
+
+    /// Returns the number of extended rows `n`.
+    pub fn extended_rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Returns extended row `index`.
+    pub fn row(&self, index: usize) -> &[P::Field] {
+        &self.rows[index]
+    }
+
+    /// Returns the proof of extended row `index`, e.g. for a sampled index.
+    ///
+    /// # Panics
+    /// If there is no row `index`.
+    pub fn open(&self, index: usize) -> RowProof<P::Field> {
+        assert!(index < self.rows.len(), "Row index out of bounds");
+        RowProof {
+            row: self.rows[index].clone(),
+            path: self.tree.open(index),
+        }
+    }
+}
+
+/// An extended row with its Merkle path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RowProof<F> {
+    pub row: Vec<F>,
+    pub path: MerkleProof<F>,
+}
+
+/// Checks that `proof.row` is extended row `proof.path.index` of the commitment to `byte_len` bytes
+/// with the shape `params`.
+pub fn verify_row<P: Permutation + Default>(
+    commitment: P::Field,
+    byte_len: u64,
+    params: ErasureParams,
+    proof: &RowProof<P::Field>,
+) -> bool {
+    if params.width == 0 || params.expansion < 2 || !params.expansion.is_power_of_two() {
+        return false;
+    }
+    let data_rows = params.data_rows::<P::Field>(byte_len as usize);
+    let extended_rows = data_rows * params.expansion;
+    if proof.path.index >= extended_rows
+        || proof.row.len() != params.width
+        || proof.path.siblings.len() as u32 != extended_rows.trailing_zeros()
+    {
+        return false;
+    }
+
+    let leaf = hash_row::<P>(&proof.row);
+    let root = proof.path.root::<P>(&domain_separator(NODE_LABEL), leaf);
+    hash_root::<P>(root, byte_len, params.width, data_rows, extended_rows) == commitment
+}
+
+/// Recovers the data of `byte_len` bytes from any `k` distinct extended rows, given as
+/// `(index, row)`. Returns `None` if fewer than `k` distinct rows are given or if they do not
+/// decode to data of `byte_len` bytes; rows should be checked with [`verify_row`] first.
+pub fn reconstruct<F: PrimeField + FftField>(
+    rows: &[(usize, Vec<F>)],
+    byte_len: usize,
+    params: ErasureParams,
+) -> Option<Vec<u8>> {
+    params.check();
+    let data_rows = params.data_rows::<F>(byte_len);
+    let extended_rows = data_rows * params.expansion;
+    let large = Radix2EvaluationDomain::<F>::new(extended_rows)?;
+
+    let mut sources: Vec<&(usize, Vec<F>)> = Vec::with_capacity(data_rows);
+    for row in rows {
+        if row.0 >= extended_rows || row.1.len() != params.width {
+            return None;
+        }
+        if sources.len() < data_rows && sources.iter().all(|source| source.0 != row.0) {
+            sources.push(row);
+        }
+    }
+    if sources.len() < data_rows {
+        return None;
+    }
+
+    // Lagrange coefficients from the source points to each original point.
+    let points: Vec<F> = sources
+        .iter()
+        .map(|source| large.element(source.0))
+        .collect();
+    let coefficients: Vec<Vec<F>> = (0..data_rows)
+        .map(|row| lagrange_coefficients(&points, large.element(row * params.expansion)))
+        .collect();
+
+    let mut elements: Vec<F> = Vec::with_capacity(data_rows * params.width);
+    for row_coefficients in &coefficients {
+        for column in 0..params.width {
+            elements.push(
+                row_coefficients
+                    .iter()
+                    .zip(&sources)
+                    .map(|(&coefficient, source)| coefficient * source.1[column])
+                    .sum(),
+            );
+        }
+    }
+    decode_bytes(&elements, byte_len)
+}
+
+/// Returns the weights `L_j(x)` such that `p(x) = sum_j L_j(x) p(points[j])`.
+fn lagrange_coefficients<F: Field>(points: &[F], x: F) -> Vec<F> {
+    if let Some(position) = points.iter().position(|&point| point == x) {
+        let mut coefficients = vec![F::zero(); points.len()];
+        coefficients[position] = F::one();
+        return coefficients;
+    }
+    points
+        .iter()
+        .enumerate()
+        .map(|(j, &point)| {
+            let (numerator, denominator) = points.iter().enumerate().filter(|&(m, _)| m != j).fold(
+                (F::one(), F::one()),
+                |(numerator, denominator), (_, &other)| {
+                    (numerator * (x - other), denominator * (point - other))
+                },
+            );
+            numerator * denominator.inverse().expect("points are distinct")
+        })
+        .collect()
+}
+
+/// Inverts [`encode_bytes`] on zero padded elements.
+fn decode_bytes<F: PrimeField>(elements: &[F], byte_len: usize) -> Option<Vec<u8>> {
+    if elements.first() != Some(&F::from(byte_len as u64)) {
+        return None;
+    }
+    let chunk_size = bytes_per_element::<F>();
+    let mut bytes = Vec::with_capacity(byte_len);
+    for element in &elements[1..1 + byte_len.div_ceil(chunk_size)] {
+        let length = (byte_len - bytes.len()).min(chunk_size);
+        let digits = element.into_bigint().to_bytes_be();
+        if digits[..digits.len() - length]
+            .iter()
+            .any(|&byte| byte != 0)
+        {
+            return None;
+        }
+        bytes.extend_from_slice(&digits[digits.len() - length..]);
+    }
+    Some(bytes)
+}
+
+#[cfg(all(test, feature = "poseidon2"))]
+mod tests {
+    use super::*;
+    use crate::poseidon2::Poseidon2;
+    use ark_ff::One;
+
+    const PARAMS: ErasureParams = ErasureParams {
+        width: 4,
+        expansion: 2,
+    };
+
+    fn data(length: usize) -> Vec<u8> {
+        (0..length).map(|i| (i * 31 % 256) as u8).collect()
+    }
+
+    #[test]
+    fn test_extension_is_systematic() {
+        let data = data(400);
+        let commitment = ErasureCommitment::<Poseidon2>::commit(&data, PARAMS);
+        // 1 + ceil(400 / 31) = 14 elements in rows of 4.
+        assert_eq!(commitment.data_rows(), 4);
+        assert_eq!(commitment.extended_rows(), 8);
+
+        let mut elements = encode_bytes::<ark_bn254::Fr>(&data);
+        elements.resize(16, ark_bn254::Fr::zero());
+        for row in 0..4 {
+            assert_eq!(commitment.row(row * 2), &elements[row * 4..row * 4 + 4]);
+        }
+    }
+
+    #[test]
+    fn test_sampling() {
+        let data = data(400);
+        let commitment = ErasureCommitment::<Poseidon2>::commit(&data, PARAMS);
+        for index in 0..commitment.extended_rows() {
+            let proof = commitment.open(index);
+            assert!(verify_row::<Poseidon2>(
+                commitment.commitment(),
+                400,
+                PARAMS,
+                &proof
+            ));
+        }
+
+        let mut forged = commitment.open(3);
+        forged.row[0] += ark_bn254::Fr::one();
+        assert!(!verify_row::<Poseidon2>(
+            commitment.commitment(),
+            400,
+            PARAMS,
+            &forged
+        ));
+        assert!(!verify_row::<Poseidon2>(
+            commitment.commitment(),
+            401,
+            PARAMS,
+            &commitment.open(3)
+        ));
+    }
+
+    #[test]
+    fn test_reconstruct_from_any_half() {
+        let data = data(400);
+        let commitment = ErasureCommitment::<Poseidon2>::commit(&data, PARAMS);
+        let rows = |indices: &[usize]| -> Vec<(usize, Vec<ark_bn254::Fr>)> {
+            indices
+                .iter()
+                .map(|&index| (index, commitment.row(index).to_vec()))
+                .collect()
+        };
+
+        assert_eq!(
+            reconstruct(&rows(&[1, 3, 5, 7]), 400, PARAMS),
+            Some(data.clone())
+        );
+        assert_eq!(
+            reconstruct(&rows(&[6, 0, 5, 2]), 400, PARAMS),
+            Some(data.clone())
+        );
+        assert_eq!(reconstruct(&rows(&[1, 3, 5, 5]), 400, PARAMS), None);
+    }
+}
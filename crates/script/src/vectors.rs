@@ -0,0 +1,355 @@
+//! JSON test vectors for the tag computation, shared with the Noir implementation.
+//!
+//! Each vector records the input (IO pattern words and domain separator) together with every
+//! intermediate value of SAFE spec 2.3, so that an implementation that diverges can tell at
+//! which stage it does:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "vectors": [
+//!     {
+//!       "name": "absorb3_squeeze1",
+//!       "io_pattern": ["0x80000003", "0x00000001"],
+//!       "domain_separator": "0x41424344000000...",
+//!       "aggregated_words": ["0x80000003", "0x00000001"],
+//!       "preimage": "0x800000030000000141424344000000...",
+//!       "digest": "0x0ea2aa7e178caa74de1f91e83ad43a81...",
+//!       "tag": "0x0ea2aa7e178caa74de1f91e83ad43a81"
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! All numbers are 0x-prefixed, lowercase, fixed-width hex strings.
+
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::io_pattern::IoPattern;
+use crate::tag::Tag;
+
+/// Version of the JSON schema written by [`to_json`].
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A tag computation with all its intermediate values.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestVector {
+    /// Free-form identifier of the vector.
+    pub name: String,
+    /// Input IO pattern, one encoded word per operation.
+    #[serde(with = "hex_words")]
+    pub io_pattern: Vec<u32>,
+    /// Input domain separator.
+    #[serde(with = "hex_array")]
+    pub domain_separator: [u8; 64],
+    /// Step 1: the pattern after aggregation of consecutive operations.
+    #[serde(with = "hex_words")]
+    pub aggregated_words: Vec<u32>,
+    /// Step 2: the serialized words followed by the domain separator.
+    #[serde(with = "hex_bytes")]
+    pub preimage: Vec<u8>,
+    /// Step 3: the SHA-256 digest of the preimage.
+    #[serde(with = "hex_array")]
+    pub digest: [u8; 32],
+    /// Step 4: the first 128 bits of the digest.
+    pub tag: Tag,
+}
+
+/// The stage of the tag computation at which a vector disagrees with this implementation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// The aggregated words differ.
+    Aggregation,
+    /// The serialized preimage differs.
+    Serialization,
+    /// The SHA-256 digest differs.
+    Hash,
+    /// The truncated tag differs.
+    Truncation,
+}
+
+/// Errors raised while loading or verifying test vectors.
+#[derive(Debug)]
+pub enum VectorError {
+    /// The JSON document is malformed or does not follow the schema.
+    Json(serde_json::Error),
+    /// The document was written with an unsupported schema version.
+    UnsupportedVersion(u32),
+    /// A vector does not match the value recomputed at the given stage.
+    Mismatch {
+        /// Name of the offending vector.
+        name: String,
+        /// First stage that diverges.
+        stage: Stage,
+    },
+}
+
+impl fmt::Display for VectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VectorError::Json(error) => write!(f, "invalid test vector JSON: {}", error),
+            VectorError::UnsupportedVersion(version) => {
+                write!(f, "unsupported test vector schema version {}", version)
+            }
+            VectorError::Mismatch { name, stage } => {
+                write!(f, "vector `{}` diverges at the {:?} stage", name, stage)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for VectorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VectorError::Json(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for VectorError {
+    fn from(error: serde_json::Error) -> Self {
+        VectorError::Json(error)
+    }
+}
+
+impl TestVector {
+    /// Computes a vector, with all intermediate values, for a pattern and domain separator.
+    pub fn compute(name: &str, io_pattern: &IoPattern, domain_separator: &[u8; 64]) -> Self {
+        let breakdown = io_pattern.tag_breakdown(domain_separator);
+        TestVector {
+            name: name.to_string(),
+            io_pattern: breakdown.words,
+            domain_separator: *domain_separator,
+            aggregated_words: breakdown.aggregated_words,
+            preimage: breakdown.preimage,
+            digest: breakdown.digest,
+            tag: breakdown.tag,
+        }
+    }
+
+    /// Recomputes every stage from the inputs and reports the first one that differs.
+    pub fn verify(&self) -> Result<(), VectorError> {
+        let expected = TestVector::compute(
+            &self.name,
+            &IoPattern::from_words(&self.io_pattern),
+            &self.domain_separator,
+        );
+        let stage = if expected.aggregated_words != self.aggregated_words {
+            Stage::Aggregation
+        } else if expected.preimage != self.preimage {
+            Stage::Serialization
+        } else if expected.digest != self.digest {
+            Stage::Hash
+        } else if expected.tag != self.tag {
+            Stage::Truncation
+        } else {
+            return Ok(());
+        };
+
+        Err(VectorError::Mismatch {
+            name: self.name.clone(),
+            stage,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct VectorFile {
+    version: u32,
+    vectors: Vec<TestVector>,
+}
+
+/// Serializes vectors to the JSON schema (pretty printed).
+pub fn to_json(vectors: &[TestVector]) -> String {
+    let file = VectorFile {
+        version: SCHEMA_VERSION,
+        vectors: vectors.to_vec(),
+    };
+    serde_json::to_string_pretty(&file).expect("test vectors always serialize")
+}
+
+/// Loads vectors from the JSON schema, without verifying them.
+pub fn from_json(json: &str) -> Result<Vec<TestVector>, VectorError> {
+    let file: VectorFile = serde_json::from_str(json)?;
+    if file.version != SCHEMA_VERSION {
+        return Err(VectorError::UnsupportedVersion(file.version));
+    }
+    Ok(file.vectors)
+}
+
+/// Loads vectors from JSON and verifies each of them against this implementation.
+pub fn verify_json(json: &str) -> Result<Vec<TestVector>, VectorError> {
+    let vectors = from_json(json)?;
+    for vector in &vectors {
+        vector.verify()?;
+    }
+    Ok(vectors)
+}
+
+/// The reference corpus: the cases of the Noir test suite and of the original demo script.
+pub fn reference_vectors() -> Vec<TestVector> {
+    let mut abcd = [0u8; 64];
+    abcd[..4].copy_from_slice(b"ABCD");
+    let mut bcde = [0u8; 64];
+    bcde[..4].copy_from_slice(b"BCDE");
+    let mut ab = [0u8; 64];
+    ab[..2].copy_from_slice(b"AB");
+
+    vec![
+        TestVector::compute(
+            "absorb3_squeeze1",
+            &IoPattern::new().absorb(3).squeeze(1),
+            &abcd,
+        ),
+        TestVector::compute(
+            "merkle_node",
+            &IoPattern::new().absorb(1).absorb(1).squeeze(1),
+            &abcd,
+        ),
+        TestVector::compute(
+            "absorb2_squeeze1",
+            &IoPattern::new().absorb(2).squeeze(1),
+            &abcd,
+        ),
+        TestVector::compute(
+            "absorb3_squeeze2",
+            &IoPattern::new().absorb(3).squeeze(2),
+            &abcd,
+        ),
+        TestVector::compute(
+            "absorb0_squeeze1",
+            &IoPattern::new().absorb(0).squeeze(1),
+            &abcd,
+        ),
+        TestVector::compute(
+            "absorb3_squeeze1_bcde",
+            &IoPattern::new().absorb(3).squeeze(1),
+            &bcde,
+        ),
+        TestVector::compute(
+            "spec_aggregation",
+            &IoPattern::new().absorb(3).absorb(3).squeeze(3),
+            &ab,
+        ),
+        TestVector::compute(
+            "absorb_squeeze_absorb",
+            &IoPattern::new().absorb(2).squeeze(2).absorb(2),
+            &abcd,
+        ),
+    ]
+}
+
+/// Fixed-width 0x hex encodings used by the schema.
+mod hex {
+    use crate::hex::Mode;
+
+    pub fn encode(bytes: &[u8]) -> String {
+        crate::hex::encode(bytes)
+    }
+
+    pub fn decode(input: &str) -> Result<Vec<u8>, String> {
+        crate::hex::decode(input, Mode::Strict).map_err(|error| format!("`{}`: {}", input, error))
+    }
+}
+
+mod hex_bytes {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        super::hex::decode(&String::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+mod hex_array {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer, const N: usize>(
+        bytes: &[u8; N],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<[u8; N], D::Error> {
+        let bytes =
+            super::hex::decode(&String::deserialize(deserializer)?).map_err(D::Error::custom)?;
+        let length = bytes.len();
+        bytes
+            .try_into()
+            .map_err(|_| D::Error::custom(format!("expected {} bytes, got {}", N, length)))
+    }
+}
+
+pub(crate) mod hex_words {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(words: &[u32], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(words.iter().map(|word| format!("0x{:08x}", word)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u32>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|word| {
+                let bytes = super::hex::decode(word).map_err(D::Error::custom)?;
+                let bytes: [u8; 4] = bytes
+                    .try_into()
+                    .map_err(|_| D::Error::custom(format!("`{}` is not a 32-bit word", word)))?;
+                Ok(u32::from_be_bytes(bytes))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let vectors = reference_vectors();
+        let json = to_json(&vectors);
+        assert_eq!(verify_json(&json).unwrap(), vectors);
+    }
+
+    #[test]
+    fn test_committed_vectors() {
+        // The committed file must match the reference corpus; regenerate it with
+        // `safe-api vectors export > vectors/tags.json`.
+        let json = include_str!("../vectors/tags.json");
+        assert_eq!(verify_json(json).unwrap(), reference_vectors());
+    }
+
+    #[test]
+    fn test_reports_divergent_stage() {
+        let mut vector = reference_vectors().remove(1);
+        vector.aggregated_words = vec![0x80000001, 0x80000001, 0x00000001];
+        assert!(matches!(
+            vector.verify(),
+            Err(VectorError::Mismatch {
+                stage: Stage::Aggregation,
+                ..
+            })
+        ));
+
+        let mut vector = reference_vectors().remove(0);
+        vector.tag = Tag::from(vector.tag.to_u128().unwrap() ^ 1);
+        assert!(matches!(
+            vector.verify(),
+            Err(VectorError::Mismatch {
+                stage: Stage::Truncation,
+                ..
+            })
+        ));
+    }
+}
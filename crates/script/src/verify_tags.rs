@@ -0,0 +1,413 @@
+//! Audit of the tag constants of a Noir project.
+//!
+//! A tag pasted into a circuit goes stale when its IO pattern or domain separator changes.
+//! `safe-api verify-tags` scans the `.nr` files of a project for tag constants annotated with
+//! the pattern and domain separator they were computed from, recomputes each tag, and reports
+//! every mismatch with its file and line:
+//!
+//! ```noir
+//! // safe-api: pattern = "A1 A1 S1", domain = "0x41424344"
+//! global TAG_MERKLE_NODE: Field = 0x08e2da1eb5257f918e9c15b5605a3516;
+//! ```
+//!
+//! The annotation is a line comment directly above the `global`. The constants emitted by
+//! `codegen noir`, whose doc comment carries the domain separator and whose `IO_PATTERN_`
+//! global precedes the tag, are verified without annotation.
+//!
+//! A tag of another length than 128 bits is checked at its length: a `[u8; N]` global holds
+//! the first `N` bytes of the digest (`[u8; 32]` for the whole digest), and a `Field` of 16 or
+//! 62 hex digits a 64- or 248-bit tag, as `codegen noir` emits them. An annotation can also
+//! give the length, e.g. `tag_length = "64"`, for a tag written without its leading zeros.
+
+use std::path::{Path, PathBuf};
+
+use ark_bn254::Fr;
+use safe_api::display;
+use safe_api::secret::ct_eq;
+use safe_api::tag::{Tag, TagLength};
+
+use crate::{parse_domain, parse_pattern};
+
+/// Prefix of the annotation comments.
+const ANNOTATION: &str = "// safe-api:";
+
+/// The tag constants checked in a project, and the problems found.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Number of tag constants checked.
+    pub checked: usize,
+    /// The problems, as `path:line: message`.
+    pub findings: Vec<String>,
+}
+
+/// Scans every `.nr` file under `root`, skipping hidden and `target` directories.
+pub fn verify_project(root: &Path) -> Result<Report, String> {
+    let mut files = Vec::new();
+    collect_sources(root, &mut files)?;
+    files.sort();
+    let mut report = Report::default();
+    for path in files {
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|error| format!("cannot read {}: {}", path.display(), error))?;
+        let display_path = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .display()
+            .to_string();
+        verify_source(&display_path, &contents, &mut report);
+    }
+    Ok(report)
+}
+
+fn collect_sources(directory: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = std::fs::read_dir(directory)
+        .map_err(|error| format!("cannot read {}: {}", directory.display(), error))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|error| format!("cannot read {}: {}", directory.display(), error))?
+            .path();
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("");
+        if path.is_dir() {
+            if !name.starts_with('.') && name != "target" {
+                collect_sources(&path, files)?;
+            }
+        } else if path.extension().is_some_and(|extension| extension == "nr") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The pattern, domain separator and optional tag length of an annotation.
+type Annotation = (String, String, Option<TagLength>);
+
+/// What the lines above a `global` say about it.
+#[derive(Default)]
+struct Pending {
+    /// An annotation, with its line.
+    annotation: Option<(Annotation, usize)>,
+    /// Domain separator of a generated doc comment.
+    generated_domain: Option<String>,
+    /// Pattern of a generated `IO_PATTERN_` global, as words.
+    generated_pattern: Option<String>,
+}
+
+/// Checks the tag constants of the source `contents` of `path`.
+pub fn verify_source(path: &str, contents: &str, report: &mut Report) {
+    let mut pending = Pending::default();
+    for (index, line) in contents.lines().enumerate() {
+        let number = index + 1;
+        let line = line.trim();
+        let finding = |message: String| format!("{}:{}: {}", path, number, message);
+
+        if let Some(annotation) = line.strip_prefix(ANNOTATION) {
+            match parse_annotation(annotation) {
+                Ok(annotation) => pending.annotation = Some((annotation, number)),
+                Err(error) => report.findings.push(finding(error)),
+            }
+            continue;
+        }
+        if let Some(domain) = generated_domain(line) {
+            pending.generated_domain = Some(domain.to_string());
+            continue;
+        }
+        let Some((name, ty, value)) = parse_global(line) else {
+            if !line.is_empty() && !line.starts_with("//") {
+                if let Some((_, annotated)) = pending.annotation.take() {
+                    report.findings.push(format!(
+                        "{}:{}: the annotation is not followed by a global",
+                        path, annotated
+                    ));
+                }
+                pending = Pending::default();
+            }
+            continue;
+        };
+
+        if ty.starts_with("[u32;") && pending.generated_domain.is_some() {
+            pending.generated_pattern = Some(value.trim_matches(['[', ']']).replace(',', " "));
+            continue;
+        }
+        let source = match pending.annotation.take() {
+            Some((annotation, _)) => Some(annotation),
+            None => pending
+                .generated_pattern
+                .take()
+                .zip(pending.generated_domain.take())
+                .map(|(pattern, domain)| (pattern, domain, None)),
+        };
+        pending = Pending::default();
+        let Some((pattern, domain, length)) = source else {
+            continue;
+        };
+        report.checked += 1;
+        if let Err(error) = check_tag(ty, value, &pattern, &domain, length) {
+            report
+                .findings
+                .push(finding(format!("{}: {}", name, error)));
+        }
+    }
+    if let Some((_, annotated)) = pending.annotation {
+        report.findings.push(format!(
+            "{}:{}: the annotation is not followed by a global",
+            path, annotated
+        ));
+    }
+}
+
+/// Parses `pattern = "...", domain = "..."`, optionally followed by `tag_length = "..."`.
+fn parse_annotation(annotation: &str) -> Result<Annotation, String> {
+    let mut pattern = None;
+    let mut domain = None;
+    let mut length = None;
+    for pair in annotation.split(',') {
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("malformed annotation `{}`", annotation.trim()))?;
+        let value = value
+            .trim()
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .ok_or_else(|| format!("the value of `{}` is not quoted", key.trim()))?;
+        match key.trim() {
+            "pattern" => pattern = Some(value.to_string()),
+            "domain" => domain = Some(value.to_string()),
+            "tag_length" => {
+                length = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .and_then(TagLength::from_bits)
+                        .ok_or_else(|| {
+                            format!("`tag_length` must be 64, 128, 248 or 256, not {}", value)
+                        })?,
+                )
+            }
+            key => return Err(format!("unknown annotation key `{}`", key)),
+        }
+    }
+    match (pattern, domain) {
+        (Some(pattern), Some(domain)) => Ok((pattern, domain, length)),
+        _ => Err("the annotation needs a `pattern` and a `domain`".to_string()),
+    }
+}
+
+/// Returns the domain separator of a doc comment emitted by `codegen noir`.
+fn generated_domain(line: &str) -> Option<&str> {
+    let (_, rest) = line
+        .strip_prefix("/// `")?
+        .split_once("`, domain separator ")?;
+    rest.strip_suffix(" (zero padded).")
+}
+
+/// Splits `[pub] global NAME: TYPE = VALUE;` into its name, type and value.
+fn parse_global(line: &str) -> Option<(&str, &str, &str)> {
+    let declaration = line.strip_prefix("pub ").unwrap_or(line);
+    let declaration = declaration.strip_prefix("global ")?;
+    let (name, rest) = declaration.split_once(':')?;
+    let (ty, value) = rest.split_once('=')?;
+    Some((
+        name.trim(),
+        ty.trim(),
+        value.trim().strip_suffix(';')?.trim(),
+    ))
+}
+
+/// Checks that `value` is the tag of `pattern` under `domain`, of `length` if given or else
+/// of the length of its type and value.
+fn check_tag(
+    ty: &str,
+    value: &str,
+    pattern: &str,
+    domain: &str,
+    length: Option<TagLength>,
+) -> Result<(), String> {
+    let byte_len = match ty.strip_prefix("[u8;").and_then(|ty| ty.strip_suffix(']')) {
+        Some(byte_len) => Some(
+            byte_len
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| format!("invalid tag type `{}`", ty))?,
+        ),
+        None if ty == "Field" => None,
+        None => return Err(format!("a tag is a `Field` or a `[u8; N]`, not a `{}`", ty)),
+    };
+    let length = match (length, byte_len) {
+        (Some(length), _) => length,
+        (None, Some(byte_len)) => TagLength::from_bits(8 * byte_len)
+            .ok_or_else(|| format!("a tag has 8, 16, 31 or 32 bytes, not {}", byte_len))?,
+        (None, None) => match value.trim_start_matches("0x").len() {
+            16 => TagLength::Bits64,
+            62 => TagLength::Bits248,
+            _ => TagLength::default(),
+        },
+    };
+    let io_pattern = parse_pattern(pattern)?;
+    let domain_separator = parse_domain(domain)?;
+    let expected = io_pattern.tag_with_length(domain_separator, length);
+    let matches = match byte_len {
+        Some(byte_len) => {
+            if byte_len != length.bytes() {
+                return Err(format!(
+                    "a tag of {} bits has {} bytes, not {}",
+                    length.bits(),
+                    length.bytes(),
+                    byte_len
+                ));
+            }
+            parse_bytes(value)
+                .and_then(|bytes| Tag::from_bytes(&bytes))
+                .ok_or_else(|| format!("invalid tag `{}`", value))?
+                == expected
+        }
+        None => {
+            let found = display::parse_element::<Fr>(value)
+                .map_err(|error| format!("invalid tag `{}`: {}", value, error))?;
+            ct_eq(&found, &expected.to_field::<Fr>())
+        }
+    };
+    if !matches {
+        return Err(format!(
+            "stale tag {}, expected {} for `{}` under {}",
+            value,
+            display::format_tag(expected),
+            pattern,
+            domain
+        ));
+    }
+    Ok(())
+}
+
+/// Parses a byte array literal such as `[0x12, 0x34]`.
+fn parse_bytes(value: &str) -> Option<Vec<u8>> {
+    value
+        .strip_prefix('[')?
+        .strip_suffix(']')?
+        .split(',')
+        .map(|byte| {
+            let byte = byte.trim();
+            match byte.strip_prefix("0x") {
+                Some(hex) => u8::from_str_radix(hex, 16).ok(),
+                None => byte.parse().ok(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verify(contents: &str) -> Report {
+        let mut report = Report::default();
+        verify_source("src/tags.nr", contents, &mut report);
+        report
+    }
+
+    #[test]
+    fn test_annotated() {
+        let report = verify(
+            "// safe-api: pattern = \"A3 S1\", domain = \"0x41424344\"\n\
+             global TAG_HASHING: Field = 0x0ea2aa7e178caa74de1f91e83ad43a81;\n\
+             \n\
+             // safe-api: pattern = \"A3 S2\", domain = \"0x41424344\"\n\
+             /// A doc comment between the annotation and the global.\n\
+             pub global TAG_STALE: Field = 0x0ea2aa7e178caa74de1f91e83ad43a81;\n\
+             global UNRELATED: Field = 7;\n",
+        );
+        assert_eq!(report.checked, 2);
+        assert_eq!(
+            report.findings,
+            [
+                "src/tags.nr:6: TAG_STALE: stale tag 0x0ea2aa7e178caa74de1f91e83ad43a81, \
+              expected 0x4b73794cad2d984072943f91b69f20a0 for `A3 S2` under 0x41424344"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_annotations() {
+        let report = verify(
+            "// safe-api: pattern = \"A3 S1\"\n\
+             global A: Field = 1;\n\
+             // safe-api: pattern = \"A3 S1\", domain = \"0x41424344\"\n\
+             fn main() {}\n\
+             // safe-api: pattern = \"A3 S1\", domain = \"0x41424344\"\n\
+             global B: u32 = 1;\n",
+        );
+        assert_eq!(report.checked, 1);
+        assert_eq!(
+            report.findings,
+            [
+                "src/tags.nr:1: the annotation needs a `pattern` and a `domain`",
+                "src/tags.nr:3: the annotation is not followed by a global",
+                "src/tags.nr:6: B: a tag is a `Field` or a `[u8; N]`, not a `u32`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generated_tags() {
+        let source = include_str!("../../example/src/tags.nr");
+        let report = verify(source);
+        assert_eq!(report.checked, 4);
+        assert!(report.findings.is_empty(), "{:?}", report.findings);
+
+        let stale = source.replace(
+            "0x0ea2aa7e178caa74de1f91e83ad43a81",
+            "0x0ea2aa7e178caa74de1f91e83ad43a82",
+        );
+        let report = verify(&stale);
+        assert_eq!(report.findings.len(), 1);
+        assert!(report.findings[0].starts_with("src/tags.nr:5: TAG_SAFE_HASHING: stale tag"));
+    }
+
+    #[test]
+    fn test_tag_lengths() {
+        use safe_api::codegen::{noir_module, TagConstant};
+        use safe_api::IoPattern;
+
+        let pattern = IoPattern::new().absorb(3).squeeze(1);
+        let domain_separator = parse_domain("0x41424344").unwrap();
+        let constants: Vec<TagConstant> = [TagLength::Bits64, TagLength::Full]
+            .into_iter()
+            .enumerate()
+            .map(|(index, length)| {
+                TagConstant::new(&format!("tag_{}", index), pattern.clone(), domain_separator)
+                    .unwrap()
+                    .with_tag_length(length)
+            })
+            .collect();
+        let source = noir_module(&constants).unwrap();
+        assert!(source.contains("pub global TAG_TAG_1: [u8; 32] = [0x"));
+        let report = verify(&source);
+        assert_eq!(report.checked, 2);
+        assert!(report.findings.is_empty(), "{:?}", report.findings);
+
+        // A 64-bit tag checked at 128 bits, or its digest altered, is stale.
+        let digest = pattern.tag_256(domain_separator);
+        let short = display::format_tag(constants[0].tag());
+        let report = verify(&format!(
+            "// safe-api: pattern = \"A3 S1\", domain = \"0x41424344\", tag_length = \"128\"\n\
+             global SHORT: Field = {};\n\
+             // safe-api: pattern = \"A3 S1\", domain = \"0x41424344\", tag_length = \"64\"\n\
+             global TRIMMED: Field = {};\n\
+             // safe-api: pattern = \"A3 S1\", domain = \"0x41424344\"\n\
+             global FULL: [u8; 32] = [{}, 0];\n",
+            short,
+            short.replacen("0x0", "0x", 1),
+            digest[..31]
+                .iter()
+                .map(u8::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+        assert_eq!(report.checked, 3);
+        assert_eq!(report.findings.len(), 2, "{:?}", report.findings);
+        assert!(report.findings[0].starts_with("src/tags.nr:2: SHORT: stale tag"));
+        assert!(report.findings[1].starts_with("src/tags.nr:6: FULL: stale tag"));
+    }
+}
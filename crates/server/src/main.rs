@@ -0,0 +1,508 @@
+//! `safe-api-server`: tag computation and Poseidon2 sponge sessions over HTTP, so services in
+//! any language share one implementation instead of re-implementing the tag aggregation.
+//!
+//! | Endpoint                    | Body                                             | Response                    |
+//! |-----------------------------|--------------------------------------------------|-----------------------------|
+//! | `POST /tag`                 | `{"io_pattern": [..], "domain_separator": ".."}` | `{"tag": ".."}`             |
+//! | `POST /sponge`              | same as `/tag`                                   | `{"id": "..", "tag": ".."}` |
+//! | `POST /sponge/{id}/absorb`  | `{"elements": [..]}`                             | `{}`                        |
+//! | `POST /sponge/{id}/squeeze` | none                                             | `{"elements": [..]}`        |
+//! | `POST /sponge/{id}/finish`  | none                                             | `{}`, closes the session    |
+//!
+//! IO patterns are the encoded 32-bit words of spec 2.3, domain separators hex strings of at
+//! most 64 bytes (zero padded, and not all zeros for `/sponge`), tags 0x-prefixed 32-digit hex
+//! and field elements 0x-prefixed big-endian hex. The IO pattern of a session operates on at
+//! most [`MAX_OPERATION_LEN`] elements. Errors are `{"error": ".."}` with status 400 (invalid
+//! input, or an operation that does not match the IO pattern), 404 (unknown session) or 503
+//! (too many sessions).
+//!
+//! Each request runs in a `safe.request` span recording its trace id, the trace id of a W3C
+//! `traceparent` header or else an `x-request-id` header, and the session and tag it operates
+//! on, so a failure downstream can be matched with the sponge operations behind it. The
+//! spans and events of the sponge (see `safe_api::instrument`) nest in it, and both headers
+//! are echoed on the response. `RUST_LOG` filters the traces written to the standard error
+//! (`info` by default, e.g. `RUST_LOG=safe_api=trace` for every sponge operation).
+//!
+//! `/tag` answers repeated patterns from a [`TagCache`] of `--tag-cache` entries (1024 by
+//! default, 0 to disable it).
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use ark_bn254::Fr;
+use axum::extract::{Path, Request, State};
+use axum::http::{HeaderMap, HeaderName, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use clap::Parser;
+use safe_api::domain::{is_empty_domain, DomainSeparator};
+use safe_api::poseidon2::Poseidon2;
+use safe_api::{display, trace_context, IoPattern, Sponge, TagCache};
+use serde::{Deserialize, Serialize};
+use tracing::{Instrument, Span};
+use tracing_subscriber::EnvFilter;
+
+/// Open sessions beyond which `POST /sponge` is refused.
+const MAX_SESSIONS: usize = 10_000;
+
+/// Elements beyond which `POST /sponge` refuses an IO pattern, counted per operation and over
+/// the whole pattern: a SQUEEZE allocates its elements while the sessions are locked.
+const MAX_OPERATION_LEN: u64 = 1 << 16;
+
+/// Headers carrying the trace context of a request, echoed on its response.
+const TRACE_HEADERS: [HeaderName; 2] = [
+    HeaderName::from_static(trace_context::TRACEPARENT),
+    HeaderName::from_static(trace_context::REQUEST_ID),
+];
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "HTTP service computing SAFE tags and running sponge sessions"
+)]
+struct Cli {
+    /// Address to listen on.
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+    /// Number of tags `/tag` keeps cached.
+    #[arg(long, default_value_t = safe_api::tag_cache::DEFAULT_CAPACITY)]
+    tag_cache: usize,
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+    let listener = match tokio::net::TcpListener::bind(cli.listen).await {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("error: cannot listen on {}: {}", cli.listen, error);
+            std::process::exit(1);
+        }
+    };
+    println!("Listening on {}", cli.listen);
+    if let Err(error) = axum::serve(listener, app(cli.tag_cache)).await {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    }
+}
+
+fn app(tag_cache: usize) -> Router {
+    Router::new()
+        .route("/tag", post(tag))
+        .route("/sponge", post(start))
+        .route("/sponge/{id}/absorb", post(absorb))
+        .route("/sponge/{id}/squeeze", post(squeeze))
+        .route("/sponge/{id}/finish", post(finish))
+        .layer(middleware::from_fn(trace))
+        .with_state(AppState {
+            sessions: Sessions::default(),
+            tags: Arc::new(TagCache::new(tag_cache)),
+        })
+}
+
+#[derive(Clone)]
+struct AppState {
+    sessions: Sessions,
+    tags: Arc<TagCache>,
+}
+
+type Sessions = Arc<Mutex<HashMap<String, Session>>>;
+
+struct Session {
+    sponge: Sponge<Poseidon2>,
+    position: usize,
+}
+
+struct Error(StatusCode, String);
+
+impl Error {
+    fn invalid(message: impl Into<String>) -> Self {
+        Error(StatusCode::BAD_REQUEST, message.into())
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        tracing::warn!(status = self.0.as_u16(), error = %self.1, "request failed");
+        (self.0, Json(ErrorBody { error: self.1 })).into_response()
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+#[derive(Deserialize)]
+struct PatternRequest {
+    io_pattern: Vec<u32>,
+    domain_separator: String,
+}
+
+#[derive(Serialize)]
+struct TagResponse {
+    tag: String,
+}
+
+#[derive(Serialize)]
+struct SessionResponse {
+    id: String,
+    tag: String,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Elements {
+    elements: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Empty {}
+
+/// Runs the request in its `safe.request` span and echoes its trace headers.
+async fn trace(request: Request, next: Next) -> Response {
+    let span = tracing::info_span!(
+        "safe.request",
+        method = %request.method(),
+        path = request.uri().path(),
+        trace_id = trace_id(request.headers()),
+        session = tracing::field::Empty,
+        tag = tracing::field::Empty,
+    );
+    let echoed: Vec<_> = TRACE_HEADERS
+        .iter()
+        .filter_map(|name| Some((name.clone(), request.headers().get(name)?.clone())))
+        .collect();
+    let mut response = next.run(request).instrument(span).await;
+    response.headers_mut().extend(echoed);
+    response
+}
+
+/// Returns the trace id of a request, see [`trace_context::trace_id`].
+fn trace_id(headers: &HeaderMap) -> Option<&str> {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    trace_context::trace_id(
+        header(trace_context::TRACEPARENT),
+        header(trace_context::REQUEST_ID),
+    )
+}
+
+async fn tag(
+    State(state): State<AppState>,
+    Json(request): Json<PatternRequest>,
+) -> Result<Json<TagResponse>, Error> {
+    let domain_separator = parse_domain(&request.domain_separator)?;
+    let tag = display::format_tag(
+        state
+            .tags
+            .compute_tag(&request.io_pattern, domain_separator),
+    );
+    Span::current().record("tag", tag.as_str());
+    Ok(Json(TagResponse { tag }))
+}
+
+async fn start(
+    State(AppState { sessions, .. }): State<AppState>,
+    Json(request): Json<PatternRequest>,
+) -> Result<Json<SessionResponse>, Error> {
+    let domain_separator = parse_domain(&request.domain_separator)?;
+    if is_empty_domain(&domain_separator) {
+        return Err(Error::invalid("the domain separator is empty"));
+    }
+    let io_pattern = IoPattern::from_words(&request.io_pattern);
+    let total: u64 = io_pattern
+        .operations()
+        .iter()
+        .map(|operation| u64::from(operation.len()))
+        .sum();
+    if total > MAX_OPERATION_LEN {
+        return Err(Error::invalid(format!(
+            "the IO pattern operates on {} elements, more than {}",
+            total, MAX_OPERATION_LEN
+        )));
+    }
+    let sponge = Sponge::start(io_pattern, domain_separator);
+    let tag = display::format_element(&sponge.tag());
+
+    let mut sessions = sessions.lock().unwrap();
+    if sessions.len() >= MAX_SESSIONS {
+        return Err(Error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "too many open sessions".to_string(),
+        ));
+    }
+    let id = format!("{:032x}", rand::random::<u128>());
+    Span::current()
+        .record("session", id.as_str())
+        .record("tag", tag.as_str());
+    sessions.insert(
+        id.clone(),
+        Session {
+            sponge,
+            position: 0,
+        },
+    );
+    Ok(Json(SessionResponse { id, tag }))
+}
+
+async fn absorb(
+    State(AppState { sessions, .. }): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<Elements>,
+) -> Result<Json<Empty>, Error> {
+    let elements = request
+        .elements
+        .iter()
+        .map(|element| parse_element(element))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut sessions = sessions.lock().unwrap();
+    let session = find(&mut sessions, &id)?;
+    session.check_next(true, elements.len())?;
+    session.sponge.absorb(&elements);
+    session.position += 1;
+    Ok(Json(Empty {}))
+}
+
+async fn squeeze(
+    State(AppState { sessions, .. }): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Elements>, Error> {
+    let mut sessions = sessions.lock().unwrap();
+    let session = find(&mut sessions, &id)?;
+    let length = session
+        .sponge
+        .io_pattern()
+        .operations()
+        .get(session.position)
+        .map_or(0, |operation| operation.len() as usize);
+    session.check_next(false, length)?;
+    session.position += 1;
+    Ok(Json(Elements {
+        elements: session
+            .sponge
+            .squeeze()
+            .iter()
+            .map(display::format_element)
+            .collect(),
+    }))
+}
+
+async fn finish(
+    State(AppState { sessions, .. }): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Empty>, Error> {
+    let mut sessions = sessions.lock().unwrap();
+    let session = find(&mut sessions, &id)?;
+    if session.position != session.sponge.io_pattern().len() {
+        return Err(Error::invalid("IO pattern not completed"));
+    }
+    session.sponge.finish();
+    sessions.remove(&id);
+    Ok(Json(Empty {}))
+}
+
+fn find<'a>(
+    sessions: &'a mut HashMap<String, Session>,
+    id: &str,
+) -> Result<&'a mut Session, Error> {
+    Span::current().record("session", id);
+    let session = sessions
+        .get_mut(id)
+        .ok_or_else(|| Error(StatusCode::NOT_FOUND, format!("unknown session `{}`", id)))?;
+    Span::current().record(
+        "tag",
+        display::format_element(&session.sponge.tag()).as_str(),
+    );
+    Ok(session)
+}
+
+impl Session {
+    /// Checks that the next operation is an ABSORB (or a SQUEEZE) of `length` elements.
+    fn check_next(&self, absorb: bool, length: usize) -> Result<(), Error> {
+        let operation = self
+            .sponge
+            .io_pattern()
+            .operations()
+            .get(self.position)
+            .ok_or_else(|| Error::invalid("IO pattern already completed"))?;
+        match (operation.is_absorb(), absorb) {
+            (false, true) => Err(Error::invalid("Expected SQUEEZE operation")),
+            (true, false) => Err(Error::invalid("Expected ABSORB operation")),
+            _ if operation.len() as usize != length => Err(Error::invalid(format!(
+                "Length mismatch: expected {} elements, got {}",
+                operation.len(),
+                length
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn parse_domain(input: &str) -> Result<DomainSeparator, Error> {
+    DomainSeparator::from_hex(input).map_err(|error| Error::invalid(error.to_string()))
+}
+
+/// Parses a 0x-prefixed big-endian hex field element, rejecting non-canonical values.
+fn parse_element(input: &str) -> Result<Fr, Error> {
+    display::parse_element_hex(input)
+        .map_err(|_| Error::invalid(format!("invalid field element `{}`", input)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    async fn call(app: &Router, uri: &str, body: Value) -> (StatusCode, Value) {
+        let request = Request::post(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_tag() {
+        let app = app(16);
+        let (status, body) = call(
+            &app,
+            "/tag",
+            json!({"io_pattern": [0x80000003u32, 1], "domain_separator": "0x41424344"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["tag"], "0x0ea2aa7e178caa74de1f91e83ad43a81");
+
+        let (status, body) = call(
+            &app,
+            "/tag",
+            json!({"io_pattern": [1], "domain_separator": "0x123"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "expected an even number of hex digits");
+    }
+
+    #[tokio::test]
+    async fn test_sponge_session() {
+        let app = app(16);
+        let (status, body) = call(
+            &app,
+            "/sponge",
+            json!({"io_pattern": [0x80000002u32, 1], "domain_separator": "0x41424344"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let id = body["id"].as_str().unwrap().to_string();
+
+        let (status, body) = call(&app, &format!("/sponge/{}/squeeze", id), json!(null)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "Expected ABSORB operation");
+
+        let absorb = format!("/sponge/{}/absorb", id);
+        let (status, _) = call(&app, &absorb, json!({"elements": ["0x01"]})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = call(&app, &absorb, json!({"elements": ["0x01", "0x02"]})).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, body) = call(&app, &format!("/sponge/{}/squeeze", id), json!(null)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = call(&app, &format!("/sponge/{}/finish", id), json!(null)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = call(&app, &format!("/sponge/{}/finish", id), json!(null)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let mut domain_separator = [0u8; 64];
+        domain_separator[..4].copy_from_slice(b"ABCD");
+        let mut expected =
+            Sponge::<Poseidon2>::start(IoPattern::new().absorb(2).squeeze(1), domain_separator);
+        expected.absorb(&[Fr::from(1u64), Fr::from(2u64)]);
+        assert_eq!(
+            body["elements"],
+            json!([display::format_element(&expected.squeeze()[0])])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trace_headers() {
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let request = Request::post("/tag")
+            .header("content-type", "application/json")
+            .header("traceparent", traceparent)
+            .header("x-request-id", "request-7")
+            .body(Body::from(
+                json!({"io_pattern": [1], "domain_separator": "0x41"}).to_string(),
+            ))
+            .unwrap();
+        let response = app(16).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["traceparent"], traceparent);
+        assert_eq!(response.headers()["x-request-id"], "request-7");
+
+        let headers = |pairs: &[(&'static str, &'static str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(*name, value.parse().unwrap());
+            }
+            headers
+        };
+        let both = headers(&[("traceparent", traceparent), ("x-request-id", "request-7")]);
+        assert_eq!(trace_id(&both), Some("4bf92f3577b34da6a3ce929d0e0e4736"));
+        let invalid = headers(&[
+            (
+                "traceparent",
+                "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            ),
+            ("x-request-id", "request-7"),
+        ]);
+        assert_eq!(trace_id(&invalid), Some("request-7"));
+        assert_eq!(trace_id(&HeaderMap::new()), None);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_pattern() {
+        let app = app(16);
+        let (status, body) = call(
+            &app,
+            "/sponge",
+            json!({"io_pattern": [0x80000001u32, 0x7FFFFFFF], "domain_separator": "0x41424344"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body["error"],
+            "the IO pattern operates on 2147483648 elements, more than 65536"
+        );
+
+        // Operations within the limit that exceed it together are refused too.
+        let (status, _) = call(
+            &app,
+            "/sponge",
+            json!({"io_pattern": [0x80008000u32, 0x8001], "domain_separator": "0x41424344"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = call(
+            &app,
+            "/sponge",
+            json!({"io_pattern": [0x80008000u32, 0x8000], "domain_separator": "0x41424344"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
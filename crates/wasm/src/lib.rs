@@ -0,0 +1,303 @@
+//! WebAssembly bindings of `safe-api`, so frontends compute the tags and sponge outputs the Noir
+//! circuit expects instead of re-implementing them in TypeScript.
+//!
+//! Build with `wasm-pack build crates/wasm --target web` (or `--target nodejs`):
+//!
+//! ```js
+//! import { computeTag, computeTagVerbose, IoPattern, Sponge } from "safe-api-wasm";
+//!
+//! computeTag([0x80000003, 0x00000001], "0x41424344"); // "0x0ea2aa7e178caa74de1f91e83ad43a81"
+//! computeTagVerbose([0x80000001, 0x80000002], "0x41424344").aggregatedWords; // [0x80000003]
+//!
+//! const pattern = new IoPattern();
+//! pattern.absorb(3);
+//! pattern.squeeze(1);
+//! const sponge = new Sponge(pattern, "0x41424344");
+//! sponge.absorb(["0x01", "0x02", "0x03"]);
+//! const [digest] = sponge.squeeze();
+//! sponge.finish();
+//! ```
+//!
+//! Domain separators are hex strings of at most 64 bytes (zero padded), tags are 0x-prefixed
+//! 32-digit hex strings and field elements are 0x-prefixed big-endian hex strings. Invalid
+//! input, including an operation that does not match the IO pattern, throws instead of
+//! aborting the module.
+
+use ark_bn254::Fr;
+use safe_api::display::{self, format_bytes, format_element, format_tag};
+use safe_api::domain::{is_empty_domain, DomainSeparator};
+use safe_api::poseidon2::Poseidon2;
+use wasm_bindgen::prelude::*;
+
+/// Computes the tag of an encoded IO pattern under a hex domain separator.
+#[wasm_bindgen(js_name = computeTag)]
+pub fn compute_tag(io_pattern: &[u32], domain_separator: &str) -> Result<String, JsError> {
+    let domain_separator = parse_domain(domain_separator).map_err(|error| JsError::new(&error))?;
+    Ok(format_tag(safe_api::compute_tag(
+        io_pattern,
+        domain_separator,
+    )))
+}
+
+/// Computes the tag of an encoded IO pattern under a hex domain separator, with every
+/// intermediate value.
+#[wasm_bindgen(js_name = computeTagVerbose)]
+pub fn compute_tag_verbose(
+    io_pattern: &[u32],
+    domain_separator: &str,
+) -> Result<TagBreakdown, JsError> {
+    let domain_separator = parse_domain(domain_separator).map_err(|error| JsError::new(&error))?;
+    Ok(TagBreakdown(safe_api::compute_tag_verbose(
+        io_pattern,
+        domain_separator,
+    )))
+}
+
+/// The intermediate values of a tag computation, for visualizations.
+#[wasm_bindgen]
+pub struct TagBreakdown(safe_api::TagBreakdown);
+
+#[wasm_bindgen]
+impl TagBreakdown {
+    /// The encoded words of the IO pattern.
+    #[wasm_bindgen(getter)]
+    pub fn words(&self) -> Vec<u32> {
+        self.0.words.clone()
+    }
+
+    /// The words after aggregation of consecutive operations.
+    #[wasm_bindgen(getter, js_name = aggregatedWords)]
+    pub fn aggregated_words(&self) -> Vec<u32> {
+        self.0.aggregated_words.clone()
+    }
+
+    /// The hashed bytes, as hex.
+    #[wasm_bindgen(getter)]
+    pub fn preimage(&self) -> String {
+        format_bytes(&self.0.preimage)
+    }
+
+    /// The full SHA-256 digest, as hex.
+    #[wasm_bindgen(getter)]
+    pub fn digest(&self) -> String {
+        format_bytes(&self.0.digest)
+    }
+
+    /// The truncated tag.
+    #[wasm_bindgen(getter)]
+    pub fn tag(&self) -> String {
+        format_tag(self.0.tag)
+    }
+}
+
+/// Builder for IO patterns.
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct IoPattern(safe_api::IoPattern);
+
+#[wasm_bindgen]
+impl IoPattern {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends ABSORB(`length`).
+    pub fn absorb(&mut self, length: u32) {
+        self.0 = self.0.clone().absorb(length);
+    }
+
+    /// Appends SQUEEZE(`length`).
+    pub fn squeeze(&mut self, length: u32) {
+        self.0 = self.0.clone().squeeze(length);
+    }
+
+    /// Returns the 32-bit words of spec 2.3.
+    #[wasm_bindgen(js_name = encodedWords)]
+    pub fn encoded_words(&self) -> Vec<u32> {
+        self.0.encoded_words()
+    }
+
+    /// Computes the tag of this pattern under a hex domain separator.
+    pub fn tag(&self, domain_separator: &str) -> Result<String, JsError> {
+        let domain_separator =
+            parse_domain(domain_separator).map_err(|error| JsError::new(&error))?;
+        Ok(format_tag(self.0.tag(domain_separator)))
+    }
+}
+
+/// The SAFE sponge over BN254 with Poseidon2, as in the Noir `SafeSponge`.
+#[wasm_bindgen]
+pub struct Sponge {
+    sponge: safe_api::Sponge<Poseidon2>,
+    position: usize,
+}
+
+#[wasm_bindgen]
+impl Sponge {
+    /// Starts a sponge for `io_pattern` under a hex domain separator, which must not be all
+    /// zeros.
+    #[wasm_bindgen(constructor)]
+    pub fn new(io_pattern: &IoPattern, domain_separator: &str) -> Result<Sponge, JsError> {
+        let domain_separator =
+            parse_domain(domain_separator).map_err(|error| JsError::new(&error))?;
+        if is_empty_domain(&domain_separator) {
+            return Err(JsError::new("the domain separator is empty"));
+        }
+        Ok(Sponge {
+            sponge: safe_api::Sponge::start(io_pattern.0.clone(), domain_separator),
+            position: 0,
+        })
+    }
+
+    /// Returns the tag of this instance.
+    pub fn tag(&self) -> String {
+        format_element(&self.sponge.tag())
+    }
+
+    /// Returns the number of operations of the IO pattern already performed, the position of
+    /// the next one.
+    #[wasm_bindgen(js_name = operationsDone)]
+    pub fn operations_done(&self) -> usize {
+        self.position
+    }
+
+    /// Absorbs hex field elements.
+    pub fn absorb(&mut self, input: Vec<String>) -> Result<(), JsError> {
+        self.try_absorb(&input)
+            .map_err(|error| JsError::new(&error))
+    }
+
+    /// Squeezes the elements of the next operation, as hex strings.
+    pub fn squeeze(&mut self) -> Result<Vec<String>, JsError> {
+        self.try_squeeze().map_err(|error| JsError::new(&error))
+    }
+
+    /// Checks that the whole IO pattern has run and erases the state.
+    pub fn finish(&mut self) -> Result<(), JsError> {
+        self.try_finish().map_err(|error| JsError::new(&error))
+    }
+}
+
+impl Sponge {
+    fn try_absorb(&mut self, input: &[String]) -> Result<(), String> {
+        let expected = self.next_operation()?;
+        if !expected.is_absorb() {
+            return Err("Expected SQUEEZE operation".to_string());
+        }
+        if expected.len() as usize != input.len() {
+            return Err(format!(
+                "Length mismatch: expected {} elements, got {}",
+                expected.len(),
+                input.len()
+            ));
+        }
+        let elements = input
+            .iter()
+            .map(|element| parse_element(element))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.sponge.absorb(&elements);
+        self.position += 1;
+        Ok(())
+    }
+
+    fn try_squeeze(&mut self) -> Result<Vec<String>, String> {
+        if self.next_operation()?.is_absorb() {
+            return Err("Expected ABSORB operation".to_string());
+        }
+        self.position += 1;
+        Ok(self.sponge.squeeze().iter().map(format_element).collect())
+    }
+
+    fn try_finish(&mut self) -> Result<(), String> {
+        if self.position != self.sponge.io_pattern().len() {
+            return Err("IO pattern not completed".to_string());
+        }
+        self.sponge.finish();
+        Ok(())
+    }
+
+    fn next_operation(&self) -> Result<safe_api::Operation, String> {
+        self.sponge
+            .io_pattern()
+            .operations()
+            .get(self.position)
+            .copied()
+            .ok_or_else(|| "IO pattern already completed".to_string())
+    }
+}
+
+fn parse_domain(input: &str) -> Result<DomainSeparator, String> {
+    DomainSeparator::from_hex(input).map_err(|error| error.to_string())
+}
+
+/// Parses a 0x-prefixed big-endian hex field element, rejecting non-canonical values.
+fn parse_element(input: &str) -> Result<Fr, String> {
+    display::parse_element_hex(input).map_err(|_| format!("invalid field element `{}`", input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tags_match_safe_api() {
+        let mut pattern = IoPattern::new();
+        pattern.absorb(3);
+        pattern.squeeze(1);
+        assert_eq!(pattern.encoded_words(), vec![0x80000003, 0x00000001]);
+        assert_eq!(
+            format_tag(pattern.0.tag(parse_domain("0x41424344").unwrap())),
+            "0x0ea2aa7e178caa74de1f91e83ad43a81"
+        );
+
+        let breakdown = TagBreakdown(safe_api::compute_tag_verbose(
+            &[0x80000001, 0x80000002, 0x00000001],
+            parse_domain("0x41424344").unwrap(),
+        ));
+        assert_eq!(breakdown.aggregated_words(), vec![0x80000003, 0x00000001]);
+        assert_eq!(breakdown.tag(), "0x0ea2aa7e178caa74de1f91e83ad43a81");
+        assert!(breakdown.digest().starts_with(&breakdown.tag()));
+    }
+
+    #[test]
+    fn test_sponge_checks_pattern() {
+        let mut pattern = IoPattern::new();
+        pattern.absorb(2);
+        pattern.squeeze(1);
+        let domain_separator = parse_domain("0x41424344").unwrap();
+        let mut sponge = Sponge {
+            sponge: safe_api::Sponge::start(pattern.0.clone(), domain_separator),
+            position: 0,
+        };
+
+        assert!(sponge.try_squeeze().is_err());
+        assert!(sponge.try_absorb(&["0x01".to_string()]).is_err());
+        assert!(sponge.try_finish().is_err());
+        assert_eq!(sponge.operations_done(), 0);
+        sponge
+            .try_absorb(&["0x01".to_string(), "0x02".to_string()])
+            .unwrap();
+        assert_eq!(sponge.operations_done(), 1);
+        let output = sponge.try_squeeze().unwrap();
+        sponge.try_finish().unwrap();
+
+        let mut expected = safe_api::Sponge::<Poseidon2>::start(pattern.0, domain_separator);
+        expected.absorb(&[Fr::from(1u64), Fr::from(2u64)]);
+        assert_eq!(output, vec![format_element(&expected.squeeze()[0])]);
+    }
+
+    #[test]
+    fn test_rejects_invalid_input() {
+        assert!(parse_domain("0x123").is_err());
+        assert!(parse_domain(&"00".repeat(65)).is_err());
+        assert!(parse_element("1").is_err());
+        assert!(parse_element("0x").is_err());
+        // The BN254 scalar field modulus.
+        assert!(parse_element(
+            "0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001"
+        )
+        .is_err());
+        assert_eq!(parse_element("0x2a").unwrap(), Fr::from(42u64));
+    }
+}
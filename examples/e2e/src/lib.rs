@@ -0,0 +1,310 @@
+//! End-to-end pipeline of `safe-api`, from the manifest of an application to the
+//! verification of a proof, as living documentation and as a regression test of the whole
+//! toolchain:
+//!
+//! 1. [`load_manifest`] reads the protocols of `safe.toml`;
+//! 2. [`write_circuit`] generates their Noir constants with [`codegen`], next to the circuit
+//!    of `circuit/src/main.nr` and the `Prover.toml` of a note;
+//! 3. with the `toolchain` feature, [`toolchain`] compiles the circuit with `nargo`, executes
+//!    it into a witness, and proves and verifies it with `bb`;
+//! 4. [`submit`] wraps the public output and the proof with the armored envelope of the
+//!    protocol, the tag preimage a relayer forwards;
+//! 5. a [`Router`] recomputes the tag of the envelope and runs the verifier registered for
+//!    that protocol.
+//!
+//! `tests/pipeline.rs` runs every step; without the `toolchain` feature, the proof is
+//! replaced by the opening of the note, which the verifier checks by recomputing the
+//! commitment in Rust.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use ark_bn254::Fr;
+use safe_api::armor::{armor_envelope, dearmor_envelope};
+use safe_api::codegen::{self, TagConstant};
+use safe_api::domain::DomainSeparator;
+use safe_api::poseidon2::Poseidon2;
+use safe_api::tag::Tag;
+use safe_api::{display, tag_from_preimage, IoPattern, Sponge};
+use serde::Deserialize;
+
+/// The circuit proving the `note_commitment` protocol.
+const CIRCUIT: &str = include_str!("../circuit/src/main.nr");
+
+/// Protocol proved by [`CIRCUIT`].
+pub const NOTE_COMMITMENT: &str = "note_commitment";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    protocol: Vec<Protocol>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Protocol {
+    name: String,
+    pattern: String,
+    domain: String,
+}
+
+/// Parses the protocols of a manifest. Only the `name`, `pattern` and `domain` keys of the
+/// manifests of `safe-api codegen` are supported.
+pub fn load_manifest(contents: &str) -> Result<Vec<TagConstant>, String> {
+    let manifest: Manifest =
+        toml::from_str(contents).map_err(|error| format!("invalid manifest: {}", error))?;
+    manifest
+        .protocol
+        .into_iter()
+        .map(|protocol| {
+            let io_pattern: IoPattern = protocol
+                .pattern
+                .parse()
+                .map_err(|error| format!("{}: {}", protocol.name, error))?;
+            let domain_separator = DomainSeparator::from_hex(&protocol.domain)
+                .map_err(|error| format!("{}: {}", protocol.name, error))?;
+            TagConstant::new(&protocol.name, io_pattern, *domain_separator)
+                .map_err(|error| format!("{}: {}", protocol.name, error))
+        })
+        .collect()
+}
+
+/// Returns the protocol `name` of `constants`.
+pub fn protocol<'a>(constants: &'a [TagConstant], name: &str) -> Result<&'a TagConstant, String> {
+    constants
+        .iter()
+        .find(|constant| constant.name() == name)
+        .ok_or_else(|| format!("the manifest has no protocol `{}`", name))
+}
+
+/// Returns the commitment to `note` of the protocol `constant`, as the circuit computes it.
+pub fn commitment(constant: &TagConstant, note: &[Fr; 3]) -> Fr {
+    let mut sponge =
+        Sponge::<Poseidon2>::start(constant.io_pattern().clone(), constant.domain_separator());
+    sponge.absorb(note);
+    let commitment = sponge.squeeze()[0];
+    sponge.finish();
+    commitment
+}
+
+/// Writes the Noir project of the circuit to `program_dir`: its `Nargo.toml` depending on the
+/// `safe` library at `safe_dir`, the circuit, the constants generated from `constants`, and
+/// the `Prover.toml` of `note` under the domain separator of `note_commitment`.
+pub fn write_circuit(
+    program_dir: &Path,
+    safe_dir: &Path,
+    constants: &[TagConstant],
+    note: &[Fr; 3],
+) -> Result<(), String> {
+    let write = |path: &Path, contents: &str| {
+        std::fs::write(path, contents)
+            .map_err(|error| format!("cannot write {}: {}", path.display(), error))
+    };
+    let source_dir = program_dir.join("src");
+    std::fs::create_dir_all(&source_dir)
+        .map_err(|error| format!("cannot create {}: {}", source_dir.display(), error))?;
+
+    write(
+        &program_dir.join("Nargo.toml"),
+        &format!(
+            "[package]\nname = \"safe_e2e\"\ntype = \"bin\"\nauthors = [\"\"]\n\n\
+             [dependencies]\nsafe = {{ path = \"{}\" }}\n",
+            safe_dir.display()
+        ),
+    )?;
+    write(&source_dir.join("main.nr"), CIRCUIT)?;
+    let tags = codegen::noir_module(constants).map_err(|error| error.to_string())?;
+    write(&source_dir.join("tags.nr"), &tags)?;
+
+    let constant = protocol(constants, NOTE_COMMITMENT)?;
+    let note: Vec<String> = note
+        .iter()
+        .map(|element| format!("\"{}\"", display::format_element(element)))
+        .collect();
+    let domain_separator: Vec<String> = constant
+        .domain_separator()
+        .iter()
+        .map(u8::to_string)
+        .collect();
+    write(
+        &program_dir.join("Prover.toml"),
+        &format!(
+            "note = [{}]\ndomain_separator = [{}]\n",
+            note.join(", "),
+            domain_separator.join(", ")
+        ),
+    )
+}
+
+/// A proof on its way to the verifier: the envelope of its protocol, its public output and
+/// the proof itself.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Submission {
+    /// The armored tag preimage of the protocol, see [`safe_api::armor`].
+    pub envelope: String,
+    /// The public output of the circuit.
+    pub output: Fr,
+    /// The proof, in the format of the verifier of the protocol.
+    pub proof: Vec<u8>,
+}
+
+/// Wraps the output and the proof of a session of the protocol `constant`.
+pub fn submit(constant: &TagConstant, output: Fr, proof: Vec<u8>) -> Submission {
+    let preimage = constant
+        .io_pattern()
+        .tag_preimage(constant.domain_separator());
+    Submission {
+        envelope: armor_envelope(&preimage),
+        output,
+        proof,
+    }
+}
+
+/// Checks the submissions of one protocol.
+pub type Verifier = Box<dyn Fn(&TagConstant, &Submission) -> Result<(), String>>;
+
+/// The verifiers of the protocols, by tag.
+#[derive(Default)]
+pub struct Router {
+    routes: HashMap<Tag, (TagConstant, Verifier)>,
+}
+
+impl Router {
+    /// Returns a router without protocols.
+    pub fn new() -> Self {
+        Router::default()
+    }
+
+    /// Routes the submissions of the protocol `constant` to `verifier`.
+    pub fn register(&mut self, constant: TagConstant, verifier: Verifier) -> Result<(), String> {
+        let tag = constant.tag();
+        if let Some((registered, _)) = self.routes.get(&tag) {
+            return Err(format!(
+                "`{}` has the tag of `{}`",
+                constant.name(),
+                registered.name()
+            ));
+        }
+        self.routes.insert(tag, (constant, verifier));
+        Ok(())
+    }
+
+    /// Verifies `submission` with the verifier of the protocol of its envelope, and returns
+    /// the name of the protocol.
+    pub fn route(&self, submission: &Submission) -> Result<&str, String> {
+        let preimage = dearmor_envelope(&submission.envelope)
+            .map_err(|error| format!("invalid envelope: {}", error))?;
+        let tag = tag_from_preimage(&preimage);
+        let (constant, verifier) = self
+            .routes
+            .get(&tag)
+            .ok_or_else(|| format!("no protocol has the tag {}", tag))?;
+        verifier(constant, submission)
+            .map_err(|error| format!("{}: {}", constant.name(), error))?;
+        Ok(constant.name())
+    }
+}
+
+/// The Noir toolchain steps: `nargo` and `bb` must be on the `PATH`, or given by the `NARGO`
+/// and `BB` environment variables.
+#[cfg(feature = "toolchain")]
+pub mod toolchain {
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    use ark_bn254::Fr;
+    use ark_ff::{BigInteger, PrimeField};
+    use safe_api::display;
+
+    fn run(variable: &str, default: &str, arguments: &[&str]) -> Result<String, String> {
+        let program = std::env::var(variable).unwrap_or_else(|_| default.to_string());
+        let output = Command::new(&program)
+            .args(arguments)
+            .output()
+            .map_err(|error| format!("cannot run {}: {}", program, error))?;
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        if !output.status.success() {
+            return Err(format!(
+                "{} {} failed:\n{}{}",
+                program,
+                arguments.join(" "),
+                stdout,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        Ok(stdout)
+    }
+
+    fn path(path: &Path) -> &str {
+        path.to_str().expect("a UTF-8 path")
+    }
+
+    /// Compiles the circuit, then executes it on its `Prover.toml` into the witness
+    /// `target/safe_e2e.gz`, and returns the public output.
+    pub fn execute(program_dir: &Path) -> Result<Fr, String> {
+        run(
+            "NARGO",
+            "nargo",
+            &["compile", "--program-dir", path(program_dir)],
+        )?;
+        let stdout = run(
+            "NARGO",
+            "nargo",
+            &["execute", "--program-dir", path(program_dir)],
+        )?;
+        let value = stdout
+            .lines()
+            .find_map(|line| line.split_once("Circuit output:"))
+            .map(|(_, value)| value.trim())
+            .ok_or_else(|| format!("no circuit output in:\n{}", stdout))?;
+        display::parse_element(value)
+            .map_err(|error| format!("unexpected circuit output `{}`: {}", value, error))
+    }
+
+    /// Proves the executed circuit with `bb`, writing the proof, its public inputs and the
+    /// verification key to `target/proof`, and returns the directory.
+    pub fn prove(program_dir: &Path) -> Result<PathBuf, String> {
+        let target = program_dir.join("target");
+        let proof_dir = target.join("proof");
+        run(
+            "BB",
+            "bb",
+            &[
+                "prove",
+                "-b",
+                path(&target.join("safe_e2e.json")),
+                "-w",
+                path(&target.join("safe_e2e.gz")),
+                "-o",
+                path(&proof_dir),
+                "--write_vk",
+            ],
+        )?;
+        Ok(proof_dir)
+    }
+
+    /// Verifies with `bb` the proof `proof` of a circuit of public output `output`, with the
+    /// verification key written by [`prove`] to `proof_dir`.
+    pub fn verify(proof_dir: &Path, proof: &[u8], output: Fr) -> Result<(), String> {
+        let write = |name: &str, contents: &[u8]| {
+            std::fs::write(proof_dir.join(name), contents)
+                .map_err(|error| format!("cannot write the {}: {}", name, error))
+        };
+        write("proof", proof)?;
+        write("public_inputs", &output.into_bigint().to_bytes_be())?;
+        run(
+            "BB",
+            "bb",
+            &[
+                "verify",
+                "-k",
+                path(&proof_dir.join("vk")),
+                "-p",
+                path(&proof_dir.join("proof")),
+                "-i",
+                path(&proof_dir.join("public_inputs")),
+            ],
+        )
+        .map(drop)
+    }
+}
@@ -0,0 +1,139 @@
+use std::path::{Path, PathBuf};
+
+use ark_bn254::Fr;
+use safe_api::codegen::TagConstant;
+use safe_api::IoPattern;
+use safe_api_e2e::{
+    commitment, load_manifest, protocol, submit, write_circuit, Router, Submission, NOTE_COMMITMENT,
+};
+
+fn manifest() -> Vec<TagConstant> {
+    load_manifest(include_str!("../safe.toml")).unwrap()
+}
+
+fn note() -> [Fr; 3] {
+    [Fr::from(1u64), Fr::from(2u64), Fr::from(3u64)]
+}
+
+/// A fresh directory for the generated Noir project.
+fn program_dir(name: &str) -> PathBuf {
+    let directory =
+        std::env::temp_dir().join(format!("safe-api-e2e-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    directory
+}
+
+fn safe_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../../crates/safe")
+}
+
+/// Routes `note_commitment` to `verifier` and `merkle_node` to a verifier that rejects all.
+fn router(constants: &[TagConstant], verifier: safe_api_e2e::Verifier) -> Router {
+    let mut router = Router::new();
+    router
+        .register(
+            protocol(constants, NOTE_COMMITMENT).unwrap().clone(),
+            verifier,
+        )
+        .unwrap();
+    router
+        .register(
+            protocol(constants, "merkle_node").unwrap().clone(),
+            Box::new(|_, _| Err("not deployed".to_string())),
+        )
+        .unwrap();
+    router
+}
+
+#[test]
+fn test_pipeline_without_toolchain() {
+    let constants = manifest();
+    let directory = program_dir("rust");
+    write_circuit(&directory, &safe_dir(), &constants, &note()).unwrap();
+    let tags = std::fs::read_to_string(directory.join("src/tags.nr")).unwrap();
+    assert!(tags.contains("pub global TAG_NOTE_COMMITMENT: Field = "));
+    assert!(std::fs::read_to_string(directory.join("Prover.toml"))
+        .unwrap()
+        .starts_with("note = [\"0x"));
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    // The proof is the opening of the note, checked against the commitment.
+    let router = router(
+        &constants,
+        Box::new(|constant, submission: &Submission| {
+            let note: Vec<Fr> = submission
+                .proof
+                .chunks(8)
+                .map(|chunk| Fr::from(u64::from_be_bytes(chunk.try_into().unwrap())))
+                .collect();
+            let note: [Fr; 3] = note.try_into().map_err(|_| "not a note".to_string())?;
+            (commitment(constant, &note) == submission.output)
+                .then_some(())
+                .ok_or_else(|| "wrong opening".to_string())
+        }),
+    );
+    let opening: Vec<u8> = [1u64, 2, 3]
+        .iter()
+        .flat_map(|value| value.to_be_bytes())
+        .collect();
+    let note_commitment = protocol(&constants, NOTE_COMMITMENT).unwrap();
+    let output = commitment(note_commitment, &note());
+    let submission = submit(note_commitment, output, opening.clone());
+    assert_eq!(router.route(&submission), Ok(NOTE_COMMITMENT));
+
+    let forged = submit(note_commitment, output + Fr::from(1u64), opening.clone());
+    assert_eq!(
+        router.route(&forged),
+        Err("note_commitment: wrong opening".to_string())
+    );
+    let merkle_node = submit(
+        protocol(&constants, "merkle_node").unwrap(),
+        output,
+        opening,
+    );
+    assert_eq!(
+        router.route(&merkle_node),
+        Err("merkle_node: not deployed".to_string())
+    );
+    let unknown =
+        TagConstant::new("unknown", IoPattern::new().absorb(3).squeeze(1), [1u8; 64]).unwrap();
+    assert!(router
+        .route(&submit(&unknown, output, Vec::new()))
+        .unwrap_err()
+        .starts_with("no protocol has the tag 0x"));
+    // An altered envelope fails its checksum.
+    let mut tampered = submission;
+    tampered.envelope = tampered.envelope.replacen("gAAA", "gBAA", 1);
+    assert!(router
+        .route(&tampered)
+        .unwrap_err()
+        .starts_with("invalid envelope"));
+}
+
+#[cfg(feature = "toolchain")]
+#[test]
+fn test_pipeline() {
+    use safe_api_e2e::toolchain;
+
+    let constants = manifest();
+    let directory = program_dir("toolchain");
+    write_circuit(&directory, &safe_dir(), &constants, &note()).unwrap();
+
+    let note_commitment = protocol(&constants, NOTE_COMMITMENT).unwrap();
+    let output = toolchain::execute(&directory).unwrap();
+    assert_eq!(output, commitment(note_commitment, &note()));
+    let proof_dir = toolchain::prove(&directory).unwrap();
+    let proof = std::fs::read(proof_dir.join("proof")).unwrap();
+
+    let router = router(
+        &constants,
+        Box::new(move |_, submission: &Submission| {
+            toolchain::verify(&proof_dir, &submission.proof, submission.output)
+        }),
+    );
+    assert_eq!(
+        router.route(&submit(note_commitment, output, proof)),
+        Ok(NOTE_COMMITMENT)
+    );
+    std::fs::remove_dir_all(&directory).unwrap();
+}